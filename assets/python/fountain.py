@@ -0,0 +1,35 @@
+# Example script for the python scene (`--scene python --script PATH`).
+# setup(world) runs once, update(world, dt) every frame the simulation advances
+# and on_click(world, x, y) on each left click. All three are optional.
+import colorsys
+import math
+import random
+
+MAX_SHAPES = 200
+
+
+def spawn(world, x, y):
+    angle = random.uniform(-math.pi * 0.75, -math.pi * 0.25)
+    speed = random.uniform(400.0, 700.0)
+    shape = world.spawn_circle(x, y, random.uniform(6.0, 16.0))
+    world.set_velocity(shape, math.cos(angle) * speed, math.sin(angle) * speed)
+    # Oldest shapes go first once the limit is reached
+    if len(world) > MAX_SHAPES:
+        world.remove(world.ids()[0])
+
+
+def setup(world):
+    world.set_gravity(0.0, 600.0)
+    world.set_restitution(0.7)
+
+
+def update(world, dt):
+    spawn(world, world.width / 2, world.height - 20.0)
+    for index, shape in enumerate(world.ids()):
+        hue = (world.time * 0.1 + index / MAX_SHAPES) % 1.0
+        world.set_color(shape, colorsys.hsv_to_rgb(hue, 0.7, 1.0))
+
+
+def on_click(world, x, y):
+    for _ in range(20):
+        spawn(world, x, y)
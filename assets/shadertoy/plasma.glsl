@@ -0,0 +1,14 @@
+// Example for `cargo run -- --shader assets/shadertoy/plasma.glsl`.
+// Any ShaderToy shader that only uses iTime, iResolution, iMouse, iTimeDelta
+// and iFrame (no iChannel textures) can be pasted in as is.
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = (2.0 * fragCoord - iResolution.xy) / iResolution.y;
+    vec2 mouse = iMouse.z > 0.0 ? (2.0 * iMouse.xy - iResolution.xy) / iResolution.y : vec2(0.0);
+    float t = iTime * 0.8;
+    float v = sin(uv.x * 3.0 + t)
+        + sin(uv.y * 4.0 - t * 1.3)
+        + sin(length(uv - mouse) * 6.0 - t * 2.0)
+        + sin((uv.x + uv.y) * 2.5 + t * 0.7);
+    vec3 color = 0.5 + 0.5 * cos(v * 1.5 + vec3(0.0, 2.1, 4.2));
+    fragColor = vec4(color, 1.0);
+}
@@ -24,8 +24,8 @@ fn main() {
                     } else {
                         // Create a macOS app bundle structure in the output directory
                         let bundle_dir = Path::new(&out_dir).join("vulkan_vibe_coding.app/Contents");
-                        fs::create_dir_all(&bundle_dir.join("Resources")).expect("Failed to create bundle dirs");
-                        fs::create_dir_all(&bundle_dir.join("MacOS")).expect("Failed to create MacOS dir");
+                        fs::create_dir_all(bundle_dir.join("Resources")).expect("Failed to create bundle dirs");
+                        fs::create_dir_all(bundle_dir.join("MacOS")).expect("Failed to create MacOS dir");
 
                         // Copy the icon to the Resources folder
                         fs::copy(
@@ -68,8 +68,62 @@ fn main() {
         }
     }
 
+    #[cfg(feature = "ffi")]
+    generate_header();
+
     println!("cargo:rerun-if-changed=assets/icon.ico");
     println!("cargo:rerun-if-changed=assets/icon.icns");
     println!("cargo:rerun-if-changed=shaders/vert.glsl");
     println!("cargo:rerun-if-changed=shaders/frag.glsl");
-}
\ No newline at end of file
+    println!("cargo:rerun-if-changed=shaders/canvas_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/canvas_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/sprite_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/sprite_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/sprite_bindless_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/sprite_bindless_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/sprite_address_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/circle_sdf_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/circle_sdf_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/fullscreen_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_copy_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_blur_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_threshold_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_composite_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/particles_comp.glsl");
+    println!("cargo:rerun-if-changed=shaders/particles_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/particles_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/particles_address_comp.glsl");
+    println!("cargo:rerun-if-changed=shaders/particles_address_vert.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_trail_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_chromatic_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_vignette_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_crt_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/life_comp.glsl");
+    println!("cargo:rerun-if-changed=shaders/life_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/metaballs_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/fractal_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/visualizer_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/background_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/overdraw_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/mesh_normals_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/post_output_frag.glsl");
+    println!("cargo:rerun-if-changed=shaders/circles_mesh.glsl");
+    println!("cargo:rerun-if-changed=shaders/raytrace_rgen.glsl");
+    println!("cargo:rerun-if-changed=shaders/raytrace_rmiss.glsl");
+    println!("cargo:rerun-if-changed=shaders/raytrace_rchit.glsl");
+}
+// Writes the C header for src/ffi.rs, configured by cbindgen.toml
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml"))
+        .expect("Failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(Path::new(&crate_dir).join("src/ffi.rs"))
+        .generate()
+        .expect("Failed to generate the C header")
+        .write_to_file(Path::new(&crate_dir).join("include/vulkan_vibe.h"));
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
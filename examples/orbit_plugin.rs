@@ -0,0 +1,89 @@
+// A scene plugin: rings of circles orbiting the cursor (or the window center).
+// Build it with `cargo build --release --example orbit_plugin` and copy
+// target/release/examples/liborbit_plugin.so (orbit_plugin.dll on Windows,
+// liborbit_plugin.dylib on macOS) into plugins/.
+
+use std::ffi::c_void;
+
+use vulkan_vibe_coding::color::hsv_to_rgba;
+use vulkan_vibe_coding::plugin::{EmitCircle, PluginApi, PluginCircle, PluginFrame, PLUGIN_ABI_VERSION};
+
+const RINGS: usize = 4;
+const PER_RING: usize = 12;
+
+struct Orbits {
+    center: [f32; 2],
+    time: f32,
+    // Seconds to add to `time`; doubles while the mouse is held
+    speed: f32,
+    hue_offset: f32,
+}
+
+unsafe extern "C" fn create(width: f32, height: f32) -> *mut c_void {
+    let orbits = Orbits {
+        center: [width * 0.5, height * 0.5],
+        time: 0.0,
+        speed: 1.0,
+        hue_offset: 0.0,
+    };
+    Box::into_raw(Box::new(orbits)) as *mut c_void
+}
+
+unsafe extern "C" fn destroy(state: *mut c_void) {
+    drop(Box::from_raw(state as *mut Orbits));
+}
+
+unsafe extern "C" fn update(state: *mut c_void, frame: *const PluginFrame) {
+    let orbits = &mut *(state as *mut Orbits);
+    let frame = &*frame;
+    orbits.center = if frame.has_cursor {
+        [frame.cursor_x, frame.cursor_y]
+    } else {
+        [frame.width * 0.5, frame.height * 0.5]
+    };
+    orbits.speed = if frame.mouse_held { 2.0 } else { 1.0 };
+    if frame.mouse_pressed {
+        orbits.hue_offset = (frame.random >> 8) as f32 / (1 << 24) as f32;
+    }
+    orbits.time += frame.steps as f32 * frame.fixed_dt * orbits.speed;
+}
+
+unsafe extern "C" fn record_draws(state: *const c_void, sink: *mut c_void, emit: EmitCircle) {
+    let orbits = &*(state as *const Orbits);
+    for ring in 0..RINGS {
+        let radius = 60.0 + ring as f32 * 45.0;
+        // Inner rings turn faster, alternating direction
+        let direction = if ring % 2 == 0 { 1.0 } else { -1.0 };
+        let angle = orbits.time * direction / (1.0 + ring as f32 * 0.5);
+        for index in 0..PER_RING {
+            let theta = angle + index as f32 * std::f32::consts::TAU / PER_RING as f32;
+            let hue = (orbits.hue_offset + index as f32 / PER_RING as f32).fract();
+            let circle = PluginCircle {
+                x: orbits.center[0] + theta.cos() * radius,
+                y: orbits.center[1] + theta.sin() * radius,
+                radius: 6.0 + ring as f32 * 2.0,
+                color: hsv_to_rgba(hue, 0.7, 1.0, 1.0),
+            };
+            emit(sink, &circle);
+        }
+    }
+}
+
+struct Api(PluginApi);
+
+// The table only holds a static string and function pointers
+unsafe impl Sync for Api {}
+
+static API: Api = Api(PluginApi {
+    abi_version: PLUGIN_ABI_VERSION,
+    name: c"orbits".as_ptr(),
+    create,
+    destroy,
+    update,
+    record_draws,
+});
+
+#[no_mangle]
+pub extern "C" fn vibe_plugin_entry() -> *const PluginApi {
+    &API.0
+}
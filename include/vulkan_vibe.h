@@ -0,0 +1,53 @@
+#ifndef VULKAN_VIBE_H
+#define VULKAN_VIBE_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum VibeWindowKind {
+  VIBE_WINDOW_KIND_WIN32 = 0,
+  VIBE_WINDOW_KIND_XLIB = 1,
+  VIBE_WINDOW_KIND_WAYLAND = 2,
+  VIBE_WINDOW_KIND_APP_KIT = 3,
+  VIBE_WINDOW_KIND_UI_KIT = 4,
+} VibeWindowKind;
+
+typedef struct VibeRenderer VibeRenderer;
+
+typedef struct VibeWindowHandle {
+  uint32_t kind;
+  void *display;
+  void *window;
+  unsigned long xlib_window;
+} VibeWindowHandle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+struct VibeRenderer *vibe_renderer_create(const struct VibeWindowHandle *window,
+                                          uint32_t width,
+                                          uint32_t height,
+                                          const char *scene);
+
+void vibe_renderer_resize(struct VibeRenderer *renderer,
+                          uint32_t width,
+                          uint32_t height,
+                          float scale_factor);
+
+void vibe_renderer_tick(struct VibeRenderer *renderer, float dt);
+
+void vibe_renderer_render(struct VibeRenderer *renderer);
+
+void vibe_renderer_destroy(struct VibeRenderer *renderer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VULKAN_VIBE_H */
@@ -0,0 +1,33 @@
+#version 450
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    // Top and bottom of the gradient, or the plasma's two extremes
+    vec4 first;
+    vec4 second;
+    vec2 resolution;
+    // Seconds of animation
+    float time;
+    // 0 vertical gradient, 1 plasma
+    uint mode;
+} pc;
+
+// Overlapping sine waves in pixel space, so the pattern keeps its scale at
+// any window size
+float plasma(vec2 pixel, float time) {
+    vec2 p = pixel / 120.0;
+    float value = sin(p.x + time);
+    value += sin((p.y + time) * 0.7);
+    value += sin((p.x + p.y + time * 0.6) * 0.6);
+    vec2 center = p - vec2(sin(time * 0.33), cos(time * 0.5)) * 3.0;
+    value += sin(length(center) * 1.3 - time);
+    return value * 0.125 + 0.5;
+}
+
+void main() {
+    float blend = pc.mode == 0u
+        ? fragUv.y
+        : plasma(fragUv * pc.resolution, pc.time);
+    outColor = vec4(mix(pc.first.rgb, pc.second.rgb, blend), 1.0);
+}
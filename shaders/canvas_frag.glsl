@@ -0,0 +1,54 @@
+#version 450
+// Must match MAX_GRADIENTS and MAX_GRADIENT_STOPS in src/gradient.rs
+const uint MAX_GRADIENTS = 32;
+const uint MAX_STOPS = 8;
+
+struct Gradient {
+    // Linear: start xy, end zw. Radial: center xy, radius z.
+    vec4 shape;
+    // x 0 for linear and 1 for radial, y stop count
+    uvec4 info;
+    vec4 offsets[MAX_STOPS / 4];
+    vec4 colors[MAX_STOPS];
+};
+
+layout(set = 0, binding = 0) uniform Gradients {
+    Gradient gradients[MAX_GRADIENTS];
+};
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec2 fragPosition;
+// 0 for a flat color, otherwise one more than the gradient's index
+layout(location = 2) flat in uint fragGradient;
+layout(location = 0) out vec4 outColor;
+
+float stopOffset(Gradient gradient, uint i) {
+    return gradient.offsets[i / 4][i % 4];
+}
+
+void main() {
+    if (fragGradient == 0) {
+        outColor = fragColor;
+        return;
+    }
+    Gradient gradient = gradients[fragGradient - 1];
+    float t;
+    if (gradient.info.x == 0) {
+        vec2 start = gradient.shape.xy;
+        vec2 direction = gradient.shape.zw - start;
+        t = dot(fragPosition - start, direction) / max(dot(direction, direction), 1e-6);
+    } else {
+        t = length(fragPosition - gradient.shape.xy) / max(gradient.shape.z, 1e-6);
+    }
+    t = clamp(t, 0.0, 1.0);
+
+    // Walk the stops; each one takes over as t passes the one before it
+    vec4 color = gradient.colors[0];
+    for (uint i = 1; i < gradient.info.y; i++) {
+        float from = stopOffset(gradient, i - 1);
+        float to = stopOffset(gradient, i);
+        color = mix(color, gradient.colors[i], clamp((t - from) / max(to - from, 1e-6), 0.0, 1.0));
+    }
+    // The vertex color tints the gradient
+    outColor = color * fragColor;
+}
@@ -0,0 +1,17 @@
+#version 450
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec4 inColor;
+layout(location = 2) in uint inGradient;
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec2 fragPosition;
+layout(location = 2) flat out uint fragGradient;
+layout(push_constant) uniform PushConstants {
+    mat4 projection;
+} pc;
+
+void main() {
+    gl_Position = pc.projection * vec4(inPosition, 0.0, 1.0);
+    fragColor = inColor;
+    fragPosition = inPosition;
+    fragGradient = inGradient;
+}
@@ -0,0 +1,39 @@
+#version 450
+layout(location = 0) in vec2 fragLocal;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    mat4 mvp;
+    vec4 color;
+    vec4 outlineColor;
+    vec4 params; // x = radius, y = outline width, z = quad half-size
+} pc;
+
+// Specialization constants, see SDF_* in renderer.rs
+// Width of the anti-aliased edge in pixels
+layout(constant_id = 0) const float AA_WIDTH = 1.0;
+// 0 = flat color, 1 = lit like a sphere
+layout(constant_id = 1) const float SHADING = 0.0;
+
+const vec3 LIGHT_DIR = vec3(-0.41, -0.57, 0.71);
+
+void main() {
+    // Signed distance to the circle edge in pixels, negative inside
+    float dist = length(fragLocal) - pc.params.x;
+    float aa = max(fwidth(dist) * AA_WIDTH, 0.0001);
+    float coverage = 1.0 - smoothstep(-aa, aa, dist);
+
+    // Sphere normal from the position inside the circle; y points down the screen
+    vec2 p = fragLocal / pc.params.x;
+    vec3 normal = vec3(p, sqrt(max(1.0 - dot(p, p), 0.0)));
+    float diffuse = max(dot(normal, LIGHT_DIR), 0.0);
+    float specular = pow(max(dot(reflect(-LIGHT_DIR, normal), vec3(0.0, 0.0, 1.0)), 0.0), 24.0);
+    vec3 lit = pc.color.rgb * (0.35 + 0.65 * diffuse) + vec3(0.4 * specular);
+    vec4 fill = vec4(mix(pc.color.rgb, lit, SHADING), pc.color.a);
+
+    vec4 color = fill;
+    if (pc.params.y > 0.0) {
+        float outline = smoothstep(-pc.params.y - aa, -pc.params.y + aa, dist);
+        color = mix(fill, pc.outlineColor, outline);
+    }
+    outColor = vec4(color.rgb, color.a * coverage);
+}
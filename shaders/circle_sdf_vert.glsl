@@ -0,0 +1,23 @@
+#version 450
+layout(location = 0) out vec2 fragLocal;
+layout(push_constant) uniform PushConstants {
+    mat4 mvp;
+    vec4 color;
+    vec4 outlineColor;
+    vec4 params; // x = radius, y = outline width, z = quad half-size
+} pc;
+
+const vec2 CORNERS[6] = vec2[6](
+    vec2(-1.0, -1.0),
+    vec2(1.0, -1.0),
+    vec2(1.0, 1.0),
+    vec2(-1.0, -1.0),
+    vec2(1.0, 1.0),
+    vec2(-1.0, 1.0)
+);
+
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+    gl_Position = pc.mvp * vec4(corner, 0.0, 1.0);
+    fragLocal = corner * pc.params.z;
+}
@@ -0,0 +1,27 @@
+#version 450
+#extension GL_EXT_mesh_shader : require
+
+// One workgroup per circle: each invocation places one rim vertex and emits one
+// triangle of a fan from vertex 0. The last two triangles are degenerate.
+layout(local_size_x = 64) in;
+layout(triangles, max_vertices = 64, max_primitives = 64) out;
+
+layout(location = 0) out vec4 vertexColor[];
+
+layout(push_constant) uniform PushConstants {
+    mat4 projection;
+    vec4 color;
+    vec2 center;
+    float radius;
+} pc;
+
+void main() {
+    uint i = gl_LocalInvocationIndex;
+    SetMeshOutputsEXT(64, 64);
+
+    float angle = float(i) * (6.2831853 / 64.0);
+    vec2 position = pc.center + vec2(cos(angle), sin(angle)) * pc.radius;
+    gl_MeshVerticesEXT[i].gl_Position = pc.projection * vec4(position, 0.0, 1.0);
+    vertexColor[i] = pc.color;
+    gl_PrimitiveTriangleIndicesEXT[i] = uvec3(0, min(i + 1, 63), min(i + 2, 63));
+}
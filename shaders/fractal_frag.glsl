@@ -0,0 +1,107 @@
+#version 450
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+
+// Double-single values: x holds the float nearest the number, y the remainder
+layout(push_constant) uniform PushConstants {
+    // Real hi, real lo, imaginary hi, imaginary lo of the view center
+    vec4 center;
+    // Complex units per pixel, hi and lo
+    vec2 scale;
+    vec2 resolution;
+    uint maxIterations;
+    // Nonzero once plain floats can no longer tell neighbouring pixels apart
+    uint highPrecision;
+} pc;
+
+const float ESCAPE_RADIUS_SQUARED = 256.0;
+
+// Exact sum of two floats as a double-single value
+vec2 twoSum(float a, float b) {
+    float s = a + b;
+    float v = s - a;
+    float e = (a - (s - v)) + (b - v);
+    return vec2(s, e);
+}
+
+vec2 quickTwoSum(float a, float b) {
+    float s = a + b;
+    float e = b - (s - a);
+    return vec2(s, e);
+}
+
+// Dekker split into two halves that multiply without rounding
+vec2 splitFloat(float a) {
+    float t = 4097.0 * a;
+    float hi = t - (t - a);
+    return vec2(hi, a - hi);
+}
+
+// Exact product of two floats as a double-single value
+vec2 twoProduct(float a, float b) {
+    float p = a * b;
+    vec2 aSplit = splitFloat(a);
+    vec2 bSplit = splitFloat(b);
+    float e = ((aSplit.x * bSplit.x - p) + aSplit.x * bSplit.y + aSplit.y * bSplit.x)
+        + aSplit.y * bSplit.y;
+    return vec2(p, e);
+}
+
+vec2 dsAdd(vec2 a, vec2 b) {
+    vec2 s = twoSum(a.x, b.x);
+    return quickTwoSum(s.x, s.y + a.y + b.y);
+}
+
+vec2 dsMul(vec2 a, vec2 b) {
+    vec2 p = twoProduct(a.x, b.x);
+    return quickTwoSum(p.x, p.y + a.x * b.y + a.y * b.x);
+}
+
+// Iterations before escaping, with a fractional part for smooth banding, or
+// -1 inside the set
+float iterateSingle(vec2 offset) {
+    vec2 c = vec2(pc.center.x, pc.center.z) + offset * pc.scale.x;
+    vec2 z = vec2(0.0);
+    for (uint i = 0u; i < pc.maxIterations; i++) {
+        z = vec2(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        float lengthSquared = dot(z, z);
+        if (lengthSquared > ESCAPE_RADIUS_SQUARED) {
+            return float(i) + 1.0 - log2(log2(lengthSquared) * 0.5);
+        }
+    }
+    return -1.0;
+}
+
+float iterateDouble(vec2 offset) {
+    vec2 cx = dsAdd(pc.center.xy, dsMul(vec2(offset.x, 0.0), pc.scale));
+    vec2 cy = dsAdd(pc.center.zw, dsMul(vec2(offset.y, 0.0), pc.scale));
+    vec2 zx = vec2(0.0);
+    vec2 zy = vec2(0.0);
+    for (uint i = 0u; i < pc.maxIterations; i++) {
+        vec2 xx = dsMul(zx, zx);
+        vec2 yy = dsMul(zy, zy);
+        vec2 xy = dsMul(zx, zy);
+        zx = dsAdd(dsAdd(xx, -yy), cx);
+        zy = dsAdd(dsAdd(xy, xy), cy);
+        float lengthSquared = zx.x * zx.x + zy.x * zy.x;
+        if (lengthSquared > ESCAPE_RADIUS_SQUARED) {
+            return float(i) + 1.0 - log2(log2(lengthSquared) * 0.5);
+        }
+    }
+    return -1.0;
+}
+
+void main() {
+    // Pixel offset from the window center, imaginary axis pointing up
+    vec2 offset = gl_FragCoord.xy - pc.resolution * 0.5;
+    offset.y = -offset.y;
+    float iterations = pc.highPrecision != 0u ? iterateDouble(offset) : iterateSingle(offset);
+    if (iterations < 0.0) {
+        outColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    // Cosine palette cycling every 64 iterations
+    float t = iterations / 64.0;
+    vec3 color = 0.5 + 0.5 * cos(6.2831853 * (t + vec3(0.0, 0.1, 0.2)));
+    outColor = vec4(color, 1.0);
+}
@@ -1,6 +1,7 @@
 #version 450
+layout(location = 0) in vec4 fragColor;
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = vec4(1.0, 0.0, 0.0, 1.0); // Red circle
-}
\ No newline at end of file
+    outColor = fragColor; // Per-object color from push constants
+}
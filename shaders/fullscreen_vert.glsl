@@ -0,0 +1,9 @@
+#version 450
+layout(location = 0) out vec2 fragUv;
+
+// One oversized triangle that covers the whole viewport
+void main() {
+    vec2 uv = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+    fragUv = uv;
+}
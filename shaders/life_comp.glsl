@@ -0,0 +1,67 @@
+#version 450
+layout(local_size_x = 16, local_size_y = 16) in;
+
+// r: 1 if the cell is alive, g: glow that fades after the cell dies
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D current;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D next;
+
+layout(push_constant) uniform PushConstants {
+    ivec2 size;
+    uint seed;
+    // 0: one Game of Life generation, 1: random fill, 2: clear
+    uint mode;
+    float density;
+    float fade;
+} pc;
+
+uint hash(uint x) {
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+float cell(ivec2 position) {
+    // The grid wraps around like a torus
+    ivec2 wrapped = (position + pc.size) % pc.size;
+    return imageLoad(current, wrapped).r;
+}
+
+void main() {
+    ivec2 position = ivec2(gl_GlobalInvocationID.xy);
+    if (position.x >= pc.size.x || position.y >= pc.size.y) {
+        return;
+    }
+
+    if (pc.mode == 1u) {
+        uint index = uint(position.y * pc.size.x + position.x);
+        float random = float(hash(index ^ hash(pc.seed))) / 4294967295.0;
+        float alive = random < pc.density ? 1.0 : 0.0;
+        imageStore(next, position, vec4(alive, alive, 0.0, 1.0));
+        return;
+    }
+    if (pc.mode == 2u) {
+        imageStore(next, position, vec4(0.0, 0.0, 0.0, 1.0));
+        return;
+    }
+
+    float neighbours = 0.0;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            if (x != 0 || y != 0) {
+                neighbours += cell(position + ivec2(x, y));
+            }
+        }
+    }
+    vec4 state = imageLoad(current, position);
+    bool alive = state.r > 0.5;
+    // B3/S23: birth on exactly three neighbours, survival on two or three
+    bool three = neighbours > 2.5 && neighbours < 3.5;
+    bool two = neighbours > 1.5 && neighbours < 2.5;
+    bool survives = three || (alive && two);
+    float nextAlive = survives ? 1.0 : 0.0;
+    float glow = max(nextAlive, state.g * pc.fade);
+    imageStore(next, position, vec4(nextAlive, glow, 0.0, 1.0));
+}
@@ -0,0 +1,12 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D cells;
+layout(set = 0, binding = 1) uniform sampler cellSampler;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+
+// Live cells are bright; recently dead ones leave a fading blue glow
+void main() {
+    vec4 state = texture(sampler2D(cells, cellSampler), fragUv);
+    vec3 glow = vec3(0.1, 0.3, 0.9) * state.g;
+    outColor = vec4(mix(glow, vec3(1.0, 0.9, 0.6), state.r), 1.0);
+}
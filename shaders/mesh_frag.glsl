@@ -0,0 +1,36 @@
+#version 450
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 0) out vec4 outColor;
+
+struct PointLight {
+    vec4 positionRange;
+    vec4 color;
+};
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 viewProjection;
+    vec4 eye;
+    vec4 ambient;
+    vec4 lightDirection;
+    vec4 lightColor;
+    PointLight pointLights[4];
+    uint pointLightCount;
+    float specular;
+    float shininess;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 normal0;
+    vec4 normal1;
+    vec4 normal2;
+    vec4 color;
+} pc;
+
+// Diffuse shading from the directional light only, without highlights
+void main() {
+    float diffuse = max(dot(normalize(fragNormal), frame.lightDirection.xyz), 0.0);
+    vec3 light = frame.ambient.rgb + frame.lightColor.rgb * diffuse;
+    outColor = vec4(pc.color.rgb * light, pc.color.a);
+}
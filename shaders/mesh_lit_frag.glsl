@@ -0,0 +1,61 @@
+#version 450
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 0) out vec4 outColor;
+
+struct PointLight {
+    vec4 positionRange;
+    vec4 color;
+};
+
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 viewProjection;
+    vec4 eye;
+    vec4 ambient;
+    vec4 lightDirection;
+    vec4 lightColor;
+    PointLight pointLights[4];
+    uint pointLightCount;
+    float specular;
+    float shininess;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    vec4 normal0;
+    vec4 normal1;
+    vec4 normal2;
+    vec4 color;
+} pc;
+
+vec3 diffuseSum = vec3(0.0);
+vec3 specularSum = vec3(0.0);
+
+// Blinn-Phong: Lambert diffuse plus a highlight around the half vector between
+// the light and view directions
+void addLight(vec3 normal, vec3 view, vec3 toLight, vec3 color) {
+    float diffuse = max(dot(normal, toLight), 0.0);
+    if (diffuse <= 0.0) {
+        return;
+    }
+    vec3 halfway = normalize(toLight + view);
+    float highlight = pow(max(dot(normal, halfway), 0.0), frame.shininess);
+    diffuseSum += color * diffuse;
+    specularSum += color * highlight * frame.specular;
+}
+
+void main() {
+    vec3 normal = normalize(fragNormal);
+    vec3 view = normalize(frame.eye.xyz - fragPosition);
+    addLight(normal, view, frame.lightDirection.xyz, frame.lightColor.rgb);
+    for (uint i = 0u; i < frame.pointLightCount; i++) {
+        PointLight light = frame.pointLights[i];
+        vec3 offset = light.positionRange.xyz - fragPosition;
+        float distance = length(offset);
+        // Smooth falloff that reaches zero at the range
+        float falloff = clamp(1.0 - distance / light.positionRange.w, 0.0, 1.0);
+        addLight(normal, view, offset / max(distance, 0.0001), light.color.rgb * falloff * falloff);
+    }
+    vec3 color = pc.color.rgb * (frame.ambient.rgb + diffuseSum) + specularSum;
+    outColor = vec4(color, pc.color.a);
+}
@@ -0,0 +1,9 @@
+#version 450
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 0) out vec4 outColor;
+
+// World-space normals mapped from -1..1 to 0..1, for the normals debug view
+void main() {
+    outColor = vec4(normalize(fragNormal) * 0.5 + 0.5, 1.0);
+}
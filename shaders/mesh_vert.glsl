@@ -0,0 +1,42 @@
+#version 450
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 0) out vec3 fragPosition;
+layout(location = 1) out vec3 fragNormal;
+
+struct PointLight {
+    // xyz position, w range
+    vec4 positionRange;
+    // rgb color times intensity
+    vec4 color;
+};
+
+// Camera and lights, the same for every draw in a frame
+layout(set = 0, binding = 0) uniform Frame {
+    mat4 viewProjection;
+    vec4 eye;
+    vec4 ambient;
+    // Towards the light, normalized
+    vec4 lightDirection;
+    vec4 lightColor;
+    PointLight pointLights[4];
+    uint pointLightCount;
+    float specular;
+    float shininess;
+} frame;
+
+layout(push_constant) uniform PushConstants {
+    mat4 model;
+    // Columns of the model matrix's inverse transpose, for world space normals
+    vec4 normal0;
+    vec4 normal1;
+    vec4 normal2;
+    vec4 color;
+} pc;
+
+void main() {
+    vec4 world = pc.model * vec4(inPosition, 1.0);
+    gl_Position = frame.viewProjection * world;
+    fragPosition = world.xyz;
+    fragNormal = mat3(pc.normal0.xyz, pc.normal1.xyz, pc.normal2.xyz) * inNormal;
+}
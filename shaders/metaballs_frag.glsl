@@ -0,0 +1,53 @@
+#version 450
+
+struct Ball {
+    // xy center in pixels, z radius, w outline width
+    vec4 shape;
+    vec4 color;
+    vec4 outlineColor;
+};
+
+layout(std430, set = 0, binding = 0) readonly buffer Balls {
+    Ball balls[];
+};
+
+layout(push_constant) uniform PushConstants {
+    uint count;
+    float threshold;
+} pc;
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+
+// Sums r^2 / d^2 over every ball; the surface is where the field crosses the
+// threshold. Colors are blended by each ball's share of the field.
+void main() {
+    vec2 point = gl_FragCoord.xy;
+    float field = 0.0;
+    vec4 color = vec4(0.0);
+    vec4 outlineColor = vec4(0.0);
+    float outlineWidth = 0.0;
+    for (uint i = 0u; i < pc.count; i++) {
+        Ball ball = balls[i];
+        vec2 offset = point - ball.shape.xy;
+        float weight = ball.shape.z * ball.shape.z / max(dot(offset, offset), 0.0001);
+        field += weight;
+        color += ball.color * weight;
+        outlineColor += ball.outlineColor * weight;
+        outlineWidth += ball.shape.w * weight;
+    }
+    if (field <= 0.0) {
+        discard;
+    }
+    color /= field;
+    outlineColor /= field;
+    outlineWidth /= field;
+
+    // Distance inside the surface in pixels, from the field's screen-space gradient
+    float gradient = max(length(vec2(dFdx(field), dFdy(field))), 0.0001);
+    float inside = (field - pc.threshold) / gradient;
+    float coverage = clamp(inside + 0.5, 0.0, 1.0);
+    float outline = outlineWidth > 0.0 ? 1.0 - clamp(inside - outlineWidth + 0.5, 0.0, 1.0) : 0.0;
+    vec4 shaded = mix(color, outlineColor, outline);
+    outColor = vec4(shaded.rgb, shaded.a * coverage);
+}
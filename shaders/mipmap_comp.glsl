@@ -0,0 +1,26 @@
+#version 450
+layout(local_size_x = 8, local_size_y = 8) in;
+
+// One mip level down: each texel of `dst` is the average of the 2x2 block of
+// `src` it covers
+layout(set = 0, binding = 0, rgba8) uniform readonly image2D src;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D dst;
+
+layout(push_constant) uniform PushConstants {
+    ivec2 size;
+} pc;
+
+void main() {
+    ivec2 position = ivec2(gl_GlobalInvocationID.xy);
+    if (position.x >= pc.size.x || position.y >= pc.size.y) {
+        return;
+    }
+    // A side of length 1 in the source has no second texel to average
+    ivec2 last = imageSize(src) - 1;
+    ivec2 corner = position * 2;
+    vec4 sum = imageLoad(src, corner)
+        + imageLoad(src, min(corner + ivec2(1, 0), last))
+        + imageLoad(src, min(corner + ivec2(0, 1), last))
+        + imageLoad(src, min(corner + ivec2(1, 1), last));
+    imageStore(dst, position, sum * 0.25);
+}
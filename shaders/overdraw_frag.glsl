@@ -0,0 +1,9 @@
+#version 450
+layout(location = 0) out vec4 outColor;
+
+// Added once per fragment with additive blending. The scene target is HDR, so
+// the sum keeps counting past 1 and the final copy clamps each channel: a few
+// layers show red, around ten orange and thirty or more yellow.
+void main() {
+    outColor = vec4(0.12, 0.04, 0.01, 1.0);
+}
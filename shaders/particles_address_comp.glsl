@@ -0,0 +1,53 @@
+#version 450
+#extension GL_EXT_buffer_reference : require
+layout(local_size_x = 256) in;
+
+struct Particle {
+    vec2 position;
+    vec2 velocity;
+    vec4 color;
+};
+
+// particles_comp.glsl with the particle buffer reached through a device
+// address in the push constants instead of a descriptor set
+layout(buffer_reference, std430, buffer_reference_align = 16) buffer Particles {
+    Particle particles[];
+};
+
+layout(push_constant) uniform PushConstants {
+    vec2 attractor;
+    vec2 bounds;
+    float dt;
+    float strength;
+    float drag;
+    uint count;
+    Particles particles;
+} pc;
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index >= pc.count) {
+        return;
+    }
+    Particle particle = pc.particles.particles[index];
+
+    // Softened inverse-square pull towards the attractor
+    vec2 toAttractor = pc.attractor - particle.position;
+    float distanceSquared = dot(toAttractor, toAttractor) + 400.0;
+    vec2 acceleration = toAttractor * (pc.strength / (distanceSquared * sqrt(distanceSquared)));
+    particle.velocity += acceleration * pc.dt;
+    particle.velocity *= exp(-pc.drag * pc.dt);
+    particle.position += particle.velocity * pc.dt;
+
+    // Bounce off the window edges
+    if (particle.position.x < 0.0 || particle.position.x > pc.bounds.x) {
+        particle.velocity.x = -particle.velocity.x;
+        particle.position.x = clamp(particle.position.x, 0.0, pc.bounds.x);
+    }
+    if (particle.position.y < 0.0 || particle.position.y > pc.bounds.y) {
+        particle.velocity.y = -particle.velocity.y;
+        particle.position.y = clamp(particle.position.y, 0.0, pc.bounds.y);
+    }
+
+    pc.particles.particles[index] = particle;
+}
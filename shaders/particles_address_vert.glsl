@@ -0,0 +1,29 @@
+#version 450
+#extension GL_EXT_buffer_reference : require
+layout(location = 0) out vec4 fragColor;
+
+struct Particle {
+    vec2 position;
+    vec2 velocity;
+    vec4 color;
+};
+
+// particles_vert.glsl pulling each particle from its device address rather
+// than from vertex attributes
+layout(buffer_reference, std430, buffer_reference_align = 16) readonly buffer Particles {
+    Particle particles[];
+};
+
+layout(push_constant) uniform PushConstants {
+    mat4 projection;
+    Particles particles;
+} pc;
+
+void main() {
+    Particle particle = pc.particles.particles[gl_VertexIndex];
+    gl_Position = pc.projection * vec4(particle.position, 0.0, 1.0);
+    gl_PointSize = 1.0;
+    // Fast particles burn brighter
+    float speed = length(particle.velocity);
+    fragColor = vec4(particle.color.rgb * (0.5 + speed / 400.0), particle.color.a);
+}
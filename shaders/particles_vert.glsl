@@ -0,0 +1,16 @@
+#version 450
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inVelocity;
+layout(location = 2) in vec4 inColor;
+layout(location = 0) out vec4 fragColor;
+layout(push_constant) uniform PushConstants {
+    mat4 projection;
+} pc;
+
+void main() {
+    gl_Position = pc.projection * vec4(inPosition, 0.0, 1.0);
+    gl_PointSize = 1.0;
+    // Fast particles burn brighter
+    float speed = length(inVelocity);
+    fragColor = vec4(inColor.rgb * (0.5 + speed / 400.0), inColor.a);
+}
@@ -0,0 +1,8 @@
+#version 450
+
+// Stands in for the vertex shader of a pipeline that is still compiling. Every
+// vertex lands on the same point, so no triangle covers a pixel and the draw
+// leaves the target untouched.
+void main() {
+    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+}
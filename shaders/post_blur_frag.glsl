@@ -0,0 +1,25 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float sigma;
+    float threshold;
+    float intensity;
+} pc;
+
+// Separable gaussian: `direction` is one texel along the blur axis
+void main() {
+    vec4 sum = vec4(0.0);
+    float weightSum = 0.0;
+    for (int i = -12; i <= 12; i++) {
+        float x = float(i);
+        float weight = exp(-(x * x) / (2.0 * pc.sigma * pc.sigma));
+        sum += texture(sampler2D(sourceTexture, sourceSampler), fragUv + pc.direction * x) * weight;
+        weightSum += weight;
+    }
+    outColor = sum / weightSum;
+}
@@ -0,0 +1,25 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    // Size of one texel in UV
+    vec2 direction;
+    float sigma;
+    float threshold;
+    // Red and blue shift in pixels at the corners
+    float intensity;
+} pc;
+
+// Samples red further out and blue further in than green. The shift grows with
+// the square of the distance from the middle, so the center stays sharp.
+void main() {
+    vec2 fromCenter = fragUv * 2.0 - 1.0;
+    vec2 shift = fromCenter * dot(fromCenter, fromCenter) * 0.5 * pc.intensity * pc.direction;
+    vec4 center = texture(sampler2D(sourceTexture, sourceSampler), fragUv);
+    float red = texture(sampler2D(sourceTexture, sourceSampler), fragUv - shift).r;
+    float blue = texture(sampler2D(sourceTexture, sourceSampler), fragUv + shift).b;
+    outColor = vec4(red, center.g, blue, center.a);
+}
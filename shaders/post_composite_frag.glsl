@@ -0,0 +1,19 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float sigma;
+    float threshold;
+    float intensity;
+} pc;
+
+// Adds the blurred bright pass back on top of the scene
+void main() {
+    vec4 scene = texture(sampler2D(sourceTexture, sourceSampler), fragUv);
+    vec3 bloom = texture(sampler2D(secondTexture, sourceSampler), fragUv).rgb;
+    outColor = vec4(scene.rgb + bloom * pc.intensity, scene.a);
+}
@@ -0,0 +1,16 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float sigma;
+    float threshold;
+    float intensity;
+} pc;
+
+void main() {
+    outColor = texture(sampler2D(sourceTexture, sourceSampler), fragUv);
+}
@@ -0,0 +1,37 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    // Size of one texel in UV
+    vec2 direction;
+    // Barrel curvature, 0 for a flat screen
+    float sigma;
+    float threshold;
+    // Scanline darkness, 0..1
+    float intensity;
+} pc;
+
+// Pixels from one scanline to the next
+const float SCANLINE_PERIOD = 3.0;
+const float PI = 3.14159265;
+
+void main() {
+    // Push the edges outwards so the image bulges towards the viewer; whatever
+    // lands outside the source is the tube's black border
+    vec2 centered = fragUv * 2.0 - 1.0;
+    centered *= 1.0 + centered.yx * centered.yx * pc.sigma;
+    vec2 uv = centered * 0.5 + 0.5;
+    if (any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+        outColor = vec4(0.0);
+        return;
+    }
+    vec4 source = texture(sampler2D(sourceTexture, sourceSampler), uv);
+    // Lines follow the curved image rather than the window
+    float row = uv.y / pc.direction.y;
+    float line = 0.5 + 0.5 * cos(row * 2.0 * PI / SCANLINE_PERIOD);
+    float shade = mix(1.0, line, pc.intensity);
+    outColor = vec4(source.rgb * shade, source.a);
+}
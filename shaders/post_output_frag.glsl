@@ -0,0 +1,62 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float sigma;
+    float threshold;
+    // Takes the scene's 1.0 to paper white: scRGB units, or nits for HDR10
+    float intensity;
+    // 0 none, 1 Reinhard, 2 ACES; SDR output only
+    uint tonemap;
+    // 0 SDR, 1 scRGB, 2 HDR10
+    uint encoding;
+} pc;
+
+vec3 tonemap(vec3 color) {
+    if (pc.tonemap == 1u) {
+        return color / (1.0 + color);
+    }
+    if (pc.tonemap == 2u) {
+        return clamp(
+            (color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14),
+            0.0,
+            1.0);
+    }
+    return color;
+}
+
+// ST 2084 inverse EOTF: absolute nits to PQ code values
+vec3 pq(vec3 nits) {
+    const float m1 = 0.1593017578125;
+    const float m2 = 78.84375;
+    const float c1 = 0.8359375;
+    const float c2 = 18.8515625;
+    const float c3 = 18.6875;
+    vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+// Rec. 709 to Rec. 2020 primaries, both linear
+const mat3 REC709_TO_REC2020 = mat3(
+    0.6274, 0.0691, 0.0164,
+    0.3293, 0.9195, 0.0880,
+    0.0433, 0.0114, 0.8956);
+
+// The last post pass: writes the linear HDR scene into the swapchain in
+// whatever encoding its color space wants
+void main() {
+    vec4 source = texture(sampler2D(sourceTexture, sourceSampler), fragUv);
+    vec3 color = max(source.rgb, vec3(0.0));
+    if (pc.encoding == 1u) {
+        color *= pc.intensity;
+    } else if (pc.encoding == 2u) {
+        color = pq(REC709_TO_REC2020 * color * pc.intensity);
+    } else {
+        color = tonemap(color);
+    }
+    outColor = vec4(color, source.a);
+}
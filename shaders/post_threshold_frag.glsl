@@ -0,0 +1,20 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float sigma;
+    float threshold;
+    float intensity;
+} pc;
+
+// Keeps only the part of each pixel brighter than the threshold
+void main() {
+    vec4 color = texture(sampler2D(sourceTexture, sourceSampler), fragUv);
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - pc.threshold, 0.0) / max(brightness, 0.0001);
+    outColor = vec4(color.rgb * contribution, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float sigma;
+    float threshold;
+    float intensity;
+} pc;
+
+// Keeps the brighter of the scene and the faded history (second texture), so
+// moving objects leave a trail without static ones blowing out. `intensity`
+// is how much of the history survives each frame.
+void main() {
+    vec4 scene = texture(sampler2D(sourceTexture, sourceSampler), fragUv);
+    vec4 history = texture(sampler2D(secondTexture, sourceSampler), fragUv);
+    outColor = max(scene, history * pc.intensity);
+}
@@ -0,0 +1,25 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D sourceTexture;
+layout(set = 0, binding = 1) uniform sampler sourceSampler;
+layout(set = 0, binding = 2) uniform texture2D secondTexture;
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+layout(push_constant) uniform PushConstants {
+    vec2 direction;
+    float sigma;
+    float threshold;
+    // How dark the corners get, 0..1
+    float intensity;
+} pc;
+
+// Where the darkening starts and ends, as a fraction of the distance from the
+// middle to a corner
+const float INNER = 0.4;
+const float OUTER = 1.0;
+
+void main() {
+    vec4 source = texture(sampler2D(sourceTexture, sourceSampler), fragUv);
+    float reach = length(fragUv * 2.0 - 1.0) / sqrt(2.0);
+    float shade = 1.0 - pc.intensity * smoothstep(INNER, OUTER, reach);
+    outColor = vec4(source.rgb * shade, source.a);
+}
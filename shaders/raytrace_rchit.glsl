@@ -0,0 +1,14 @@
+#version 460
+#extension GL_EXT_ray_tracing : require
+
+layout(location = 0) rayPayloadInEXT vec3 payload;
+
+// Every sphere is an instance of one unit sphere, so the normal points away
+// from the instance's translation. The color comes packed in the custom index.
+void main() {
+    vec3 position = gl_WorldRayOriginEXT + gl_WorldRayDirectionEXT * gl_HitTEXT;
+    vec3 normal = normalize(position - gl_ObjectToWorldEXT[3]);
+    vec3 albedo = unpackUnorm4x8(uint(gl_InstanceCustomIndexEXT)).rgb;
+    float diffuse = max(dot(normal, vec3(0.3578, 0.8944, 0.2683)), 0.0);
+    payload = albedo * (0.15 + 0.85 * diffuse);
+}
@@ -0,0 +1,21 @@
+#version 460
+#extension GL_EXT_ray_tracing : require
+
+layout(set = 0, binding = 0) uniform accelerationStructureEXT scene;
+layout(set = 0, binding = 1, rgba8) uniform writeonly image2D outputImage;
+
+layout(push_constant) uniform PushConstants {
+    mat4 inverseViewProjection;
+    vec4 eye;
+} pc;
+
+layout(location = 0) rayPayloadEXT vec3 payload;
+
+// One primary ray per pixel from the eye through the far plane
+void main() {
+    vec2 ndc = (vec2(gl_LaunchIDEXT.xy) + 0.5) / vec2(gl_LaunchSizeEXT.xy) * 2.0 - 1.0;
+    vec4 target = pc.inverseViewProjection * vec4(ndc, 1.0, 1.0);
+    vec3 direction = normalize(target.xyz * (1.0 / target.w) - pc.eye.xyz);
+    traceRayEXT(scene, gl_RayFlagsOpaqueEXT, 0xff, 0, 0, 0, pc.eye.xyz, 0.001, direction, 1000.0, 0);
+    imageStore(outputImage, ivec2(gl_LaunchIDEXT.xy), vec4(payload, 1.0));
+}
@@ -0,0 +1,10 @@
+#version 460
+#extension GL_EXT_ray_tracing : require
+
+layout(location = 0) rayPayloadInEXT vec3 payload;
+
+// Sky gradient from the horizon up
+void main() {
+    float up = gl_WorldRayDirectionEXT.y * 0.5 + 0.5;
+    payload = mix(vec3(0.9, 0.9, 1.0), vec3(0.3, 0.5, 0.9), vec3(up));
+}
@@ -0,0 +1,54 @@
+#version 450
+#extension GL_EXT_buffer_reference : require
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragTint;
+layout(location = 2) flat out uint fragTexture;
+
+// Mirrors `SpriteInstance` in sprite.rs. The tint is split in two so the
+// struct keeps its 8-byte alignment and 56-byte stride; a vec4 would pad it
+// to 64.
+struct SpriteInstance {
+    vec2 position;
+    vec2 size;
+    vec2 uvMin;
+    vec2 uvMax;
+    vec2 tintRg;
+    vec2 tintBa;
+    float rotation;
+    uint textureIndex;
+};
+
+layout(buffer_reference, std430, buffer_reference_align = 8) readonly buffer SpriteInstances {
+    SpriteInstance instances[];
+};
+
+// Points at the frame ring offset the batch was uploaded to
+layout(push_constant) uniform PushConstants {
+    mat4 projection;
+    SpriteInstances instances;
+} pc;
+
+const vec2 CORNERS[6] = vec2[6](
+    vec2(-0.5, -0.5),
+    vec2(0.5, -0.5),
+    vec2(0.5, 0.5),
+    vec2(-0.5, -0.5),
+    vec2(0.5, 0.5),
+    vec2(-0.5, 0.5)
+);
+
+// sprite_bindless_vert.glsl pulling its instance from a device address rather
+// than from vertex attributes. Goes with either fragment shader; the plain one
+// ignores fragTexture.
+void main() {
+    SpriteInstance instance = pc.instances.instances[gl_InstanceIndex];
+    vec2 corner = CORNERS[gl_VertexIndex];
+    float s = sin(instance.rotation);
+    float c = cos(instance.rotation);
+    vec2 local = corner * instance.size;
+    vec2 rotated = vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+    gl_Position = pc.projection * vec4(instance.position + rotated, 0.0, 1.0);
+    fragUv = mix(instance.uvMin, instance.uvMax, corner + 0.5);
+    fragTint = vec4(instance.tintRg, instance.tintBa);
+    fragTexture = instance.textureIndex;
+}
@@ -0,0 +1,16 @@
+#version 450
+#extension GL_EXT_nonuniform_qualifier : require
+// Every sprite texture; slots without one are never sampled. The size is
+// MAX_SPRITE_TEXTURES in sprite.rs.
+layout(set = 0, binding = 0) uniform texture2D spriteTextures[256];
+layout(set = 0, binding = 1) uniform sampler spriteSampler;
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragTint;
+layout(location = 2) flat in uint fragTexture;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = texture(
+        sampler2D(spriteTextures[nonuniformEXT(fragTexture)], spriteSampler),
+        fragUv) * fragTint;
+}
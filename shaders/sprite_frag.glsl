@@ -0,0 +1,10 @@
+#version 450
+layout(set = 0, binding = 0) uniform texture2D spriteTexture;
+layout(set = 0, binding = 1) uniform sampler spriteSampler;
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragTint;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = texture(sampler2D(spriteTexture, spriteSampler), fragUv) * fragTint;
+}
@@ -0,0 +1,32 @@
+#version 450
+layout(location = 0) in vec2 inPosition;
+layout(location = 1) in vec2 inSize;
+layout(location = 2) in vec2 inUvMin;
+layout(location = 3) in vec2 inUvMax;
+layout(location = 4) in vec4 inTint;
+layout(location = 5) in float inRotation;
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragTint;
+layout(push_constant) uniform PushConstants {
+    mat4 projection;
+} pc;
+
+const vec2 CORNERS[6] = vec2[6](
+    vec2(-0.5, -0.5),
+    vec2(0.5, -0.5),
+    vec2(0.5, 0.5),
+    vec2(-0.5, -0.5),
+    vec2(0.5, 0.5),
+    vec2(-0.5, 0.5)
+);
+
+void main() {
+    vec2 corner = CORNERS[gl_VertexIndex];
+    float s = sin(inRotation);
+    float c = cos(inRotation);
+    vec2 local = corner * inSize;
+    vec2 rotated = vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+    gl_Position = pc.projection * vec4(inPosition + rotated, 0.0, 1.0);
+    fragUv = mix(inUvMin, inUvMax, corner + 0.5);
+    fragTint = inTint;
+}
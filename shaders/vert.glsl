@@ -1,9 +1,12 @@
 #version 450
 layout(location = 0) in vec2 inPosition;
+layout(location = 0) out vec4 fragColor;
 layout(push_constant) uniform PushConstants {
     mat4 mvp;
+    vec4 color;
 } pc;
 
 void main() {
     gl_Position = pc.mvp * vec4(inPosition, 0.0, 1.0);
-}
\ No newline at end of file
+    fragColor = pc.color;
+}
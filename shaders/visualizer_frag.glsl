@@ -0,0 +1,55 @@
+#version 450
+layout(location = 0) in vec2 fragUv;
+layout(location = 0) out vec4 outColor;
+
+// 64 band levels and 128 waveform samples, packed four to a vec4 for std140
+layout(set = 0, binding = 0) uniform Spectrum {
+    vec4 bands[16];
+    vec4 waveform[32];
+} spectrum;
+
+layout(push_constant) uniform PushConstants {
+    vec2 resolution;
+    float time;
+} pc;
+
+const uint BAND_COUNT = 64u;
+const uint WAVEFORM_SIZE = 128u;
+
+float band(uint index) {
+    return spectrum.bands[index / 4u][index % 4u];
+}
+
+float waveSample(uint index) {
+    return spectrum.waveform[index / 4u][index % 4u];
+}
+
+// Bars along the bottom colored from blue (quiet) to magenta (loud), with the
+// waveform drawn as a line across the upper half
+void main() {
+    vec2 pixel = fragUv * pc.resolution;
+    vec3 color = vec3(0.02, 0.02, 0.05);
+
+    float barPosition = fragUv.x * float(BAND_COUNT);
+    uint index = min(uint(barPosition), BAND_COUNT - 1u);
+    float level = band(index);
+    float height = 1.0 - fragUv.y;
+    bool inGap = fract(barPosition) > 0.8;
+    if (!inGap && height < level * 0.6) {
+        vec3 quiet = vec3(0.1, 0.4, 1.0);
+        vec3 loud = vec3(1.0, 0.2, 0.8);
+        color = mix(quiet, loud, height / 0.6);
+    }
+
+    // Linear interpolation between neighbouring waveform samples
+    float wavePosition = fragUv.x * float(WAVEFORM_SIZE - 1u);
+    uint waveIndex = min(uint(wavePosition), WAVEFORM_SIZE - 2u);
+    float wave = mix(waveSample(waveIndex), waveSample(waveIndex + 1u), fract(wavePosition));
+    float waveY = pc.resolution.y * (0.25 - wave * 0.2);
+    float lineDistance = abs(pixel.y - waveY);
+    float line = clamp(2.0 - lineDistance, 0.0, 1.0);
+    float hue = 0.5 + 0.5 * sin(pc.time);
+    color = mix(color, vec3(1.0, 0.8 + 0.2 * hue, 0.4), line);
+
+    outColor = vec4(color, 1.0);
+}
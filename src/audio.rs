@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, Sample, SizedSample};
+
+// Captures the default input device through cpal and keeps the most recent
+// samples, mixed down to mono, for the spectrum analyzer. To visualize what is
+// playing rather than the microphone, pick a loopback/monitor source as the
+// system's default input.
+pub struct AudioCapture {
+    // Kept alive for as long as capture should run
+    _stream: cpal::Stream,
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: f32,
+}
+
+impl AudioCapture {
+    // Starts capturing, keeping the last `capacity` samples. None if there is no
+    // input device or the stream could not be opened.
+    pub fn new(capacity: usize) -> Option<Self> {
+        let host = cpal::default_host();
+        let Some(device) = host.default_input_device() else {
+            println!("Audio capture unavailable: no input device");
+            return None;
+        };
+        let config = match device.default_input_config() {
+            Ok(config) => config,
+            Err(e) => {
+                println!("Audio capture unavailable: {:?}", e);
+                return None;
+            }
+        };
+        let samples = Arc::new(Mutex::new(VecDeque::from(vec![0.0; capacity])));
+        let sample_format = config.sample_format();
+        let config: cpal::StreamConfig = config.into();
+        let result = match sample_format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, &samples),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, &samples),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, &samples),
+            format => {
+                println!(
+                    "Audio capture unavailable: unsupported sample format {:?}",
+                    format
+                );
+                return None;
+            }
+        };
+        let stream = match result {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Audio capture unavailable: {:?}", e);
+                return None;
+            }
+        };
+        if let Err(e) = stream.play() {
+            println!("Audio capture unavailable: {:?}", e);
+            return None;
+        }
+        println!(
+            "Capturing audio from {} at {} Hz",
+            device
+                .name()
+                .unwrap_or_else(|_| "unknown device".to_string()),
+            config.sample_rate.0
+        );
+        Some(Self {
+            _stream: stream,
+            samples,
+            sample_rate: config.sample_rate.0 as f32,
+        })
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    // Copies the newest `out.len()` samples into `out`, oldest first
+    pub fn latest(&self, out: &mut [f32]) {
+        let samples = self.samples.lock().expect("Failed to lock audio samples");
+        let skip = samples.len().saturating_sub(out.len());
+        for (slot, &sample) in out.iter_mut().zip(samples.iter().skip(skip)) {
+            *slot = sample;
+        }
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    samples: &Arc<Mutex<VecDeque<f32>>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let samples = Arc::clone(samples);
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            let mut samples = samples.lock().expect("Failed to lock audio samples");
+            for frame in data.chunks(channels) {
+                let mono = frame
+                    .iter()
+                    .map(|&sample| f32::from_sample(sample))
+                    .sum::<f32>()
+                    / channels as f32;
+                // The buffer stays at its initial capacity
+                samples.pop_front();
+                samples.push_back(mono);
+            }
+        },
+        |e| println!("Audio stream error: {:?}", e),
+        None,
+    )
+}
@@ -0,0 +1,113 @@
+use ash::vk;
+use glam::Vec2;
+
+use crate::gpu::GpuContext;
+use crate::pipeline::{PipelineDesc, PipelineManager};
+
+// What a scene shows under everything it draws
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Background {
+    // The renderer's clear color
+    #[default]
+    Clear,
+    // Fades from `top` at the top of the window to `bottom` at the bottom
+    VerticalGradient { top: [f32; 3], bottom: [f32; 3] },
+    // Slowly moving sine-wave plasma blending between two colors. `speed`
+    // scales simulation seconds into animation seconds.
+    Plasma {
+        low: [f32; 3],
+        high: [f32; 3],
+        speed: f32,
+    },
+}
+
+// Mirrors the push constant block in shaders/background_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct BackgroundInputs {
+    first: [f32; 4],
+    second: [f32; 4],
+    resolution: [f32; 2],
+    time: f32,
+    mode: u32,
+}
+
+// Draws a procedural `Background` as a fullscreen triangle before any other
+// layer. Animated backgrounds follow the time passed to `advance`.
+pub struct BackgroundPass {
+    pub background: Background,
+    time: f32,
+    pipeline: PipelineDesc,
+}
+
+impl BackgroundPass {
+    pub fn new(gpu: &GpuContext, pipelines: &mut PipelineManager, background: Background) -> Self {
+        let shaders = pipelines.register_shaders(
+            gpu,
+            "background",
+            include_bytes!("../shaders/fullscreen_vert.spv"),
+            include_bytes!("../shaders/background_frag.spv"),
+        );
+        let pipeline_layout = pipelines.interface(shaders).create_pipeline_layout(
+            gpu,
+            &[],
+            size_of::<BackgroundInputs>(),
+        );
+        let pipeline = PipelineDesc::new(shaders, pipeline_layout);
+        pipelines.prepare(gpu, &pipeline);
+        println!("Background pass created: {:?}", background);
+
+        Self {
+            background,
+            time: 0.0,
+            pipeline,
+        }
+    }
+
+    // Moves animated backgrounds on by `dt` simulation seconds
+    pub fn advance(&mut self, dt: f32) {
+        if let Background::Plasma { speed, .. } = self.background {
+            self.time += dt * speed;
+        }
+    }
+
+    // `resolution` is the scene target size in pixels
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+        resolution: Vec2,
+    ) {
+        let (first, second, mode) = match self.background {
+            Background::Clear => return,
+            Background::VerticalGradient { top, bottom } => (top, bottom, 0),
+            Background::Plasma { low, high, .. } => (low, high, 1),
+        };
+        let inputs = BackgroundInputs {
+            first: [first[0], first[1], first[2], 1.0],
+            second: [second[0], second[1], second[2], 1.0],
+            resolution: resolution.to_array(),
+            time: self.time,
+            mode,
+        };
+        let pipeline = pipelines.get(gpu, &self.pipeline);
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&inputs),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, pipelines: &mut PipelineManager) {
+        pipelines.release_shaders(device, self.pipeline.shaders);
+        unsafe { device.destroy_pipeline_layout(self.pipeline.layout, None) };
+    }
+}
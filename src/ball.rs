@@ -0,0 +1,162 @@
+use glam::Vec2;
+
+// Wall bounces slower than this (pixels per second) come to rest instead of
+// jittering forever against the floor, when gravity or losses are on
+const REST_SPEED: f32 = 20.0;
+
+// Forces and losses applied to every ball each step
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Physics {
+    // Acceleration in pixels per second squared; +y is down
+    pub gravity: Vec2,
+    // Fraction of the normal speed kept after hitting a wall, 0..1
+    pub restitution: f32,
+    // Exponential velocity decay per second
+    pub air_drag: f32,
+}
+
+// No gravity, no losses: the original constant-velocity reflection
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            gravity: Vec2::ZERO,
+            restitution: 1.0,
+            air_drag: 0.0,
+        }
+    }
+}
+
+impl Physics {
+    // Earth-ish gravity with lossy bounces, for the demo
+    pub fn bouncy() -> Self {
+        Self {
+            gravity: Vec2::new(0.0, 980.0),
+            restitution: 0.8,
+            air_drag: 0.1,
+        }
+    }
+
+    // Bounces slower than this stop dead. Without gravity or bounce losses a
+    // ball never settles, so nothing is cut off.
+    pub fn rest_speed(&self) -> f32 {
+        if self.restitution < 1.0 || self.gravity != Vec2::ZERO {
+            REST_SPEED
+        } else {
+            0.0
+        }
+    }
+}
+
+// A moving disc in window (ortho) space: y down, origin top-left
+#[derive(Clone, Copy, Debug)]
+pub struct Ball {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+    // Added to the demo's cycling hue so balls stay distinguishable
+    pub hue_offset: f32,
+}
+
+impl Ball {
+    pub fn new(position: Vec2, velocity: Vec2, radius: f32) -> Self {
+        Self {
+            position,
+            velocity,
+            radius,
+            hue_offset: 0.0,
+        }
+    }
+
+    // Applies gravity and drag, moves the ball (semi-implicit Euler) and bounces
+    // it off the edges of the 0..bounds rectangle. Returns the wall hit, if any,
+    // as in `keep_inside`.
+    pub fn step(&mut self, dt: f32, bounds: Vec2, physics: &Physics) -> Option<Vec2> {
+        self.velocity += physics.gravity * dt;
+        self.velocity *= (-physics.air_drag * dt).exp();
+        self.position += self.velocity * dt;
+        self.keep_inside(bounds, physics)
+    }
+
+    // Pushes the ball back inside the bounds, turning its velocity away from any
+    // wall it touched and scaling that component by the restitution. Returns the
+    // inward normal of the wall(s) touched, None if the ball was already inside.
+    pub fn keep_inside(&mut self, bounds: Vec2, physics: &Physics) -> Option<Vec2> {
+        let min = Vec2::splat(self.radius);
+        let max = (bounds - self.radius).max(min);
+        let rest_speed = physics.rest_speed();
+        let bounce = |speed: f32| {
+            let speed = speed.abs() * physics.restitution;
+            if speed < rest_speed {
+                0.0
+            } else {
+                speed
+            }
+        };
+        let mut normal = Vec2::ZERO;
+        if self.position.x < min.x {
+            self.position.x = min.x;
+            self.velocity.x = bounce(self.velocity.x);
+            normal.x = 1.0;
+        } else if self.position.x > max.x {
+            self.position.x = max.x;
+            self.velocity.x = -bounce(self.velocity.x);
+            normal.x = -1.0;
+        }
+        if self.position.y < min.y {
+            self.position.y = min.y;
+            self.velocity.y = bounce(self.velocity.y);
+            normal.y = 1.0;
+        } else if self.position.y > max.y {
+            self.position.y = max.y;
+            self.velocity.y = -bounce(self.velocity.y);
+            normal.y = -1.0;
+        }
+        (normal != Vec2::ZERO).then(|| normal.normalize())
+    }
+}
+
+// Separates two overlapping balls and, if they were moving into each other,
+// exchanges momentum along the line between their centers. Mass goes with
+// area. `restitution` is the fraction of the closing speed kept. Returns the
+// closing speed of the impact, None if they didn't collide.
+pub fn collide(a: &mut Ball, b: &mut Ball, restitution: f32) -> Option<f32> {
+    let offset = b.position - a.position;
+    let distance = offset.length();
+    let overlap = a.radius + b.radius - distance;
+    if overlap <= 0.0 {
+        return None;
+    }
+    // Coincident centers have no contact direction; pick one
+    let normal = if distance > f32::EPSILON {
+        offset / distance
+    } else {
+        Vec2::X
+    };
+    let (mass_a, mass_b) = (a.radius * a.radius, b.radius * b.radius);
+    let total = mass_a + mass_b;
+    a.position -= normal * overlap * mass_b / total;
+    b.position += normal * overlap * mass_a / total;
+
+    let closing_speed = (a.velocity - b.velocity).dot(normal);
+    if closing_speed <= 0.0 {
+        return None;
+    }
+    let impulse = (1.0 + restitution) * closing_speed / (1.0 / mass_a + 1.0 / mass_b);
+    a.velocity -= normal * impulse / mass_a;
+    b.velocity += normal * impulse / mass_b;
+    Some(closing_speed)
+}
+
+// Runs `collide` on every pair of balls, calling `on_impact` with the closing
+// speed of each collision
+pub fn collide_all(balls: &mut [Ball], restitution: f32, mut on_impact: impl FnMut(f32)) {
+    for i in 1..balls.len() {
+        let (before, rest) = balls.split_at_mut(i);
+        let b = &mut rest[0];
+        for a in before.iter_mut() {
+            if let Some(speed) = collide(a, b, restitution) {
+                on_impact(speed);
+            }
+        }
+    }
+}
@@ -0,0 +1,1410 @@
+// Transcoding of Basis Universal ETC1S (BasisLZ) data in KTX2 files. The
+// shared codebooks in the supercompression global data are decoded once, then
+// every block of every level is rebuilt from its endpoint and selector and
+// re-encoded in a format the device samples: ETC2 when it has it, since ETC1S
+// blocks are valid ETC1 blocks and convert losslessly, otherwise BC7, then
+// ASTC 4x4.
+//
+// Only ETC1S is transcoded. UASTC, the other Basis Universal encoding, needs
+// the reference encoder's mode and partition tables to rebuild its blocks, and
+// `ktx` rejects it before it gets here.
+
+use ash::vk;
+
+use crate::gpu::Capabilities;
+
+// Bytes in the global data header and in each image description after it
+const GLOBAL_HEADER_LEN: usize = 20;
+const IMAGE_DESC_LEN: usize = 20;
+// Inter-frame blocks, only used by video
+const IMAGE_FLAG_P_FRAME: u32 = 0x02;
+
+// The Huffman tables' code length codes are sent in this order
+const CODE_LENGTH_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+const SMALL_ZERO_RUN: u32 = 17;
+const BIG_ZERO_RUN: u32 = 18;
+const SMALL_REPEAT: u32 = 19;
+const MAX_CODE_LEN: usize = 16;
+
+// Endpoint color deltas are coded with one of three tables, picked by the
+// previous endpoint's value
+const COLOR5_MODEL0_MAX: u8 = 9;
+const COLOR5_MODEL1_MAX: u8 = 21;
+// The endpoint predictor symbol that repeats the previous one
+const ENDPOINT_PRED_REPEAT: u32 = 256;
+const ENDPOINT_PRED_MIN_REPEAT: u32 = 3;
+// Selector history runs: the last run symbol is followed by a longer count
+const SELECTOR_RUN_LONG: u32 = 63;
+const SELECTOR_RUN_MIN: u32 = 3;
+
+// ETC1 intensity modifiers, ascending, as ETC1S selectors index them
+const ETC1_MODIFIERS: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+// ETC1's own pixel index for each ETC1S selector
+const ETC1_INDEX: [u8; 4] = [3, 2, 0, 1];
+
+// EAC alpha modifier tables, before scaling by the block's multiplier
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+// The table with a zero modifier, for blocks of one alpha value
+const EAC_FLAT_TABLE: usize = 13;
+const EAC_FLAT_INDEX: u64 = 4;
+
+// Interpolation weights out of 64, shared by BC7 and unquantized ASTC
+const WEIGHTS_2BIT: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3BIT: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4BIT: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// ASTC 4x4 weight grids: 3-bit weights for RGB, 2-bit weights for RGBA, whose
+// extra endpoint values leave less room
+const ASTC_MODE_RGB: u32 = 83;
+const ASTC_MODE_RGBA: u32 = 66;
+const ASTC_CEM_RGB: u32 = 8;
+const ASTC_CEM_RGBA: u32 = 12;
+
+// What ETC1S data becomes, in order of preference
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Etc2,
+    Bc7,
+    Astc,
+}
+
+impl Target {
+    fn choose(capabilities: &Capabilities) -> Option<Self> {
+        if capabilities.texture_compression_etc2 {
+            Some(Target::Etc2)
+        } else if capabilities.texture_compression_bc {
+            Some(Target::Bc7)
+        } else if capabilities.texture_compression_astc {
+            Some(Target::Astc)
+        } else {
+            None
+        }
+    }
+
+    fn format(self, alpha: bool, srgb: bool) -> vk::Format {
+        match (self, alpha, srgb) {
+            (Target::Etc2, false, false) => vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+            (Target::Etc2, false, true) => vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+            (Target::Etc2, true, false) => vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+            (Target::Etc2, true, true) => vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+            (Target::Bc7, _, false) => vk::Format::BC7_UNORM_BLOCK,
+            (Target::Bc7, _, true) => vk::Format::BC7_SRGB_BLOCK,
+            (Target::Astc, _, false) => vk::Format::ASTC_4X4_UNORM_BLOCK,
+            (Target::Astc, _, true) => vk::Format::ASTC_4X4_SRGB_BLOCK,
+        }
+    }
+}
+
+// Transcodes the ETC1S levels of `reader` for a device with `capabilities`,
+// returning the format picked and each level's blocks, largest first. `srgb`
+// picks the sRGB variant of that format.
+pub fn transcode(
+    reader: &ktx2::Reader<&[u8]>,
+    capabilities: &Capabilities,
+    srgb: bool,
+) -> Result<(vk::Format, Vec<Vec<u8>>), String> {
+    let target = Target::choose(capabilities)
+        .ok_or("the device has no BC, ETC2 or ASTC support to transcode to")?;
+    transcode_to(reader, target, srgb)
+}
+
+fn transcode_to(
+    reader: &ktx2::Reader<&[u8]>,
+    target: Target,
+    srgb: bool,
+) -> Result<(vk::Format, Vec<Vec<u8>>), String> {
+    let header = reader.header();
+    let global = reader.supercompression_global_data();
+    let level_count = reader.levels().len();
+    let codebook_start = IMAGE_DESC_LEN
+        .checked_mul(level_count)
+        .and_then(|len| len.checked_add(GLOBAL_HEADER_LEN))
+        .filter(|&start| start <= global.len())
+        .ok_or("truncated BasisLZ global data")?;
+    let endpoint_count = read_u16(global, 0) as usize;
+    let selector_count = read_u16(global, 2) as usize;
+    let truncated = || "truncated BasisLZ codebooks".to_string();
+    let endpoints = slice(global, codebook_start, read_u32(global, 4)).ok_or_else(truncated)?;
+    let selectors_start = codebook_start + endpoints.len();
+    let selectors = slice(global, selectors_start, read_u32(global, 8)).ok_or_else(truncated)?;
+    let tables_start = selectors_start + selectors.len();
+    let tables = slice(global, tables_start, read_u32(global, 12)).ok_or_else(truncated)?;
+    let codebooks = Codebooks {
+        endpoints: decode_endpoints(endpoints, endpoint_count)?,
+        selectors: decode_selectors(selectors, selector_count)?,
+        tables: Tables::decode(tables)?,
+    };
+
+    let images: Vec<ImageDesc> = (0..level_count)
+        .map(|level| ImageDesc::read(&global[GLOBAL_HEADER_LEN + IMAGE_DESC_LEN * level..]))
+        .collect();
+    let alpha = images.iter().any(|image| image.alpha_len > 0);
+    let mut levels = Vec::with_capacity(level_count);
+    for (i, (level, image)) in reader.levels().zip(&images).enumerate() {
+        if image.flags & IMAGE_FLAG_P_FRAME != 0 {
+            return Err("BasisLZ video frames are not supported".to_string());
+        }
+        let level_size = |size: u32| size.checked_shr(i as u32).unwrap_or(0).max(1);
+        let blocks_x = level_size(header.pixel_width).div_ceil(4) as usize;
+        let blocks_y = level_size(header.pixel_height).div_ceil(4) as usize;
+        let outside = || "BasisLZ slice is outside its level".to_string();
+        let rgb = codebooks.decode_slice(
+            slice(level.data, image.rgb_offset as usize, image.rgb_len).ok_or_else(outside)?,
+            blocks_x,
+            blocks_y,
+        )?;
+        let alpha_blocks = if image.alpha_len > 0 {
+            Some(
+                codebooks.decode_slice(
+                    slice(level.data, image.alpha_offset as usize, image.alpha_len)
+                        .ok_or_else(outside)?,
+                    blocks_x,
+                    blocks_y,
+                )?,
+            )
+        } else {
+            None
+        };
+        let mut data = Vec::with_capacity(rgb.len() * 16);
+        for (i, &(endpoint, selector)) in rgb.iter().enumerate() {
+            let color = codebooks.block(endpoint, selector);
+            let alpha_block = alpha_blocks
+                .as_ref()
+                .map(|blocks| codebooks.block(blocks[i].0, blocks[i].1));
+            let block = Block::new(color, alpha_block);
+            match target {
+                Target::Etc2 => {
+                    if alpha {
+                        data.extend_from_slice(&eac_block(&block.alpha));
+                    }
+                    let endpoint = &codebooks.endpoints[endpoint];
+                    data.extend_from_slice(&etc1_block(endpoint, &block.selectors));
+                }
+                Target::Bc7 if alpha => data.extend_from_slice(&bc7_mode5(&block)),
+                Target::Bc7 => data.extend_from_slice(&bc7_mode6(&block)),
+                Target::Astc if alpha => data.extend_from_slice(&astc_rgba(&block)),
+                Target::Astc => data.extend_from_slice(&astc_rgb(&block)),
+            }
+        }
+        levels.push(data);
+    }
+    Ok((target.format(alpha, srgb), levels))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+// The `len` bytes at `offset`, both read from the file, if they are all there
+fn slice(data: &[u8], offset: usize, len: u32) -> Option<&[u8]> {
+    data.get(offset..offset.checked_add(len as usize)?)
+}
+
+// Where one level's slices sit in its data
+struct ImageDesc {
+    flags: u32,
+    rgb_offset: u32,
+    rgb_len: u32,
+    alpha_offset: u32,
+    alpha_len: u32,
+}
+
+impl ImageDesc {
+    fn read(data: &[u8]) -> Self {
+        Self {
+            flags: read_u32(data, 0),
+            rgb_offset: read_u32(data, 4),
+            rgb_len: read_u32(data, 8),
+            alpha_offset: read_u32(data, 12),
+            alpha_len: read_u32(data, 16),
+        }
+    }
+}
+
+// Reads bits least significant first. Past the end it reads zeros, as the
+// reference decoder does.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn bits(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.data.get(self.position / 8).copied().unwrap_or(0);
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+            self.position += 1;
+        }
+        value
+    }
+
+    // A variable-length count in chunks of `chunk_bits`, each followed by a
+    // bit saying whether another chunk comes
+    fn vlc(&mut self, chunk_bits: u32) -> Result<u32, String> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = self.bits(chunk_bits + 1);
+            value |= (chunk & ((1 << chunk_bits) - 1)) << shift;
+            shift += chunk_bits;
+            if chunk >> chunk_bits == 0 {
+                return Ok(value);
+            }
+            if shift >= 32 {
+                return Err("BasisLZ count overflows".to_string());
+            }
+        }
+    }
+
+    fn huffman(&mut self, table: &Huffman) -> Result<u32, String> {
+        table.decode(self)
+    }
+
+    // A table is sent as code lengths, themselves Huffman coded with runs of
+    // zeros and repeats
+    fn huffman_table(&mut self) -> Result<Huffman, String> {
+        let symbol_count = self.bits(14) as usize;
+        if symbol_count == 0 {
+            return Ok(Huffman::default());
+        }
+        let code_length_count = self.bits(5) as usize;
+        if !(1..=CODE_LENGTH_ORDER.len()).contains(&code_length_count) {
+            return Err("bad BasisLZ Huffman table".to_string());
+        }
+        let mut code_length_lengths = [0u8; 21];
+        for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_length_lengths[symbol] = self.bits(3) as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_lengths);
+        let mut lengths = vec![0u8; symbol_count];
+        let mut i = 0;
+        while i < symbol_count {
+            let code = self.huffman(&code_lengths)?;
+            let (value, run) = match code {
+                0..=16 => (code as u8, 1),
+                SMALL_ZERO_RUN => (0, self.bits(3) as usize + 3),
+                BIG_ZERO_RUN => (0, self.bits(7) as usize + 11),
+                _ => {
+                    let run = if code == SMALL_REPEAT {
+                        self.bits(2) as usize + 3
+                    } else {
+                        self.bits(7) as usize + 7
+                    };
+                    match i.checked_sub(1).map(|previous| lengths[previous]) {
+                        Some(previous) if previous != 0 => (previous, run),
+                        _ => return Err("bad BasisLZ Huffman table".to_string()),
+                    }
+                }
+            };
+            if i + run > symbol_count {
+                return Err("bad BasisLZ Huffman table".to_string());
+            }
+            lengths[i..i + run].fill(value);
+            i += run;
+        }
+        Ok(Huffman::new(&lengths))
+    }
+}
+
+// A canonical Huffman code, decoded a bit at a time
+#[derive(Default)]
+struct Huffman {
+    // Codes of each length, and the symbols sorted by code
+    counts: [u16; MAX_CODE_LEN + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_CODE_LEN + 1];
+        for &length in lengths.iter().filter(|&&length| length != 0) {
+            counts[length as usize] += 1;
+        }
+        let mut offsets = [0usize; MAX_CODE_LEN + 1];
+        for length in 1..MAX_CODE_LEN {
+            offsets[length + 1] = offsets[length] + counts[length] as usize;
+        }
+        let mut symbols = vec![0; offsets[MAX_CODE_LEN] + counts[MAX_CODE_LEN] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize]] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u32, String> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1) as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize] as u32);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("bad BasisLZ Huffman code".to_string())
+    }
+}
+
+// A block's base color, in 5 bits per channel, and its ETC1 intensity table
+#[derive(Clone, Copy)]
+struct Endpoint {
+    color5: [u8; 3],
+    inten: u8,
+}
+
+impl Endpoint {
+    // The color each selector picks
+    fn palette(&self) -> [[u8; 3]; 4] {
+        let base = self.color5.map(|c| (c << 3 | c >> 2) as i32);
+        ETC1_MODIFIERS[self.inten as usize]
+            .map(|modifier| base.map(|c| (c + modifier).clamp(0, 255) as u8))
+    }
+}
+
+// Each endpoint is a delta from the previous one
+fn decode_endpoints(data: &[u8], count: usize) -> Result<Vec<Endpoint>, String> {
+    let mut reader = BitReader::new(data);
+    let color_models = [
+        reader.huffman_table()?,
+        reader.huffman_table()?,
+        reader.huffman_table()?,
+    ];
+    let inten_model = reader.huffman_table()?;
+    let grayscale = reader.bits(1) != 0;
+    let mut previous = Endpoint {
+        color5: [16; 3],
+        inten: 0,
+    };
+    let mut endpoints = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut endpoint = previous;
+        endpoint.inten = ((previous.inten as u32 + reader.huffman(&inten_model)?) & 7) as u8;
+        let channels = if grayscale { 1 } else { 3 };
+        for c in 0..channels {
+            let model = if previous.color5[c] <= COLOR5_MODEL0_MAX {
+                &color_models[0]
+            } else if previous.color5[c] <= COLOR5_MODEL1_MAX {
+                &color_models[1]
+            } else {
+                &color_models[2]
+            };
+            endpoint.color5[c] = ((previous.color5[c] as u32 + reader.huffman(model)?) & 31) as u8;
+        }
+        if grayscale {
+            endpoint.color5 = [endpoint.color5[0]; 3];
+        }
+        endpoints.push(endpoint);
+        previous = endpoint;
+    }
+    Ok(endpoints)
+}
+
+// Each selector is 16 2-bit values, row by row. They are sent raw or as XOR
+// deltas of each row byte from the previous selector's.
+fn decode_selectors(data: &[u8], count: usize) -> Result<Vec<[u8; 16]>, String> {
+    let mut reader = BitReader::new(data);
+    if reader.bits(1) != 0 || reader.bits(1) != 0 {
+        return Err("BasisLZ global selector codebooks are not supported".to_string());
+    }
+    let raw = reader.bits(1) != 0;
+    let delta_model = if raw {
+        Huffman::default()
+    } else {
+        reader.huffman_table()?
+    };
+    let mut previous = [0u8; 4];
+    let mut selectors = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut selector = [0u8; 16];
+        for (row, previous) in previous.iter_mut().enumerate() {
+            let byte = if raw || i == 0 {
+                reader.bits(8) as u8
+            } else {
+                reader.huffman(&delta_model)? as u8 ^ *previous
+            };
+            *previous = byte;
+            for x in 0..4 {
+                selector[row * 4 + x] = (byte >> (x * 2)) & 3;
+            }
+        }
+        selectors.push(selector);
+    }
+    Ok(selectors)
+}
+
+// The Huffman tables the slices are coded with
+struct Tables {
+    endpoint_pred: Huffman,
+    endpoint_delta: Huffman,
+    selector: Huffman,
+    selector_run: Huffman,
+    selector_history_len: usize,
+}
+
+impl Tables {
+    fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut reader = BitReader::new(data);
+        Ok(Self {
+            endpoint_pred: reader.huffman_table()?,
+            endpoint_delta: reader.huffman_table()?,
+            selector: reader.huffman_table()?,
+            selector_run: reader.huffman_table()?,
+            selector_history_len: reader.bits(13) as usize,
+        })
+    }
+}
+
+// Recently used selectors. A hit swaps the entry halfway towards the front;
+// new entries go in the back half.
+struct SelectorHistory {
+    entries: Vec<usize>,
+    rover: usize,
+}
+
+impl SelectorHistory {
+    fn new(len: usize) -> Self {
+        Self {
+            entries: vec![0; len],
+            rover: len / 2,
+        }
+    }
+
+    fn add(&mut self, selector: usize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.entries[self.rover] = selector;
+        self.rover += 1;
+        if self.rover == self.entries.len() {
+            self.rover = self.entries.len() / 2;
+        }
+    }
+
+    fn take(&mut self, index: usize) -> Result<usize, String> {
+        let selector = *self
+            .entries
+            .get(index)
+            .ok_or("BasisLZ selector history index out of range")?;
+        self.entries.swap(index / 2, index);
+        Ok(selector)
+    }
+}
+
+// What the slice decoder remembers of a block for the blocks below it
+#[derive(Clone, Copy, Default)]
+struct BlockPred {
+    endpoint: usize,
+    // Predictors for this block and its right neighbor in the next row
+    pred_bits: u32,
+}
+
+struct Codebooks {
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<[u8; 16]>,
+    tables: Tables,
+}
+
+impl Codebooks {
+    // The endpoint and selector index of each block of a slice, row by row.
+    // Endpoints are predicted from a neighbor, with the predictors for a 2x2
+    // group of blocks sent together; selectors come from the history buffer or
+    // directly.
+    fn decode_slice(
+        &self,
+        data: &[u8],
+        blocks_x: usize,
+        blocks_y: usize,
+    ) -> Result<Vec<(usize, usize)>, String> {
+        let bad = || "bad BasisLZ slice".to_string();
+        let tables = &self.tables;
+        let mut reader = BitReader::new(data);
+        let mut history = SelectorHistory::new(tables.selector_history_len);
+        let history_run_symbol = (self.selectors.len() + tables.selector_history_len) as u32;
+        let mut selector_run = 0;
+        let mut preds = [
+            vec![BlockPred::default(); blocks_x],
+            vec![BlockPred::default(); blocks_x],
+        ];
+        let mut pred_bits = 0;
+        let mut previous_pred = 0;
+        let mut pred_repeat = 0;
+        let mut previous_endpoint = 0;
+        let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+        for y in 0..blocks_y {
+            let row = y & 1;
+            for x in 0..blocks_x {
+                if x & 1 == 0 {
+                    if row == 0 {
+                        if pred_repeat > 0 {
+                            pred_repeat -= 1;
+                            pred_bits = previous_pred;
+                        } else {
+                            pred_bits = reader.huffman(&tables.endpoint_pred)?;
+                            if pred_bits == ENDPOINT_PRED_REPEAT {
+                                pred_repeat = reader.vlc(4)? + ENDPOINT_PRED_MIN_REPEAT - 1;
+                                pred_bits = previous_pred;
+                            } else {
+                                previous_pred = pred_bits;
+                            }
+                        }
+                        preds[row ^ 1][x].pred_bits = pred_bits >> 4;
+                    } else {
+                        pred_bits = preds[row][x].pred_bits;
+                    }
+                }
+                let pred = pred_bits & 3;
+                pred_bits >>= 2;
+                let endpoint = match pred {
+                    0 if x > 0 => previous_endpoint,
+                    1 if y > 0 => preds[row ^ 1][x].endpoint,
+                    2 if x > 0 && y > 0 => preds[row ^ 1][x - 1].endpoint,
+                    3 => {
+                        let endpoint =
+                            previous_endpoint + reader.huffman(&tables.endpoint_delta)? as usize;
+                        if endpoint >= self.endpoints.len() {
+                            endpoint - self.endpoints.len()
+                        } else {
+                            endpoint
+                        }
+                    }
+                    _ => return Err(bad()),
+                };
+                if endpoint >= self.endpoints.len() {
+                    return Err(bad());
+                }
+                preds[row][x].endpoint = endpoint;
+                previous_endpoint = endpoint;
+
+                let symbol = if selector_run > 0 {
+                    selector_run -= 1;
+                    self.selectors.len() as u32
+                } else {
+                    let symbol = reader.huffman(&tables.selector)?;
+                    if symbol == history_run_symbol {
+                        let run = reader.huffman(&tables.selector_run)?;
+                        selector_run = (if run == SELECTOR_RUN_LONG {
+                            reader.vlc(7)? + SELECTOR_RUN_MIN
+                        } else {
+                            run + SELECTOR_RUN_MIN
+                        }) as usize;
+                        if selector_run > blocks_x * blocks_y {
+                            return Err(bad());
+                        }
+                        selector_run -= 1;
+                        self.selectors.len() as u32
+                    } else {
+                        symbol
+                    }
+                };
+                let symbol = symbol as usize;
+                let selector = if symbol >= self.selectors.len() {
+                    history.take(symbol - self.selectors.len())?
+                } else {
+                    history.add(symbol);
+                    symbol
+                };
+                blocks.push((endpoint, selector));
+            }
+        }
+        Ok(blocks)
+    }
+
+    fn block(&self, endpoint: usize, selector: usize) -> ([[u8; 3]; 4], [u8; 16]) {
+        (self.endpoints[endpoint].palette(), self.selectors[selector])
+    }
+}
+
+// One decoded block: its pixels row by row, and the colors spanning them
+struct Block {
+    selectors: [u8; 16],
+    rgb: [[u8; 3]; 16],
+    alpha: [u8; 16],
+    rgb_ends: [[u8; 3]; 2],
+    alpha_ends: [u8; 2],
+}
+
+impl Block {
+    // Alpha is the green channel of its own ETC1S block, opaque without one
+    fn new(color: ([[u8; 3]; 4], [u8; 16]), alpha: Option<([[u8; 3]; 4], [u8; 16])>) -> Self {
+        let (palette, selectors) = color;
+        let (low, high) = selector_range(&selectors);
+        let (alpha, alpha_ends) = match alpha {
+            Some((alpha_palette, alpha_selectors)) => {
+                let (alpha_low, alpha_high) = selector_range(&alpha_selectors);
+                (
+                    alpha_selectors.map(|s| alpha_palette[s as usize][1]),
+                    [alpha_palette[alpha_low][1], alpha_palette[alpha_high][1]],
+                )
+            }
+            None => ([255; 16], [255; 2]),
+        };
+        Self {
+            selectors,
+            rgb: selectors.map(|s| palette[s as usize]),
+            alpha,
+            rgb_ends: [palette[low], palette[high]],
+            alpha_ends,
+        }
+    }
+}
+
+// The lowest and highest selector a block uses
+fn selector_range(selectors: &[u8; 16]) -> (usize, usize) {
+    let low = *selectors.iter().min().unwrap();
+    let high = *selectors.iter().max().unwrap();
+    (low as usize, high as usize)
+}
+
+fn interpolate(a: u8, b: u8, weight: u32) -> u8 {
+    ((a as u32 * (64 - weight) + b as u32 * weight + 32) >> 6) as u8
+}
+
+fn distance<const N: usize>(a: [u8; N], b: [u8; N]) -> u32 {
+    a.iter()
+        .zip(&b)
+        .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+// The index of the palette entry nearest each pixel
+fn nearest<const N: usize, const P: usize>(
+    pixels: &[[u8; N]; 16],
+    palette: &[[u8; N]; P],
+) -> [u8; 16] {
+    pixels.map(|pixel| (0..P).min_by_key(|&i| distance(pixel, palette[i])).unwrap() as u8)
+}
+
+// ETC1S blocks are differential ETC1 blocks with no delta and no flip, so the
+// conversion is exact. Pixel indices are stored column by column.
+fn etc1_block(endpoint: &Endpoint, selectors: &[u8; 16]) -> [u8; 8] {
+    let mut block = [0u8; 8];
+    for (byte, c) in block.iter_mut().zip(endpoint.color5) {
+        *byte = c << 3;
+    }
+    block[3] = endpoint.inten << 5 | endpoint.inten << 2 | 0b10;
+    let mut high = 0u16;
+    let mut low = 0u16;
+    for y in 0..4 {
+        for x in 0..4 {
+            let index = ETC1_INDEX[selectors[y * 4 + x] as usize] as u16;
+            let bit = x * 4 + y;
+            high |= (index >> 1) << bit;
+            low |= (index & 1) << bit;
+        }
+    }
+    block[4..6].copy_from_slice(&high.to_be_bytes());
+    block[6..8].copy_from_slice(&low.to_be_bytes());
+    block
+}
+
+// An EAC alpha block, searching the tables and multipliers near the one that
+// spans the block's range. An ETC1S block has at most four alpha values, so
+// only those are compared.
+fn eac_block(alpha: &[u8; 16]) -> [u8; 8] {
+    let mut values: Vec<i32> = alpha.iter().map(|&a| a as i32).collect();
+    values.sort_unstable();
+    values.dedup();
+    let (min, max) = (values[0], values[values.len() - 1]);
+    let mut best = (u32::MAX, min, 1, EAC_FLAT_TABLE);
+    if min != max {
+        for (table, modifiers) in EAC_MODIFIERS.iter().enumerate() {
+            let range = modifiers[7] - modifiers[3];
+            let estimate = ((max - min) as f32 / range as f32).round() as i32;
+            for multiplier in (estimate - 1).max(1)..=(estimate + 1).min(15) {
+                let center = (min + max) as f32 / 2.0;
+                let offset = (modifiers[3] + modifiers[7]) as f32 * multiplier as f32 / 2.0;
+                let base = (center - offset).round().clamp(0.0, 255.0) as i32;
+                let error: u32 = values
+                    .iter()
+                    .map(|&value| {
+                        modifiers
+                            .iter()
+                            .map(|&m| (value - (base + m * multiplier).clamp(0, 255)).pow(2) as u32)
+                            .min()
+                            .unwrap()
+                    })
+                    .sum();
+                if error < best.0 {
+                    best = (error, base, multiplier, table);
+                }
+            }
+        }
+    }
+    let (_, base, multiplier, table) = best;
+    let mut bits = (base as u64) << 56 | (multiplier as u64) << 52 | (table as u64) << 48;
+    for y in 0..4 {
+        for x in 0..4 {
+            let value = alpha[y * 4 + x] as i32;
+            let index = if min == max {
+                EAC_FLAT_INDEX
+            } else {
+                (0..8)
+                    .min_by_key(|&i| {
+                        (value - (base + EAC_MODIFIERS[table][i] * multiplier).clamp(0, 255)).abs()
+                    })
+                    .unwrap() as u64
+            };
+            bits |= index << (45 - 3 * (x * 4 + y));
+        }
+    }
+    bits.to_be_bytes()
+}
+
+// Bits of a 128-bit block, written from the least significant end
+struct BlockWriter {
+    bits: u128,
+    len: u32,
+}
+
+impl BlockWriter {
+    fn new() -> Self {
+        Self { bits: 0, len: 0 }
+    }
+
+    fn put(&mut self, value: u32, count: u32) {
+        self.bits |= (value as u128) << self.len;
+        self.len += count;
+    }
+}
+
+// A BC7 endpoint in 7 bits per channel plus a shared low bit, picking the low
+// bit that lands closest
+fn bc7_quantize_pbit(color: [u8; 4]) -> ([u8; 4], u8) {
+    (0..2u8)
+        .map(|pbit| {
+            let quantized = color.map(|c| ((c as i32 - pbit as i32 + 1) >> 1).clamp(0, 127) as u8);
+            (quantized, pbit)
+        })
+        .min_by_key(|&(quantized, pbit)| distance(quantized.map(|q| q << 1 | pbit), color))
+        .unwrap()
+}
+
+// BC7 mode 6: RGBA endpoints with 4-bit indices, for opaque textures
+fn bc7_mode6(block: &Block) -> [u8; 16] {
+    let opaque = |rgb: [u8; 3]| [rgb[0], rgb[1], rgb[2], 255];
+    let mut ends = [
+        bc7_quantize_pbit(opaque(block.rgb_ends[0])),
+        bc7_quantize_pbit(opaque(block.rgb_ends[1])),
+    ];
+    let expanded = ends.map(|(quantized, pbit)| quantized.map(|q| q << 1 | pbit));
+    let palette: [[u8; 4]; 16] = BC7_WEIGHTS_4BIT
+        .map(|weight| std::array::from_fn(|c| interpolate(expanded[0][c], expanded[1][c], weight)));
+    let mut indices = nearest(&block.rgb.map(opaque), &palette);
+    // The first pixel's index has its top bit dropped, so it must be below 8
+    if indices[0] >= 8 {
+        ends.swap(0, 1);
+        indices = indices.map(|i| 15 - i);
+    }
+    let mut writer = BlockWriter::new();
+    writer.put(1 << 6, 7);
+    for c in 0..4 {
+        writer.put(ends[0].0[c] as u32, 7);
+        writer.put(ends[1].0[c] as u32, 7);
+    }
+    writer.put(ends[0].1 as u32, 1);
+    writer.put(ends[1].1 as u32, 1);
+    for (i, &index) in indices.iter().enumerate() {
+        writer.put(index as u32, if i == 0 { 3 } else { 4 });
+    }
+    writer.bits.to_le_bytes()
+}
+
+// BC7 mode 5: 7-bit color and 8-bit alpha endpoints, each with its own 2-bit
+// indices, so alpha needn't follow color
+fn bc7_mode5(block: &Block) -> [u8; 16] {
+    let quantize = |c: u8| ((c as u32 * 127 + 127) / 255) as u8;
+    let mut color_ends = block.rgb_ends.map(|end| end.map(quantize));
+    let expanded = color_ends.map(|end| end.map(|q| q << 1 | q >> 6));
+    let color_palette: [[u8; 3]; 4] = WEIGHTS_2BIT
+        .map(|weight| std::array::from_fn(|c| interpolate(expanded[0][c], expanded[1][c], weight)));
+    let mut color_indices = nearest(&block.rgb, &color_palette);
+    if color_indices[0] >= 2 {
+        color_ends.swap(0, 1);
+        color_indices = color_indices.map(|i| 3 - i);
+    }
+    let mut alpha_ends = block.alpha_ends;
+    let alpha_palette =
+        WEIGHTS_2BIT.map(|weight| [interpolate(alpha_ends[0], alpha_ends[1], weight)]);
+    let mut alpha_indices = nearest(&block.alpha.map(|a| [a]), &alpha_palette);
+    if alpha_indices[0] >= 2 {
+        alpha_ends.swap(0, 1);
+        alpha_indices = alpha_indices.map(|i| 3 - i);
+    }
+    let mut writer = BlockWriter::new();
+    writer.put(1 << 5, 6);
+    // No channel rotation
+    writer.put(0, 2);
+    for (low, high) in color_ends[0].into_iter().zip(color_ends[1]) {
+        writer.put(low as u32, 7);
+        writer.put(high as u32, 7);
+    }
+    writer.put(alpha_ends[0] as u32, 8);
+    writer.put(alpha_ends[1] as u32, 8);
+    for indices in [color_indices, alpha_indices] {
+        for (i, &index) in indices.iter().enumerate() {
+            writer.put(index as u32, if i == 0 { 1 } else { 2 });
+        }
+    }
+    writer.bits.to_le_bytes()
+}
+
+// A single-partition ASTC 4x4 block with 8-bit endpoints. Weights fill the
+// block from the top bit down.
+fn astc_block<const N: usize>(
+    mode: u32,
+    cem: u32,
+    mut ends: [[u8; N]; 2],
+    pixels: &[[u8; N]; 16],
+    weights: &[u32],
+) -> [u8; 16] {
+    // A second endpoint darker than the first would be decoded with blue
+    // contraction
+    let sum = |end: &[u8; N]| end[..3].iter().map(|&c| c as u32).sum::<u32>();
+    if sum(&ends[1]) < sum(&ends[0]) {
+        ends.swap(0, 1);
+    }
+    let palette: Vec<[u8; N]> = weights
+        .iter()
+        .map(|&weight| std::array::from_fn(|c| interpolate(ends[0][c], ends[1][c], weight)))
+        .collect();
+    let mut writer = BlockWriter::new();
+    writer.put(mode, 11);
+    // One partition
+    writer.put(0, 2);
+    writer.put(cem, 4);
+    for (low, high) in ends[0].into_iter().zip(ends[1]) {
+        writer.put(low as u32, 8);
+        writer.put(high as u32, 8);
+    }
+    let weight_bits = weights.len().trailing_zeros();
+    for (i, pixel) in pixels.iter().enumerate() {
+        let index = (0..palette.len())
+            .min_by_key(|&w| distance(*pixel, palette[w]))
+            .unwrap() as u32;
+        for bit in 0..weight_bits {
+            let position = 127 - (i as u32 * weight_bits + bit);
+            writer.bits |= ((index >> bit & 1) as u128) << position;
+        }
+    }
+    writer.bits.to_le_bytes()
+}
+
+fn astc_rgb(block: &Block) -> [u8; 16] {
+    astc_block(
+        ASTC_MODE_RGB,
+        ASTC_CEM_RGB,
+        block.rgb_ends,
+        &block.rgb,
+        &WEIGHTS_3BIT,
+    )
+}
+
+// Color and alpha share weights, so alpha is paired with the color ends
+// whichever way round fits the block better
+fn astc_rgba(block: &Block) -> [u8; 16] {
+    let pixels: [[u8; 4]; 16] = std::array::from_fn(|i| {
+        [
+            block.rgb[i][0],
+            block.rgb[i][1],
+            block.rgb[i][2],
+            block.alpha[i],
+        ]
+    });
+    let ends = |alpha: [u8; 2]| -> [[u8; 4]; 2] {
+        std::array::from_fn(|e| {
+            let [r, g, b] = block.rgb_ends[e];
+            [r, g, b, alpha[e]]
+        })
+    };
+    let [low, high] = block.alpha_ends;
+    let error = |ends: [[u8; 4]; 2]| -> u32 {
+        let palette = WEIGHTS_2BIT
+            .map(|weight| std::array::from_fn(|c| interpolate(ends[0][c], ends[1][c], weight)));
+        pixels
+            .iter()
+            .map(|&pixel| {
+                palette
+                    .iter()
+                    .map(|&entry| distance(pixel, entry))
+                    .min()
+                    .unwrap()
+            })
+            .sum()
+    };
+    let ends = [ends([low, high]), ends([high, low])]
+        .into_iter()
+        .min_by_key(|&ends| error(ends))
+        .unwrap();
+    astc_block(ASTC_MODE_RGBA, ASTC_CEM_RGBA, ends, &pixels, &WEIGHTS_2BIT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An 8x4 sRGB ETC1S texture with one level, built by hand: two endpoints,
+    // (20, 10, 5) with intensity table 2 and (28, 28, 4) with table 5, two raw
+    // selectors, and a slice giving the left block the first of each and the
+    // right block the second
+    const FIXTURE: &[u8] = include_bytes!("../tests/fixtures/etc1s_8x4.ktx2");
+    const FIXTURE_ETC2: [u8; 16] = [
+        0xA0, 0x50, 0x28, 0x4A, 0x66, 0x77, 0xBA, 0xAB, //
+        0xE0, 0xE0, 0x20, 0xB6, 0x55, 0xCC, 0xD4, 0x4D,
+    ];
+
+    // Writes bits least significant first, as `BitReader` reads them
+    #[derive(Default)]
+    struct BitWriter {
+        bits: Vec<bool>,
+    }
+
+    impl BitWriter {
+        fn put(&mut self, value: u32, count: u32) {
+            self.bits.extend((0..count).map(|i| value >> i & 1 != 0));
+        }
+
+        // A Huffman code, whose first bit is its most significant
+        fn code(&mut self, code: u32, len: u32) {
+            self.bits.extend((0..len).rev().map(|i| code >> i & 1 != 0));
+        }
+
+        // A table of the given code lengths, each sent as a 4-bit code: the
+        // code length code gives lengths 0 to 15 four bits each
+        fn table(&mut self, lengths: &[u8]) {
+            self.put(lengths.len() as u32, 14);
+            self.put(20, 5);
+            for &symbol in &CODE_LENGTH_ORDER[..20] {
+                self.put(if symbol < 16 { 4 } else { 0 }, 3);
+            }
+            for &length in lengths {
+                self.code(length as u32, 4);
+            }
+        }
+
+        // A table where every symbol below 2^bits is coded as itself
+        fn fixed(&mut self, bits: u8) {
+            self.table(&vec![bits; 1 << bits]);
+        }
+
+        fn bytes(&self) -> Vec<u8> {
+            let mut bytes = vec![0u8; self.bits.len().div_ceil(8)];
+            for (i, &bit) in self.bits.iter().enumerate() {
+                bytes[i / 8] |= (bit as u8) << (i % 8);
+            }
+            bytes
+        }
+    }
+
+    fn solid(color: [u8; 3], alpha: Option<u8>) -> Block {
+        Block::new(
+            ([color; 4], [0; 16]),
+            alpha.map(|a| ([[0, a, 0]; 4], [0; 16])),
+        )
+    }
+
+    // Black with a white first pixel
+    fn black_and_white() -> Block {
+        let mut selectors = [0; 16];
+        selectors[0] = 3;
+        Block::new(([[0; 3], [0; 3], [255; 3], [255; 3]], selectors), None)
+    }
+
+    #[test]
+    fn huffman_codes_are_canonical() {
+        // Lengths 2, 1, 3, 3 give the codes 10, 0, 110 and 111
+        let table = Huffman::new(&[2, 1, 3, 3]);
+        let mut reader = BitReader::new(&[0xF9, 0x00]);
+        let symbols: Vec<u32> = (0..4).map(|_| table.decode(&mut reader).unwrap()).collect();
+        assert_eq!(symbols, [0, 1, 3, 2]);
+    }
+
+    #[test]
+    fn huffman_table_expands_runs_and_repeats() {
+        // Code length codes: 3 is 0, a big zero run 10, a small repeat 11
+        let mut writer = BitWriter::default();
+        writer.put(28, 14);
+        writer.put(15, 5);
+        for &symbol in &CODE_LENGTH_ORDER[..15] {
+            let length = match symbol {
+                3 => 1,
+                18 | 19 => 2,
+                _ => 0,
+            };
+            writer.put(length, 3);
+        }
+        // Five 3s, twenty 0s, three 3s
+        writer.code(0, 1);
+        writer.code(0b11, 2);
+        writer.put(1, 2);
+        writer.code(0b10, 2);
+        writer.put(9, 7);
+        for _ in 0..3 {
+            writer.code(0, 1);
+        }
+        let table = BitReader::new(&writer.bytes()).huffman_table().unwrap();
+        assert_eq!(table.counts[3], 8);
+        assert_eq!(table.symbols, [0, 1, 2, 3, 4, 25, 26, 27]);
+    }
+
+    #[test]
+    fn huffman_table_rejects_a_leading_repeat() {
+        let mut writer = BitWriter::default();
+        writer.put(4, 14);
+        writer.put(3, 5);
+        // Only the small repeat code, 19, has a length
+        writer.put(0, 3);
+        writer.put(0, 3);
+        writer.put(1, 3);
+        writer.code(0, 1);
+        writer.put(0, 2);
+        assert!(BitReader::new(&writer.bytes()).huffman_table().is_err());
+    }
+
+    #[test]
+    fn vlc_reads_chunks_until_the_stop_bit() {
+        // 5 then 3, in 4-bit chunks: 0b11_0101
+        let mut writer = BitWriter::default();
+        writer.put(0b1_0101, 5);
+        writer.put(0b0_0011, 5);
+        assert_eq!(BitReader::new(&writer.bytes()).vlc(4), Ok(0x35));
+    }
+
+    #[test]
+    fn endpoints_are_deltas_coded_by_the_previous_value() {
+        // Each color model codes one set of values, so using the wrong one
+        // misreads the stream: model 0 only 31, model 1 anything in 5 bits,
+        // model 2 only 4
+        let mut writer = BitWriter::default();
+        let mut model0 = [0; 32];
+        model0[31] = 1;
+        writer.table(&model0);
+        writer.fixed(5);
+        writer.table(&[0, 0, 0, 0, 1]);
+        writer.fixed(3);
+        writer.put(0, 1);
+        // From (16, 16, 16): +4, -6, -11 to (20, 10, 5), table 0 + 2
+        writer.code(2, 3);
+        for delta in [4, 26, 21] {
+            writer.code(delta, 5);
+        }
+        // From (20, 10, 5): +8, +18, -1 to (28, 28, 4), table 2 + 3
+        writer.code(3, 3);
+        writer.code(8, 5);
+        writer.code(18, 5);
+        writer.code(0, 1);
+        // From (28, 28, 4): +4, +4, -1 wrapping to (0, 0, 3), table 5 + 3
+        writer.code(3, 3);
+        writer.code(0, 1);
+        writer.code(0, 1);
+        writer.code(0, 1);
+
+        let endpoints = decode_endpoints(&writer.bytes(), 3).unwrap();
+        let decoded: Vec<([u8; 3], u8)> = endpoints.iter().map(|e| (e.color5, e.inten)).collect();
+        assert_eq!(
+            decoded,
+            [([20, 10, 5], 2), ([28, 28, 4], 5), ([0, 0, 3], 0)]
+        );
+    }
+
+    #[test]
+    fn grayscale_endpoints_copy_the_first_channel() {
+        let mut writer = BitWriter::default();
+        for _ in 0..3 {
+            writer.fixed(5);
+        }
+        writer.fixed(3);
+        writer.put(1, 1);
+        writer.code(1, 3);
+        writer.code(7, 5);
+        let endpoints = decode_endpoints(&writer.bytes(), 1).unwrap();
+        assert_eq!((endpoints[0].color5, endpoints[0].inten), ([23; 3], 1));
+    }
+
+    #[test]
+    fn endpoint_palette_applies_the_intensity_table() {
+        let endpoint = Endpoint {
+            color5: [20, 10, 5],
+            inten: 2,
+        };
+        assert_eq!(
+            endpoint.palette(),
+            [[136, 53, 12], [156, 73, 32], [174, 91, 50], [194, 111, 70]]
+        );
+    }
+
+    #[test]
+    fn raw_selectors_are_read_row_by_row() {
+        let mut writer = BitWriter::default();
+        writer.put(0b100, 3);
+        for byte in [0xE4, 0x00, 0x55, 0xFF] {
+            writer.put(byte, 8);
+        }
+        let selectors = decode_selectors(&writer.bytes(), 1).unwrap();
+        assert_eq!(
+            selectors[0],
+            [0, 1, 2, 3, 0, 0, 0, 0, 1, 1, 1, 1, 3, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn selectors_after_the_first_are_xor_deltas() {
+        let mut writer = BitWriter::default();
+        writer.put(0b000, 3);
+        writer.fixed(8);
+        for byte in [0xE4, 0x00, 0x55, 0xFF] {
+            writer.put(byte, 8);
+        }
+        for delta in [0xFF, 0x00, 0x0F, 0x1B] {
+            writer.code(delta, 8);
+        }
+        let selectors = decode_selectors(&writer.bytes(), 2).unwrap();
+        // Rows 0x1B, 0x00, 0x5A and 0xE4
+        assert_eq!(
+            selectors[1],
+            [3, 2, 1, 0, 0, 0, 0, 0, 2, 2, 1, 1, 0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn global_selector_codebooks_are_rejected() {
+        assert!(decode_selectors(&[0b1], 1).is_err());
+    }
+
+    #[test]
+    fn slices_predict_endpoints_and_reuse_selectors() {
+        let mut writer = BitWriter::default();
+        writer.fixed(8);
+        writer.fixed(2);
+        writer.fixed(3);
+        writer.fixed(6);
+        writer.put(4, 13);
+        let tables = Tables::decode(&writer.bytes()).unwrap();
+        let codebooks = Codebooks {
+            endpoints: vec![
+                Endpoint {
+                    color5: [0; 3],
+                    inten: 0,
+                };
+                3
+            ],
+            selectors: vec![[0; 16]; 2],
+            tables,
+        };
+
+        // Predictors, two bits per block of a 2x2 group: 0 left, 1 upper,
+        // 2 upper left, 3 a delta from the previous block. Selector symbols
+        // 0 and 1 are selectors, 2 to 5 the history and 6 a history run.
+        let mut writer = BitWriter::default();
+        // Deltas from 0 and from 2, wrapping to 1; deltas for the group below
+        // from 1 and then upper left
+        writer.code(0b10_11_11_11, 8);
+        writer.code(2, 2);
+        writer.code(1, 3);
+        writer.code(2, 2);
+        writer.code(0, 3);
+        // Left then a delta wrapping to 0; upper then left below
+        writer.code(0b00_01_11_00, 8);
+        writer.code(4, 3);
+        writer.code(2, 2);
+        // A run of three of the history's front entry
+        writer.code(6, 3);
+        writer.code(0, 6);
+        // The second row, with the run covering its first two blocks
+        writer.code(0, 2);
+        writer.code(3, 3);
+        writer.code(2, 3);
+
+        let blocks = codebooks.decode_slice(&writer.bytes(), 4, 2).unwrap();
+        let (endpoints, selectors): (Vec<usize>, Vec<usize>) = blocks.into_iter().unzip();
+        assert_eq!(endpoints, [2, 1, 1, 0, 0, 2, 1, 1]);
+        assert_eq!(selectors, [1, 0, 1, 0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn slices_reject_a_left_prediction_in_the_first_column() {
+        let mut writer = BitWriter::default();
+        writer.fixed(8);
+        writer.fixed(2);
+        writer.fixed(3);
+        writer.fixed(6);
+        writer.put(4, 13);
+        let codebooks = Codebooks {
+            endpoints: vec![
+                Endpoint {
+                    color5: [0; 3],
+                    inten: 0,
+                };
+                2
+            ],
+            selectors: vec![[0; 16]; 2],
+            tables: Tables::decode(&writer.bytes()).unwrap(),
+        };
+        assert!(codebooks.decode_slice(&[0x00, 0x00], 1, 1).is_err());
+    }
+
+    #[test]
+    fn etc1_blocks_keep_the_endpoint_and_reorder_indices() {
+        let endpoint = Endpoint {
+            color5: [31, 0, 16],
+            inten: 3,
+        };
+        let mut selectors = [0; 16];
+        selectors[..4].copy_from_slice(&[0, 1, 2, 3]);
+        assert_eq!(
+            etc1_block(&endpoint, &selectors),
+            [0xF8, 0x00, 0x80, 0x6E, 0xEE, 0xFF, 0xFE, 0xEF]
+        );
+    }
+
+    #[test]
+    fn eac_blocks_of_one_value_use_the_zero_modifier() {
+        assert_eq!(
+            eac_block(&[200; 16]),
+            [0xC8, 0x1D, 0x92, 0x49, 0x24, 0x92, 0x49, 0x24]
+        );
+    }
+
+    #[test]
+    fn eac_blocks_reach_both_extremes() {
+        let alpha: [u8; 16] = std::array::from_fn(|i| if i % 3 == 0 { 255 } else { 0 });
+        let bits = u64::from_be_bytes(eac_block(&alpha));
+        let base = (bits >> 56) as i32;
+        let multiplier = (bits >> 52 & 0xF) as i32;
+        let table = (bits >> 48 & 0xF) as usize;
+        let decoded: [u8; 16] = std::array::from_fn(|i| {
+            let (x, y) = (i % 4, i / 4);
+            let index = (bits >> (45 - 3 * (x * 4 + y)) & 7) as usize;
+            (base + EAC_MODIFIERS[table][index] * multiplier).clamp(0, 255) as u8
+        });
+        assert_eq!(decoded, alpha);
+    }
+
+    #[test]
+    fn bc7_mode6_solid_block() {
+        assert_eq!(
+            bc7_mode6(&solid([128, 64, 32], None)),
+            [0x40, 0x20, 0x10, 0x04, 0x82, 0x40, 0xFE, 0x7F, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn bc7_mode6_swaps_ends_for_the_first_index() {
+        // White, with the low bit set, ends up first so the first pixel's
+        // index is 0
+        let mut expected = [0xFF; 16];
+        expected[..9].copy_from_slice(&[0xC0, 0x3F, 0xE0, 0x0F, 0xF8, 0x03, 0xFE, 0xFF, 0xF0]);
+        assert_eq!(bc7_mode6(&black_and_white()), expected);
+    }
+
+    #[test]
+    fn bc7_mode5_solid_block() {
+        assert_eq!(
+            bc7_mode5(&solid([128, 64, 32], Some(200))),
+            [0x20, 0x40, 0x20, 0x08, 0x04, 0x81, 0x20, 0x23, 0x03, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn astc_rgb_solid_block() {
+        assert_eq!(
+            astc_rgb(&solid([128, 64, 32], None)),
+            [0x53, 0x00, 0x01, 0x01, 0x81, 0x80, 0x40, 0x40, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn astc_weights_fill_from_the_top_bit() {
+        // The first pixel's weight, 7, is in bits 125 to 127
+        assert_eq!(
+            astc_rgb(&black_and_white()),
+            [0x53, 0x00, 0x01, 0xFE, 0x01, 0xFE, 0x01, 0xFE, 0x01, 0, 0, 0, 0, 0, 0, 0xE0]
+        );
+    }
+
+    #[test]
+    fn astc_rgba_solid_block() {
+        assert_eq!(
+            astc_rgba(&solid([128, 64, 32], Some(200))),
+            [0x42, 0x80, 0x01, 0x01, 0x81, 0x80, 0x40, 0x40, 0x90, 0x91, 0x01, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn fixture_transcodes_to_each_target() {
+        let reader = ktx2::Reader::new(FIXTURE).unwrap();
+        let (format, levels) = transcode_to(&reader, Target::Etc2, true).unwrap();
+        assert_eq!(format, vk::Format::ETC2_R8G8B8_SRGB_BLOCK);
+        assert_eq!(levels, [FIXTURE_ETC2.to_vec()]);
+
+        for (target, format) in [
+            (Target::Bc7, vk::Format::BC7_SRGB_BLOCK),
+            (Target::Astc, vk::Format::ASTC_4X4_SRGB_BLOCK),
+        ] {
+            let (transcoded, levels) = transcode_to(&reader, target, true).unwrap();
+            assert_eq!(transcoded, format);
+            assert_eq!(levels.len(), 1);
+            assert_eq!(levels[0].len(), 32);
+        }
+    }
+
+    #[test]
+    fn offsets_past_u32_are_rejected() {
+        let sgd_offset = ktx2::Reader::new(FIXTURE)
+            .unwrap()
+            .header()
+            .index
+            .sgd_byte_offset as usize;
+        // The first image's RGB slice offset, then the endpoint codebook length
+        for field in [GLOBAL_HEADER_LEN + 4, 4] {
+            let mut bytes = FIXTURE.to_vec();
+            let at = sgd_offset + field;
+            bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            let reader = ktx2::Reader::new(bytes.as_slice()).unwrap();
+            assert!(transcode_to(&reader, Target::Etc2, false).is_err());
+        }
+    }
+}
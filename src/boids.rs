@@ -0,0 +1,226 @@
+use glam::Vec2;
+
+use crate::rng::Rng;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Boid {
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+// Steering weights and limits for the three classic flocking rules
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlockParams {
+    // Neighbours closer than this are aligned with and steered towards
+    pub view_radius: f32,
+    // Neighbours closer than this push each other apart
+    pub separation_radius: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+}
+
+impl Default for FlockParams {
+    fn default() -> Self {
+        Self {
+            view_radius: 40.0,
+            separation_radius: 16.0,
+            separation: 400.0,
+            alignment: 2.0,
+            cohesion: 1.0,
+            min_speed: 60.0,
+            max_speed: 160.0,
+        }
+    }
+}
+
+// Boids flocking on the CPU in a world that wraps at the edges. Neighbours are
+// found through a uniform grid with `view_radius` sized cells, rebuilt every
+// step, so each boid only looks at the 3x3 cells around it.
+pub struct Flock {
+    pub params: FlockParams,
+    // Boids within `scatter_radius` of this point flee from it
+    pub scatter: Option<Vec2>,
+    pub scatter_radius: f32,
+    boids: Vec<Boid>,
+    // Grid columns and rows of the last step
+    grid_size: (usize, usize),
+    // Boid indices sorted by cell; cell i owns cell_boids[cell_start[i]..cell_start[i + 1]]
+    cell_start: Vec<usize>,
+    cell_boids: Vec<usize>,
+    accelerations: Vec<Vec2>,
+}
+
+impl Flock {
+    pub fn new() -> Self {
+        Self {
+            params: FlockParams::default(),
+            scatter: None,
+            scatter_radius: 120.0,
+            boids: Vec::new(),
+            grid_size: (0, 0),
+            cell_start: Vec::new(),
+            cell_boids: Vec::new(),
+            accelerations: Vec::new(),
+        }
+    }
+
+    pub fn boids(&self) -> &[Boid] {
+        &self.boids
+    }
+
+    pub fn len(&self) -> usize {
+        self.boids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boids.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.boids.clear();
+    }
+
+    // Adds `count` boids at random positions inside `bounds`, heading in random directions
+    pub fn spawn(&mut self, rng: &mut Rng, bounds: Vec2, count: usize) {
+        let speed = (self.params.min_speed + self.params.max_speed) * 0.5;
+        self.boids.extend((0..count).map(|_| Boid {
+            position: Vec2::new(rng.range(0.0, bounds.x), rng.range(0.0, bounds.y)),
+            velocity: Vec2::from_angle(rng.range(0.0, std::f32::consts::TAU)) * speed,
+        }));
+    }
+
+    // Shortest offset from `from` to `to` when the world wraps at `bounds`
+    fn wrapped_offset(from: Vec2, to: Vec2, bounds: Vec2) -> Vec2 {
+        let offset = to - from;
+        offset - (offset / bounds).round() * bounds
+    }
+
+    fn cell_of(&self, position: Vec2) -> (usize, usize) {
+        let cell = self.params.view_radius;
+        let (columns, rows) = self.grid_size;
+        (
+            ((position.x / cell) as usize).min(columns - 1),
+            ((position.y / cell) as usize).min(rows - 1),
+        )
+    }
+
+    // Counting sort of boid indices into grid cells
+    fn rebuild_grid(&mut self, bounds: Vec2) {
+        let columns = ((bounds.x / self.params.view_radius).ceil() as usize).max(1);
+        let rows = ((bounds.y / self.params.view_radius).ceil() as usize).max(1);
+        self.grid_size = (columns, rows);
+        self.cell_start.clear();
+        self.cell_start.resize(columns * rows + 1, 0);
+        for boid in &self.boids {
+            let (column, row) = self.cell_of(boid.position);
+            self.cell_start[row * columns + column + 1] += 1;
+        }
+        for i in 1..self.cell_start.len() {
+            self.cell_start[i] += self.cell_start[i - 1];
+        }
+        let mut next = self.cell_start.clone();
+        self.cell_boids.resize(self.boids.len(), 0);
+        for (index, boid) in self.boids.iter().enumerate() {
+            let (column, row) = self.cell_of(boid.position);
+            let slot = &mut next[row * columns + column];
+            self.cell_boids[*slot] = index;
+            *slot += 1;
+        }
+    }
+
+    // Separation, alignment and cohesion from the neighbours of one boid
+    fn steering(&self, index: usize, bounds: Vec2) -> Vec2 {
+        let params = &self.params;
+        let boid = self.boids[index];
+        let (columns, rows) = self.grid_size;
+        let (column, row) = self.cell_of(boid.position);
+
+        let mut separation = Vec2::ZERO;
+        let mut velocity_sum = Vec2::ZERO;
+        let mut offset_sum = Vec2::ZERO;
+        let mut neighbours = 0;
+        let view_radius_squared = params.view_radius * params.view_radius;
+        // The 3x3 block of cells around this one, wrapped. Grids narrower than
+        // three cells would otherwise visit the same cell twice.
+        let mut cells = [0; 9];
+        for (slot, cell) in cells.iter_mut().enumerate() {
+            let neighbour_column = (column + columns + slot % 3 - 1) % columns;
+            let neighbour_row = (row + rows + slot / 3 - 1) % rows;
+            *cell = neighbour_row * columns + neighbour_column;
+        }
+        cells.sort_unstable();
+        for (slot, &cell) in cells.iter().enumerate() {
+            if slot > 0 && cells[slot - 1] == cell {
+                continue;
+            }
+            for &other in &self.cell_boids[self.cell_start[cell]..self.cell_start[cell + 1]] {
+                if other == index {
+                    continue;
+                }
+                let other = self.boids[other];
+                let offset = Self::wrapped_offset(boid.position, other.position, bounds);
+                let distance_squared = offset.length_squared();
+                if distance_squared >= view_radius_squared || distance_squared <= f32::EPSILON {
+                    continue;
+                }
+                let distance = distance_squared.sqrt();
+                if distance < params.separation_radius {
+                    separation -= offset / distance * (1.0 - distance / params.separation_radius);
+                }
+                velocity_sum += other.velocity;
+                offset_sum += offset;
+                neighbours += 1;
+            }
+        }
+
+        let mut acceleration = separation * params.separation;
+        if neighbours > 0 {
+            let count = neighbours as f32;
+            acceleration += (velocity_sum / count - boid.velocity) * params.alignment;
+            acceleration += offset_sum / count * params.cohesion;
+        }
+        if let Some(scatter) = self.scatter {
+            let away = Self::wrapped_offset(scatter, boid.position, bounds);
+            let distance = away.length();
+            if distance < self.scatter_radius && distance > f32::EPSILON {
+                acceleration += away / distance
+                    * params.separation
+                    * 4.0
+                    * (1.0 - distance / self.scatter_radius);
+            }
+        }
+        acceleration
+    }
+
+    // Advances the flock by one fixed step. Steering is computed for every boid
+    // before any of them move, so update order doesn't matter.
+    pub fn update(&mut self, dt: f32, bounds: Vec2) {
+        if self.boids.is_empty() || bounds.min_element() <= 0.0 {
+            return;
+        }
+        self.rebuild_grid(bounds);
+        let mut accelerations = std::mem::take(&mut self.accelerations);
+        accelerations.clear();
+        accelerations.extend((0..self.boids.len()).map(|index| self.steering(index, bounds)));
+        self.accelerations = accelerations;
+
+        let (min_speed, max_speed) = (self.params.min_speed, self.params.max_speed);
+        for (boid, &acceleration) in self.boids.iter_mut().zip(&self.accelerations) {
+            boid.velocity += acceleration * dt;
+            let speed = boid.velocity.length();
+            if speed > f32::EPSILON {
+                boid.velocity *= speed.clamp(min_speed, max_speed) / speed;
+            }
+            boid.position = (boid.position + boid.velocity * dt).rem_euclid(bounds);
+        }
+    }
+}
+
+impl Default for Flock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
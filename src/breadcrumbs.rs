@@ -0,0 +1,205 @@
+use ash::vk;
+use std::ffi::c_void;
+
+use crate::gpu::GpuContext;
+
+// Bits of a marker below the frame number, holding the breadcrumb
+const BREADCRUMB_BITS: u32 = 3;
+// The marker buffer's slots: last breadcrumb the GPU started past, and last one
+// it finished everything before
+const STARTED_SLOT: vk::DeviceSize = 0;
+const FINISHED_SLOT: vk::DeviceSize = 4;
+
+// Points in a frame where the renderer leaves a breadcrumb, in recording order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Breadcrumb {
+    FrameStart,
+    Compute,
+    ScenePass,
+    Meshes,
+    PostProcessing,
+    FrameEnd,
+}
+
+const BREADCRUMBS: [Breadcrumb; 6] = [
+    Breadcrumb::FrameStart,
+    Breadcrumb::Compute,
+    Breadcrumb::ScenePass,
+    Breadcrumb::Meshes,
+    Breadcrumb::PostProcessing,
+    Breadcrumb::FrameEnd,
+];
+
+impl Breadcrumb {
+    pub fn name(self) -> &'static str {
+        match self {
+            Breadcrumb::FrameStart => "frame start",
+            Breadcrumb::Compute => "compute",
+            Breadcrumb::ScenePass => "scene pass",
+            Breadcrumb::Meshes => "meshes",
+            Breadcrumb::PostProcessing => "post-processing",
+            Breadcrumb::FrameEnd => "frame end",
+        }
+    }
+
+    // The frame number (which starts at 1) above the breadcrumb, so zero means
+    // nothing was written. The frame number wraps after 2^29 frames.
+    fn marker(self, frame: u64) -> u32 {
+        ((frame as u32) << BREADCRUMB_BITS) | self as u32
+    }
+
+    fn describe(marker: u32) -> String {
+        if marker == 0 {
+            return "none".to_string();
+        }
+        let name = BREADCRUMBS
+            .get((marker & ((1 << BREADCRUMB_BITS) - 1)) as usize)
+            .map_or("unknown", |breadcrumb| breadcrumb.name());
+        format!("{} of frame {}", name, marker >> BREADCRUMB_BITS)
+    }
+}
+
+// GPU crash breadcrumbs, turned on with `GpuContext::enable_breadcrumbs`. With
+// VK_NV_device_diagnostic_checkpoints each breadcrumb is a checkpoint, and after
+// a crash the driver reports the last one each pipeline stage reached. With
+// VK_AMD_buffer_marker each is written into a host-visible buffer twice: at the
+// top of the pipe once the commands before it have started, and at the bottom
+// once they have finished.
+pub struct Breadcrumbs {
+    checkpoints: Option<ash::nv::device_diagnostic_checkpoints::Device>,
+    markers: Option<MarkerBuffer>,
+}
+
+struct MarkerBuffer {
+    extension: ash::amd::buffer_marker::Device,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    // Host coherent and mapped for the buffer's lifetime
+    mapped: *const u32,
+}
+
+// The mapping is only read, after the device is lost, by whichever thread
+// noticed
+unsafe impl Send for Breadcrumbs {}
+unsafe impl Sync for Breadcrumbs {}
+
+impl Breadcrumbs {
+    // None without `capabilities.diagnostic_checkpoints` or
+    // `capabilities.buffer_marker`. Checkpoints win when there are both.
+    pub fn new(gpu: &GpuContext) -> Option<Self> {
+        if gpu.capabilities.diagnostic_checkpoints {
+            println!("GPU breadcrumbs: diagnostic checkpoints");
+            return Some(Self {
+                checkpoints: Some(ash::nv::device_diagnostic_checkpoints::Device::new(
+                    &gpu.instance,
+                    &gpu.device,
+                )),
+                markers: None,
+            });
+        }
+        if !gpu.capabilities.buffer_marker {
+            return None;
+        }
+        let (buffer, memory) = gpu.create_buffer(
+            FINISHED_SLOT + size_of::<u32>() as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        gpu.set_name(buffer, "breadcrumb markers");
+        let mapped = unsafe {
+            let mapped = gpu
+                .device
+                .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .expect("Failed to map breadcrumb markers")
+                .cast::<u32>();
+            mapped.write_bytes(0, 2);
+            mapped
+        };
+        println!("GPU breadcrumbs: buffer markers");
+        Some(Self {
+            checkpoints: None,
+            markers: Some(MarkerBuffer {
+                extension: ash::amd::buffer_marker::Device::new(&gpu.instance, &gpu.device),
+                buffer,
+                memory,
+                mapped,
+            }),
+        })
+    }
+
+    // Caller ensures the device is idle
+    pub fn destroy(&self, gpu: &GpuContext) {
+        if let Some(markers) = self.markers.as_ref() {
+            unsafe {
+                gpu.device.unmap_memory(markers.memory);
+                gpu.device.destroy_buffer(markers.buffer, None);
+            }
+            gpu.free_memory(markers.memory);
+        }
+    }
+
+    // Leaves `breadcrumb` for frame `frame` in the command buffer
+    pub fn record(&self, command_buffer: vk::CommandBuffer, frame: u64, breadcrumb: Breadcrumb) {
+        let marker = breadcrumb.marker(frame);
+        unsafe {
+            if let Some(checkpoints) = self.checkpoints.as_ref() {
+                // The marker is an opaque pointer-sized value, never dereferenced
+                checkpoints.cmd_set_checkpoint(command_buffer, marker as usize as *const c_void);
+            }
+            if let Some(markers) = self.markers.as_ref() {
+                for (stage, offset) in [
+                    (vk::PipelineStageFlags::TOP_OF_PIPE, STARTED_SLOT),
+                    (vk::PipelineStageFlags::BOTTOM_OF_PIPE, FINISHED_SLOT),
+                ] {
+                    markers.extension.cmd_write_buffer_marker(
+                        command_buffer,
+                        stage,
+                        markers.buffer,
+                        offset,
+                        marker,
+                    );
+                }
+            }
+        }
+    }
+
+    // What the GPU got through before it was lost, one line each
+    pub fn report(&self, queue: vk::Queue) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(checkpoints) = self.checkpoints.as_ref() {
+            let data = unsafe {
+                let mut data = vec![
+                    vk::CheckpointDataNV::default();
+                    checkpoints.get_queue_checkpoint_data_len(queue)
+                ];
+                checkpoints.get_queue_checkpoint_data(queue, &mut data);
+                data
+            };
+            if data.is_empty() {
+                lines.push("The driver reported no checkpoints".to_string());
+            }
+            for checkpoint in data {
+                lines.push(format!(
+                    "Last checkpoint at {:?}: {}",
+                    checkpoint.stage,
+                    Breadcrumb::describe(checkpoint.p_checkpoint_marker as usize as u32)
+                ));
+            }
+        }
+        if let Some(markers) = self.markers.as_ref() {
+            let [started, finished] = [STARTED_SLOT, FINISHED_SLOT].map(|offset| unsafe {
+                let index = offset as usize / size_of::<u32>();
+                markers.mapped.add(index).read_volatile()
+            });
+            lines.push(format!(
+                "Last breadcrumb started: {}",
+                Breadcrumb::describe(started)
+            ));
+            lines.push(format!(
+                "Last breadcrumb finished: {}",
+                Breadcrumb::describe(finished)
+            ));
+        }
+        lines
+    }
+}
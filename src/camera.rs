@@ -0,0 +1,118 @@
+use glam::{Mat4, Vec2, Vec3};
+
+// Radians per logical unit the cursor moves while dragging
+const ROTATE_SPEED: f32 = 0.01;
+// Distance factor per scroll notch
+const DOLLY_STEP: f32 = 1.15;
+// Short of straight up or down, where the up vector would flip
+const MAX_PITCH: f32 = 1.5;
+const MIN_DISTANCE: f32 = 1.5;
+const MAX_DISTANCE: f32 = 50.0;
+const NEAR: f32 = 0.1;
+const FAR: f32 = 100.0;
+// Smallest and largest 2D zoom, in screen pixels per world pixel
+const MIN_ZOOM_2D: f32 = 0.25;
+const MAX_ZOOM_2D: f32 = 8.0;
+
+// Perspective camera circling `target`, y up. Yaw turns around the y axis and
+// pitch tilts towards the poles; the distance is changed by dollying.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    // Vertical field of view in radians
+    pub fov_y: f32,
+}
+
+impl OrbitCamera {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            yaw: 0.6,
+            pitch: 0.4,
+            distance,
+            fov_y: 60f32.to_radians(),
+        }
+    }
+
+    pub fn eye(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        self.target + Vec3::new(sin_yaw * cos_pitch, sin_pitch, cos_yaw * cos_pitch) * self.distance
+    }
+
+    // Turns the camera by a cursor drag of `delta` logical units; dragging
+    // right swings it to the left around the target, like grabbing the scene
+    pub fn rotate(&mut self, delta: Vec2) {
+        self.yaw -= delta.x * ROTATE_SPEED;
+        self.pitch = (self.pitch + delta.y * ROTATE_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // Moves towards the target for positive scroll, away for negative
+    pub fn dolly(&mut self, scroll: f32) {
+        self.distance = (self.distance * DOLLY_STEP.powf(-scroll)).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye(), self.target, Vec3::Y)
+    }
+
+    // Vulkan clip space: depth 0..1 and y pointing down, so the flip keeps
+    // world y up on screen
+    pub fn projection(&self, aspect_ratio: f32) -> Mat4 {
+        let mut projection = Mat4::perspective_rh(self.fov_y, aspect_ratio, NEAR, FAR);
+        projection.y_axis.y = -projection.y_axis.y;
+        projection
+    }
+
+    pub fn view_projection(&self, aspect_ratio: f32) -> Mat4 {
+        self.projection(aspect_ratio) * self.view()
+    }
+}
+
+// Camera over a 2D world measured in pixels, y down like the canvas.
+// `position` is the world point shown at the center of the view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera2d {
+    pub position: Vec2,
+    pub zoom: f32,
+}
+
+impl Camera2d {
+    pub fn new(position: Vec2, zoom: f32) -> Self {
+        Self {
+            position,
+            zoom: zoom.clamp(MIN_ZOOM_2D, MAX_ZOOM_2D),
+        }
+    }
+
+    // `viewport` is the size of the view in screen units, such as the ortho bounds
+    pub fn world_to_screen(&self, point: Vec2, viewport: Vec2) -> Vec2 {
+        (point - self.position) * self.zoom + viewport * 0.5
+    }
+
+    pub fn screen_to_world(&self, point: Vec2, viewport: Vec2) -> Vec2 {
+        (point - viewport * 0.5) / self.zoom + self.position
+    }
+
+    // The world rectangle in view, as its min and max corners
+    pub fn visible_rect(&self, viewport: Vec2) -> (Vec2, Vec2) {
+        let half = viewport * 0.5 / self.zoom;
+        (self.position - half, self.position + half)
+    }
+
+    // Moves the view by `delta` screen units; dragging the world right moves
+    // the camera left
+    pub fn pan(&mut self, delta: Vec2) {
+        self.position -= delta / self.zoom;
+    }
+
+    // Zooms by `factor` keeping the world point under `anchor` fixed on screen
+    pub fn zoom_at(&mut self, anchor: Vec2, viewport: Vec2, factor: f32) {
+        let fixed = self.screen_to_world(anchor, viewport);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM_2D, MAX_ZOOM_2D);
+        self.position += fixed - self.screen_to_world(anchor, viewport);
+    }
+}
@@ -0,0 +1,265 @@
+use glam::Vec2;
+
+use crate::geometry::{self, Geometry, StrokeStyle, Tessellation};
+use crate::gradient::{Gradient, MAX_GRADIENTS};
+use crate::path::Path;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CanvasVertex {
+    pub position: [f32; 2],
+    // Tints the gradient, if there is one
+    pub color: [f32; 4],
+    // 0 for a flat color, otherwise one more than an index into `gradients()`
+    pub gradient: u32,
+}
+
+// Clip shapes nest at most this deep; the stencil attachment has 8 bits
+pub const MAX_CLIP_DEPTH: usize = 255;
+
+// How the renderer draws a batch of canvas indices
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CanvasOp {
+    // Shapes, colored, inside every clip shape pushed at the time
+    Draw,
+    // A clip shape, marked in the stencil without drawing color
+    PushClip,
+    // A clip shape drawn again to unmark it
+    PopClip,
+}
+
+// A run of indices drawn with one op. Pixels are only touched where the
+// stencil equals `stencil_reference`: the number of clip shapes covering them,
+// which is the clip depth for draws and pushes, and one more for pops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanvasBatch {
+    pub op: CanvasOp,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub stencil_reference: u32,
+}
+
+// Immediate-mode 2D draw list. Shapes are tessellated into an indexed triangle
+// list on the CPU; the renderer uploads the whole list into a dynamic vertex
+// buffer once per frame and draws it in order, one call per batch. Without
+// clip shapes that is a single call.
+#[derive(Default)]
+pub struct Canvas {
+    vertices: Vec<CanvasVertex>,
+    indices: Vec<u32>,
+    batches: Vec<CanvasBatch>,
+    // Index range of each clip shape pushed and not yet popped, innermost last
+    clips: Vec<(u32, u32)>,
+    gradients: Vec<Gradient>,
+}
+
+impl Canvas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.batches.clear();
+        self.clips.clear();
+        self.gradients.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn vertices(&self) -> &[CanvasVertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    // At most MAX_GRADIENTS
+    pub fn gradients(&self) -> &[Gradient] {
+        &self.gradients
+    }
+
+    pub fn batches(&self) -> &[CanvasBatch] {
+        &self.batches
+    }
+
+    // Number of clip shapes currently pushed
+    pub fn clip_depth(&self) -> usize {
+        self.clips.len()
+    }
+
+    // Restricts everything drawn until the matching `pop_clip` to the inside of
+    // `geometry`, intersected with any clip shapes already pushed
+    pub fn push_clip_shape(&mut self, geometry: &Geometry) {
+        assert!(
+            self.clips.len() < MAX_CLIP_DEPTH,
+            "Canvas clip shapes nested deeper than {}",
+            MAX_CLIP_DEPTH
+        );
+        let first_index = self.indices.len() as u32;
+        // Only the stencil sees clip shapes, so their color doesn't matter
+        self.push_geometry(geometry, [0.0; 4], 0);
+        let index_count = self.indices.len() as u32 - first_index;
+        self.batches.push(CanvasBatch {
+            op: CanvasOp::PushClip,
+            first_index,
+            index_count,
+            stencil_reference: self.clips.len() as u32,
+        });
+        self.clips.push((first_index, index_count));
+    }
+
+    // Removes the innermost clip shape by drawing its triangles again
+    pub fn pop_clip(&mut self) {
+        let (first_index, index_count) = self
+            .clips
+            .pop()
+            .expect("Canvas pop_clip without a matching push_clip_shape");
+        self.batches.push(CanvasBatch {
+            op: CanvasOp::PopClip,
+            first_index,
+            index_count,
+            stencil_reference: self.clips.len() as u32 + 1,
+        });
+    }
+
+    // Adds the indices from `first_index` on to the draw batch at the current
+    // clip depth, extending the last batch if it is one
+    fn extend_draw(&mut self, first_index: u32) {
+        let end = self.indices.len() as u32;
+        let stencil_reference = self.clips.len() as u32;
+        match self.batches.last_mut() {
+            Some(batch)
+                if batch.op == CanvasOp::Draw
+                    && batch.stencil_reference == stencil_reference
+                    && batch.first_index + batch.index_count == first_index =>
+            {
+                batch.index_count = end - batch.first_index;
+            }
+            _ if end > first_index => self.batches.push(CanvasBatch {
+                op: CanvasOp::Draw,
+                first_index,
+                index_count: end - first_index,
+                stencil_reference,
+            }),
+            _ => {}
+        }
+    }
+
+    pub fn fill_circle(&mut self, center: Vec2, radius: f32, color: [f32; 4]) {
+        self.fill_geometry(
+            &geometry::circle(center, radius, Tessellation::default()),
+            color,
+        );
+    }
+
+    // Adds any shape from the `geometry` module in a single flat color
+    pub fn fill_geometry(&mut self, geometry: &Geometry, color: [f32; 4]) {
+        let first_index = self.indices.len() as u32;
+        self.push_geometry(geometry, color, 0);
+        self.extend_draw(first_index);
+    }
+
+    // Adds a shape colored by `gradient`. Shapes using the same gradient one
+    // after another share its slot; once MAX_GRADIENTS are in use this frame,
+    // more are filled with their first stop's color.
+    pub fn fill_gradient(&mut self, geometry: &Geometry, gradient: &Gradient) {
+        if self.gradients.last() != Some(gradient) {
+            if self.gradients.len() == MAX_GRADIENTS {
+                self.fill_geometry(geometry, gradient.first_color());
+                return;
+            }
+            self.gradients.push(gradient.clone());
+        }
+        let first_index = self.indices.len() as u32;
+        self.push_geometry(geometry, [1.0; 4], self.gradients.len() as u32);
+        self.extend_draw(first_index);
+    }
+
+    fn push_geometry(&mut self, geometry: &Geometry, color: [f32; 4], gradient: u32) {
+        let base = self.vertices.len() as u32;
+        self.vertices
+            .extend(geometry.positions.iter().map(|&position| CanvasVertex {
+                position,
+                color,
+                gradient,
+            }));
+        self.indices
+            .extend(geometry.indices.iter().map(|index| index + base));
+    }
+
+    pub fn fill_path(&mut self, path: &Path, color: [f32; 4]) {
+        self.fill_geometry(&path.fill(), color);
+    }
+
+    pub fn stroke_path(&mut self, path: &Path, style: StrokeStyle, color: [f32; 4]) {
+        self.fill_geometry(&path.stroke(style), color);
+    }
+
+    pub fn fill_rect(&mut self, min: Vec2, size: Vec2, color: [f32; 4]) {
+        let max = min + size;
+        self.fill_polygon(
+            &[
+                min,
+                Vec2::new(max.x, min.y),
+                max,
+                Vec2::new(min.x, max.y),
+            ],
+            color,
+        );
+    }
+
+    // Radii are per corner: top left, top right, bottom right, bottom left
+    pub fn fill_rounded_rect(&mut self, min: Vec2, size: Vec2, radii: [f32; 4], color: [f32; 4]) {
+        self.fill_geometry(
+            &geometry::rounded_rect_corners(min, size, radii, Tessellation::default()),
+            color,
+        );
+    }
+
+    // A butt-capped line `thickness` wide
+    pub fn stroke_line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: [f32; 4]) {
+        self.draw_line(from, to, StrokeStyle::new(thickness), color);
+    }
+
+    pub fn draw_line(&mut self, from: Vec2, to: Vec2, style: StrokeStyle, color: [f32; 4]) {
+        self.draw_polyline(&[from, to], false, style, color);
+    }
+
+    // Strokes through `points` with the style's joins and caps; `closed` also
+    // joins the last point back to the first
+    pub fn draw_polyline(
+        &mut self,
+        points: &[Vec2],
+        closed: bool,
+        style: StrokeStyle,
+        color: [f32; 4],
+    ) {
+        self.fill_geometry(
+            &geometry::stroke_polyline(points, closed, style, Tessellation::default()),
+            color,
+        );
+    }
+
+    // Fills a convex polygon as a triangle fan around its first point
+    pub fn fill_polygon(&mut self, points: &[Vec2], color: [f32; 4]) {
+        if points.len() < 3 {
+            return;
+        }
+        let first_index = self.indices.len() as u32;
+        let base = self.vertices.len() as u32;
+        self.vertices.extend(points.iter().map(|p| CanvasVertex {
+            position: p.to_array(),
+            color,
+            gradient: 0,
+        }));
+        for i in 1..points.len() as u32 - 1 {
+            self.indices.extend_from_slice(&[base, base + i, base + i + 1]);
+        }
+        self.extend_draw(first_index);
+    }
+}
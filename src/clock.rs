@@ -0,0 +1,83 @@
+// Simulation step, independent of the frame rate
+pub const FIXED_DT: f32 = 1.0 / 60.0;
+
+// Fixed-timestep simulation clock, decoupled from the render loop. Frames feed in
+// wall-clock time; the clock hands back how many fixed steps to simulate, scaled,
+// paused or single-stepped as requested.
+pub struct SimulationClock {
+    pub fixed_dt: f32,
+    time_scale: f32,
+    paused: bool,
+    pending_steps: u32,
+    accumulator: f32,
+}
+
+impl SimulationClock {
+    pub const MIN_TIME_SCALE: f32 = 0.1;
+    pub const MAX_TIME_SCALE: f32 = 4.0;
+    // Upper bound on steps per frame so a long hitch can't snowball
+    const MAX_STEPS_PER_FRAME: u32 = 8;
+
+    pub fn new(fixed_dt: f32) -> Self {
+        Self {
+            fixed_dt,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.accumulator = 0.0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.set_paused(!self.paused);
+    }
+
+    // Runs exactly one fixed step on the next `advance`, pausing first if needed
+    pub fn step(&mut self) {
+        self.set_paused(true);
+        self.pending_steps += 1;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.clamp(Self::MIN_TIME_SCALE, Self::MAX_TIME_SCALE);
+    }
+
+    // Scaled time fed in but not yet simulated, less than one fixed step. Saved
+    // in snapshots so a restored run steps on the same frames as the original.
+    pub fn accumulator(&self) -> f32 {
+        self.accumulator
+    }
+
+    // Call after `set_paused`, which empties it
+    pub fn set_accumulator(&mut self, accumulator: f32) {
+        self.accumulator = accumulator.clamp(0.0, self.fixed_dt);
+    }
+
+    // Feeds one frame of wall-clock time and returns the number of fixed steps to run
+    pub fn advance(&mut self, frame_dt: f32) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.pending_steps);
+        }
+        self.accumulator += frame_dt * self.time_scale;
+        let steps = (self.accumulator / self.fixed_dt) as u32;
+        self.accumulator -= steps as f32 * self.fixed_dt;
+        if steps > Self::MAX_STEPS_PER_FRAME {
+            self.accumulator = 0.0;
+            return Self::MAX_STEPS_PER_FRAME;
+        }
+        steps
+    }
+}
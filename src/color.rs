@@ -0,0 +1,51 @@
+// Converts hue/saturation/value (all in 0..1, hue wraps) to linear RGBA
+pub fn hsv_to_rgba(hue: f32, saturation: f32, value: f32, alpha: f32) -> [f32; 4] {
+    let h = hue.rem_euclid(1.0) * 6.0;
+    let c = value * saturation;
+    let x = c * (1.0 - ((h % 2.0) - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [r + m, g + m, b + m, alpha]
+}
+
+// Converts one sRGB-encoded channel (0..1) to linear
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Parses "#rrggbb" (or "rrggbb"), an sRGB hex color as an image editor shows
+// it, or "r,g,b" with linear components in 0..1
+pub fn parse_rgb(text: &str) -> Option<[f32; 3]> {
+    let text = text.trim();
+    if text.contains(',') {
+        let components = text
+            .split(',')
+            .map(|component| component.trim().parse::<f32>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        return match components[..] {
+            [r, g, b] => Some([r, g, b].map(|c| c.clamp(0.0, 1.0))),
+            _ => None,
+        };
+    }
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([
+        srgb_to_linear(channel(0)? as f32 / 255.0),
+        srgb_to_linear(channel(2)? as f32 / 255.0),
+        srgb_to_linear(channel(4)? as f32 / 255.0),
+    ])
+}
@@ -0,0 +1,253 @@
+use ash::vk;
+use std::ffi::{c_void, CString};
+use std::fmt;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Most shader stages a pipeline built here has: vertex or mesh, and fragment
+const MAX_STAGES: usize = 2;
+
+type Job = Box<dyn FnOnce(&ash::Device, vk::PipelineCache) + Send>;
+
+// A worker thread that creates pipelines, so the frame loop never waits on the
+// driver's shader compiler. It builds into the same pipeline cache as the main
+// thread, which is safe because pipeline caches are internally synchronized.
+// Jobs run one at a time in the order they were queued.
+pub struct PipelineCompiler {
+    jobs: Option<mpsc::Sender<Job>>,
+    thread: Option<thread::JoinHandle<()>>,
+    // The `GpuContext`'s loader, to name pipelines where they're created
+    debug_utils: Option<ash::ext::debug_utils::Device>,
+    // Set with `capabilities.pipeline_creation_feedback`
+    feedback: bool,
+    stats: Arc<Mutex<PipelineStats>>,
+}
+
+impl PipelineCompiler {
+    pub fn new(
+        device: &ash::Device,
+        cache: vk::PipelineCache,
+        debug_utils: Option<ash::ext::debug_utils::Device>,
+        feedback: bool,
+    ) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let device = device.clone();
+        let thread = thread::Builder::new()
+            .name("pipeline compiler".to_string())
+            .spawn(move || {
+                for job in receiver {
+                    job(&device, cache);
+                }
+            })
+            .expect("Failed to start the pipeline compiler thread");
+        Self {
+            jobs: Some(jobs),
+            thread: Some(thread),
+            debug_utils,
+            feedback,
+            stats: Arc::new(Mutex::new(PipelineStats::default())),
+        }
+    }
+
+    // Creation feedback totals so far; all zero without
+    // `capabilities.pipeline_creation_feedback`
+    pub fn stats(&self) -> PipelineStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    // Queues `build`, which creates one pipeline and owns everything it reads.
+    // Handles it uses, like shader modules and layouts, must outlive the job.
+    // The pipeline is given `name` like `GpuContext::set_name` would. With
+    // creation feedback `build` gets a `CreationFeedback` to chain, and what the
+    // driver fills in is logged and added to `stats`.
+    pub fn compile<F>(&self, name: String, build: F) -> PendingPipeline
+    where
+        F: FnOnce(&ash::Device, vk::PipelineCache, Option<&mut CreationFeedback>) -> vk::Pipeline
+            + Send
+            + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let debug_utils = self.debug_utils.clone();
+        let mut feedback = self.feedback.then(CreationFeedback::default);
+        let stats = self.stats.clone();
+        let job: Job = Box::new(move |device, cache| {
+            let pipeline = build(device, cache, feedback.as_mut());
+            if let Some(feedback) = feedback {
+                stats.lock().unwrap().record(&name, &feedback);
+            }
+            if let Some(debug_utils) = debug_utils {
+                let name = CString::new(name).expect("Object name contains a nul byte");
+                let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+                    .object_handle(pipeline)
+                    .object_name(&name);
+                unsafe {
+                    debug_utils
+                        .set_debug_utils_object_name(&name_info)
+                        .expect("Failed to name pipeline");
+                }
+            }
+            // The receiver is gone if the pipeline was abandoned, so nobody else
+            // will destroy it
+            if let Err(mpsc::SendError(pipeline)) = sender.send(pipeline) {
+                unsafe { device.destroy_pipeline(pipeline, None) };
+            }
+        });
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send(job)
+            .expect("The pipeline compiler thread has stopped");
+        PendingPipeline(receiver)
+    }
+}
+
+impl Drop for PipelineCompiler {
+    // Finishes the queued jobs before the device can be destroyed
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// A pipeline the compiler thread is building
+pub struct PendingPipeline(mpsc::Receiver<vk::Pipeline>);
+
+impl PendingPipeline {
+    // The pipeline if it's done, without blocking. Returns it once only.
+    pub fn poll(&self) -> Option<vk::Pipeline> {
+        self.0.try_recv().ok()
+    }
+
+    // Blocks until the pipeline is done. Must not follow a successful `poll`.
+    pub fn wait(self) -> vk::Pipeline {
+        self.0
+            .recv()
+            .expect("The pipeline compiler thread stopped mid-build")
+    }
+}
+
+// What the driver reports about creating one pipeline. Build jobs put it on
+// their create info's p_next chain with `chain`.
+#[derive(Default)]
+pub struct CreationFeedback {
+    pipeline: vk::PipelineCreationFeedback,
+    stages: [vk::PipelineCreationFeedback; MAX_STAGES],
+    stage_flags: [vk::ShaderStageFlags; MAX_STAGES],
+    stage_count: usize,
+}
+
+impl CreationFeedback {
+    // Create info to put in front of `next` on the p_next chain. `stages` are
+    // the create info's stages in order, as the driver reports one per stage.
+    pub fn chain(
+        &mut self,
+        stages: &[vk::ShaderStageFlags],
+        next: *const c_void,
+    ) -> vk::PipelineCreationFeedbackCreateInfo<'_> {
+        self.stage_count = stages.len();
+        self.stage_flags[..stages.len()].copy_from_slice(stages);
+        let mut info = vk::PipelineCreationFeedbackCreateInfo::default()
+            .pipeline_creation_feedback(&mut self.pipeline)
+            .pipeline_stage_creation_feedbacks(&mut self.stages[..stages.len()]);
+        info.p_next = next;
+        info
+    }
+}
+
+fn stage_name(stage: vk::ShaderStageFlags) -> &'static str {
+    match stage {
+        vk::ShaderStageFlags::VERTEX => "vertex",
+        vk::ShaderStageFlags::FRAGMENT => "fragment",
+        vk::ShaderStageFlags::MESH_EXT => "mesh",
+        vk::ShaderStageFlags::COMPUTE => "compute",
+        _ => "other",
+    }
+}
+
+// Creation feedback totals over every pipeline the compiler has built. The cache
+// starts empty each run, so hits come from pipelines built again, such as after
+// a scene is left and entered again.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineStats {
+    // Pipelines the driver gave valid feedback for
+    pub pipelines: u32,
+    // Ones the driver found in the pipeline cache instead of compiling
+    pub cache_hits: u32,
+    pub total: Duration,
+    // Name and creation time of the slowest one
+    pub slowest: Option<(String, Duration)>,
+}
+
+impl PipelineStats {
+    fn record(&mut self, name: &str, feedback: &CreationFeedback) {
+        if !feedback
+            .pipeline
+            .flags
+            .contains(vk::PipelineCreationFeedbackFlags::VALID)
+        {
+            println!("No creation feedback for {}", name);
+            return;
+        }
+        let duration = Duration::from_nanos(feedback.pipeline.duration);
+        let cache_hit = feedback
+            .pipeline
+            .flags
+            .contains(vk::PipelineCreationFeedbackFlags::APPLICATION_PIPELINE_CACHE_HIT);
+        let stages: Vec<String> = (0..feedback.stage_count)
+            .map(|index| {
+                let stage = &feedback.stages[index];
+                let name = stage_name(feedback.stage_flags[index]);
+                if stage
+                    .flags
+                    .contains(vk::PipelineCreationFeedbackFlags::VALID)
+                {
+                    format!("{} {:.2} ms", name, stage.duration as f64 / 1e6)
+                } else {
+                    format!("{} unknown", name)
+                }
+            })
+            .collect();
+        println!(
+            "Created {} in {:.2} ms{} ({})",
+            name,
+            duration.as_secs_f64() * 1000.0,
+            if cache_hit { ", cache hit" } else { "" },
+            stages.join(", ")
+        );
+
+        self.pipelines += 1;
+        self.cache_hits += cache_hit as u32;
+        self.total += duration;
+        if self
+            .slowest
+            .as_ref()
+            .is_none_or(|(_, slowest)| duration > *slowest)
+        {
+            self.slowest = Some((name.to_string(), duration));
+        }
+    }
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Pipeline creation: {} pipelines in {:.1} ms, {} from the cache",
+            self.pipelines,
+            self.total.as_secs_f64() * 1000.0,
+            self.cache_hits
+        )?;
+        if let Some((name, duration)) = &self.slowest {
+            write!(
+                f,
+                ", slowest {} ({:.1} ms)",
+                name,
+                duration.as_secs_f64() * 1000.0
+            )?;
+        }
+        writeln!(f)
+    }
+}
@@ -0,0 +1,418 @@
+use ash::vk;
+use std::cell::Cell;
+
+use crate::compiler::PendingPipeline;
+use crate::gpu::GpuContext;
+use crate::reflect::ShaderInterface;
+use crate::sync::{Access, Barriers};
+
+const MAX_COMPUTE_SETS: u32 = 16;
+const COLOR_SUBRESOURCE_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+// A compute shader whose descriptor set 0 is a list of storage buffers or
+// storage images (binding 0, 1, ...) plus one push constant block, both
+// reflected from the SPIR-V. Owns a small descriptor pool for the sets bound to it.
+// Shaders that reach their buffers through device addresses have no set at all.
+// The pipeline itself is built on the `GpuContext`'s compiler thread.
+pub struct ComputePipeline {
+    pub descriptor_set_layout: vk::DescriptorSetLayout,
+    pub pipeline_layout: vk::PipelineLayout,
+    // Null until `pending` is done
+    pipeline: Cell<vk::Pipeline>,
+    pending: Cell<Option<PendingPipeline>>,
+    descriptor_pool: vk::DescriptorPool,
+    binding_types: Vec<vk::DescriptorType>,
+}
+
+impl ComputePipeline {
+    // `push_constant_size` is what the CPU pushes, checked against the shader
+    pub fn new(gpu: &GpuContext, name: &str, spirv: &[u8], push_constant_size: usize) -> Self {
+        let device = &gpu.device;
+
+        let interface = ShaderInterface::new(&[(spirv, vk::ShaderStageFlags::COMPUTE)]);
+        assert!(
+            interface.set_count() <= 1,
+            "Compute shaders use at most descriptor set 0"
+        );
+        let binding_types: Vec<vk::DescriptorType> = interface
+            .set_bindings(0)
+            .enumerate()
+            .map(|(index, binding)| {
+                assert_eq!(
+                    binding.binding, index as u32,
+                    "Compute bindings must be dense"
+                );
+                binding.descriptor_type
+            })
+            .collect();
+        let set_layouts = interface.create_set_layouts(gpu);
+        let descriptor_set_layout = set_layouts.first().copied().unwrap_or_default();
+
+        let pool_sizes = interface.pool_sizes(MAX_COMPUTE_SETS);
+        let descriptor_pool = if pool_sizes.is_empty() {
+            vk::DescriptorPool::null()
+        } else {
+            unsafe {
+                device
+                    .create_descriptor_pool(
+                        &vk::DescriptorPoolCreateInfo {
+                            max_sets: MAX_COMPUTE_SETS,
+                            pool_size_count: pool_sizes.len() as u32,
+                            p_pool_sizes: pool_sizes.as_ptr(),
+                            ..Default::default()
+                        },
+                        None,
+                    )
+                    .expect("Failed to create compute descriptor pool")
+            }
+        };
+
+        let pipeline_layout =
+            interface.create_pipeline_layout(gpu, &set_layouts, push_constant_size);
+
+        // The module belongs to the job, which destroys it once it's done
+        let shader_module = gpu.create_shader_module(spirv);
+        gpu.set_name(shader_module, &format!("{} compute shader", name));
+        let pending = gpu
+            .compiler
+            .compile(format!("{} pipeline", name), move |device, cache, feedback| {
+                let feedback_info;
+                let mut p_next = std::ptr::null();
+                if let Some(feedback) = feedback {
+                    feedback_info = feedback.chain(&[vk::ShaderStageFlags::COMPUTE], p_next);
+                    p_next = &feedback_info as *const _ as *const std::ffi::c_void;
+                }
+                let pipeline_info = vk::ComputePipelineCreateInfo {
+                    p_next,
+                    stage: vk::PipelineShaderStageCreateInfo {
+                        stage: vk::ShaderStageFlags::COMPUTE,
+                        module: shader_module,
+                        p_name: c"main".as_ptr(),
+                        ..Default::default()
+                    },
+                    layout: pipeline_layout,
+                    ..Default::default()
+                };
+                let pipeline = unsafe {
+                    let pipeline = device
+                        .create_compute_pipelines(cache, &[pipeline_info], None)
+                        .expect("Failed to create compute pipeline")[0];
+                    device.destroy_shader_module(shader_module, None);
+                    pipeline
+                };
+                println!("Compute pipeline created: {:?}", pipeline);
+                pipeline
+            });
+
+        Self {
+            descriptor_set_layout,
+            pipeline_layout,
+            pipeline: Cell::new(vk::Pipeline::null()),
+            pending: Cell::new(Some(pending)),
+            descriptor_pool,
+            binding_types,
+        }
+    }
+
+    // Whether the compiler thread is done with the pipeline, without blocking
+    pub fn is_ready(&self) -> bool {
+        if let Some(pending) = self.pending.take() {
+            match pending.poll() {
+                Some(pipeline) => self.pipeline.set(pipeline),
+                None => self.pending.set(Some(pending)),
+            }
+        }
+        self.pipeline.get() != vk::Pipeline::null()
+    }
+
+    // Blocks until the pipeline is built, for one-off work that can't be skipped
+    pub fn wait(&self) {
+        if let Some(pending) = self.pending.take() {
+            self.pipeline.set(pending.wait());
+        }
+    }
+
+    fn allocate_descriptor_set(&self, gpu: &GpuContext) -> vk::DescriptorSet {
+        unsafe {
+            gpu.device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool: self.descriptor_pool,
+                    descriptor_set_count: 1,
+                    p_set_layouts: &self.descriptor_set_layout,
+                    ..Default::default()
+                })
+                .expect("Failed to allocate compute descriptor set")[0]
+        }
+    }
+
+    // Allocates a set binding `buffers` (whole range each) in binding order
+    pub fn create_descriptor_set(
+        &self,
+        gpu: &GpuContext,
+        buffers: &[vk::Buffer],
+    ) -> vk::DescriptorSet {
+        assert!(
+            buffers.len() == self.binding_types.len()
+                && self
+                    .binding_types
+                    .iter()
+                    .all(|&ty| ty == vk::DescriptorType::STORAGE_BUFFER),
+            "Compute pipeline expects bindings {:?}",
+            self.binding_types
+        );
+        let descriptor_set = self.allocate_descriptor_set(gpu);
+        let buffer_infos: Vec<vk::DescriptorBufferInfo> = buffers
+            .iter()
+            .map(|&buffer| vk::DescriptorBufferInfo {
+                buffer,
+                offset: 0,
+                range: vk::WHOLE_SIZE,
+            })
+            .collect();
+        let writes: Vec<vk::WriteDescriptorSet> = buffer_infos
+            .iter()
+            .enumerate()
+            .map(|(binding, info)| vk::WriteDescriptorSet {
+                dst_set: descriptor_set,
+                dst_binding: binding as u32,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                p_buffer_info: info,
+                ..Default::default()
+            })
+            .collect();
+        unsafe { gpu.device.update_descriptor_sets(&writes, &[]) };
+        descriptor_set
+    }
+
+    // Allocates a set binding `views` in binding order. The images must be in
+    // GENERAL layout whenever the pipeline runs.
+    pub fn create_image_descriptor_set(
+        &self,
+        gpu: &GpuContext,
+        views: &[vk::ImageView],
+    ) -> vk::DescriptorSet {
+        assert!(
+            views.len() == self.binding_types.len()
+                && self
+                    .binding_types
+                    .iter()
+                    .all(|&ty| ty == vk::DescriptorType::STORAGE_IMAGE),
+            "Compute pipeline expects bindings {:?}",
+            self.binding_types
+        );
+        let descriptor_set = self.allocate_descriptor_set(gpu);
+        let image_infos: Vec<vk::DescriptorImageInfo> = views
+            .iter()
+            .map(|&image_view| vk::DescriptorImageInfo {
+                image_view,
+                image_layout: vk::ImageLayout::GENERAL,
+                ..Default::default()
+            })
+            .collect();
+        let writes: Vec<vk::WriteDescriptorSet> = image_infos
+            .iter()
+            .enumerate()
+            .map(|(binding, info)| vk::WriteDescriptorSet {
+                dst_set: descriptor_set,
+                dst_binding: binding as u32,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::STORAGE_IMAGE,
+                p_image_info: info,
+                ..Default::default()
+            })
+            .collect();
+        unsafe { gpu.device.update_descriptor_sets(&writes, &[]) };
+        descriptor_set
+    }
+
+    // Releases sets from the pool so it can be refilled, e.g. after a resize
+    pub fn reset_descriptor_sets(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device
+                .reset_descriptor_pool(self.descriptor_pool, vk::DescriptorPoolResetFlags::empty())
+                .expect("Failed to reset compute descriptor pool");
+        }
+    }
+
+    // Records nothing until the pipeline is built, so the first frames after
+    // creating it skip the dispatch
+    pub fn dispatch(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        descriptor_set: Option<vk::DescriptorSet>,
+        push_constants: &[u8],
+        group_count: [u32; 3],
+    ) {
+        if !self.is_ready() {
+            return;
+        }
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline.get(),
+            );
+            if let Some(descriptor_set) = descriptor_set {
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.pipeline_layout,
+                    0,
+                    &[descriptor_set],
+                    &[],
+                );
+            }
+            if !push_constants.is_empty() {
+                device.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    push_constants,
+                );
+            }
+            device.cmd_dispatch(
+                command_buffer,
+                group_count[0],
+                group_count[1],
+                group_count[2],
+            );
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device) {
+        // The job still uses the layout until it's done
+        self.wait();
+        unsafe {
+            device.destroy_pipeline(self.pipeline.get(), None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
+
+// Number of workgroups of `group_size` needed to cover `count` invocations
+pub fn group_count(count: u32, group_size: u32) -> u32 {
+    count.div_ceil(group_size)
+}
+
+fn compute_storage(access: vk::AccessFlags2) -> Access {
+    Access::new(vk::PipelineStageFlags2::COMPUTE_SHADER, access)
+}
+
+// Vertex input, or vertex shaders reading the buffer through its device address
+fn vertex_reads() -> Access {
+    Access::new(
+        vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT | vk::PipelineStageFlags2::VERTEX_SHADER,
+        vk::AccessFlags2::VERTEX_ATTRIBUTE_READ | vk::AccessFlags2::SHADER_STORAGE_READ,
+    )
+}
+
+// Makes compute shader writes to `buffer` visible to vertex reads in later draws
+pub fn compute_to_vertex_barrier(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    buffer: vk::Buffer,
+) {
+    Barriers::new()
+        .buffer(
+            buffer,
+            compute_storage(vk::AccessFlags2::SHADER_STORAGE_WRITE),
+            vertex_reads(),
+        )
+        .record(gpu, command_buffer);
+}
+
+// Keeps the next compute pass from overwriting `buffer` while earlier draws are
+// still reading it
+pub fn vertex_to_compute_barrier(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    buffer: vk::Buffer,
+) {
+    Barriers::new()
+        .buffer(
+            buffer,
+            vertex_reads(),
+            compute_storage(
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            ),
+        )
+        .record(gpu, command_buffer);
+}
+
+// Transitions `image` from UNDEFINED (contents discarded) to GENERAL for
+// compute storage access
+pub fn undefined_to_general_barrier(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+) {
+    Barriers::new()
+        .image(
+            image,
+            COLOR_SUBRESOURCE_RANGE,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            Access::NONE,
+            compute_storage(
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            ),
+        )
+        .record(gpu, command_buffer);
+}
+
+// Makes compute shader writes to a GENERAL-layout storage image visible to
+// later compute dispatches and fragment shader sampling
+pub fn storage_image_barrier(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+) {
+    Barriers::new()
+        .image(
+            image,
+            COLOR_SUBRESOURCE_RANGE,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            compute_storage(vk::AccessFlags2::SHADER_STORAGE_WRITE),
+            Access::new(
+                vk::PipelineStageFlags2::COMPUTE_SHADER | vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::SHADER_STORAGE_READ
+                    | vk::AccessFlags2::SHADER_STORAGE_WRITE
+                    | vk::AccessFlags2::SHADER_SAMPLED_READ,
+            ),
+        )
+        .record(gpu, command_buffer);
+}
+
+// Keeps a dispatch from overwriting a storage image that earlier fragment
+// shaders may still be sampling
+pub fn fragment_to_compute_image_barrier(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+) {
+    Barriers::new()
+        .image(
+            image,
+            COLOR_SUBRESOURCE_RANGE,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::GENERAL,
+            Access::new(
+                vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::SHADER_SAMPLED_READ,
+            ),
+            compute_storage(vk::AccessFlags2::SHADER_STORAGE_WRITE),
+        )
+        .record(gpu, command_buffer);
+}
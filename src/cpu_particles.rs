@@ -0,0 +1,153 @@
+use glam::Vec2;
+
+use crate::rng::Rng;
+use crate::sprite::{Sprite, SpriteBatch, TextureId};
+
+// A short-lived particle simulated on the CPU
+#[derive(Clone, Copy, Debug)]
+pub struct CpuParticle {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+impl CpuParticle {
+    // 1 when born, 0 when about to die
+    pub fn life_left(&self) -> f32 {
+        (1.0 - self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+}
+
+// Spawn parameters for particles: where they start, which way they go and how
+// long they live. Emits continuously at `rate` per second and/or in bursts.
+#[derive(Clone, Copy, Debug)]
+pub struct Emitter {
+    pub position: Vec2,
+    // Centre of the emission cone; need not be normalized
+    pub direction: Vec2,
+    // Half-angle of the emission cone in radians; PI emits in every direction
+    pub spread: f32,
+    pub speed: (f32, f32),
+    pub lifetime: (f32, f32),
+    pub size: (f32, f32),
+    pub color: [f32; 4],
+    // Particles per second while `update` is called
+    pub rate: f32,
+    // Fraction of a particle still owed by `rate` from earlier updates
+    pub accumulator: f32,
+}
+
+impl Emitter {
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            direction: Vec2::NEG_Y,
+            spread: std::f32::consts::PI,
+            speed: (50.0, 150.0),
+            lifetime: (0.5, 1.0),
+            size: (4.0, 8.0),
+            color: [1.0, 1.0, 1.0, 1.0],
+            rate: 0.0,
+            accumulator: 0.0,
+        }
+    }
+
+    // Emits `count` particles at once
+    pub fn burst(&self, rng: &mut Rng, system: &mut CpuParticleSystem, count: usize) {
+        let base_angle = self.direction.to_angle();
+        for _ in 0..count {
+            let angle = base_angle + rng.range(-self.spread, self.spread);
+            system.spawn(CpuParticle {
+                position: self.position,
+                velocity: Vec2::from_angle(angle) * rng.range(self.speed.0, self.speed.1),
+                age: 0.0,
+                lifetime: rng.range(self.lifetime.0, self.lifetime.1),
+                size: rng.range(self.size.0, self.size.1),
+                color: self.color,
+            });
+        }
+    }
+
+    // Emits whatever `rate` owes for the last `dt` seconds
+    pub fn update(&mut self, dt: f32, rng: &mut Rng, system: &mut CpuParticleSystem) {
+        self.accumulator += self.rate * dt;
+        let count = self.accumulator.floor();
+        self.accumulator -= count;
+        self.burst(rng, system, count as usize);
+    }
+}
+
+// Pool of CPU particles sharing gravity and drag. Drawn as one instanced
+// sprite batch; particles shrink and fade out over their lifetime.
+pub struct CpuParticleSystem {
+    pub gravity: Vec2,
+    // Exponential velocity decay per second
+    pub drag: f32,
+    particles: Vec<CpuParticle>,
+    max_particles: usize,
+}
+
+impl CpuParticleSystem {
+    pub fn new(max_particles: usize) -> Self {
+        Self {
+            gravity: Vec2::ZERO,
+            drag: 0.0,
+            particles: Vec::with_capacity(max_particles),
+            max_particles,
+        }
+    }
+
+    // Adds a particle unless the pool is full
+    pub fn spawn(&mut self, particle: CpuParticle) {
+        if self.particles.len() < self.max_particles {
+            self.particles.push(particle);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn particles(&self) -> &[CpuParticle] {
+        &self.particles
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    // Ages and integrates every particle, dropping the expired ones
+    pub fn update(&mut self, dt: f32) {
+        let damping = (-self.drag * dt).exp();
+        for particle in &mut self.particles {
+            particle.age += dt;
+            particle.velocity = (particle.velocity + self.gravity * dt) * damping;
+            particle.position += particle.velocity * dt;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+    }
+
+    // One sprite instance per particle using `texture`
+    pub fn draw(&self, batch: &mut SpriteBatch, texture: TextureId) {
+        for particle in &self.particles {
+            let life = particle.life_left();
+            let [r, g, b, a] = particle.color;
+            let sprite = Sprite {
+                tint: [r, g, b, a * life],
+                ..Sprite::new(
+                    particle.position,
+                    Vec2::splat(particle.size * (0.5 + 0.5 * life)),
+                )
+            };
+            batch.draw(texture, &sprite);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use ash::vk;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceGroupMode {
+    // Each frame is rendered whole by the next GPU in turn, and its instance of
+    // the swapchain image is presented (REMOTE presentation)
+    AlternateFrames,
+    // Every GPU renders its own vertical strip of each frame over black, and the
+    // presentation engine adds the instances together (SUM presentation)
+    SplitScreen,
+}
+
+impl DeviceGroupMode {
+    // Names as `--device-group` takes them
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "afr" => Some(DeviceGroupMode::AlternateFrames),
+            "split" => Some(DeviceGroupMode::SplitScreen),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DeviceGroupMode::AlternateFrames => "alternate frames",
+            DeviceGroupMode::SplitScreen => "split screen",
+        }
+    }
+
+    // What the swapchains have to be able to do for this mode
+    pub fn present_mode(self) -> vk::DeviceGroupPresentModeFlagsKHR {
+        match self {
+            DeviceGroupMode::AlternateFrames => vk::DeviceGroupPresentModeFlagsKHR::REMOTE,
+            DeviceGroupMode::SplitScreen => vk::DeviceGroupPresentModeFlagsKHR::SUM,
+        }
+    }
+}
+
+// The GPUs the logical device was created over, set on `GpuContext` when
+// `--device-group` found a group of two or more that can present in `mode`.
+// Device-local memory has one instance per GPU, so each keeps its own copy of
+// every image and buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct DeviceGroup {
+    pub mode: DeviceGroupMode,
+    pub device_count: u32,
+}
+
+impl DeviceGroup {
+    pub fn all_devices(&self) -> u32 {
+        (1 << self.device_count) - 1
+    }
+
+    // Which GPUs render frame `frame_number`. `presentable` is false when some
+    // window's surface can't present in the group's mode; the frame then stays
+    // on the first GPU and is presented locally.
+    pub fn frame(&self, frame_number: u64, presentable: bool) -> GroupFrame {
+        if !presentable {
+            return GroupFrame {
+                device_mask: 1,
+                device_index: 0,
+                present_mode: vk::DeviceGroupPresentModeFlagsKHR::LOCAL,
+                strips: None,
+            };
+        }
+        match self.mode {
+            DeviceGroupMode::AlternateFrames => {
+                let device_index = (frame_number % self.device_count as u64) as u32;
+                GroupFrame {
+                    device_mask: 1 << device_index,
+                    device_index,
+                    present_mode: vk::DeviceGroupPresentModeFlagsKHR::REMOTE,
+                    strips: None,
+                }
+            }
+            DeviceGroupMode::SplitScreen => GroupFrame {
+                device_mask: self.all_devices(),
+                device_index: 0,
+                present_mode: vk::DeviceGroupPresentModeFlagsKHR::SUM,
+                strips: Some(self.device_count),
+            },
+        }
+    }
+}
+
+// What each GPU of a device group does in one frame. Compute work runs on every
+// GPU so their copies of simulation buffers stay in step; the render passes run
+// on `device_mask`.
+#[derive(Clone, Copy, Debug)]
+pub struct GroupFrame {
+    // GPUs that render the frame, and whose swapchain image instances are
+    // acquired and presented
+    pub device_mask: u32,
+    // The GPU that waits on the acquire and signals the frame's semaphores
+    pub device_index: u32,
+    pub present_mode: vk::DeviceGroupPresentModeFlagsKHR,
+    // Number of vertical strips, one per GPU, in split-screen frames
+    strips: Option<u32>,
+}
+
+impl GroupFrame {
+    pub fn is_split(&self) -> bool {
+        self.strips.is_some()
+    }
+
+    // Columns of `extent` GPU `index` renders in a split-screen frame
+    fn strip(strips: u32, index: u32, extent: vk::Extent2D) -> vk::Rect2D {
+        let left = extent.width * index / strips;
+        let right = extent.width * (index + 1) / strips;
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: left as i32,
+                y: 0,
+            },
+            extent: vk::Extent2D {
+                width: right - left,
+                height: extent.height,
+            },
+        }
+    }
+}
+
+// vkCmdBeginRenderPass on the frame's GPUs. With `split` each GPU's render area
+// is its strip of the framebuffer; without it every GPU gets the whole render
+// area, so a clearing load op clears all of it.
+pub fn begin_render_pass(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    begin_info: vk::RenderPassBeginInfo,
+    contents: vk::SubpassContents,
+    group: Option<&GroupFrame>,
+    split: bool,
+) {
+    let Some(group) = group else {
+        unsafe { device.cmd_begin_render_pass(command_buffer, &begin_info, contents) };
+        return;
+    };
+    let render_areas: Vec<vk::Rect2D> = match group.strips.filter(|_| split) {
+        Some(strips) => (0..strips)
+            .map(|index| GroupFrame::strip(strips, index, begin_info.render_area.extent))
+            .collect(),
+        None => Vec::new(),
+    };
+    let mut group_info = vk::DeviceGroupRenderPassBeginInfo::default()
+        .device_mask(group.device_mask)
+        .device_render_areas(&render_areas);
+    let begin_info = begin_info.push_next(&mut group_info);
+    unsafe { device.cmd_begin_render_pass(command_buffer, &begin_info, contents) };
+}
+
+// vkCmdSetScissor, narrowed on each GPU of a split-screen frame to its strip of
+// `extent`, the framebuffer's size. Rendering outside a GPU's render area isn't
+// allowed, and in the present pass it keeps the rest of its image instance
+// black for the sum.
+pub fn set_scissor(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    scissor: vk::Rect2D,
+    extent: vk::Extent2D,
+    group: Option<&GroupFrame>,
+) {
+    let Some((group, strips)) = group.and_then(|group| Some((group, group.strips?))) else {
+        unsafe { device.cmd_set_scissor(command_buffer, 0, &[scissor]) };
+        return;
+    };
+    for index in 0..strips {
+        let strip = GroupFrame::strip(strips, index, extent);
+        let left = scissor.offset.x.max(strip.offset.x);
+        let right = (scissor.offset.x + scissor.extent.width as i32)
+            .min(strip.offset.x + strip.extent.width as i32);
+        let narrowed = vk::Rect2D {
+            offset: vk::Offset2D {
+                x: left,
+                y: scissor.offset.y,
+            },
+            extent: vk::Extent2D {
+                width: (right - left).max(0) as u32,
+                height: scissor.extent.height,
+            },
+        };
+        unsafe {
+            device.cmd_set_device_mask(command_buffer, 1 << index);
+            device.cmd_set_scissor(command_buffer, 0, &[narrowed]);
+        }
+    }
+    unsafe { device.cmd_set_device_mask(command_buffer, group.device_mask) };
+}
+
+// The GPUs of the first group the instance lists with two or more of them.
+// Drivers only group GPUs that are linked, such as SLI or CrossFire pairs.
+pub fn find(instance: &ash::Instance) -> Option<Vec<vk::PhysicalDevice>> {
+    let groups = unsafe {
+        let count = instance
+            .enumerate_physical_device_groups_len()
+            .expect("Failed to count physical device groups");
+        let mut groups = vec![vk::PhysicalDeviceGroupProperties::default(); count];
+        instance
+            .enumerate_physical_device_groups(&mut groups)
+            .expect("Failed to enumerate physical device groups");
+        groups
+    };
+    println!(
+        "Found {} physical device groups of {:?} GPUs",
+        groups.len(),
+        groups
+            .iter()
+            .map(|group| group.physical_device_count)
+            .collect::<Vec<_>>()
+    );
+    groups
+        .iter()
+        .find(|group| group.physical_device_count >= 2)
+        .map(|group| group.physical_devices[..group.physical_device_count as usize].to_vec())
+}
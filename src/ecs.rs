@@ -0,0 +1,148 @@
+// Components and systems for scenes that keep their objects in a hecs
+// `World`. An entity with `Transform`, `Velocity` and `Shape` moves and
+// bounces; `Color` makes it drawable, and the optional components add
+// behaviors on top. Systems are plain functions a scene calls in order.
+
+use glam::Vec2;
+use hecs::{Entity, World};
+
+use crate::ball::{self, Ball, Physics};
+use crate::color::hsv_to_rgba;
+use crate::renderer::Circle;
+
+// Window (ortho) space position: y down, origin top-left
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub position: Vec2,
+}
+
+// Pixels per second
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity(pub Vec2);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Circle { radius: f32 },
+}
+
+impl Shape {
+    // Radius of the bounding circle, used for bounces and collisions
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Shape::Circle { radius } => radius,
+        }
+    }
+}
+
+// Fill color, rewritten every frame for entities with `HueCycle`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color(pub [f32; 4]);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    pub width: f32,
+    pub color: [f32; 4],
+}
+
+// Hue that cycles slowly over time, starting at `offset`; faster entities get
+// more saturated colors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HueCycle {
+    pub offset: f32,
+}
+
+// Not moved by `integrate`, e.g. while dragged with the mouse; still collides
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frozen;
+
+pub fn spawn_ball(world: &mut World, position: Vec2, velocity: Vec2, radius: f32) -> Entity {
+    world.spawn((
+        Transform { position },
+        Velocity(velocity),
+        Shape::Circle { radius },
+        Color([1.0; 4]),
+    ))
+}
+
+// A moving entity's state as a `Ball`, so the systems share ball.rs physics
+fn as_ball(transform: &Transform, velocity: &Velocity, shape: &Shape) -> Ball {
+    Ball::new(transform.position, velocity.0, shape.radius())
+}
+
+// Applies `physics`, moves every unfrozen entity and bounces it off the edges
+// of the 0..bounds rectangle, calling `on_wall_hit` with the speed into each
+// wall it touched
+pub fn integrate(
+    world: &mut World,
+    dt: f32,
+    bounds: Vec2,
+    physics: &Physics,
+    mut on_wall_hit: impl FnMut(f32),
+) {
+    let mut query = world
+        .query::<(&mut Transform, &mut Velocity, &Shape)>()
+        .without::<&Frozen>();
+    for (_, (transform, velocity, shape)) in query.iter() {
+        let mut ball = as_ball(transform, velocity, shape);
+        let incoming = ball.velocity;
+        if let Some(normal) = ball.step(dt, bounds, physics) {
+            on_wall_hit(-incoming.dot(normal));
+        }
+        transform.position = ball.position;
+        velocity.0 = ball.velocity;
+    }
+}
+
+// Resolves collisions between every pair of moving entities, calling
+// `on_impact` with the closing speed of each
+pub fn collide(world: &mut World, restitution: f32, on_impact: impl FnMut(f32)) {
+    let bodies: Vec<(Entity, Ball)> = world
+        .query_mut::<(&Transform, &Velocity, &Shape)>()
+        .into_iter()
+        .map(|(entity, (transform, velocity, shape))| {
+            (entity, as_ball(transform, velocity, shape))
+        })
+        .collect();
+    let mut balls: Vec<Ball> = bodies.iter().map(|(_, ball)| *ball).collect();
+    ball::collide_all(&mut balls, restitution, on_impact);
+    for ((entity, _), ball) in bodies.iter().zip(balls) {
+        let components = world.query_one_mut::<(&mut Transform, &mut Velocity)>(*entity);
+        if let Ok((transform, velocity)) = components {
+            transform.position = ball.position;
+            velocity.0 = ball.velocity;
+        }
+    }
+}
+
+// Recolors every `HueCycle` entity for `elapsed` seconds of cycling
+pub fn cycle_hues(world: &mut World, elapsed: f32) {
+    for (_, (color, hue, velocity)) in world.query_mut::<(&mut Color, &HueCycle, &Velocity)>() {
+        let saturation = (velocity.0.length() / 400.0).clamp(0.4, 1.0);
+        color.0 = hsv_to_rgba(elapsed * 0.1 + hue.offset, saturation, 1.0, 1.0);
+    }
+}
+
+// Builds one circle instance per drawable entity
+pub fn record_circles(world: &World, circles: &mut Vec<Circle>) {
+    let mut query = world.query::<(&Transform, &Shape, &Color, Option<&Outline>)>();
+    for (_, (transform, shape, color, outline)) in query.iter() {
+        let Shape::Circle { radius } = *shape;
+        let circle = Circle::new(transform.position, radius, color.0);
+        circles.push(match outline {
+            Some(outline) => circle.with_outline(outline.width, outline.color),
+            None => circle,
+        });
+    }
+}
+
+// The last-drawn entity whose shape contains `point`
+pub fn pick(world: &World, point: Vec2) -> Option<Entity> {
+    let mut query = world.query::<(&Transform, &Shape)>();
+    query
+        .iter()
+        .filter(|(_, (transform, shape))| {
+            transform.position.distance_squared(point) <= shape.radius() * shape.radius()
+        })
+        .last()
+        .map(|(entity, _)| entity)
+}
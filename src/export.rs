@@ -0,0 +1,483 @@
+use ash::vk;
+
+use crate::frame_ring::FRAMES_IN_FLIGHT;
+use crate::gpu::GpuContext;
+use crate::memory::MemoryCategory;
+use crate::sync::{Access, Barriers};
+
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, OwnedFd as OwnedHandle};
+#[cfg(windows)]
+use std::os::windows::io::{FromRawHandle, OwnedHandle};
+
+#[cfg(unix)]
+const MEMORY_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+const MEMORY_HANDLE_TYPE: vk::ExternalMemoryHandleTypeFlags =
+    vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32;
+#[cfg(unix)]
+const SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD;
+#[cfg(windows)]
+const SEMAPHORE_HANDLE_TYPE: vk::ExternalSemaphoreHandleTypeFlags =
+    vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_WIN32;
+
+// Loaders for getting handles out of memory and semaphores
+#[cfg(unix)]
+struct HandleExtensions {
+    memory: ash::khr::external_memory_fd::Device,
+    semaphore: ash::khr::external_semaphore_fd::Device,
+}
+#[cfg(windows)]
+struct HandleExtensions {
+    memory: ash::khr::external_memory_win32::Device,
+    semaphore: ash::khr::external_semaphore_win32::Device,
+}
+
+impl HandleExtensions {
+    fn new(gpu: &GpuContext) -> Self {
+        #[cfg(unix)]
+        return Self {
+            memory: ash::khr::external_memory_fd::Device::new(&gpu.instance, &gpu.device),
+            semaphore: ash::khr::external_semaphore_fd::Device::new(&gpu.instance, &gpu.device),
+        };
+        #[cfg(windows)]
+        return Self {
+            memory: ash::khr::external_memory_win32::Device::new(&gpu.instance, &gpu.device),
+            semaphore: ash::khr::external_semaphore_win32::Device::new(&gpu.instance, &gpu.device),
+        };
+    }
+
+    // A new handle to `memory`, owned by the caller
+    fn memory_handle(&self, memory: vk::DeviceMemory) -> OwnedHandle {
+        unsafe {
+            #[cfg(unix)]
+            let handle = {
+                let info = vk::MemoryGetFdInfoKHR::default()
+                    .memory(memory)
+                    .handle_type(MEMORY_HANDLE_TYPE);
+                let fd = self
+                    .memory
+                    .get_memory_fd(&info)
+                    .expect("Failed to export memory fd");
+                OwnedHandle::from_raw_fd(fd)
+            };
+            #[cfg(windows)]
+            let handle = {
+                let info = vk::MemoryGetWin32HandleInfoKHR::default()
+                    .memory(memory)
+                    .handle_type(MEMORY_HANDLE_TYPE);
+                let handle = self
+                    .memory
+                    .get_memory_win32_handle(&info)
+                    .expect("Failed to export memory handle");
+                OwnedHandle::from_raw_handle(handle as _)
+            };
+            handle
+        }
+    }
+
+    // A new handle to `semaphore`, owned by the caller
+    fn semaphore_handle(&self, semaphore: vk::Semaphore) -> OwnedHandle {
+        unsafe {
+            #[cfg(unix)]
+            let handle = {
+                let info = vk::SemaphoreGetFdInfoKHR::default()
+                    .semaphore(semaphore)
+                    .handle_type(SEMAPHORE_HANDLE_TYPE);
+                let fd = self
+                    .semaphore
+                    .get_semaphore_fd(&info)
+                    .expect("Failed to export semaphore fd");
+                OwnedHandle::from_raw_fd(fd)
+            };
+            #[cfg(windows)]
+            let handle = {
+                let info = vk::SemaphoreGetWin32HandleInfoKHR::default()
+                    .semaphore(semaphore)
+                    .handle_type(SEMAPHORE_HANDLE_TYPE);
+                let handle = self
+                    .semaphore
+                    .get_semaphore_win32_handle(&info)
+                    .expect("Failed to export semaphore handle");
+                OwnedHandle::from_raw_handle(handle as _)
+            };
+            handle
+        }
+    }
+}
+
+// One exported image and the handle to its memory. Importers need the size,
+// extent and format to recreate the image on their side.
+pub struct ExportedImage {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub handle: OwnedHandle,
+    pub size: vk::DeviceSize,
+    // The driver wants a dedicated allocation; importers must use one too
+    pub dedicated: bool,
+}
+
+// Copies of the primary window's frames in images whose memory can be imported
+// by another API or process, such as CUDA, OpenGL (GL_EXT_memory_object) or
+// another Vulkan device, without going through the CPU. Frame N is copied into
+// image N % FRAMES_IN_FLIGHT and then signals N on an exported timeline
+// semaphore; a consumer waits for N and must be done reading before frame
+// N + FRAMES_IN_FLIGHT overwrites the image. The images are released to
+// VK_QUEUE_FAMILY_EXTERNAL in GENERAL layout, optimal tiling.
+pub struct FrameExport {
+    extensions: HandleExtensions,
+    images: Vec<ExportedImage>,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    semaphore: vk::Semaphore,
+    semaphore_handle: OwnedHandle,
+    // Bumped whenever the images are recreated, so consumers know to re-import
+    generation: u32,
+}
+
+impl FrameExport {
+    // Creates the exported semaphore; the images follow with the first frame.
+    // None when the device can't export them.
+    pub fn new(gpu: &GpuContext, format: vk::Format) -> Option<Self> {
+        if !gpu.capabilities.external_memory
+            || !Self::image_exportable(gpu, format).0
+            || !Self::semaphore_exportable(gpu)
+        {
+            return None;
+        }
+        let mut export_info =
+            vk::ExportSemaphoreCreateInfo::default().handle_types(SEMAPHORE_HANDLE_TYPE);
+        let mut type_info = vk::SemaphoreTypeCreateInfo::default()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+        let create_info = vk::SemaphoreCreateInfo::default()
+            .push_next(&mut type_info)
+            .push_next(&mut export_info);
+        let semaphore = unsafe {
+            gpu.device
+                .create_semaphore(&create_info, None)
+                .expect("Failed to create exported semaphore")
+        };
+        gpu.set_name(semaphore, "exported frame semaphore");
+        let extensions = HandleExtensions::new(gpu);
+        let semaphore_handle = extensions.semaphore_handle(semaphore);
+        Some(Self {
+            extensions,
+            images: Vec::new(),
+            extent: vk::Extent2D::default(),
+            format,
+            semaphore,
+            semaphore_handle,
+            generation: 0,
+        })
+    }
+
+    // Whether optimal-tiled `format` images with TRANSFER_DST can be exported,
+    // and whether they need a dedicated allocation
+    fn image_exportable(gpu: &GpuContext, format: vk::Format) -> (bool, bool) {
+        let mut external_info =
+            vk::PhysicalDeviceExternalImageFormatInfo::default().handle_type(MEMORY_HANDLE_TYPE);
+        let format_info = vk::PhysicalDeviceImageFormatInfo2::default()
+            .format(format)
+            .ty(vk::ImageType::TYPE_2D)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::TRANSFER_DST)
+            .push_next(&mut external_info);
+        let mut external_properties = vk::ExternalImageFormatProperties::default();
+        let mut properties =
+            vk::ImageFormatProperties2::default().push_next(&mut external_properties);
+        let supported = unsafe {
+            gpu.instance.get_physical_device_image_format_properties2(
+                gpu.physical_device,
+                &format_info,
+                &mut properties,
+            )
+        }
+        .is_ok();
+        let features = external_properties
+            .external_memory_properties
+            .external_memory_features;
+        (
+            supported && features.contains(vk::ExternalMemoryFeatureFlags::EXPORTABLE),
+            features.contains(vk::ExternalMemoryFeatureFlags::DEDICATED_ONLY),
+        )
+    }
+
+    fn semaphore_exportable(gpu: &GpuContext) -> bool {
+        let mut type_info =
+            vk::SemaphoreTypeCreateInfo::default().semaphore_type(vk::SemaphoreType::TIMELINE);
+        let info = vk::PhysicalDeviceExternalSemaphoreInfo::default()
+            .handle_type(SEMAPHORE_HANDLE_TYPE)
+            .push_next(&mut type_info);
+        let mut properties = vk::ExternalSemaphoreProperties::default();
+        unsafe {
+            gpu.instance.get_physical_device_external_semaphore_properties(
+                gpu.physical_device,
+                &info,
+                &mut properties,
+            );
+        }
+        properties
+            .external_semaphore_features
+            .contains(vk::ExternalSemaphoreFeatureFlags::EXPORTABLE)
+    }
+
+    pub fn images(&self) -> &[ExportedImage] {
+        &self.images
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    // Timeline semaphore signalled with each frame's number once its copy is done
+    pub fn semaphore(&self) -> vk::Semaphore {
+        self.semaphore
+    }
+
+    pub fn semaphore_handle(&self) -> &OwnedHandle {
+        &self.semaphore_handle
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    // Copies frames of `format` from now on, with new images from the next
+    // frame. False, changing nothing, when such images can't be exported. Caller
+    // ensures the device is idle.
+    pub fn set_format(&mut self, gpu: &GpuContext, format: vk::Format) -> bool {
+        if !Self::image_exportable(gpu, format).0 {
+            return false;
+        }
+        self.destroy_images(gpu);
+        self.format = format;
+        true
+    }
+
+    // Exported images for `extent`, replacing the old ones after waiting for the
+    // device. Their handles are new, so consumers have to import them again.
+    fn recreate_images(&mut self, gpu: &GpuContext, extent: vk::Extent2D) {
+        unsafe {
+            gpu.device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        self.destroy_images(gpu);
+        let dedicated = Self::image_exportable(gpu, self.format).1;
+        for i in 0..FRAMES_IN_FLIGHT {
+            let mut external_info =
+                vk::ExternalMemoryImageCreateInfo::default().handle_types(MEMORY_HANDLE_TYPE);
+            let create_info = vk::ImageCreateInfo::default()
+                .image_type(vk::ImageType::TYPE_2D)
+                .format(self.format)
+                .extent(vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                })
+                .mip_levels(1)
+                .array_layers(1)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .usage(vk::ImageUsageFlags::TRANSFER_DST)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .push_next(&mut external_info);
+            let image = unsafe {
+                gpu.device
+                    .create_image(&create_info, None)
+                    .expect("Failed to create exported image")
+            };
+            gpu.set_name(image, &format!("exported frame image {}", i));
+            let requirements = unsafe { gpu.device.get_image_memory_requirements(image) };
+            let mut export_info =
+                vk::ExportMemoryAllocateInfo::default().handle_types(MEMORY_HANDLE_TYPE);
+            let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+            let mut alloc_info = vk::MemoryAllocateInfo::default()
+                .allocation_size(requirements.size)
+                .memory_type_index(gpu.find_memory_type(
+                    requirements.memory_type_bits,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                ))
+                .push_next(&mut export_info);
+            if dedicated {
+                alloc_info = alloc_info.push_next(&mut dedicated_info);
+            }
+            let memory = gpu.allocate_memory_with(&alloc_info, MemoryCategory::Images);
+            unsafe {
+                gpu.device
+                    .bind_image_memory(image, memory, 0)
+                    .expect("Failed to bind exported image memory");
+            }
+            self.images.push(ExportedImage {
+                image,
+                memory,
+                handle: self.extensions.memory_handle(memory),
+                size: requirements.size,
+                dedicated,
+            });
+        }
+        self.extent = extent;
+        self.generation += 1;
+    }
+
+    // Records a copy of `image`, a swapchain image in PRESENT_SRC_KHR with the
+    // export's format, into the image for `frame`. The swapchain image is back in
+    // PRESENT_SRC_KHR afterwards. Signal `semaphore` with `frame` in the same
+    // submit. Images are recreated first when `extent` changed.
+    pub fn record_copy(
+        &mut self,
+        gpu: &GpuContext,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        frame: u64,
+    ) {
+        if self.images.is_empty() || self.extent != extent {
+            self.recreate_images(gpu, extent);
+            println!("{}", self);
+        }
+        let exported = self.images[frame as usize % FRAMES_IN_FLIGHT].image;
+        let color_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let color_written = Access::new(
+            vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        );
+        let transfer_read = Access::new(
+            vk::PipelineStageFlags2::COPY,
+            vk::AccessFlags2::TRANSFER_READ,
+        );
+        let transfer_write = Access::new(
+            vk::PipelineStageFlags2::COPY,
+            vk::AccessFlags2::TRANSFER_WRITE,
+        );
+        // Take the image back from the consumer; its old contents don't matter
+        let mut acquire = Barriers::new();
+        acquire
+            .image(
+                exported,
+                color_range,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                Access::NONE,
+                transfer_write,
+            )
+            .transfer_ownership(vk::QUEUE_FAMILY_EXTERNAL, gpu.queue_family_index);
+        acquire
+            .image(
+                image,
+                color_range,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                color_written,
+                transfer_read,
+            )
+            .record(gpu, command_buffer);
+        let layers = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        unsafe {
+            gpu.device.cmd_copy_image(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                exported,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::ImageCopy {
+                    src_subresource: layers,
+                    src_offset: vk::Offset3D::default(),
+                    dst_subresource: layers,
+                    dst_offset: vk::Offset3D::default(),
+                    extent: vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    },
+                }],
+            );
+        }
+        // Hand the copy to the consumer, and the swapchain image back to
+        // presentation, which waits on the frame's semaphore
+        let mut release = Barriers::new();
+        release
+            .image(
+                exported,
+                color_range,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::GENERAL,
+                transfer_write,
+                Access::NONE,
+            )
+            .transfer_ownership(gpu.queue_family_index, vk::QUEUE_FAMILY_EXTERNAL);
+        release
+            .image(
+                image,
+                color_range,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                transfer_read,
+                Access::NONE,
+            )
+            .record(gpu, command_buffer);
+    }
+
+    fn destroy_images(&mut self, gpu: &GpuContext) {
+        for exported in self.images.drain(..) {
+            unsafe {
+                gpu.device.destroy_image(exported.image, None);
+            }
+            gpu.free_memory(exported.memory);
+        }
+    }
+
+    // Caller ensures the device is idle
+    pub fn destroy(mut self, gpu: &GpuContext) {
+        self.destroy_images(gpu);
+        unsafe {
+            gpu.device.destroy_semaphore(self.semaphore, None);
+        }
+    }
+}
+
+// The handles a consumer needs, with the process id to duplicate them from
+// (pidfd_getfd on Linux, DuplicateHandle on Windows)
+impl std::fmt::Display for FrameExport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Exporting frames from process {}, generation {}: {}x{} {:?}",
+            std::process::id(),
+            self.generation,
+            self.extent.width,
+            self.extent.height,
+            self.format
+        )?;
+        for (i, exported) in self.images.iter().enumerate() {
+            writeln!(
+                f,
+                "  image {}: memory {:?}, {} bytes{}",
+                i,
+                exported.handle,
+                exported.size,
+                if exported.dedicated { ", dedicated" } else { "" }
+            )?;
+        }
+        write!(f, "  timeline semaphore {:?}", self.semaphore_handle)
+    }
+}
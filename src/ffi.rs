@@ -0,0 +1,249 @@
+// C API for hosting the renderer in a non-Rust application (`ffi` feature). The
+// host owns the window and the frame loop: it creates a renderer for its window,
+// then calls `vibe_renderer_tick` and `vibe_renderer_render` once per frame and
+// `vibe_renderer_resize` when the window changes size. include/vulkan_vibe.h is
+// generated from this file by cbindgen.
+//
+// Vulkan errors panic inside the renderer. Every entry point catches the panic,
+// since unwinding into C would abort the host: create returns null and the
+// others return without doing anything.
+
+use std::ffi::{c_char, c_ulong, c_void, CStr};
+use std::num::NonZeroIsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+
+use winit::raw_window_handle::{
+    AppKitDisplayHandle, AppKitWindowHandle, RawDisplayHandle, RawWindowHandle,
+    UiKitDisplayHandle, UiKitWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
+    Win32WindowHandle, WindowsDisplayHandle, XlibDisplayHandle, XlibWindowHandle,
+};
+
+use crate::clock::{SimulationClock, FIXED_DT};
+use crate::input::Input;
+use crate::renderer::Renderer;
+use crate::rng::{Rng, DEFAULT_SEED};
+use crate::scene::{DrawList, Scene, SceneContext};
+use crate::scenes::{SceneConfig, SceneKind};
+use crate::sound::SoundQueue;
+use crate::window::WindowContext;
+
+// The window system a `VibeWindowHandle` comes from
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VibeWindowKind {
+    Win32 = 0,
+    Xlib = 1,
+    Wayland = 2,
+    AppKit = 3,
+    UiKit = 4,
+}
+
+impl VibeWindowKind {
+    // None for a value C passed that isn't one of the kinds
+    fn from_raw(kind: u32) -> Option<Self> {
+        match kind {
+            0 => Some(VibeWindowKind::Win32),
+            1 => Some(VibeWindowKind::Xlib),
+            2 => Some(VibeWindowKind::Wayland),
+            3 => Some(VibeWindowKind::AppKit),
+            4 => Some(VibeWindowKind::UiKit),
+            _ => None,
+        }
+    }
+}
+
+// The host's window, as the pointers its window system hands out
+#[repr(C)]
+pub struct VibeWindowHandle {
+    // A `VibeWindowKind`. Plain u32, so an out-of-range value from C is refused
+    // rather than read as an enum.
+    pub kind: u32,
+    // HINSTANCE (may be null), Display* or wl_display*; unused for AppKit and UIKit
+    pub display: *mut c_void,
+    // HWND, wl_surface*, NSView* or UIView*; unused for Xlib
+    pub window: *mut c_void,
+    // The X11 Window for Xlib
+    pub xlib_window: c_ulong,
+}
+
+impl VibeWindowHandle {
+    // None for an unknown kind or when a pointer the kind needs is null
+    fn to_raw(&self) -> Option<(RawDisplayHandle, RawWindowHandle)> {
+        let window = NonNull::new(self.window);
+        Some(match VibeWindowKind::from_raw(self.kind)? {
+            VibeWindowKind::Win32 => {
+                let mut handle = Win32WindowHandle::new(NonZeroIsize::new(self.window as isize)?);
+                handle.hinstance = NonZeroIsize::new(self.display as isize);
+                (
+                    WindowsDisplayHandle::new().into(),
+                    RawWindowHandle::Win32(handle),
+                )
+            }
+            VibeWindowKind::Xlib => (
+                XlibDisplayHandle::new(Some(NonNull::new(self.display)?), 0).into(),
+                XlibWindowHandle::new(self.xlib_window).into(),
+            ),
+            VibeWindowKind::Wayland => (
+                WaylandDisplayHandle::new(NonNull::new(self.display)?).into(),
+                WaylandWindowHandle::new(window?).into(),
+            ),
+            VibeWindowKind::AppKit => (
+                AppKitDisplayHandle::new().into(),
+                AppKitWindowHandle::new(window?).into(),
+            ),
+            VibeWindowKind::UiKit => (
+                UiKitDisplayHandle::new().into(),
+                UiKitWindowHandle::new(window?).into(),
+            ),
+        })
+    }
+}
+
+// Runs an entry point's body, returning None if it panicked. The panic hook has
+// already printed the message.
+fn catch_panic<T>(function: &str, body: impl FnOnce() -> T) -> Option<T> {
+    let result = panic::catch_unwind(AssertUnwindSafe(body));
+    if result.is_err() {
+        eprintln!("{}: the renderer panicked", function);
+    }
+    result.ok()
+}
+
+// A renderer and the scene it runs, opaque to C
+pub struct VibeRenderer {
+    renderer: Renderer,
+    window: WindowContext,
+    scene: Box<dyn Scene>,
+    draws: DrawList,
+    clock: SimulationClock,
+    rng: Rng,
+    input: Input,
+    sounds: SoundQueue,
+}
+
+// Creates a renderer drawing into `window`, `width` x `height` physical pixels,
+// running the built-in scene called `scene` ("bouncing" when null). Returns null
+// when the handle or scene name is invalid or Vulkan setup fails. The window
+// must outlive the renderer.
+#[no_mangle]
+pub unsafe extern "C" fn vibe_renderer_create(
+    window: *const VibeWindowHandle,
+    width: u32,
+    height: u32,
+    scene: *const c_char,
+) -> *mut VibeRenderer {
+    let Some((display_handle, window_handle)) = window.as_ref().and_then(VibeWindowHandle::to_raw)
+    else {
+        eprintln!("vibe_renderer_create: invalid window handle");
+        return std::ptr::null_mut();
+    };
+    let kind = if scene.is_null() {
+        Some(SceneKind::Bouncing)
+    } else {
+        CStr::from_ptr(scene).to_str().ok().and_then(SceneKind::from_name)
+    };
+    let Some(kind) = kind else {
+        eprintln!("vibe_renderer_create: unknown scene");
+        return std::ptr::null_mut();
+    };
+    catch_panic("vibe_renderer_create", || {
+        let (mut renderer, window) =
+            Renderer::from_raw_handles(display_handle, window_handle, width, height);
+        let mut scene = kind.create(&SceneConfig::default());
+        scene.create_resources(&mut renderer);
+        Box::into_raw(Box::new(VibeRenderer {
+            renderer,
+            window,
+            scene,
+            draws: DrawList::new(),
+            clock: SimulationClock::new(FIXED_DT),
+            rng: Rng::new(DEFAULT_SEED),
+            input: Input::new(),
+            sounds: SoundQueue::new(),
+        }))
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+// The window is now `width` x `height` physical pixels; `scale_factor` is its
+// physical pixels per logical pixel
+#[no_mangle]
+pub unsafe extern "C" fn vibe_renderer_resize(
+    renderer: *mut VibeRenderer,
+    width: u32,
+    height: u32,
+    scale_factor: f32,
+) {
+    let Some(vibe) = renderer.as_mut() else {
+        return;
+    };
+    catch_panic("vibe_renderer_resize", || {
+        vibe.renderer.set_scale_factor(scale_factor);
+        vibe.renderer.resize(&mut vibe.window, width, height);
+    });
+}
+
+// Advances the scene by `dt` seconds of wall-clock time and collects its draws
+#[no_mangle]
+pub unsafe extern "C" fn vibe_renderer_tick(renderer: *mut VibeRenderer, dt: f32) {
+    let Some(vibe) = renderer.as_mut() else {
+        return;
+    };
+    catch_panic("vibe_renderer_tick", || {
+        vibe.renderer.wait_for_present(&vibe.window);
+        let steps = vibe.clock.advance(dt);
+        let mut context = SceneContext {
+            renderer: &mut vibe.renderer,
+            input: &vibe.input,
+            rng: &mut vibe.rng,
+            sounds: &mut vibe.sounds,
+            stick: glam::Vec2::ZERO,
+            frame_dt: dt,
+            steps,
+            fixed_dt: FIXED_DT,
+        };
+        vibe.scene.update(&mut context);
+        // The host has no sound output
+        vibe.sounds.clear();
+        vibe.draws.clear();
+        vibe.scene.record_draws(&mut vibe.draws);
+        vibe.input.end_frame();
+    });
+}
+
+// Draws the draws from the last tick and presents them to the window
+#[no_mangle]
+pub unsafe extern "C" fn vibe_renderer_render(renderer: *mut VibeRenderer) {
+    let Some(vibe) = renderer.as_mut() else {
+        return;
+    };
+    catch_panic("vibe_renderer_render", || {
+        vibe.renderer.render(
+            &mut [&mut vibe.window],
+            &vibe.draws.circles,
+            &vibe.draws.canvas,
+            &vibe.draws.sprites,
+        );
+    });
+}
+
+// Frees the scene's resources, the window's swapchain and surface, and then the
+// device and instance
+#[no_mangle]
+pub unsafe extern "C" fn vibe_renderer_destroy(renderer: *mut VibeRenderer) {
+    if renderer.is_null() {
+        return;
+    }
+    let mut vibe = Box::from_raw(renderer);
+    catch_panic("vibe_renderer_destroy", move || {
+        vibe.scene.destroy_resources(&mut vibe.renderer);
+        let VibeRenderer {
+            mut renderer,
+            window,
+            ..
+        } = *vibe;
+        renderer.close_window(window);
+        renderer.destroy();
+    });
+}
@@ -0,0 +1,104 @@
+use ash::vk;
+use glam::{DVec2, Vec2};
+
+use crate::gpu::GpuContext;
+use crate::pipeline::{PipelineDesc, PipelineManager};
+
+// Below this many complex units per pixel, neighbouring pixels near |c| = 2
+// are only a few float ulps apart and the shader switches to double-single math
+const HIGH_PRECISION_SCALE: f64 = 1e-6;
+
+// Mirrors the push constant block in shaders/fractal_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FractalInputs {
+    center: [f32; 4],
+    scale: [f32; 2],
+    resolution: [f32; 2],
+    max_iterations: u32,
+    high_precision: u32,
+}
+
+// Splits an f64 into a float and the float of what's left over, which the
+// shader recombines for roughly 48 bits of mantissa
+fn split_f64(value: f64) -> [f32; 2] {
+    let hi = value as f32;
+    [hi, (value - hi as f64) as f32]
+}
+
+impl FractalInputs {
+    // `center` is the complex number at the middle of the window, `scale` the
+    // complex units per pixel
+    pub fn new(center: DVec2, scale: f64, resolution: Vec2, max_iterations: u32) -> Self {
+        let [real_hi, real_lo] = split_f64(center.x);
+        let [imaginary_hi, imaginary_lo] = split_f64(center.y);
+        Self {
+            center: [real_hi, real_lo, imaginary_hi, imaginary_lo],
+            scale: split_f64(scale),
+            resolution: resolution.to_array(),
+            max_iterations,
+            high_precision: (scale < HIGH_PRECISION_SCALE) as u32,
+        }
+    }
+
+    pub fn is_high_precision(&self) -> bool {
+        self.high_precision != 0
+    }
+}
+
+// Fullscreen Mandelbrot set, computed per pixel in the fragment shader from the
+// view in `inputs`
+pub struct FractalPass {
+    pub inputs: FractalInputs,
+    pipeline: PipelineDesc,
+}
+
+impl FractalPass {
+    pub fn new(gpu: &GpuContext, pipelines: &mut PipelineManager) -> Self {
+        let shaders = pipelines.register_shaders(
+            gpu,
+            "fractal",
+            include_bytes!("../shaders/fullscreen_vert.spv"),
+            include_bytes!("../shaders/fractal_frag.spv"),
+        );
+        let pipeline_layout = pipelines.interface(shaders).create_pipeline_layout(
+            gpu,
+            &[],
+            size_of::<FractalInputs>(),
+        );
+        let pipeline = PipelineDesc::new(shaders, pipeline_layout);
+        pipelines.prepare(gpu, &pipeline);
+        println!("Fractal pass created");
+
+        Self {
+            inputs: FractalInputs::default(),
+            pipeline,
+        }
+    }
+
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let pipeline = pipelines.get(gpu, &self.pipeline);
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.inputs),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, pipelines: &mut PipelineManager) {
+        pipelines.release_shaders(device, self.pipeline.shaders);
+        unsafe { device.destroy_pipeline_layout(self.pipeline.layout, None) };
+    }
+}
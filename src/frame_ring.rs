@@ -0,0 +1,167 @@
+use ash::vk;
+
+use crate::gpu::GpuContext;
+use crate::memory::MemoryCategory;
+
+// Frames the CPU may record ahead of the GPU. Each gets its own ring region, so
+// data for one frame is never overwritten while an earlier frame may still read it.
+pub const FRAMES_IN_FLIGHT: usize = 2;
+// Regions start at least this big and grow in powers of two
+const MIN_FRAME_SIZE: vk::DeviceSize = 256 * 1024;
+
+// One HOST_VISIBLE | HOST_COHERENT buffer, mapped once for its whole lifetime and
+// split into a region per frame in flight. Per-frame data (canvas geometry, sprite
+// instances, uniforms) is bump-allocated from the current region; `begin_frame`
+// moves to the next region and forgets everything pushed there before.
+pub struct FrameRing {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    // Where shaders find `buffer`, with buffer device address
+    address: Option<vk::DeviceAddress>,
+    mapped: *mut u8,
+    frame_size: vk::DeviceSize,
+    // Every push starts on a multiple of this, so offsets are valid for any usage
+    alignment: vk::DeviceSize,
+    frame: usize,
+    cursor: vk::DeviceSize,
+    // Bumped on every reallocation; handles can be reused after a destroy, so
+    // descriptor sets compare this rather than the buffer
+    generation: u64,
+}
+
+impl FrameRing {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let limits = unsafe {
+            gpu.instance
+                .get_physical_device_properties(gpu.physical_device)
+                .limits
+        };
+        let alignment = limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment)
+            .max(16);
+        let mut ring = Self {
+            buffer: vk::Buffer::null(),
+            memory: vk::DeviceMemory::null(),
+            address: None,
+            mapped: std::ptr::null_mut(),
+            frame_size: 0,
+            alignment,
+            frame: 0,
+            cursor: 0,
+            generation: 0,
+        };
+        ring.allocate(gpu, MIN_FRAME_SIZE);
+        ring
+    }
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    // Device address of `buffer()`, so shaders can read pushed data without a
+    // descriptor or vertex binding. Offsets from `push` add to it. Changes along
+    // with the buffer.
+    pub fn address(&self) -> Option<vk::DeviceAddress> {
+        self.address
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Bytes a push of `size` bytes takes up, including alignment padding
+    pub fn padded_size(&self, size: usize) -> vk::DeviceSize {
+        (size as vk::DeviceSize).next_multiple_of(self.alignment)
+    }
+
+    // Moves to the next frame's region. `required` is the total padded size of
+    // everything the frame will push; the ring grows first if it doesn't fit, which
+    // waits for the GPU and invalidates offsets and the buffer handle from earlier
+    // frames.
+    pub fn begin_frame(&mut self, gpu: &GpuContext, required: vk::DeviceSize) {
+        if required > self.frame_size {
+            unsafe {
+                gpu.device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+                gpu.device.unmap_memory(self.memory);
+                gpu.device.destroy_buffer(self.buffer, None);
+            }
+            gpu.free_memory(self.memory);
+            self.allocate(gpu, required.next_power_of_two());
+        }
+        self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+        self.cursor = 0;
+    }
+
+    // Caller ensures the device is idle
+    pub fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device.unmap_memory(self.memory);
+            gpu.device.destroy_buffer(self.buffer, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+
+    // Copies `data` into the current region and returns its offset in `buffer()`
+    pub fn push(&mut self, data: &[u8]) -> vk::DeviceSize {
+        self.push_parts(&[data])
+    }
+
+    // Like `push`, but packs several slices back to back without padding between
+    // them, e.g. vertices followed by indices
+    pub fn push_parts(&mut self, parts: &[&[u8]]) -> vk::DeviceSize {
+        let length = parts.iter().map(|part| part.len()).sum();
+        let size = self.padded_size(length);
+        assert!(
+            self.cursor + size <= self.frame_size,
+            "Frame ring overflow: reserve more in begin_frame"
+        );
+        let offset = self.frame as vk::DeviceSize * self.frame_size + self.cursor;
+        let mut write = offset as usize;
+        for part in parts {
+            unsafe {
+                self.mapped
+                    .add(write)
+                    .copy_from_nonoverlapping(part.as_ptr(), part.len());
+            }
+            write += part.len();
+        }
+        self.cursor += size;
+        offset
+    }
+
+    fn allocate(&mut self, gpu: &GpuContext, frame_size: vk::DeviceSize) {
+        let size = frame_size * FRAMES_IN_FLIGHT as vk::DeviceSize;
+        let buffer_device_address = gpu.capabilities.buffer_device_address;
+        let mut usage = vk::BufferUsageFlags::VERTEX_BUFFER
+            | vk::BufferUsageFlags::INDEX_BUFFER
+            | vk::BufferUsageFlags::UNIFORM_BUFFER
+            | vk::BufferUsageFlags::STORAGE_BUFFER
+            // Per-frame image contents such as the shading rate map are copied out
+            | vk::BufferUsageFlags::TRANSFER_SRC;
+        if buffer_device_address {
+            usage |= vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+        }
+        (self.buffer, self.memory) = gpu.create_buffer_in(
+            MemoryCategory::FrameRing,
+            size,
+            usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        gpu.set_name(self.buffer, "frame ring buffer");
+        self.address = buffer_device_address.then(|| gpu.buffer_address(self.buffer));
+        self.mapped = unsafe {
+            gpu.device
+                .map_memory(self.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .expect("Failed to map frame ring memory") as *mut u8
+        };
+        self.frame_size = frame_size;
+        self.generation += 1;
+        println!(
+            "Frame ring (re)created: {} regions of {} bytes",
+            FRAMES_IN_FLIGHT, frame_size
+        );
+    }
+}
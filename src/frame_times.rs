@@ -0,0 +1,185 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use glam::Vec2;
+use serde::Serialize;
+
+use crate::canvas::Canvas;
+
+// Frames kept for the histogram and the percentiles, a few seconds' worth
+const HISTORY_LEN: usize = 240;
+// The histogram spans zero to twice the spike threshold; slower frames pile
+// into the last bucket
+const HISTOGRAM_BUCKETS: usize = 40;
+const HISTOGRAM_HEIGHT: f32 = 40.0;
+const HISTOGRAM_GAP: f32 = 1.0;
+
+// Where the renderer's share of a frame's CPU time went
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FramePhases {
+    // Waiting for the previous frame's fence, then acquiring swapchain images
+    pub acquire: Duration,
+    // Uploading the frame's data and recording the command buffer
+    pub record: Duration,
+    pub submit: Duration,
+    // Queueing every window's image for presentation
+    pub present: Duration,
+}
+
+impl FramePhases {
+    pub fn total(&self) -> Duration {
+        self.acquire + self.record + self.submit + self.present
+    }
+}
+
+// The time since `mark`, moving it to now, for timing consecutive phases
+pub fn lap(mark: &mut Instant) -> Duration {
+    let now = Instant::now();
+    let elapsed = now - *mark;
+    *mark = now;
+    elapsed
+}
+
+// A frame over the spike threshold, logged as one line of JSON
+#[derive(Serialize)]
+struct StutterEvent {
+    event: &'static str,
+    frame: u64,
+    cpu_ms: f64,
+    threshold_ms: f64,
+    // Of the frames before it, for comparison
+    median_ms: f64,
+    // Everything outside the renderer: input, simulation and draw lists
+    update_ms: f64,
+    acquire_ms: f64,
+    record_ms: f64,
+    submit_ms: f64,
+    present_ms: f64,
+}
+
+// CPU time of the most recent frames, from the start of the simulation step to
+// the frame's present being queued. Pacing waits aren't counted, so a capped
+// frame rate doesn't read as slow frames.
+pub struct FrameTimes {
+    times: VecDeque<Duration>,
+    spike_threshold: Duration,
+    spikes: u64,
+}
+
+impl FrameTimes {
+    pub fn new(spike_threshold: Duration) -> Self {
+        Self {
+            times: VecDeque::with_capacity(HISTORY_LEN),
+            spike_threshold,
+            spikes: 0,
+        }
+    }
+
+    // Adds a frame that took `total`, of which the renderer spent `phases`.
+    // Logs a stutter event when it's over the spike threshold.
+    pub fn push(&mut self, frame: u64, total: Duration, phases: FramePhases) {
+        if total > self.spike_threshold {
+            self.spikes += 1;
+            let event = StutterEvent {
+                event: "stutter",
+                frame,
+                cpu_ms: millis(total),
+                threshold_ms: millis(self.spike_threshold),
+                median_ms: millis(self.percentile(0.5)),
+                update_ms: millis(total.saturating_sub(phases.total())),
+                acquire_ms: millis(phases.acquire),
+                record_ms: millis(phases.record),
+                submit_ms: millis(phases.submit),
+                present_ms: millis(phases.present),
+            };
+            println!("{}", serde_json::to_string(&event).unwrap());
+        }
+        if self.times.len() == HISTORY_LEN {
+            self.times.pop_front();
+        }
+        self.times.push_back(total);
+    }
+
+    // Frames over the threshold since startup
+    pub fn spikes(&self) -> u64 {
+        self.spikes
+    }
+
+    // The frame time `fraction` of the way through the sorted history, zero
+    // before the first frame
+    pub fn percentile(&self, fraction: f64) -> Duration {
+        let mut sorted: Vec<_> = self.times.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() as f64 - 1.0) * fraction).round().max(0.0) as usize;
+        sorted.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn overlay_size(&self, width: f32) -> Vec2 {
+        Vec2::new(width, HISTOGRAM_HEIGHT)
+    }
+
+    // A histogram of the history, scaled to its fullest bucket. Frames under the
+    // threshold are green and the ones over it red; a white tick marks the
+    // threshold itself, halfway across.
+    pub fn draw_overlay(&self, canvas: &mut Canvas, origin: Vec2, width: f32) {
+        canvas.fill_rect(
+            origin,
+            Vec2::new(width, HISTOGRAM_HEIGHT),
+            [0.1, 0.1, 0.1, 0.7],
+        );
+        let span = self.spike_threshold.as_secs_f64() * 2.0;
+        let mut buckets = [0u32; HISTOGRAM_BUCKETS];
+        for time in &self.times {
+            let bucket = (time.as_secs_f64() / span * HISTOGRAM_BUCKETS as f64) as usize;
+            buckets[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+        }
+        let tallest = buckets.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let bucket_width = width / HISTOGRAM_BUCKETS as f32;
+        for (i, &count) in buckets.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let color = if i < HISTOGRAM_BUCKETS / 2 {
+                [0.2, 0.9, 0.3, 0.9]
+            } else {
+                [0.9, 0.2, 0.2, 0.9]
+            };
+            // A single frame still gets a visible sliver
+            let height = (count as f32 / tallest * HISTOGRAM_HEIGHT).max(1.0);
+            canvas.fill_rect(
+                origin + Vec2::new(i as f32 * bucket_width, HISTOGRAM_HEIGHT - height),
+                Vec2::new(bucket_width - HISTOGRAM_GAP, height),
+                color,
+            );
+        }
+        canvas.fill_rect(
+            origin + Vec2::new(width / 2.0 - 1.0, -2.0),
+            Vec2::new(2.0, HISTOGRAM_HEIGHT + 4.0),
+            [1.0, 1.0, 1.0, 1.0],
+        );
+    }
+}
+
+impl fmt::Display for FrameTimes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "CPU frame time: median {:.2} ms, p99 {:.2} ms, max {:.2} ms over {} frames",
+            millis(self.percentile(0.5)),
+            millis(self.percentile(0.99)),
+            millis(self.percentile(1.0)),
+            self.times.len()
+        )?;
+        writeln!(
+            f,
+            "Stutters over {:.1} ms: {}",
+            millis(self.spike_threshold),
+            self.spikes
+        )
+    }
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,82 @@
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use glam::Vec2;
+
+use crate::input::{Action, Input};
+
+// Polls connected controllers through gilrs. Button presses are forwarded to
+// `Input` as actions; the left stick of the most recently used pad is exposed
+// as an analog steering vector.
+pub struct GamepadInput {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+}
+
+impl GamepadInput {
+    // None if the platform backend could not be initialized
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                for (id, gamepad) in gilrs.gamepads() {
+                    println!("Gamepad {} connected: {}", id, gamepad.name());
+                }
+                let active = gilrs.gamepads().next().map(|(id, _)| id);
+                Some(Self { gilrs, active })
+            }
+            Err(e) => {
+                println!("Gamepad support unavailable: {:?}", e);
+                None
+            }
+        }
+    }
+
+    // Drains pending controller events; call once per frame before the update step
+    pub fn poll(&mut self, input: &mut Input) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    println!("Gamepad {} connected", event.id);
+                    self.active.get_or_insert(event.id);
+                }
+                EventType::Disconnected => {
+                    println!("Gamepad {} disconnected", event.id);
+                    if self.active == Some(event.id) {
+                        self.active = self.gilrs.gamepads().next().map(|(id, _)| id);
+                    }
+                }
+                EventType::ButtonPressed(button, _) => {
+                    self.active = Some(event.id);
+                    if let Some(action) = Self::button_action(button) {
+                        input.trigger(action);
+                    }
+                }
+                EventType::AxisChanged(..) => {
+                    self.active = Some(event.id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn button_action(button: Button) -> Option<Action> {
+        match button {
+            Button::South => Some(Action::SpawnBall),
+            Button::East => Some(Action::ClearBalls),
+            Button::Start => Some(Action::Pause),
+            Button::DPadUp => Some(Action::SpeedUp),
+            Button::DPadDown => Some(Action::SpeedDown),
+            _ => None,
+        }
+    }
+
+    // Left stick in -1..1 with y pointing down to match window space
+    pub fn left_stick(&self) -> Vec2 {
+        let Some(gamepad) = self.active.map(|id| self.gilrs.gamepad(id)) else {
+            return Vec2::ZERO;
+        };
+        let stick = Vec2::new(
+            gamepad.value(Axis::LeftStickX),
+            -gamepad.value(Axis::LeftStickY),
+        );
+        stick.clamp_length_max(1.0)
+    }
+}
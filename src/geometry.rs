@@ -0,0 +1,559 @@
+use glam::Vec2;
+use std::f32::consts::{PI, TAU};
+
+// How finely curved outlines are split into straight edges
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tessellation {
+    // Always use this many segments for a full turn (scaled down for partial arcs)
+    Segments(u32),
+    // Pick the segment count so no edge strays further than this from the true curve
+    Tolerance(f32),
+}
+
+impl Tessellation {
+    pub fn segments_for(&self, radius: f32, sweep: f32) -> u32 {
+        let fraction = (sweep.abs() / TAU).min(1.0);
+        let segments = match *self {
+            Tessellation::Segments(segments) => (segments as f32 * fraction).ceil() as u32,
+            Tessellation::Tolerance(tolerance) => {
+                if radius <= tolerance {
+                    return 3;
+                }
+                let step = 2.0 * (1.0 - tolerance / radius).acos();
+                (sweep.abs() / step).ceil() as u32
+            }
+        };
+        segments.clamp(3, 1024)
+    }
+
+    // Segments for a curve that strays `deviation / n²` from its chord when split
+    // evenly into n of them, as Bezier curves do
+    pub fn segments_for_curve(&self, deviation: f32) -> u32 {
+        let segments = match *self {
+            Tessellation::Segments(segments) => segments,
+            Tessellation::Tolerance(tolerance) => (deviation / tolerance).sqrt().ceil() as u32,
+        };
+        segments.clamp(1, 1024)
+    }
+}
+
+impl Default for Tessellation {
+    fn default() -> Self {
+        Tessellation::Tolerance(0.25)
+    }
+}
+
+// Indexed triangle list in 2D
+#[derive(Clone, Debug, Default)]
+pub struct Geometry {
+    pub positions: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    // Appends another geometry, rebasing its indices
+    pub fn append(&mut self, other: &Geometry) {
+        let base = self.positions.len() as u32;
+        self.positions.extend_from_slice(&other.positions);
+        self.indices.extend(other.indices.iter().map(|i| i + base));
+    }
+
+    // Adds a center vertex plus an outline and fans triangles around the center.
+    // Works for any outline that is star-shaped with respect to `center`.
+    fn push_fan(&mut self, center: Vec2, outline: &[Vec2], closed: bool) {
+        let base = self.positions.len() as u32;
+        self.positions.push(center.to_array());
+        self.positions.extend(outline.iter().map(|p| p.to_array()));
+        let count = outline.len() as u32;
+        let edges = if closed { count } else { count - 1 };
+        for i in 0..edges {
+            self.indices.extend_from_slice(&[
+                base,
+                base + 1 + i,
+                base + 1 + (i + 1) % count,
+            ]);
+        }
+    }
+}
+
+fn ellipse_outline(center: Vec2, radii: Vec2, start: f32, sweep: f32, segments: u32) -> Vec<Vec2> {
+    (0..=segments)
+        .map(|i| {
+            let angle = start + sweep * i as f32 / segments as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radii
+        })
+        .collect()
+}
+
+pub fn circle(center: Vec2, radius: f32, tessellation: Tessellation) -> Geometry {
+    ellipse(center, Vec2::splat(radius), tessellation)
+}
+
+pub fn ellipse(center: Vec2, radii: Vec2, tessellation: Tessellation) -> Geometry {
+    let segments = tessellation.segments_for(radii.max_element(), TAU);
+    let mut outline = ellipse_outline(center, radii, 0.0, TAU, segments);
+    outline.pop(); // Last point duplicates the first
+    let mut geometry = Geometry::new();
+    geometry.push_fan(center, &outline, true);
+    geometry
+}
+
+pub fn regular_polygon(center: Vec2, radius: f32, sides: u32, rotation: f32) -> Geometry {
+    let sides = sides.max(3);
+    let outline: Vec<Vec2> = (0..sides)
+        .map(|i| {
+            let angle = rotation + TAU * i as f32 / sides as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect();
+    let mut geometry = Geometry::new();
+    geometry.push_fan(center, &outline, true);
+    geometry
+}
+
+pub fn star(
+    center: Vec2,
+    outer_radius: f32,
+    inner_radius: f32,
+    points: u32,
+    rotation: f32,
+) -> Geometry {
+    let points = points.max(2);
+    let outline: Vec<Vec2> = (0..points * 2)
+        .map(|i| {
+            let angle = rotation + PI * i as f32 / points as f32;
+            let radius = if i % 2 == 0 { outer_radius } else { inner_radius };
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect();
+    let mut geometry = Geometry::new();
+    geometry.push_fan(center, &outline, true);
+    geometry
+}
+
+pub fn rounded_rect(min: Vec2, size: Vec2, radius: f32, tessellation: Tessellation) -> Geometry {
+    rounded_rect_corners(min, size, [radius; 4], tessellation)
+}
+
+// Rounded rectangle with a radius per corner: top left, top right, bottom right,
+// bottom left (y down). When two corners on a side don't fit, every radius is
+// scaled down by the same factor so the shape keeps its proportions.
+pub fn rounded_rect_corners(
+    min: Vec2,
+    size: Vec2,
+    radii: [f32; 4],
+    tessellation: Tessellation,
+) -> Geometry {
+    let [top_left, top_right, bottom_right, bottom_left] = radii.map(|radius| radius.max(0.0));
+    let scale = [
+        (top_left + top_right, size.x),
+        (bottom_left + bottom_right, size.x),
+        (top_left + bottom_left, size.y),
+        (top_right + bottom_right, size.y),
+    ]
+    .into_iter()
+    .filter(|&(sum, side)| sum > side)
+    .map(|(sum, side)| side.max(0.0) / sum)
+    .fold(1.0, f32::min);
+    let max = min + size;
+    // Corner centers in clockwise order (y down), each sweeping a quarter turn
+    let corners = [
+        (top_right * scale, Vec2::new(max.x, min.y), Vec2::new(-1.0, 1.0), -PI * 0.5),
+        (bottom_right * scale, max, Vec2::new(-1.0, -1.0), 0.0),
+        (bottom_left * scale, Vec2::new(min.x, max.y), Vec2::new(1.0, -1.0), PI * 0.5),
+        (top_left * scale, min, Vec2::new(1.0, 1.0), PI),
+    ];
+    let mut outline = Vec::new();
+    for (radius, corner, inwards, start) in corners {
+        // A square corner is just its point
+        if radius <= 0.0 {
+            outline.push(corner);
+            continue;
+        }
+        let corner_segments = (tessellation.segments_for(radius, TAU) / 4).max(1);
+        outline.extend(ellipse_outline(
+            corner + inwards * radius,
+            Vec2::splat(radius),
+            start,
+            PI * 0.5,
+            corner_segments,
+        ));
+    }
+    let mut geometry = Geometry::new();
+    geometry.push_fan(min + size * 0.5, &outline, true);
+    geometry
+}
+
+// Annular sector between `inner_radius` and `outer_radius`. An inner radius of zero
+// gives a pie slice.
+pub fn arc(
+    center: Vec2,
+    inner_radius: f32,
+    outer_radius: f32,
+    start_angle: f32,
+    sweep: f32,
+    tessellation: Tessellation,
+) -> Geometry {
+    let segments = tessellation.segments_for(outer_radius, sweep);
+    let outer = ellipse_outline(center, Vec2::splat(outer_radius), start_angle, sweep, segments);
+    let mut geometry = Geometry::new();
+    if inner_radius <= 0.0 {
+        geometry.push_fan(center, &outer, false);
+        return geometry;
+    }
+
+    let inner = ellipse_outline(center, Vec2::splat(inner_radius), start_angle, sweep, segments);
+    for (outer_point, inner_point) in outer.iter().zip(&inner) {
+        geometry.positions.push(outer_point.to_array());
+        geometry.positions.push(inner_point.to_array());
+    }
+    for i in 0..segments {
+        let o0 = i * 2;
+        let i0 = o0 + 1;
+        let o1 = o0 + 2;
+        let i1 = o0 + 3;
+        geometry.indices.extend_from_slice(&[o0, o1, i0, i0, o1, i1]);
+    }
+    geometry
+}
+
+// How two segments of a stroked polyline meet
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineJoin {
+    // Outer edges extended until they meet, bevelled past the miter limit
+    #[default]
+    Miter,
+    // Outer corners joined by a straight edge
+    Bevel,
+    // Outer corners joined by an arc around the point
+    Round,
+}
+
+// How the ends of an open polyline are finished
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineCap {
+    // Cut square at the end point
+    #[default]
+    Butt,
+    // Cut square half a width past the end point
+    Square,
+    // A half circle around the end point
+    Round,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    // Longest miter allowed, in stroke widths, as in SVG. Sharper turns are
+    // bevelled instead of growing a long spike.
+    pub miter_limit: f32,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+}
+
+// Triangles covering a line of `style.width` through `points`, for drivers
+// where wide line primitives are missing or drawn differently. Each segment is
+// a quad; joins fill the gap on the outside of each turn, and the quads share
+// the inner corner so translucent strokes don't overlap themselves, unless the
+// segments are too short for it. A closed polyline also joins the last point to
+// the first and has no caps.
+pub fn stroke_polyline(
+    points: &[Vec2],
+    closed: bool,
+    style: StrokeStyle,
+    tessellation: Tessellation,
+) -> Geometry {
+    let mut geometry = Geometry::new();
+    // Repeated points have no direction to stroke in
+    let mut points = points.to_vec();
+    points.dedup_by(|a, b| a.distance_squared(*b) < 1e-12);
+    if closed && points.len() > 2 && points[0].distance_squared(points[points.len() - 1]) < 1e-12 {
+        points.pop();
+    }
+    let half_width = style.width * 0.5;
+    if points.len() < 2 || half_width <= 0.0 {
+        return geometry;
+    }
+    let closed = closed && points.len() > 2;
+    let count = points.len();
+    let segment_count = if closed { count } else { count - 1 };
+
+    let segments: Vec<(Vec2, f32)> = (0..segment_count)
+        .map(|i| {
+            let delta = points[(i + 1) % count] - points[i];
+            let length = delta.length();
+            (delta / length, length)
+        })
+        .collect();
+    // Left (along the perpendicular) and right corners where each segment
+    // starts and ends
+    let mut starts = Vec::with_capacity(segment_count);
+    let mut ends = Vec::with_capacity(segment_count);
+    for (i, &(direction, _)) in segments.iter().enumerate() {
+        let normal = direction.perp() * half_width;
+        let (start, end) = (points[i], points[(i + 1) % count]);
+        starts.push([start + normal, start - normal]);
+        ends.push([end + normal, end - normal]);
+    }
+
+    let joints = if closed { 0..count } else { 1..count - 1 };
+    for point in joints {
+        let before = (point + segment_count - 1) % segment_count;
+        let after = point % segment_count;
+        let joint = Joint {
+            point: points[point],
+            before: segments[before],
+            after: segments[after],
+        };
+        if let Some((inner_side, inner)) =
+            geometry.push_join(joint, style, half_width, tessellation)
+        {
+            ends[before][inner_side] = inner;
+            starts[after][inner_side] = inner;
+        }
+    }
+
+    if !closed {
+        let first = points[0];
+        let last = points[count - 1];
+        let (first_direction, _) = segments[0];
+        let (last_direction, _) = segments[segment_count - 1];
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                for corner in &mut starts[0] {
+                    *corner -= first_direction * half_width;
+                }
+                for corner in &mut ends[segment_count - 1] {
+                    *corner += last_direction * half_width;
+                }
+            }
+            LineCap::Round => {
+                // Half turns from one side of the line around the end to the other
+                let segments = tessellation.segments_for(half_width, PI);
+                let start = first_direction.perp();
+                let outline = ellipse_outline(
+                    first,
+                    Vec2::splat(half_width),
+                    start.y.atan2(start.x),
+                    PI,
+                    segments,
+                );
+                geometry.push_fan(first, &outline, false);
+                let end = -last_direction.perp();
+                let outline = ellipse_outline(
+                    last,
+                    Vec2::splat(half_width),
+                    end.y.atan2(end.x),
+                    PI,
+                    segments,
+                );
+                geometry.push_fan(last, &outline, false);
+            }
+        }
+    }
+
+    for ([start_left, start_right], [end_left, end_right]) in starts.into_iter().zip(ends) {
+        let base = geometry.positions.len() as u32;
+        geometry.positions.extend(
+            [start_left, end_left, end_right, start_right]
+                .iter()
+                .map(|p| p.to_array()),
+        );
+        geometry
+            .indices
+            .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+    geometry
+}
+
+// A point of a polyline with the (direction, length) of the segments either
+// side of it
+#[derive(Clone, Copy)]
+struct Joint {
+    point: Vec2,
+    before: (Vec2, f32),
+    after: (Vec2, f32),
+}
+
+impl Geometry {
+    // Fills the outside of the turn at `joint`. Returns which corner (0 left, 1
+    // right) is on the inside and where the two segments' inner edges cross,
+    // if that is within the near half of both segments, so it can't cross the
+    // inner corner of the joint at their other end.
+    fn push_join(
+        &mut self,
+        joint: Joint,
+        style: StrokeStyle,
+        half_width: f32,
+        tessellation: Tessellation,
+    ) -> Option<(usize, Vec2)> {
+        let Joint {
+            point,
+            before: (before, before_length),
+            after: (after, after_length),
+        } = joint;
+        let cross = before.perp_dot(after);
+        if cross.abs() < 1e-6 && before.dot(after) > 0.0 {
+            // Straight on; the quads already meet
+            return None;
+        }
+        // Turning towards the left puts the right side on the outside
+        let (outer_sign, inner_side) = if cross > 0.0 { (-1.0, 0) } else { (1.0, 1) };
+        let outer_before = before.perp() * outer_sign;
+        let outer_after = after.perp() * outer_sign;
+
+        // The inner edges cross on the bisector of the two normals, further out
+        // the sharper the turn. A half turn has no bisector.
+        let bisector = (outer_before + outer_after).normalize_or_zero();
+        let cos_half = bisector.dot(outer_before);
+        let miter = (cos_half > 1e-4).then(|| bisector * (half_width / cos_half));
+        let inner = miter.map(|miter| point - miter).filter(|&inner| {
+            let along = inner - point;
+            along.dot(before).abs() <= before_length * 0.5
+                && along.dot(after).abs() <= after_length * 0.5
+        });
+        let apex = inner.unwrap_or(point);
+
+        let outer_start = point + outer_before * half_width;
+        let outer_end = point + outer_after * half_width;
+        let base = self.positions.len() as u32;
+        match style.join {
+            LineJoin::Miter
+                if miter.is_some_and(|_| 1.0 / cos_half <= style.miter_limit.max(1.0)) =>
+            {
+                let tip = point + miter.unwrap();
+                self.positions.extend(
+                    [apex, outer_start, tip, outer_end]
+                        .iter()
+                        .map(|p| p.to_array()),
+                );
+                self.indices.extend_from_slice(&[
+                    base,
+                    base + 1,
+                    base + 2,
+                    base,
+                    base + 2,
+                    base + 3,
+                ]);
+            }
+            LineJoin::Miter | LineJoin::Bevel => {
+                self.positions
+                    .extend([apex, outer_start, outer_end].iter().map(|p| p.to_array()));
+                self.indices.extend_from_slice(&[base, base + 1, base + 2]);
+            }
+            LineJoin::Round => {
+                let sweep = outer_before
+                    .perp_dot(outer_after)
+                    .atan2(outer_before.dot(outer_after));
+                let segments = tessellation.segments_for(half_width, sweep);
+                let outline = ellipse_outline(
+                    point,
+                    Vec2::splat(half_width),
+                    outer_before.y.atan2(outer_before.x),
+                    sweep,
+                    segments,
+                );
+                self.push_fan(apex, &outline, false);
+            }
+        }
+        inner.map(|inner| (inner_side, inner))
+    }
+}
+
+// Triangulates a simple polygon, convex or not, by clipping ears: repeatedly
+// cutting off a convex corner with no other point inside it. Either winding
+// works. Self-intersecting outlines still give triangles, just not an exact fill.
+pub fn fill_polygon(outline: &[Vec2]) -> Geometry {
+    let mut geometry = Geometry::new();
+    let mut points = outline.to_vec();
+    points.dedup_by(|a, b| a.distance_squared(*b) < 1e-12);
+    if points.len() > 1 && points[0].distance_squared(points[points.len() - 1]) < 1e-12 {
+        points.pop();
+    }
+    if points.len() < 3 {
+        return geometry;
+    }
+    geometry
+        .positions
+        .extend(points.iter().map(|p| p.to_array()));
+
+    // Twice the signed area; positive when the outline turns left
+    let area: f32 = (0..points.len())
+        .map(|i| points[i].perp_dot(points[(i + 1) % points.len()]))
+        .sum();
+    let winding = if area < 0.0 { -1.0 } else { 1.0 };
+    let mut remaining: Vec<u32> = (0..points.len() as u32).collect();
+    let mut corner = 0;
+    // Corners looked at since the last ear was clipped
+    let mut misses = 0;
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let [previous, current, next] = [corner + count - 1, corner, corner + 1]
+            .map(|i| remaining[i % count]);
+        let [a, b, c] = [previous, current, next].map(|i| points[i as usize]);
+        let turn = (b - a).perp_dot(c - b) * winding;
+        let is_ear = turn > 0.0
+            && remaining.iter().all(|&other| {
+                [previous, current, next].contains(&other)
+                    || !in_triangle(points[other as usize], a, b, c)
+            });
+        // Corners in a straight line cover nothing and can always go. A polygon
+        // that crosses itself can run out of ears; clip anyway.
+        if is_ear || turn == 0.0 || misses >= count {
+            geometry.indices.extend_from_slice(&[previous, current, next]);
+            remaining.remove(corner % count);
+            corner %= remaining.len();
+            misses = 0;
+        } else {
+            corner = (corner + 1) % count;
+            misses += 1;
+        }
+    }
+    geometry.indices.extend_from_slice(&remaining);
+    geometry
+}
+
+// Whether `point` is inside or on the edge of the triangle, in either winding
+fn in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let sides = [(a, b), (b, c), (c, a)].map(|(from, to)| (to - from).perp_dot(point - from));
+    sides.iter().all(|&side| side >= 0.0) || sides.iter().all(|&side| side <= 0.0)
+}
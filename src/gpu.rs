@@ -0,0 +1,1450 @@
+use ash::vk;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::breadcrumbs::{Breadcrumb, Breadcrumbs};
+use crate::compiler::PipelineCompiler;
+use crate::device_group::{self, DeviceGroup, DeviceGroupMode};
+use crate::memory::{MemoryCategory, MemoryReport, MemoryTracker};
+use crate::sync::Submission;
+use crate::transfer::{self, TransferQueue, UploadHandoff, UploadTarget};
+
+// What the device can do beyond Vulkan 1.0, decided once at startup. Code paths
+// that need a newer feature check these instead of versions or extension names;
+// each flag means the feature is enabled and usable, either as core or through
+// its extension.
+#[derive(Clone, Copy, Debug)]
+pub struct Capabilities {
+    // The lower of the loader's and the device's version. Device functions above
+    // it can't be called.
+    pub api_version: u32,
+    // VK_EXT_memory_budget, for heap budgets in the memory report
+    pub memory_budget: bool,
+    // Core in 1.2, or VK_KHR_timeline_semaphore
+    pub timeline_semaphore: bool,
+    // Core in 1.3, or VK_KHR_synchronization2
+    pub synchronization2: bool,
+    // Core in 1.3, or VK_KHR_dynamic_rendering on a 1.2 device
+    pub dynamic_rendering: bool,
+    // VK_EXT_debug_utils, for naming objects in captures and validation messages
+    pub debug_utils: bool,
+    // VK_EXT_full_screen_exclusive with VK_KHR_get_surface_capabilities2, for
+    // exclusive fullscreen swapchains. Only looked for on Windows.
+    pub full_screen_exclusive: bool,
+    // Memory and semaphores exportable as file descriptors (VK_KHR_external_memory_fd
+    // and VK_KHR_external_semaphore_fd) or, on Windows, NT handles (the _win32
+    // extensions), for `export::FrameExport`. Needs 1.1 and timeline semaphores.
+    pub external_memory: bool,
+    // Block-compressed texture formats (Vulkan 1.0 features): BC1-7 on desktop,
+    // ETC2/EAC and ASTC LDR mostly on mobile. KTX2 textures need the matching one.
+    pub texture_compression_bc: bool,
+    pub texture_compression_etc2: bool,
+    pub texture_compression_astc: bool,
+    // fillModeNonSolid (a Vulkan 1.0 feature), for the wireframe debug view
+    pub fill_mode_non_solid: bool,
+    // VK_EXT_swapchain_colorspace, without which surfaces only offer sRGB color
+    // spaces. Whether a surface has HDR ones is up to the display.
+    pub swapchain_colorspace: bool,
+    // Core in 1.2, or VK_EXT_descriptor_indexing on a 1.1 device: arrays of
+    // sampled images that are partially bound, updated after binding and
+    // indexed non-uniformly, for the bindless sprite textures
+    pub descriptor_indexing: bool,
+    // Core in 1.2, or VK_KHR_buffer_device_address on a 1.1 device, plus
+    // shaderInt64: buffers read through 64-bit addresses in push constants, for
+    // the particle and sprite instance data
+    pub buffer_device_address: bool,
+    // VK_EXT_mesh_shader on a 1.2 device (the extension needs SPIR-V 1.4): vertex
+    // geometry generated by compute-like workgroups, for the mesh shader circles
+    pub mesh_shader: bool,
+    // VK_KHR_acceleration_structure and VK_KHR_ray_tracing_pipeline on a 1.2
+    // device with buffer device address, for the raytrace scene. Only looked for
+    // in builds with the `raytracing` feature.
+    pub ray_tracing: bool,
+    // VK_KHR_fragment_shading_rate on a 1.2 device (it needs render pass 2) with
+    // attachmentFragmentShadingRate: coarser shading away from the ball, driven
+    // by an image attached to the scene pass
+    pub fragment_shading_rate: bool,
+    // A device group of two or more GPUs (core in 1.1) that can present in the
+    // mode `--device-group` asked for; see `GpuContext::device_group`
+    pub device_group: bool,
+    // Core in 1.3, or VK_EXT_pipeline_creation_feedback: whether a pipeline came
+    // from the cache and how long it and each stage took to create, logged by
+    // the pipeline compiler
+    pub pipeline_creation_feedback: bool,
+    // VK_NV_device_diagnostic_checkpoints and VK_AMD_buffer_marker, for the GPU
+    // crash breadcrumbs of `--breadcrumbs`
+    pub diagnostic_checkpoints: bool,
+    pub buffer_marker: bool,
+    // VK_EXT_swapchain_maintenance1 with VK_EXT_surface_maintenance1 and its
+    // feature: a fence for each present, so a replaced swapchain is destroyed
+    // once its presents are done rather than after waiting for the device to go
+    // idle, and control over how a presented image is scaled to the window
+    pub swapchain_maintenance1: bool,
+    // VK_KHR_present_id and VK_KHR_present_wait with their features: presents
+    // are numbered and the CPU can wait for one to reach the display, to keep
+    // input latency down and measure the time between presents
+    pub present_wait: bool,
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Vulkan {}.{}.{}",
+            vk::api_version_major(self.api_version),
+            vk::api_version_minor(self.api_version),
+            vk::api_version_patch(self.api_version)
+        )?;
+        for (enabled, name) in [
+            (self.memory_budget, "memory budget"),
+            (self.timeline_semaphore, "timeline semaphores"),
+            (self.synchronization2, "synchronization2"),
+            (self.dynamic_rendering, "dynamic rendering"),
+            (self.debug_utils, "debug names"),
+            (self.full_screen_exclusive, "exclusive fullscreen"),
+            (self.external_memory, "external memory"),
+            (self.texture_compression_bc, "BC textures"),
+            (self.texture_compression_etc2, "ETC2 textures"),
+            (self.texture_compression_astc, "ASTC textures"),
+            (self.fill_mode_non_solid, "wireframe"),
+            (self.swapchain_colorspace, "HDR color spaces"),
+            (self.descriptor_indexing, "bindless textures"),
+            (self.buffer_device_address, "buffer device address"),
+            (self.mesh_shader, "mesh shaders"),
+            (self.ray_tracing, "ray tracing"),
+            (self.fragment_shading_rate, "variable rate shading"),
+            (self.device_group, "device group"),
+            (self.pipeline_creation_feedback, "pipeline creation feedback"),
+            (self.diagnostic_checkpoints, "diagnostic checkpoints"),
+            (self.buffer_marker, "buffer markers"),
+            (self.swapchain_maintenance1, "swapchain maintenance"),
+            (self.present_wait, "present wait"),
+        ] {
+            if enabled {
+                write!(f, ", {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Device-level Vulkan state shared by every subsystem that creates GPU resources
+// (renderer, textures, sprite batcher, ...). Window/surface state lives in the
+// renderer.
+pub struct GpuContext {
+    pub entry: ash::Entry,
+    pub instance: ash::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    // Where `physical_device` is among the instance's devices, and how many
+    // there are
+    pub physical_device_index: usize,
+    pub physical_device_count: usize,
+    pub device_name: String,
+    pub device: ash::Device,
+    pub queue: vk::Queue,
+    pub queue_family_index: u32,
+    // Set when the device has a transfer-only queue family; uploads go through it
+    pub transfer_family_index: Option<u32>,
+    // Every extension the instance and the device were created with
+    pub instance_extensions: Vec<String>,
+    pub device_extensions: Vec<String>,
+    // For long-lived command buffers that are reset one at a time, like the
+    // renderer's frame command buffer
+    pub command_pool: vk::CommandPool,
+    // For the short-lived command buffers of `submit_immediate`
+    transient_command_pool: vk::CommandPool,
+    // Every pipeline is created through this cache, on either thread
+    pub pipeline_cache: vk::PipelineCache,
+    // Creates graphics and compute pipelines in the background
+    pub compiler: PipelineCompiler,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub capabilities: Capabilities,
+    // Set with `capabilities.device_group`
+    pub device_group: Option<DeviceGroup>,
+    // Extension loaders, set only when the functions aren't core in `api_version`
+    properties2_extension: Option<ash::khr::get_physical_device_properties2::Instance>,
+    timeline_semaphore_extension: Option<ash::khr::timeline_semaphore::Device>,
+    synchronization2_extension: Option<ash::khr::synchronization2::Device>,
+    buffer_device_address_extension: Option<ash::khr::buffer_device_address::Device>,
+    // Never core, so set whenever `capabilities.mesh_shader` is
+    mesh_shader_extension: Option<ash::ext::mesh_shader::Device>,
+    // Never core either; set whenever `capabilities.ray_tracing` is
+    acceleration_structure_extension: Option<ash::khr::acceleration_structure::Device>,
+    ray_tracing_pipeline_extension: Option<ash::khr::ray_tracing_pipeline::Device>,
+    debug_utils: Option<ash::ext::debug_utils::Device>,
+    memory_tracker: Mutex<MemoryTracker>,
+    // Set when the device has a transfer-only queue family; uploads go through it
+    transfer: Option<Mutex<TransferQueue>>,
+    // Set by `enable_breadcrumbs`
+    breadcrumbs: Option<Breadcrumbs>,
+}
+
+impl GpuContext {
+    // `surface_extensions` are the instance extensions the window needs, from
+    // `surface::required_extensions`
+    pub fn new(surface_extensions: &[&CStr]) -> Self {
+        Self::with_device_group(surface_extensions, None)
+    }
+
+    // Like `new`, but with a `group_mode` the device spans every GPU of the
+    // first device group that has more than one and can present in that mode.
+    // Without such a group it falls back to one GPU.
+    pub fn with_device_group(
+        surface_extensions: &[&CStr],
+        group_mode: Option<DeviceGroupMode>,
+    ) -> Self {
+        Self::with_physical_device(surface_extensions, group_mode, None)
+    }
+
+    // Like `with_device_group`, on the physical device at `device_index` in the
+    // order `--info` lists them instead of the first. A device group overrides
+    // it, and an index past the last device falls back to the first.
+    pub fn with_physical_device(
+        surface_extensions: &[&CStr],
+        group_mode: Option<DeviceGroupMode>,
+        device_index: Option<usize>,
+    ) -> Self {
+        println!("Initializing Vulkan");
+        let entry = unsafe { ash::Entry::load().expect("Failed to load Vulkan entry") };
+
+        let available_extensions = unsafe {
+            entry
+                .enumerate_instance_extension_properties(None)
+                .expect("Failed to enumerate instance extensions")
+        };
+        println!("Available Vulkan extensions:");
+        for ext in &available_extensions {
+            let ext_name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
+            println!("- {:?}", ext_name);
+        }
+        let is_available = |name: &CStr| {
+            available_extensions
+                .iter()
+                .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name)
+        };
+
+        // Ask for the newest version the loader supports; 1.0 loaders can't be asked
+        let instance_version = unsafe {
+            entry
+                .try_enumerate_instance_version()
+                .expect("Failed to query instance version")
+                .unwrap_or(vk::API_VERSION_1_0)
+        };
+        let app_info = vk::ApplicationInfo {
+            api_version: instance_version,
+            ..Default::default()
+        };
+
+        // Without these there is nothing to present to
+        let mut instance_extension_names: Vec<&CStr> = Vec::new();
+        for &name in surface_extensions {
+            assert!(
+                is_available(name),
+                "Instance extension {:?} is required for this window but not available",
+                name
+            );
+            instance_extension_names.push(name);
+        }
+        // Only needed to see portability implementations such as MoltenVK
+        let portability_name = ash::khr::portability_enumeration::NAME;
+        let has_portability = is_available(portability_name);
+        if has_portability {
+            instance_extension_names.push(portability_name);
+        }
+        // Object names for RenderDoc and validation layers; capture tools usually
+        // provide it even when no layer is installed
+        let debug_utils_name = ash::ext::debug_utils::NAME;
+        let has_debug_utils = is_available(debug_utils_name);
+        if has_debug_utils {
+            instance_extension_names.push(debug_utils_name);
+        }
+        // Feature and memory budget queries; core from 1.1
+        let properties2_name = ash::khr::get_physical_device_properties2::NAME;
+        let has_properties2_extension =
+            instance_version < vk::API_VERSION_1_1 && is_available(properties2_name);
+        if has_properties2_extension {
+            instance_extension_names.push(properties2_name);
+        }
+        // Exclusive fullscreen needs it to check the surface, and swapchain
+        // maintenance to ask the surface how it can scale presented images
+        let surface_capabilities2_name = ash::khr::get_surface_capabilities2::NAME;
+        let has_surface_capabilities2 = is_available(surface_capabilities2_name);
+        if has_surface_capabilities2 {
+            instance_extension_names.push(surface_capabilities2_name);
+        }
+        let surface_maintenance1_name = ash::ext::surface_maintenance1::NAME;
+        let has_surface_maintenance1 =
+            has_surface_capabilities2 && is_available(surface_maintenance1_name);
+        if has_surface_maintenance1 {
+            instance_extension_names.push(surface_maintenance1_name);
+        }
+        // Lets surfaces list HDR10 and scRGB formats for --hdr
+        let swapchain_colorspace_name = ash::ext::swapchain_colorspace::NAME;
+        let has_swapchain_colorspace = is_available(swapchain_colorspace_name);
+        if has_swapchain_colorspace {
+            instance_extension_names.push(swapchain_colorspace_name);
+        }
+
+        let instance_extension_names_ptrs: Vec<*const std::os::raw::c_char> =
+            instance_extension_names
+                .iter()
+                .map(|c| c.as_ptr())
+                .collect();
+
+        let instance_create_info = vk::InstanceCreateInfo {
+            p_application_info: &app_info,
+            enabled_extension_count: instance_extension_names_ptrs.len() as u32,
+            pp_enabled_extension_names: instance_extension_names_ptrs.as_ptr(),
+            flags: if has_portability {
+                vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
+            } else {
+                vk::InstanceCreateFlags::empty()
+            },
+            ..Default::default()
+        };
+
+        println!(
+            "Attempting to create Vulkan instance with extensions: {:?}",
+            instance_extension_names
+        );
+        let instance = unsafe {
+            entry
+                .create_instance(&instance_create_info, None)
+                .expect("Failed to create Vulkan instance")
+        };
+        println!("Vulkan instance created successfully");
+
+        // Physical device enumeration
+        let physical_devices = unsafe {
+            instance
+                .enumerate_physical_devices()
+                .expect("Failed to enumerate physical devices")
+        };
+        println!("Found {} physical devices", physical_devices.len());
+        // Device groups are core in 1.1; the group's first GPU stands in for
+        // all of them wherever a physical device is asked for
+        let group_devices = match group_mode {
+            Some(_) if instance_version < vk::API_VERSION_1_1 => {
+                println!("Device groups need Vulkan 1.1, using one GPU");
+                None
+            }
+            Some(_) => {
+                let group_devices = device_group::find(&instance);
+                if group_devices.is_none() {
+                    println!("No device group with more than one GPU, using one GPU");
+                }
+                group_devices
+            }
+            None => None,
+        };
+        let physical_device_index = match (group_devices.as_ref(), device_index) {
+            (Some(group_devices), _) => physical_devices
+                .iter()
+                .position(|&device| device == group_devices[0])
+                .unwrap_or(0),
+            (None, Some(index)) if index >= physical_devices.len() => {
+                println!("No physical device {}, using the first", index);
+                0
+            }
+            (None, index) => index.unwrap_or(0),
+        };
+        let physical_device = physical_devices[physical_device_index];
+        let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let device_name = unsafe { CStr::from_ptr(device_properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+        println!(
+            "Selected physical device {} of {}: {} ({:?})",
+            physical_device_index,
+            physical_devices.len(),
+            device_name,
+            physical_device
+        );
+        let api_version = device_properties.api_version.min(instance_version);
+        let properties2_extension = has_properties2_extension
+            .then(|| ash::khr::get_physical_device_properties2::Instance::new(&entry, &instance));
+        let has_properties2 = api_version >= vk::API_VERSION_1_1 || properties2_extension.is_some();
+
+        // Queue family selection and device creation
+        let queue_family_properties =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        println!("Found {} queue families", queue_family_properties.len());
+        // One queue does everything, so it must support compute as well as graphics
+        let queue_family_index = queue_family_properties
+            .iter()
+            .position(|props| {
+                props
+                    .queue_flags
+                    .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            })
+            .expect("No graphics + compute queue family found") as u32;
+        println!("Selected queue family index: {}", queue_family_index);
+        // A family without graphics or compute is usually backed by the copy engine
+        let transfer_family_index = queue_family_properties
+            .iter()
+            .position(|props| {
+                props.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                    && !props
+                        .queue_flags
+                        .intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            })
+            .map(|index| index as u32);
+        match transfer_family_index {
+            Some(index) => println!("Dedicated transfer queue family index: {}", index),
+            None => println!("No dedicated transfer queue family, uploading on the graphics queue"),
+        }
+
+        let mut device_extension_names = vec![CString::new("VK_KHR_swapchain").unwrap()];
+        let available_device_extensions = unsafe {
+            instance
+                .enumerate_device_extension_properties(physical_device)
+                .expect("Failed to enumerate device extensions")
+        };
+        let device_has = |name: &CStr| {
+            available_device_extensions
+                .iter()
+                .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name)
+        };
+        // A newer feature is either core in `api_version` (Some(false)) or comes from
+        // an extension that has to be enabled (Some(true)). The feature bit must be
+        // set either way.
+        let feature_support = |supported: vk::Bool32, core: u32, name: &CStr| {
+            if supported != vk::TRUE {
+                None
+            } else if api_version >= core {
+                Some(false)
+            } else if device_has(name) {
+                Some(true)
+            } else {
+                None
+            }
+        };
+
+        let mut timeline_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::default();
+        let mut synchronization2_features = vk::PhysicalDeviceSynchronization2Features::default();
+        let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+        let mut descriptor_indexing_features =
+            vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+        let mut buffer_device_address_features =
+            vk::PhysicalDeviceBufferDeviceAddressFeatures::default();
+        let mut mesh_shader_features = vk::PhysicalDeviceMeshShaderFeaturesEXT::default();
+        let mesh_shader_name = ash::ext::mesh_shader::NAME;
+        let mut acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+        let mut ray_tracing_pipeline_features =
+            vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::default();
+        // Building acceleration structures on the host isn't used, but the
+        // extension needs deferred host operations enabled all the same
+        let ray_tracing_names = [
+            ash::khr::acceleration_structure::NAME,
+            ash::khr::ray_tracing_pipeline::NAME,
+            ash::khr::deferred_host_operations::NAME,
+        ];
+        let has_ray_tracing_extensions = cfg!(feature = "raytracing")
+            && ray_tracing_names.iter().all(|&name| device_has(name));
+        let mut shading_rate_features =
+            vk::PhysicalDeviceFragmentShadingRateFeaturesKHR::default();
+        let shading_rate_name = ash::khr::fragment_shading_rate::NAME;
+        let mut present_id_features = vk::PhysicalDevicePresentIdFeaturesKHR::default();
+        let mut present_wait_features = vk::PhysicalDevicePresentWaitFeaturesKHR::default();
+        let present_wait_names = [ash::khr::present_id::NAME, ash::khr::present_wait::NAME];
+        let has_present_wait_extensions = present_wait_names.iter().all(|&name| device_has(name));
+        let mut swapchain_maintenance1_features =
+            vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT::default();
+        let swapchain_maintenance1_name = ash::ext::swapchain_maintenance1::NAME;
+        let has_swapchain_maintenance1_extension =
+            has_surface_maintenance1 && device_has(swapchain_maintenance1_name);
+        if has_properties2 {
+            let mut features2 = vk::PhysicalDeviceFeatures2::default()
+                .push_next(&mut timeline_features)
+                .push_next(&mut synchronization2_features)
+                .push_next(&mut dynamic_rendering_features)
+                .push_next(&mut descriptor_indexing_features)
+                .push_next(&mut buffer_device_address_features);
+            if device_has(mesh_shader_name) {
+                features2 = features2.push_next(&mut mesh_shader_features);
+            }
+            if has_ray_tracing_extensions {
+                features2 = features2
+                    .push_next(&mut acceleration_structure_features)
+                    .push_next(&mut ray_tracing_pipeline_features);
+            }
+            if device_has(shading_rate_name) {
+                features2 = features2.push_next(&mut shading_rate_features);
+            }
+            if has_swapchain_maintenance1_extension {
+                features2 = features2.push_next(&mut swapchain_maintenance1_features);
+            }
+            if has_present_wait_extensions {
+                features2 = features2
+                    .push_next(&mut present_id_features)
+                    .push_next(&mut present_wait_features);
+            }
+            unsafe {
+                match properties2_extension.as_ref() {
+                    Some(extension) => {
+                        extension.get_physical_device_features2(physical_device, &mut features2)
+                    }
+                    None => instance.get_physical_device_features2(physical_device, &mut features2),
+                }
+            }
+        }
+
+        let memory_budget_name = ash::ext::memory_budget::NAME;
+        let memory_budget = has_properties2 && device_has(memory_budget_name);
+        if memory_budget {
+            device_extension_names.push(memory_budget_name.to_owned());
+        }
+        // Portability implementations such as MoltenVK list this and require it
+        // to be enabled
+        let portability_subset_name = ash::khr::portability_subset::NAME;
+        if device_has(portability_subset_name) {
+            device_extension_names.push(portability_subset_name.to_owned());
+            println!("Portability subset device, enabling {:?}", portability_subset_name);
+        }
+        let full_screen_exclusive_name = ash::ext::full_screen_exclusive::NAME;
+        // The extension itself is Windows-only
+        let full_screen_exclusive = cfg!(target_os = "windows")
+            && has_surface_capabilities2
+            && device_has(full_screen_exclusive_name);
+        if full_screen_exclusive {
+            device_extension_names.push(full_screen_exclusive_name.to_owned());
+        }
+        // The handle-type extensions; external memory and semaphores themselves
+        // are core in 1.1
+        let external_extension_names = if cfg!(windows) {
+            [
+                ash::khr::external_memory_win32::NAME,
+                ash::khr::external_semaphore_win32::NAME,
+            ]
+        } else {
+            [
+                ash::khr::external_memory_fd::NAME,
+                ash::khr::external_semaphore_fd::NAME,
+            ]
+        };
+        let timeline_semaphore_support = feature_support(
+            timeline_features.timeline_semaphore,
+            vk::API_VERSION_1_2,
+            ash::khr::timeline_semaphore::NAME,
+        );
+        let synchronization2_support = feature_support(
+            synchronization2_features.synchronization2,
+            vk::API_VERSION_1_3,
+            ash::khr::synchronization2::NAME,
+        );
+        // Below 1.2 the extension also needs depth_stencil_resolve and its
+        // dependencies, so it is only used on 1.2 devices
+        let dynamic_rendering_support = feature_support(
+            dynamic_rendering_features.dynamic_rendering,
+            vk::API_VERSION_1_3,
+            ash::khr::dynamic_rendering::NAME,
+        )
+        .filter(|_| api_version >= vk::API_VERSION_1_2);
+        // Only the parts the sprite texture array uses. The extension needs
+        // maintenance3, which is core in 1.1.
+        let descriptor_indexing_bits = [
+            descriptor_indexing_features.shader_sampled_image_array_non_uniform_indexing,
+            descriptor_indexing_features.descriptor_binding_sampled_image_update_after_bind,
+            descriptor_indexing_features.descriptor_binding_update_unused_while_pending,
+            descriptor_indexing_features.descriptor_binding_partially_bound,
+        ];
+        let descriptor_indexing_support = feature_support(
+            descriptor_indexing_bits
+                .iter()
+                .all(|&bit| bit == vk::TRUE)
+                .into(),
+            vk::API_VERSION_1_2,
+            ash::ext::descriptor_indexing::NAME,
+        )
+        .filter(|_| api_version >= vk::API_VERSION_1_1);
+        // Addresses are pushed as 64-bit integers, so shaders need shaderInt64
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let buffer_device_address_support = feature_support(
+            buffer_device_address_features.buffer_device_address,
+            vk::API_VERSION_1_2,
+            ash::khr::buffer_device_address::NAME,
+        )
+        .filter(|_| {
+            api_version >= vk::API_VERSION_1_1 && supported_features.shader_int64 == vk::TRUE
+        });
+        for (support, name) in [
+            (
+                timeline_semaphore_support,
+                ash::khr::timeline_semaphore::NAME,
+            ),
+            (synchronization2_support, ash::khr::synchronization2::NAME),
+            (dynamic_rendering_support, ash::khr::dynamic_rendering::NAME),
+            (
+                descriptor_indexing_support,
+                ash::ext::descriptor_indexing::NAME,
+            ),
+            (
+                buffer_device_address_support,
+                ash::khr::buffer_device_address::NAME,
+            ),
+        ] {
+            if support == Some(true) {
+                device_extension_names.push(name.to_owned());
+            }
+        }
+        let timeline_semaphore = timeline_semaphore_support.is_some();
+        let synchronization2 = synchronization2_support.is_some();
+        let dynamic_rendering = dynamic_rendering_support.is_some();
+        let descriptor_indexing = descriptor_indexing_support.is_some();
+        let buffer_device_address = buffer_device_address_support.is_some();
+        // Only mesh shaders are used; task shaders would need their own feature bit
+        let mesh_shader = api_version >= vk::API_VERSION_1_2
+            && device_has(mesh_shader_name)
+            && mesh_shader_features.mesh_shader == vk::TRUE;
+        if mesh_shader {
+            device_extension_names.push(mesh_shader_name.to_owned());
+        }
+        // Acceleration structures are built from buffer device addresses
+        let ray_tracing = api_version >= vk::API_VERSION_1_2
+            && buffer_device_address
+            && has_ray_tracing_extensions
+            && acceleration_structure_features.acceleration_structure == vk::TRUE
+            && ray_tracing_pipeline_features.ray_tracing_pipeline == vk::TRUE;
+        if ray_tracing {
+            device_extension_names.extend(ray_tracing_names.map(CStr::to_owned));
+        }
+        let fragment_shading_rate = api_version >= vk::API_VERSION_1_2
+            && device_has(shading_rate_name)
+            && shading_rate_features.attachment_fragment_shading_rate == vk::TRUE;
+        if fragment_shading_rate {
+            device_extension_names.push(shading_rate_name.to_owned());
+        }
+        let external_memory = api_version >= vk::API_VERSION_1_1
+            && timeline_semaphore
+            && external_extension_names.iter().all(|&name| device_has(name));
+        if external_memory {
+            device_extension_names.extend(external_extension_names.map(CStr::to_owned));
+        }
+        // Nothing to enable but the extension itself below 1.3
+        let creation_feedback_name = ash::ext::pipeline_creation_feedback::NAME;
+        let pipeline_creation_feedback =
+            api_version >= vk::API_VERSION_1_3 || device_has(creation_feedback_name);
+        if api_version < vk::API_VERSION_1_3 && pipeline_creation_feedback {
+            device_extension_names.push(creation_feedback_name.to_owned());
+        }
+        // Enabled whenever they're there; nothing is recorded with them unless
+        // breadcrumbs are turned on
+        let diagnostic_checkpoints = device_has(ash::nv::device_diagnostic_checkpoints::NAME);
+        if diagnostic_checkpoints {
+            device_extension_names.push(ash::nv::device_diagnostic_checkpoints::NAME.to_owned());
+        }
+        let buffer_marker = device_has(ash::amd::buffer_marker::NAME);
+        if buffer_marker {
+            device_extension_names.push(ash::amd::buffer_marker::NAME.to_owned());
+        }
+        let swapchain_maintenance1 = has_swapchain_maintenance1_extension
+            && swapchain_maintenance1_features.swapchain_maintenance1 == vk::TRUE;
+        if swapchain_maintenance1 {
+            device_extension_names.push(swapchain_maintenance1_name.to_owned());
+        }
+        let present_wait = has_present_wait_extensions
+            && present_id_features.present_id == vk::TRUE
+            && present_wait_features.present_wait == vk::TRUE;
+        if present_wait {
+            device_extension_names.extend(present_wait_names.map(CStr::to_owned));
+        }
+        let capabilities = Capabilities {
+            api_version,
+            memory_budget,
+            timeline_semaphore,
+            synchronization2,
+            dynamic_rendering,
+            debug_utils: has_debug_utils,
+            full_screen_exclusive,
+            external_memory,
+            texture_compression_bc: supported_features.texture_compression_bc == vk::TRUE,
+            texture_compression_etc2: supported_features.texture_compression_etc2 == vk::TRUE,
+            texture_compression_astc: supported_features.texture_compression_astc_ldr == vk::TRUE,
+            fill_mode_non_solid: supported_features.fill_mode_non_solid == vk::TRUE,
+            swapchain_colorspace: has_swapchain_colorspace,
+            descriptor_indexing,
+            buffer_device_address,
+            mesh_shader,
+            ray_tracing,
+            fragment_shading_rate,
+            device_group: group_devices.is_some(),
+            pipeline_creation_feedback,
+            diagnostic_checkpoints,
+            buffer_marker,
+            swapchain_maintenance1,
+            present_wait,
+        };
+        println!("Device capabilities: {}", capabilities);
+        let device_extension_names_ptrs: Vec<*const std::os::raw::c_char> =
+            device_extension_names.iter().map(|c| c.as_ptr()).collect();
+        let queue_create_infos: Vec<vk::DeviceQueueCreateInfo> =
+            std::iter::once(queue_family_index)
+                .chain(transfer_family_index)
+                .map(|queue_family_index| vk::DeviceQueueCreateInfo {
+                    queue_family_index,
+                    queue_count: 1,
+                    p_queue_priorities: &1.0,
+                    ..Default::default()
+                })
+                .collect();
+        let enabled_features = vk::PhysicalDeviceFeatures {
+            texture_compression_bc: supported_features.texture_compression_bc,
+            texture_compression_etc2: supported_features.texture_compression_etc2,
+            texture_compression_astc_ldr: supported_features.texture_compression_astc_ldr,
+            fill_mode_non_solid: supported_features.fill_mode_non_solid,
+            shader_int64: buffer_device_address.into(),
+            ..Default::default()
+        };
+        let mut device_create_info = vk::DeviceCreateInfo {
+            queue_create_info_count: queue_create_infos.len() as u32,
+            p_queue_create_infos: queue_create_infos.as_ptr(),
+            enabled_extension_count: device_extension_names_ptrs.len() as u32,
+            pp_enabled_extension_names: device_extension_names_ptrs.as_ptr(),
+            p_enabled_features: &enabled_features,
+            ..Default::default()
+        };
+        // Turn on every feature found above
+        let mut enabled_timeline_features = vk::PhysicalDeviceTimelineSemaphoreFeatures {
+            timeline_semaphore: vk::TRUE,
+            ..Default::default()
+        };
+        let mut enabled_synchronization2_features = vk::PhysicalDeviceSynchronization2Features {
+            synchronization2: vk::TRUE,
+            ..Default::default()
+        };
+        let mut enabled_dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures {
+            dynamic_rendering: vk::TRUE,
+            ..Default::default()
+        };
+        let mut enabled_descriptor_indexing_features =
+            vk::PhysicalDeviceDescriptorIndexingFeatures {
+                shader_sampled_image_array_non_uniform_indexing: vk::TRUE,
+                descriptor_binding_sampled_image_update_after_bind: vk::TRUE,
+                descriptor_binding_update_unused_while_pending: vk::TRUE,
+                descriptor_binding_partially_bound: vk::TRUE,
+                ..Default::default()
+            };
+        let mut enabled_buffer_device_address_features =
+            vk::PhysicalDeviceBufferDeviceAddressFeatures {
+                buffer_device_address: vk::TRUE,
+                ..Default::default()
+            };
+        let mut enabled_mesh_shader_features = vk::PhysicalDeviceMeshShaderFeaturesEXT {
+            mesh_shader: vk::TRUE,
+            ..Default::default()
+        };
+        let mut enabled_acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR {
+                acceleration_structure: vk::TRUE,
+                ..Default::default()
+            };
+        let mut enabled_ray_tracing_pipeline_features =
+            vk::PhysicalDeviceRayTracingPipelineFeaturesKHR {
+                ray_tracing_pipeline: vk::TRUE,
+                ..Default::default()
+            };
+        // Only the attachment rate; pipelines keep their own rate at 1x1
+        let mut enabled_shading_rate_features = vk::PhysicalDeviceFragmentShadingRateFeaturesKHR {
+            attachment_fragment_shading_rate: vk::TRUE,
+            ..Default::default()
+        };
+        let mut enabled_swapchain_maintenance1_features =
+            vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT {
+                swapchain_maintenance1: vk::TRUE,
+                ..Default::default()
+            };
+        let mut enabled_present_id_features = vk::PhysicalDevicePresentIdFeaturesKHR {
+            present_id: vk::TRUE,
+            ..Default::default()
+        };
+        let mut enabled_present_wait_features = vk::PhysicalDevicePresentWaitFeaturesKHR {
+            present_wait: vk::TRUE,
+            ..Default::default()
+        };
+        if timeline_semaphore {
+            device_create_info = device_create_info.push_next(&mut enabled_timeline_features);
+        }
+        if synchronization2 {
+            device_create_info =
+                device_create_info.push_next(&mut enabled_synchronization2_features);
+        }
+        if dynamic_rendering {
+            device_create_info =
+                device_create_info.push_next(&mut enabled_dynamic_rendering_features);
+        }
+        if descriptor_indexing {
+            device_create_info =
+                device_create_info.push_next(&mut enabled_descriptor_indexing_features);
+        }
+        if buffer_device_address {
+            device_create_info =
+                device_create_info.push_next(&mut enabled_buffer_device_address_features);
+        }
+        if mesh_shader {
+            device_create_info = device_create_info.push_next(&mut enabled_mesh_shader_features);
+        }
+        if ray_tracing {
+            device_create_info = device_create_info
+                .push_next(&mut enabled_acceleration_structure_features)
+                .push_next(&mut enabled_ray_tracing_pipeline_features);
+        }
+        if fragment_shading_rate {
+            device_create_info = device_create_info.push_next(&mut enabled_shading_rate_features);
+        }
+        if swapchain_maintenance1 {
+            device_create_info =
+                device_create_info.push_next(&mut enabled_swapchain_maintenance1_features);
+        }
+        if present_wait {
+            device_create_info = device_create_info
+                .push_next(&mut enabled_present_id_features)
+                .push_next(&mut enabled_present_wait_features);
+        }
+        let mut group_create_info = vk::DeviceGroupDeviceCreateInfo::default()
+            .physical_devices(group_devices.as_deref().unwrap_or_default());
+        if group_devices.is_some() {
+            device_create_info = device_create_info.push_next(&mut group_create_info);
+        }
+        let device = unsafe {
+            instance
+                .create_device(physical_device, &device_create_info, None)
+                .expect("Failed to create Vulkan device")
+        };
+        println!("Vulkan device created successfully");
+        let device_group = match (group_mode, group_devices.as_ref()) {
+            (Some(mode), Some(group_devices)) => Some(DeviceGroup {
+                mode,
+                device_count: group_devices.len() as u32,
+            }),
+            _ => None,
+        };
+        if let Some(group) = device_group {
+            // Which ways the group can present doesn't depend on a surface, so
+            // a group that can't present in `mode` at all is dropped right away
+            let mut present_capabilities = vk::DeviceGroupPresentCapabilitiesKHR::default();
+            unsafe {
+                ash::khr::swapchain::Device::new(&instance, &device)
+                    .get_device_group_present_capabilities(&mut present_capabilities)
+                    .expect("Failed to get device group present capabilities");
+            }
+            if !present_capabilities.modes.contains(group.mode.present_mode()) {
+                println!(
+                    "Device group can't present {} ({:?}), recreating the device on one GPU",
+                    group.mode.name(),
+                    present_capabilities.modes
+                );
+                unsafe {
+                    device.destroy_device(None);
+                    instance.destroy_instance(None);
+                }
+                return Self::with_physical_device(surface_extensions, None, device_index);
+            }
+            println!(
+                "Device group of {} GPUs rendering {}",
+                group.device_count,
+                group.mode.name()
+            );
+        }
+        let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+        println!("Graphics queue obtained: {:?}", queue);
+        let transfer = transfer_family_index.map(|transfer_family_index| {
+            let transfer_queue = unsafe { device.get_device_queue(transfer_family_index, 0) };
+            println!("Transfer queue obtained: {:?}", transfer_queue);
+            Mutex::new(TransferQueue::new(
+                &device,
+                transfer_queue,
+                transfer_family_index,
+                queue_family_index,
+            ))
+        });
+
+        // Command pool creation. vkResetCommandBuffer is only allowed on buffers
+        // from a pool created with RESET_COMMAND_BUFFER.
+        let command_pool_create_info = vk::CommandPoolCreateInfo {
+            flags: vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            queue_family_index,
+            ..Default::default()
+        };
+        let command_pool = unsafe {
+            device
+                .create_command_pool(&command_pool_create_info, None)
+                .expect("Failed to create command pool")
+        };
+        println!("Command pool created: {:?}", command_pool);
+        // One-shot command buffers are allocated, submitted once and freed, which
+        // TRANSIENT lets the driver optimize for
+        let transient_command_pool = unsafe {
+            device
+                .create_command_pool(
+                    &vk::CommandPoolCreateInfo {
+                        flags: vk::CommandPoolCreateFlags::TRANSIENT,
+                        queue_family_index,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create transient command pool")
+        };
+        println!("Transient command pool created: {:?}", transient_command_pool);
+        let pipeline_cache = unsafe {
+            device
+                .create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None)
+                .expect("Failed to create pipeline cache")
+        };
+
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+        let timeline_semaphore_extension = (timeline_semaphore_support == Some(true))
+            .then(|| ash::khr::timeline_semaphore::Device::new(&instance, &device));
+        let synchronization2_extension = (synchronization2_support == Some(true))
+            .then(|| ash::khr::synchronization2::Device::new(&instance, &device));
+        let buffer_device_address_extension = (buffer_device_address_support == Some(true))
+            .then(|| ash::khr::buffer_device_address::Device::new(&instance, &device));
+        let mesh_shader_extension =
+            mesh_shader.then(|| ash::ext::mesh_shader::Device::new(&instance, &device));
+        let acceleration_structure_extension = ray_tracing
+            .then(|| ash::khr::acceleration_structure::Device::new(&instance, &device));
+        let ray_tracing_pipeline_extension = ray_tracing
+            .then(|| ash::khr::ray_tracing_pipeline::Device::new(&instance, &device));
+        let debug_utils =
+            has_debug_utils.then(|| ash::ext::debug_utils::Device::new(&instance, &device));
+        let compiler = PipelineCompiler::new(
+            &device,
+            pipeline_cache,
+            debug_utils.clone(),
+            capabilities.pipeline_creation_feedback,
+        );
+
+        Self {
+            entry,
+            instance,
+            physical_device,
+            physical_device_index,
+            physical_device_count: physical_devices.len(),
+            device_name,
+            device,
+            queue,
+            queue_family_index,
+            transfer_family_index,
+            instance_extensions: instance_extension_names
+                .iter()
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect(),
+            device_extensions: device_extension_names
+                .iter()
+                .map(|name| name.to_string_lossy().into_owned())
+                .collect(),
+            command_pool,
+            transient_command_pool,
+            pipeline_cache,
+            compiler,
+            memory_properties,
+            capabilities,
+            device_group,
+            properties2_extension,
+            timeline_semaphore_extension,
+            synchronization2_extension,
+            buffer_device_address_extension,
+            mesh_shader_extension,
+            acceleration_structure_extension,
+            ray_tracing_pipeline_extension,
+            debug_utils,
+            memory_tracker: Mutex::new(MemoryTracker::default()),
+            transfer,
+            breadcrumbs: None,
+        }
+    }
+
+    // Destroys the device and the instance. Everything created from them,
+    // surfaces included, must be gone already; memory still allocated is
+    // reported, since whoever allocated it forgot to free it.
+    pub fn destroy(mut self) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        if let Some(transfer) = self.transfer.take() {
+            transfer
+                .into_inner()
+                .expect("Failed to lock transfer queue")
+                .destroy(&self);
+        }
+        if let Some(breadcrumbs) = self.breadcrumbs.take() {
+            breadcrumbs.destroy(&self);
+        }
+        let leaked: usize = self.memory_report().allocation_counts.iter().sum();
+        if leaked > 0 {
+            println!("{} device memory allocations were never freed", leaked);
+        }
+        // The compiler thread may still be creating pipelines through the cache
+        drop(self.compiler);
+        unsafe {
+            self.device
+                .destroy_pipeline_cache(self.pipeline_cache, None);
+            self.device
+                .destroy_command_pool(self.transient_command_pool, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+        println!("Vulkan device and instance destroyed");
+    }
+
+    // Labels `handle` for RenderDoc captures and validation messages. Does
+    // nothing without `capabilities.debug_utils`.
+    pub fn set_name<H: vk::Handle>(&self, handle: H, name: &str) {
+        let Some(debug_utils) = self.debug_utils.as_ref() else {
+            return;
+        };
+        let name = CString::new(name).expect("Object name contains a nul byte");
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(handle)
+            .object_name(&name);
+        unsafe {
+            debug_utils
+                .set_debug_utils_object_name(&name_info)
+                .expect("Failed to set object name");
+        }
+    }
+
+    // Starts recording crash breadcrumbs, returning false without
+    // `capabilities.diagnostic_checkpoints` or `capabilities.buffer_marker`
+    pub fn enable_breadcrumbs(&mut self) -> bool {
+        if self.breadcrumbs.is_none() {
+            self.breadcrumbs = Breadcrumbs::new(self);
+        }
+        self.breadcrumbs.is_some()
+    }
+
+    // Leaves `breadcrumb` for frame `frame` in the command buffer. Does nothing
+    // unless breadcrumbs are enabled.
+    pub fn breadcrumb(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: u64,
+        breadcrumb: Breadcrumb,
+    ) {
+        if let Some(breadcrumbs) = self.breadcrumbs.as_ref() {
+            breadcrumbs.record(command_buffer, frame, breadcrumb);
+        }
+    }
+
+    // `result.expect(message)`, except that a lost device first prints how far
+    // the GPU got according to the breadcrumbs
+    pub fn expect<T>(&self, result: ash::prelude::VkResult<T>, message: &str) -> T {
+        match result {
+            Err(vk::Result::ERROR_DEVICE_LOST) => self.device_lost(message),
+            result => result.expect(message),
+        }
+    }
+
+    pub fn device_lost(&self, message: &str) -> ! {
+        eprintln!("{}: the device was lost", message);
+        match self.breadcrumbs.as_ref() {
+            Some(breadcrumbs) => {
+                for line in breadcrumbs.report(self.queue) {
+                    eprintln!("  {}", line);
+                }
+            }
+            None => eprintln!("  Run with --breadcrumbs to see how far the GPU got"),
+        }
+        panic!("{}: {:?}", message, vk::Result::ERROR_DEVICE_LOST);
+    }
+
+    pub fn find_memory_type(&self, type_filter: u32, properties: vk::MemoryPropertyFlags) -> u32 {
+        for i in 0..self.memory_properties.memory_type_count {
+            if (type_filter & (1 << i)) != 0
+                && (self.memory_properties.memory_types[i as usize].property_flags & properties)
+                    == properties
+            {
+                return i;
+            }
+        }
+        panic!("Failed to find suitable memory type");
+    }
+
+    pub fn create_buffer(
+        &self,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+    ) -> (vk::Buffer, vk::DeviceMemory) {
+        self.create_buffer_in(MemoryCategory::for_buffer(usage), size, usage, properties)
+    }
+
+    // Like `create_buffer`, but counted under `category` in the memory report
+    pub fn create_buffer_in(
+        &self,
+        category: MemoryCategory,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        properties: vk::MemoryPropertyFlags,
+    ) -> (vk::Buffer, vk::DeviceMemory) {
+        let buffer_create_info = vk::BufferCreateInfo {
+            size,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        unsafe {
+            let buffer = self
+                .device
+                .create_buffer(&buffer_create_info, None)
+                .expect("Failed to create buffer");
+            let mem_requirements = self.device.get_buffer_memory_requirements(buffer);
+            // Buffers read through device addresses need memory that hands them out
+            let memory = if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+                let mut flags_info = vk::MemoryAllocateFlagsInfo::default()
+                    .flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+                let alloc_info = vk::MemoryAllocateInfo::default()
+                    .allocation_size(mem_requirements.size)
+                    .memory_type_index(
+                        self.find_memory_type(mem_requirements.memory_type_bits, properties),
+                    )
+                    .push_next(&mut flags_info);
+                self.allocate_memory_with(&alloc_info, category)
+            } else {
+                self.allocate_memory(mem_requirements, properties, category)
+            };
+            self.device
+                .bind_buffer_memory(buffer, memory, 0)
+                .expect("Failed to bind buffer memory");
+            (buffer, memory)
+        }
+    }
+
+    // Creates a DEVICE_LOCAL buffer filled with `data` through a staging copy.
+    // TRANSFER_DST is added to `usage` automatically; `name` labels the buffer.
+    pub fn create_device_local_buffer(
+        &self,
+        name: &str,
+        data: &[u8],
+        usage: vk::BufferUsageFlags,
+    ) -> (vk::Buffer, vk::DeviceMemory) {
+        let size = data.len() as vk::DeviceSize;
+        let (staging_buffer, staging_memory) = self.create_buffer(
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        self.write_memory(staging_memory, 0, data);
+        let (buffer, memory) = self.create_buffer(
+            size,
+            usage | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        self.set_name(staging_buffer, &format!("{} staging", name));
+        self.set_name(buffer, name);
+        self.upload(
+            staging_buffer,
+            staging_memory,
+            UploadTarget::Buffer(buffer),
+            |command_buffer| unsafe {
+                self.device.cmd_copy_buffer(
+                    command_buffer,
+                    staging_buffer,
+                    buffer,
+                    &[vk::BufferCopy {
+                        src_offset: 0,
+                        dst_offset: 0,
+                        size,
+                    }],
+                );
+            },
+        );
+        (buffer, memory)
+    }
+
+    // Where shaders find `buffer`, which must have been created with
+    // SHADER_DEVICE_ADDRESS usage. Needs `capabilities.buffer_device_address`.
+    pub fn buffer_address(&self, buffer: vk::Buffer) -> vk::DeviceAddress {
+        let info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
+        unsafe {
+            match self.buffer_device_address_extension.as_ref() {
+                Some(extension) => extension.get_buffer_device_address(&info),
+                None => self.device.get_buffer_device_address(&info),
+            }
+        }
+    }
+
+    // Records a draw of `group_count` mesh shader workgroups with the bound
+    // pipeline. Needs `capabilities.mesh_shader`.
+    pub fn cmd_draw_mesh_tasks(&self, command_buffer: vk::CommandBuffer, group_count: u32) {
+        let extension = self
+            .mesh_shader_extension
+            .as_ref()
+            .expect("Mesh shaders are not supported");
+        unsafe { extension.cmd_draw_mesh_tasks(command_buffer, group_count, 1, 1) };
+    }
+
+    // Acceleration structure functions. Panics without `capabilities.ray_tracing`.
+    pub fn acceleration_structures(&self) -> &ash::khr::acceleration_structure::Device {
+        self.acceleration_structure_extension
+            .as_ref()
+            .expect("Ray tracing is not supported")
+    }
+
+    // Ray tracing pipeline functions. Panics without `capabilities.ray_tracing`.
+    pub fn ray_tracing_pipelines(&self) -> &ash::khr::ray_tracing_pipeline::Device {
+        self.ray_tracing_pipeline_extension
+            .as_ref()
+            .expect("Ray tracing is not supported")
+    }
+
+    // Runs `record`, the copies out of a staging buffer into `target`, and frees the
+    // staging buffer once they are done. With a dedicated transfer queue this
+    // returns right away: the next frame waits for the copy and takes ownership of
+    // `target` before anything reads it, so it must not be used before then.
+    // Otherwise it blocks on the graphics queue like `submit_immediate`.
+    pub fn upload(
+        &self,
+        staging_buffer: vk::Buffer,
+        staging_memory: vk::DeviceMemory,
+        target: UploadTarget,
+        record: impl FnOnce(vk::CommandBuffer),
+    ) {
+        if let Some(transfer) = self.transfer.as_ref() {
+            transfer
+                .lock()
+                .expect("Failed to lock transfer queue")
+                .submit(self, staging_buffer, staging_memory, target, record);
+            return;
+        }
+        self.submit_immediate(|command_buffer| {
+            record(command_buffer);
+            transfer::record_upload_barrier(self, command_buffer, target);
+        });
+        unsafe {
+            self.device.destroy_buffer(staging_buffer, None);
+        }
+        self.free_memory(staging_memory);
+    }
+
+    // Uploads submitted since the last frame, which that frame has to wait for.
+    // Empty without a dedicated transfer queue.
+    pub fn take_uploads(&self) -> UploadHandoff {
+        match self.transfer.as_ref() {
+            Some(transfer) => transfer
+                .lock()
+                .expect("Failed to lock transfer queue")
+                .take_pending(self),
+            None => UploadHandoff::default(),
+        }
+    }
+
+    // Called after submitting the frame that waited on `handoff`
+    pub fn retire_uploads(&self, handoff: UploadHandoff) {
+        if let Some(transfer) = self.transfer.as_ref() {
+            transfer
+                .lock()
+                .expect("Failed to lock transfer queue")
+                .retire(self, handoff);
+        }
+    }
+
+    // Allocates memory for `requirements` and records it under `category`.
+    // Free it with `free_memory` so the memory report stays accurate.
+    pub fn allocate_memory(
+        &self,
+        requirements: vk::MemoryRequirements,
+        properties: vk::MemoryPropertyFlags,
+        category: MemoryCategory,
+    ) -> vk::DeviceMemory {
+        let memory_type_index = self.find_memory_type(requirements.memory_type_bits, properties);
+        let alloc_info = vk::MemoryAllocateInfo {
+            allocation_size: requirements.size,
+            memory_type_index,
+            ..Default::default()
+        };
+        self.allocate_memory_with(&alloc_info, category)
+    }
+
+    // Like `allocate_memory`, for allocations that chain more info such as
+    // export or dedicated allocation structs
+    pub fn allocate_memory_with(
+        &self,
+        alloc_info: &vk::MemoryAllocateInfo,
+        category: MemoryCategory,
+    ) -> vk::DeviceMemory {
+        let memory_type_index = alloc_info.memory_type_index;
+        let memory = unsafe {
+            self.device
+                .allocate_memory(alloc_info, None)
+                .expect("Failed to allocate device memory")
+        };
+        let heap = self.memory_properties.memory_types[memory_type_index as usize].heap_index;
+        self.memory_tracker
+            .lock()
+            .expect("Failed to lock memory tracker")
+            .record(memory, category, heap as usize, alloc_info.allocation_size);
+        memory
+    }
+
+    pub fn free_memory(&self, memory: vk::DeviceMemory) {
+        self.memory_tracker
+            .lock()
+            .expect("Failed to lock memory tracker")
+            .forget(memory);
+        unsafe { self.device.free_memory(memory, None) };
+    }
+
+    // Heap sizes, budgets and usage (with VK_EXT_memory_budget) and the app's
+    // own allocations by category
+    pub fn memory_report(&self) -> MemoryReport {
+        let tracker = self
+            .memory_tracker
+            .lock()
+            .expect("Failed to lock memory tracker");
+        if !self.capabilities.memory_budget {
+            return MemoryReport::new(&self.memory_properties, None, &tracker);
+        }
+        let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut memory_properties =
+            vk::PhysicalDeviceMemoryProperties2::default().push_next(&mut budget);
+        unsafe {
+            match self.properties2_extension.as_ref() {
+                Some(extension) => extension.get_physical_device_memory_properties2(
+                    self.physical_device,
+                    &mut memory_properties,
+                ),
+                None => self.instance.get_physical_device_memory_properties2(
+                    self.physical_device,
+                    &mut memory_properties,
+                ),
+            }
+        };
+        let properties = memory_properties.memory_properties;
+        MemoryReport::new(&properties, Some(&budget), &tracker)
+    }
+
+    // Blocks until timeline `semaphore` reaches `value`. Needs
+    // `capabilities.timeline_semaphore`.
+    pub fn wait_timeline(&self, semaphore: vk::Semaphore, value: u64) {
+        let semaphores = [semaphore];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::default()
+            .semaphores(&semaphores)
+            .values(&values);
+        let result = unsafe {
+            match self.timeline_semaphore_extension.as_ref() {
+                Some(extension) => extension.wait_semaphores(&wait_info, u64::MAX),
+                None => self.device.wait_semaphores(&wait_info, u64::MAX),
+            }
+        };
+        self.expect(result, "Failed to wait for timeline semaphore");
+    }
+
+    // Current value of timeline `semaphore`, without waiting. Needs
+    // `capabilities.timeline_semaphore`.
+    pub fn timeline_value(&self, semaphore: vk::Semaphore) -> u64 {
+        unsafe {
+            match self.timeline_semaphore_extension.as_ref() {
+                Some(extension) => extension.get_semaphore_counter_value(semaphore),
+                None => self.device.get_semaphore_counter_value(semaphore),
+            }
+            .expect("Failed to read timeline semaphore")
+        }
+    }
+
+    // vkCmdPipelineBarrier2, core or extension. Needs
+    // `capabilities.synchronization2`; see `sync::Barriers`.
+    pub fn cmd_pipeline_barrier2(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        dependency_info: &vk::DependencyInfo,
+    ) {
+        unsafe {
+            match self.synchronization2_extension.as_ref() {
+                Some(extension) => extension.cmd_pipeline_barrier2(command_buffer, dependency_info),
+                None => self
+                    .device
+                    .cmd_pipeline_barrier2(command_buffer, dependency_info),
+            }
+        }
+    }
+
+    // vkQueueSubmit2, core or extension. Needs `capabilities.synchronization2`;
+    // see `sync::Submission`.
+    pub fn queue_submit2(&self, queue: vk::Queue, submits: &[vk::SubmitInfo2], fence: vk::Fence) {
+        let result = unsafe {
+            match self.synchronization2_extension.as_ref() {
+                Some(extension) => extension.queue_submit2(queue, submits, fence),
+                None => self.device.queue_submit2(queue, submits, fence),
+            }
+        };
+        self.expect(result, "Failed to submit command buffer");
+    }
+
+    // Copies `data` into HOST_VISIBLE | HOST_COHERENT memory at `offset`
+    pub fn write_memory(&self, memory: vk::DeviceMemory, offset: vk::DeviceSize, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        unsafe {
+            let data_ptr = self
+                .device
+                .map_memory(
+                    memory,
+                    offset,
+                    data.len() as vk::DeviceSize,
+                    vk::MemoryMapFlags::empty(),
+                )
+                .expect("Failed to map memory") as *mut u8;
+            data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
+            self.device.unmap_memory(memory);
+        }
+    }
+
+    pub fn create_shader_module(&self, code: &[u8]) -> vk::ShaderModule {
+        let create_info = vk::ShaderModuleCreateInfo {
+            code_size: code.len(),
+            p_code: code.as_ptr() as *const u32,
+            ..Default::default()
+        };
+        unsafe {
+            self.device
+                .create_shader_module(&create_info, None)
+                .expect("Failed to create shader module")
+        }
+    }
+
+    // Records commands into a throwaway command buffer, submits it, and blocks until
+    // the queue is idle. Meant for uploads at load time, not per-frame work.
+    pub fn submit_immediate(&self, record: impl FnOnce(vk::CommandBuffer)) {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+            command_pool: self.transient_command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+            ..Default::default()
+        };
+        unsafe {
+            let command_buffer = self
+                .device
+                .allocate_command_buffers(&command_buffer_allocate_info)
+                .expect("Failed to allocate upload command buffer")[0];
+            self.set_name(command_buffer, "immediate upload");
+            let begin_info = vk::CommandBufferBeginInfo {
+                flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                ..Default::default()
+            };
+            self.device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .expect("Failed to begin upload command buffer");
+            record(command_buffer);
+            self.device
+                .end_command_buffer(command_buffer)
+                .expect("Failed to end upload command buffer");
+
+            Submission::new(command_buffer).submit(self, self.queue, vk::Fence::null());
+            self.device
+                .queue_wait_idle(self.queue)
+                .expect("Failed to wait for upload");
+            self.device
+                .free_command_buffers(self.transient_command_pool, &[command_buffer]);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use ash::vk;
+use glam::Vec2;
+
+use crate::frame_ring::FrameRing;
+use crate::gpu::GpuContext;
+use crate::reflect::ShaderInterface;
+
+// Gradients the canvas can use in one frame; shapes past that are filled with
+// their gradient's first stop
+pub const MAX_GRADIENTS: usize = 32;
+// Stops one gradient can have; later ones are ignored
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+// Bytes pushed to the frame ring each frame the canvas draws anything
+pub const UPLOAD_SIZE: usize = size_of::<GradientUniforms>();
+
+// A color at a position along the gradient, 0 at the start and 1 at the end
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: [f32; 4],
+}
+
+// Where a gradient runs, in canvas coordinates. Past either end the color of
+// the nearest stop continues.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientShape {
+    // Along the line from `start` to `end`, constant across it
+    Linear { start: Vec2, end: Vec2 },
+    // Outwards from `center`, reaching the end at `radius`
+    Radial { center: Vec2, radius: f32 },
+}
+
+// Colors blended between stops, in straight (not premultiplied) alpha
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    pub stops: Vec<GradientStop>,
+}
+
+impl Gradient {
+    pub fn linear(start: Vec2, end: Vec2) -> Self {
+        Self {
+            shape: GradientShape::Linear { start, end },
+            stops: Vec::new(),
+        }
+    }
+
+    pub fn radial(center: Vec2, radius: f32) -> Self {
+        Self {
+            shape: GradientShape::Radial { center, radius },
+            stops: Vec::new(),
+        }
+    }
+
+    // Stops may be added in any order
+    pub fn with_stop(mut self, offset: f32, color: [f32; 4]) -> Self {
+        self.stops.push(GradientStop { offset, color });
+        self
+    }
+
+    // Color of the first stop, or transparent without any
+    pub fn first_color(&self) -> [f32; 4] {
+        self.sorted_stops()
+            .first()
+            .map_or([0.0; 4], |stop| stop.color)
+    }
+
+    fn sorted_stops(&self) -> Vec<GradientStop> {
+        let mut stops: Vec<GradientStop> = self
+            .stops
+            .iter()
+            .take(MAX_GRADIENT_STOPS)
+            .map(|stop| GradientStop {
+                offset: stop.offset.clamp(0.0, 1.0),
+                ..*stop
+            })
+            .collect();
+        stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+        stops
+    }
+}
+
+// Mirrors one element of `gradients` in shaders/canvas_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniform {
+    // Linear: start xy, end zw. Radial: center xy, radius z.
+    shape: [f32; 4],
+    // x 0 for linear and 1 for radial, y stop count
+    info: [u32; 4],
+    // Stop offsets, four to a vec4
+    offsets: [[f32; 4]; MAX_GRADIENT_STOPS / 4],
+    colors: [[f32; 4]; MAX_GRADIENT_STOPS],
+}
+
+impl GradientUniform {
+    fn new(gradient: &Gradient) -> Self {
+        let (shape, kind) = match gradient.shape {
+            GradientShape::Linear { start, end } => ([start.x, start.y, end.x, end.y], 0),
+            GradientShape::Radial { center, radius } => ([center.x, center.y, radius, 0.0], 1),
+        };
+        let mut uniform = Self {
+            shape,
+            ..Default::default()
+        };
+        let stops = gradient.sorted_stops();
+        for (i, stop) in stops.iter().enumerate() {
+            uniform.offsets[i / 4][i % 4] = stop.offset;
+            uniform.colors[i] = stop.color;
+        }
+        // Without stops, one transparent one
+        uniform.info = [kind, stops.len().max(1) as u32, 0, 0];
+        uniform
+    }
+}
+
+// Mirrors the `Gradients` uniform block in shaders/canvas_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientUniforms {
+    gradients: [GradientUniform; MAX_GRADIENTS],
+}
+
+// The canvas's gradients for this frame, copied into the frame ring and read
+// by the canvas fragment shader through a dynamic uniform buffer descriptor
+pub struct GradientTable {
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    // Frame ring generation the descriptor set points at, None before the first upload
+    bound_generation: Option<u64>,
+    offset: u32,
+}
+
+impl GradientTable {
+    // `interface` is the canvas shaders' with set 0, binding 0 made dynamic
+    pub fn new(gpu: &GpuContext, interface: &ShaderInterface) -> Self {
+        let set_layouts = interface.create_set_layouts(gpu);
+        let pool_sizes = interface.pool_sizes(1);
+        let descriptor_pool = unsafe {
+            gpu.device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        max_sets: 1,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create gradient descriptor pool")
+        };
+        let descriptor_set = unsafe {
+            gpu.device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool,
+                    descriptor_set_count: 1,
+                    p_set_layouts: set_layouts.as_ptr(),
+                    ..Default::default()
+                })
+                .expect("Failed to allocate gradient descriptor set")[0]
+        };
+        Self {
+            descriptor_set_layout: set_layouts[0],
+            descriptor_pool,
+            descriptor_set,
+            bound_generation: None,
+            offset: 0,
+        }
+    }
+
+    // For building the canvas pipeline layout
+    pub fn set_layout(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+
+    // Copies `gradients` (at most MAX_GRADIENTS) into the frame ring; call
+    // before recording
+    pub fn upload(&mut self, gpu: &GpuContext, ring: &mut FrameRing, gradients: &[Gradient]) {
+        let mut uniforms = GradientUniforms {
+            gradients: [GradientUniform::default(); MAX_GRADIENTS],
+        };
+        for (uniform, gradient) in uniforms.gradients.iter_mut().zip(gradients) {
+            *uniform = GradientUniform::new(gradient);
+        }
+        self.offset = ring.push(bytemuck::bytes_of(&uniforms)) as u32;
+        if self.bound_generation != Some(ring.generation()) {
+            self.bound_generation = Some(ring.generation());
+            let buffer_info = vk::DescriptorBufferInfo {
+                buffer: ring.buffer(),
+                offset: 0,
+                range: UPLOAD_SIZE as vk::DeviceSize,
+            };
+            unsafe {
+                gpu.device.update_descriptor_sets(
+                    &[vk::WriteDescriptorSet {
+                        dst_set: self.descriptor_set,
+                        dst_binding: 0,
+                        descriptor_count: 1,
+                        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                        p_buffer_info: &buffer_info,
+                        ..Default::default()
+                    }],
+                    &[],
+                );
+            }
+        }
+    }
+
+    pub fn bind(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+    ) {
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                layout,
+                0,
+                &[self.descriptor_set],
+                &[self.offset],
+            );
+        }
+    }
+
+    pub fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            gpu.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use ash::vk;
+
+// Nits the scene's 1.0 white is shown at on an HDR display. SDR content is
+// usually mastered for 80-100 nits, but looks dim next to the desktop at that.
+pub const PAPER_WHITE_NITS: f32 = 200.0;
+// scRGB defines 1.0 as this many nits
+const SCRGB_WHITE_NITS: f32 = 80.0;
+
+// How the final post pass encodes the linear scene for the swapchain, going by
+// the swapchain's color space
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputEncoding {
+    // Whatever the surface offered first; values above 1 are tonemapped or clipped
+    #[default]
+    Sdr,
+    // Linear Rec. 709 in half floats, 1.0 at 80 nits and above 1 for brighter
+    ScRgb,
+    // Rec. 2020 primaries with the ST 2084 (PQ) curve in 10-bit channels
+    Hdr10,
+}
+
+impl OutputEncoding {
+    pub fn of(surface_format: vk::SurfaceFormatKHR) -> Self {
+        match surface_format.color_space {
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => OutputEncoding::ScRgb,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT => OutputEncoding::Hdr10,
+            _ => OutputEncoding::Sdr,
+        }
+    }
+
+    // `hdr10` or `scrgb`, as --hdr takes them
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "hdr10" => Some(OutputEncoding::Hdr10),
+            "scrgb" => Some(OutputEncoding::ScRgb),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputEncoding::Sdr => "SDR",
+            OutputEncoding::ScRgb => "scRGB",
+            OutputEncoding::Hdr10 => "HDR10",
+        }
+    }
+
+    pub fn is_hdr(self) -> bool {
+        self != OutputEncoding::Sdr
+    }
+
+    // The value `post_output_frag.glsl` switches on
+    pub fn shader_value(self) -> u32 {
+        match self {
+            OutputEncoding::Sdr => 0,
+            OutputEncoding::ScRgb => 1,
+            OutputEncoding::Hdr10 => 2,
+        }
+    }
+
+    // Multiplier taking the scene's 1.0 white to paper white in the output's
+    // units: scRGB units, or nits for HDR10
+    pub fn white_scale(self) -> f32 {
+        match self {
+            OutputEncoding::Sdr => 1.0,
+            OutputEncoding::ScRgb => PAPER_WHITE_NITS / SCRGB_WHITE_NITS,
+            OutputEncoding::Hdr10 => PAPER_WHITE_NITS,
+        }
+    }
+}
+
+// Surface formats for each HDR encoding, in order of preference. Drivers list
+// HDR10 with either 10-bit channel order.
+const HDR_SURFACE_FORMATS: [(OutputEncoding, vk::Format, vk::ColorSpaceKHR); 3] = [
+    (
+        OutputEncoding::ScRgb,
+        vk::Format::R16G16B16A16_SFLOAT,
+        vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+    ),
+    (
+        OutputEncoding::Hdr10,
+        vk::Format::A2B10G10R10_UNORM_PACK32,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    ),
+    (
+        OutputEncoding::Hdr10,
+        vk::Format::A2R10G10B10_UNORM_PACK32,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    ),
+];
+
+// The first of `formats` with an HDR color space, trying `preferred`'s formats
+// before the others. Surfaces only list these with VK_EXT_swapchain_colorspace.
+pub fn find_hdr_format(
+    formats: &[vk::SurfaceFormatKHR],
+    preferred: Option<OutputEncoding>,
+) -> Option<vk::SurfaceFormatKHR> {
+    let candidates = HDR_SURFACE_FORMATS
+        .iter()
+        .filter(|&&(encoding, ..)| Some(encoding) == preferred)
+        .chain(HDR_SURFACE_FORMATS.iter());
+    candidates
+        .filter_map(|&(_, format, color_space)| {
+            formats
+                .iter()
+                .find(|surface_format| {
+                    surface_format.format == format && surface_format.color_space == color_space
+                })
+                .copied()
+        })
+        .next()
+}
+
+// How SDR output squeezes HDR scene values into 0..1. HDR output shows them
+// as they are, up to what the display can do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tonemap {
+    // Clip at 1, as the scene has always been shown
+    #[default]
+    None,
+    // x / (1 + x) per channel: never clips, but dims everything
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve: a toe, a shoulder and more contrast
+    Aces,
+}
+
+impl Tonemap {
+    pub fn next(self) -> Self {
+        match self {
+            Tonemap::None => Tonemap::Reinhard,
+            Tonemap::Reinhard => Tonemap::Aces,
+            Tonemap::Aces => Tonemap::None,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Some(Tonemap::None),
+            "reinhard" => Some(Tonemap::Reinhard),
+            "aces" => Some(Tonemap::Aces),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tonemap::None => "none",
+            Tonemap::Reinhard => "Reinhard",
+            Tonemap::Aces => "ACES",
+        }
+    }
+
+    // The value `post_output_frag.glsl` switches on
+    pub fn shader_value(self) -> u32 {
+        match self {
+            Tonemap::None => 0,
+            Tonemap::Reinhard => 1,
+            Tonemap::Aces => 2,
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use std::ffi::CStr;
+use std::fmt;
+
+use ash::vk;
+use winit::application::ApplicationHandler;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::window::{Window, WindowId};
+
+use crate::surface::{create_surface, required_extensions};
+
+const MIB: u64 = 1024 * 1024;
+
+pub struct QueueFamilyInfo {
+    pub flags: vk::QueueFlags,
+    pub queue_count: u32,
+    pub timestamp_valid_bits: u32,
+    // Can present to the window's surface
+    pub present: bool,
+}
+
+pub struct MemoryHeapInfo {
+    pub size: vk::DeviceSize,
+    pub flags: vk::MemoryHeapFlags,
+    // Property flags of every memory type in this heap
+    pub memory_types: Vec<vk::MemoryPropertyFlags>,
+}
+
+pub struct DeviceInfo {
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub api_version: u32,
+    // Vendor-specific encoding, so reported raw
+    pub driver_version: u32,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub queue_families: Vec<QueueFamilyInfo>,
+    pub memory_heaps: Vec<MemoryHeapInfo>,
+    pub surface_formats: Vec<vk::SurfaceFormatKHR>,
+    pub present_modes: Vec<vk::PresentModeKHR>,
+    pub extensions: Vec<String>,
+}
+
+// Everything `--info` reports: the loader's version and every physical device,
+// including what it can present to a window on this desktop
+pub struct VulkanInfo {
+    pub instance_version: u32,
+    pub devices: Vec<DeviceInfo>,
+}
+
+impl VulkanInfo {
+    // Creates a throwaway instance and a surface for `window`, queries every
+    // device, then destroys both
+    pub fn collect(window: &Window) -> Self {
+        let entry = unsafe { ash::Entry::load().expect("Failed to load Vulkan entry") };
+        let instance_version = unsafe {
+            entry
+                .try_enumerate_instance_version()
+                .expect("Failed to query instance version")
+                .unwrap_or(vk::API_VERSION_1_0)
+        };
+        let available_extensions = unsafe {
+            entry
+                .enumerate_instance_extension_properties(None)
+                .expect("Failed to enumerate instance extensions")
+        };
+        let mut extension_names: Vec<*const std::os::raw::c_char> = required_extensions(window)
+            .iter()
+            .map(|name| name.as_ptr())
+            .collect();
+        // Lists portability implementations such as MoltenVK too
+        let has_portability = available_extensions.iter().any(|ext| {
+            let name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
+            name == ash::khr::portability_enumeration::NAME
+        });
+        if has_portability {
+            extension_names.push(ash::khr::portability_enumeration::NAME.as_ptr());
+        }
+        let app_info = vk::ApplicationInfo {
+            api_version: instance_version,
+            ..Default::default()
+        };
+        let instance = unsafe {
+            entry
+                .create_instance(
+                    &vk::InstanceCreateInfo {
+                        p_application_info: &app_info,
+                        enabled_extension_count: extension_names.len() as u32,
+                        pp_enabled_extension_names: extension_names.as_ptr(),
+                        flags: if has_portability {
+                            vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
+                        } else {
+                            vk::InstanceCreateFlags::empty()
+                        },
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create Vulkan instance")
+        };
+        let surface_ext = ash::khr::surface::Instance::new(&entry, &instance);
+        let surface = create_surface(&entry, &instance, window);
+
+        let physical_devices = unsafe {
+            instance
+                .enumerate_physical_devices()
+                .expect("Failed to enumerate physical devices")
+        };
+        let devices = physical_devices
+            .into_iter()
+            .map(|physical_device| {
+                DeviceInfo::collect(&instance, &surface_ext, surface, physical_device)
+            })
+            .collect();
+
+        unsafe {
+            surface_ext.destroy_surface(surface, None);
+            instance.destroy_instance(None);
+        }
+        Self {
+            instance_version,
+            devices,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let devices: Vec<String> = self.devices.iter().map(DeviceInfo::to_json).collect();
+        format!(
+            "{{\"instance_version\":{},\"devices\":[{}]}}",
+            json_string(&version_string(self.instance_version)),
+            devices.join(",")
+        )
+    }
+}
+
+impl fmt::Display for VulkanInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Vulkan instance {}",
+            version_string(self.instance_version)
+        )?;
+        for (index, device) in self.devices.iter().enumerate() {
+            write!(f, "\nDevice {}: {}", index, device)?;
+        }
+        Ok(())
+    }
+}
+
+impl DeviceInfo {
+    fn collect(
+        instance: &ash::Instance,
+        surface_ext: &ash::khr::surface::Instance,
+        surface: vk::SurfaceKHR,
+        physical_device: vk::PhysicalDevice,
+    ) -> Self {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+                .iter()
+                .enumerate()
+                .map(|(index, family)| QueueFamilyInfo {
+                    flags: family.queue_flags,
+                    queue_count: family.queue_count,
+                    timestamp_valid_bits: family.timestamp_valid_bits,
+                    present: unsafe {
+                        surface_ext
+                            .get_physical_device_surface_support(
+                                physical_device,
+                                index as u32,
+                                surface,
+                            )
+                            .unwrap_or(false)
+                    },
+                })
+                .collect();
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let memory_types =
+            &memory_properties.memory_types[..memory_properties.memory_type_count as usize];
+        let memory_heaps = memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .enumerate()
+            .map(|(index, heap)| MemoryHeapInfo {
+                size: heap.size,
+                flags: heap.flags,
+                memory_types: memory_types
+                    .iter()
+                    .filter(|memory_type| memory_type.heap_index == index as u32)
+                    .map(|memory_type| memory_type.property_flags)
+                    .collect(),
+            })
+            .collect();
+        // Empty when no queue family can present to the surface
+        let surface_formats = unsafe {
+            surface_ext
+                .get_physical_device_surface_formats(physical_device, surface)
+                .unwrap_or_default()
+        };
+        let present_modes = unsafe {
+            surface_ext
+                .get_physical_device_surface_present_modes(physical_device, surface)
+                .unwrap_or_default()
+        };
+        let extensions = unsafe {
+            instance
+                .enumerate_device_extension_properties(physical_device)
+                .expect("Failed to enumerate device extensions")
+        }
+        .iter()
+        .map(|ext| {
+            unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+
+        Self {
+            name: unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+                .to_string_lossy()
+                .into_owned(),
+            device_type: properties.device_type,
+            api_version: properties.api_version,
+            driver_version: properties.driver_version,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            queue_families,
+            memory_heaps,
+            surface_formats,
+            present_modes,
+            extensions,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let queue_families: Vec<String> = self
+            .queue_families
+            .iter()
+            .map(|family| {
+                format!(
+                    "{{\"flags\":{},\"queue_count\":{},\"timestamp_valid_bits\":{},\"present\":{}}}",
+                    json_string(&format!("{:?}", family.flags)),
+                    family.queue_count,
+                    family.timestamp_valid_bits,
+                    family.present
+                )
+            })
+            .collect();
+        let memory_heaps: Vec<String> = self
+            .memory_heaps
+            .iter()
+            .map(|heap| {
+                let memory_types: Vec<String> = heap
+                    .memory_types
+                    .iter()
+                    .map(|flags| json_string(&format!("{:?}", flags)))
+                    .collect();
+                format!(
+                    "{{\"size\":{},\"flags\":{},\"memory_types\":[{}]}}",
+                    heap.size,
+                    json_string(&format!("{:?}", heap.flags)),
+                    memory_types.join(",")
+                )
+            })
+            .collect();
+        let surface_formats: Vec<String> = self
+            .surface_formats
+            .iter()
+            .map(|format| {
+                format!(
+                    "{{\"format\":{},\"color_space\":{}}}",
+                    json_string(&format!("{:?}", format.format)),
+                    json_string(&format!("{:?}", format.color_space))
+                )
+            })
+            .collect();
+        let present_modes: Vec<String> = self
+            .present_modes
+            .iter()
+            .map(|mode| json_string(&format!("{:?}", mode)))
+            .collect();
+        let extensions: Vec<String> = self
+            .extensions
+            .iter()
+            .map(|name| json_string(name))
+            .collect();
+        format!(
+            "{{\"name\":{},\"device_type\":{},\"api_version\":{},\"driver_version\":{},\
+             \"vendor_id\":{},\"device_id\":{},\"queue_families\":[{}],\"memory_heaps\":[{}],\
+             \"surface_formats\":[{}],\"present_modes\":[{}],\"extensions\":[{}]}}",
+            json_string(&self.name),
+            json_string(&format!("{:?}", self.device_type)),
+            json_string(&version_string(self.api_version)),
+            self.driver_version,
+            self.vendor_id,
+            self.device_id,
+            queue_families.join(","),
+            memory_heaps.join(","),
+            surface_formats.join(","),
+            present_modes.join(","),
+            extensions.join(",")
+        )
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} ({:?})", self.name, self.device_type)?;
+        writeln!(
+            f,
+            "  Vulkan {}, driver {:#x}, vendor {:#06x}, device {:#06x}",
+            version_string(self.api_version),
+            self.driver_version,
+            self.vendor_id,
+            self.device_id
+        )?;
+        writeln!(f, "  Queue families:")?;
+        for (index, family) in self.queue_families.iter().enumerate() {
+            writeln!(
+                f,
+                "    {}: {:?} x{}, {}-bit timestamps{}",
+                index,
+                family.flags,
+                family.queue_count,
+                family.timestamp_valid_bits,
+                if family.present { ", present" } else { "" }
+            )?;
+        }
+        writeln!(f, "  Memory heaps:")?;
+        for (index, heap) in self.memory_heaps.iter().enumerate() {
+            writeln!(
+                f,
+                "    {}: {} MiB {:?}, types {:?}",
+                index,
+                heap.size / MIB,
+                heap.flags,
+                heap.memory_types
+            )?;
+        }
+        let surface_formats: Vec<String> = self
+            .surface_formats
+            .iter()
+            .map(|format| format!("{:?}/{:?}", format.format, format.color_space))
+            .collect();
+        writeln!(f, "  Surface formats: {}", surface_formats.join(", "))?;
+        writeln!(f, "  Present modes: {:?}", self.present_modes)?;
+        writeln!(f, "  Extensions: {}", self.extensions.len())
+    }
+}
+
+pub fn version_string(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// Surface formats and present modes need a window, so `--info` still opens one,
+// hidden, and exits as soon as the report is printed
+struct InfoApp {
+    json: bool,
+}
+
+impl ApplicationHandler for InfoApp {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let window = event_loop
+            .create_window(Window::default_attributes().with_visible(false))
+            .expect("Failed to create window");
+        let info = VulkanInfo::collect(&window);
+        if self.json {
+            println!("{}", info.to_json());
+        } else {
+            print!("{}", info);
+        }
+        event_loop.exit();
+    }
+
+    fn window_event(&mut self, _: &ActiveEventLoop, _: WindowId, _: WindowEvent) {}
+}
+
+// Prints every device's capabilities, as JSON with `json`, instead of running the app
+pub fn run(json: bool) {
+    let event_loop = EventLoop::new().expect("Failed to create event loop");
+    event_loop
+        .run_app(&mut InfoApp { json })
+        .expect("Event loop run failed");
+}
@@ -0,0 +1,271 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+// Things the player can ask for, independent of which key is bound to them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    SteerLeft,
+    SteerRight,
+    SteerUp,
+    SteerDown,
+    Pause,
+    Step,
+    TimeScaleUp,
+    TimeScaleDown,
+    ToggleGravity,
+    ToggleTrail,
+    CycleCircleMode,
+    ToggleMute,
+    TogglePowerSave,
+    ToggleMemoryOverlay,
+    CycleDebugView,
+    CycleShadingRate,
+    ToggleStylizeOverlay,
+    CycleTonemap,
+    CyclePresentMode,
+    NextGpu,
+    OpenWindow,
+    ToggleFullscreen,
+    ToggleRecording,
+    SaveSnapshot,
+    LoadSnapshot,
+    NextScene,
+    SpeedUp,
+    SpeedDown,
+    SpawnBall,
+    ClearBalls,
+    Quit,
+}
+
+// Everything `Input` reports during one frame, for recording and replay
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    held_keys: Vec<KeyCode>,
+    pressed_actions: Vec<Action>,
+    cursor_position: Option<Vec2>,
+    held_buttons: Vec<MouseButton>,
+    pressed_buttons: Vec<MouseButton>,
+    released_buttons: Vec<MouseButton>,
+    scroll: f32,
+}
+
+// Tracks which physical keys are held and which actions were triggered since the
+// last `end_frame`. Physical keys keep the bindings layout independent. Also
+// follows the cursor (in physical window pixels) and mouse buttons.
+pub struct Input {
+    bindings: HashMap<KeyCode, Action>,
+    held_keys: HashSet<KeyCode>,
+    pressed_actions: HashSet<Action>,
+    cursor_position: Option<Vec2>,
+    held_buttons: HashSet<MouseButton>,
+    pressed_buttons: HashSet<MouseButton>,
+    released_buttons: HashSet<MouseButton>,
+    scroll: f32,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        let mut input = Self {
+            bindings: HashMap::new(),
+            held_keys: HashSet::new(),
+            pressed_actions: HashSet::new(),
+            cursor_position: None,
+            held_buttons: HashSet::new(),
+            pressed_buttons: HashSet::new(),
+            released_buttons: HashSet::new(),
+            scroll: 0.0,
+        };
+        input.bind(KeyCode::ArrowLeft, Action::SteerLeft);
+        input.bind(KeyCode::ArrowRight, Action::SteerRight);
+        input.bind(KeyCode::ArrowUp, Action::SteerUp);
+        input.bind(KeyCode::ArrowDown, Action::SteerDown);
+        input.bind(KeyCode::Space, Action::Pause);
+        input.bind(KeyCode::Period, Action::Step);
+        input.bind(KeyCode::BracketRight, Action::TimeScaleUp);
+        input.bind(KeyCode::BracketLeft, Action::TimeScaleDown);
+        input.bind(KeyCode::KeyG, Action::ToggleGravity);
+        input.bind(KeyCode::KeyT, Action::ToggleTrail);
+        input.bind(KeyCode::KeyM, Action::CycleCircleMode);
+        input.bind(KeyCode::KeyV, Action::ToggleMute);
+        input.bind(KeyCode::KeyP, Action::TogglePowerSave);
+        input.bind(KeyCode::F3, Action::ToggleMemoryOverlay);
+        input.bind(KeyCode::F4, Action::CycleDebugView);
+        input.bind(KeyCode::F10, Action::CycleShadingRate);
+        input.bind(KeyCode::F8, Action::ToggleStylizeOverlay);
+        input.bind(KeyCode::F7, Action::CycleTonemap);
+        input.bind(KeyCode::F12, Action::CyclePresentMode);
+        input.bind(KeyCode::F1, Action::NextGpu);
+        input.bind(KeyCode::F2, Action::OpenWindow);
+        input.bind(KeyCode::F11, Action::ToggleFullscreen);
+        input.bind(KeyCode::F9, Action::ToggleRecording);
+        input.bind(KeyCode::F5, Action::SaveSnapshot);
+        input.bind(KeyCode::F6, Action::LoadSnapshot);
+        input.bind(KeyCode::Tab, Action::NextScene);
+        input.bind(KeyCode::Equal, Action::SpeedUp);
+        input.bind(KeyCode::NumpadAdd, Action::SpeedUp);
+        input.bind(KeyCode::Minus, Action::SpeedDown);
+        input.bind(KeyCode::NumpadSubtract, Action::SpeedDown);
+        input.bind(KeyCode::KeyN, Action::SpawnBall);
+        input.bind(KeyCode::KeyC, Action::ClearBalls);
+        input.bind(KeyCode::Escape, Action::Quit);
+        input
+    }
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Binds `key` to `action`, replacing whatever the key did before
+    pub fn bind(&mut self, key: KeyCode, action: Action) {
+        self.bindings.insert(key, action);
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent) {
+        let PhysicalKey::Code(key) = event.physical_key else {
+            return;
+        };
+        match event.state {
+            ElementState::Pressed => {
+                // Auto-repeat keeps the key held but must not re-trigger the action
+                if self.held_keys.insert(key) {
+                    if let Some(&action) = self.bindings.get(&key) {
+                        self.pressed_actions.insert(action);
+                    }
+                }
+            }
+            ElementState::Released => {
+                self.held_keys.remove(&key);
+            }
+        }
+    }
+
+    pub fn handle_cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor_position = Some(Vec2::new(position.x as f32, position.y as f32));
+    }
+
+    pub fn handle_cursor_left(&mut self) {
+        self.cursor_position = None;
+    }
+
+    pub fn handle_mouse_button(&mut self, state: ElementState, button: MouseButton) {
+        match state {
+            ElementState::Pressed => {
+                self.held_buttons.insert(button);
+                self.pressed_buttons.insert(button);
+            }
+            ElementState::Released => {
+                self.held_buttons.remove(&button);
+                self.released_buttons.insert(button);
+            }
+        }
+    }
+
+    // Touchpads report pixels rather than lines; treat this many as one notch
+    const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+
+    pub fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        self.scroll += match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines,
+            MouseScrollDelta::PixelDelta(position) => {
+                position.y as f32 / Self::PIXELS_PER_SCROLL_LINE
+            }
+        };
+    }
+
+    // Vertical wheel movement since the last `end_frame` in lines (notches),
+    // positive when scrolling up/away from the user
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll
+    }
+
+    // Last known cursor position in physical window pixels, None while outside
+    pub fn cursor_position(&self) -> Option<Vec2> {
+        self.cursor_position
+    }
+
+    pub fn is_mouse_held(&self, button: MouseButton) -> bool {
+        self.held_buttons.contains(&button)
+    }
+
+    pub fn was_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
+    pub fn was_mouse_released(&self, button: MouseButton) -> bool {
+        self.released_buttons.contains(&button)
+    }
+
+    // Hides this frame's use of `button` from everything after the caller, such
+    // as a click an overlay took. Still held, it stays hidden until pressed again.
+    pub fn consume_mouse(&mut self, button: MouseButton) {
+        self.held_buttons.remove(&button);
+        self.pressed_buttons.remove(&button);
+        self.released_buttons.remove(&button);
+    }
+
+    pub fn is_key_held(&self, key: KeyCode) -> bool {
+        self.held_keys.contains(&key)
+    }
+
+    // True while any key bound to `action` is down
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held_keys
+            .iter()
+            .any(|key| self.bindings.get(key) == Some(&action))
+    }
+
+    // True if `action` was triggered since the last `end_frame`
+    pub fn was_pressed(&self, action: Action) -> bool {
+        self.pressed_actions.contains(&action)
+    }
+
+    // Fires `action` as if a bound key had been pressed, for other devices
+    pub fn trigger(&mut self, action: Action) {
+        self.pressed_actions.insert(action);
+    }
+
+    pub fn state(&self) -> InputState {
+        InputState {
+            held_keys: self.held_keys.iter().copied().collect(),
+            pressed_actions: self.pressed_actions.iter().copied().collect(),
+            cursor_position: self.cursor_position,
+            held_buttons: self.held_buttons.iter().copied().collect(),
+            pressed_buttons: self.pressed_buttons.iter().copied().collect(),
+            released_buttons: self.released_buttons.iter().copied().collect(),
+            scroll: self.scroll,
+        }
+    }
+
+    // Replaces everything seen this frame with a recorded `state`
+    pub fn set_state(&mut self, state: &InputState) {
+        self.held_keys = state.held_keys.iter().copied().collect();
+        self.pressed_actions = state.pressed_actions.iter().copied().collect();
+        self.cursor_position = state.cursor_position;
+        self.held_buttons = state.held_buttons.iter().copied().collect();
+        self.pressed_buttons = state.pressed_buttons.iter().copied().collect();
+        self.released_buttons = state.released_buttons.iter().copied().collect();
+        self.scroll = state.scroll;
+    }
+
+    // Forgets one-shot presses; call once per frame after the update step
+    pub fn end_frame(&mut self) {
+        self.pressed_actions.clear();
+        self.pressed_buttons.clear();
+        self.released_buttons.clear();
+        self.scroll = 0.0;
+    }
+
+    // Drops every held key and button, e.g. when the window loses focus and
+    // releases are missed. Held buttons count as released this frame.
+    pub fn release_all(&mut self) {
+        self.held_keys.clear();
+        self.pressed_actions.clear();
+        self.released_buttons.extend(self.held_buttons.drain());
+    }
+}
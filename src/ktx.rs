@@ -0,0 +1,132 @@
+// KTX2 texture containers, read with the ktx2 crate. Textures already in a
+// Vulkan format (usually BC7, ETC2 or ASTC, with their mip chain) are uploaded
+// as they are, after undoing Zstandard supercompression. Basis Universal
+// ETC1S (BasisLZ) payloads are transcoded by `basis` to whichever of ETC2, BC7
+// or ASTC the device supports. That is the only Basis encoding handled: UASTC
+// payloads are rejected with an error naming the format to re-encode to.
+
+use std::io::Read;
+use std::path::Path;
+
+use ash::vk;
+
+use crate::basis;
+use crate::gpu::Capabilities;
+
+// A 2D texture with every mip level decompressed and ready to copy into an image
+pub struct KtxTexture {
+    pub format: vk::Format,
+    pub width: u32,
+    pub height: u32,
+    // Largest first; level i is (width >> i) x (height >> i), at least 1x1
+    pub levels: Vec<Vec<u8>>,
+}
+
+// Reads the KTX2 file at `path`. Only single 2D images are supported: no
+// arrays, cube maps or 3D textures. `capabilities` picks what Basis data is
+// transcoded to.
+pub fn load(path: &Path, capabilities: &Capabilities) -> Result<KtxTexture, String> {
+    let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+    let reader = ktx2::Reader::new(bytes.as_slice()).map_err(|error| error.to_string())?;
+    let header = reader.header();
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+        return Err("only 2D textures are supported".to_string());
+    }
+    let (format, levels) = match basis_model(&reader) {
+        Some(ktx2::ColorModel::ETC1S) => basis::transcode(&reader, capabilities, is_srgb(&reader))?,
+        Some(_) => {
+            return Err(
+                "UASTC Basis Universal data isn't supported; re-encode it as ETC1S, BC7, ETC2 \
+                 or ASTC (e.g. toktx --encode etc1s, or ktx transcode --target bc7)"
+                    .to_string(),
+            )
+        }
+        None => (
+            header
+                .format
+                .map(|format| vk::Format::from_raw(format.value() as i32))
+                .ok_or("no Vulkan format")?,
+            decompress_levels(&reader)?,
+        ),
+    };
+    println!(
+        "Loaded {}: {}x{} {:?}, {} mip levels",
+        path.display(),
+        header.pixel_width,
+        header.pixel_height,
+        format,
+        levels.len()
+    );
+    Ok(KtxTexture {
+        format,
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        levels,
+    })
+}
+
+// Every level's data, undoing Zstandard supercompression
+fn decompress_levels(reader: &ktx2::Reader<&[u8]>) -> Result<Vec<Vec<u8>>, String> {
+    let header = reader.header();
+    reader
+        .levels()
+        .map(|level| match header.supercompression_scheme {
+            None => Ok(level.data.to_vec()),
+            Some(ktx2::SupercompressionScheme::Zstandard) => {
+                let mut data = Vec::with_capacity(level.uncompressed_byte_length as usize);
+                ruzstd::decoding::StreamingDecoder::new(level.data)
+                    .map_err(|error| error.to_string())?
+                    .read_to_end(&mut data)
+                    .map_err(|error| error.to_string())?;
+                Ok(data)
+            }
+            Some(scheme) => Err(format!("unsupported supercompression {:?}", scheme)),
+        })
+        .collect()
+}
+
+// Which Basis Universal encoding the file holds, if any. Basis textures have no
+// Vulkan format in the header; the data format descriptor says which one it is.
+fn basis_model(reader: &ktx2::Reader<&[u8]>) -> Option<ktx2::ColorModel> {
+    if reader.header().supercompression_scheme == Some(ktx2::SupercompressionScheme::BasisLZ) {
+        return Some(ktx2::ColorModel::ETC1S);
+    }
+    reader
+        .dfd_blocks()
+        .filter_map(|block| ktx2::DfdBlockBasic::parse(block.data).ok())
+        .find_map(|block| match block.header.color_model {
+            Some(model @ (ktx2::ColorModel::ETC1S | ktx2::ColorModel::UASTC)) => Some(model),
+            _ => None,
+        })
+}
+
+// Whether the data format descriptor says the texels are sRGB encoded
+fn is_srgb(reader: &ktx2::Reader<&[u8]>) -> bool {
+    reader
+        .dfd_blocks()
+        .filter_map(|block| ktx2::DfdBlockBasic::parse(block.data).ok())
+        .any(|block| block.header.transfer_function == Some(ktx2::TransferFunction::SRGB))
+}
+
+// Whether `format` is a compressed family the device has enabled. Uncompressed
+// formats need no feature.
+pub fn compression_enabled(format: vk::Format, capabilities: &Capabilities) -> bool {
+    let raw = format.as_raw();
+    let in_range =
+        |first: vk::Format, last: vk::Format| (first.as_raw()..=last.as_raw()).contains(&raw);
+    if in_range(vk::Format::BC1_RGB_UNORM_BLOCK, vk::Format::BC7_SRGB_BLOCK) {
+        capabilities.texture_compression_bc
+    } else if in_range(
+        vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+        vk::Format::EAC_R11G11_SNORM_BLOCK,
+    ) {
+        capabilities.texture_compression_etc2
+    } else if in_range(
+        vk::Format::ASTC_4X4_UNORM_BLOCK,
+        vk::Format::ASTC_12X12_SRGB_BLOCK,
+    ) {
+        capabilities.texture_compression_astc
+    } else {
+        true
+    }
+}
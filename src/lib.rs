@@ -0,0 +1,85 @@
+// Comments here are plain `//` ones, so the safety requirements of unsafe
+// functions are written next to them like everything else
+#![allow(clippy::missing_safety_doc)]
+
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod background;
+pub mod ball;
+pub mod basis;
+pub mod boids;
+pub mod breadcrumbs;
+pub mod camera;
+pub mod canvas;
+pub mod clock;
+pub mod color;
+pub mod compiler;
+pub mod compute;
+pub mod cpu_particles;
+pub mod device_group;
+pub mod ecs;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fractal;
+pub mod frame_ring;
+pub mod frame_times;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod geometry;
+pub mod gpu;
+pub mod gradient;
+pub mod hdr;
+pub mod info;
+pub mod input;
+pub mod ktx;
+pub mod life;
+pub mod lighting;
+pub mod memory;
+pub mod mesh;
+pub mod mesh_circles;
+pub mod metaballs;
+pub mod mipmap;
+pub mod monitor;
+pub mod obj;
+pub mod parallel;
+pub mod particles;
+pub mod path;
+pub mod pipeline;
+pub mod plugin;
+pub mod post;
+pub mod present;
+pub mod profiling;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "raytracing")]
+pub mod raytracing;
+pub mod recording;
+pub mod reflect;
+#[cfg(feature = "remote")]
+pub mod remote;
+pub mod renderer;
+pub mod replay;
+pub mod report;
+pub mod rng;
+pub mod scene;
+pub mod scenes;
+#[cfg(feature = "rhai")]
+pub mod script;
+pub mod shadertoy;
+pub mod shading_rate;
+pub mod shapes;
+pub mod snapshot;
+pub mod sound;
+pub mod spectrum;
+pub mod sprite;
+pub mod stats;
+pub mod stylize;
+pub mod surface;
+pub mod sync;
+pub mod texture;
+pub mod tilemap;
+pub mod transfer;
+pub mod visualizer;
+pub mod window;
+pub mod window_state;
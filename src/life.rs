@@ -0,0 +1,418 @@
+use ash::vk;
+
+use crate::compute::{self, ComputePipeline};
+use crate::gpu::GpuContext;
+use crate::memory::MemoryCategory;
+use crate::pipeline::{PipelineDesc, PipelineManager};
+
+const CELL_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+const WORKGROUP_SIZE: u32 = 16;
+// Generations beyond this in one frame are dropped so a hitch can't stall the GPU
+const MAX_GENERATIONS_PER_FRAME: u32 = 8;
+
+// Mirrors the push constant block in shaders/life_comp.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LifePushConstants {
+    size: [i32; 2],
+    seed: u32,
+    mode: u32,
+    density: f32,
+    fade: f32,
+}
+
+// Values of `mode` in shaders/life_comp.glsl
+const MODE_STEP: u32 = 0;
+const MODE_RANDOMIZE: u32 = 1;
+const MODE_CLEAR: u32 = 2;
+
+// One cell state image, kept in GENERAL layout for both storage and sampling
+struct CellImage {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+impl CellImage {
+    fn new(gpu: &GpuContext, name: &str, extent: vk::Extent2D) -> Self {
+        let device = &gpu.device;
+        let image = unsafe {
+            device
+                .create_image(
+                    &vk::ImageCreateInfo {
+                        image_type: vk::ImageType::TYPE_2D,
+                        format: CELL_FORMAT,
+                        extent: vk::Extent3D {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                        mip_levels: 1,
+                        array_layers: 1,
+                        samples: vk::SampleCountFlags::TYPE_1,
+                        tiling: vk::ImageTiling::OPTIMAL,
+                        usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+                        sharing_mode: vk::SharingMode::EXCLUSIVE,
+                        initial_layout: vk::ImageLayout::UNDEFINED,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create cell image")
+        };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory = gpu.allocate_memory(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Images,
+        );
+        unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Failed to bind cell image memory");
+        }
+        let view = unsafe {
+            device
+                .create_image_view(
+                    &vk::ImageViewCreateInfo {
+                        image,
+                        view_type: vk::ImageViewType::TYPE_2D,
+                        format: CELL_FORMAT,
+                        components: vk::ComponentMapping::default(),
+                        subresource_range: vk::ImageSubresourceRange {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        },
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create cell image view")
+        };
+        gpu.set_name(image, name);
+        gpu.set_name(view, &format!("{} view", name));
+        Self {
+            image,
+            memory,
+            view,
+        }
+    }
+
+    fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device.destroy_image_view(self.view, None);
+            gpu.device.destroy_image(self.image, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+}
+
+// Conway's Game of Life on the GPU, one cell per pixel. Two storage images
+// ping-pong: each generation reads one and writes the other, and the latest
+// is drawn with a fullscreen pass.
+pub struct LifeSimulation {
+    // Fraction of cells alive after `randomize`
+    pub density: f32,
+    // How much of a dead cell's glow survives each generation
+    pub fade: f32,
+    extent: vk::Extent2D,
+    images: [CellImage; 2],
+    // Index of the image holding the latest generation
+    current: usize,
+    images_initialized: bool,
+    generation: u64,
+    pending_generations: u32,
+    // A randomize or clear dispatch to run before the next generations
+    pending_reset: Option<(u32, u32)>,
+    // Seed of the last randomize, reused when a resize refills the grid
+    seed: u32,
+    simulation: ComputePipeline,
+    // step_sets[i] reads images[i] and writes the other one
+    step_sets: [vk::DescriptorSet; 2],
+    sampler: vk::Sampler,
+    draw_set_layout: vk::DescriptorSetLayout,
+    draw_pool: vk::DescriptorPool,
+    // draw_sets[i] samples images[i]
+    draw_sets: [vk::DescriptorSet; 2],
+    render_pipeline: PipelineDesc,
+}
+
+impl LifeSimulation {
+    pub fn new(
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        extent: vk::Extent2D,
+        seed: u32,
+    ) -> Self {
+        let device = &gpu.device;
+        let simulation = ComputePipeline::new(
+            gpu,
+            "life simulation",
+            include_bytes!("../shaders/life_comp.spv"),
+            size_of::<LifePushConstants>(),
+        );
+
+        let sampler = unsafe {
+            device
+                .create_sampler(
+                    &vk::SamplerCreateInfo {
+                        mag_filter: vk::Filter::NEAREST,
+                        min_filter: vk::Filter::NEAREST,
+                        mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+                        address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create cell sampler")
+        };
+        let shaders = pipelines.register_shaders(
+            gpu,
+            "life",
+            include_bytes!("../shaders/fullscreen_vert.spv"),
+            include_bytes!("../shaders/life_frag.spv"),
+        );
+        let interface = pipelines.interface(shaders);
+        let set_layouts = interface.create_set_layouts(gpu);
+        let draw_set_layout = set_layouts[0];
+        // One set per cell image
+        let pool_sizes = interface.pool_sizes(2);
+        let draw_pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        max_sets: 2,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create cell descriptor pool")
+        };
+        let pipeline_layout = interface.create_pipeline_layout(gpu, &set_layouts, 0);
+        let render_pipeline = PipelineDesc::new(shaders, pipeline_layout);
+        pipelines.prepare(gpu, &render_pipeline);
+
+        let mut life = Self {
+            density: 0.25,
+            fade: 0.9,
+            extent,
+            images: [0, 1].map(|i| CellImage::new(gpu, &format!("life cells {}", i), extent)),
+            current: 0,
+            images_initialized: false,
+            generation: 0,
+            pending_generations: 0,
+            pending_reset: None,
+            seed,
+            simulation,
+            step_sets: [vk::DescriptorSet::null(); 2],
+            sampler,
+            draw_set_layout,
+            draw_pool,
+            draw_sets: [vk::DescriptorSet::null(); 2],
+            render_pipeline,
+        };
+        life.create_descriptor_sets(gpu);
+        life.randomize(seed);
+        println!("Game of Life created at {}x{}", extent.width, extent.height);
+        life
+    }
+
+    fn create_descriptor_sets(&mut self, gpu: &GpuContext) {
+        let [first, second] = [&self.images[0], &self.images[1]];
+        self.step_sets = [
+            self.simulation
+                .create_image_descriptor_set(gpu, &[first.view, second.view]),
+            self.simulation
+                .create_image_descriptor_set(gpu, &[second.view, first.view]),
+        ];
+
+        let set_layouts = [self.draw_set_layout; 2];
+        let draw_sets = unsafe {
+            gpu.device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool: self.draw_pool,
+                    descriptor_set_count: set_layouts.len() as u32,
+                    p_set_layouts: set_layouts.as_ptr(),
+                    ..Default::default()
+                })
+                .expect("Failed to allocate cell descriptor sets")
+        };
+        for (image, &descriptor_set) in self.images.iter().zip(&draw_sets) {
+            let image_info = vk::DescriptorImageInfo {
+                image_view: image.view,
+                image_layout: vk::ImageLayout::GENERAL,
+                ..Default::default()
+            };
+            let sampler_info = vk::DescriptorImageInfo {
+                sampler: self.sampler,
+                ..Default::default()
+            };
+            let writes = [
+                (0, vk::DescriptorType::SAMPLED_IMAGE, &image_info),
+                (1, vk::DescriptorType::SAMPLER, &sampler_info),
+            ]
+            .map(|(binding, descriptor_type, info)| vk::WriteDescriptorSet {
+                dst_set: descriptor_set,
+                dst_binding: binding,
+                descriptor_count: 1,
+                descriptor_type,
+                p_image_info: info,
+                ..Default::default()
+            });
+            unsafe { gpu.device.update_descriptor_sets(&writes, &[]) };
+        }
+        self.draw_sets = [draw_sets[0], draw_sets[1]];
+    }
+
+    // Generations simulated since the last reset
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Queues `count` generations for the next update
+    pub fn simulate(&mut self, count: u32) {
+        self.pending_generations += count;
+    }
+
+    // Fills the grid with random cells (see `density`) on the next update
+    pub fn randomize(&mut self, seed: u32) {
+        self.seed = seed;
+        self.pending_reset = Some((MODE_RANDOMIZE, seed));
+    }
+
+    // Kills every cell on the next update
+    pub fn clear(&mut self) {
+        self.pending_reset = Some((MODE_CLEAR, 0));
+    }
+
+    // Recreates the grid at the new size and refills it with the last seed.
+    // Call with the device idle.
+    pub fn resize(&mut self, gpu: &GpuContext, extent: vk::Extent2D) {
+        for image in &self.images {
+            image.destroy(gpu);
+        }
+        self.images = [0, 1].map(|i| CellImage::new(gpu, &format!("life cells {}", i), extent));
+        self.extent = extent;
+        self.images_initialized = false;
+        self.simulation.reset_descriptor_sets(gpu);
+        unsafe {
+            gpu.device
+                .reset_descriptor_pool(self.draw_pool, vk::DescriptorPoolResetFlags::empty())
+                .expect("Failed to reset cell descriptor pool");
+        }
+        self.create_descriptor_sets(gpu);
+        self.randomize(self.seed);
+    }
+
+    fn dispatch(
+        &mut self,
+        gpu: &GpuContext,
+        command_buffer: vk::CommandBuffer,
+        mode: u32,
+        seed: u32,
+    ) {
+        let push_constants = LifePushConstants {
+            size: [self.extent.width as i32, self.extent.height as i32],
+            seed,
+            mode,
+            density: self.density,
+            fade: self.fade,
+        };
+        let next = 1 - self.current;
+        self.simulation.dispatch(
+            &gpu.device,
+            command_buffer,
+            Some(self.step_sets[self.current]),
+            bytemuck::bytes_of(&push_constants),
+            [
+                compute::group_count(self.extent.width, WORKGROUP_SIZE),
+                compute::group_count(self.extent.height, WORKGROUP_SIZE),
+                1,
+            ],
+        );
+        compute::storage_image_barrier(gpu, command_buffer, self.images[next].image);
+        self.current = next;
+    }
+
+    // Records any pending reset and generations. Must be called outside a render
+    // pass, before the pass that draws the grid.
+    pub fn record_update(&mut self, gpu: &GpuContext, command_buffer: vk::CommandBuffer) {
+        if !self.images_initialized {
+            for image in &self.images {
+                compute::undefined_to_general_barrier(gpu, command_buffer, image.image);
+            }
+            self.images_initialized = true;
+            if self.pending_reset.is_none() {
+                self.pending_reset = Some((MODE_CLEAR, 0));
+            }
+        }
+        // Keep the reset and generations for when the simulation is built
+        if !self.simulation.is_ready() {
+            return;
+        }
+        let generations = self.pending_generations.min(MAX_GENERATIONS_PER_FRAME);
+        self.pending_generations = 0;
+        if self.pending_reset.is_none() && generations == 0 {
+            return;
+        }
+
+        // The image about to be written may still be sampled by last frame's draw
+        compute::fragment_to_compute_image_barrier(
+            gpu,
+            command_buffer,
+            self.images[1 - self.current].image,
+        );
+        if let Some((mode, seed)) = self.pending_reset.take() {
+            self.dispatch(gpu, command_buffer, mode, seed);
+            self.generation = 0;
+        }
+        for _ in 0..generations {
+            self.dispatch(gpu, command_buffer, MODE_STEP, 0);
+            self.generation += 1;
+        }
+    }
+
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let pipeline = pipelines.get(gpu, &self.render_pipeline);
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.render_pipeline.layout,
+                0,
+                &[self.draw_sets[self.current]],
+                &[],
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, gpu: &GpuContext, pipelines: &mut PipelineManager) {
+        let device = &gpu.device;
+        pipelines.release_shaders(device, self.render_pipeline.shaders);
+        self.simulation.destroy(device);
+        for image in &self.images {
+            image.destroy(gpu);
+        }
+        unsafe {
+            device.destroy_pipeline_layout(self.render_pipeline.layout, None);
+            device.destroy_descriptor_pool(self.draw_pool, None);
+            device.destroy_descriptor_set_layout(self.draw_set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use glam::{Mat4, Vec3};
+
+// Point lights beyond this many are ignored; the uniform block has a fixed array
+pub const MAX_POINT_LIGHTS: usize = 4;
+
+// Light from infinitely far away, like the sun
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    // World space, pointing towards the light
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+// Light from a point, fading out smoothly to nothing at `range`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+}
+
+// Every light in a 3D scene, set by the scene and uploaded once per frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lighting {
+    // Added everywhere so faces turned away from every light aren't black
+    pub ambient: Vec3,
+    pub directional: DirectionalLight,
+    // At most MAX_POINT_LIGHTS
+    pub point_lights: Vec<PointLight>,
+    // Blinn-Phong highlight: strength, and exponent (higher is tighter)
+    pub specular: f32,
+    pub shininess: f32,
+}
+
+impl Default for Lighting {
+    // A white light from above and in front, as the mesh shaders used before
+    // scenes could set their own
+    fn default() -> Self {
+        Self {
+            ambient: Vec3::splat(0.25),
+            directional: DirectionalLight {
+                direction: Vec3::new(0.36, 0.8, 0.48),
+                color: Vec3::ONE,
+                intensity: 0.75,
+            },
+            point_lights: Vec::new(),
+            specular: 0.5,
+            shininess: 32.0,
+        }
+    }
+}
+
+// Mirrors one element of `pointLights` in shaders/mesh_*.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointLightUniform {
+    // xyz position, w range
+    position_range: [f32; 4],
+    // rgb color times intensity
+    color: [f32; 4],
+}
+
+// Mirrors the `Frame` uniform block in shaders/mesh_*.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FrameUniforms {
+    view_projection: [f32; 16],
+    // xyz camera position, w unused
+    eye: [f32; 4],
+    // rgb ambient, w unused
+    ambient: [f32; 4],
+    // xyz towards the light (normalized), w unused
+    light_direction: [f32; 4],
+    // rgb color times intensity, w unused
+    light_color: [f32; 4],
+    point_lights: [PointLightUniform; MAX_POINT_LIGHTS],
+    point_light_count: u32,
+    specular: f32,
+    shininess: f32,
+    _padding: u32,
+}
+
+impl FrameUniforms {
+    pub fn new(view_projection: Mat4, eye: Vec3, lighting: &Lighting) -> Self {
+        let mut point_lights = [PointLightUniform::default(); MAX_POINT_LIGHTS];
+        let count = lighting.point_lights.len().min(MAX_POINT_LIGHTS);
+        for (uniform, light) in point_lights.iter_mut().zip(&lighting.point_lights) {
+            *uniform = PointLightUniform {
+                position_range: light
+                    .position
+                    .extend(light.range.max(f32::EPSILON))
+                    .to_array(),
+                color: (light.color * light.intensity).extend(0.0).to_array(),
+            };
+        }
+        let directional = &lighting.directional;
+        Self {
+            view_projection: view_projection.to_cols_array(),
+            eye: eye.extend(1.0).to_array(),
+            ambient: lighting.ambient.extend(0.0).to_array(),
+            light_direction: directional
+                .direction
+                .normalize_or_zero()
+                .extend(0.0)
+                .to_array(),
+            light_color: (directional.color * directional.intensity)
+                .extend(0.0)
+                .to_array(),
+            point_lights,
+            point_light_count: count as u32,
+            specular: lighting.specular,
+            shininess: lighting.shininess,
+            _padding: 0,
+        }
+    }
+}
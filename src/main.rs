@@ -1,68 +1,611 @@
-use ash::vk;
-use bytemuck;
-use glam::{Mat4, Vec2};
+use glam::Vec2;
+#[cfg(feature = "remote")]
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use vulkan_vibe_coding::background::Background;
+use vulkan_vibe_coding::clock::{SimulationClock, FIXED_DT};
+use vulkan_vibe_coding::color;
+use vulkan_vibe_coding::device_group::DeviceGroupMode;
+use vulkan_vibe_coding::frame_times::FrameTimes;
+#[cfg(feature = "gamepad")]
+use vulkan_vibe_coding::gamepad::GamepadInput;
+use vulkan_vibe_coding::gpu::GpuContext;
+use vulkan_vibe_coding::hdr::{OutputEncoding, Tonemap};
+use vulkan_vibe_coding::info;
+use vulkan_vibe_coding::input::{Action, Input};
+use vulkan_vibe_coding::memory::MemoryReport;
+use vulkan_vibe_coding::monitor;
+use vulkan_vibe_coding::pipeline::DebugView;
+use vulkan_vibe_coding::plugin;
+use vulkan_vibe_coding::post::PostEffect;
+use vulkan_vibe_coding::profiling;
+use vulkan_vibe_coding::recording::{GifRecorder, VideoRecorder};
+#[cfg(feature = "remote")]
+use vulkan_vibe_coding::remote::{RemoteServer, Request, Response, Stats};
+use vulkan_vibe_coding::renderer::Renderer;
+use vulkan_vibe_coding::report::StartupReport;
+use vulkan_vibe_coding::replay::{self, InputRecorder, RecordedFrame, RecordingHeader, Replay, RECORDING_VERSION};
+use vulkan_vibe_coding::rng::{Rng, DEFAULT_SEED};
+use vulkan_vibe_coding::scene::{DrawList, Scene, SceneContext};
+#[cfg(feature = "remote")]
+use vulkan_vibe_coding::scene::SceneParam;
+use vulkan_vibe_coding::scenes::{SceneConfig, SceneKind};
+#[cfg(feature = "remote")]
+use vulkan_vibe_coding::shapes;
+use vulkan_vibe_coding::snapshot::{Snapshot, SNAPSHOT_VERSION};
+#[cfg(feature = "audio")]
+use vulkan_vibe_coding::sound::SoundPlayer;
+use vulkan_vibe_coding::sound::SoundQueue;
+use vulkan_vibe_coding::sprite::NinePatch;
+use vulkan_vibe_coding::stats::StatsWriter;
+use vulkan_vibe_coding::stylize::{Stylize, StylizePanel};
+use vulkan_vibe_coding::surface::required_extensions;
+use vulkan_vibe_coding::window::{PresentMode, SwapchainOptions, WindowContext};
+use vulkan_vibe_coding::window_state::WindowState;
 use winit::application::ApplicationHandler;
-use winit::dpi::LogicalSize;
-use winit::event::WindowEvent;
-use winit::event_loop::{ActiveEventLoop, EventLoop};
-use winit::window::{Window, WindowId};
-use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
-#[cfg(target_os = "linux")]
-use winit::raw_window_handle::{HasDisplayHandle, RawDisplayHandle};
-#[cfg(target_os = "macos")]
-use objc::{
-    rc::autoreleasepool,
-    runtime::{Object, YES, NO},
-    class,
-    msg_send,
-    sel,
-    sel_impl,
-};
-
-#[repr(C)]
-struct Vertex {
-    position: [f32; 2],
+use winit::dpi::{LogicalSize, PhysicalPosition};
+use winit::event::{MouseButton, StartCause, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowId, WindowLevel};
+
+// Time scales [ / ] step through, from SimulationClock::MIN_TIME_SCALE to
+// MAX_TIME_SCALE. A scale set in between (by a snapshot or remotely) steps to
+// the nearest one in that direction, so 1x is always reachable.
+const TIME_SCALES: [f32; 6] = [0.1, 0.25, 0.5, 1.0, 2.0, 4.0];
+// Fraction of the trail kept each frame while the trail effect is on
+const TRAIL_PERSISTENCE: f32 = 0.85;
+// Redraw interval while power save is idling on a paused simulation
+const BACKGROUND_FRAME_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+// Frame rate cap while the window is in the background, unless --unfocused-fps says otherwise
+const DEFAULT_UNFOCUSED_FPS: f32 = 10.0;
+// Smallest inner window size in logical pixels, unless --min-size says otherwise
+const DEFAULT_MIN_SIZE: LogicalSize<u32> = LogicalSize::new(320, 240);
+const DEFAULT_WINDOW_SIZE: LogicalSize<u32> = LogicalSize::new(800, 600);
+// A frame this many refresh intervals after the previous one missed a refresh
+const LATE_FRAME_INTERVALS: f32 = 1.5;
+// CPU time over which a frame is logged as a stutter, unless --stutter-ms says
+// otherwise: two refreshes at 60 Hz
+const DEFAULT_STUTTER_MS: f32 = 33.0;
+// Placement of the memory overlay bars, in pixels
+const MEMORY_OVERLAY_ORIGIN: Vec2 = Vec2::new(10.0, 10.0);
+const MEMORY_OVERLAY_WIDTH: f32 = 200.0;
+// Room between the memory overlay bars and the edge of the panel behind them
+const OVERLAY_PANEL_PADDING: f32 = 6.0;
+// Gap between the memory overlay and the post effects sliders below it
+const OVERLAY_PANEL_GAP: f32 = 10.0;
+// Overlay panel texture: a rounded square whose corners and rim become the
+// nine-patch border
+const OVERLAY_PANEL_TEXTURE_SIZE: u32 = 16;
+const OVERLAY_PANEL_RADIUS: f32 = 6.0;
+// Overlay mode has no title bar, so the FPS shows as a bar in the top right
+// corner, clear of the memory overlay
+const FPS_METER_MARGIN: f32 = 10.0;
+// Cursor movement in pixels that doesn't end the screensaver; windows report a
+// move when they open, and a mouse can jitter on the desk
+const SCREENSAVER_CURSOR_SLACK: f64 = 10.0;
+const FPS_METER_SIZE: Vec2 = Vec2::new(120.0, 6.0);
+const FPS_METER_RADIUS: f32 = 3.0;
+// Frame rate of recordings when rendering is uncapped
+const DEFAULT_RECORDING_FPS: f32 = 60.0;
+// Frames per second sampled into --gif captures; GIF frame delays are whole
+// hundredths of a second, so this divides 100
+const GIF_FPS: f32 = 25.0;
+// Written by F5 and read by F6 when --snapshot doesn't name a file
+const DEFAULT_SNAPSHOT_PATH: &str = "snapshot.json";
+// Where --report writes when it isn't given a file; stdout is full of log lines
+const DEFAULT_REPORT_PATH: &str = "startup_report.json";
+// Port of the remote control server when --remote doesn't give one
+const DEFAULT_REMOTE_PORT: u16 = 9002;
+
+// Command line options
+struct Options {
+    seed: u64,
+    scene: SceneKind,
+    scene_config: SceneConfig,
+    power_save: bool,
+    // Zero disables the cap
+    unfocused_fps: f32,
+    // SDF circle edge width in pixels, None for the shader default
+    aa_width: Option<f32>,
+    // Print device capabilities and exit instead of running
+    info: bool,
+    json: bool,
+    // Print the built-in and plugin scene names, then quit
+    list_scenes: bool,
+    // Forget the saved window geometry
+    reset_window: bool,
+    min_size: LogicalSize<u32>,
+    // Width over height the scene is letterboxed to, None to fill the window
+    aspect_ratio: Option<f32>,
+    // Fixed scene size in logical units, letterboxed to its own aspect ratio
+    virtual_resolution: Option<Vec2>,
+    // Index into the window system's monitor list, None for its default
+    monitor: Option<usize>,
+    // Linear RGB shown behind scenes without a background, None for black
+    clear_color: Option<[f32; 3]>,
+    // Ask for an HDR swapchain, of this encoding if given
+    hdr: bool,
+    hdr_encoding: Option<OutputEncoding>,
+    // None for ACES with --hdr (in case it falls back to SDR), otherwise none
+    tonemap: Option<Tonemap>,
+    // Zero disables the cap, None caps to the monitor's refresh rate
+    fps_cap: Option<f32>,
+    // Frames whose CPU time goes over this many milliseconds are logged
+    stutter_ms: f32,
+    // Write every frame's timing, FPS and memory use here, as CSV or JSON lines
+    stats_path: Option<PathBuf>,
+    // Describe the GPU, queues and swapchain as JSON in this file once running
+    report_path: Option<PathBuf>,
+    // Asked of every window's swapchain; each falls back if its surface lacks it
+    present_mode: PresentMode,
+    // Minimum swapchain images, clamped to what each surface allows
+    swapchain_images: Option<u32>,
+    // Per-pixel alpha, so the desktop shows through the background
+    transparent: bool,
+    // Fullscreen takes the display exclusively where the driver allows it
+    exclusive_fullscreen: bool,
+    // Render on every GPU of a device group, splitting the work this way
+    device_group: Option<DeviceGroupMode>,
+    // Physical device to render on, numbered as --info lists them
+    gpu: Option<usize>,
+    // Leave GPU crash breadcrumbs in every frame, reported if the device is lost
+    breadcrumbs: bool,
+    // Transparent, undecorated, always on top and, where supported, click-through
+    overlay: bool,
+    // Fullscreen on every monitor without a cursor until there is any input
+    screensaver: bool,
+    // Where F9 records to, None for a timestamped file in the working directory
+    record_path: Option<PathBuf>,
+    // Capture this many seconds from the start into a GIF, then quit
+    gif_seconds: Option<f32>,
+    // Copy frames into exported memory for another process to import
+    export_frames: bool,
+    // Serve the remote control on this port
+    remote_port: Option<u16>,
+    // Snapshot file to restore at startup and to save to
+    snapshot_path: Option<PathBuf>,
+    // Write every frame's input here for --replay
+    record_input_path: Option<PathBuf>,
+    // Play this input recording back instead of live input, then quit
+    replay_path: Option<PathBuf>,
 }
 
-fn create_circle_vertices(radius: f32, segments: u32) -> Vec<Vertex> {
-    let mut vertices = Vec::with_capacity(segments as usize + 2);
-    vertices.push(Vertex {
-        position: [0.0, 0.0],
-    }); // Center
-    for i in 0..=segments {
-        let angle = i as f32 * 2.0 * std::f32::consts::PI / segments as f32;
-        vertices.push(Vertex {
-            position: [radius * angle.cos(), radius * angle.sin()],
-        });
+impl Options {
+    // Accepts `--seed N`, `--scene NAME`, `--shader PATH`, `--unfocused-fps N`,
+    // `--aa-width N`, `--min-size WxH`, `--aspect W:H` (or a plain ratio),
+    // `--virtual-resolution WxH`, `--monitor N`, `--fps-cap N`, `--stutter-ms N`,
+    // `--stats-out PATH`, `--gravity N`, `--restitution N`, `--drag N`, `--clear-color COLOR`
+    // (`#rrggbb` or `r,g,b`), `--tonemap none|reinhard|aces`,
+    // `--present-mode mailbox|immediate|vsync|adaptive`, `--swapchain-images N`,
+    // `--gpu N` and `--device-group afr|split`, each also as
+    // `--flag=value`, plus `--hdr` (or `--hdr=hdr10|scrgb`), `--power-save`, `--info`
+    // (with `--json` for machine-readable output), `--report` (or `--report=PATH`),
+    // `--list-scenes`, `--reset-window`,
+    // `--transparent`, `--exclusive-fullscreen`, `--breadcrumbs`, `--overlay` and
+    // `--screensaver` (or Windows' `/s`, `/c` and `/p`), `--record PATH`,
+    // `--gif SECONDS`, `--export-frames`,
+    // `--script PATH`, `--model PATH`, `--map PATH`, `--snapshot PATH`,
+    // `--record-input PATH`, `--replay PATH` and `--remote` (or `--remote=PORT`). A
+    // shader path on its own selects the shadertoy scene, a script path the python
+    // or script scene, a model path the solids scene and a map path the tilemap scene.
+    fn from_args() -> Self {
+        let mut options = Self {
+            seed: DEFAULT_SEED,
+            scene: SceneKind::Bouncing,
+            scene_config: SceneConfig::default(),
+            power_save: false,
+            unfocused_fps: DEFAULT_UNFOCUSED_FPS,
+            aa_width: None,
+            info: false,
+            json: false,
+            list_scenes: false,
+            reset_window: false,
+            min_size: DEFAULT_MIN_SIZE,
+            aspect_ratio: None,
+            virtual_resolution: None,
+            monitor: None,
+            clear_color: None,
+            hdr: false,
+            hdr_encoding: None,
+            tonemap: None,
+            fps_cap: None,
+            stutter_ms: DEFAULT_STUTTER_MS,
+            stats_path: None,
+            report_path: None,
+            present_mode: PresentMode::default(),
+            swapchain_images: None,
+            transparent: false,
+            exclusive_fullscreen: false,
+            device_group: None,
+            gpu: None,
+            breadcrumbs: false,
+            overlay: false,
+            screensaver: false,
+            record_path: None,
+            gif_seconds: None,
+            export_frames: false,
+            remote_port: None,
+            snapshot_path: None,
+            record_input_path: None,
+            replay_path: None,
+        };
+        let mut scene_given = false;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            match flag.as_str() {
+                "--seed" => {
+                    options.seed = value
+                        .or_else(|| args.next())
+                        .and_then(|seed| seed.parse().ok())
+                        .expect("--seed expects an unsigned integer");
+                }
+                "--scene" => {
+                    let name = value.or_else(|| args.next()).unwrap_or_default();
+                    options.scene = SceneKind::from_name(&name).unwrap_or_else(|| {
+                        let names: Vec<_> = SceneKind::all().iter().map(|kind| kind.name()).collect();
+                        panic!("--scene expects one of {}", names.join("|"))
+                    });
+                    scene_given = true;
+                }
+                "--shader" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--shader expects a file path");
+                    options.scene_config.shader_path = Some(path.into());
+                    if !scene_given {
+                        options.scene = SceneKind::ShaderToy;
+                    }
+                }
+                "--model" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--model expects an OBJ file path");
+                    options.scene_config.model_path = Some(path.into());
+                    if !scene_given {
+                        options.scene = SceneKind::Solids;
+                    }
+                }
+                "--map" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--map expects a TMX or TMJ file path");
+                    options.scene_config.map_path = Some(path.into());
+                    if !scene_given {
+                        options.scene = SceneKind::Tilemap;
+                    }
+                }
+                "--script" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--script expects a file path");
+                    match script_scene(&path) {
+                        Some(scene) if !scene_given => options.scene = scene,
+                        Some(_) => {}
+                        None => eprintln!(
+                            "--script needs a .py file and the python feature, or a .rhai file \
+                             and the rhai feature; ignoring it"
+                        ),
+                    }
+                    options.scene_config.script_path = Some(path.into());
+                }
+                "--gravity" => {
+                    options.scene_config.physics.gravity.y = value
+                        .or_else(|| args.next())
+                        .and_then(|gravity| gravity.parse().ok())
+                        .filter(|gravity: &f32| gravity.is_finite())
+                        .expect("--gravity expects a number of pixels per second squared");
+                }
+                "--restitution" => {
+                    options.scene_config.physics.restitution = value
+                        .or_else(|| args.next())
+                        .and_then(|restitution| restitution.parse().ok())
+                        .filter(|restitution| (0.0..=1.0).contains(restitution))
+                        .expect("--restitution expects a number from 0 to 1");
+                }
+                "--drag" => {
+                    options.scene_config.physics.air_drag = value
+                        .or_else(|| args.next())
+                        .and_then(|drag| drag.parse().ok())
+                        .filter(|drag: &f32| drag.is_finite() && *drag >= 0.0)
+                        .expect("--drag expects a non-negative number per second");
+                }
+                "--power-save" => options.power_save = true,
+                "--info" => options.info = true,
+                "--json" => {
+                    options.info = true;
+                    options.json = true;
+                }
+                "--list-scenes" => options.list_scenes = true,
+                "--reset-window" => options.reset_window = true,
+                "--transparent" => options.transparent = true,
+                "--exclusive-fullscreen" => options.exclusive_fullscreen = true,
+                "--breadcrumbs" => options.breadcrumbs = true,
+                "--screensaver" => options.screensaver = true,
+                "--export-frames" => options.export_frames = true,
+                "--snapshot" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--snapshot expects a file path");
+                    options.snapshot_path = Some(path.into());
+                }
+                "--record-input" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--record-input expects a file path");
+                    options.record_input_path = Some(path.into());
+                }
+                "--replay" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--replay expects a file path");
+                    options.replay_path = Some(path.into());
+                }
+                "--remote" => {
+                    let port = value.map(|port| port.parse().expect("--remote expects a port number"));
+                    options.remote_port = Some(port.unwrap_or(DEFAULT_REMOTE_PORT));
+                }
+                "--report" => {
+                    let path = value.unwrap_or_else(|| DEFAULT_REPORT_PATH.to_string());
+                    options.report_path = Some(path.into());
+                }
+                "--hdr" => {
+                    options.hdr = true;
+                    options.hdr_encoding = value.map(|name| {
+                        OutputEncoding::parse(&name).expect("--hdr expects hdr10 or scrgb")
+                    });
+                }
+                "--device-group" => {
+                    let mode = value
+                        .or_else(|| args.next())
+                        .and_then(|name| DeviceGroupMode::parse(&name))
+                        .expect("--device-group expects afr or split");
+                    options.device_group = Some(mode);
+                }
+                "--tonemap" => {
+                    let tonemap = value
+                        .or_else(|| args.next())
+                        .and_then(|name| Tonemap::parse(&name))
+                        .expect("--tonemap expects none, reinhard or aces");
+                    options.tonemap = Some(tonemap);
+                }
+                "--present-mode" => {
+                    options.present_mode = value
+                        .or_else(|| args.next())
+                        .and_then(|name| PresentMode::parse(&name))
+                        .expect("--present-mode expects mailbox, immediate, vsync or adaptive");
+                }
+                "--overlay" => {
+                    options.overlay = true;
+                    options.transparent = true;
+                }
+                "--unfocused-fps" => {
+                    options.unfocused_fps = value
+                        .or_else(|| args.next())
+                        .and_then(|fps| fps.parse().ok())
+                        .filter(|fps: &f32| *fps >= 0.0)
+                        .expect("--unfocused-fps expects a non-negative number");
+                }
+                "--aa-width" => {
+                    let width = value
+                        .or_else(|| args.next())
+                        .and_then(|width| width.parse().ok())
+                        .filter(|width: &f32| *width > 0.0)
+                        .expect("--aa-width expects a positive number");
+                    options.aa_width = Some(width);
+                }
+                "--min-size" => {
+                    options.min_size = value
+                        .or_else(|| args.next())
+                        .and_then(|size| {
+                            let (width, height) = size.split_once('x')?;
+                            Some(LogicalSize::new(width.parse().ok()?, height.parse().ok()?))
+                        })
+                        .filter(|size: &LogicalSize<u32>| size.width > 0 && size.height > 0)
+                        .expect("--min-size expects WIDTHxHEIGHT, e.g. 640x480");
+                }
+                "--aspect" => {
+                    let aspect_ratio = value
+                        .or_else(|| args.next())
+                        .and_then(|ratio| match ratio.split_once(':') {
+                            Some((width, height)) => {
+                                Some(width.parse::<f32>().ok()? / height.parse::<f32>().ok()?)
+                            }
+                            None => ratio.parse().ok(),
+                        })
+                        .filter(|ratio: &f32| ratio.is_finite() && *ratio > 0.0)
+                        .expect("--aspect expects W:H or a positive ratio, e.g. 16:9");
+                    options.aspect_ratio = Some(aspect_ratio);
+                }
+                "--virtual-resolution" => {
+                    let size = value
+                        .or_else(|| args.next())
+                        .and_then(|size| {
+                            let (width, height) = size.split_once('x')?;
+                            Some(Vec2::new(width.parse().ok()?, height.parse().ok()?))
+                        })
+                        .filter(|size: &Vec2| size.is_finite() && size.min_element() > 0.0)
+                        .expect("--virtual-resolution expects WIDTHxHEIGHT, e.g. 1920x1080");
+                    options.virtual_resolution = Some(size);
+                }
+                "--gpu" => {
+                    let index = value
+                        .or_else(|| args.next())
+                        .and_then(|index| index.parse().ok())
+                        .expect("--gpu expects a device index from --info, e.g. 1");
+                    options.gpu = Some(index);
+                }
+                "--monitor" => {
+                    let index = value
+                        .or_else(|| args.next())
+                        .and_then(|index| index.parse().ok())
+                        .expect("--monitor expects a monitor index, e.g. 1");
+                    options.monitor = Some(index);
+                }
+                "--record" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--record expects a file path, e.g. session.mp4");
+                    options.record_path = Some(path.into());
+                }
+                "--gif" => {
+                    let seconds = value
+                        .or_else(|| args.next())
+                        .and_then(|seconds| seconds.parse().ok())
+                        .filter(|seconds: &f32| *seconds > 0.0)
+                        .expect("--gif expects a positive number of seconds");
+                    options.gif_seconds = Some(seconds);
+                }
+                "--fps-cap" => {
+                    let fps = value
+                        .or_else(|| args.next())
+                        .and_then(|fps| fps.parse().ok())
+                        .filter(|fps: &f32| *fps >= 0.0)
+                        .expect("--fps-cap expects a non-negative number");
+                    options.fps_cap = Some(fps);
+                }
+                "--stutter-ms" => {
+                    options.stutter_ms = value
+                        .or_else(|| args.next())
+                        .and_then(|ms| ms.parse().ok())
+                        .filter(|ms: &f32| ms.is_finite() && *ms > 0.0)
+                        .expect("--stutter-ms expects a positive number of milliseconds");
+                }
+                "--stats-out" => {
+                    let path = value
+                        .or_else(|| args.next())
+                        .expect("--stats-out expects a file path, e.g. stats.csv");
+                    options.stats_path = Some(path.into());
+                }
+                "--swapchain-images" => {
+                    let count = value
+                        .or_else(|| args.next())
+                        .and_then(|count| count.parse().ok())
+                        .filter(|&count: &u32| count > 0)
+                        .expect("--swapchain-images expects a positive number");
+                    options.swapchain_images = Some(count);
+                }
+                "--clear-color" => {
+                    let color = value
+                        .or_else(|| args.next())
+                        .and_then(|color| color::parse_rgb(&color))
+                        .expect("--clear-color expects #rrggbb or r,g,b, e.g. #102030");
+                    options.clear_color = Some(color);
+                }
+                // How Windows starts a screensaver (.scr): `/s` to run it, `/c`
+                // (or `/c:HWND`) for its settings dialog and `/p HWND` for the
+                // preview inside Control Panel's window
+                _ if flag.starts_with('/') => match flag[1..].to_ascii_lowercase().get(..1) {
+                    Some("s") => options.screensaver = true,
+                    Some("c") => {
+                        println!("The screensaver has no settings");
+                        std::process::exit(0);
+                    }
+                    Some("p") => {
+                        println!("Screensaver previews aren't supported");
+                        std::process::exit(0);
+                    }
+                    _ => eprintln!("Ignoring unknown argument: {}", arg),
+                },
+                _ => eprintln!("Ignoring unknown argument: {}", arg),
+            }
+        }
+        options
     }
-    vertices
 }
 
 struct App {
-    window: Option<Window>,
-    entry: ash::Entry,
-    instance: Option<ash::Instance>,
-    surface: vk::SurfaceKHR,
-    physical_device: vk::PhysicalDevice,
-    device: Option<ash::Device>,
-    queue: vk::Queue,
-    swapchain: vk::SwapchainKHR,
-    swapchain_ext: Option<ash::khr::swapchain::Device>,
-    images: Vec<vk::Image>,
-    image_views: Vec<vk::ImageView>,
-    render_pass: vk::RenderPass,
-    framebuffers: Vec<vk::Framebuffer>,
-    command_pool: vk::CommandPool,
-    command_buffer: vk::CommandBuffer,
-    image_available_semaphore: vk::Semaphore,
-    render_finished_semaphore: vk::Semaphore,
-    pipeline: vk::Pipeline,
-    pipeline_layout: vk::PipelineLayout,
-    vertex_buffer: vk::Buffer,
-    vertex_buffer_memory: vk::DeviceMemory,
-    extent: vk::Extent2D,
-    circle_position: Vec2,
-    circle_velocity: Vec2,
+    // Every open window; the renderer's primary window sizes the scene and
+    // drives the frame loop
+    windows: HashMap<WindowId, WindowContext>,
+    renderer: Option<Renderer>,
+    scene_kind: SceneKind,
+    scene_config: SceneConfig,
+    scene: Box<dyn Scene>,
+    draws: DrawList,
+    seed: u64,
+    rng: Rng,
+    input: Input,
+    #[cfg(feature = "gamepad")]
+    gamepad: Option<GamepadInput>,
+    sounds: SoundQueue,
+    muted: bool,
+    #[cfg(feature = "audio")]
+    sound_player: Option<SoundPlayer>,
+    clock: SimulationClock,
+    // Stop rendering continuously while paused
+    power_save: bool,
+    focused: bool,
+    unfocused_fps: f32,
+    aa_width: Option<f32>,
+    min_size: LogicalSize<u32>,
+    aspect_ratio: Option<f32>,
+    virtual_resolution: Option<Vec2>,
+    // Requested with --monitor, resolved when the first window opens
+    monitor_index: Option<usize>,
+    monitor: Option<MonitorHandle>,
+    clear_color: Option<[f32; 3]>,
+    hdr: bool,
+    hdr_encoding: Option<OutputEncoding>,
+    tonemap: Option<Tonemap>,
+    fps_cap: Option<f32>,
+    present_mode: PresentMode,
+    swapchain_images: Option<u32>,
+    transparent: bool,
+    exclusive_fullscreen: bool,
+    device_group: Option<DeviceGroupMode>,
+    // Physical device the GPU context is created on; F1 moves to the next
+    gpu_index: Option<usize>,
+    breadcrumbs: bool,
+    overlay: bool,
+    screensaver: bool,
+    // Where the cursor was first seen in screensaver mode, and in which window
+    screensaver_cursor: Option<(WindowId, PhysicalPosition<f64>)>,
+    // Of the monitor the primary window is on
+    refresh_rate: Option<f32>,
+    frame_start: std::time::Instant,
+    // Frames that missed a refresh since the last title update
+    late_frames: u32,
+    // Recent frames' CPU times, shown as a histogram with the memory overlay
+    frame_times: FrameTimes,
+    // --stats-out, fed every rendered frame
+    stats: Option<StatsWriter>,
+    // --report, written once the first window's swapchain exists
+    report_path: Option<PathBuf>,
+    memory_overlay: bool,
+    // F8 shows `stylize_panel`, whose sliders set the stylized post effects
+    stylize_overlay: bool,
+    stylize_panel: StylizePanel,
+    // Drawn behind the memory overlay; created with the renderer
+    overlay_panel: Option<NinePatch>,
+    record_path: Option<PathBuf>,
+    // F9 recording is on; `recorder` starts with its first frame
+    recording: bool,
+    recorder: Option<VideoRecorder>,
+    // Until the --gif capture is written; `gif` starts with its first frame
+    gif_seconds: Option<f32>,
+    gif: Option<GifRecorder>,
+    export_frames: bool,
+    #[cfg(feature = "remote")]
+    remote: Option<RemoteServer>,
+    // Where F5 saves and F6 restores the simulation state
+    snapshot_path: PathBuf,
+    // Restore `snapshot_path` once the scene has started
+    restore_snapshot: bool,
+    // --record-input, until the recorder starts with the scene
+    record_input_path: Option<PathBuf>,
+    input_recorder: Option<InputRecorder>,
+    // Feeds every frame's input, time and steps while replaying
+    replay: Option<Replay>,
+    // Number of the last frame read back, so no frame is encoded twice
+    last_captured_frame: u64,
+    // Primary window geometry, saved on exit
+    window_state: WindowState,
     last_title_update: std::time::Instant,
     frame_count: u32,
     fps: f32,
@@ -70,15 +613,285 @@ struct App {
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // Back from a suspend: only the surfaces and swapchains are gone
+        if let Some(renderer) = self.renderer.as_mut() {
+            // The other windows follow the primary window's format
+            let primary = renderer.primary_window();
+            let mut ids: Vec<WindowId> = self.windows.keys().copied().collect();
+            ids.sort_by_key(|&id| id != primary);
+            for id in ids {
+                let context = self.windows.get_mut(&id).unwrap();
+                if renderer.resume(context) {
+                    context.window().request_redraw();
+                } else {
+                    renderer.close_window(self.windows.remove(&id).unwrap());
+                }
+            }
+            println!("Resumed with the existing device");
+            return;
+        }
+        if let Some(index) = self.monitor_index {
+            self.monitor = monitor::select(event_loop, index);
+        }
+        // The screensaver covers every monitor, the first with the primary window
+        let monitors: Vec<_> = if self.screensaver {
+            event_loop.available_monitors().collect()
+        } else {
+            Vec::new()
+        };
+        let window = match monitors.first() {
+            Some(monitor) => self.create_screensaver_window(event_loop, monitor.clone()),
+            None => self.create_window(event_loop),
+        };
+        self.refresh_rate = monitor::current_refresh_rate(&window);
+        self.log_frame_cap();
+        let (mut renderer, context) = self.create_renderer(window);
+        self.rng = Rng::new(self.seed);
+        self.scene.create_resources(&mut renderer);
+        println!("Scene: {}", self.scene_kind.name());
+        self.overlay_panel = Some(create_overlay_panel(&mut renderer));
+
+        context.window().request_redraw();
+        for monitor in monitors.into_iter().skip(1) {
+            let window = self.create_screensaver_window(event_loop, monitor);
+            if let Some(context) = renderer.open_window(window) {
+                self.windows.insert(context.id(), context);
+            }
+        }
+        self.renderer = Some(renderer);
+        self.windows.insert(context.id(), context);
+        if self.gif_seconds.is_some() && !self.update_capture() {
+            println!("GIF capture unavailable: the swapchain images can't be copied");
+            self.gif_seconds = None;
+        }
+        if self.restore_snapshot {
+            self.load_snapshot();
+        }
+        self.start_input_recording();
+        // The handles are printed once the first frame creates the images
+        if self.export_frames {
+            let renderer = self.renderer.as_mut().unwrap();
+            let primary = self.windows.get(&renderer.primary_window()).unwrap();
+            if !renderer.set_frame_export(primary, true) {
+                println!(
+                    "Frame export unavailable: needs external memory and copyable swapchain images"
+                );
+            }
+        }
+        if let Some(path) = self.report_path.as_ref() {
+            self.write_startup_report(path);
+        }
+        println!("Resumed event completed");
+    }
+
+    // Android destroys the native window while the app is in the background; the
+    // device and scene stay alive so resuming is quick
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(renderer) = self.renderer.as_mut() {
+            for context in self.windows.values_mut() {
+                renderer.suspend(context);
+            }
+        }
+        println!("Suspended");
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        // Quitting mid-recording still leaves a playable file
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish();
+        }
+        if let Some(recorder) = self.input_recorder.take() {
+            recorder.finish();
+        }
+        if let Some(gif) = self.gif.take() {
+            gif.finish();
+        }
+        if let Some(stats) = self.stats.take() {
+            stats.finish();
+        }
+        // Screensaver windows say nothing about where the window should be
+        if !self.screensaver {
+            self.window_state.save();
+        }
+    }
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        // The background frame while idling
+        if let StartCause::ResumeTimeReached { .. } = cause {
+            if let Some(primary) = self.primary() {
+                primary.window().request_redraw();
+            }
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        if self.screensaver && self.ends_screensaver(window_id, &event) {
+            println!("Input received, ending the screensaver");
+            event_loop.exit();
+            return;
+        }
+        // While idling only window events (input, resizes, exposes) redraw
+        if self.is_idle() && event != WindowEvent::RedrawRequested {
+            if let Some(primary) = self.primary() {
+                primary.window().request_redraw();
+            }
+        }
+        let is_primary = self
+            .renderer
+            .as_ref()
+            .is_some_and(|renderer| renderer.primary_window() == window_id);
+        match event {
+            WindowEvent::CloseRequested if !is_primary => {
+                if let Some(context) = self.windows.remove(&window_id) {
+                    self.renderer.as_mut().unwrap().close_window(context);
+                    println!("Window closed ({} open)", self.windows.len());
+                }
+            }
+            WindowEvent::CloseRequested => {
+                println!("Close requested, exiting");
+                event_loop.exit();
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                self.input.handle_key(&event);
+                if self.input.was_pressed(Action::Quit) {
+                    println!("Escape pressed, exiting");
+                    event_loop.exit();
+                }
+            }
+            WindowEvent::Focused(false) => {
+                self.input.release_all();
+                self.focused = false;
+                // Alt-tab: let the other window have the display
+                if let Some(context) = self.windows.get_mut(&window_id) {
+                    context.release_exclusive();
+                }
+                if self.unfocused_fps > 0.0 {
+                    println!("Window unfocused, throttling to {} FPS", self.unfocused_fps);
+                }
+            }
+            WindowEvent::Focused(true) => {
+                self.focused = true;
+                if let Some(context) = self.windows.get_mut(&window_id) {
+                    context.acquire_exclusive();
+                }
+                // Back to full rate right away rather than after the throttled wait
+                if let Some(primary) = self.primary() {
+                    primary.window().request_redraw();
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = self.to_primary_position(window_id, position);
+                self.input.handle_cursor_moved(position);
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.input.handle_cursor_left();
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.input.handle_mouse_button(state, button);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.input.handle_mouse_wheel(delta);
+            }
+            // The primary window's redraw renders every window
+            WindowEvent::RedrawRequested if !is_primary => {}
+            WindowEvent::RedrawRequested => self.redraw(event_loop),
+            WindowEvent::Moved(position) if is_primary => {
+                let window = self.primary().unwrap().window();
+                let fullscreen = window.fullscreen().is_some();
+                // The window may have moved to another monitor
+                let refresh_rate = monitor::current_refresh_rate(window);
+                // Fullscreen geometry isn't worth remembering
+                if !fullscreen {
+                    self.window_state.position = Some(position);
+                }
+                if refresh_rate != self.refresh_rate {
+                    self.refresh_rate = refresh_rate;
+                    self.log_frame_cap();
+                }
+            }
+            WindowEvent::Resized(new_size) => {
+                let Some(context) = self.windows.get_mut(&window_id) else {
+                    return;
+                };
+                if is_primary && context.window().fullscreen().is_none() {
+                    self.window_state.size = Some(new_size);
+                }
+                #[cfg(target_os = "ios")]
+                vulkan_vibe_coding::surface::resize_metal_layer(context.window());
+                self.renderer
+                    .as_mut()
+                    .unwrap()
+                    .resize(context, new_size.width, new_size.height);
+                // Draw at the new size right away. While an edge is dragged on
+                // Windows, requested redraws lag behind the resize events and the
+                // old frame is shown stretched.
+                self.redraw(event_loop);
+            }
+            // Only the primary window's scale sets the scene's logical units
+            WindowEvent::ScaleFactorChanged { .. } if !is_primary => {}
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // Resized follows with the new physical size
+                self.renderer
+                    .as_mut()
+                    .unwrap()
+                    .set_scale_factor(scale_factor as f32);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl App {
+    // Opens a window with the app icon. Windows after the first show the same
+    // scene; see `open_window`.
+    fn create_window(&self, event_loop: &ActiveEventLoop) -> Window {
+        let mut attributes = Window::default_attributes()
+            .with_title("winit/Vulkan Window - Moving Circle")
+            .with_inner_size(DEFAULT_WINDOW_SIZE)
+            .with_min_inner_size(self.min_size)
+            .with_transparent(self.transparent);
+        if self.overlay {
+            attributes = attributes
+                .with_decorations(false)
+                .with_window_level(WindowLevel::AlwaysOnTop);
+        }
+        // The first window is the primary one and gets the saved geometry, or
+        // the recorded size when replaying
+        if let Some(replay) = self.replay.as_ref().filter(|_| self.windows.is_empty()) {
+            let size = replay.header().window_size;
+            attributes = attributes
+                .with_inner_size(LogicalSize::new(size.x, size.y))
+                .with_resizable(false);
+        } else if self.windows.is_empty() {
+            attributes = self.window_state.apply(attributes);
+            // A chosen monitor wins over the saved position
+            if let Some(monitor) = self.monitor.as_ref() {
+                let size = attributes.inner_size.unwrap_or(DEFAULT_WINDOW_SIZE.into());
+                attributes = attributes.with_position(monitor::centered_position(monitor, size));
+                if attributes.fullscreen.is_some() {
+                    attributes = attributes
+                        .with_fullscreen(Some(Fullscreen::Borderless(Some(monitor.clone()))));
+                }
+            }
+        }
         let window = event_loop
-            .create_window(
-                Window::default_attributes()
-                    .with_title("winit/Vulkan Window - Moving Circle")
-                    .with_inner_size(LogicalSize::new(800, 600)),
-            )
+            .create_window(attributes)
             .expect("Failed to create window");
 
         println!("Window created successfully");
+        // Clicks go to whatever is behind the window. It keeps keyboard focus
+        // until something else is clicked, so Escape still quits.
+        if self.overlay {
+            match window.set_cursor_hittest(false) {
+                Ok(()) => println!("Overlay window is click-through"),
+                Err(e) => println!("Overlay window can't be click-through: {}", e),
+            }
+        }
 
         #[cfg(target_os = "windows")]
         {
@@ -130,1127 +943,1311 @@ impl ApplicationHandler for App {
                 }
             }
         }
+        window
+    }
 
-        self.window = Some(window);
-        self.init_vulkan();
-        println!("Resumed event completed");
+    // Creates the GPU context on `gpu_index` and a renderer with `window` as
+    // its primary window, set up as the command line asks
+    fn create_renderer(&self, window: Window) -> (Renderer, WindowContext) {
+        let mut gpu = GpuContext::with_physical_device(
+            &required_extensions(&window),
+            self.device_group,
+            self.gpu_index,
+        );
+        if self.breadcrumbs && !gpu.enable_breadcrumbs() {
+            println!(
+                "GPU breadcrumbs need VK_NV_device_diagnostic_checkpoints or VK_AMD_buffer_marker"
+            );
+        }
+        let options = SwapchainOptions {
+            hdr: self.hdr,
+            hdr_encoding: self.hdr_encoding,
+            present_mode: self.present_mode,
+            image_count: self.swapchain_images,
+            ..Default::default()
+        };
+        let mut context = WindowContext::new(&gpu, window, self.transparent, &options);
+        if self.hdr && !context.is_hdr() {
+            if gpu.capabilities.swapchain_colorspace {
+                println!("The surface has no HDR format, tonemapping to SDR");
+            } else {
+                println!("HDR needs VK_EXT_swapchain_colorspace, tonemapping to SDR");
+            }
+        }
+        let mut renderer = Renderer::new(gpu, &mut context);
+        let default_tonemap = if self.hdr { Tonemap::Aces } else { Tonemap::None };
+        renderer.set_tonemap(self.tonemap.unwrap_or(default_tonemap));
+        if let Some(width) = self.aa_width {
+            renderer.set_sdf_aa_width(width);
+        }
+        if let Some(color) = self.clear_color {
+            renderer.set_clear_color(color);
+        }
+        // A virtual resolution brings its own aspect ratio
+        if self.virtual_resolution.is_some() {
+            renderer.set_virtual_resolution(&context, self.virtual_resolution);
+        } else if self.aspect_ratio.is_some() {
+            renderer.set_aspect_ratio(&context, self.aspect_ratio);
+        }
+        // Restored fullscreen
+        if self.exclusive_fullscreen && context.window().fullscreen().is_some() {
+            set_exclusive_fullscreen(&mut renderer, &mut context, true);
+        }
+        (renderer, context)
     }
 
-    fn window_event(
-        &mut self,
+    // Borderless fullscreen on `monitor` with the cursor hidden
+    fn create_screensaver_window(
+        &self,
         event_loop: &ActiveEventLoop,
-        _window_id: WindowId,
-        event: WindowEvent,
-    ) {
+        monitor: MonitorHandle,
+    ) -> Window {
+        let attributes = Window::default_attributes()
+            .with_title("Vulkan Vibe")
+            .with_position(monitor.position())
+            .with_fullscreen(Some(Fullscreen::Borderless(Some(monitor))));
+        let window = event_loop
+            .create_window(attributes)
+            .expect("Failed to create screensaver window");
+        window.set_cursor_visible(false);
+        window
+    }
+
+    // Any key, click or scroll ends the screensaver, and so does moving the mouse
+    // more than a little or into another window
+    fn ends_screensaver(&mut self, window_id: WindowId, event: &WindowEvent) -> bool {
         match event {
-            WindowEvent::CloseRequested => {
-                println!("Close requested, exiting");
-                event_loop.exit();
-            }
-            WindowEvent::RedrawRequested => {
-                self.update_circle_position();
-                self.render();
-            }
-            WindowEvent::Resized(_new_size) => {
-                self.recreate_swapchain();
-                self.window.as_ref().unwrap().request_redraw();
+            WindowEvent::KeyboardInput { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. } => true,
+            &WindowEvent::CursorMoved { position, .. } => {
+                let (start_window, start) =
+                    *self.screensaver_cursor.get_or_insert((window_id, position));
+                start_window != window_id
+                    || (position.x - start.x).hypot(position.y - start.y) > SCREENSAVER_CURSOR_SLACK
             }
-            _ => {}
+            _ => false,
         }
     }
-}
 
-impl App {
-    fn init_vulkan(&mut self) {
-        println!("Initializing Vulkan");
-        use std::ffi::{CStr, CString};
-
-        let available_extensions = unsafe {
-            self.entry
-                .enumerate_instance_extension_properties(None)
-                .expect("Failed to enumerate instance extensions")
+    // Runs one frame: input, simulation and rendering to every window
+    fn redraw(&mut self, event_loop: &ActiveEventLoop) {
+        // Nothing to draw to; `resumed` requests a redraw
+        if self.primary().unwrap().is_suspended() {
+            return;
+        }
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepad) = self.gamepad.as_mut() {
+            gamepad.poll(&mut self.input);
+        }
+        // A replay overrides this frame's input, time and steps
+        let replayed = match self.replay.as_mut() {
+            Some(replay) => match replay.next_frame() {
+                Some(frame) => Some(frame.clone()),
+                None => {
+                    self.finish_replay(event_loop);
+                    return;
+                }
+            },
+            None => None,
         };
-        println!("Available Vulkan extensions:");
-        for ext in &available_extensions {
-            let ext_name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
-            println!("- {:?}", ext_name);
+        if let Some(frame) = replayed.as_ref() {
+            self.input.set_state(&frame.input);
         }
-
-        let app_info = vk::ApplicationInfo {
-            api_version: vk::make_api_version(0, 1, 0, 0),
-            ..Default::default()
+        self.handle_actions(event_loop);
+        #[cfg(feature = "remote")]
+        self.handle_remote();
+        self.frame_start = std::time::Instant::now();
+        let mut frame_dt = self.frame_time();
+        if self.is_late(frame_dt) {
+            self.late_frames += 1;
+        }
+        let mut steps = self.clock.advance(frame_dt);
+        let mut stick = Vec2::ZERO;
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepad) = self.gamepad.as_ref() {
+            stick = gamepad.left_stick();
+        }
+        if let Some(frame) = replayed {
+            frame_dt = frame.frame_dt;
+            steps = frame.steps;
+            stick = frame.stick;
+        }
+        if let Some(recorder) = self.input_recorder.as_mut() {
+            recorder.push(&RecordedFrame {
+                frame_dt,
+                steps,
+                stick,
+                input: self.input.state(),
+            });
+        }
+        // Queried once a frame for the memory overlay and the stats file, and
+        // only when one of them is on: it takes the allocator lock and asks the
+        // driver
+        let memory = (self.memory_overlay || self.stats.is_some())
+            .then(|| self.renderer.as_ref().unwrap().gpu.memory_report());
+        let memory_overlay = memory.as_ref().filter(|_| self.memory_overlay);
+        if self.stylize_overlay {
+            self.handle_stylize_panel(memory_overlay);
+        }
+        let renderer = self.renderer.as_mut().unwrap();
+        let mut context = SceneContext {
+            renderer,
+            input: &self.input,
+            rng: &mut self.rng,
+            sounds: &mut self.sounds,
+            stick,
+            frame_dt,
+            steps,
+            fixed_dt: FIXED_DT,
         };
-
-        let mut instance_extension_names = vec![
-            CString::new("VK_KHR_surface").unwrap(),
-            CString::new("VK_KHR_portability_enumeration").unwrap(),
-        ];
-        #[cfg(target_os = "windows")]
-        instance_extension_names.push(CString::new("VK_KHR_win32_surface").unwrap());
-        #[cfg(target_os = "macos")]
-        instance_extension_names.push(CString::new("VK_EXT_metal_surface").unwrap());
-        #[cfg(target_os = "linux")]
         {
-            instance_extension_names.push(CString::new("VK_KHR_xlib_surface").unwrap());
-            instance_extension_names.push(CString::new("VK_KHR_wayland_surface").unwrap());
+            vulkan_vibe_coding::zone!("update");
+            self.scene.update(&mut context);
+            self.play_sounds();
+            self.draws.clear();
+            self.scene.record_draws(&mut self.draws);
         }
-
-        let instance_extension_names_ptrs: Vec<*const std::os::raw::c_char> =
-            instance_extension_names
-                .iter()
-                .map(|c| c.as_ptr())
-                .collect();
-
-        let instance_create_info = vk::InstanceCreateInfo {
-            p_application_info: &app_info,
-            enabled_extension_count: instance_extension_names_ptrs.len() as u32,
-            pp_enabled_extension_names: instance_extension_names_ptrs.as_ptr(),
-            flags: vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR,
-            ..Default::default()
-        };
-
-        println!(
-            "Attempting to create Vulkan instance with extensions: {:?}",
-            instance_extension_names
-        );
-        match unsafe { self.entry.create_instance(&instance_create_info, None) } {
-            Ok(instance) => {
-                self.instance = Some(instance);
-                println!("Vulkan instance created successfully");
-            }
-            Err(e) => {
-                println!("Failed to create Vulkan instance: {:?}", e);
-                return;
+        // Animated backgrounds keep simulation time, so they stop with pause
+        self.renderer
+            .as_mut()
+            .unwrap()
+            .advance_background(steps as f32 * FIXED_DT);
+        self.renderer
+            .as_ref()
+            .unwrap()
+            .draw_shading_rate_overlay(&mut self.draws.canvas);
+        if let Some(report) = memory_overlay {
+            let size = self.memory_overlay_size(report);
+            if let Some(panel) = self.overlay_panel.as_ref() {
+                let padding = Vec2::splat(OVERLAY_PANEL_PADDING);
+                self.draws.sprites.draw_nine_patch(
+                    panel,
+                    MEMORY_OVERLAY_ORIGIN - padding,
+                    size + padding * 2.0,
+                    [1.0, 1.0, 1.0, 1.0],
+                );
             }
+            report.draw_overlay(
+                &mut self.draws.canvas,
+                MEMORY_OVERLAY_ORIGIN,
+                MEMORY_OVERLAY_WIDTH,
+            );
+            let histogram_top = report.overlay_size(MEMORY_OVERLAY_WIDTH).y + OVERLAY_PANEL_GAP;
+            self.frame_times.draw_overlay(
+                &mut self.draws.canvas,
+                MEMORY_OVERLAY_ORIGIN + Vec2::new(0.0, histogram_top),
+                MEMORY_OVERLAY_WIDTH,
+            );
         }
-
-        // Surface creation
-        println!("Creating Vulkan surface");
-        let window = self.window.as_ref().unwrap();
-        println!("Got window reference");
-        let raw_window_handle = window.window_handle().expect("Failed to get window handle").as_raw();
-        println!("Got raw window handle");
-        match raw_window_handle {
-            #[cfg(target_os = "windows")]
-            RawWindowHandle::Win32(handle) => {
-                let surface_create_info = vk::Win32SurfaceCreateInfoKHR {
-                    hinstance: handle.hinstance.map(|nz| nz.get()).unwrap_or(0),
-                    hwnd: handle.hwnd.get(),
-                    ..Default::default()
-                };
-                let win32_surface_instance = ash::khr::win32_surface::Instance::new(&self.entry, self.instance.as_ref().unwrap());
-                match unsafe { win32_surface_instance.create_win32_surface(&surface_create_info, None) } {
-                    Ok(surface) => {
-                        self.surface = surface;
-                        println!("Vulkan surface created successfully (Windows)");
-                    }
-                    Err(e) => {
-                        println!("Failed to create Vulkan surface: {:?}", e);
-                        return;
-                    }
-                }
-            }
-            #[cfg(target_os = "macos")]
-            RawWindowHandle::AppKit(handle) => {
-                #[cfg(target_os = "macos")]
-                use ash::ext::metal_surface;
-
-                #[cfg(target_os = "macos")]
-                #[allow(unexpected_cfgs)]
-                autoreleasepool(|| {
-                    let ns_view = handle.ns_view.as_ptr() as *mut Object;
-                    println!("NSView pointer: {:p}", ns_view);
-
-                    // Create a CAMetalLayer
-                    let metal_layer: *mut Object = unsafe { msg_send![class!(CAMetalLayer), layer] };
-                    println!("Created CAMetalLayer: {:p}", metal_layer);
-
-                    // Set the layer on the NSView
-                    unsafe {
-                        let () = msg_send![ns_view, setLayer: metal_layer];
-                        let () = msg_send![ns_view, setWantsLayer: YES];
-                        let () = msg_send![metal_layer, setDisplaySyncEnabled: NO];
-                    }
-                    println!("Set CAMetalLayer on NSView");
-
-                    // Create Vulkan surface with the CAMetalLayer
-                    let surface_create_info = vk::MetalSurfaceCreateInfoEXT {
-                        s_type: vk::StructureType::METAL_SURFACE_CREATE_INFO_EXT,
-                        p_next: std::ptr::null(),
-                        flags: vk::MetalSurfaceCreateFlagsEXT::empty(),
-                        p_layer: metal_layer as *const _,
-                        _marker: std::marker::PhantomData,
-                    };
-                    println!("Building surface create info");
-                    let metal_surface_instance = metal_surface::Instance::new(&self.entry, self.instance.as_ref().unwrap());
-                    println!("Creating metal surface instance");
-                    println!("Attempting to create metal surface");
-                    match unsafe { metal_surface_instance.create_metal_surface(&surface_create_info, None) } {
-                        Ok(surface) => {
-                            self.surface = surface;
-                            println!("Vulkan surface created successfully (macOS)");
-                        }
-                        Err(e) => {
-                            println!("Failed to create Vulkan surface: {:?}", e);
-                            return;
-                        }
-                    }
-                });
-            }
-            #[cfg(target_os = "linux")]
-            RawWindowHandle::Xlib(handle) => {
-                let display_handle = self.window.as_ref().unwrap().display_handle().expect("Failed to get display handle");
-                let xlib_display_handle = match display_handle.as_raw() {
-                    RawDisplayHandle::Xlib(xlib) => xlib,
-                    _ => panic!("Expected Xlib display handle for X11 window"),
-                };
-                let display = xlib_display_handle.display.unwrap().as_ptr();
-                let surface_create_info = vk::XlibSurfaceCreateInfoKHR {
-                    dpy: display,
-                    window: handle.window,
-                    ..Default::default()
-                };
-                let xlib_surface_instance = ash::khr::xlib_surface::Instance::new(&self.entry, self.instance.as_ref().unwrap());
-                self.surface = unsafe { xlib_surface_instance.create_xlib_surface(&surface_create_info, None).expect("Failed to create Xlib surface") };
-                println!("Vulkan surface created successfully (Linux X11)");
-            }
-            #[cfg(target_os = "linux")]
-            RawWindowHandle::Wayland(handle) => {
-                let display_handle = self.window.as_ref().unwrap().display_handle().expect("Failed to get display handle");
-                let wayland_display_handle = match display_handle.as_raw() {
-                    RawDisplayHandle::Wayland(wayland) => wayland,
-                    _ => panic!("Expected Wayland display handle for Wayland window"),
-                };
-                let display = wayland_display_handle.display.as_ptr();
-                let surface = handle.surface.as_ptr(); // Get surface from RawWindowHandle::Wayland
-                let surface_create_info = vk::WaylandSurfaceCreateInfoKHR {
-                    display,
-                    surface,
-                    ..Default::default()
-                };
-                let wayland_surface_instance = ash::khr::wayland_surface::Instance::new(&self.entry, self.instance.as_ref().unwrap());
-                self.surface = unsafe { wayland_surface_instance.create_wayland_surface(&surface_create_info, None).expect("Failed to create Wayland surface") };
-                println!("Vulkan surface created successfully (Linux Wayland)");
+        if self.stylize_overlay {
+            if let Some(panel) = self.overlay_panel.as_ref() {
+                let padding = Vec2::splat(OVERLAY_PANEL_PADDING);
+                self.draws.sprites.draw_nine_patch(
+                    panel,
+                    self.stylize_panel.origin() - padding,
+                    self.stylize_panel.size() + padding * 2.0,
+                    [1.0, 1.0, 1.0, 1.0],
+                );
             }
-            _ => panic!("Unsupported platform."),
+            self.stylize_panel.draw(&mut self.draws.canvas);
+        }
+        if self.overlay {
+            self.draw_fps_meter();
+        }
+        let mut windows: Vec<_> = self.windows.values_mut().collect();
+        self.renderer.as_mut().unwrap().render(
+            &mut windows,
+            &self.draws.circles,
+            &self.draws.canvas,
+            &self.draws.sprites,
+        );
+        // The wait for the display is pacing, not work, so it's left out
+        let renderer = self.renderer.as_ref().unwrap();
+        let cpu_time = self.frame_start.elapsed();
+        self.frame_times
+            .push(renderer.frame_number(), cpu_time, renderer.frame_phases());
+        if let (Some(stats), Some(memory)) = (self.stats.as_mut(), memory.as_ref()) {
+            stats.push(
+                renderer.frame_number(),
+                cpu_time,
+                renderer.frame_phases(),
+                self.fps,
+                memory,
+            );
+        }
+        // Events that arrive while this waits are read by the next frame
+        let primary = &self.windows[&self.renderer.as_ref().unwrap().primary_window()];
+        self.renderer.as_mut().unwrap().wait_for_present(primary);
+        profiling::frame_mark();
+        self.record_frame(event_loop);
+        self.update_fps();
+        self.input.end_frame();
+
+        if let Some(interval) = self.frame_interval() {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(self.frame_start + interval));
+        } else {
+            // Request the next frame
+            event_loop.set_control_flow(ControlFlow::Wait);
+            self.primary().unwrap().window().request_redraw();
         }
+    }
 
-        // Physical device enumeration
-        let physical_devices = unsafe {
-            self.instance
-                .as_ref()
-                .unwrap()
-                .enumerate_physical_devices()
-                .expect("Failed to enumerate physical devices")
-        };
-        println!("Found {} physical devices", physical_devices.len());
-        self.physical_device = physical_devices[0]; // Pick the first one for now
-        println!("Selected physical device: {:?}", self.physical_device);
-
-        // Queue family selection and device creation
-        let queue_family_properties = unsafe {
-            self.instance
-                .as_ref()
-                .unwrap()
-                .get_physical_device_queue_family_properties(self.physical_device)
-        };
-        println!("Found {} queue families", queue_family_properties.len());
-        let queue_family_index = queue_family_properties
-            .iter()
-            .position(|props| props.queue_flags.contains(vk::QueueFlags::GRAPHICS))
-            .expect("No graphics queue family found") as u32;
-        println!("Selected queue family index: {}", queue_family_index);
-
-        let device_extension_names = vec![CString::new("VK_KHR_swapchain").unwrap()];
-        let device_extension_names_ptrs: Vec<*const std::os::raw::c_char> =
-            device_extension_names.iter().map(|c| c.as_ptr()).collect();
-        let device_create_info = vk::DeviceCreateInfo {
-            queue_create_info_count: 1,
-            p_queue_create_infos: &vk::DeviceQueueCreateInfo {
-                queue_family_index,
-                queue_count: 1,
-                p_queue_priorities: &1.0,
-                ..Default::default()
-            },
-            enabled_extension_count: device_extension_names_ptrs.len() as u32,
-            pp_enabled_extension_names: device_extension_names_ptrs.as_ptr(),
-            ..Default::default()
-        };
-        self.device = Some(unsafe {
-            self.instance
-                .as_ref()
-                .unwrap()
-                .create_device(self.physical_device, &device_create_info, None)
-                .expect("Failed to create Vulkan device")
-        });
-        println!("Vulkan device created successfully");
-        self.queue = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .get_device_queue(queue_family_index, 0)
-        };
-        println!("Graphics queue obtained: {:?}", self.queue);
-
-        // Swapchain creation
-        let surface_instance =
-            ash::khr::surface::Instance::new(&self.entry, self.instance.as_ref().unwrap());
-        let surface_capabilities = unsafe {
-            surface_instance
-                .get_physical_device_surface_capabilities(self.physical_device, self.surface)
-                .expect("Failed to get surface capabilities")
-        };
-        let surface_formats = unsafe {
-            surface_instance
-                .get_physical_device_surface_formats(self.physical_device, self.surface)
-                .expect("Failed to get surface formats")
-        };
-        let present_modes = unsafe {
-            surface_instance
-                .get_physical_device_surface_present_modes(self.physical_device, self.surface)
-                .expect("Failed to get present modes")
-        };
-        println!("Surface formats: {:?}", surface_formats);
-        println!("Present modes: {:?}", present_modes);
+    fn primary(&self) -> Option<&WindowContext> {
+        let renderer = self.renderer.as_ref()?;
+        self.windows.get(&renderer.primary_window())
+    }
 
-        let format = surface_formats[0];
-        let present_mode = present_modes
-            .into_iter()
-            .find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
-            .unwrap_or(vk::PresentModeKHR::IMMEDIATE);
-        let extent = if surface_capabilities.current_extent.width == u32::MAX {
-            let window_size = window.inner_size();
-            vk::Extent2D {
-                width: window_size.width,
-                height: window_size.height,
-            }
-        } else {
-            surface_capabilities.current_extent
-        };
-        let image_count = surface_capabilities.min_image_count + 1;
-        let image_count = if surface_capabilities.max_image_count > 0 {
-            image_count.min(surface_capabilities.max_image_count)
-        } else {
-            image_count
+    // Opens another window on the same simulation. Its swapchain gets the final
+    // post pass every frame, so it shows the primary window's frame scaled to fit.
+    fn open_window(&mut self, event_loop: &ActiveEventLoop) {
+        let window = self.create_window(event_loop);
+        let Some(context) = self.renderer.as_mut().unwrap().open_window(window) else {
+            return;
         };
+        self.windows.insert(context.id(), context);
+        println!("Window opened ({} open)", self.windows.len());
+        self.update_title();
+    }
 
-        let swapchain_create_info = vk::SwapchainCreateInfoKHR {
-            surface: self.surface,
-            min_image_count: image_count,
-            image_format: format.format,
-            image_color_space: format.color_space,
-            image_extent: extent,
-            image_array_layers: 1,
-            image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
-            pre_transform: surface_capabilities.current_transform,
-            composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
-            present_mode,
-            clipped: vk::TRUE,
-            ..Default::default()
-        };
-        self.swapchain_ext = Some(ash::khr::swapchain::Device::new(
-            self.instance.as_ref().unwrap(),
-            self.device.as_ref().unwrap(),
-        ));
-        self.swapchain = unsafe {
-            self.swapchain_ext
-                .as_ref()
-                .unwrap()
-                .create_swapchain(&swapchain_create_info, None)
-                .expect("Failed to create swapchain")
+    // Switches every window to the next present mode the primary window's surface
+    // supports
+    fn cycle_present_mode(&mut self) {
+        let primary = self.primary().unwrap();
+        let supported = primary.supported_present_modes();
+        let current = primary.active_present_mode();
+        let Some(position) = supported.iter().position(|&mode| mode == current) else {
+            return;
         };
-        println!("Swapchain created: {:?}", self.swapchain);
-        self.images = unsafe {
-            self.swapchain_ext
-                .as_ref()
-                .unwrap()
-                .get_swapchain_images(self.swapchain)
-                .expect("Failed to get swapchain images")
-        };
-        println!("Swapchain images obtained: {:?}", self.images);
-
-        // Image views creation
-        self.image_views = self
-            .images
-            .iter()
-            .map(|&image| {
-                let create_info = vk::ImageViewCreateInfo {
-                    image,
-                    view_type: vk::ImageViewType::TYPE_2D,
-                    format: format.format,
-                    components: vk::ComponentMapping::default(),
-                    subresource_range: vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: 1,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    },
-                    ..Default::default()
-                };
-                unsafe {
-                    self.device
-                        .as_ref()
-                        .unwrap()
-                        .create_image_view(&create_info, None)
-                        .expect("Failed to create image view")
-                }
-            })
-            .collect();
-        println!("Image views created: {:?}", self.image_views);
-
-        // Render pass creation
-        let attachment = vk::AttachmentDescription {
-            format: format.format,
-            samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::STORE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-            ..Default::default()
-        };
-        let color_attachment_ref = vk::AttachmentReference {
-            attachment: 0,
-            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        };
-        let subpass = vk::SubpassDescription {
-            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
-            color_attachment_count: 1,
-            p_color_attachments: &color_attachment_ref,
-            ..Default::default()
-        };
-        let render_pass_create_info = vk::RenderPassCreateInfo {
-            attachment_count: 1,
-            p_attachments: &attachment,
-            subpass_count: 1,
-            p_subpasses: &subpass,
-            ..Default::default()
-        };
-        self.render_pass = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_render_pass(&render_pass_create_info, None)
-                .expect("Failed to create render pass")
-        };
-        println!("Render pass created: {:?}", self.render_pass);
-
-        // Framebuffers creation
-        self.framebuffers = self
-            .image_views
-            .iter()
-            .map(|&image_view| {
-                let framebuffer_create_info = vk::FramebufferCreateInfo {
-                    render_pass: self.render_pass,
-                    attachment_count: 1,
-                    p_attachments: &image_view,
-                    width: extent.width,
-                    height: extent.height,
-                    layers: 1,
-                    ..Default::default()
-                };
-                unsafe {
-                    self.device
-                        .as_ref()
-                        .unwrap()
-                        .create_framebuffer(&framebuffer_create_info, None)
-                        .expect("Failed to create framebuffer")
-                }
-            })
-            .collect();
-        println!("Framebuffers created: {:?}", self.framebuffers);
+        self.present_mode = supported[(position + 1) % supported.len()];
+        let mut windows: Vec<_> = self.windows.values_mut().collect();
+        self.renderer
+            .as_mut()
+            .unwrap()
+            .set_present_mode(&mut windows, self.present_mode);
+        println!("Present mode: {}", describe_present_modes(self.primary().unwrap()));
+        self.log_frame_cap();
+        self.update_title();
+    }
 
-        // Command pool creation
-        let command_pool_create_info = vk::CommandPoolCreateInfo {
-            queue_family_index,
-            ..Default::default()
-        };
-        self.command_pool = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_command_pool(&command_pool_create_info, None)
-                .expect("Failed to create command pool")
-        };
-        println!("Command pool created: {:?}", self.command_pool);
-
-        // Command buffer allocation
-        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
-            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
-            p_next: std::ptr::null(),
-            _marker: std::marker::PhantomData,
-            command_pool: self.command_pool,
-            level: vk::CommandBufferLevel::PRIMARY,
-            command_buffer_count: 1,
-        };
-        self.command_buffer = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .allocate_command_buffers(&command_buffer_allocate_info)
-                .expect("Failed to allocate command buffers")[0]
-        };
-        println!("Command buffer allocated: {:?}", self.command_buffer);
-
-        // Semaphore creation
-        self.image_available_semaphore = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
-                .expect("Failed to create image available semaphore")
-        };
-        println!(
-            "Image available semaphore created: {:?}",
-            self.image_available_semaphore
-        );
-        self.render_finished_semaphore = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
-                .expect("Failed to create render finished semaphore")
+    // Rebuilds everything on the next physical device
+    fn switch_gpu(&mut self) {
+        let Some(renderer) = self.renderer.as_ref() else {
+            return;
         };
+        let count = renderer.gpu.physical_device_count;
+        if count < 2 {
+            println!("There is only one GPU");
+            return;
+        }
+        if renderer.gpu.device_group.is_some() {
+            println!("Leaving the device group to render on one GPU");
+            self.device_group = None;
+        }
+        self.gpu_index = Some((renderer.gpu.physical_device_index + 1) % count);
+        self.rebuild_renderer();
+        let gpu = &self.renderer.as_ref().unwrap().gpu;
         println!(
-            "Render finished semaphore created: {:?}",
-            self.render_finished_semaphore
+            "Switched to GPU {} of {}: {}",
+            gpu.physical_device_index, count, gpu.device_name
         );
-
-        // Vertex buffer creation
-        let vertices = create_circle_vertices(50.0, 32);
-        self.create_vertex_buffer(&vertices);
-
-        // Graphics pipeline creation
-        self.create_graphics_pipeline();
-
-        // Set extent (move this after swapchain creation, before image views)
-        self.extent = extent;
-
-        // Initialize circle position and velocity
-        self.circle_position = Vec2::new(
-            self.extent.width as f32 / 2.0,
-            self.extent.height as f32 / 2.0,
-        );
-        self.circle_velocity = Vec2::new(200.0, 150.0); // pixels per second
-        self.window.as_ref().unwrap().request_redraw();
     }
 
-    fn create_vertex_buffer(&mut self, vertices: &[Vertex]) {
-        let buffer_size = size_of_val(vertices) as vk::DeviceSize;
-        let buffer_create_info = vk::BufferCreateInfo {
-            size: buffer_size,
-            usage: vk::BufferUsageFlags::VERTEX_BUFFER,
-            sharing_mode: vk::SharingMode::EXCLUSIVE,
-            ..Default::default()
-        };
-
-        self.vertex_buffer = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_buffer(&buffer_create_info, None)
-                .expect("Failed to create vertex buffer")
-        };
-
-        let mem_requirements = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .get_buffer_memory_requirements(self.vertex_buffer)
+    // Rebuilds the device, renderer and every window's swapchain, on the
+    // --gpu device. The scene, clock and RNG carry over, so the simulation
+    // goes on where it was; what only lives on the GPU, like particles, starts
+    // over.
+    fn rebuild_renderer(&mut self) {
+        let Some(mut renderer) = self.renderer.take() else {
+            return;
         };
+        // Some scenes start over in `create_resources`, so their state goes
+        // across the way a snapshot would
+        let state = self.scene.save_state();
+        let tonemap = renderer.tonemap();
+        let circle_mode = renderer.circle_mode();
+        let debug_view = renderer.debug_view();
+        let shading_rate_mode = renderer.shading_rate_mode();
+        let exporting = renderer.frame_export().is_some();
+        self.scene.destroy_resources(&mut renderer);
+        let primary_id = renderer.primary_window();
+        let mut primary = None;
+        let mut others = Vec::new();
+        for (id, context) in std::mem::take(&mut self.windows) {
+            let window = renderer.release_window(context).unwrap();
+            if id == primary_id {
+                primary = Some(window);
+            } else {
+                others.push(window);
+            }
+        }
+        renderer.destroy();
+
+        // Settings from the command line are applied again; these may have
+        // changed since
+        let (mut renderer, context) = self.create_renderer(primary.unwrap());
+        renderer.set_tonemap(tonemap);
+        renderer.set_circle_mode(circle_mode);
+        if !renderer.set_debug_view(debug_view) {
+            println!("Wireframe view unavailable: the device lacks fillModeNonSolid");
+        }
+        if let Some(mode) = shading_rate_mode {
+            renderer.set_shading_rate_mode(mode);
+        }
+        self.scene.create_resources(&mut renderer);
+        if let Some(state) = state {
+            if let Err(error) = self.scene.load_state(state) {
+                eprintln!("Failed to carry the scene over: {}", error);
+            }
+        }
+        let effects = self.stylize_panel.stylize.apply(renderer.post_effects());
+        renderer.set_post_effects(effects);
+        self.overlay_panel = Some(create_overlay_panel(&mut renderer));
+        for context in others
+            .into_iter()
+            .filter_map(|window| renderer.open_window(window))
+        {
+            self.windows.insert(context.id(), context);
+        }
+        self.windows.insert(context.id(), context);
+        self.renderer = Some(renderer);
+        // The new renderer counts its frames from zero
+        self.last_captured_frame = 0;
+        if (self.recording || self.gif_seconds.is_some()) && !self.update_capture() {
+            println!("Capture unavailable on this GPU: the swapchain images can't be copied");
+        }
+        if exporting {
+            let renderer = self.renderer.as_mut().unwrap();
+            let primary = self.windows.get(&renderer.primary_window()).unwrap();
+            if !renderer.set_frame_export(primary, true) {
+                println!("Frame export unavailable on this GPU");
+            }
+        }
+        for context in self.windows.values() {
+            context.window().request_redraw();
+        }
+        self.update_title();
+    }
 
-        let memory_type_index = self.find_memory_type(
-            mem_requirements.memory_type_bits,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        );
+    // Borderless fullscreen for the primary window, on the --monitor one if given
+    // and otherwise the current one
+    fn toggle_fullscreen(&mut self) {
+        let window = self.primary().unwrap().window();
+        let fullscreen = window.fullscreen().is_none();
+        window.set_fullscreen(fullscreen.then(|| Fullscreen::Borderless(self.monitor.clone())));
+        self.window_state.fullscreen = fullscreen;
+        println!("Fullscreen {}", if fullscreen { "on" } else { "off" });
+        if self.exclusive_fullscreen {
+            let renderer = self.renderer.as_mut().unwrap();
+            let context = self.windows.get_mut(&renderer.primary_window()).unwrap();
+            set_exclusive_fullscreen(renderer, context, fullscreen);
+        }
+    }
 
-        let alloc_info = vk::MemoryAllocateInfo {
-            allocation_size: mem_requirements.size,
-            memory_type_index,
-            ..Default::default()
+    // Cursor positions are kept in primary window pixels. Other windows show the
+    // same frame scaled to their size, so positions are mapped from the scene's
+    // area in one window to its area in the other.
+    fn to_primary_position(
+        &self,
+        window_id: WindowId,
+        position: PhysicalPosition<f64>,
+    ) -> PhysicalPosition<f64> {
+        let (Some(context), Some(primary)) = (self.windows.get(&window_id), self.primary()) else {
+            return position;
         };
-
-        self.vertex_buffer_memory = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .allocate_memory(&alloc_info, None)
-                .expect("Failed to allocate vertex buffer memory")
+        let renderer = self.renderer.as_ref().unwrap();
+        // Offset and size of the scene's area in a window
+        let area = |extent| {
+            let rect = renderer.letterbox(extent);
+            (
+                Vec2::new(rect.offset.x as f32, rect.offset.y as f32),
+                Vec2::new(
+                    rect.extent.width.max(1) as f32,
+                    rect.extent.height.max(1) as f32,
+                ),
+            )
         };
-
-        unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .bind_buffer_memory(self.vertex_buffer, self.vertex_buffer_memory, 0)
-                .expect("Failed to bind vertex buffer memory");
-
-            let data_ptr = self
-                .device
-                .as_ref()
-                .unwrap()
-                .map_memory(
-                    self.vertex_buffer_memory,
-                    0,
-                    buffer_size,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .expect("Failed to map memory") as *mut Vertex;
-            data_ptr.copy_from_nonoverlapping(vertices.as_ptr(), vertices.len());
-            self.device
-                .as_ref()
-                .unwrap()
-                .unmap_memory(self.vertex_buffer_memory);
-        }
-        println!("Vertex buffer created: {:?}", self.vertex_buffer);
+        let (from_offset, from_size) = area(context.window_extent());
+        let (to_offset, to_size) = area(primary.window_extent());
+        let position = Vec2::new(position.x as f32, position.y as f32);
+        let mapped = (position - from_offset) * to_size / from_size + to_offset;
+        PhysicalPosition::new(mapped.x as f64, mapped.y as f64)
     }
 
-    fn create_graphics_pipeline(&mut self) {
-        let vertex_shader_code = include_bytes!("../shaders/vert.spv");
-        let vertex_shader_module = self.create_shader_module(vertex_shader_code);
-
-        let fragment_shader_code = include_bytes!("../shaders/frag.spv");
-        let fragment_shader_module = self.create_shader_module(fragment_shader_code);
-
-        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo {
-            vertex_binding_description_count: 1,
-            p_vertex_binding_descriptions: &vk::VertexInputBindingDescription {
-                binding: 0,
-                stride: size_of::<Vertex>() as u32,
-                input_rate: vk::VertexInputRate::VERTEX,
-            },
-            vertex_attribute_description_count: 1,
-            p_vertex_attribute_descriptions: &vk::VertexInputAttributeDescription {
-                location: 0,
-                binding: 0,
-                format: vk::Format::R32G32_SFLOAT,
-                offset: 0,
-            },
-            ..Default::default()
-        };
-
-        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
-            push_constant_range_count: 1,
-            p_push_constant_ranges: &vk::PushConstantRange {
-                stage_flags: vk::ShaderStageFlags::VERTEX,
-                offset: 0,
-                size: std::mem::size_of::<Mat4>() as u32,
-            },
-            ..Default::default()
-        };
-        self.pipeline_layout = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_pipeline_layout(&pipeline_layout_create_info, None)
-                .expect("Failed to create pipeline layout")
-        };
+    // Power save with nothing simulating: render only on window events and
+    // every BACKGROUND_FRAME_INTERVAL
+    fn is_idle(&self) -> bool {
+        self.power_save && self.clock.is_paused()
+    }
 
-        let shader_stages = [
-            vk::PipelineShaderStageCreateInfo {
-                stage: vk::ShaderStageFlags::VERTEX,
-                module: vertex_shader_module,
-                p_name: b"main\0".as_ptr() as *const _,
-                ..Default::default()
-            },
-            vk::PipelineShaderStageCreateInfo {
-                stage: vk::ShaderStageFlags::FRAGMENT,
-                module: fragment_shader_module,
-                p_name: b"main\0".as_ptr() as *const _,
-                ..Default::default()
-            },
-        ];
-
-        let pipeline_info = vk::GraphicsPipelineCreateInfo {
-            stage_count: 2,
-            p_stages: shader_stages.as_ptr(),
-            p_vertex_input_state: &vertex_input_info,
-            p_input_assembly_state: &vk::PipelineInputAssemblyStateCreateInfo {
-                topology: vk::PrimitiveTopology::TRIANGLE_FAN,
-                ..Default::default()
-            },
-            p_viewport_state: &vk::PipelineViewportStateCreateInfo {
-                viewport_count: 1,
-                scissor_count: 1,
-                ..Default::default()
-            },
-            p_rasterization_state: &vk::PipelineRasterizationStateCreateInfo {
-                polygon_mode: vk::PolygonMode::FILL,
-                line_width: 1.0,
-                cull_mode: vk::CullModeFlags::NONE,
-                front_face: vk::FrontFace::CLOCKWISE,
-                ..Default::default()
-            },
-            p_multisample_state: &vk::PipelineMultisampleStateCreateInfo {
-                rasterization_samples: vk::SampleCountFlags::TYPE_1,
-                ..Default::default()
-            },
-            p_color_blend_state: &vk::PipelineColorBlendStateCreateInfo {
-                attachment_count: 1,
-                p_attachments: &vk::PipelineColorBlendAttachmentState {
-                    blend_enable: vk::FALSE,
-                    color_write_mask: vk::ColorComponentFlags::RGBA,
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            p_dynamic_state: &vk::PipelineDynamicStateCreateInfo {
-                dynamic_state_count: 2,
-                p_dynamic_states: [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR].as_ptr(),
-                ..Default::default()
-            },
-            layout: self.pipeline_layout,
-            render_pass: self.render_pass,
-            subpass: 0,
-            ..Default::default()
-        };
+    // Time from the start of one frame to the next, None to render continuously.
+    // Idling, an unfocused window and the FPS cap all slow rendering down; the
+    // slowest one wins.
+    fn frame_interval(&self) -> Option<std::time::Duration> {
+        let idle = self.is_idle().then_some(BACKGROUND_FRAME_INTERVAL);
+        let unfocused = (!self.focused && self.unfocused_fps > 0.0)
+            .then(|| std::time::Duration::from_secs_f32(1.0 / self.unfocused_fps));
+        let capped = self
+            .frame_cap()
+            .map(|fps| std::time::Duration::from_secs_f32(1.0 / fps));
+        idle.max(unfocused).max(capped)
+    }
 
-        self.pipeline = unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
-                .expect("Failed to create graphics pipeline")[0]
-        };
+    // Frames per second rendering is held to: --fps-cap, or the monitor's
+    // refresh rate when not given and the present mode doesn't already wait for
+    // it. None when uncapped.
+    fn frame_cap(&self) -> Option<f32> {
+        match self.fps_cap {
+            Some(fps) => (fps > 0.0).then_some(fps),
+            None => self.refresh_rate.filter(|_| !self.is_vsync()),
+        }
+    }
 
-        unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .destroy_shader_module(vertex_shader_module, None);
-            self.device
-                .as_ref()
-                .unwrap()
-                .destroy_shader_module(fragment_shader_module, None);
-        }
-        println!("Graphics pipeline created: {:?}", self.pipeline);
+    // Whether the primary window presents in step with the display, going by
+    // the requested mode until there is a window
+    fn is_vsync(&self) -> bool {
+        self.primary()
+            .map_or(self.present_mode, WindowContext::active_present_mode)
+            .is_vsync()
     }
 
-    fn find_memory_type(&self, type_filter: u32, properties: vk::MemoryPropertyFlags) -> u32 {
-        let mem_properties = unsafe {
-            self.instance
-                .as_ref()
-                .unwrap()
-                .get_physical_device_memory_properties(self.physical_device)
-        };
-        for i in 0..mem_properties.memory_type_count {
-            if (type_filter & (1 << i)) != 0
-                && (mem_properties.memory_types[i as usize].property_flags & properties)
-                    == properties
-            {
-                return i;
-            }
+    fn log_frame_cap(&self) {
+        match self.refresh_rate {
+            Some(hz) => println!("Monitor refresh rate: {:.2} Hz", hz),
+            None => println!("Monitor refresh rate unknown"),
+        }
+        match self.frame_cap() {
+            Some(fps) => println!("Frame rate capped to {:.2} FPS", fps),
+            None => println!("Frame rate uncapped"),
         }
-        panic!("Failed to find suitable memory type");
     }
 
-    fn create_shader_module(&self, code: &[u8]) -> vk::ShaderModule {
-        let create_info = vk::ShaderModuleCreateInfo {
-            code_size: code.len(),
-            p_code: code.as_ptr() as *const u32,
-            ..Default::default()
+    // Whether a frame `frame_dt` after the previous one missed its slot: a refresh,
+    // or a capped frame when the cap is below the refresh rate. Throttled frames
+    // never count.
+    fn is_late(&self, frame_dt: f32) -> bool {
+        let Some(hz) = self.frame_cap().or(self.refresh_rate) else {
+            return false;
         };
-        unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .create_shader_module(&create_info, None)
-                .expect("Failed to create shader module")
-        }
+        let throttled = self.is_idle() || (!self.focused && self.unfocused_fps > 0.0);
+        !throttled && frame_dt > LATE_FRAME_INTERVALS / hz
     }
 
-    fn update_circle_position(&mut self) {
+    // Seconds since the previous frame
+    fn frame_time(&self) -> f32 {
         static mut LAST_TIME: Option<std::time::Instant> = None;
         let now = std::time::Instant::now();
         let dt = unsafe {
             LAST_TIME.map(|last| now.duration_since(last).as_secs_f32()).unwrap_or(1.0 / 60.0)
         };
         unsafe { LAST_TIME = Some(now); }
+        dt
+    }
 
-        self.circle_position += self.circle_velocity * dt;
+    // Tears down the current scene's resources and starts `kind` from scratch,
+    // with the RNG reseeded so every scene is reproducible on its own
+    fn switch_scene(&mut self, kind: SceneKind) {
+        let renderer = self.renderer.as_mut().unwrap();
+        self.scene.destroy_resources(renderer);
+        renderer.set_post_effects(Vec::new());
+        renderer.set_background(Background::Clear);
+        self.scene_kind = kind;
+        self.scene = kind.create(&self.scene_config);
+        self.rng = Rng::new(self.seed);
+        self.scene.create_resources(renderer);
+        // The stylized effects are the player's, so they outlast the scene
+        let effects = self.stylize_panel.stylize.apply(renderer.post_effects());
+        renderer.set_post_effects(effects);
+        println!("Scene: {}", kind.name());
+        self.update_title();
+    }
 
-        let radius = 50.0;
-        let bounds = Vec2::new(self.extent.width as f32, self.extent.height as f32);
+    // One-shot actions that apply to every scene
+    fn handle_actions(&mut self, event_loop: &ActiveEventLoop) {
+        if self.input.was_pressed(Action::NextScene) {
+            self.switch_scene(self.scene_kind.next());
+        }
+        if self.input.was_pressed(Action::Pause) {
+            self.clock.toggle_pause();
+            println!("{}", if self.clock.is_paused() { "Paused" } else { "Resumed" });
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::Step) {
+            self.clock.step();
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::TimeScaleUp) {
+            let scale = self.clock.time_scale();
+            let faster = TIME_SCALES.into_iter().find(|&step| step > scale);
+            self.clock.set_time_scale(faster.unwrap_or(scale));
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::TimeScaleDown) {
+            let scale = self.clock.time_scale();
+            let slower = TIME_SCALES.into_iter().rev().find(|&step| step < scale);
+            self.clock.set_time_scale(slower.unwrap_or(scale));
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::ToggleTrail) {
+            self.toggle_trail();
+        }
+        if self.input.was_pressed(Action::CycleCircleMode) {
+            let renderer = self.renderer.as_mut().unwrap();
+            let mode = renderer.circle_mode().next();
+            renderer.set_circle_mode(mode);
+        }
+        if self.input.was_pressed(Action::ToggleMute) {
+            self.muted = !self.muted;
+            println!("Sound {}", if self.muted { "muted" } else { "unmuted" });
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::TogglePowerSave) {
+            self.power_save = !self.power_save;
+            println!("Power save {}", if self.power_save { "on" } else { "off" });
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::ToggleMemoryOverlay) {
+            self.memory_overlay = !self.memory_overlay;
+            if self.memory_overlay {
+                let gpu = &self.renderer.as_ref().unwrap().gpu;
+                println!(
+                    "GPU {} of {}: {}",
+                    gpu.physical_device_index, gpu.physical_device_count, gpu.device_name
+                );
+                print!("{}", gpu.memory_report());
+                if gpu.capabilities.pipeline_creation_feedback {
+                    print!("{}", gpu.compiler.stats());
+                }
+                let primary = self.primary().unwrap();
+                println!("Present mode: {}", describe_present_modes(primary));
+                println!("Swapchain images: {}", primary.image_count());
+                print!("{}", self.frame_times);
+            }
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::ToggleStylizeOverlay) {
+            self.stylize_overlay = !self.stylize_overlay;
+            println!(
+                "Post effect sliders {}",
+                if self.stylize_overlay { "shown" } else { "hidden" }
+            );
+        }
+        if self.input.was_pressed(Action::CycleDebugView) {
+            let renderer = self.renderer.as_mut().unwrap();
+            let mut view = renderer.debug_view().next();
+            if !renderer.set_debug_view(view) {
+                println!("Wireframe view unavailable: the device lacks fillModeNonSolid");
+                view = view.next();
+                renderer.set_debug_view(view);
+            }
+            println!("Debug view: {}", view.name());
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::CycleShadingRate) {
+            let renderer = self.renderer.as_mut().unwrap();
+            match renderer.shading_rate_mode() {
+                Some(mode) => {
+                    renderer.set_shading_rate_mode(mode.next());
+                    println!("Variable rate shading: {}", mode.next().name());
+                }
+                None => println!("Variable rate shading is not supported on this device"),
+            }
+        }
+        if self.input.was_pressed(Action::CycleTonemap) {
+            let renderer = self.renderer.as_mut().unwrap();
+            let tonemap = renderer.tonemap().next();
+            renderer.set_tonemap(tonemap);
+            let encoding = renderer.output_encoding();
+            if encoding.is_hdr() {
+                println!("Tonemap: {} (unused, output is {})", tonemap.name(), encoding.name());
+            } else {
+                println!("Tonemap: {}", tonemap.name());
+            }
+            self.update_title();
+        }
+        if self.input.was_pressed(Action::CyclePresentMode) {
+            self.cycle_present_mode();
+        }
+        if self.input.was_pressed(Action::NextGpu) {
+            self.switch_gpu();
+        }
+        if self.input.was_pressed(Action::OpenWindow) {
+            self.open_window(event_loop);
+        }
+        if self.input.was_pressed(Action::ToggleFullscreen) {
+            self.toggle_fullscreen();
+        }
+        if self.input.was_pressed(Action::ToggleRecording) {
+            self.toggle_recording();
+        }
+        if self.input.was_pressed(Action::SaveSnapshot) {
+            self.save_snapshot();
+        }
+        if self.input.was_pressed(Action::LoadSnapshot) {
+            self.load_snapshot();
+        }
+    }
 
-        if self.circle_position.x - radius < 0.0 || self.circle_position.x + radius > bounds.x {
-            self.circle_velocity.x = -self.circle_velocity.x;
+    // Starts --record-input with the scene it was given. Also warns when a
+    // replay's window came out another size than the recording's, since the
+    // scene bounds are part of the simulation.
+    fn start_input_recording(&mut self) {
+        let bounds = self.renderer.as_ref().unwrap().bounds();
+        if let Some(replay) = self.replay.as_ref() {
+            let recorded = replay.header().window_size;
+            if bounds != recorded {
+                eprintln!(
+                    "Replay window is {}x{} but was recorded at {}x{}; the replay may diverge",
+                    bounds.x, bounds.y, recorded.x, recorded.y
+                );
+            }
         }
-        if self.circle_position.y - radius < 0.0 || self.circle_position.y + radius > bounds.y {
-            self.circle_velocity.y = -self.circle_velocity.y;
+        let Some(path) = self.record_input_path.take() else {
+            return;
+        };
+        let header = RecordingHeader {
+            version: RECORDING_VERSION,
+            seed: self.seed,
+            scene: self.scene_kind.name().to_string(),
+            window_size: bounds,
+        };
+        match InputRecorder::create(&path, &header) {
+            Ok(recorder) => self.input_recorder = Some(recorder),
+            Err(error) => eprintln!("Failed to record input to {}: {}", path.display(), error),
         }
     }
 
-    fn render(&mut self) {
-        // Reset command buffer to prevent state corruption
-        unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .reset_command_buffer(self.command_buffer, vk::CommandBufferResetFlags::empty())
-                .expect("Failed to reset command buffer");
-        }
-
-        // Acquire the next swapchain image
-        let result = unsafe {
-            self.swapchain_ext.as_ref().unwrap().acquire_next_image(
-                self.swapchain,
-                u64::MAX,
-                self.image_available_semaphore,
-                vk::Fence::null(),
-            )
+    // Prints how the replay ended, with a checksum of the scene's state when it
+    // can be saved, and quits
+    fn finish_replay(&mut self, event_loop: &ActiveEventLoop) {
+        let frames = self.replay.take().map_or(0, |replay| replay.frames_played());
+        match self.scene.save_state() {
+            Some(state) => println!(
+                "Replay finished after {} frames; state checksum {:016x}",
+                frames,
+                replay::state_checksum(&state)
+            ),
+            None => println!("Replay finished after {} frames", frames),
+        }
+        event_loop.exit();
+    }
+
+    // The --report document, pretty-printed into `path`
+    fn write_startup_report(&self, path: &Path) {
+        let renderer = self.renderer.as_ref().unwrap();
+        let report = StartupReport::collect(&renderer.gpu, self.primary().unwrap());
+        let json = serde_json::to_string_pretty(&report).unwrap() + "\n";
+        match std::fs::write(path, json) {
+            Ok(()) => println!("Startup report written to {}", path.display()),
+            Err(error) => {
+                eprintln!("Failed to write the startup report to {}: {}", path.display(), error)
+            }
+        }
+    }
+
+    // Writes the scene, RNG and clock state to `snapshot_path`
+    fn save_snapshot(&self) {
+        let Some(state) = self.scene.save_state() else {
+            println!("The {} scene doesn't support snapshots", self.scene_kind.name());
+            return;
         };
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            scene: self.scene_kind.name().to_string(),
+            seed: self.seed,
+            rng: self.rng.clone(),
+            paused: self.clock.is_paused(),
+            time_scale: self.clock.time_scale(),
+            accumulator: self.clock.accumulator(),
+            state,
+        };
+        match snapshot.save(&self.snapshot_path) {
+            Ok(()) => println!("Saved snapshot to {}", self.snapshot_path.display()),
+            Err(error) => eprintln!(
+                "Failed to save snapshot to {}: {}",
+                self.snapshot_path.display(),
+                error
+            ),
+        }
+    }
 
-        let (image_index, _) = match result {
-            Ok(index) => index,
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                self.recreate_swapchain();
+    // Switches to the snapshot's scene if needed and restores its state
+    fn load_snapshot(&mut self) {
+        let snapshot = match Snapshot::load(&self.snapshot_path) {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                eprintln!(
+                    "Failed to load snapshot from {}: {}",
+                    self.snapshot_path.display(),
+                    error
+                );
                 return;
             }
-            Err(e) => panic!("Failed to acquire next image: {:?}", e),
         };
+        let Some(kind) = SceneKind::from_name(&snapshot.scene) else {
+            eprintln!("The snapshot's {} scene isn't in this build", snapshot.scene);
+            return;
+        };
+        self.seed = snapshot.seed;
+        if kind != self.scene_kind {
+            self.switch_scene(kind);
+        }
+        if let Err(error) = self.scene.load_state(snapshot.state) {
+            eprintln!("Failed to restore the {} scene: {}", snapshot.scene, error);
+            return;
+        }
+        self.rng = snapshot.rng;
+        self.clock.set_paused(snapshot.paused);
+        self.clock.set_time_scale(snapshot.time_scale);
+        self.clock.set_accumulator(snapshot.accumulator);
+        println!("Restored snapshot from {}", self.snapshot_path.display());
+        self.update_title();
+    }
 
-        // Begin command buffer recording
-        unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .begin_command_buffer(self.command_buffer, &vk::CommandBufferBeginInfo::default())
-                .expect("Failed to begin command buffer");
-
-            // Start render pass with clear color (black)
-            let clear_value = vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: [0.0, 0.0, 0.0, 1.0],
-                },
-            };
-            let render_pass_begin_info = vk::RenderPassBeginInfo {
-                render_pass: self.render_pass,
-                framebuffer: self.framebuffers[image_index as usize],
-                render_area: vk::Rect2D {
-                    offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: self.extent,
-                },
-                clear_value_count: 1,
-                p_clear_values: &clear_value,
-                ..Default::default()
-            };
+    // Answers the remote control's requests from the last frame
+    #[cfg(feature = "remote")]
+    fn handle_remote(&mut self) {
+        let requests = self.remote.as_ref().map(RemoteServer::drain).unwrap_or_default();
+        for pending in requests {
+            let response = self.apply_remote(&pending.request);
+            pending.reply(response);
+        }
+    }
 
-            self.device.as_ref().unwrap().cmd_begin_render_pass(
-                self.command_buffer,
-                &render_pass_begin_info,
-                vk::SubpassContents::INLINE,
-            );
+    #[cfg(feature = "remote")]
+    fn apply_remote(&mut self, request: &Request) -> Response {
+        match request {
+            Request::Stats => Ok(json!(Stats {
+                fps: self.fps,
+                frame_ms: if self.fps > 0.0 { 1000.0 / self.fps } else { 0.0 },
+                scene: self.scene_kind.name().to_string(),
+                paused: self.clock.is_paused(),
+                time_scale: self.clock.time_scale(),
+            })),
+            Request::Scenes => {
+                let names: Vec<_> = SceneKind::all().iter().map(|kind| kind.name()).collect();
+                Ok(json!(names))
+            }
+            Request::Pause { paused } => {
+                let paused = paused.unwrap_or(!self.clock.is_paused());
+                if paused != self.clock.is_paused() {
+                    self.clock.set_paused(paused);
+                    println!("{}", if paused { "Paused" } else { "Resumed" });
+                    self.update_title();
+                }
+                Ok(json!(paused))
+            }
+            Request::TimeScale { value: scale } => {
+                if !(scale.is_finite() && *scale > 0.0) {
+                    return Err("time_scale expects a positive number".to_string());
+                }
+                self.clock.set_time_scale(*scale);
+                self.update_title();
+                Ok(json!(self.clock.time_scale()))
+            }
+            Request::Velocity { x, y } => {
+                self.set_scene_param(SceneParam::Velocity(Vec2::new(*x, *y)), "velocity")
+            }
+            Request::Color { color } => {
+                let color = match color {
+                    Some(color) => Some(
+                        shapes::parse_color(color).ok_or_else(|| shapes::COLOR_ERROR.to_string())?,
+                    ),
+                    None => None,
+                };
+                self.set_scene_param(SceneParam::Color(color), "color")
+            }
+            Request::Scene { name } => {
+                let kind = SceneKind::from_name(name).ok_or_else(|| format!("no scene called {}", name))?;
+                self.switch_scene(kind);
+                Ok(json!(kind.name()))
+            }
+        }
+    }
 
-            // Bind graphics pipeline
-            self.device.as_ref().unwrap().cmd_bind_pipeline(
-                self.command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.pipeline,
-            );
+    #[cfg(feature = "remote")]
+    fn set_scene_param(&mut self, param: SceneParam, what: &str) -> Response {
+        if self.scene.set_param(param) {
+            Ok(json!(null))
+        } else {
+            Err(format!("the {} scene has no {} to set", self.scene_kind.name(), what))
+        }
+    }
 
-            // Set viewport and scissor
-            let viewport = vk::Viewport {
-                x: 0.0,
-                y: 0.0,
-                width: self.extent.width as f32,
-                height: self.extent.height as f32,
-                min_depth: 0.0,
-                max_depth: 1.0,
-            };
-            self.device
-                .as_ref()
-                .unwrap()
-                .cmd_set_viewport(self.command_buffer, 0, &[viewport]);
-
-            let scissor = vk::Rect2D {
-                offset: vk::Offset2D { x: 0, y: 0 },
-                extent: self.extent,
-            };
-            self.device
-                .as_ref()
-                .unwrap()
-                .cmd_set_scissor(self.command_buffer, 0, &[scissor]);
-
-            // Bind vertex buffer
-            self.device.as_ref().unwrap().cmd_bind_vertex_buffers(
-                self.command_buffer,
-                0,
-                &[self.vertex_buffer],
-                &[0],
-            );
+    // Starts copying the primary window's frames out for the encoder, or stops
+    // and finishes the file. The encoder starts with the first copied frame, once
+    // its size is known.
+    fn toggle_recording(&mut self) {
+        self.recording = !self.recording;
+        if !self.recording {
+            if let Some(recorder) = self.recorder.take() {
+                recorder.finish();
+            }
+        }
+        if !self.update_capture() {
+            println!("Recording unavailable: the swapchain images can't be copied");
+            self.recording = false;
+        }
+        self.update_title();
+    }
 
-            // Set up transformation matrix for circle position
-            let ortho = Mat4::orthographic_rh(
-                0.0,
-                self.extent.width as f32,
-                self.extent.height as f32,
-                0.0,
-                -1.0,
-                1.0,
-            );
-            let transform = Mat4::from_translation(self.circle_position.extend(0.0));
-            let mvp = ortho * transform;
-            let mvp_array = mvp.to_cols_array();
-            self.device.as_ref().unwrap().cmd_push_constants(
-                self.command_buffer,
-                self.pipeline_layout,
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                bytemuck::cast_slice(&mvp_array),
-            );
+    // Copies frames out while a video or GIF wants them. False when the
+    // swapchain images can't be copied.
+    fn update_capture(&mut self) -> bool {
+        let renderer = self.renderer.as_mut().unwrap();
+        let primary = self.windows.get(&renderer.primary_window()).unwrap();
+        renderer.set_capture(primary, self.recording || self.gif_seconds.is_some())
+    }
 
-            // Draw the circle (triangle fan, 32 segments + center + closing vertex)
-            self.device.as_ref().unwrap().cmd_draw(
-                self.command_buffer,
-                34,
-                1,
-                0,
+    // Hands the last completed frame to the GIF and the video encoder, once
+    // each. Videos play at the frame cap, so frames rendered late play back
+    // slightly slowed. The app quits once the GIF is written.
+    fn record_frame(&mut self, event_loop: &ActiveEventLoop) {
+        let renderer = self.renderer.as_ref().unwrap();
+        let Some(frame) = renderer
+            .read_frame()
+            .filter(|frame| frame.frame > self.last_captured_frame)
+        else {
+            return;
+        };
+        self.last_captured_frame = frame.frame;
+        if let Some(seconds) = self.gif_seconds {
+            if self.gif.is_none() {
+                let path = timestamped_path("gif");
+                self.gif = GifRecorder::start(&path, frame.width, frame.height, GIF_FPS, seconds);
+            }
+            let capturing = self.gif.as_mut().is_some_and(|gif| gif.push(&frame));
+            if !capturing {
+                if let Some(gif) = self.gif.take() {
+                    gif.finish();
+                }
+                self.gif_seconds = None;
+                self.update_capture();
+                event_loop.exit();
+            }
+        }
+        if !self.recording {
+            return;
+        }
+        if self.recorder.is_none() {
+            let path = self
+                .record_path
+                .clone()
+                .unwrap_or_else(|| timestamped_path("mp4"));
+            let fps = self.frame_cap().unwrap_or(DEFAULT_RECORDING_FPS);
+            self.recorder = VideoRecorder::start(&path, frame.width, frame.height, fps);
+        }
+        let recording = self
+            .recorder
+            .as_mut()
+            .is_some_and(|recorder| recorder.push(frame));
+        if !recording {
+            self.toggle_recording();
+        }
+    }
+
+    // Plays the sounds the scene queued this frame, or drops them when muted
+    fn play_sounds(&mut self) {
+        #[cfg(feature = "audio")]
+        if let (false, Some(player)) = (self.muted, self.sound_player.as_ref()) {
+            for sound in self.sounds.drain() {
+                player.play(sound);
+            }
+        }
+        self.sounds.clear();
+    }
+
+    // Adds or removes the accumulation trail at the front of the post chain, so
+    // bloom still applies to the trail
+    fn toggle_trail(&mut self) {
+        let renderer = self.renderer.as_mut().unwrap();
+        let mut effects = renderer.post_effects().to_vec();
+        let before = effects.len();
+        effects.retain(|effect| !matches!(effect, PostEffect::Trail { .. }));
+        if effects.len() == before {
+            effects.insert(
                 0,
+                PostEffect::Trail {
+                    persistence: TRAIL_PERSISTENCE,
+                },
             );
+        }
+        renderer.set_post_effects(effects);
+    }
 
-            // End render pass and command buffer
-            self.device
-                .as_ref()
-                .unwrap()
-                .cmd_end_render_pass(self.command_buffer);
-            self.device
-                .as_ref()
-                .unwrap()
-                .end_command_buffer(self.command_buffer)
-                .expect("Failed to end command buffer");
-
-            // Submit commands to the queue
-            let wait_semaphores = [self.image_available_semaphore];
-            let signal_semaphores = [self.render_finished_semaphore];
-            let submit_info = vk::SubmitInfo {
-                wait_semaphore_count: 1,
-                p_wait_semaphores: wait_semaphores.as_ptr(),
-                p_wait_dst_stage_mask: &vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-                command_buffer_count: 1,
-                p_command_buffers: &self.command_buffer,
-                signal_semaphore_count: 1,
-                p_signal_semaphores: signal_semaphores.as_ptr(),
-                ..Default::default()
-            };
-            self.device
-                .as_ref()
-                .unwrap()
-                .queue_submit(self.queue, &[submit_info], vk::Fence::null())
-                .expect("Failed to submit queue");
-
-            // Present the rendered image
-            let present_info = vk::PresentInfoKHR {
-                wait_semaphore_count: 1,
-                p_wait_semaphores: &self.render_finished_semaphore,
-                swapchain_count: 1,
-                p_swapchains: &self.swapchain,
-                p_image_indices: &image_index,
-                ..Default::default()
-            };
-            let present_result = self
-                .swapchain_ext
-                .as_ref()
-                .unwrap()
-                .queue_present(self.queue, &present_info);
-
-            match present_result {
-                Ok(_) => (),
-                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                    self.recreate_swapchain();
-                    return;
-                }
-                Err(e) => panic!("Failed to present queue: {:?}", e),
-            }
+    // The memory overlay's heap bars with the frame time histogram under them
+    fn memory_overlay_size(&self, report: &MemoryReport) -> Vec2 {
+        let bars = report.overlay_size(MEMORY_OVERLAY_WIDTH);
+        let histogram = self.frame_times.overlay_size(MEMORY_OVERLAY_WIDTH);
+        Vec2::new(
+            bars.x.max(histogram.x),
+            bars.y + OVERLAY_PANEL_GAP + histogram.y,
+        )
+    }
+
+    // Lets the sliders have the left mouse button before the scene sees it, and
+    // puts any change into the post chain. They sit below the memory overlay
+    // when that is shown.
+    fn handle_stylize_panel(&mut self, memory_overlay: Option<&MemoryReport>) {
+        let mut origin = MEMORY_OVERLAY_ORIGIN;
+        if let Some(report) = memory_overlay {
+            origin.y += self.memory_overlay_size(report).y
+                + OVERLAY_PANEL_PADDING * 2.0
+                + OVERLAY_PANEL_GAP;
+        }
+        self.stylize_panel.set_origin(origin);
+        let renderer = self.renderer.as_mut().unwrap();
+        let before = self.stylize_panel.stylize;
+        let cursor = self
+            .input
+            .cursor_position()
+            .map(|position| renderer.window_to_ortho(position));
+        let took_mouse = self.stylize_panel.handle_mouse(
+            cursor,
+            self.input.was_mouse_pressed(MouseButton::Left),
+            self.input.was_mouse_released(MouseButton::Left),
+        );
+        if took_mouse {
+            self.input.consume_mouse(MouseButton::Left);
         }
+        let stylize = self.stylize_panel.stylize;
+        if stylize != before {
+            let effects = stylize.apply(renderer.post_effects());
+            renderer.set_post_effects(effects);
+        }
+    }
 
+    fn update_fps(&mut self) {
         // Calculate FPS and update window title every second
         self.frame_count += 1;
         let now = std::time::Instant::now();
         let elapsed = now.duration_since(self.last_title_update).as_secs_f32();
         if elapsed >= 1.0 {
             self.fps = self.frame_count as f32 / elapsed;
-            self.window
-                .as_ref()
-                .unwrap()
-                .set_title(&format!("Vulkan Vibe - FPS: {:.1}", self.fps));
+            self.update_title();
             self.last_title_update = now;
             self.frame_count = 0;
+            self.late_frames = 0;
         }
+    }
 
-        // Request the next frame
-        self.window.as_ref().unwrap().request_redraw();
+    // Last second's FPS as a bar, full at the frame cap (or 60 FPS when
+    // uncapped). Green when on target, yellow below 90% and red below 50%.
+    fn draw_fps_meter(&mut self) {
+        let target = self.frame_cap().unwrap_or(60.0);
+        let fraction = (self.fps / target).min(1.0);
+        let color = if fraction >= 0.9 {
+            [0.2, 0.9, 0.3, 0.9]
+        } else if fraction >= 0.5 {
+            [0.9, 0.8, 0.2, 0.9]
+        } else {
+            [0.9, 0.2, 0.2, 0.9]
+        };
+        let bounds = self.renderer.as_ref().unwrap().bounds();
+        let origin = Vec2::new(
+            bounds.x - FPS_METER_SIZE.x - FPS_METER_MARGIN,
+            FPS_METER_MARGIN,
+        );
+        let canvas = &mut self.draws.canvas;
+        canvas.fill_rounded_rect(
+            origin,
+            FPS_METER_SIZE,
+            [FPS_METER_RADIUS; 4],
+            [0.1, 0.1, 0.1, 0.7],
+        );
+        // The filled part keeps the track's rounded ends only where it reaches them
+        let right_radius = if fraction >= 1.0 { FPS_METER_RADIUS } else { 0.0 };
+        canvas.fill_rounded_rect(
+            origin,
+            FPS_METER_SIZE * Vec2::new(fraction, 1.0),
+            [FPS_METER_RADIUS, right_radius, right_radius, FPS_METER_RADIUS],
+            color,
+        );
     }
 
-    fn recreate_swapchain(&mut self) {
-        unsafe {
-            self.device
-                .as_ref()
-                .unwrap()
-                .device_wait_idle()
-                .expect("Failed to wait for device idle");
-
-            for &framebuffer in &self.framebuffers {
-                self.device
-                    .as_ref()
-                    .unwrap()
-                    .destroy_framebuffer(framebuffer, None);
+    fn update_title(&self) {
+        let mut title = format!(
+            "Vulkan Vibe - {} - FPS: {:.1}",
+            self.scene_kind.name(),
+            self.fps
+        );
+        if let Some(hz) = self.refresh_rate {
+            title += &format!(" / {:.0} Hz", hz);
+            if self.late_frames > 0 {
+                title += &format!(" ({} late)", self.late_frames);
             }
-            for &image_view in &self.image_views {
-                self.device
-                    .as_ref()
-                    .unwrap()
-                    .destroy_image_view(image_view, None);
+        }
+        if self.clock.time_scale() != 1.0 {
+            title += &format!(" - {:.2}x", self.clock.time_scale());
+        }
+        if self.clock.is_paused() {
+            title += " - Paused";
+        }
+        if self.muted {
+            title += " - Muted";
+        }
+        if self.power_save {
+            title += " - Power save";
+        }
+        if self.recording {
+            title += " - Recording";
+        }
+        if let Some(renderer) = self.renderer.as_ref() {
+            let encoding = renderer.output_encoding();
+            if encoding.is_hdr() {
+                title += &format!(" - {}", encoding.name());
+            } else if renderer.tonemap() != Tonemap::None {
+                title += &format!(" - {} tonemap", renderer.tonemap().name());
             }
-            self.swapchain_ext
-                .as_ref()
-                .unwrap()
-                .destroy_swapchain(self.swapchain, None);
-
-            let window = self.window.as_ref().unwrap();
-            let new_size = window.inner_size();
-            self.extent = vk::Extent2D {
-                width: new_size.width,
-                height: new_size.height,
-            };
+        }
+        let debug_view = self.renderer.as_ref().map_or(DebugView::Off, Renderer::debug_view);
+        if debug_view != DebugView::Off {
+            title += &format!(" - {} view", debug_view.name());
+        }
+        if self.memory_overlay {
+            if let Some(primary) = self.primary() {
+                title += &format!(
+                    " - {}, {} images",
+                    primary.active_present_mode().name(),
+                    primary.image_count()
+                );
+            }
+            let report = self.renderer.as_ref().unwrap().gpu.memory_report();
+            title += &format!(
+                " - GPU memory {:.1} MiB",
+                report.tracked_total() as f64 / (1024.0 * 1024.0)
+            );
+            title += &format!(
+                " - CPU p99 {:.1} ms",
+                self.frame_times.percentile(0.99).as_secs_f64() * 1000.0
+            );
+            if self.frame_times.spikes() > 0 {
+                title += &format!(" ({} stutters)", self.frame_times.spikes());
+            }
+            let stats = self.renderer.as_ref().unwrap().gpu.compiler.stats();
+            if stats.pipelines > 0 {
+                title += &format!(
+                    " - {} pipelines in {:.0} ms",
+                    stats.pipelines,
+                    stats.total.as_secs_f64() * 1000.0
+                );
+            }
+            if let Some(timing) = self.renderer.as_ref().unwrap().present_timing() {
+                title += &format!(
+                    " - present every {:.1} ms, {:.1} ms latency",
+                    timing.interval.as_secs_f64() * 1000.0,
+                    timing.latency.as_secs_f64() * 1000.0
+                );
+            }
+        }
+        for context in self.windows.values() {
+            context.window().set_title(&title);
+        }
+    }
+}
 
-            let surface_instance =
-                ash::khr::surface::Instance::new(&self.entry, self.instance.as_ref().unwrap());
-            let surface_capabilities = surface_instance
-                .get_physical_device_surface_capabilities(self.physical_device, self.surface)
-                .expect("Failed to get surface capabilities");
-            let surface_formats = surface_instance
-                .get_physical_device_surface_formats(self.physical_device, self.surface)
-                .expect("Failed to get surface formats");
-            let present_modes = surface_instance
-                .get_physical_device_surface_present_modes(self.physical_device, self.surface)
-                .expect("Failed to get present modes");
-
-            let format = surface_formats[0];
-            let present_mode = present_modes
-                .into_iter()
-                .find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
-                .unwrap_or(vk::PresentModeKHR::IMMEDIATE);
-            let image_count = surface_capabilities.min_image_count + 1;
-            let image_count = if surface_capabilities.max_image_count > 0 {
-                image_count.min(surface_capabilities.max_image_count)
-            } else {
-                image_count
-            };
+// The mode `window` presents with and every one its surface supports, e.g.
+// `vsync (supports mailbox, immediate, vsync)`
+fn describe_present_modes(window: &WindowContext) -> String {
+    let supported: Vec<_> = window
+        .supported_present_modes()
+        .iter()
+        .map(|mode| mode.name())
+        .collect();
+    let mut description = format!(
+        "{} (supports {})",
+        window.active_present_mode().name(),
+        supported.join(", ")
+    );
+    if window.active_present_mode() != window.present_mode() {
+        description += &format!(", {} not supported", window.present_mode().name());
+    }
+    description
+}
 
-            let swapchain_create_info = vk::SwapchainCreateInfoKHR {
-                surface: self.surface,
-                min_image_count: image_count,
-                image_format: format.format,
-                image_color_space: format.color_space,
-                image_extent: self.extent,
-                image_array_layers: 1,
-                image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
-                pre_transform: surface_capabilities.current_transform,
-                composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
-                present_mode,
-                clipped: vk::TRUE,
-                ..Default::default()
-            };
-            self.swapchain = self
-                .swapchain_ext
-                .as_ref()
-                .unwrap()
-                .create_swapchain(&swapchain_create_info, None)
-                .expect("Failed to recreate swapchain");
-            self.images = self
-                .swapchain_ext
-                .as_ref()
-                .unwrap()
-                .get_swapchain_images(self.swapchain)
-                .expect("Failed to get swapchain images");
-
-            self.image_views = self
-                .images
-                .iter()
-                .map(|&image| {
-                    let create_info = vk::ImageViewCreateInfo {
-                        image,
-                        view_type: vk::ImageViewType::TYPE_2D,
-                        format: format.format,
-                        components: vk::ComponentMapping::default(),
-                        subresource_range: vk::ImageSubresourceRange {
-                            aspect_mask: vk::ImageAspectFlags::COLOR,
-                            base_mip_level: 0,
-                            level_count: 1,
-                            base_array_layer: 0,
-                            layer_count: 1,
-                        },
-                        ..Default::default()
-                    };
-                    self.device
-                        .as_ref()
-                        .unwrap()
-                        .create_image_view(&create_info, None)
-                        .expect("Failed to create image view")
-                })
-                .collect();
-
-            self.framebuffers = self
-                .image_views
-                .iter()
-                .map(|&image_view| {
-                    let framebuffer_create_info = vk::FramebufferCreateInfo {
-                        render_pass: self.render_pass,
-                        attachment_count: 1,
-                        p_attachments: &image_view,
-                        width: self.extent.width,
-                        height: self.extent.height,
-                        layers: 1,
-                        ..Default::default()
-                    };
-                    self.device
-                        .as_ref()
-                        .unwrap()
-                        .create_framebuffer(&framebuffer_create_info, None)
-                        .expect("Failed to create framebuffer")
-                })
-                .collect();
+// The scene that runs a --script file, going by its extension, if this build
+// has the feature for it
+fn script_scene(path: &str) -> Option<SceneKind> {
+    let extension = std::path::Path::new(path).extension()?.to_str()?;
+    match extension {
+        #[cfg(feature = "python")]
+        "py" => Some(SceneKind::Python),
+        #[cfg(feature = "rhai")]
+        "rhai" => Some(SceneKind::Script),
+        _ => None,
+    }
+}
+
+// `vulkan_vibe-<unix time>.<extension>` in the working directory
+fn timestamped_path(extension: &str) -> PathBuf {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    PathBuf::from(format!("vulkan_vibe-{}.{}", seconds, extension))
+}
+
+// A translucent dark rounded square with a faint rim, stretched as a nine-patch
+// so any panel size keeps the same corners
+fn create_overlay_panel(renderer: &mut Renderer) -> NinePatch {
+    let size = OVERLAY_PANEL_TEXTURE_SIZE;
+    let half = size as f32 * 0.5;
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            // Signed distance to the rounded outline in pixels, positive inside
+            let local = (Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - half).abs();
+            let corner = local - Vec2::splat(half - OVERLAY_PANEL_RADIUS);
+            let inside = OVERLAY_PANEL_RADIUS
+                - corner.max(Vec2::ZERO).length()
+                - corner.max_element().min(0.0);
+            let coverage = (inside + 0.5).clamp(0.0, 1.0);
+            let (shade, alpha) = if inside < 1.5 { (90, 200) } else { (20, 170) };
+            pixels.extend_from_slice(&[shade, shade, shade, (coverage * alpha as f32) as u8]);
+        }
+    }
+    let texture = renderer.create_texture(size, size, &pixels);
+    NinePatch::new(texture, size, size, [OVERLAY_PANEL_RADIUS; 4])
+}
+
+// Exclusive fullscreen on top of borderless, for --exclusive-fullscreen
+fn set_exclusive_fullscreen(renderer: &mut Renderer, context: &mut WindowContext, exclusive: bool) {
+    if !renderer.set_exclusive_fullscreen(context, exclusive) && exclusive {
+        println!("Exclusive fullscreen unavailable, using borderless");
+    }
+}
+
+// One scene per line, plugins followed by the library they came from
+fn list_scenes() {
+    for kind in SceneKind::all() {
+        match kind {
+            SceneKind::Plugin(index) => {
+                let plugin = &plugin::registry()[index];
+                println!("{} ({})", plugin.name(), plugin.path().display());
+            }
+            _ => println!("{}", kind.name()),
         }
     }
 }
 
 fn main() {
+    let mut options = Options::from_args();
+    if options.info {
+        info::run(options.json);
+        return;
+    }
+    if options.list_scenes {
+        list_scenes();
+        return;
+    }
+    #[cfg(not(feature = "remote"))]
+    if options.remote_port.is_some() {
+        eprintln!("--remote needs the remote feature; ignoring it");
+    }
+    let stats = options.stats_path.as_ref().and_then(|path| {
+        StatsWriter::create(path)
+            .map_err(|error| eprintln!("Failed to write stats to {}: {}", path.display(), error))
+            .ok()
+    });
+    // A replay starts the way its recording did
+    let replay = options.replay_path.as_ref().map(|path| {
+        let replay = Replay::load(path)
+            .unwrap_or_else(|error| panic!("Failed to load {}: {}", path.display(), error));
+        let header = replay.header();
+        options.seed = header.seed;
+        options.scene = SceneKind::from_name(&header.scene)
+            .unwrap_or_else(|| panic!("The recording's {} scene isn't in this build", header.scene));
+        println!("Replaying {}", path.display());
+        replay
+    });
+    profiling::start();
+    println!("Random seed: {}", options.seed);
+    if options.reset_window {
+        WindowState::reset();
+    }
+    let window_state = WindowState::load().unwrap_or_default();
+
     let event_loop = EventLoop::new().expect("Failed to create event loop");
     println!("Event loop created");
 
     let mut app = App {
-        window: None,
-        entry: unsafe { ash::Entry::load().expect("Failed to load Vulkan entry") },
-        instance: None,
-        surface: vk::SurfaceKHR::null(),
-        physical_device: vk::PhysicalDevice::null(),
-        device: None,
-        queue: vk::Queue::null(),
-        swapchain: vk::SwapchainKHR::null(),
-        swapchain_ext: None,
-        images: Vec::new(),
-        image_views: Vec::new(),
-        render_pass: vk::RenderPass::null(),
-        framebuffers: Vec::new(),
-        command_pool: vk::CommandPool::null(),
-        command_buffer: vk::CommandBuffer::null(),
-        image_available_semaphore: vk::Semaphore::null(),
-        render_finished_semaphore: vk::Semaphore::null(),
-        pipeline: vk::Pipeline::null(),
-        pipeline_layout: vk::PipelineLayout::null(),
-        vertex_buffer: vk::Buffer::null(),
-        vertex_buffer_memory: vk::DeviceMemory::null(),
-        extent: vk::Extent2D {
-            width: 0,
-            height: 0,
-        },
-        circle_position: Vec2::ZERO,
-        circle_velocity: Vec2::ZERO,
+        windows: HashMap::new(),
+        renderer: None,
+        scene_kind: options.scene,
+        scene: options.scene.create(&options.scene_config),
+        scene_config: options.scene_config,
+        draws: DrawList::new(),
+        seed: options.seed,
+        rng: Rng::new(options.seed),
+        input: Input::new(),
+        #[cfg(feature = "gamepad")]
+        gamepad: GamepadInput::new(),
+        sounds: SoundQueue::new(),
+        muted: false,
+        #[cfg(feature = "audio")]
+        sound_player: SoundPlayer::new(),
+        clock: SimulationClock::new(FIXED_DT),
+        power_save: options.power_save,
+        focused: true,
+        unfocused_fps: options.unfocused_fps,
+        aa_width: options.aa_width,
+        min_size: options.min_size,
+        aspect_ratio: options.aspect_ratio,
+        virtual_resolution: options.virtual_resolution,
+        monitor_index: options.monitor,
+        monitor: None,
+        clear_color: options.clear_color,
+        hdr: options.hdr,
+        hdr_encoding: options.hdr_encoding,
+        tonemap: options.tonemap,
+        fps_cap: options.fps_cap,
+        present_mode: options.present_mode,
+        swapchain_images: options.swapchain_images,
+        transparent: options.transparent,
+        exclusive_fullscreen: options.exclusive_fullscreen,
+        device_group: options.device_group,
+        gpu_index: options.gpu,
+        breadcrumbs: options.breadcrumbs,
+        overlay: options.overlay,
+        screensaver: options.screensaver,
+        screensaver_cursor: None,
+        refresh_rate: None,
+        frame_start: std::time::Instant::now(),
+        late_frames: 0,
+        frame_times: FrameTimes::new(std::time::Duration::from_secs_f32(
+            options.stutter_ms / 1000.0,
+        )),
+        stats,
+        report_path: options.report_path,
+        memory_overlay: false,
+        stylize_overlay: false,
+        stylize_panel: StylizePanel::new(
+            Stylize::default(),
+            MEMORY_OVERLAY_ORIGIN,
+            MEMORY_OVERLAY_WIDTH,
+        ),
+        overlay_panel: None,
+        record_path: options.record_path,
+        recording: false,
+        recorder: None,
+        gif_seconds: options.gif_seconds,
+        gif: None,
+        export_frames: options.export_frames,
+        #[cfg(feature = "remote")]
+        remote: options.remote_port.and_then(RemoteServer::start),
+        restore_snapshot: options.snapshot_path.is_some(),
+        snapshot_path: options
+            .snapshot_path
+            .unwrap_or_else(|| DEFAULT_SNAPSHOT_PATH.into()),
+        record_input_path: options.record_input_path,
+        input_recorder: None,
+        replay,
+        last_captured_frame: 0,
+        window_state,
         last_title_update: std::time::Instant::now(),
         frame_count: 0,
         fps: 0.0,
     };
-    println!("App initialized with Vulkan entry");
+    println!("App initialized");
 
     event_loop.run_app(&mut app).expect("Event loop run failed");
     println!("Application exited");
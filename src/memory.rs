@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ash::vk;
+use glam::Vec2;
+
+use crate::canvas::Canvas;
+
+const MIB: f64 = 1024.0 * 1024.0;
+// Height of each heap's bar in the overlay, and the gap between them
+const OVERLAY_BAR_HEIGHT: f32 = 10.0;
+const OVERLAY_SPACING: f32 = 4.0;
+
+// What an allocation is for, so leaks show up in the right bucket
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryCategory {
+    // Static meshes
+    VertexBuffers,
+    // Compute data such as the GPU particles
+    StorageBuffers,
+    // Per-frame vertices, instances and uniforms
+    FrameRing,
+    // Textures and render targets
+    Images,
+    // Upload copies, freed once the copy has finished
+    Staging,
+    // Host copies of rendered frames, e.g. for recording
+    Readback,
+    // Ray tracing BVHs and the scratch space they are built in
+    AccelerationStructures,
+    Other,
+}
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; 8] = [
+        MemoryCategory::VertexBuffers,
+        MemoryCategory::StorageBuffers,
+        MemoryCategory::FrameRing,
+        MemoryCategory::Images,
+        MemoryCategory::Staging,
+        MemoryCategory::Readback,
+        MemoryCategory::AccelerationStructures,
+        MemoryCategory::Other,
+    ];
+
+    // Best guess from how a buffer will be used
+    pub fn for_buffer(usage: vk::BufferUsageFlags) -> Self {
+        if usage.contains(vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR) {
+            MemoryCategory::AccelerationStructures
+        } else if usage == vk::BufferUsageFlags::TRANSFER_SRC {
+            MemoryCategory::Staging
+        } else if usage.contains(vk::BufferUsageFlags::STORAGE_BUFFER) {
+            MemoryCategory::StorageBuffers
+        } else if usage
+            .intersects(vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER)
+        {
+            MemoryCategory::VertexBuffers
+        } else {
+            MemoryCategory::Other
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MemoryCategory::VertexBuffers => "vertex buffers",
+            MemoryCategory::StorageBuffers => "storage buffers",
+            MemoryCategory::FrameRing => "frame ring",
+            MemoryCategory::Images => "images",
+            MemoryCategory::Staging => "staging",
+            MemoryCategory::Readback => "readback",
+            MemoryCategory::AccelerationStructures => "acceleration structures",
+            MemoryCategory::Other => "other",
+        }
+    }
+
+    // Segment color in the overlay
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            MemoryCategory::VertexBuffers => [0.3, 0.6, 1.0, 0.9],
+            MemoryCategory::StorageBuffers => [0.3, 1.0, 0.5, 0.9],
+            MemoryCategory::FrameRing => [1.0, 0.8, 0.2, 0.9],
+            MemoryCategory::Images => [1.0, 0.4, 0.8, 0.9],
+            MemoryCategory::Staging => [1.0, 0.3, 0.2, 0.9],
+            MemoryCategory::Readback => [0.6, 0.4, 1.0, 0.9],
+            MemoryCategory::AccelerationStructures => [0.2, 0.9, 0.9, 0.9],
+            MemoryCategory::Other => [0.7, 0.7, 0.7, 0.9],
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|&category| category == self)
+            .unwrap()
+    }
+}
+
+struct Allocation {
+    category: MemoryCategory,
+    heap: usize,
+    size: vk::DeviceSize,
+}
+
+// Every live vkDeviceMemory the app allocated through `GpuContext`
+#[derive(Default)]
+pub struct MemoryTracker {
+    allocations: HashMap<vk::DeviceMemory, Allocation>,
+}
+
+impl MemoryTracker {
+    pub fn record(
+        &mut self,
+        memory: vk::DeviceMemory,
+        category: MemoryCategory,
+        heap: usize,
+        size: vk::DeviceSize,
+    ) {
+        self.allocations.insert(
+            memory,
+            Allocation {
+                category,
+                heap,
+                size,
+            },
+        );
+    }
+
+    pub fn forget(&mut self, memory: vk::DeviceMemory) {
+        self.allocations.remove(&memory);
+    }
+
+    // Bytes per heap, split by category
+    fn tracked(&self, heap_count: usize) -> Vec<[vk::DeviceSize; MemoryCategory::ALL.len()]> {
+        let mut tracked = vec![[0; MemoryCategory::ALL.len()]; heap_count];
+        for allocation in self.allocations.values() {
+            tracked[allocation.heap][allocation.category.index()] += allocation.size;
+        }
+        tracked
+    }
+
+    // Live allocations per category
+    fn counts(&self) -> [usize; MemoryCategory::ALL.len()] {
+        let mut counts = [0; MemoryCategory::ALL.len()];
+        for allocation in self.allocations.values() {
+            counts[allocation.category.index()] += 1;
+        }
+        counts
+    }
+}
+
+pub struct HeapReport {
+    pub size: vk::DeviceSize,
+    pub device_local: bool,
+    // From VK_EXT_memory_budget: what this process may use and what it uses,
+    // including allocations made by the driver
+    pub budget: Option<vk::DeviceSize>,
+    pub usage: Option<vk::DeviceSize>,
+    // Allocated by the app, indexed like `MemoryCategory::ALL`
+    pub tracked: [vk::DeviceSize; MemoryCategory::ALL.len()],
+}
+
+impl HeapReport {
+    pub fn tracked_total(&self) -> vk::DeviceSize {
+        self.tracked.iter().sum()
+    }
+}
+
+// Snapshot from `GpuContext::memory_report`
+pub struct MemoryReport {
+    pub heaps: Vec<HeapReport>,
+    // Live allocations per category, indexed like `MemoryCategory::ALL`
+    pub allocation_counts: [usize; MemoryCategory::ALL.len()],
+}
+
+impl MemoryReport {
+    pub fn new(
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        budget: Option<&vk::PhysicalDeviceMemoryBudgetPropertiesEXT>,
+        tracker: &MemoryTracker,
+    ) -> Self {
+        let heap_count = memory_properties.memory_heap_count as usize;
+        let tracked = tracker.tracked(heap_count);
+        let heaps = (0..heap_count)
+            .map(|heap| {
+                let properties = memory_properties.memory_heaps[heap];
+                HeapReport {
+                    size: properties.size,
+                    device_local: properties.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+                    budget: budget.map(|budget| budget.heap_budget[heap]),
+                    usage: budget.map(|budget| budget.heap_usage[heap]),
+                    tracked: tracked[heap],
+                }
+            })
+            .collect();
+        Self {
+            heaps,
+            allocation_counts: tracker.counts(),
+        }
+    }
+
+    // Bytes the app has allocated across all heaps
+    pub fn tracked_total(&self) -> vk::DeviceSize {
+        self.heaps.iter().map(HeapReport::tracked_total).sum()
+    }
+
+    // What `draw_overlay` covers with bars `width` wide, leaving out the usage
+    // ticks that poke above and below them
+    pub fn overlay_size(&self, width: f32) -> Vec2 {
+        let rows = self.heaps.len() as f32;
+        Vec2::new(
+            width,
+            (rows * (OVERLAY_BAR_HEIGHT + OVERLAY_SPACING) - OVERLAY_SPACING).max(0.0),
+        )
+    }
+
+    // One bar per heap, scaled to its budget (or size without the extension).
+    // App allocations are stacked in category colors; a white tick marks the
+    // driver-reported usage.
+    pub fn draw_overlay(&self, canvas: &mut Canvas, origin: Vec2, width: f32) {
+        for (row, heap) in self.heaps.iter().enumerate() {
+            let top = origin + Vec2::new(0.0, row as f32 * (OVERLAY_BAR_HEIGHT + OVERLAY_SPACING));
+            canvas.fill_rect(
+                top,
+                Vec2::new(width, OVERLAY_BAR_HEIGHT),
+                [0.1, 0.1, 0.1, 0.7],
+            );
+            let scale = width / heap.budget.unwrap_or(heap.size).max(1) as f32;
+            let mut x = 0.0;
+            for (category, &bytes) in MemoryCategory::ALL.iter().zip(&heap.tracked) {
+                // Small allocations still get a visible sliver
+                let segment = if bytes > 0 {
+                    (bytes as f32 * scale).max(1.0)
+                } else {
+                    0.0
+                };
+                let segment = segment.min(width - x);
+                canvas.fill_rect(
+                    top + Vec2::new(x, 0.0),
+                    Vec2::new(segment, OVERLAY_BAR_HEIGHT),
+                    category.color(),
+                );
+                x += segment;
+            }
+            if let Some(usage) = heap.usage {
+                let tick = (usage as f32 * scale).min(width - 2.0);
+                canvas.fill_rect(
+                    top + Vec2::new(tick, -2.0),
+                    Vec2::new(2.0, OVERLAY_BAR_HEIGHT + 4.0),
+                    [1.0, 1.0, 1.0, 1.0],
+                );
+            }
+        }
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, heap) in self.heaps.iter().enumerate() {
+            write!(
+                f,
+                "Heap {} ({}, {:.0} MiB): app {:.1} MiB",
+                index,
+                if heap.device_local {
+                    "device local"
+                } else {
+                    "host"
+                },
+                heap.size as f64 / MIB,
+                heap.tracked_total() as f64 / MIB
+            )?;
+            if let (Some(budget), Some(usage)) = (heap.budget, heap.usage) {
+                write!(
+                    f,
+                    ", process {:.1} / {:.1} MiB budget",
+                    usage as f64 / MIB,
+                    budget as f64 / MIB
+                )?;
+            }
+            writeln!(f)?;
+        }
+        for (index, category) in MemoryCategory::ALL.iter().enumerate() {
+            let bytes: vk::DeviceSize = self.heaps.iter().map(|heap| heap.tracked[index]).sum();
+            writeln!(
+                f,
+                "  {}: {:.2} MiB in {} allocations",
+                category.name(),
+                bytes as f64 / MIB,
+                self.allocation_counts[index]
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,687 @@
+use std::f32::consts::{PI, TAU};
+
+use ash::vk;
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::device_group::{self, GroupFrame};
+use crate::frame_ring::FrameRing;
+use crate::gpu::GpuContext;
+use crate::lighting::{FrameUniforms, Lighting};
+use crate::memory::MemoryCategory;
+use crate::pipeline::{DebugView, PipelineDesc, PipelineManager, VertexAttribute, VertexLayout};
+use crate::post::HDR_FORMAT;
+
+// Bytes pushed to the frame ring each frame while the mesh pass is on
+pub const UPLOAD_SIZE: usize = size_of::<FrameUniforms>();
+
+// Tried in order; Vulkan requires one of the first two for depth attachments
+const DEPTH_FORMATS: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::X8_D24_UNORM_PACK32,
+    vk::Format::D16_UNORM,
+];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+const MESH_VERTEX_LAYOUT: VertexLayout = VertexLayout {
+    stride: size_of::<MeshVertex>() as u32,
+    input_rate: vk::VertexInputRate::VERTEX,
+    attributes: &[
+        VertexAttribute {
+            location: 0,
+            format: vk::Format::R32G32B32_SFLOAT,
+            offset: 0,
+        },
+        VertexAttribute {
+            location: 1,
+            format: vk::Format::R32G32B32_SFLOAT,
+            offset: size_of::<[f32; 3]>() as u32,
+        },
+    ],
+};
+
+// Mirrors the push constant block in shaders/mesh_*.glsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MeshPushConstants {
+    model: [f32; 16],
+    // Columns of the normal matrix, padded to vec4
+    normal_matrix: [[f32; 4]; 3],
+    color: [f32; 4],
+}
+
+// Indexed triangle list in 3D, y up
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    // Axis-aligned cube centered on the origin, with its own vertices per face
+    // so the normals stay flat
+    pub fn cube(half_extent: f32) -> Self {
+        let mut mesh = Self::default();
+        for normal in [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z] {
+            // Two axes spanning the face, chosen so u x v points along the normal
+            let u = Vec3::new(normal.y, normal.z, normal.x);
+            let v = normal.cross(u);
+            let base = mesh.vertices.len() as u32;
+            for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let position = (normal + u * su + v * sv) * half_extent;
+                mesh.vertices.push(MeshVertex {
+                    position: position.to_array(),
+                    normal: normal.to_array(),
+                });
+            }
+            mesh.indices
+                .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        mesh
+    }
+
+    // Sphere centered on the origin, split into `segments` around the y axis
+    // and `rings` from pole to pole
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Self {
+        let segments = segments.max(3);
+        let rings = rings.max(2);
+        let mut mesh = Self::default();
+        for ring in 0..=rings {
+            let (sin_polar, cos_polar) = (ring as f32 / rings as f32 * PI).sin_cos();
+            for segment in 0..=segments {
+                let (sin_azimuth, cos_azimuth) = (segment as f32 / segments as f32 * TAU).sin_cos();
+                let normal = Vec3::new(sin_polar * cos_azimuth, cos_polar, sin_polar * sin_azimuth);
+                mesh.vertices.push(MeshVertex {
+                    position: (normal * radius).to_array(),
+                    normal: normal.to_array(),
+                });
+            }
+        }
+        let row = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * row + segment;
+                let b = a + row;
+                mesh.indices
+                    .extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+        mesh
+    }
+
+    // Replaces every normal with the area-weighted average of the faces
+    // around its vertex
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(self.vertices[triangle[i] as usize].position));
+            // The cross product's length is twice the triangle's area
+            let normal = (b - a).cross(c - a);
+            for &index in triangle {
+                normals[index as usize] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.normalize_or_zero().to_array();
+        }
+    }
+}
+
+// Handle to a mesh uploaded with `MeshPass::add_mesh`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshId(usize);
+
+// One mesh instance to draw this frame
+#[derive(Clone, Copy, Debug)]
+pub struct MeshDraw {
+    pub mesh: MeshId,
+    // Model to world space
+    pub transform: Mat4,
+    pub color: [f32; 4],
+}
+
+// Which fragment shader lights the meshes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshShading {
+    // Ambient plus diffuse from the directional light
+    Diffuse,
+    // Blinn-Phong: diffuse and specular highlights from the directional light
+    // and every point light
+    #[default]
+    BlinnPhong,
+}
+
+// A mesh in one device-local buffer: vertices followed by indices
+struct GpuMesh {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    index_offset: vk::DeviceSize,
+    index_count: u32,
+}
+
+struct DepthTarget {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+}
+
+// Draws depth-tested 3D meshes on top of the 2D scene. It has its own render
+// pass over the scene target plus a depth buffer, recorded after the scene pass
+// ends, so the 2D pipelines and the post chain stay as they are.
+pub struct MeshPass {
+    // Projection times view, set by the scene every frame
+    pub view_projection: Mat4,
+    // World space camera position, for specular highlights
+    pub eye: Vec3,
+    pub lighting: Lighting,
+    pub shading: MeshShading,
+    // Cleared by the scene and refilled every frame
+    pub draws: Vec<MeshDraw>,
+    render_pass: vk::RenderPass,
+    depth_format: vk::Format,
+    target: DepthTarget,
+    extent: vk::Extent2D,
+    pipelines: PipelineManager,
+    diffuse_pipeline: PipelineDesc,
+    blinn_phong_pipeline: PipelineDesc,
+    // Drawn instead of either shading in the normals debug view
+    normals_pipeline: PipelineDesc,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    // Frame ring generation the descriptor set points at, None before the first upload
+    bound_generation: Option<u64>,
+    offset: u32,
+    meshes: Vec<GpuMesh>,
+}
+
+impl MeshPass {
+    // `scene_view` is the post processor's scene target, of size `extent`
+    pub fn new(gpu: &GpuContext, scene_view: vk::ImageView, extent: vk::Extent2D) -> Self {
+        let depth_format = find_depth_format(gpu);
+        let render_pass = create_render_pass(gpu, depth_format);
+        let mut pipelines = PipelineManager::new(render_pass);
+        let diffuse_shaders = pipelines.register_shaders(
+            gpu,
+            "mesh",
+            include_bytes!("../shaders/mesh_vert.spv"),
+            include_bytes!("../shaders/mesh_frag.spv"),
+        );
+        let blinn_phong_shaders = pipelines.register_shaders(
+            gpu,
+            "mesh blinn-phong",
+            include_bytes!("../shaders/mesh_vert.spv"),
+            include_bytes!("../shaders/mesh_lit_frag.spv"),
+        );
+        let normals_shaders = pipelines.register_shaders(
+            gpu,
+            "mesh normals",
+            include_bytes!("../shaders/mesh_vert.spv"),
+            include_bytes!("../shaders/mesh_normals_frag.spv"),
+        );
+        // Both shading variants declare the same frame uniforms and push
+        // constants, so they share one layout with the normals view, which
+        // uses none of them. The uniforms are bound at a different ring
+        // offset every frame.
+        let interface = pipelines
+            .interface(blinn_phong_shaders)
+            .clone()
+            .with_dynamic(0, 0);
+        let set_layouts = interface.create_set_layouts(gpu);
+        let pool_sizes = interface.pool_sizes(1);
+        let descriptor_pool = unsafe {
+            gpu.device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        max_sets: 1,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create mesh descriptor pool")
+        };
+        let descriptor_set = unsafe {
+            gpu.device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool,
+                    descriptor_set_count: 1,
+                    p_set_layouts: set_layouts.as_ptr(),
+                    ..Default::default()
+                })
+                .expect("Failed to allocate mesh descriptor set")[0]
+        };
+        let pipeline_layout = interface.create_pipeline_layout(
+            gpu,
+            &set_layouts,
+            size_of::<MeshPushConstants>(),
+        );
+        let diffuse_pipeline = PipelineDesc::new(diffuse_shaders, pipeline_layout)
+            .with_vertex_layout(MESH_VERTEX_LAYOUT)
+            .with_depth_test();
+        let blinn_phong_pipeline = PipelineDesc::new(blinn_phong_shaders, pipeline_layout)
+            .with_vertex_layout(MESH_VERTEX_LAYOUT)
+            .with_depth_test();
+        let normals_pipeline = PipelineDesc::new(normals_shaders, pipeline_layout)
+            .with_vertex_layout(MESH_VERTEX_LAYOUT)
+            .with_depth_test();
+        pipelines.prepare(gpu, &diffuse_pipeline);
+        pipelines.prepare(gpu, &blinn_phong_pipeline);
+        let target = DepthTarget::new(gpu, render_pass, depth_format, scene_view, extent);
+        println!("Mesh pass created with {:?} depth", depth_format);
+
+        Self {
+            view_projection: Mat4::IDENTITY,
+            eye: Vec3::ZERO,
+            lighting: Lighting::default(),
+            shading: MeshShading::default(),
+            draws: Vec::new(),
+            render_pass,
+            depth_format,
+            target,
+            extent,
+            pipelines,
+            diffuse_pipeline,
+            blinn_phong_pipeline,
+            normals_pipeline,
+            descriptor_set_layout: set_layouts[0],
+            descriptor_pool,
+            descriptor_set,
+            bound_generation: None,
+            offset: 0,
+            meshes: Vec::new(),
+        }
+    }
+
+    // Uploads `data` to the GPU; `name` labels its buffer
+    pub fn add_mesh(&mut self, gpu: &GpuContext, name: &str, data: &MeshData) -> MeshId {
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&data.vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&data.indices);
+        let (buffer, memory) = gpu.create_device_local_buffer(
+            name,
+            &[vertex_bytes, index_bytes].concat(),
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER,
+        );
+        self.meshes.push(GpuMesh {
+            buffer,
+            memory,
+            index_offset: vertex_bytes.len() as vk::DeviceSize,
+            index_count: data.indices.len() as u32,
+        });
+        println!(
+            "Mesh {} uploaded: {} vertices, {} triangles",
+            name,
+            data.vertices.len(),
+            data.indices.len() / 3
+        );
+        MeshId(self.meshes.len() - 1)
+    }
+
+    // Copies this frame's camera and lights into the frame ring; call before
+    // recording
+    pub fn upload(&mut self, gpu: &GpuContext, ring: &mut FrameRing) {
+        let uniforms = FrameUniforms::new(self.view_projection, self.eye, &self.lighting);
+        self.offset = ring.push(bytemuck::bytes_of(&uniforms)) as u32;
+        if self.bound_generation != Some(ring.generation()) {
+            self.bound_generation = Some(ring.generation());
+            let buffer_info = vk::DescriptorBufferInfo {
+                buffer: ring.buffer(),
+                offset: 0,
+                range: UPLOAD_SIZE as vk::DeviceSize,
+            };
+            unsafe {
+                gpu.device.update_descriptor_sets(
+                    &[vk::WriteDescriptorSet {
+                        dst_set: self.descriptor_set,
+                        dst_binding: 0,
+                        descriptor_count: 1,
+                        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                        p_buffer_info: &buffer_info,
+                        ..Default::default()
+                    }],
+                    &[],
+                );
+            }
+        }
+    }
+
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        self.pipelines.set_debug_view(view);
+    }
+
+    // Call after the scene target was recreated (device already idle)
+    pub fn resize(&mut self, gpu: &GpuContext, scene_view: vk::ImageView, extent: vk::Extent2D) {
+        self.target.destroy(gpu);
+        self.target = DepthTarget::new(gpu, self.render_pass, self.depth_format, scene_view, extent);
+        self.extent = extent;
+    }
+
+    // Records `draws` into the scene target. Must be called outside any render
+    // pass, after the scene pass has ended. On a device group it renders on the
+    // GPUs of `group`.
+    pub fn record(
+        &mut self,
+        gpu: &GpuContext,
+        command_buffer: vk::CommandBuffer,
+        group: Option<&GroupFrame>,
+    ) {
+        if self.draws.is_empty() {
+            return;
+        }
+        let desc = match (self.pipelines.debug_view(), self.shading) {
+            (DebugView::Normals, _) => &self.normals_pipeline,
+            (_, MeshShading::Diffuse) => &self.diffuse_pipeline,
+            (_, MeshShading::BlinnPhong) => &self.blinn_phong_pipeline,
+        };
+        let layout = desc.layout;
+        let pipeline = self.pipelines.get(gpu, desc);
+        let device = &gpu.device;
+        let clear_values = [
+            vk::ClearValue::default(),
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.extent,
+        };
+        unsafe {
+            device_group::begin_render_pass(
+                device,
+                command_buffer,
+                vk::RenderPassBeginInfo {
+                    render_pass: self.render_pass,
+                    framebuffer: self.target.framebuffer,
+                    render_area: area,
+                    clear_value_count: clear_values.len() as u32,
+                    p_clear_values: clear_values.as_ptr(),
+                    ..Default::default()
+                },
+                vk::SubpassContents::INLINE,
+                group,
+                true,
+            );
+            device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: self.extent.width as f32,
+                    height: self.extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            device_group::set_scissor(device, command_buffer, area, self.extent, group);
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                layout,
+                0,
+                &[self.descriptor_set],
+                &[self.offset],
+            );
+            for draw in &self.draws {
+                let mesh = &self.meshes[draw.mesh.0];
+                let normal_matrix = Mat3::from_mat4(draw.transform).inverse().transpose();
+                let push_constants = MeshPushConstants {
+                    model: draw.transform.to_cols_array(),
+                    normal_matrix: [
+                        normal_matrix.x_axis.extend(0.0).to_array(),
+                        normal_matrix.y_axis.extend(0.0).to_array(),
+                        normal_matrix.z_axis.extend(0.0).to_array(),
+                    ],
+                    color: draw.color,
+                };
+                device.cmd_push_constants(
+                    command_buffer,
+                    layout,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    bytemuck::bytes_of(&push_constants),
+                );
+                device.cmd_bind_vertex_buffers(command_buffer, 0, &[mesh.buffer], &[0]);
+                device.cmd_bind_index_buffer(
+                    command_buffer,
+                    mesh.buffer,
+                    mesh.index_offset,
+                    vk::IndexType::UINT32,
+                );
+                device.cmd_draw_indexed(command_buffer, mesh.index_count, 1, 0, 0, 0);
+            }
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    // Caller ensures the GPU is idle
+    pub fn destroy(&mut self, gpu: &GpuContext) {
+        for mesh in self.meshes.drain(..) {
+            unsafe { gpu.device.destroy_buffer(mesh.buffer, None) };
+            gpu.free_memory(mesh.memory);
+        }
+        self.target.destroy(gpu);
+        self.pipelines.destroy(&gpu.device);
+        unsafe {
+            gpu.device
+                .destroy_pipeline_layout(self.diffuse_pipeline.layout, None);
+            gpu.device.destroy_descriptor_pool(self.descriptor_pool, None);
+            gpu.device
+                .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            gpu.device.destroy_render_pass(self.render_pass, None);
+        }
+    }
+}
+
+impl DepthTarget {
+    fn new(
+        gpu: &GpuContext,
+        render_pass: vk::RenderPass,
+        format: vk::Format,
+        scene_view: vk::ImageView,
+        extent: vk::Extent2D,
+    ) -> Self {
+        let device = &gpu.device;
+        let image = unsafe {
+            device
+                .create_image(
+                    &vk::ImageCreateInfo {
+                        image_type: vk::ImageType::TYPE_2D,
+                        format,
+                        extent: vk::Extent3D {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                        mip_levels: 1,
+                        array_layers: 1,
+                        samples: vk::SampleCountFlags::TYPE_1,
+                        tiling: vk::ImageTiling::OPTIMAL,
+                        usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                        sharing_mode: vk::SharingMode::EXCLUSIVE,
+                        initial_layout: vk::ImageLayout::UNDEFINED,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create depth image")
+        };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory = gpu.allocate_memory(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Images,
+        );
+        unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Failed to bind depth image memory");
+        }
+        let view = unsafe {
+            device
+                .create_image_view(
+                    &vk::ImageViewCreateInfo {
+                        image,
+                        view_type: vk::ImageViewType::TYPE_2D,
+                        format,
+                        components: vk::ComponentMapping::default(),
+                        subresource_range: vk::ImageSubresourceRange {
+                            aspect_mask: vk::ImageAspectFlags::DEPTH,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        },
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create depth image view")
+        };
+        let attachments = [scene_view, view];
+        let framebuffer = unsafe {
+            device
+                .create_framebuffer(
+                    &vk::FramebufferCreateInfo {
+                        render_pass,
+                        attachment_count: attachments.len() as u32,
+                        p_attachments: attachments.as_ptr(),
+                        width: extent.width,
+                        height: extent.height,
+                        layers: 1,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create mesh framebuffer")
+        };
+        gpu.set_name(image, "depth target");
+        gpu.set_name(view, "depth target view");
+        gpu.set_name(framebuffer, "mesh framebuffer");
+        Self {
+            image,
+            memory,
+            view,
+            framebuffer,
+        }
+    }
+
+    fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device.destroy_framebuffer(self.framebuffer, None);
+            gpu.device.destroy_image_view(self.view, None);
+            gpu.device.destroy_image(self.image, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+}
+
+fn find_depth_format(gpu: &GpuContext) -> vk::Format {
+    DEPTH_FORMATS
+        .into_iter()
+        .find(|&format| {
+            let properties = unsafe {
+                gpu.instance
+                    .get_physical_device_format_properties(gpu.physical_device, format)
+            };
+            properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .expect("No supported depth format")
+}
+
+// Keeps what the scene pass drew and clears depth. The scene target arrives and
+// leaves ready for sampling, like in the post passes.
+fn create_render_pass(gpu: &GpuContext, depth_format: vk::Format) -> vk::RenderPass {
+    let attachments = [
+        vk::AttachmentDescription {
+            format: HDR_FORMAT,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::LOAD,
+            store_op: vk::AttachmentStoreOp::STORE,
+            initial_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..Default::default()
+        },
+        vk::AttachmentDescription {
+            format: depth_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ..Default::default()
+        },
+    ];
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 1,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+    let subpass = vk::SubpassDescription {
+        pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+        color_attachment_count: 1,
+        p_color_attachments: &color_attachment_ref,
+        p_depth_stencil_attachment: &depth_attachment_ref,
+        ..Default::default()
+    };
+    // The scene pass wrote the color before us, and last frame's depth tests
+    // must be done before the clear; the post chain samples what we write
+    let dependencies = [
+        vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ..Default::default()
+        },
+        vk::SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            ..Default::default()
+        },
+    ];
+    unsafe {
+        gpu.device
+            .create_render_pass(
+                &vk::RenderPassCreateInfo {
+                    attachment_count: attachments.len() as u32,
+                    p_attachments: attachments.as_ptr(),
+                    subpass_count: 1,
+                    p_subpasses: &subpass,
+                    dependency_count: dependencies.len() as u32,
+                    p_dependencies: dependencies.as_ptr(),
+                    ..Default::default()
+                },
+                None,
+            )
+            .expect("Failed to create mesh render pass")
+    }
+}
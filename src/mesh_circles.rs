@@ -0,0 +1,100 @@
+use ash::vk;
+use glam::Mat4;
+
+use crate::gpu::GpuContext;
+use crate::pipeline::{BlendMode, PipelineDesc, PipelineManager};
+use crate::reflect::ShaderInterface;
+use crate::renderer::Circle;
+
+// Mirrors the push constant block in shaders/circles_mesh.glsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct MeshCirclePushConstants {
+    projection: [f32; 16],
+    color: [f32; 4],
+    center: [f32; 2],
+    radius: f32,
+}
+
+// Circles generated entirely by a mesh shader: one workgroup per circle writes
+// its own rim vertices and triangle fan, so nothing is bound but push constants.
+// While enabled it stands in for the renderer's circle mode. Needs
+// `capabilities.mesh_shader`.
+pub struct MeshCirclePass {
+    pipeline: PipelineDesc,
+}
+
+impl MeshCirclePass {
+    pub fn new(gpu: &GpuContext, pipelines: &mut PipelineManager) -> Self {
+        let mesh_interface = ShaderInterface {
+            push_constants: Some(vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::MESH_EXT,
+                offset: 0,
+                size: size_of::<MeshCirclePushConstants>() as u32,
+            }),
+            ..Default::default()
+        };
+        // The fragment shader only passes the interpolated color through
+        let shaders = pipelines.register_mesh_shaders(
+            gpu,
+            "mesh shader circles",
+            include_bytes!("../shaders/circles_mesh.spv"),
+            &mesh_interface,
+            include_bytes!("../shaders/particles_frag.spv"),
+        );
+        let pipeline_layout = pipelines.interface(shaders).create_pipeline_layout(
+            gpu,
+            &[],
+            size_of::<MeshCirclePushConstants>(),
+        );
+        let pipeline = PipelineDesc::new(shaders, pipeline_layout);
+        pipelines.prepare(gpu, &pipeline);
+        pipelines.prepare(gpu, &pipeline.with_blend_mode(BlendMode::Alpha));
+        println!("Mesh shader circle pass created");
+
+        Self { pipeline }
+    }
+
+    // One workgroup per circle, in order. Outlines are not drawn.
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+        circles: &[Circle],
+        ortho: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        if circles.is_empty() {
+            return;
+        }
+        let pipeline = pipelines.get(gpu, &self.pipeline.with_blend_mode(blend_mode));
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+        }
+        for circle in circles {
+            let push_constants = MeshCirclePushConstants {
+                projection: ortho.to_cols_array(),
+                color: circle.color,
+                center: circle.position.to_array(),
+                radius: circle.radius,
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline.layout,
+                    vk::ShaderStageFlags::MESH_EXT,
+                    0,
+                    bytemuck::bytes_of(&push_constants),
+                );
+            }
+            gpu.cmd_draw_mesh_tasks(command_buffer, 1);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, pipelines: &mut PipelineManager) {
+        pipelines.release_shaders(device, self.pipeline.shaders);
+        unsafe { device.destroy_pipeline_layout(self.pipeline.layout, None) };
+    }
+}
@@ -0,0 +1,198 @@
+use ash::vk;
+
+use crate::frame_ring::FrameRing;
+use crate::gpu::GpuContext;
+use crate::pipeline::{BlendMode, PipelineDesc, PipelineManager};
+use crate::renderer::Circle;
+
+// Circles past this are left out of the field
+pub const MAX_METABALLS: usize = 256;
+// Bytes pushed to the frame ring each frame in metaballs mode
+pub const UPLOAD_SIZE: usize = MAX_METABALLS * size_of::<GpuMetaball>();
+// Field value at the surface; a lone ball's surface sits exactly at its radius
+const THRESHOLD: f32 = 1.0;
+
+// Mirrors `Ball` in shaders/metaballs_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GpuMetaball {
+    shape: [f32; 4],
+    color: [f32; 4],
+    outline_color: [f32; 4],
+}
+
+// Mirrors the push constant block in shaders/metaballs_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MetaballPushConstants {
+    count: u32,
+    threshold: f32,
+}
+
+// Draws all circles as one implicit surface so nearby ones merge. Circle
+// positions, radii and colors go into the frame ring each frame, read through a
+// dynamic storage buffer descriptor, and a fullscreen fragment shader evaluates
+// the field per pixel.
+pub struct MetaballPass {
+    count: u32,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    // Frame ring generation the descriptor set points at, None before the first upload
+    bound_generation: Option<u64>,
+    offset: u32,
+    pipeline: PipelineDesc,
+}
+
+impl MetaballPass {
+    pub fn new(gpu: &GpuContext, pipelines: &mut PipelineManager) -> Self {
+        let shaders = pipelines.register_shaders(
+            gpu,
+            "metaballs",
+            include_bytes!("../shaders/fullscreen_vert.spv"),
+            include_bytes!("../shaders/metaballs_frag.spv"),
+        );
+        // The ball buffer is bound at a different ring offset every frame
+        let interface = pipelines.interface(shaders).clone().with_dynamic(0, 0);
+        let set_layouts = interface.create_set_layouts(gpu);
+        let pool_sizes = interface.pool_sizes(1);
+        let descriptor_pool = unsafe {
+            gpu.device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        max_sets: 1,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create metaball descriptor pool")
+        };
+        let descriptor_set = unsafe {
+            gpu.device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool,
+                    descriptor_set_count: 1,
+                    p_set_layouts: set_layouts.as_ptr(),
+                    ..Default::default()
+                })
+                .expect("Failed to allocate metaball descriptor set")[0]
+        };
+        let pipeline_layout =
+            interface.create_pipeline_layout(gpu, &set_layouts, size_of::<MetaballPushConstants>());
+        let pipeline =
+            PipelineDesc::new(shaders, pipeline_layout).with_blend_mode(BlendMode::Alpha);
+
+        Self {
+            count: 0,
+            set_layouts,
+            descriptor_pool,
+            descriptor_set,
+            bound_generation: None,
+            offset: 0,
+            pipeline,
+        }
+    }
+
+    pub fn pipeline(&self) -> PipelineDesc {
+        self.pipeline
+    }
+
+    // Copies this frame's circles into the frame ring, pointing the descriptor set
+    // at the ring buffer again if it was reallocated. The shader works in
+    // framebuffer pixels, so logical units are scaled by `pixels_per_unit`.
+    pub fn upload(
+        &mut self,
+        gpu: &GpuContext,
+        ring: &mut FrameRing,
+        circles: &[Circle],
+        pixels_per_unit: f32,
+    ) {
+        let mut balls: Vec<GpuMetaball> = circles
+            .iter()
+            .take(MAX_METABALLS)
+            .map(|circle| GpuMetaball {
+                shape: [
+                    circle.position.x * pixels_per_unit,
+                    circle.position.y * pixels_per_unit,
+                    circle.radius * pixels_per_unit,
+                    circle.outline_width * pixels_per_unit,
+                ],
+                color: circle.color,
+                outline_color: circle.outline_color,
+            })
+            .collect();
+        self.count = balls.len() as u32;
+        // The descriptor range always covers MAX_METABALLS entries
+        balls.resize(MAX_METABALLS, bytemuck::Zeroable::zeroed());
+        self.offset = ring.push(bytemuck::cast_slice(&balls)) as u32;
+
+        if self.bound_generation != Some(ring.generation()) {
+            self.bound_generation = Some(ring.generation());
+            let buffer_info = vk::DescriptorBufferInfo {
+                buffer: ring.buffer(),
+                offset: 0,
+                range: UPLOAD_SIZE as vk::DeviceSize,
+            };
+            unsafe {
+                gpu.device.update_descriptor_sets(
+                    &[vk::WriteDescriptorSet {
+                        dst_set: self.descriptor_set,
+                        dst_binding: 0,
+                        descriptor_count: 1,
+                        descriptor_type: vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+                        p_buffer_info: &buffer_info,
+                        ..Default::default()
+                    }],
+                    &[],
+                );
+            }
+        }
+    }
+
+    pub fn record_draw(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        pipeline: vk::Pipeline,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+        let push_constants = MetaballPushConstants {
+            count: self.count,
+            threshold: THRESHOLD,
+        };
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.layout,
+                0,
+                &[self.descriptor_set],
+                &[self.offset],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&push_constants),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, pipelines: &mut PipelineManager) {
+        pipelines.release_shaders(device, self.pipeline.shaders);
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline.layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            for &layout in &self.set_layouts {
+                device.destroy_descriptor_set_layout(layout, None);
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use ash::vk;
+
+use crate::compute::{self, ComputePipeline};
+use crate::gpu::GpuContext;
+use crate::sync::{Access, Barriers};
+
+const GROUP_SIZE: u32 = 8;
+
+// How levels 1.. of a texture are filled in from level 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MipmapMethod {
+    // A linear-filtered vkCmdBlitImage per level, each halving the one before
+    Blit,
+    // A compute shader averaging 2x2 texels, for RGBA8 images the device can't
+    // blit with linear filtering
+    Compute,
+}
+
+impl MipmapMethod {
+    // The method the device supports for `format`, if any. Block-compressed
+    // formats can't be blitted or written by shaders, so they only get the
+    // levels they were loaded with.
+    pub fn for_format(gpu: &GpuContext, format: vk::Format) -> Option<Self> {
+        let features = unsafe {
+            gpu.instance
+                .get_physical_device_format_properties(gpu.physical_device, format)
+                .optimal_tiling_features
+        };
+        if features.contains(
+            vk::FormatFeatureFlags::BLIT_SRC
+                | vk::FormatFeatureFlags::BLIT_DST
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        ) {
+            Some(Self::Blit)
+        } else if format == vk::Format::R8G8B8A8_UNORM
+            && features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE)
+        {
+            Some(Self::Compute)
+        } else {
+            None
+        }
+    }
+
+    // What the image needs to be created with on top of sampling
+    pub fn image_usage(self) -> vk::ImageUsageFlags {
+        match self {
+            Self::Blit => vk::ImageUsageFlags::TRANSFER_SRC,
+            Self::Compute => vk::ImageUsageFlags::STORAGE,
+        }
+    }
+}
+
+// Levels in a full chain down to 1x1
+pub fn level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+fn level_range(level: u32) -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: level,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+fn sampled() -> Access {
+    Access::new(
+        vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        vk::AccessFlags2::SHADER_SAMPLED_READ,
+    )
+}
+
+// The image whose levels are generated, with the size of level 0
+#[derive(Clone, Copy, Debug)]
+pub struct MipChain {
+    pub image: vk::Image,
+    pub format: vk::Format,
+    pub width: u32,
+    pub height: u32,
+    pub mip_levels: u32,
+}
+
+impl MipChain {
+    fn extent(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+}
+
+// What a compute generation created, destroyed once its commands have executed
+pub struct MipmapScratch {
+    pipeline: ComputePipeline,
+    views: Vec<vk::ImageView>,
+}
+
+impl MipmapScratch {
+    pub fn destroy(self, gpu: &GpuContext) {
+        for view in self.views {
+            unsafe { gpu.device.destroy_image_view(view, None) };
+        }
+        self.pipeline.destroy(&gpu.device);
+    }
+}
+
+// Records filling levels 1..`mip_levels` of `image` from level 0. Every level
+// must be in TRANSFER_DST_OPTIMAL, with level 0 just written by a copy, and
+// afterwards all of them are SHADER_READ_ONLY_OPTIMAL. Needs a graphics queue
+// either way. The compute method returns resources to destroy after the
+// command buffer has finished.
+pub fn generate_mipmaps(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    method: MipmapMethod,
+    chain: MipChain,
+) -> Option<MipmapScratch> {
+    match method {
+        MipmapMethod::Blit => {
+            record_blits(gpu, command_buffer, chain);
+            None
+        }
+        MipmapMethod::Compute => Some(record_dispatches(gpu, command_buffer, chain)),
+    }
+}
+
+fn record_blits(gpu: &GpuContext, command_buffer: vk::CommandBuffer, chain: MipChain) {
+    let MipChain {
+        image, mip_levels, ..
+    } = chain;
+    let copy_write = Access::new(
+        vk::PipelineStageFlags2::COPY,
+        vk::AccessFlags2::TRANSFER_WRITE,
+    );
+    let blit_write = Access::new(
+        vk::PipelineStageFlags2::BLIT,
+        vk::AccessFlags2::TRANSFER_WRITE,
+    );
+    let blit_read = Access::new(
+        vk::PipelineStageFlags2::BLIT,
+        vk::AccessFlags2::TRANSFER_READ,
+    );
+    for level in 1..mip_levels {
+        // The level above was written by the copy or the previous blit
+        let written = if level == 1 { copy_write } else { blit_write };
+        Barriers::new()
+            .image(
+                image,
+                level_range(level - 1),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                written,
+                blit_read,
+            )
+            .record(gpu, command_buffer);
+
+        let (src_width, src_height) = chain.extent(level - 1);
+        let (dst_width, dst_height) = chain.extent(level);
+        let subresource = |mip_level| vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let blit = vk::ImageBlit {
+            src_subresource: subresource(level - 1),
+            src_offsets: [
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: src_width as i32,
+                    y: src_height as i32,
+                    z: 1,
+                },
+            ],
+            dst_subresource: subresource(level),
+            dst_offsets: [
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: dst_width as i32,
+                    y: dst_height as i32,
+                    z: 1,
+                },
+            ],
+        };
+        unsafe {
+            gpu.device.cmd_blit_image(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[blit],
+                vk::Filter::LINEAR,
+            );
+        }
+
+        Barriers::new()
+            .image(
+                image,
+                level_range(level - 1),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                blit_read,
+                sampled(),
+            )
+            .record(gpu, command_buffer);
+    }
+    let last_written = if mip_levels > 1 {
+        blit_write
+    } else {
+        copy_write
+    };
+    Barriers::new()
+        .image(
+            image,
+            level_range(mip_levels - 1),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            last_written,
+            sampled(),
+        )
+        .record(gpu, command_buffer);
+}
+
+fn record_dispatches(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    chain: MipChain,
+) -> MipmapScratch {
+    let MipChain {
+        image,
+        format,
+        mip_levels,
+        ..
+    } = chain;
+    let pipeline = ComputePipeline::new(
+        gpu,
+        "mipmap",
+        include_bytes!("../shaders/mipmap_comp.spv"),
+        std::mem::size_of::<[i32; 2]>(),
+    );
+    // Recorded right away, so there's no frame to skip
+    pipeline.wait();
+    let views: Vec<vk::ImageView> = (0..mip_levels)
+        .map(|level| {
+            let view_create_info = vk::ImageViewCreateInfo {
+                image,
+                view_type: vk::ImageViewType::TYPE_2D,
+                format,
+                subresource_range: level_range(level),
+                ..Default::default()
+            };
+            unsafe {
+                gpu.device
+                    .create_image_view(&view_create_info, None)
+                    .expect("Failed to create mip level view")
+            }
+        })
+        .collect();
+
+    let all_levels = vk::ImageSubresourceRange {
+        level_count: mip_levels,
+        ..level_range(0)
+    };
+    let storage_write = Access::new(
+        vk::PipelineStageFlags2::COMPUTE_SHADER,
+        vk::AccessFlags2::SHADER_STORAGE_WRITE,
+    );
+    let storage_read = Access::new(
+        vk::PipelineStageFlags2::COMPUTE_SHADER,
+        vk::AccessFlags2::SHADER_STORAGE_READ,
+    );
+    Barriers::new()
+        .image(
+            image,
+            all_levels,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::GENERAL,
+            Access::new(
+                vk::PipelineStageFlags2::COPY,
+                vk::AccessFlags2::TRANSFER_WRITE,
+            ),
+            Access::new(
+                vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            ),
+        )
+        .record(gpu, command_buffer);
+    for level in 1..mip_levels {
+        let descriptor_set = pipeline
+            .create_image_descriptor_set(gpu, &[views[level as usize - 1], views[level as usize]]);
+        let (dst_width, dst_height) = chain.extent(level);
+        let size = [dst_width as i32, dst_height as i32];
+        pipeline.dispatch(
+            &gpu.device,
+            command_buffer,
+            Some(descriptor_set),
+            bytemuck::bytes_of(&size),
+            [
+                compute::group_count(dst_width, GROUP_SIZE),
+                compute::group_count(dst_height, GROUP_SIZE),
+                1,
+            ],
+        );
+        // The next dispatch reads what this one wrote
+        Barriers::new()
+            .image(
+                image,
+                level_range(level),
+                vk::ImageLayout::GENERAL,
+                vk::ImageLayout::GENERAL,
+                storage_write,
+                storage_read,
+            )
+            .record(gpu, command_buffer);
+    }
+    Barriers::new()
+        .image(
+            image,
+            all_levels,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            storage_write,
+            sampled(),
+        )
+        .record(gpu, command_buffer);
+
+    MipmapScratch { pipeline, views }
+}
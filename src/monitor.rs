@@ -0,0 +1,59 @@
+use winit::dpi::{PhysicalPosition, Size};
+use winit::event_loop::ActiveEventLoop;
+use winit::monitor::MonitorHandle;
+use winit::window::Window;
+
+// Monitor `index` in the window system's order, listing them all so the index
+// can be picked. None (with a warning) when there is no such monitor.
+pub fn select(event_loop: &ActiveEventLoop, index: usize) -> Option<MonitorHandle> {
+    let monitors: Vec<_> = event_loop.available_monitors().collect();
+    for (i, monitor) in monitors.iter().enumerate() {
+        println!("Monitor {}: {}", i, describe(monitor));
+    }
+    let monitor = monitors.into_iter().nth(index);
+    if monitor.is_none() {
+        eprintln!("No monitor {}, using the default one", index);
+    }
+    monitor
+}
+
+// Refresh rate in Hz, None when the window system doesn't report it
+pub fn refresh_rate(monitor: &MonitorHandle) -> Option<f32> {
+    monitor
+        .refresh_rate_millihertz()
+        .map(|millihertz| millihertz as f32 / 1000.0)
+}
+
+// Of the monitor `window` is mostly on
+pub fn current_refresh_rate(window: &Window) -> Option<f32> {
+    window.current_monitor().as_ref().and_then(refresh_rate)
+}
+
+// Top-left corner that centers a window of `size` on `monitor`
+pub fn centered_position(monitor: &MonitorHandle, size: Size) -> PhysicalPosition<i32> {
+    let size = size.to_physical::<i32>(monitor.scale_factor());
+    let origin = monitor.position();
+    let area = monitor.size();
+    PhysicalPosition::new(
+        origin.x + (area.width as i32 - size.width) / 2,
+        origin.y + (area.height as i32 - size.height) / 2,
+    )
+}
+
+pub fn describe(monitor: &MonitorHandle) -> String {
+    let size = monitor.size();
+    let position = monitor.position();
+    let refresh = refresh_rate(monitor)
+        .map(|hz| format!("{:.2} Hz", hz))
+        .unwrap_or_else(|| "unknown refresh rate".to_string());
+    format!(
+        "{} {}x{} at ({}, {}), {}, scale {}",
+        monitor.name().unwrap_or_else(|| "unnamed".to_string()),
+        size.width,
+        size.height,
+        position.x,
+        position.y,
+        refresh,
+        monitor.scale_factor()
+    )
+}
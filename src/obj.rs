@@ -0,0 +1,105 @@
+// Wavefront OBJ models with MTL materials, loaded with tobj into `MeshData`
+// for the mesh pass. Faces are triangulated and every position/normal pair
+// becomes one vertex. Only the diffuse color and dissolve of each material are
+// used; textures are ignored.
+
+use std::path::Path;
+
+use glam::Vec3;
+
+use crate::mesh::{MeshData, MeshVertex};
+
+// Used for objects without a material, or when the MTL file can't be read
+const DEFAULT_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+
+// One object (or one material's faces of it) from an OBJ file
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    pub name: String,
+    pub data: MeshData,
+    pub color: [f32; 4],
+}
+
+// Loads every object in the OBJ file at `path`. Objects without normals get
+// smooth ones computed from their faces. A missing or broken MTL file is
+// reported and the objects are loaded uncolored.
+pub fn load(path: &Path) -> Result<Vec<ObjMesh>, String> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    };
+    let (models, materials) = tobj::load_obj(path, &options).map_err(|error| error.to_string())?;
+    let materials = materials.unwrap_or_else(|error| {
+        eprintln!("Materials for {} not loaded: {}", path.display(), error);
+        Vec::new()
+    });
+    let meshes: Vec<ObjMesh> = models
+        .into_iter()
+        .filter(|model| !model.mesh.indices.is_empty())
+        .map(|model| {
+            let mesh = model.mesh;
+            let mut data = MeshData {
+                vertices: mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(|position| MeshVertex {
+                        position: [position[0], position[1], position[2]],
+                        normal: [0.0; 3],
+                    })
+                    .collect(),
+                indices: mesh.indices,
+            };
+            if mesh.normals.len() == mesh.positions.len() {
+                for (vertex, normal) in data.vertices.iter_mut().zip(mesh.normals.chunks_exact(3)) {
+                    vertex.normal = [normal[0], normal[1], normal[2]];
+                }
+            } else {
+                data.compute_normals();
+            }
+            let color = mesh
+                .material_id
+                .and_then(|id| materials.get(id))
+                .map_or(DEFAULT_COLOR, |material| {
+                    let [r, g, b] = material.diffuse.unwrap_or([0.8; 3]);
+                    [r, g, b, material.dissolve.unwrap_or(1.0)]
+                });
+            ObjMesh {
+                name: model.name,
+                data,
+                color,
+            }
+        })
+        .collect();
+    if meshes.is_empty() {
+        return Err("no faces".to_string());
+    }
+    let triangles: usize = meshes.iter().map(|mesh| mesh.data.indices.len() / 3).sum();
+    println!(
+        "Loaded {}: {} objects, {} triangles",
+        path.display(),
+        meshes.len(),
+        triangles
+    );
+    Ok(meshes)
+}
+
+// Center and radius of a sphere around every vertex of `meshes`, for framing
+// a model of any size
+pub fn bounding_sphere(meshes: &[ObjMesh]) -> (Vec3, f32) {
+    let positions = meshes
+        .iter()
+        .flat_map(|mesh| mesh.data.vertices.iter())
+        .map(|vertex| Vec3::from(vertex.position));
+    let (min, max) = positions.fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), position| (min.min(position), max.max(position)),
+    );
+    let center = (min + max) * 0.5;
+    let radius = meshes
+        .iter()
+        .flat_map(|mesh| mesh.data.vertices.iter())
+        .map(|vertex| Vec3::from(vertex.position).distance(center))
+        .fold(0.0, f32::max);
+    (center, radius)
+}
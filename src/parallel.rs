@@ -0,0 +1,198 @@
+use std::sync::Mutex;
+
+use ash::vk;
+use rayon::prelude::*;
+
+use crate::gpu::GpuContext;
+
+// Worker threads are capped here; recording rarely scales past a few cores
+const MAX_RECORDING_THREADS: usize = 8;
+
+// The command pool used by one thread and the secondary buffers allocated from it.
+// Buffers are reused every frame once the pool has been reset.
+struct ThreadCommands {
+    // Prefix of the debug name of each buffer
+    name: String,
+    pool: vk::CommandPool,
+    buffers: Vec<vk::CommandBuffer>,
+    used: usize,
+}
+
+impl ThreadCommands {
+    fn new(gpu: &GpuContext, name: String) -> Self {
+        let pool = unsafe {
+            gpu.device
+                .create_command_pool(
+                    &vk::CommandPoolCreateInfo {
+                        flags: vk::CommandPoolCreateFlags::TRANSIENT,
+                        queue_family_index: gpu.queue_family_index,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create recording command pool")
+        };
+        Self {
+            name,
+            pool,
+            buffers: Vec::new(),
+            used: 0,
+        }
+    }
+
+    fn next_buffer(&mut self, gpu: &GpuContext) -> vk::CommandBuffer {
+        if self.used == self.buffers.len() {
+            let buffer = unsafe {
+                gpu.device
+                    .allocate_command_buffers(&vk::CommandBufferAllocateInfo {
+                        command_pool: self.pool,
+                        level: vk::CommandBufferLevel::SECONDARY,
+                        command_buffer_count: 1,
+                        ..Default::default()
+                    })
+                    .expect("Failed to allocate secondary command buffer")[0]
+            };
+            gpu.set_name(
+                buffer,
+                &format!("{} secondary {}", self.name, self.buffers.len()),
+            );
+            self.buffers.push(buffer);
+        }
+        self.used += 1;
+        self.buffers[self.used - 1]
+    }
+}
+
+// Records secondary command buffers for one render pass subpass, in parallel on a
+// rayon pool. A command pool may only be used by one thread at a time, so every
+// worker has its own, plus one for the thread that owns the recorder. The primary
+// buffer runs the results with `vkCmdExecuteCommands`.
+pub struct ParallelRecorder {
+    threads: rayon::ThreadPool,
+    // Indexed by rayon worker index; the last one belongs to the calling thread
+    commands: Vec<Mutex<ThreadCommands>>,
+}
+
+impl ParallelRecorder {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let thread_count = std::thread::available_parallelism()
+            .map_or(1, |count| count.get())
+            .min(MAX_RECORDING_THREADS);
+        let threads = rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .thread_name(|index| format!("record-{}", index))
+            .build()
+            .expect("Failed to create recording thread pool");
+        let commands = (0..=thread_count)
+            .map(|index| {
+                let name = if index < thread_count {
+                    format!("record-{}", index)
+                } else {
+                    "main thread".to_string()
+                };
+                Mutex::new(ThreadCommands::new(gpu, name))
+            })
+            .collect();
+        println!("Parallel recorder created with {} threads", thread_count);
+        Self { threads, commands }
+    }
+
+    // Makes every secondary buffer available again. Like the primary buffer, they
+    // are reused without waiting for the GPU, so the previous frame must be done.
+    pub fn begin_frame(&mut self, gpu: &GpuContext) {
+        for commands in &mut self.commands {
+            let commands = commands.get_mut().unwrap();
+            unsafe {
+                gpu.device
+                    .reset_command_pool(commands.pool, vk::CommandPoolResetFlags::empty())
+                    .expect("Failed to reset recording command pool");
+            }
+            commands.used = 0;
+        }
+    }
+
+    // Frees every pool with its buffers. Caller ensures the device is idle.
+    pub fn destroy(&mut self, gpu: &GpuContext) {
+        for commands in self.commands.drain(..) {
+            let commands = commands.into_inner().unwrap();
+            unsafe { gpu.device.destroy_command_pool(commands.pool, None) };
+        }
+    }
+
+    // Starts a secondary buffer on the calling thread; finish it with
+    // `end_secondary`
+    pub fn begin_secondary(
+        &self,
+        gpu: &GpuContext,
+        inheritance: &vk::CommandBufferInheritanceInfo,
+    ) -> vk::CommandBuffer {
+        let mut commands = self.commands.last().unwrap().lock().unwrap();
+        let command_buffer = commands.next_buffer(gpu);
+        Self::begin(&gpu.device, command_buffer, inheritance);
+        command_buffer
+    }
+
+    pub fn end_secondary(device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device
+                .end_command_buffer(command_buffer)
+                .expect("Failed to end secondary command buffer");
+        }
+    }
+
+    // Splits `items` into chunks of `chunk_size` and records each chunk into its own
+    // secondary buffer on the worker threads. The buffers come back in chunk order.
+    pub fn record_chunks<T: Sync>(
+        &self,
+        gpu: &GpuContext,
+        inheritance: &vk::CommandBufferInheritanceInfo,
+        items: &[T],
+        chunk_size: usize,
+        record: impl Fn(vk::CommandBuffer, &[T]) + Sync,
+    ) -> Vec<vk::CommandBuffer> {
+        // The inheritance info holds raw pointers, so it is rebuilt on each worker
+        let render_pass = inheritance.render_pass;
+        let subpass = inheritance.subpass;
+        let framebuffer = inheritance.framebuffer;
+        self.threads.install(|| {
+            items
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    let index = rayon::current_thread_index().unwrap();
+                    let mut commands = self.commands[index].lock().unwrap();
+                    let command_buffer = commands.next_buffer(gpu);
+                    let inheritance = vk::CommandBufferInheritanceInfo {
+                        render_pass,
+                        subpass,
+                        framebuffer,
+                        ..Default::default()
+                    };
+                    Self::begin(&gpu.device, command_buffer, &inheritance);
+                    record(command_buffer, chunk);
+                    Self::end_secondary(&gpu.device, command_buffer);
+                    command_buffer
+                })
+                .collect()
+        })
+    }
+
+    fn begin(
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        inheritance: &vk::CommandBufferInheritanceInfo,
+    ) {
+        unsafe {
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo {
+                        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                            | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+                        p_inheritance_info: inheritance,
+                        ..Default::default()
+                    },
+                )
+                .expect("Failed to begin secondary command buffer");
+        }
+    }
+}
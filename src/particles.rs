@@ -0,0 +1,289 @@
+use ash::vk;
+use glam::{Mat4, Vec2};
+
+use crate::color::hsv_to_rgba;
+use crate::compute::{self, ComputePipeline};
+use crate::gpu::GpuContext;
+use crate::pipeline::{BlendMode, PipelineDesc, PipelineManager, VertexAttribute, VertexLayout};
+
+const WORKGROUP_SIZE: u32 = 256;
+
+// Mirrors `struct Particle` in shaders/particles_comp.glsl (std430)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Particle {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub color: [f32; 4],
+}
+
+// Mirrors the push constant block in shaders/particles_comp.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SimulationPushConstants {
+    attractor: [f32; 2],
+    bounds: [f32; 2],
+    dt: f32,
+    strength: f32,
+    drag: f32,
+    count: u32,
+}
+
+// Mirrors the push constant block in shaders/particles_address_comp.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AddressSimulationPushConstants {
+    simulation: SimulationPushConstants,
+    particles: vk::DeviceAddress,
+}
+
+// The particle buffer is read directly as a point-list vertex buffer
+const PARTICLE_VERTEX_LAYOUT: VertexLayout = VertexLayout {
+    stride: size_of::<Particle>() as u32,
+    input_rate: vk::VertexInputRate::VERTEX,
+    attributes: &[
+        VertexAttribute {
+            location: 0,
+            format: vk::Format::R32G32_SFLOAT,
+            offset: std::mem::offset_of!(Particle, position) as u32,
+        },
+        VertexAttribute {
+            location: 1,
+            format: vk::Format::R32G32_SFLOAT,
+            offset: std::mem::offset_of!(Particle, velocity) as u32,
+        },
+        VertexAttribute {
+            location: 2,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+            offset: std::mem::offset_of!(Particle, color) as u32,
+        },
+    ],
+};
+
+// Particles that live entirely on the GPU: a compute shader integrates them in
+// place each frame and the same buffer is drawn as additive points. With buffer
+// device address both shaders reach the buffer through its address in their
+// push constants, so there is no descriptor set or vertex binding.
+pub struct ParticleSystem {
+    pub attractor: Vec2,
+    pub strength: f32,
+    pub drag: f32,
+    count: u32,
+    pending_dt: f32,
+    buffer: vk::Buffer,
+    buffer_memory: vk::DeviceMemory,
+    simulation: ComputePipeline,
+    // Only without `address`
+    descriptor_set: Option<vk::DescriptorSet>,
+    address: Option<vk::DeviceAddress>,
+    render_pipeline: PipelineDesc,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        count: u32,
+        bounds: Vec2,
+    ) -> Self {
+        let particles = Self::initial_particles(count, bounds);
+        let buffer_device_address = gpu.capabilities.buffer_device_address;
+        let mut usage = vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::VERTEX_BUFFER;
+        if buffer_device_address {
+            usage |= vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+        }
+        let (buffer, buffer_memory) = gpu.create_device_local_buffer(
+            "particle buffer",
+            bytemuck::cast_slice(&particles),
+            usage,
+        );
+        let address = buffer_device_address.then(|| gpu.buffer_address(buffer));
+
+        let (simulation, descriptor_set) = if buffer_device_address {
+            let simulation = ComputePipeline::new(
+                gpu,
+                "particle simulation",
+                include_bytes!("../shaders/particles_address_comp.spv"),
+                size_of::<AddressSimulationPushConstants>(),
+            );
+            (simulation, None)
+        } else {
+            let simulation = ComputePipeline::new(
+                gpu,
+                "particle simulation",
+                include_bytes!("../shaders/particles_comp.spv"),
+                size_of::<SimulationPushConstants>(),
+            );
+            let descriptor_set = simulation.create_descriptor_set(gpu, &[buffer]);
+            (simulation, Some(descriptor_set))
+        };
+
+        let render_pipeline = if buffer_device_address {
+            let shaders = pipelines.register_shaders(
+                gpu,
+                "particles (device address)",
+                include_bytes!("../shaders/particles_address_vert.spv"),
+                include_bytes!("../shaders/particles_frag.spv"),
+            );
+            let pipeline_layout = pipelines.interface(shaders).create_pipeline_layout(
+                gpu,
+                &[],
+                size_of::<Mat4>() + size_of::<vk::DeviceAddress>(),
+            );
+            PipelineDesc::new(shaders, pipeline_layout)
+        } else {
+            let shaders = pipelines.register_shaders(
+                gpu,
+                "particles",
+                include_bytes!("../shaders/particles_vert.spv"),
+                include_bytes!("../shaders/particles_frag.spv"),
+            );
+            let pipeline_layout =
+                pipelines
+                    .interface(shaders)
+                    .create_pipeline_layout(gpu, &[], size_of::<Mat4>());
+            PipelineDesc::new(shaders, pipeline_layout).with_vertex_layout(PARTICLE_VERTEX_LAYOUT)
+        }
+        .with_topology(vk::PrimitiveTopology::POINT_LIST)
+        .with_blend_mode(BlendMode::Additive);
+        pipelines.prepare(gpu, &render_pipeline);
+        println!("Particle system created with {} particles", count);
+
+        Self {
+            attractor: bounds * 0.5,
+            strength: 2.0e6,
+            drag: 0.2,
+            count,
+            pending_dt: 0.0,
+            buffer,
+            buffer_memory,
+            simulation,
+            descriptor_set,
+            address,
+            render_pipeline,
+        }
+    }
+
+    // Scatters particles over the window with small random velocities and a
+    // spread of hues. Uses a cheap integer hash so no RNG crate is needed.
+    fn initial_particles(count: u32, bounds: Vec2) -> Vec<Particle> {
+        let hash = |mut x: u32| {
+            x ^= x >> 16;
+            x = x.wrapping_mul(0x7feb_352d);
+            x ^= x >> 15;
+            x = x.wrapping_mul(0x846c_a68b);
+            x ^= x >> 16;
+            x as f32 / u32::MAX as f32
+        };
+        (0..count)
+            .map(|i| {
+                let seed = i.wrapping_mul(4);
+                let position = Vec2::new(hash(seed), hash(seed + 1)) * bounds;
+                let angle = hash(seed + 2) * std::f32::consts::TAU;
+                let velocity = Vec2::from_angle(angle) * 40.0 * hash(seed + 3);
+                Particle {
+                    position: position.to_array(),
+                    velocity: velocity.to_array(),
+                    color: hsv_to_rgba(0.5 + 0.2 * hash(seed + 1), 0.7, 1.0, 0.25),
+                }
+            })
+            .collect()
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    // Queues simulation time; it is consumed by the next compute dispatch
+    pub fn simulate(&mut self, dt: f32) {
+        self.pending_dt += dt;
+    }
+
+    // Records the compute step. Must be called outside a render pass, before
+    // the pass that draws the particles.
+    pub fn record_update(
+        &mut self,
+        gpu: &GpuContext,
+        command_buffer: vk::CommandBuffer,
+        bounds: Vec2,
+    ) {
+        // Large hitches would fling everything through the walls
+        let dt = self.pending_dt.min(1.0 / 20.0);
+        self.pending_dt = 0.0;
+        let push_constants = SimulationPushConstants {
+            attractor: self.attractor.to_array(),
+            bounds: bounds.to_array(),
+            dt,
+            strength: self.strength,
+            drag: self.drag,
+            count: self.count,
+        };
+        let address_push_constants;
+        let push_constants = match self.address {
+            Some(particles) => {
+                address_push_constants = AddressSimulationPushConstants {
+                    simulation: push_constants,
+                    particles,
+                };
+                bytemuck::bytes_of(&address_push_constants)
+            }
+            None => bytemuck::bytes_of(&push_constants),
+        };
+        compute::vertex_to_compute_barrier(gpu, command_buffer, self.buffer);
+        self.simulation.dispatch(
+            &gpu.device,
+            command_buffer,
+            self.descriptor_set,
+            push_constants,
+            [compute::group_count(self.count, WORKGROUP_SIZE), 1, 1],
+        );
+        compute::compute_to_vertex_barrier(gpu, command_buffer, self.buffer);
+    }
+
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+        projection: &Mat4,
+    ) {
+        let pipeline = pipelines.get(gpu, &self.render_pipeline);
+        let device = &gpu.device;
+        let projection = projection.to_cols_array();
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_push_constants(
+                command_buffer,
+                self.render_pipeline.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::cast_slice(&projection),
+            );
+            // The address follows the projection in the push constants
+            match self.address {
+                Some(address) => device.cmd_push_constants(
+                    command_buffer,
+                    self.render_pipeline.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    size_of::<Mat4>() as u32,
+                    bytemuck::bytes_of(&address),
+                ),
+                None => device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.buffer], &[0]),
+            }
+            device.cmd_draw(command_buffer, self.count, 1, 0, 0);
+        }
+    }
+
+    // Also releases the render pipeline from `pipelines`, so a system can be
+    // torn down and recreated without leaking shader modules
+    pub fn destroy(&self, gpu: &GpuContext, pipelines: &mut PipelineManager) {
+        let device = &gpu.device;
+        pipelines.release_shaders(device, self.render_pipeline.shaders);
+        self.simulation.destroy(device);
+        unsafe {
+            device.destroy_pipeline_layout(self.render_pipeline.layout, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+        gpu.free_memory(self.buffer_memory);
+    }
+}
@@ -0,0 +1,162 @@
+// Vector paths in the style of the HTML canvas: subpaths of straight lines,
+// Bezier curves and arcs, filled or stroked on the canvas. Curves are flattened
+// into line segments as they are added, with more segments the further a curve
+// bends, so they stay smooth at any size.
+
+use glam::Vec2;
+
+use crate::geometry::{self, Geometry, StrokeStyle, Tessellation};
+
+// A run of connected points started by `move_to`
+#[derive(Clone, Debug, Default)]
+struct Subpath {
+    points: Vec<Vec2>,
+    closed: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Path {
+    subpaths: Vec<Subpath>,
+    tessellation: Tessellation,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // How finely curves added from now on are flattened
+    pub fn with_tessellation(mut self, tessellation: Tessellation) -> Self {
+        self.tessellation = tessellation;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subpaths.is_empty()
+    }
+
+    // Starts a new subpath at `point`
+    pub fn move_to(&mut self, point: Vec2) -> &mut Self {
+        self.subpaths.push(Subpath {
+            points: vec![point],
+            closed: false,
+        });
+        self
+    }
+
+    // A straight line from the current point. Without one, starts there instead.
+    pub fn line_to(&mut self, point: Vec2) -> &mut Self {
+        self.current().points.push(point);
+        self
+    }
+
+    // Quadratic Bezier curve from the current point, pulled towards `control`
+    pub fn quad_to(&mut self, control: Vec2, to: Vec2) -> &mut Self {
+        let from = self.current_point(control);
+        let deviation = (from - 2.0 * control + to).length() / 4.0;
+        let segments = self.tessellation.segments_for_curve(deviation);
+        let points = (1..=segments).map(|i| {
+            let t = i as f32 / segments as f32;
+            let u = 1.0 - t;
+            from * (u * u) + control * (2.0 * u * t) + to * (t * t)
+        });
+        self.current().points.extend(points);
+        self
+    }
+
+    // Cubic Bezier curve from the current point, leaving towards `control1` and
+    // arriving from `control2`
+    pub fn cubic_to(&mut self, control1: Vec2, control2: Vec2, to: Vec2) -> &mut Self {
+        let from = self.current_point(control1);
+        let deviation = (from - 2.0 * control1 + control2)
+            .length()
+            .max((control1 - 2.0 * control2 + to).length())
+            * 0.75;
+        let segments = self.tessellation.segments_for_curve(deviation);
+        let points = (1..=segments).map(|i| {
+            let t = i as f32 / segments as f32;
+            let u = 1.0 - t;
+            from * (u * u * u)
+                + control1 * (3.0 * u * u * t)
+                + control2 * (3.0 * u * t * t)
+                + to * (t * t * t)
+        });
+        self.current().points.extend(points);
+        self
+    }
+
+    // Circular arc around `center` from `start_angle`, turning by `sweep`
+    // radians (positive is clockwise on screen). Like the HTML canvas, a line
+    // joins the current point to where the arc starts.
+    pub fn arc(&mut self, center: Vec2, radius: f32, start_angle: f32, sweep: f32) -> &mut Self {
+        let segments = self.tessellation.segments_for(radius, sweep);
+        let points = (0..=segments).map(|i| {
+            let angle = start_angle + sweep * i as f32 / segments as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        });
+        self.current().points.extend(points);
+        self
+    }
+
+    // Joins the current subpath back to its start. Whatever comes next starts
+    // a new subpath from there.
+    pub fn close(&mut self) -> &mut Self {
+        if let Some(subpath) = self.subpaths.last_mut() {
+            subpath.closed = true;
+        }
+        self
+    }
+
+    // Triangles covering the inside of every subpath, each closed implicitly and
+    // filled on its own. Overlapping subpaths are drawn twice, so holes need a
+    // clip shape instead.
+    pub fn fill(&self) -> Geometry {
+        let mut geometry = Geometry::new();
+        for subpath in &self.subpaths {
+            geometry.append(&geometry::fill_polygon(&subpath.points));
+        }
+        geometry
+    }
+
+    // Triangles covering a line along every subpath
+    pub fn stroke(&self, style: StrokeStyle) -> Geometry {
+        let mut geometry = Geometry::new();
+        for subpath in &self.subpaths {
+            geometry.append(&geometry::stroke_polyline(
+                &subpath.points,
+                subpath.closed,
+                style,
+                self.tessellation,
+            ));
+        }
+        geometry
+    }
+
+    // The subpath being added to. A closed subpath is over, so the next one
+    // starts where it did.
+    fn current(&mut self) -> &mut Subpath {
+        match self.subpaths.last() {
+            Some(subpath) if !subpath.closed => {}
+            Some(subpath) => {
+                let start = subpath.points[0];
+                self.move_to(start);
+            }
+            None => {
+                self.subpaths.push(Subpath::default());
+            }
+        }
+        self.subpaths.last_mut().unwrap()
+    }
+
+    // Where the next segment starts, or `fallback` if nothing has been drawn
+    fn current_point(&mut self, fallback: Vec2) -> Vec2 {
+        let subpath = self.current();
+        match subpath.points.last() {
+            Some(&point) => point,
+            None => {
+                subpath.points.push(fallback);
+                fallback
+            }
+        }
+    }
+}
@@ -0,0 +1,796 @@
+use ash::vk;
+use std::collections::HashMap;
+
+use crate::compiler::{CreationFeedback, PendingPipeline};
+use crate::gpu::GpuContext;
+use crate::reflect::ShaderInterface;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    // Source replaces the destination; alpha is written but ignored
+    Opaque,
+    // Standard non-premultiplied "over": src * a + dst * (1 - a)
+    Alpha,
+    // Light accumulation: src * a + dst. Order independent, good for glowing particles
+    Additive,
+}
+
+impl BlendMode {
+    pub fn attachment_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        match self {
+            BlendMode::Opaque => vk::PipelineColorBlendAttachmentState {
+                blend_enable: vk::FALSE,
+                color_write_mask: vk::ColorComponentFlags::RGBA,
+                ..Default::default()
+            },
+            BlendMode::Alpha => vk::PipelineColorBlendAttachmentState {
+                blend_enable: vk::TRUE,
+                src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+                dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                color_blend_op: vk::BlendOp::ADD,
+                src_alpha_blend_factor: vk::BlendFactor::ONE,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                alpha_blend_op: vk::BlendOp::ADD,
+                color_write_mask: vk::ColorComponentFlags::RGBA,
+            },
+            BlendMode::Additive => vk::PipelineColorBlendAttachmentState {
+                blend_enable: vk::TRUE,
+                src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+                dst_color_blend_factor: vk::BlendFactor::ONE,
+                color_blend_op: vk::BlendOp::ADD,
+                src_alpha_blend_factor: vk::BlendFactor::ZERO,
+                dst_alpha_blend_factor: vk::BlendFactor::ONE,
+                alpha_blend_op: vk::BlendOp::ADD,
+                color_write_mask: vk::ColorComponentFlags::RGBA,
+            },
+        }
+    }
+}
+
+// What a pipeline does with the stencil attachment. Anything but Disabled needs
+// a render pass with one, and takes its reference value from
+// vkCmdSetStencilReference. Each mode only touches pixels whose stencil equals
+// the reference.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StencilMode {
+    #[default]
+    Disabled,
+    // Draw where the stencil equals the reference
+    Test,
+    // Write no color; add one to the stencil instead
+    Increment,
+    // Write no color; subtract one from the stencil instead
+    Decrement,
+}
+
+impl StencilMode {
+    fn op_state(&self) -> vk::StencilOpState {
+        let pass_op = match self {
+            StencilMode::Disabled | StencilMode::Test => vk::StencilOp::KEEP,
+            StencilMode::Increment => vk::StencilOp::INCREMENT_AND_CLAMP,
+            StencilMode::Decrement => vk::StencilOp::DECREMENT_AND_CLAMP,
+        };
+        vk::StencilOpState {
+            fail_op: vk::StencilOp::KEEP,
+            pass_op,
+            depth_fail_op: vk::StencilOp::KEEP,
+            compare_op: vk::CompareOp::EQUAL,
+            compare_mask: 0xff,
+            write_mask: 0xff,
+            reference: 0,
+        }
+    }
+
+    fn writes_color(&self) -> bool {
+        matches!(self, StencilMode::Disabled | StencilMode::Test)
+    }
+}
+
+// Debug visualizations a `PipelineManager` applies to every pipeline it hands
+// out, so whole passes change without knowing about them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    #[default]
+    Off,
+    // Triangle edges only; needs the fillModeNonSolid device feature
+    Wireframe,
+    // Every fragment adds the same dim red, so pixels drawn many times heat up
+    // through orange to yellow
+    Overdraw,
+    // 3D meshes colored by their world-space normal. The mesh pass picks its
+    // own shader for this, so other pipelines draw as usual.
+    Normals,
+}
+
+impl DebugView {
+    pub fn next(self) -> Self {
+        match self {
+            DebugView::Off => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Overdraw,
+            DebugView::Overdraw => DebugView::Normals,
+            DebugView::Normals => DebugView::Off,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Off => "off",
+            DebugView::Wireframe => "wireframe",
+            DebugView::Overdraw => "overdraw",
+            DebugView::Normals => "normals",
+        }
+    }
+
+    // The view as far as pipeline state goes
+    fn pipeline_view(self) -> Self {
+        match self {
+            DebugView::Normals => DebugView::Off,
+            view => view,
+        }
+    }
+}
+
+// Handle to a vertex (or mesh) + fragment shader pair registered with a
+// `PipelineManager`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub format: vk::Format,
+    pub offset: u32,
+}
+
+// A single interleaved vertex binding. Pipelines that generate their vertices from
+// gl_VertexIndex use no layout at all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub stride: u32,
+    pub input_rate: vk::VertexInputRate,
+    pub attributes: &'static [VertexAttribute],
+}
+
+// Specialization constants one pipeline may set
+pub const MAX_SPECIALIZATION_CONSTANTS: usize = 4;
+
+// Specialization constant values by constant_id, applied to both stages. Every
+// value is 32 bits wide; floats are stored as their bits so descriptors stay
+// hashable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Specialization {
+    len: usize,
+    entries: [(u32, u32); MAX_SPECIALIZATION_CONSTANTS],
+}
+
+impl Specialization {
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.entries[..self.len].iter().map(|&(id, _)| id)
+    }
+
+    // Replaces the value if `id` is already set. Entries are kept sorted by id so
+    // the order constants were set in doesn't make a different descriptor.
+    fn set(&mut self, id: u32, bits: u32) {
+        match self.entries[..self.len].binary_search_by_key(&id, |&(entry, _)| entry) {
+            Ok(index) => self.entries[index].1 = bits,
+            Err(index) => {
+                assert!(
+                    self.len < MAX_SPECIALIZATION_CONSTANTS,
+                    "Too many specialization constants"
+                );
+                self.entries[index..=self.len].rotate_right(1);
+                self.entries[index] = (id, bits);
+                self.len += 1;
+            }
+        }
+    }
+
+    fn map_entries(&self) -> Vec<vk::SpecializationMapEntry> {
+        (0..self.len)
+            .map(|index| vk::SpecializationMapEntry {
+                constant_id: self.entries[index].0,
+                offset: (index * size_of::<u32>()) as u32,
+                size: size_of::<u32>(),
+            })
+            .collect()
+    }
+
+    fn data(&self) -> Vec<u32> {
+        self.entries[..self.len]
+            .iter()
+            .map(|&(_, bits)| bits)
+            .collect()
+    }
+}
+
+// Everything that distinguishes one graphics pipeline from another. Viewport and
+// scissor are always dynamic, so the same pipeline survives a resize.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineDesc {
+    pub shaders: ShaderId,
+    pub layout: vk::PipelineLayout,
+    pub vertex_layout: Option<VertexLayout>,
+    pub topology: vk::PrimitiveTopology,
+    pub blend_mode: BlendMode,
+    // Test and write depth; the render pass must have a depth attachment
+    pub depth_test: bool,
+    pub stencil: StencilMode,
+    pub specialization: Specialization,
+}
+
+impl PipelineDesc {
+    pub fn new(shaders: ShaderId, layout: vk::PipelineLayout) -> Self {
+        Self {
+            shaders,
+            layout,
+            vertex_layout: None,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            blend_mode: BlendMode::Opaque,
+            depth_test: false,
+            stencil: StencilMode::Disabled,
+            specialization: Specialization::default(),
+        }
+    }
+
+    pub fn with_vertex_layout(mut self, vertex_layout: VertexLayout) -> Self {
+        self.vertex_layout = Some(vertex_layout);
+        self
+    }
+
+    pub fn with_topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_depth_test(mut self) -> Self {
+        self.depth_test = true;
+        self
+    }
+
+    pub fn with_stencil(mut self, stencil: StencilMode) -> Self {
+        self.stencil = stencil;
+        self
+    }
+
+    // Overrides a `layout(constant_id = id)` constant. The shader's type decides
+    // how the bits are read, so use the setter matching it.
+    pub fn with_constant_u32(mut self, id: u32, value: u32) -> Self {
+        self.specialization.set(id, value);
+        self
+    }
+
+    pub fn with_constant_f32(self, id: u32, value: f32) -> Self {
+        self.with_constant_u32(id, value.to_bits())
+    }
+
+    pub fn with_constant_bool(self, id: u32, value: bool) -> Self {
+        self.with_constant_u32(id, value as vk::Bool32)
+    }
+}
+
+// Builds graphics pipelines for one render pass on first use and caches them by
+// descriptor and debug view. Shader modules stay alive so new variants can be
+// built at any time. Each shader pair is reflected when registered, and
+// pipelines check their vertex layout against it. Pipelines are built on the
+// `GpuContext`'s compiler thread, and until one is done `get` hands out a
+// placeholder in its place.
+pub struct PipelineManager {
+    render_pass: vk::RenderPass,
+    shaders: Vec<(vk::ShaderModule, vk::ShaderModule)>,
+    // Stage of the first module of each pair: VERTEX, or MESH_EXT for pairs from
+    // `register_mesh_shaders`
+    first_stages: Vec<vk::ShaderStageFlags>,
+    // Debug name of each shader pair, also given to its pipelines
+    names: Vec<String>,
+    interfaces: Vec<ShaderInterface>,
+    pipelines: HashMap<(PipelineDesc, DebugView), vk::Pipeline>,
+    // Pipelines the compiler thread is still building
+    pending: HashMap<(PipelineDesc, DebugView), PendingPipeline>,
+    debug_view: DebugView,
+    // Replaces every fragment shader in the overdraw view; created on first use
+    overdraw_shader: vk::ShaderModule,
+    // The render pass has a fragment shading rate attachment for pipelines to follow
+    shading_rate_attachment: bool,
+    // Drawn instead of pipelines that aren't built yet; created on first use. It
+    // has no resources, vertex inputs or fragment shader, so it can stand in for
+    // any pipeline of the render pass and draws nothing.
+    placeholder: vk::Pipeline,
+    placeholder_layout: vk::PipelineLayout,
+    placeholder_shader: vk::ShaderModule,
+}
+
+// Everything a graphics pipeline is built from, copied out of the manager so the
+// compiler thread doesn't need it
+#[derive(Clone, Copy)]
+struct PipelineBuild {
+    desc: PipelineDesc,
+    view: DebugView,
+    render_pass: vk::RenderPass,
+    first_stage: vk::ShaderStageFlags,
+    first_module: vk::ShaderModule,
+    // Null for a pipeline without a fragment shader, which writes no color
+    fragment_module: vk::ShaderModule,
+    shading_rate_attachment: bool,
+}
+
+impl PipelineManager {
+    pub fn new(render_pass: vk::RenderPass) -> Self {
+        Self {
+            render_pass,
+            shaders: Vec::new(),
+            first_stages: Vec::new(),
+            names: Vec::new(),
+            interfaces: Vec::new(),
+            pipelines: HashMap::new(),
+            pending: HashMap::new(),
+            debug_view: DebugView::Off,
+            overdraw_shader: vk::ShaderModule::null(),
+            shading_rate_attachment: false,
+            placeholder: vk::Pipeline::null(),
+            placeholder_layout: vk::PipelineLayout::null(),
+            placeholder_shader: vk::ShaderModule::null(),
+        }
+    }
+
+    // Builds every pipeline to take its shading rate from the render pass's
+    // fragment shading rate attachment. Without this the attachment is ignored.
+    pub fn with_shading_rate_attachment(mut self) -> Self {
+        self.shading_rate_attachment = true;
+        self
+    }
+
+    pub fn register_shaders(
+        &mut self,
+        gpu: &GpuContext,
+        name: &str,
+        vertex: &[u8],
+        fragment: &[u8],
+    ) -> ShaderId {
+        let id = ShaderId(self.shaders.len() as u32);
+        let vertex_module = gpu.create_shader_module(vertex);
+        let fragment_module = gpu.create_shader_module(fragment);
+        gpu.set_name(vertex_module, &format!("{} vertex shader", name));
+        gpu.set_name(fragment_module, &format!("{} fragment shader", name));
+        self.shaders.push((vertex_module, fragment_module));
+        self.first_stages.push(vk::ShaderStageFlags::VERTEX);
+        self.names.push(name.to_string());
+        self.interfaces.push(ShaderInterface::new(&[
+            (vertex, vk::ShaderStageFlags::VERTEX),
+            (fragment, vk::ShaderStageFlags::FRAGMENT),
+        ]));
+        id
+    }
+
+    // A mesh shader in place of the vertex shader; its pipelines take no vertex
+    // input. naga can't parse mesh shaders, so the caller describes that stage's
+    // interface and only the fragment shader is reflected. Needs
+    // `capabilities.mesh_shader`.
+    pub fn register_mesh_shaders(
+        &mut self,
+        gpu: &GpuContext,
+        name: &str,
+        mesh: &[u8],
+        mesh_interface: &ShaderInterface,
+        fragment: &[u8],
+    ) -> ShaderId {
+        let id = ShaderId(self.shaders.len() as u32);
+        let mesh_module = gpu.create_shader_module(mesh);
+        let fragment_module = gpu.create_shader_module(fragment);
+        gpu.set_name(mesh_module, &format!("{} mesh shader", name));
+        gpu.set_name(fragment_module, &format!("{} fragment shader", name));
+        self.shaders.push((mesh_module, fragment_module));
+        self.first_stages.push(vk::ShaderStageFlags::MESH_EXT);
+        self.names.push(name.to_string());
+        self.interfaces.push(
+            ShaderInterface::new(&[(fragment, vk::ShaderStageFlags::FRAGMENT)])
+                .merge(mesh_interface),
+        );
+        id
+    }
+
+    // Descriptor bindings, push constants and vertex inputs of a shader pair
+    pub fn interface(&self, shaders: ShaderId) -> &ShaderInterface {
+        &self.interfaces[shaders.0 as usize]
+    }
+
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    // Builds pipelines for `render_pass` from now on, e.g. the swapchain pass
+    // made again for a new surface format. Every pipeline built for the old one
+    // is destroyed; the shaders stay, so they are built again on their next
+    // `get`. Caller ensures the GPU is idle.
+    pub fn set_render_pass(&mut self, device: &ash::Device, render_pass: vk::RenderPass) {
+        self.wait_for_pending(None);
+        unsafe {
+            for (_, pipeline) in self.pipelines.drain() {
+                device.destroy_pipeline(pipeline, None);
+            }
+            device.destroy_pipeline(self.placeholder, None);
+            device.destroy_pipeline_layout(self.placeholder_layout, None);
+            device.destroy_shader_module(self.placeholder_shader, None);
+        }
+        self.placeholder = vk::Pipeline::null();
+        self.placeholder_layout = vk::PipelineLayout::null();
+        self.placeholder_shader = vk::ShaderModule::null();
+        self.render_pass = render_pass;
+    }
+
+    // Number of pipelines built so far, not counting the placeholder
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    // Pipelines handed out from now on draw with `view`. Pipelines built for
+    // other views stay cached, so switching back is free.
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        self.debug_view = view;
+    }
+
+    // The pipeline for `desc` in the current debug view. One that isn't built yet
+    // is queued on the compiler thread, and until it's done this returns the same
+    // pipeline in the plain view if that's built, or else the placeholder, so the
+    // draw is skipped. Mesh shader pipelines have no placeholder, so for those
+    // this blocks instead.
+    pub fn get(&mut self, gpu: &GpuContext, desc: &PipelineDesc) -> vk::Pipeline {
+        let view = self.debug_view.pipeline_view();
+        let key = (*desc, view);
+        if let Some(&pipeline) = self.pipelines.get(&key) {
+            return pipeline;
+        }
+        self.prepare(gpu, desc);
+        if let Some(pipeline) = self.pending[&key].poll() {
+            self.pending.remove(&key);
+            return self.finish(key, pipeline);
+        }
+        if self.first_stages[desc.shaders.0 as usize] == vk::ShaderStageFlags::MESH_EXT {
+            let pipeline = self.pending.remove(&key).unwrap().wait();
+            return self.finish(key, pipeline);
+        }
+        if let Some(&pipeline) = self.pipelines.get(&(*desc, DebugView::Off)) {
+            return pipeline;
+        }
+        self.placeholder(gpu)
+    }
+
+    // Starts building the pipeline for `desc` in the current debug view, if it
+    // isn't built or being built already, without waiting for it. Passes call
+    // this when they're created so their pipelines are usually done before the
+    // first `get`.
+    pub fn prepare(&mut self, gpu: &GpuContext, desc: &PipelineDesc) {
+        let view = self.debug_view.pipeline_view();
+        let key = (*desc, view);
+        if !self.pipelines.contains_key(&key) && !self.pending.contains_key(&key) {
+            self.queue(gpu, desc, view);
+        }
+    }
+
+    fn queue(&mut self, gpu: &GpuContext, desc: &PipelineDesc, view: DebugView) {
+        if view == DebugView::Overdraw && self.overdraw_shader == vk::ShaderModule::null() {
+            self.overdraw_shader =
+                gpu.create_shader_module(include_bytes!("../shaders/overdraw_frag.spv"));
+            gpu.set_name(self.overdraw_shader, "overdraw fragment shader");
+        }
+        // Checked here so a mismatch panics on this thread
+        let interface = self.interface(desc.shaders);
+        interface.check_vertex_layout(desc.vertex_layout.as_ref());
+        interface.check_specialization(&desc.specialization);
+
+        let (first_module, mut fragment_module) = self.shaders[desc.shaders.0 as usize];
+        // The overdraw shader has no inputs or resources, so it fits any
+        // vertex shader and layout
+        if view == DebugView::Overdraw {
+            fragment_module = self.overdraw_shader;
+        }
+        let build = PipelineBuild {
+            desc: *desc,
+            view,
+            render_pass: self.render_pass,
+            first_stage: self.first_stages[desc.shaders.0 as usize],
+            first_module,
+            fragment_module,
+            shading_rate_attachment: self.shading_rate_attachment,
+        };
+        let name = self.names[desc.shaders.0 as usize].as_str();
+        let name = match view {
+            DebugView::Off => format!("{} pipeline", name),
+            _ => format!("{} {} pipeline", name, view.name()),
+        };
+        let pending = gpu.compiler.compile(name, move |device, cache, feedback| {
+            let pipeline = build_pipeline(device, cache, &build, feedback);
+            println!(
+                "Pipeline created: {:?} for {:?} ({:?})",
+                pipeline, build.desc, build.view
+            );
+            pipeline
+        });
+        self.pending.insert((*desc, view), pending);
+    }
+
+    fn finish(&mut self, key: (PipelineDesc, DebugView), pipeline: vk::Pipeline) -> vk::Pipeline {
+        self.pipelines.insert(key, pipeline);
+        pipeline
+    }
+
+    fn placeholder(&mut self, gpu: &GpuContext) -> vk::Pipeline {
+        if self.placeholder != vk::Pipeline::null() {
+            return self.placeholder;
+        }
+        self.placeholder_shader =
+            gpu.create_shader_module(include_bytes!("../shaders/placeholder_vert.spv"));
+        gpu.set_name(self.placeholder_shader, "placeholder vertex shader");
+        self.placeholder_layout = unsafe {
+            gpu.device
+                .create_pipeline_layout(&vk::PipelineLayoutCreateInfo::default(), None)
+                .expect("Failed to create placeholder pipeline layout")
+        };
+        let build = PipelineBuild {
+            desc: PipelineDesc::new(ShaderId::default(), self.placeholder_layout),
+            view: DebugView::Off,
+            render_pass: self.render_pass,
+            first_stage: vk::ShaderStageFlags::VERTEX,
+            first_module: self.placeholder_shader,
+            fragment_module: vk::ShaderModule::null(),
+            shading_rate_attachment: self.shading_rate_attachment,
+        };
+        self.placeholder = build_pipeline(&gpu.device, gpu.pipeline_cache, &build, None);
+        gpu.set_name(self.placeholder, "placeholder pipeline");
+        println!("Placeholder pipeline created: {:?}", self.placeholder);
+        self.placeholder
+    }
+
+    // Blocks until every pipeline built from `shaders` is done, so its modules
+    // can be destroyed
+    fn wait_for_pending(&mut self, shaders: Option<ShaderId>) {
+        let keys: Vec<(PipelineDesc, DebugView)> = self
+            .pending
+            .keys()
+            .filter(|(desc, _)| shaders.is_none_or(|shaders| desc.shaders == shaders))
+            .copied()
+            .collect();
+        for key in keys {
+            let pipeline = self.pending.remove(&key).unwrap().wait();
+            self.finish(key, pipeline);
+        }
+    }
+
+    // Destroys the shader modules behind `shaders` and every cached pipeline built
+    // from them, waiting for any still being built. The id must not be used
+    // again. Caller ensures the GPU is idle.
+    pub fn release_shaders(&mut self, device: &ash::Device, shaders: ShaderId) {
+        self.wait_for_pending(Some(shaders));
+        self.pipelines.retain(|(desc, _), &mut pipeline| {
+            if desc.shaders == shaders {
+                unsafe { device.destroy_pipeline(pipeline, None) };
+                false
+            } else {
+                true
+            }
+        });
+        let (vertex_shader_module, fragment_shader_module) = std::mem::replace(
+            &mut self.shaders[shaders.0 as usize],
+            (vk::ShaderModule::null(), vk::ShaderModule::null()),
+        );
+        self.interfaces[shaders.0 as usize] = ShaderInterface::default();
+        unsafe {
+            device.destroy_shader_module(vertex_shader_module, None);
+            device.destroy_shader_module(fragment_shader_module, None);
+        }
+    }
+
+    pub fn destroy(&mut self, device: &ash::Device) {
+        self.wait_for_pending(None);
+        unsafe {
+            for (_, pipeline) in self.pipelines.drain() {
+                device.destroy_pipeline(pipeline, None);
+            }
+            for (vertex_shader_module, fragment_shader_module) in self.shaders.drain(..) {
+                device.destroy_shader_module(vertex_shader_module, None);
+                device.destroy_shader_module(fragment_shader_module, None);
+            }
+            device.destroy_shader_module(self.overdraw_shader, None);
+            device.destroy_pipeline(self.placeholder, None);
+            device.destroy_pipeline_layout(self.placeholder_layout, None);
+            device.destroy_shader_module(self.placeholder_shader, None);
+        }
+        self.overdraw_shader = vk::ShaderModule::null();
+        self.placeholder = vk::Pipeline::null();
+        self.placeholder_layout = vk::PipelineLayout::null();
+        self.placeholder_shader = vk::ShaderModule::null();
+        self.interfaces.clear();
+    }
+}
+
+fn build_pipeline(
+    device: &ash::Device,
+    cache: vk::PipelineCache,
+    build: &PipelineBuild,
+    feedback: Option<&mut CreationFeedback>,
+) -> vk::Pipeline {
+    let PipelineBuild {
+        desc,
+        view,
+        first_stage,
+        ..
+    } = *build;
+    let binding;
+    let attributes: Vec<vk::VertexInputAttributeDescription>;
+    let vertex_input_info = match &desc.vertex_layout {
+        Some(layout) => {
+            binding = vk::VertexInputBindingDescription {
+                binding: 0,
+                stride: layout.stride,
+                input_rate: layout.input_rate,
+            };
+            attributes = layout
+                .attributes
+                .iter()
+                .map(|attribute| vk::VertexInputAttributeDescription {
+                    location: attribute.location,
+                    binding: 0,
+                    format: attribute.format,
+                    offset: attribute.offset,
+                })
+                .collect();
+            vk::PipelineVertexInputStateCreateInfo {
+                vertex_binding_description_count: 1,
+                p_vertex_binding_descriptions: &binding,
+                vertex_attribute_description_count: attributes.len() as u32,
+                p_vertex_attribute_descriptions: attributes.as_ptr(),
+                ..Default::default()
+            }
+        }
+        None => vk::PipelineVertexInputStateCreateInfo::default(),
+    };
+
+    // Stages ignore constant ids they don't declare
+    let map_entries = desc.specialization.map_entries();
+    let data = desc.specialization.data();
+    let specialization_info = vk::SpecializationInfo {
+        map_entry_count: map_entries.len() as u32,
+        p_map_entries: map_entries.as_ptr(),
+        data_size: size_of_val(data.as_slice()),
+        p_data: data.as_ptr().cast(),
+        ..Default::default()
+    };
+    let shader_stages = [
+        vk::PipelineShaderStageCreateInfo {
+            stage: first_stage,
+            module: build.first_module,
+            p_name: c"main".as_ptr(),
+            p_specialization_info: &specialization_info,
+            ..Default::default()
+        },
+        vk::PipelineShaderStageCreateInfo {
+            stage: vk::ShaderStageFlags::FRAGMENT,
+            module: build.fragment_module,
+            p_name: c"main".as_ptr(),
+            p_specialization_info: &specialization_info,
+            ..Default::default()
+        },
+    ];
+
+    let stencil_enabled = desc.stencil != StencilMode::Disabled;
+    let stencil_op_state = desc.stencil.op_state();
+    let blend_mode = match view {
+        DebugView::Overdraw => BlendMode::Additive,
+        _ => desc.blend_mode,
+    };
+    let mut blend_attachment_state = blend_mode.attachment_state();
+    let has_fragment_shader = build.fragment_module != vk::ShaderModule::null();
+    if !desc.stencil.writes_color() || !has_fragment_shader {
+        blend_attachment_state.color_write_mask = vk::ColorComponentFlags::empty();
+    }
+    let mut dynamic_states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    if stencil_enabled {
+        dynamic_states.push(vk::DynamicState::STENCIL_REFERENCE);
+    }
+
+    // Mesh pipelines have no vertex input or input assembly stage
+    let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo {
+        topology: desc.topology,
+        ..Default::default()
+    };
+    let (p_vertex_input_state, p_input_assembly_state) = match first_stage {
+        vk::ShaderStageFlags::MESH_EXT => (std::ptr::null(), std::ptr::null()),
+        _ => (
+            &vertex_input_info as *const _,
+            &input_assembly_info as *const _,
+        ),
+    };
+
+    // The pipeline's own rate stays 1x1 and the attachment's replaces it
+    let shading_rate_info = vk::PipelineFragmentShadingRateStateCreateInfoKHR {
+        fragment_size: vk::Extent2D {
+            width: 1,
+            height: 1,
+        },
+        combiner_ops: [
+            vk::FragmentShadingRateCombinerOpKHR::KEEP,
+            vk::FragmentShadingRateCombinerOpKHR::REPLACE,
+        ],
+        ..Default::default()
+    };
+    let mut p_next = if build.shading_rate_attachment {
+        &shading_rate_info as *const _ as *const std::ffi::c_void
+    } else {
+        std::ptr::null()
+    };
+    let stage_count = if has_fragment_shader { 2 } else { 1 };
+    let feedback_info;
+    if let Some(feedback) = feedback {
+        let stages = [first_stage, vk::ShaderStageFlags::FRAGMENT];
+        feedback_info = feedback.chain(&stages[..stage_count], p_next);
+        p_next = &feedback_info as *const _ as *const std::ffi::c_void;
+    }
+
+    let pipeline_info = vk::GraphicsPipelineCreateInfo {
+        p_next,
+        stage_count: stage_count as u32,
+        p_stages: shader_stages.as_ptr(),
+        p_vertex_input_state,
+        p_input_assembly_state,
+        p_viewport_state: &vk::PipelineViewportStateCreateInfo {
+            viewport_count: 1,
+            scissor_count: 1,
+            ..Default::default()
+        },
+        p_rasterization_state: &vk::PipelineRasterizationStateCreateInfo {
+            polygon_mode: match view {
+                DebugView::Wireframe => vk::PolygonMode::LINE,
+                _ => vk::PolygonMode::FILL,
+            },
+            line_width: 1.0,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::CLOCKWISE,
+            ..Default::default()
+        },
+        p_multisample_state: &vk::PipelineMultisampleStateCreateInfo {
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            ..Default::default()
+        },
+        p_depth_stencil_state: &vk::PipelineDepthStencilStateCreateInfo {
+            depth_test_enable: desc.depth_test as vk::Bool32,
+            depth_write_enable: desc.depth_test as vk::Bool32,
+            depth_compare_op: vk::CompareOp::LESS,
+            stencil_test_enable: stencil_enabled as vk::Bool32,
+            front: stencil_op_state,
+            back: stencil_op_state,
+            ..Default::default()
+        },
+        p_color_blend_state: &vk::PipelineColorBlendStateCreateInfo {
+            attachment_count: 1,
+            p_attachments: &blend_attachment_state,
+            ..Default::default()
+        },
+        p_dynamic_state: &vk::PipelineDynamicStateCreateInfo {
+            dynamic_state_count: dynamic_states.len() as u32,
+            p_dynamic_states: dynamic_states.as_ptr(),
+            ..Default::default()
+        },
+        layout: desc.layout,
+        render_pass: build.render_pass,
+        subpass: 0,
+        ..Default::default()
+    };
+
+    unsafe {
+        device
+            .create_graphics_pipelines(cache, &[pipeline_info], None)
+            .expect("Failed to create graphics pipeline")[0]
+    }
+}
@@ -0,0 +1,165 @@
+// Scene plugins: shared libraries in `plugins/` that export `vibe_plugin_entry`.
+// Everything crossing the library boundary is `#[repr(C)]`, so a plugin can be
+// built by any compiler (or Rust version) as long as it uses the same
+// PLUGIN_ABI_VERSION. examples/orbit_plugin.rs is a plugin written in Rust.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use libloading::Library;
+
+// Bumped whenever anything below changes layout or meaning
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+// Searched relative to the working directory
+pub const PLUGIN_DIR: &str = "plugins";
+const ENTRY_SYMBOL: &[u8] = b"vibe_plugin_entry\0";
+
+// What a plugin sees of the frame. Positions are in logical pixels with the
+// origin at the top-left corner and +y down.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PluginFrame {
+    pub width: f32,
+    pub height: f32,
+    // Fixed simulation steps to run this frame, each `fixed_dt` seconds long
+    pub steps: u32,
+    pub fixed_dt: f32,
+    // Wall-clock seconds since the last frame
+    pub frame_dt: f32,
+    pub has_cursor: bool,
+    pub cursor_x: f32,
+    pub cursor_y: f32,
+    pub mouse_held: bool,
+    pub mouse_pressed: bool,
+    // Drawn from the app's seeded RNG, for plugins that want reproducible runs
+    pub random: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PluginCircle {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+    pub color: [f32; 4],
+}
+
+// Called by `record_draws` once per circle, with the `sink` it was given
+pub type EmitCircle = unsafe extern "C" fn(sink: *mut c_void, circle: *const PluginCircle);
+
+// The table `vibe_plugin_entry` returns. It and `name` must stay valid while
+// the library is loaded, which is until the app exits.
+#[repr(C)]
+pub struct PluginApi {
+    pub abi_version: u32,
+    // NUL-terminated UTF-8, used with --scene and shown in the title
+    pub name: *const c_char,
+    // Returns the plugin's state for a window of `width` x `height`, or null
+    pub create: unsafe extern "C" fn(width: f32, height: f32) -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(state: *mut c_void),
+    pub update: unsafe extern "C" fn(state: *mut c_void, frame: *const PluginFrame),
+    pub record_draws: unsafe extern "C" fn(state: *const c_void, sink: *mut c_void, emit: EmitCircle),
+}
+
+pub type PluginEntry = unsafe extern "C" fn() -> *const PluginApi;
+
+pub struct Plugin {
+    name: String,
+    path: PathBuf,
+    api: &'static PluginApi,
+    // Keeps `api` alive; never unloaded
+    _library: Library,
+}
+
+// The API table is immutable after loading and plugins are only called from
+// the thread running the scene
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+impl Plugin {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn api(&self) -> &PluginApi {
+        self.api
+    }
+
+    unsafe fn load(path: &Path) -> Result<Self, String> {
+        let library = Library::new(path).map_err(|error| error.to_string())?;
+        let entry = library
+            .get::<PluginEntry>(ENTRY_SYMBOL)
+            .map_err(|error| error.to_string())?;
+        let api = entry()
+            .as_ref()
+            .ok_or("vibe_plugin_entry returned null")?;
+        if api.abi_version != PLUGIN_ABI_VERSION {
+            return Err(format!(
+                "built for plugin ABI {}, this app uses {}",
+                api.abi_version, PLUGIN_ABI_VERSION
+            ));
+        }
+        if api.name.is_null() {
+            return Err("the plugin has no name".to_string());
+        }
+        let name = CStr::from_ptr(api.name)
+            .to_str()
+            .map_err(|_| "the plugin name is not UTF-8")?
+            .to_string();
+        // The table lives as long as the library, which is never unloaded
+        let api = &*(api as *const PluginApi);
+        Ok(Self {
+            name,
+            path: path.to_path_buf(),
+            api,
+            _library: library,
+        })
+    }
+}
+
+// Plugins found in PLUGIN_DIR, loaded on first use, in file name order. A
+// library that fails to load, or whose name an earlier plugin already uses, is
+// reported and skipped.
+pub fn registry() -> &'static [Plugin] {
+    static PLUGINS: OnceLock<Vec<Plugin>> = OnceLock::new();
+    PLUGINS.get_or_init(|| discover(Path::new(PLUGIN_DIR)))
+}
+
+fn discover(dir: &Path) -> Vec<Plugin> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION)
+        })
+        .collect();
+    paths.sort();
+
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for path in paths {
+        match unsafe { Plugin::load(&path) } {
+            Ok(plugin) => {
+                if plugins.iter().any(|other| other.name() == plugin.name()) {
+                    eprintln!(
+                        "Skipping plugin {}: another plugin is already called {}",
+                        path.display(),
+                        plugin.name()
+                    );
+                    continue;
+                }
+                println!("Loaded plugin {} from {}", plugin.name(), path.display());
+                plugins.push(plugin);
+            }
+            Err(error) => eprintln!("Failed to load plugin {}: {}", path.display(), error),
+        }
+    }
+    plugins
+}
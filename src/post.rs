@@ -0,0 +1,1259 @@
+use ash::vk;
+
+use crate::device_group::{self, GroupFrame};
+use crate::gpu::GpuContext;
+use crate::hdr::{OutputEncoding, Tonemap};
+use crate::memory::MemoryCategory;
+use crate::pipeline::{PipelineDesc, PipelineManager, ShaderId};
+use crate::reflect::ShaderInterface;
+use crate::shading_rate::{self, ShadingRateMap};
+
+// Offscreen targets are linear HDR so bright pixels survive until the bloom pass
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+const MAX_POST_PASSES: u32 = 64;
+
+// One step of the post-processing chain, applied in order after the scene is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PostEffect {
+    // Separable gaussian blur of the whole image
+    GaussianBlur {
+        sigma: f32,
+    },
+    // Bright pass above `threshold`, blurred at half resolution and added back
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        sigma: f32,
+    },
+    // Persistent accumulation target: each frame keeps the brighter of the
+    // scene and the previous result scaled by `persistence` (0..1), leaving a
+    // fading trail behind moving objects
+    Trail {
+        persistence: f32,
+    },
+    // Pulls red and blue apart from the middle outwards, by `offset` pixels
+    // each way at the corners, like a cheap lens
+    ChromaticAberration {
+        offset: f32,
+    },
+    // Darkens towards the corners; a `strength` of 1 takes them to black
+    Vignette {
+        strength: f32,
+    },
+    // An old tube monitor: the image bulges by `curvature` (0 is flat) with
+    // black outside it, and dark scanlines cross it at `scanlines` (0..1)
+    Crt {
+        curvature: f32,
+        scanlines: f32,
+    },
+}
+
+// Mirrors the push constant block in shaders/post_*_frag.glsl. Only the output
+// pass declares the last two.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostPushConstants {
+    direction: [f32; 2],
+    sigma: f32,
+    threshold: f32,
+    intensity: f32,
+    tonemap: u32,
+    encoding: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PassKind {
+    Copy,
+    Blur,
+    Threshold,
+    Composite,
+    Trail,
+    ChromaticAberration,
+    Vignette,
+    Crt,
+    // Into the swapchain, tonemapped or encoded for HDR
+    Output,
+}
+
+impl PassKind {
+    const ALL: [PassKind; 9] = [
+        PassKind::Copy,
+        PassKind::Blur,
+        PassKind::Threshold,
+        PassKind::Composite,
+        PassKind::Trail,
+        PassKind::ChromaticAberration,
+        PassKind::Vignette,
+        PassKind::Crt,
+        PassKind::Output,
+    ];
+}
+
+// A pass before it has a descriptor set: its kind, the two targets it samples,
+// where it writes (the swapchain if None) and its push constants
+type PassPlan = (PassKind, [Target; 2], Option<Target>, PostPushConstants);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Scene,
+    Full(usize),
+    Half(usize),
+    // Survives between frames; only written at the end of a Trail effect
+    History,
+}
+
+impl Target {
+    // A full-resolution scratch target that is not `self`
+    fn other_full(self) -> Target {
+        match self {
+            Target::Full(0) => Target::Full(1),
+            _ => Target::Full(0),
+        }
+    }
+}
+
+// A fullscreen pass writing `output` (the swapchain if None); its inputs are
+// baked into the descriptor set
+#[derive(Clone, Copy)]
+struct PostPass {
+    kind: PassKind,
+    output: Option<Target>,
+    push_constants: PostPushConstants,
+    descriptor_set: vk::DescriptorSet,
+}
+
+// Formats tried for the scene's stencil attachment, in order of preference.
+// Drivers only have to support one of the combined depth/stencil formats.
+const STENCIL_FORMATS: [vk::Format; 3] = [
+    vk::Format::S8_UINT,
+    vk::Format::D24_UNORM_S8_UINT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+];
+
+fn find_stencil_format(gpu: &GpuContext) -> vk::Format {
+    STENCIL_FORMATS
+        .into_iter()
+        .find(|&format| {
+            let properties = unsafe {
+                gpu.instance
+                    .get_physical_device_format_properties(gpu.physical_device, format)
+            };
+            properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .expect("No supported stencil format")
+}
+
+// A single-level 2D image with its memory and a view of it
+struct Attachment {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+impl Attachment {
+    fn new(
+        gpu: &GpuContext,
+        name: &str,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+        extent: vk::Extent2D,
+    ) -> Self {
+        let device = &gpu.device;
+        let image = unsafe {
+            device
+                .create_image(
+                    &vk::ImageCreateInfo {
+                        image_type: vk::ImageType::TYPE_2D,
+                        format,
+                        extent: vk::Extent3D {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                        mip_levels: 1,
+                        array_layers: 1,
+                        samples: vk::SampleCountFlags::TYPE_1,
+                        tiling: vk::ImageTiling::OPTIMAL,
+                        usage,
+                        sharing_mode: vk::SharingMode::EXCLUSIVE,
+                        initial_layout: vk::ImageLayout::UNDEFINED,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create render target image")
+        };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory = gpu.allocate_memory(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Images,
+        );
+        unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Failed to bind render target memory");
+        }
+        let view = unsafe {
+            device
+                .create_image_view(
+                    &vk::ImageViewCreateInfo {
+                        image,
+                        view_type: vk::ImageViewType::TYPE_2D,
+                        format,
+                        components: vk::ComponentMapping::default(),
+                        subresource_range: vk::ImageSubresourceRange {
+                            aspect_mask,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        },
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create render target view")
+        };
+        gpu.set_name(image, name);
+        gpu.set_name(view, &format!("{} view", name));
+        Self {
+            image,
+            memory,
+            view,
+        }
+    }
+
+    fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device.destroy_image_view(self.view, None);
+            gpu.device.destroy_image(self.image, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+}
+
+struct RenderTarget {
+    color: Attachment,
+    // Only the scene target has one, for stencil clipping
+    stencil: Option<Attachment>,
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+}
+
+impl RenderTarget {
+    // `stencil_format` adds a stencil attachment after the color one, and
+    // `shading_rate_view` one more after that; the render pass must have them too
+    fn new(
+        gpu: &GpuContext,
+        name: &str,
+        render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+        stencil_format: Option<vk::Format>,
+        shading_rate_view: Option<vk::ImageView>,
+    ) -> Self {
+        let color = Attachment::new(
+            gpu,
+            name,
+            HDR_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::COLOR,
+            extent,
+        );
+        let stencil = stencil_format.map(|format| {
+            // Views of combined formats used as attachments need both aspects
+            let aspect_mask = if format == vk::Format::S8_UINT {
+                vk::ImageAspectFlags::STENCIL
+            } else {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            };
+            Attachment::new(
+                gpu,
+                &format!("{} stencil", name),
+                format,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                aspect_mask,
+                extent,
+            )
+        });
+        let attachments: Vec<vk::ImageView> = std::iter::once(&color)
+            .chain(stencil.as_ref())
+            .map(|attachment| attachment.view)
+            .chain(shading_rate_view)
+            .collect();
+        let framebuffer = unsafe {
+            gpu.device
+                .create_framebuffer(
+                    &vk::FramebufferCreateInfo {
+                        render_pass,
+                        attachment_count: attachments.len() as u32,
+                        p_attachments: attachments.as_ptr(),
+                        width: extent.width,
+                        height: extent.height,
+                        layers: 1,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create render target framebuffer")
+        };
+        gpu.set_name(framebuffer, &format!("{} framebuffer", name));
+        Self {
+            color,
+            stencil,
+            framebuffer,
+            extent,
+        }
+    }
+
+    fn destroy(&self, gpu: &GpuContext) {
+        unsafe { gpu.device.destroy_framebuffer(self.framebuffer, None) };
+        self.color.destroy(gpu);
+        if let Some(stencil) = &self.stencil {
+            stencil.destroy(gpu);
+        }
+    }
+}
+
+// Shader programs for each pass kind, registered with one pipeline manager
+struct PostShaders {
+    copy: ShaderId,
+    blur: ShaderId,
+    threshold: ShaderId,
+    composite: ShaderId,
+    trail: ShaderId,
+    chromatic_aberration: ShaderId,
+    vignette: ShaderId,
+    crt: ShaderId,
+    output: ShaderId,
+}
+
+impl PostShaders {
+    fn register(gpu: &GpuContext, pipelines: &mut PipelineManager) -> Self {
+        let vertex = include_bytes!("../shaders/fullscreen_vert.spv");
+        Self {
+            copy: pipelines.register_shaders(
+                gpu,
+                "post copy",
+                vertex,
+                include_bytes!("../shaders/post_copy_frag.spv"),
+            ),
+            blur: pipelines.register_shaders(
+                gpu,
+                "post blur",
+                vertex,
+                include_bytes!("../shaders/post_blur_frag.spv"),
+            ),
+            threshold: pipelines.register_shaders(
+                gpu,
+                "post threshold",
+                vertex,
+                include_bytes!("../shaders/post_threshold_frag.spv"),
+            ),
+            composite: pipelines.register_shaders(
+                gpu,
+                "post composite",
+                vertex,
+                include_bytes!("../shaders/post_composite_frag.spv"),
+            ),
+            trail: pipelines.register_shaders(
+                gpu,
+                "post trail",
+                vertex,
+                include_bytes!("../shaders/post_trail_frag.spv"),
+            ),
+            chromatic_aberration: pipelines.register_shaders(
+                gpu,
+                "post chromatic aberration",
+                vertex,
+                include_bytes!("../shaders/post_chromatic_frag.spv"),
+            ),
+            vignette: pipelines.register_shaders(
+                gpu,
+                "post vignette",
+                vertex,
+                include_bytes!("../shaders/post_vignette_frag.spv"),
+            ),
+            crt: pipelines.register_shaders(
+                gpu,
+                "post crt",
+                vertex,
+                include_bytes!("../shaders/post_crt_frag.spv"),
+            ),
+            output: pipelines.register_shaders(
+                gpu,
+                "post output",
+                vertex,
+                include_bytes!("../shaders/post_output_frag.spv"),
+            ),
+        }
+    }
+
+    fn get(&self, kind: PassKind) -> ShaderId {
+        match kind {
+            PassKind::Copy => self.copy,
+            PassKind::Blur => self.blur,
+            PassKind::Threshold => self.threshold,
+            PassKind::Composite => self.composite,
+            PassKind::Trail => self.trail,
+            PassKind::ChromaticAberration => self.chromatic_aberration,
+            PassKind::Vignette => self.vignette,
+            PassKind::Crt => self.crt,
+            PassKind::Output => self.output,
+        }
+    }
+}
+
+// Where a pass draws: a framebuffer, its extent and the part of it the image
+// covers. The rest is left cleared, which letterboxes the swapchain.
+#[derive(Clone, Copy)]
+pub struct PassOutput {
+    pub framebuffer: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+    pub viewport: vk::Rect2D,
+}
+
+impl PassOutput {
+    // Covering the whole framebuffer
+    pub fn full(framebuffer: vk::Framebuffer, extent: vk::Extent2D) -> Self {
+        Self {
+            framebuffer,
+            extent,
+            viewport: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+        }
+    }
+}
+
+// Owns the offscreen HDR scene target and runs the post effect chain from it
+// into the swapchain. The scene is drawn with `scene_render_pass`, which also
+// clears a stencil attachment; the final pass of the chain always lands in the
+// caller's present render pass.
+pub struct PostProcessor {
+    scene_render_pass: vk::RenderPass,
+    post_render_pass: vk::RenderPass,
+    // Color-only and clearing, for starting the history target off black
+    clear_render_pass: vk::RenderPass,
+    stencil_format: vk::Format,
+    // Attached to the scene pass with `capabilities.fragment_shading_rate`
+    shading_rate: Option<ShadingRateMap>,
+    scene: RenderTarget,
+    full: [RenderTarget; 2],
+    half: [RenderTarget; 2],
+    history: RenderTarget,
+    // False until the history target has been cleared to black
+    history_valid: bool,
+    sampler: vk::Sampler,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    pipeline_layout: vk::PipelineLayout,
+    offscreen_pipelines: PipelineManager,
+    offscreen_shaders: PostShaders,
+    present_pipelines: PipelineManager,
+    present_shaders: PostShaders,
+    effects: Vec<PostEffect>,
+    passes: Vec<PostPass>,
+    // How the output pass writes the swapchain
+    encoding: OutputEncoding,
+    tonemap: Tonemap,
+}
+
+impl PostProcessor {
+    pub fn new(
+        gpu: &GpuContext,
+        present_render_pass: vk::RenderPass,
+        extent: vk::Extent2D,
+    ) -> Self {
+        let device = &gpu.device;
+        let stencil_format = find_stencil_format(gpu);
+        println!("Scene stencil format: {:?}", stencil_format);
+        let shading_rate = gpu
+            .capabilities
+            .fragment_shading_rate
+            .then(|| ShadingRateMap::new(gpu, extent));
+        let scene_render_pass = Self::create_render_pass(
+            gpu,
+            vk::AttachmentLoadOp::CLEAR,
+            Some(stencil_format),
+            shading_rate.as_ref(),
+        );
+        let post_render_pass =
+            Self::create_render_pass(gpu, vk::AttachmentLoadOp::DONT_CARE, None, None);
+        let clear_render_pass =
+            Self::create_render_pass(gpu, vk::AttachmentLoadOp::CLEAR, None, None);
+
+        let sampler = unsafe {
+            device
+                .create_sampler(
+                    &vk::SamplerCreateInfo {
+                        mag_filter: vk::Filter::LINEAR,
+                        min_filter: vk::Filter::LINEAR,
+                        mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+                        address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create post sampler")
+        };
+
+        let mut offscreen_pipelines = PipelineManager::new(post_render_pass);
+        let offscreen_shaders = PostShaders::register(gpu, &mut offscreen_pipelines);
+        let mut present_pipelines = PipelineManager::new(present_render_pass);
+        let present_shaders = PostShaders::register(gpu, &mut present_pipelines);
+
+        // Every post pass binds the same kind of set, so one layout covers them all
+        let interface = PassKind::ALL
+            .iter()
+            .map(|&kind| offscreen_pipelines.interface(offscreen_shaders.get(kind)))
+            .fold(ShaderInterface::default(), |shared, interface| {
+                shared.merge(interface)
+            });
+        let set_layouts = interface.create_set_layouts(gpu);
+        let descriptor_set_layout = set_layouts[0];
+        let pool_sizes = interface.pool_sizes(MAX_POST_PASSES);
+        let descriptor_pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        max_sets: MAX_POST_PASSES,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create post descriptor pool")
+        };
+
+        let pipeline_layout =
+            interface.create_pipeline_layout(gpu, &set_layouts, size_of::<PostPushConstants>());
+
+        let (scene, full, half, history) = Self::create_targets(
+            gpu,
+            scene_render_pass,
+            post_render_pass,
+            stencil_format,
+            shading_rate.as_ref(),
+            extent,
+        );
+        let mut post = Self {
+            scene_render_pass,
+            post_render_pass,
+            clear_render_pass,
+            stencil_format,
+            shading_rate,
+            scene,
+            full,
+            half,
+            history,
+            history_valid: false,
+            sampler,
+            descriptor_set_layout,
+            descriptor_pool,
+            pipeline_layout,
+            offscreen_pipelines,
+            offscreen_shaders,
+            present_pipelines,
+            present_shaders,
+            effects: Vec::new(),
+            passes: Vec::new(),
+            encoding: OutputEncoding::Sdr,
+            tonemap: Tonemap::None,
+        };
+        post.rebuild_passes(gpu);
+        println!(
+            "Post processor created at {}x{}",
+            extent.width, extent.height
+        );
+        post
+    }
+
+    // HDR pass whose color result is left ready for sampling by the next pass.
+    // With `stencil_format` it also has a stencil attachment, cleared to zero
+    // and thrown away at the end, and with `shading_rate` its image as well.
+    fn create_render_pass(
+        gpu: &GpuContext,
+        load_op: vk::AttachmentLoadOp,
+        stencil_format: Option<vk::Format>,
+        shading_rate: Option<&ShadingRateMap>,
+    ) -> vk::RenderPass {
+        let mut attachments = vec![vk::AttachmentDescription {
+            format: HDR_FORMAT,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op,
+            store_op: vk::AttachmentStoreOp::STORE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..Default::default()
+        }];
+        if let Some(format) = stencil_format {
+            attachments.push(vk::AttachmentDescription {
+                format,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::DONT_CARE,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                stencil_load_op: vk::AttachmentLoadOp::CLEAR,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            });
+        }
+        let color_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let stencil_attachment_ref = vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+        let subpass = vk::SubpassDescription {
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            color_attachment_count: 1,
+            p_color_attachments: &color_attachment_ref,
+            p_depth_stencil_attachment: match stencil_format {
+                Some(_) => &stencil_attachment_ref,
+                None => std::ptr::null(),
+            },
+            ..Default::default()
+        };
+        // Earlier passes may still be sampling the target we are about to overwrite,
+        // and later passes sample what we write. Last frame's stencil tests must
+        // also be done before the stencil is cleared.
+        let (test_stages, test_access) = match stencil_format {
+            Some(_) => (
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ),
+            None => (vk::PipelineStageFlags::empty(), vk::AccessFlags::empty()),
+        };
+        let dependencies = [
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER | test_stages,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | test_stages,
+                src_access_mask: vk::AccessFlags::SHADER_READ | test_access,
+                dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE | test_access,
+                ..Default::default()
+            },
+            vk::SubpassDependency {
+                src_subpass: 0,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                ..Default::default()
+            },
+        ];
+        if let Some(shading_rate) = shading_rate {
+            return shading_rate::create_render_pass(
+                gpu,
+                &attachments,
+                color_attachment_ref,
+                stencil_format.map(|_| stencil_attachment_ref),
+                &dependencies,
+                shading_rate.texel_size(),
+            );
+        }
+        unsafe {
+            gpu.device
+                .create_render_pass(
+                    &vk::RenderPassCreateInfo {
+                        attachment_count: attachments.len() as u32,
+                        p_attachments: attachments.as_ptr(),
+                        subpass_count: 1,
+                        p_subpasses: &subpass,
+                        dependency_count: dependencies.len() as u32,
+                        p_dependencies: dependencies.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create post render pass")
+        }
+    }
+
+    fn create_targets(
+        gpu: &GpuContext,
+        scene_render_pass: vk::RenderPass,
+        post_render_pass: vk::RenderPass,
+        stencil_format: vk::Format,
+        shading_rate: Option<&ShadingRateMap>,
+        extent: vk::Extent2D,
+    ) -> (
+        RenderTarget,
+        [RenderTarget; 2],
+        [RenderTarget; 2],
+        RenderTarget,
+    ) {
+        let half_extent = vk::Extent2D {
+            width: (extent.width / 2).max(1),
+            height: (extent.height / 2).max(1),
+        };
+        (
+            RenderTarget::new(
+                gpu,
+                "scene target",
+                scene_render_pass,
+                extent,
+                Some(stencil_format),
+                shading_rate.map(ShadingRateMap::view),
+            ),
+            [0, 1].map(|i| {
+                let name = format!("full-size target {}", i);
+                RenderTarget::new(gpu, &name, post_render_pass, extent, None, None)
+            }),
+            [0, 1].map(|i| {
+                let name = format!("half-size target {}", i);
+                RenderTarget::new(gpu, &name, post_render_pass, half_extent, None, None)
+            }),
+            RenderTarget::new(gpu, "history target", post_render_pass, extent, None, None),
+        )
+    }
+
+    // Render pass every scene pipeline must be built against
+    pub fn scene_render_pass(&self) -> vk::RenderPass {
+        self.scene_render_pass
+    }
+
+    pub fn shading_rate(&self) -> Option<&ShadingRateMap> {
+        self.shading_rate.as_ref()
+    }
+
+    pub fn shading_rate_mut(&mut self) -> Option<&mut ShadingRateMap> {
+        self.shading_rate.as_mut()
+    }
+
+    pub fn scene_framebuffer(&self) -> vk::Framebuffer {
+        self.scene.framebuffer
+    }
+
+    // View of the scene target, for passes that draw into it with their own
+    // render pass
+    pub fn scene_view(&self) -> vk::ImageView {
+        self.scene.color.view
+    }
+
+    pub fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+
+    // A chain of the same effects in the same order only has its push constants
+    // updated, so sliders can change parameters every frame without waiting
+    // for the device
+    pub fn set_effects(&mut self, gpu: &GpuContext, effects: Vec<PostEffect>) {
+        let same_chain = effects.len() == self.effects.len()
+            && effects
+                .iter()
+                .zip(&self.effects)
+                .all(|(new, old)| std::mem::discriminant(new) == std::mem::discriminant(old));
+        self.effects = effects;
+        if same_chain {
+            let plan = self.plan_passes();
+            for (pass, (.., push_constants)) in self.passes.iter_mut().zip(plan) {
+                pass.push_constants = push_constants;
+            }
+            return;
+        }
+        println!("Post effects set to {:?}", self.effects);
+        self.history_valid = false;
+        unsafe {
+            gpu.device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        self.rebuild_passes(gpu);
+    }
+
+    pub fn encoding(&self) -> OutputEncoding {
+        self.encoding
+    }
+
+    pub fn tonemap(&self) -> Tonemap {
+        self.tonemap
+    }
+
+    // Sets how the last pass writes the swapchain: `encoding` must match its
+    // color space, and `tonemap` only applies to SDR. Takes effect from the
+    // next recorded frame.
+    pub fn set_output(&mut self, encoding: OutputEncoding, tonemap: Tonemap) {
+        self.encoding = encoding;
+        self.tonemap = tonemap;
+    }
+
+    // Draws the last pass into `render_pass` from now on, after the swapchain
+    // render pass was made again for another format. Its pipelines are rebuilt
+    // when next used. Caller ensures the device is idle.
+    pub fn set_present_render_pass(&mut self, gpu: &GpuContext, render_pass: vk::RenderPass) {
+        self.present_pipelines
+            .set_render_pass(&gpu.device, render_pass);
+    }
+
+    // Push constants of the output pass, which change without a rebuild
+    fn output_constants(&self) -> PostPushConstants {
+        PostPushConstants {
+            intensity: self.encoding.white_scale(),
+            tonemap: self.tonemap.shader_value(),
+            encoding: self.encoding.shader_value(),
+            ..Default::default()
+        }
+    }
+
+    // Call after the swapchain has been recreated (device already idle)
+    pub fn resize(&mut self, gpu: &GpuContext, extent: vk::Extent2D) {
+        for target in self.targets() {
+            target.destroy(gpu);
+        }
+        if let Some(shading_rate) = self.shading_rate.as_mut() {
+            shading_rate.resize(gpu, extent);
+        }
+        (self.scene, self.full, self.half, self.history) = Self::create_targets(
+            gpu,
+            self.scene_render_pass,
+            self.post_render_pass,
+            self.stencil_format,
+            self.shading_rate.as_ref(),
+            extent,
+        );
+        self.history_valid = false;
+        self.rebuild_passes(gpu);
+    }
+
+    // Frees every target, pipeline and render pass. Caller ensures the device
+    // is idle.
+    pub fn destroy(&mut self, gpu: &GpuContext) {
+        for target in self.targets() {
+            target.destroy(gpu);
+        }
+        if let Some(shading_rate) = self.shading_rate.take() {
+            shading_rate.destroy(gpu);
+        }
+        self.offscreen_pipelines.destroy(&gpu.device);
+        self.present_pipelines.destroy(&gpu.device);
+        self.passes.clear();
+        unsafe {
+            let device = &gpu.device;
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_render_pass(self.scene_render_pass, None);
+            device.destroy_render_pass(self.post_render_pass, None);
+            device.destroy_render_pass(self.clear_render_pass, None);
+        }
+        println!("Post processor destroyed");
+    }
+
+    fn targets(&self) -> impl Iterator<Item = &RenderTarget> {
+        std::iter::once(&self.scene)
+            .chain(self.full.iter())
+            .chain(self.half.iter())
+            .chain(std::iter::once(&self.history))
+    }
+
+    fn target(&self, target: Target) -> &RenderTarget {
+        match target {
+            Target::Scene => &self.scene,
+            Target::Full(index) => &self.full[index],
+            Target::Half(index) => &self.half[index],
+            Target::History => &self.history,
+        }
+    }
+
+    // Expands the effect chain into fullscreen passes, ending with the output
+    // pass into the swapchain
+    fn plan_passes(&self) -> Vec<PassPlan> {
+        let mut passes = Vec::new();
+        let mut pass = |kind, inputs, output: Target, push_constants| {
+            passes.push((kind, inputs, Some(output), push_constants));
+        };
+        let texel = |target: &RenderTarget| {
+            [
+                1.0 / target.extent.width as f32,
+                1.0 / target.extent.height as f32,
+            ]
+        };
+        let full_texel = texel(&self.scene);
+        let half_texel = texel(&self.half[0]);
+
+        let mut current = Target::Scene;
+        for effect in &self.effects {
+            match *effect {
+                PostEffect::GaussianBlur { sigma } => {
+                    let horizontal = current.other_full();
+                    let vertical = horizontal.other_full();
+                    pass(
+                        PassKind::Blur,
+                        [current, current],
+                        horizontal,
+                        PostPushConstants {
+                            direction: [full_texel[0], 0.0],
+                            sigma,
+                            ..Default::default()
+                        },
+                    );
+                    pass(
+                        PassKind::Blur,
+                        [horizontal, horizontal],
+                        vertical,
+                        PostPushConstants {
+                            direction: [0.0, full_texel[1]],
+                            sigma,
+                            ..Default::default()
+                        },
+                    );
+                    current = vertical;
+                }
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                    sigma,
+                } => {
+                    pass(
+                        PassKind::Threshold,
+                        [current, current],
+                        Target::Half(0),
+                        PostPushConstants {
+                            threshold,
+                            ..Default::default()
+                        },
+                    );
+                    pass(
+                        PassKind::Blur,
+                        [Target::Half(0), Target::Half(0)],
+                        Target::Half(1),
+                        PostPushConstants {
+                            direction: [half_texel[0], 0.0],
+                            sigma,
+                            ..Default::default()
+                        },
+                    );
+                    pass(
+                        PassKind::Blur,
+                        [Target::Half(1), Target::Half(1)],
+                        Target::Half(0),
+                        PostPushConstants {
+                            direction: [0.0, half_texel[1]],
+                            sigma,
+                            ..Default::default()
+                        },
+                    );
+                    let composite = current.other_full();
+                    pass(
+                        PassKind::Composite,
+                        [current, Target::Half(0)],
+                        composite,
+                        PostPushConstants {
+                            intensity,
+                            ..Default::default()
+                        },
+                    );
+                    current = composite;
+                }
+                PostEffect::Trail { persistence } => {
+                    // A pass can't sample the target it writes, so accumulate into
+                    // scratch and copy the result back into the history
+                    let accumulated = current.other_full();
+                    pass(
+                        PassKind::Trail,
+                        [current, Target::History],
+                        accumulated,
+                        PostPushConstants {
+                            intensity: persistence,
+                            ..Default::default()
+                        },
+                    );
+                    pass(
+                        PassKind::Copy,
+                        [accumulated, accumulated],
+                        Target::History,
+                        PostPushConstants::default(),
+                    );
+                    current = accumulated;
+                }
+                PostEffect::ChromaticAberration { offset } => {
+                    let output = current.other_full();
+                    pass(
+                        PassKind::ChromaticAberration,
+                        [current, current],
+                        output,
+                        PostPushConstants {
+                            direction: full_texel,
+                            intensity: offset,
+                            ..Default::default()
+                        },
+                    );
+                    current = output;
+                }
+                PostEffect::Vignette { strength } => {
+                    let output = current.other_full();
+                    pass(
+                        PassKind::Vignette,
+                        [current, current],
+                        output,
+                        PostPushConstants {
+                            intensity: strength,
+                            ..Default::default()
+                        },
+                    );
+                    current = output;
+                }
+                PostEffect::Crt {
+                    curvature,
+                    scanlines,
+                } => {
+                    let output = current.other_full();
+                    pass(
+                        PassKind::Crt,
+                        [current, current],
+                        output,
+                        PostPushConstants {
+                            direction: full_texel,
+                            sigma: curvature,
+                            intensity: scanlines,
+                            ..Default::default()
+                        },
+                    );
+                    current = output;
+                }
+            }
+        }
+        passes.push((
+            PassKind::Output,
+            [current, current],
+            None,
+            PostPushConstants::default(),
+        ));
+        passes
+    }
+
+    // Replaces the passes with a fresh plan and writes one descriptor set per pass
+    fn rebuild_passes(&mut self, gpu: &GpuContext) {
+        let passes = self.plan_passes();
+        assert!(
+            passes.len() as u32 <= MAX_POST_PASSES,
+            "Post effect chain needs {} passes, limit is {}",
+            passes.len(),
+            MAX_POST_PASSES
+        );
+
+        let device = &gpu.device;
+        let set_layouts = vec![self.descriptor_set_layout; passes.len()];
+        let descriptor_sets = unsafe {
+            device
+                .reset_descriptor_pool(self.descriptor_pool, vk::DescriptorPoolResetFlags::empty())
+                .expect("Failed to reset post descriptor pool");
+            device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool: self.descriptor_pool,
+                    descriptor_set_count: set_layouts.len() as u32,
+                    p_set_layouts: set_layouts.as_ptr(),
+                    ..Default::default()
+                })
+                .expect("Failed to allocate post descriptor sets")
+        };
+
+        self.passes = passes
+            .into_iter()
+            .zip(descriptor_sets)
+            .map(|((kind, inputs, output, push_constants), descriptor_set)| {
+                let image_info = |target| vk::DescriptorImageInfo {
+                    image_view: self.target(target).color.view,
+                    image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    ..Default::default()
+                };
+                let first = image_info(inputs[0]);
+                let second = image_info(inputs[1]);
+                let sampler = vk::DescriptorImageInfo {
+                    sampler: self.sampler,
+                    ..Default::default()
+                };
+                let writes = [
+                    (0, vk::DescriptorType::SAMPLED_IMAGE, &first),
+                    (1, vk::DescriptorType::SAMPLER, &sampler),
+                    (2, vk::DescriptorType::SAMPLED_IMAGE, &second),
+                ]
+                .map(|(binding, descriptor_type, info)| vk::WriteDescriptorSet {
+                    dst_set: descriptor_set,
+                    dst_binding: binding,
+                    descriptor_count: 1,
+                    descriptor_type,
+                    p_image_info: info,
+                    ..Default::default()
+                });
+                unsafe { device.update_descriptor_sets(&writes, &[]) };
+                PostPass {
+                    kind,
+                    output,
+                    push_constants,
+                    descriptor_set,
+                }
+            })
+            .collect();
+    }
+
+    // Records the whole chain. The final pass runs once per `presents` entry
+    // (a framebuffer of the present render pass), so several swapchains can show
+    // the same result. Must be called outside any render
+    // pass, after the scene pass has ended. On a device group it renders on the
+    // GPUs of `group`.
+    pub fn record(
+        &mut self,
+        gpu: &GpuContext,
+        command_buffer: vk::CommandBuffer,
+        presents: &[PassOutput],
+        group: Option<&GroupFrame>,
+    ) {
+        if !self.history_valid {
+            self.clear_history(&gpu.device, command_buffer);
+        }
+        for pass in &self.passes {
+            match pass.output {
+                Some(output) => {
+                    let target = self.target(output);
+                    let output = PassOutput::full(target.framebuffer, target.extent);
+                    let desc = PipelineDesc::new(
+                        self.offscreen_shaders.get(pass.kind),
+                        self.pipeline_layout,
+                    );
+                    let pipeline = self.offscreen_pipelines.get(gpu, &desc);
+                    self.record_pass(
+                        &gpu.device,
+                        command_buffer,
+                        pass,
+                        pipeline,
+                        self.post_render_pass,
+                        output,
+                        group,
+                    );
+                }
+                None => {
+                    let desc = PipelineDesc::new(
+                        self.present_shaders.get(pass.kind),
+                        self.pipeline_layout,
+                    );
+                    let pipeline = self.present_pipelines.get(gpu, &desc);
+                    let pass = PostPass {
+                        push_constants: self.output_constants(),
+                        ..*pass
+                    };
+                    for &present in presents {
+                        self.record_pass(
+                            &gpu.device,
+                            command_buffer,
+                            &pass,
+                            pipeline,
+                            self.present_pipelines.render_pass(),
+                            present,
+                            group,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // One fullscreen draw of `pass` into `output`, a framebuffer of `render_pass`.
+    // In a split-screen device group frame each GPU draws its strip; the present
+    // pass still clears all of every GPU's image instance.
+    #[allow(clippy::too_many_arguments)]
+    fn record_pass(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        pass: &PostPass,
+        pipeline: vk::Pipeline,
+        render_pass: vk::RenderPass,
+        output: PassOutput,
+        group: Option<&GroupFrame>,
+    ) {
+        let viewport = output.viewport;
+        let present = render_pass != self.post_render_pass;
+        unsafe {
+            device_group::begin_render_pass(
+                device,
+                command_buffer,
+                vk::RenderPassBeginInfo {
+                    render_pass,
+                    framebuffer: output.framebuffer,
+                    render_area: vk::Rect2D {
+                        offset: vk::Offset2D { x: 0, y: 0 },
+                        extent: output.extent,
+                    },
+                    clear_value_count: 1,
+                    p_clear_values: &vk::ClearValue::default(),
+                    ..Default::default()
+                },
+                vk::SubpassContents::INLINE,
+                group,
+                !present,
+            );
+            device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: viewport.offset.x as f32,
+                    y: viewport.offset.y as f32,
+                    width: viewport.extent.width as f32,
+                    height: viewport.extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            device_group::set_scissor(device, command_buffer, viewport, output.extent, group);
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout,
+                0,
+                &[pass.descriptor_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&pass.push_constants),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    // The clear pass is compatible with the history target and clears on load;
+    // beginning and ending it leaves the history black and ready for sampling
+    fn clear_history(&mut self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &vk::RenderPassBeginInfo {
+                    render_pass: self.clear_render_pass,
+                    framebuffer: self.history.framebuffer,
+                    render_area: vk::Rect2D {
+                        offset: vk::Offset2D { x: 0, y: 0 },
+                        extent: self.history.extent,
+                    },
+                    clear_value_count: 1,
+                    p_clear_values: &vk::ClearValue::default(),
+                    ..Default::default()
+                },
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_end_render_pass(command_buffer);
+        }
+        self.history_valid = true;
+    }
+}
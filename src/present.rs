@@ -0,0 +1,109 @@
+use ash::vk;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::gpu::GpuContext;
+use crate::window::WindowContext;
+
+// Presents left queued for the display when a frame starts. One keeps the GPU
+// busy while holding the CPU at most a frame ahead of what is on screen.
+const PRESENTS_AHEAD: usize = 1;
+// Longest a frame waits for a present. A hidden or occluded window may never
+// show its images, so the frame goes ahead anyway.
+const PRESENT_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+// Weight of the newest measurement in the running averages
+const SMOOTHING: f64 = 0.1;
+
+// Measured on the primary window with `capabilities.present_wait`
+#[derive(Clone, Copy, Debug)]
+pub struct PresentTiming {
+    // Between one present reaching the display and the next
+    pub interval: Duration,
+    // From queueing a present to it reaching the display
+    pub latency: Duration,
+}
+
+// Holds each frame back until the primary window's presents have caught up, so
+// input is read as late as possible, and times the presents as they reach the
+// display. Every present is tagged with the frame number as its present ID.
+pub struct PresentPacer {
+    // Presents that may not have reached the display yet, as (present ID, when
+    // it was queued), oldest first
+    queued: VecDeque<(u64, Instant)>,
+    // The swapchain they went to; IDs don't carry over to a new one
+    swapchain: vk::SwapchainKHR,
+    // The latest present seen on the display
+    displayed: Option<(u64, Instant)>,
+    timing: Option<PresentTiming>,
+}
+
+impl PresentPacer {
+    pub fn new() -> Self {
+        Self {
+            queued: VecDeque::new(),
+            swapchain: vk::SwapchainKHR::null(),
+            displayed: None,
+            timing: None,
+        }
+    }
+
+    // Notes that `primary` was just presented with `present_id`
+    pub fn presented(&mut self, primary: &WindowContext, present_id: u64) {
+        if primary.swapchain() != self.swapchain {
+            self.queued.clear();
+            self.displayed = None;
+            self.swapchain = primary.swapchain();
+        }
+        self.queued.push_back((present_id, Instant::now()));
+    }
+
+    // Blocks until no more than `PRESENTS_AHEAD` of `primary`'s presents are
+    // still waiting for the display. Does nothing without
+    // `capabilities.present_wait`.
+    pub fn wait(&mut self, gpu: &GpuContext, primary: &WindowContext) {
+        if !gpu.capabilities.present_wait || primary.swapchain() != self.swapchain {
+            return;
+        }
+        // Waiting for one present covers every one before it
+        let mut target = None;
+        while self.queued.len() > PRESENTS_AHEAD {
+            target = self.queued.pop_front();
+        }
+        let Some((present_id, queued_at)) = target else {
+            return;
+        };
+        if !primary.wait_for_present(gpu, present_id, PRESENT_WAIT_TIMEOUT) {
+            self.displayed = None;
+            return;
+        }
+        let now = Instant::now();
+        let latency = now - queued_at;
+        let interval = self.displayed.map(|(displayed_id, displayed_at)| {
+            (now - displayed_at).div_f64((present_id - displayed_id) as f64)
+        });
+        self.displayed = Some((present_id, now));
+        let smooth = |average: Duration, sample: Duration| {
+            average.mul_f64(1.0 - SMOOTHING) + sample.mul_f64(SMOOTHING)
+        };
+        self.timing = match (self.timing, interval) {
+            (Some(timing), Some(interval)) => Some(PresentTiming {
+                interval: smooth(timing.interval, interval),
+                latency: smooth(timing.latency, latency),
+            }),
+            (None, Some(interval)) => Some(PresentTiming { interval, latency }),
+            (timing, None) => timing,
+        };
+    }
+
+    // Running averages over the presents waited for so far. None until two in
+    // a row have reached the display.
+    pub fn timing(&self) -> Option<PresentTiming> {
+        self.timing
+    }
+}
+
+impl Default for PresentPacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
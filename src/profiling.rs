@@ -0,0 +1,311 @@
+// Tracy instrumentation, compiled in with the `profiling` feature. Without it
+// `zone!` expands to nothing and `GpuProfiler` records no queries, so call sites
+// don't need their own cfg.
+
+// Times the rest of the enclosing block as a named CPU zone
+#[macro_export]
+macro_rules! zone {
+    ($name:literal) => {
+        #[cfg(feature = "profiling")]
+        let _zone = $crate::profiling::tracy_client::span!($name);
+    };
+}
+
+#[cfg(feature = "profiling")]
+pub use tracy::{frame_mark, start, GpuProfiler, GpuZone};
+#[cfg(feature = "profiling")]
+pub use tracy_client;
+
+#[cfg(not(feature = "profiling"))]
+pub use disabled::{frame_mark, start, GpuProfiler, GpuZone};
+
+#[cfg(feature = "profiling")]
+mod tracy {
+    use ash::vk;
+    use tracy_client::{Client, GpuContextType, GpuSpan};
+
+    use crate::frame_ring::FRAMES_IN_FLIGHT;
+    use crate::gpu::GpuContext;
+
+    // GPU zones one frame can record; later ones are dropped
+    const MAX_ZONES_PER_FRAME: u32 = 16;
+    const QUERIES_PER_FRAME: u32 = MAX_ZONES_PER_FRAME * 2;
+
+    // Connects to the Tracy profiler; must run before the first zone
+    pub fn start() {
+        Client::start();
+        println!("Tracy client started");
+    }
+
+    pub fn frame_mark() {
+        tracy_client::frame_mark();
+    }
+
+    // A zone opened with `GpuProfiler::begin_zone`. Empty when the frame had
+    // run out of queries.
+    #[derive(Clone, Copy)]
+    pub struct GpuZone(Option<usize>);
+
+    // The queries and zones of one frame in flight
+    #[derive(Default)]
+    struct FrameZones {
+        spans: Vec<GpuSpan>,
+        // Query of each timestamp in recording order, with its span and
+        // whether it ends it
+        timestamps: Vec<(u32, usize, bool)>,
+        next_query: u32,
+    }
+
+    // GPU zones on the graphics queue, measured with timestamp queries. Each
+    // frame in flight writes its own range of the query pool; the results are
+    // read back and handed to Tracy when the range is reused.
+    pub struct GpuProfiler {
+        // None when the queue has no timestamps
+        context: Option<tracy_client::GpuContext>,
+        query_pool: vk::QueryPool,
+        frames: [FrameZones; FRAMES_IN_FLIGHT],
+        frame: usize,
+    }
+
+    impl GpuProfiler {
+        pub fn new(gpu: &GpuContext) -> Self {
+            let queue_family = unsafe {
+                gpu.instance
+                    .get_physical_device_queue_family_properties(gpu.physical_device)
+            }[gpu.queue_family_index as usize];
+            let mut profiler = Self {
+                context: None,
+                query_pool: vk::QueryPool::null(),
+                frames: Default::default(),
+                frame: 0,
+            };
+            if queue_family.timestamp_valid_bits == 0 {
+                println!("GPU zones disabled: the graphics queue has no timestamps");
+                return profiler;
+            }
+            // Each GPU would write its own instance of the queries, and in
+            // alternate frames some are never written
+            if gpu.device_group.is_some() {
+                println!("GPU zones disabled: rendering on a device group");
+                return profiler;
+            }
+            profiler.query_pool = unsafe {
+                gpu.device
+                    .create_query_pool(
+                        &vk::QueryPoolCreateInfo {
+                            query_type: vk::QueryType::TIMESTAMP,
+                            query_count: QUERIES_PER_FRAME * FRAMES_IN_FLIGHT as u32,
+                            ..Default::default()
+                        },
+                        None,
+                    )
+                    .expect("Failed to create timestamp query pool")
+            };
+            gpu.set_name(profiler.query_pool, "profiler timestamps");
+
+            // Tracy lines GPU time up with CPU time from one timestamp taken now
+            gpu.submit_immediate(|command_buffer| unsafe {
+                gpu.device
+                    .cmd_reset_query_pool(command_buffer, profiler.query_pool, 0, 1);
+                gpu.device.cmd_write_timestamp(
+                    command_buffer,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    profiler.query_pool,
+                    0,
+                );
+            });
+            let mut timestamp = [0u64];
+            unsafe {
+                gpu.device
+                    .get_query_pool_results(
+                        profiler.query_pool,
+                        0,
+                        &mut timestamp,
+                        vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+                    )
+                    .expect("Failed to read calibration timestamp");
+            }
+            let period = unsafe {
+                gpu.instance
+                    .get_physical_device_properties(gpu.physical_device)
+                    .limits
+                    .timestamp_period
+            };
+            profiler.context = Some(
+                Client::running()
+                    .expect("Tracy client not started")
+                    .new_gpu_context(
+                        Some("graphics queue"),
+                        GpuContextType::Vulkan,
+                        timestamp[0] as i64,
+                        period,
+                    )
+                    .expect("Failed to create Tracy GPU context"),
+            );
+            println!("GPU zones enabled ({} ns per tick)", period);
+            profiler
+        }
+
+        // Moves to the next frame's query range. Results of the frame that last
+        // used it are uploaded first, which waits for that frame if needed.
+        // Call right after beginning the frame's command buffer.
+        pub fn begin_frame(&mut self, gpu: &GpuContext, command_buffer: vk::CommandBuffer) {
+            if self.context.is_none() {
+                return;
+            }
+            self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+            let first_query = self.frame as u32 * QUERIES_PER_FRAME;
+            let frame = &mut self.frames[self.frame];
+            if frame.next_query > 0 {
+                let mut results = vec![0u64; frame.next_query as usize];
+                unsafe {
+                    gpu.device
+                        .get_query_pool_results(
+                            self.query_pool,
+                            first_query,
+                            &mut results,
+                            vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+                        )
+                        .expect("Failed to read GPU zone timestamps");
+                }
+                // Recording order is timestamp order, which Tracy needs
+                for &(query, span, end) in &frame.timestamps {
+                    let timestamp = results[query as usize] as i64;
+                    if end {
+                        frame.spans[span].upload_timestamp_end(timestamp);
+                    } else {
+                        frame.spans[span].upload_timestamp_start(timestamp);
+                    }
+                }
+            }
+            *frame = FrameZones::default();
+            unsafe {
+                gpu.device.cmd_reset_query_pool(
+                    command_buffer,
+                    self.query_pool,
+                    first_query,
+                    QUERIES_PER_FRAME,
+                );
+            }
+        }
+
+        // Opens a zone at the current point of `command_buffer`, which must be
+        // outside a render pass or in its first subpass
+        pub fn begin_zone(
+            &mut self,
+            gpu: &GpuContext,
+            command_buffer: vk::CommandBuffer,
+            name: &'static str,
+        ) -> GpuZone {
+            let Some(context) = self.context.as_ref() else {
+                return GpuZone(None);
+            };
+            let frame = &mut self.frames[self.frame];
+            if frame.next_query + 2 > QUERIES_PER_FRAME {
+                return GpuZone(None);
+            }
+            let Ok(span) = context.span_alloc(name, "", file!(), line!()) else {
+                return GpuZone(None);
+            };
+            frame.spans.push(span);
+            let span = frame.spans.len() - 1;
+            self.write_timestamp(
+                gpu,
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                span,
+                false,
+            );
+            GpuZone(Some(span))
+        }
+
+        pub fn end_zone(
+            &mut self,
+            gpu: &GpuContext,
+            command_buffer: vk::CommandBuffer,
+            zone: GpuZone,
+        ) {
+            let Some(span) = zone.0 else {
+                return;
+            };
+            self.frames[self.frame].spans[span].end_zone();
+            self.write_timestamp(
+                gpu,
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                span,
+                true,
+            );
+        }
+
+        // Caller ensures the device is idle
+        pub fn destroy(&self, gpu: &GpuContext) {
+            unsafe { gpu.device.destroy_query_pool(self.query_pool, None) };
+        }
+
+        fn write_timestamp(
+            &mut self,
+            gpu: &GpuContext,
+            command_buffer: vk::CommandBuffer,
+            stage: vk::PipelineStageFlags,
+            span: usize,
+            end: bool,
+        ) {
+            let frame = &mut self.frames[self.frame];
+            let query = frame.next_query;
+            frame.next_query += 1;
+            frame.timestamps.push((query, span, end));
+            unsafe {
+                gpu.device.cmd_write_timestamp(
+                    command_buffer,
+                    stage,
+                    self.query_pool,
+                    self.frame as u32 * QUERIES_PER_FRAME + query,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+mod disabled {
+    use ash::vk;
+
+    use crate::gpu::GpuContext;
+
+    pub fn start() {}
+
+    pub fn frame_mark() {}
+
+    #[derive(Clone, Copy)]
+    pub struct GpuZone;
+
+    pub struct GpuProfiler;
+
+    impl GpuProfiler {
+        pub fn new(_gpu: &GpuContext) -> Self {
+            Self
+        }
+
+        pub fn begin_frame(&mut self, _gpu: &GpuContext, _command_buffer: vk::CommandBuffer) {}
+
+        pub fn begin_zone(
+            &mut self,
+            _gpu: &GpuContext,
+            _command_buffer: vk::CommandBuffer,
+            _name: &'static str,
+        ) -> GpuZone {
+            GpuZone
+        }
+
+        pub fn end_zone(
+            &mut self,
+            _gpu: &GpuContext,
+            _command_buffer: vk::CommandBuffer,
+            _zone: GpuZone,
+        ) {
+        }
+
+        pub fn destroy(&self, _gpu: &GpuContext) {}
+    }
+}
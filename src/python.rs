@@ -0,0 +1,130 @@
+// Python bindings for the python scene (`python` feature). A script gets a
+// `World`, the `ShapeWorld` of circles it can spawn, move and recolor.
+
+use std::ops::{Deref, DerefMut};
+
+use glam::Vec2;
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::shapes::{self, Shape, ShapeWorld};
+
+#[pyclass(module = "vibe")]
+pub struct World {
+    shapes: ShapeWorld,
+}
+
+impl World {
+    pub fn new(bounds: Vec2) -> Self {
+        Self {
+            shapes: ShapeWorld::new(bounds),
+        }
+    }
+
+    fn shape(&mut self, id: u64) -> PyResult<&mut Shape> {
+        self.shapes
+            .get_mut(id)
+            .ok_or_else(|| PyKeyError::new_err(format!("no shape with id {}", id)))
+    }
+}
+
+impl Deref for World {
+    type Target = ShapeWorld;
+
+    fn deref(&self) -> &ShapeWorld {
+        &self.shapes
+    }
+}
+
+impl DerefMut for World {
+    fn deref_mut(&mut self) -> &mut ShapeWorld {
+        &mut self.shapes
+    }
+}
+
+fn parse_color(color: &[f32]) -> PyResult<[f32; 4]> {
+    shapes::parse_color(color).ok_or_else(|| PyValueError::new_err(shapes::COLOR_ERROR))
+}
+
+#[pymethods]
+impl World {
+    // Window size in logical pixels; the origin is the top-left corner, +y down
+    #[getter]
+    fn width(&self) -> f32 {
+        self.shapes.bounds().x
+    }
+
+    #[getter]
+    fn height(&self) -> f32 {
+        self.shapes.bounds().y
+    }
+
+    // Simulated seconds since the script started
+    #[getter]
+    fn time(&self) -> f32 {
+        self.shapes.time()
+    }
+
+    #[pyo3(signature = (x, y, radius, color = vec![1.0, 1.0, 1.0, 1.0]))]
+    fn spawn_circle(&mut self, x: f32, y: f32, radius: f32, color: Vec<f32>) -> PyResult<u64> {
+        let color = parse_color(&color)?;
+        Ok(self.shapes.spawn_circle(Vec2::new(x, y), radius, color))
+    }
+
+    // True if the shape existed
+    fn remove(&mut self, id: u64) -> bool {
+        self.shapes.remove(id)
+    }
+
+    fn clear(&mut self) {
+        self.shapes.clear();
+    }
+
+    fn ids(&self) -> Vec<u64> {
+        self.shapes.ids()
+    }
+
+    fn __len__(&self) -> usize {
+        self.shapes.len()
+    }
+
+    fn position(&mut self, id: u64) -> PyResult<(f32, f32)> {
+        Ok(self.shape(id)?.ball.position.into())
+    }
+
+    fn set_position(&mut self, id: u64, x: f32, y: f32) -> PyResult<()> {
+        self.shape(id)?.ball.position = Vec2::new(x, y);
+        Ok(())
+    }
+
+    // Pixels per second
+    fn velocity(&mut self, id: u64) -> PyResult<(f32, f32)> {
+        Ok(self.shape(id)?.ball.velocity.into())
+    }
+
+    fn set_velocity(&mut self, id: u64, vx: f32, vy: f32) -> PyResult<()> {
+        self.shape(id)?.ball.velocity = Vec2::new(vx, vy);
+        Ok(())
+    }
+
+    fn set_radius(&mut self, id: u64, radius: f32) -> PyResult<()> {
+        self.shape(id)?.ball.radius = radius.max(0.0);
+        Ok(())
+    }
+
+    fn set_color(&mut self, id: u64, color: Vec<f32>) -> PyResult<()> {
+        let color = parse_color(&color)?;
+        self.shape(id)?.color = color;
+        Ok(())
+    }
+
+    // Acceleration in pixels per second squared applied to every shape
+    fn set_gravity(&mut self, x: f32, y: f32) {
+        self.shapes.physics.gravity = Vec2::new(x, y);
+    }
+
+    // Fraction of speed kept after hitting a window edge, 0..1
+    fn set_restitution(&mut self, restitution: f32) {
+        self.shapes.physics.restitution = restitution.clamp(0.0, 1.0);
+    }
+}
@@ -0,0 +1,878 @@
+use ash::vk;
+use glam::{Mat4, Vec3};
+
+use crate::gpu::GpuContext;
+use crate::memory::MemoryCategory;
+use crate::mesh::{MeshData, MeshVertex};
+use crate::pipeline::{PipelineDesc, PipelineManager};
+use crate::sync::{Access, Barriers};
+
+const OUTPUT_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+const COLOR_SUBRESOURCE_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+// Slices and stacks of the mesh every sphere instance shares
+const SPHERE_SEGMENTS: u32 = 48;
+const SPHERE_RINGS: u32 = 24;
+
+// Mirrors the push constant block in shaders/raytrace_rgen.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct RayGenPushConstants {
+    inverse_view_projection: [f32; 16],
+    eye: [f32; 4],
+}
+
+// An acceleration structure and the buffer it lives in
+pub struct AccelerationStructure {
+    pub handle: vk::AccelerationStructureKHR,
+    // Where instances and shaders refer to it
+    pub address: vk::DeviceAddress,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+}
+
+impl AccelerationStructure {
+    // Builds a bottom-level structure over the triangles of `mesh`. Blocks until
+    // the build is done; the input buffers are freed before returning.
+    pub fn from_mesh(gpu: &GpuContext, name: &str, mesh: &MeshData) -> Self {
+        let (vertex_buffer, vertex_memory) =
+            build_input_buffer(gpu, bytemuck::cast_slice(&mesh.vertices));
+        let (index_buffer, index_memory) =
+            build_input_buffer(gpu, bytemuck::cast_slice(&mesh.indices));
+        let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR::default()
+            .vertex_format(vk::Format::R32G32B32_SFLOAT)
+            .vertex_data(vk::DeviceOrHostAddressConstKHR {
+                device_address: gpu.buffer_address(vertex_buffer),
+            })
+            .vertex_stride(size_of::<MeshVertex>() as vk::DeviceSize)
+            .max_vertex(mesh.vertices.len().saturating_sub(1) as u32)
+            .index_type(vk::IndexType::UINT32)
+            .index_data(vk::DeviceOrHostAddressConstKHR {
+                device_address: gpu.buffer_address(index_buffer),
+            });
+        let geometry = vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::TRIANGLES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR { triangles })
+            .flags(vk::GeometryFlagsKHR::OPAQUE);
+        let structure = Self::build(
+            gpu,
+            name,
+            vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+            geometry,
+            (mesh.indices.len() / 3) as u32,
+        );
+        unsafe {
+            gpu.device.destroy_buffer(vertex_buffer, None);
+            gpu.device.destroy_buffer(index_buffer, None);
+        }
+        gpu.free_memory(vertex_memory);
+        gpu.free_memory(index_memory);
+        structure
+    }
+
+    // Builds a top-level structure over `instances` (see `instance`). Blocks
+    // until the build is done.
+    pub fn from_instances(
+        gpu: &GpuContext,
+        name: &str,
+        instances: &[vk::AccelerationStructureInstanceKHR],
+    ) -> Self {
+        // The instance struct is plain data, but has no Pod impl to cast with
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                instances.as_ptr() as *const u8,
+                std::mem::size_of_val(instances),
+            )
+        };
+        let (instance_buffer, instance_memory) = build_input_buffer(gpu, bytes);
+        let instances_data = vk::AccelerationStructureGeometryInstancesDataKHR::default()
+            .array_of_pointers(false)
+            .data(vk::DeviceOrHostAddressConstKHR {
+                device_address: gpu.buffer_address(instance_buffer),
+            });
+        let geometry = vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::INSTANCES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR {
+                instances: instances_data,
+            });
+        let structure = Self::build(
+            gpu,
+            name,
+            vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+            geometry,
+            instances.len() as u32,
+        );
+        unsafe { gpu.device.destroy_buffer(instance_buffer, None) };
+        gpu.free_memory(instance_memory);
+        structure
+    }
+
+    fn build(
+        gpu: &GpuContext,
+        name: &str,
+        ty: vk::AccelerationStructureTypeKHR,
+        geometry: vk::AccelerationStructureGeometryKHR,
+        primitive_count: u32,
+    ) -> Self {
+        let structures = gpu.acceleration_structures();
+        let geometries = [geometry];
+        let mut build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(ty)
+            .flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .geometries(&geometries);
+        let mut sizes = vk::AccelerationStructureBuildSizesInfoKHR::default();
+        unsafe {
+            structures.get_acceleration_structure_build_sizes(
+                vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                &build_info,
+                &[primitive_count],
+                &mut sizes,
+            );
+        }
+
+        let (buffer, memory) = gpu.create_buffer(
+            sizes.acceleration_structure_size,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let handle = unsafe {
+            structures
+                .create_acceleration_structure(
+                    &vk::AccelerationStructureCreateInfoKHR::default()
+                        .buffer(buffer)
+                        .size(sizes.acceleration_structure_size)
+                        .ty(ty),
+                    None,
+                )
+                .expect("Failed to create acceleration structure")
+        };
+
+        // Padded so the address handed to the build can be aligned up
+        let alignment = scratch_alignment(gpu);
+        let (scratch_buffer, scratch_memory) = gpu.create_buffer_in(
+            MemoryCategory::AccelerationStructures,
+            sizes.build_scratch_size + alignment,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        build_info = build_info.dst_acceleration_structure(handle).scratch_data(
+            vk::DeviceOrHostAddressKHR {
+                device_address: gpu
+                    .buffer_address(scratch_buffer)
+                    .next_multiple_of(alignment),
+            },
+        );
+        let range =
+            vk::AccelerationStructureBuildRangeInfoKHR::default().primitive_count(primitive_count);
+        gpu.submit_immediate(|command_buffer| {
+            unsafe {
+                structures.cmd_build_acceleration_structures(
+                    command_buffer,
+                    &[build_info],
+                    &[&[range]],
+                );
+            }
+            // Later builds and traces read the result
+            Barriers::new()
+                .buffer(
+                    buffer,
+                    Access::new(
+                        vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR,
+                        vk::AccessFlags2::ACCELERATION_STRUCTURE_WRITE_KHR,
+                    ),
+                    Access::new(
+                        vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR
+                            | vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+                        vk::AccessFlags2::ACCELERATION_STRUCTURE_READ_KHR,
+                    ),
+                )
+                .record(gpu, command_buffer);
+        });
+        unsafe { gpu.device.destroy_buffer(scratch_buffer, None) };
+        gpu.free_memory(scratch_memory);
+
+        let address = unsafe {
+            structures.get_acceleration_structure_device_address(
+                &vk::AccelerationStructureDeviceAddressInfoKHR::default()
+                    .acceleration_structure(handle),
+            )
+        };
+        gpu.set_name(handle, name);
+        gpu.set_name(buffer, &format!("{} buffer", name));
+        println!(
+            "Acceleration structure {} built: {} primitives, {} bytes",
+            name, primitive_count, sizes.acceleration_structure_size
+        );
+
+        Self {
+            handle,
+            address,
+            buffer,
+            memory,
+        }
+    }
+
+    // Places this bottom-level structure in a top-level one. `custom_index` is
+    // the low 24 bits shaders read as gl_InstanceCustomIndexEXT.
+    pub fn instance(
+        &self,
+        transform: Mat4,
+        custom_index: u32,
+    ) -> vk::AccelerationStructureInstanceKHR {
+        // Row-major 3x4: the first three rows of the matrix
+        let rows = transform.transpose().to_cols_array();
+        let mut matrix = [0.0; 12];
+        matrix.copy_from_slice(&rows[..12]);
+        vk::AccelerationStructureInstanceKHR {
+            transform: vk::TransformMatrixKHR { matrix },
+            instance_custom_index_and_mask: vk::Packed24_8::new(custom_index, 0xff),
+            instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
+                0,
+                vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw() as u8,
+            ),
+            acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                device_handle: self.address,
+            },
+        }
+    }
+
+    pub fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.acceleration_structures()
+                .destroy_acceleration_structure(self.handle, None);
+            gpu.device.destroy_buffer(self.buffer, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+}
+
+// A HOST_VISIBLE buffer holding `data` for an acceleration structure build.
+// Written directly rather than staged so the build can use it right away.
+fn build_input_buffer(gpu: &GpuContext, data: &[u8]) -> (vk::Buffer, vk::DeviceMemory) {
+    let (buffer, memory) = gpu.create_buffer_in(
+        MemoryCategory::AccelerationStructures,
+        data.len() as vk::DeviceSize,
+        vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    );
+    gpu.write_memory(memory, 0, data);
+    (buffer, memory)
+}
+
+fn scratch_alignment(gpu: &GpuContext) -> vk::DeviceSize {
+    let mut structure_properties = vk::PhysicalDeviceAccelerationStructurePropertiesKHR::default();
+    let mut properties =
+        vk::PhysicalDeviceProperties2::default().push_next(&mut structure_properties);
+    unsafe {
+        gpu.instance
+            .get_physical_device_properties2(gpu.physical_device, &mut properties);
+    }
+    structure_properties
+        .min_acceleration_structure_scratch_offset_alignment
+        .max(1) as vk::DeviceSize
+}
+
+// Packs an RGBA color into the 24-bit instance custom index the closest hit
+// shader unpacks with unpackUnorm4x8; alpha is dropped
+fn pack_color(color: [f32; 4]) -> u32 {
+    color[..3]
+        .iter()
+        .enumerate()
+        .map(|(channel, value)| ((value.clamp(0.0, 1.0) * 255.0).round() as u32) << (channel * 8))
+        .sum()
+}
+
+// One sphere of the ray traced scene
+#[derive(Clone, Copy, Debug)]
+pub struct TracedSphere {
+    pub center: Vec3,
+    pub radius: f32,
+    pub color: [f32; 4],
+}
+
+// The storage image the rays are written to, kept in GENERAL layout for both
+// storage and sampling
+struct OutputImage {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+impl OutputImage {
+    fn new(gpu: &GpuContext, extent: vk::Extent2D) -> Self {
+        let device = &gpu.device;
+        let image = unsafe {
+            device
+                .create_image(
+                    &vk::ImageCreateInfo {
+                        image_type: vk::ImageType::TYPE_2D,
+                        format: OUTPUT_FORMAT,
+                        extent: vk::Extent3D {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                        mip_levels: 1,
+                        array_layers: 1,
+                        samples: vk::SampleCountFlags::TYPE_1,
+                        tiling: vk::ImageTiling::OPTIMAL,
+                        usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+                        sharing_mode: vk::SharingMode::EXCLUSIVE,
+                        initial_layout: vk::ImageLayout::UNDEFINED,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create ray tracing output image")
+        };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory = gpu.allocate_memory(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Images,
+        );
+        unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Failed to bind ray tracing output memory");
+        }
+        let view = unsafe {
+            device
+                .create_image_view(
+                    &vk::ImageViewCreateInfo {
+                        image,
+                        view_type: vk::ImageViewType::TYPE_2D,
+                        format: OUTPUT_FORMAT,
+                        components: vk::ComponentMapping::default(),
+                        subresource_range: COLOR_SUBRESOURCE_RANGE,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create ray tracing output view")
+        };
+        gpu.set_name(image, "ray tracing output");
+        gpu.set_name(view, "ray tracing output view");
+        Self {
+            image,
+            memory,
+            view,
+        }
+    }
+
+    fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device.destroy_image_view(self.view, None);
+            gpu.device.destroy_image(self.image, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+}
+
+// Spheres ray traced with VK_KHR_ray_tracing_pipeline: one bottom-level
+// structure holds a sphere mesh, the top-level one places an instance of it
+// per sphere with its color in the custom index. Each frame a ray per pixel is
+// traced into a storage image, which is then drawn as the background. Needs
+// `capabilities.ray_tracing`.
+pub struct RayTracedSpheres {
+    pub view_projection: Mat4,
+    pub eye: Vec3,
+    extent: vk::Extent2D,
+    output: OutputImage,
+    output_initialized: bool,
+    sphere: AccelerationStructure,
+    scene: AccelerationStructure,
+    trace_set_layout: vk::DescriptorSetLayout,
+    trace_pool: vk::DescriptorPool,
+    trace_set: vk::DescriptorSet,
+    trace_layout: vk::PipelineLayout,
+    trace_pipeline: vk::Pipeline,
+    binding_table_buffer: vk::Buffer,
+    binding_table_memory: vk::DeviceMemory,
+    // Ray generation, miss and hit group records in the binding table
+    binding_table_regions: [vk::StridedDeviceAddressRegionKHR; 3],
+    sampler: vk::Sampler,
+    draw_set_layout: vk::DescriptorSetLayout,
+    draw_pool: vk::DescriptorPool,
+    draw_set: vk::DescriptorSet,
+    draw_pipeline: PipelineDesc,
+}
+
+impl RayTracedSpheres {
+    pub fn new(
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        extent: vk::Extent2D,
+        spheres: &[TracedSphere],
+    ) -> Self {
+        let device = &gpu.device;
+        let sphere = AccelerationStructure::from_mesh(
+            gpu,
+            "ray traced sphere",
+            &MeshData::uv_sphere(1.0, SPHERE_SEGMENTS, SPHERE_RINGS),
+        );
+        let instances: Vec<vk::AccelerationStructureInstanceKHR> = spheres
+            .iter()
+            .map(|traced| {
+                let transform = Mat4::from_translation(traced.center)
+                    * Mat4::from_scale(Vec3::splat(traced.radius));
+                sphere.instance(transform, pack_color(traced.color))
+            })
+            .collect();
+        let scene = AccelerationStructure::from_instances(gpu, "ray traced scene", &instances);
+
+        let trace_bindings = [
+            (0, vk::DescriptorType::ACCELERATION_STRUCTURE_KHR),
+            (1, vk::DescriptorType::STORAGE_IMAGE),
+        ]
+        .map(
+            |(binding, descriptor_type)| vk::DescriptorSetLayoutBinding {
+                binding,
+                descriptor_type,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::RAYGEN_KHR,
+                ..Default::default()
+            },
+        );
+        let trace_set_layout = unsafe {
+            device
+                .create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::default().bindings(&trace_bindings),
+                    None,
+                )
+                .expect("Failed to create ray tracing descriptor set layout")
+        };
+        let trace_pool_sizes = trace_bindings.map(|binding| vk::DescriptorPoolSize {
+            ty: binding.descriptor_type,
+            descriptor_count: 1,
+        });
+        let trace_pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo::default()
+                        .max_sets(1)
+                        .pool_sizes(&trace_pool_sizes),
+                    None,
+                )
+                .expect("Failed to create ray tracing descriptor pool")
+        };
+        let trace_set = unsafe {
+            device
+                .allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::default()
+                        .descriptor_pool(trace_pool)
+                        .set_layouts(&[trace_set_layout]),
+                )
+                .expect("Failed to allocate ray tracing descriptor set")[0]
+        };
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::RAYGEN_KHR,
+            offset: 0,
+            size: size_of::<RayGenPushConstants>() as u32,
+        };
+        let trace_layout = unsafe {
+            device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo::default()
+                        .set_layouts(&[trace_set_layout])
+                        .push_constant_ranges(&[push_constant_range]),
+                    None,
+                )
+                .expect("Failed to create ray tracing pipeline layout")
+        };
+        let trace_pipeline = create_trace_pipeline(gpu, trace_layout);
+        let (binding_table_buffer, binding_table_memory, binding_table_regions) =
+            create_binding_table(gpu, trace_pipeline);
+
+        let sampler = unsafe {
+            device
+                .create_sampler(
+                    &vk::SamplerCreateInfo {
+                        mag_filter: vk::Filter::NEAREST,
+                        min_filter: vk::Filter::NEAREST,
+                        mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+                        address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create ray tracing output sampler")
+        };
+        // The post copy shader just samples its first texture
+        let shaders = pipelines.register_shaders(
+            gpu,
+            "ray tracing output",
+            include_bytes!("../shaders/fullscreen_vert.spv"),
+            include_bytes!("../shaders/post_copy_frag.spv"),
+        );
+        let interface = pipelines.interface(shaders);
+        let set_layouts = interface.create_set_layouts(gpu);
+        let draw_set_layout = set_layouts[0];
+        let pool_sizes = interface.pool_sizes(1);
+        let draw_pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        max_sets: 1,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create ray tracing output descriptor pool")
+        };
+        let draw_set = unsafe {
+            device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool: draw_pool,
+                    descriptor_set_count: 1,
+                    p_set_layouts: &draw_set_layout,
+                    ..Default::default()
+                })
+                .expect("Failed to allocate ray tracing output descriptor set")[0]
+        };
+        let pipeline_layout = interface.create_pipeline_layout(gpu, &set_layouts, 0);
+        let draw_pipeline = PipelineDesc::new(shaders, pipeline_layout);
+        pipelines.prepare(gpu, &draw_pipeline);
+
+        let traced = Self {
+            view_projection: Mat4::IDENTITY,
+            eye: Vec3::ZERO,
+            extent,
+            output: OutputImage::new(gpu, extent),
+            output_initialized: false,
+            sphere,
+            scene,
+            trace_set_layout,
+            trace_pool,
+            trace_set,
+            trace_layout,
+            trace_pipeline,
+            binding_table_buffer,
+            binding_table_memory,
+            binding_table_regions,
+            sampler,
+            draw_set_layout,
+            draw_pool,
+            draw_set,
+            draw_pipeline,
+        };
+        traced.write_descriptor_sets(gpu);
+        println!(
+            "Ray traced spheres created: {} spheres at {}x{}",
+            spheres.len(),
+            extent.width,
+            extent.height
+        );
+        traced
+    }
+
+    fn write_descriptor_sets(&self, gpu: &GpuContext) {
+        let structures = [self.scene.handle];
+        let mut structure_info = vk::WriteDescriptorSetAccelerationStructureKHR::default()
+            .acceleration_structures(&structures);
+        let storage_info = [vk::DescriptorImageInfo {
+            image_view: self.output.view,
+            image_layout: vk::ImageLayout::GENERAL,
+            ..Default::default()
+        }];
+        let sampled_info = [vk::DescriptorImageInfo {
+            image_view: self.output.view,
+            image_layout: vk::ImageLayout::GENERAL,
+            ..Default::default()
+        }];
+        let sampler_info = [vk::DescriptorImageInfo {
+            sampler: self.sampler,
+            ..Default::default()
+        }];
+        let writes = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(self.trace_set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                .descriptor_count(1)
+                .push_next(&mut structure_info),
+            vk::WriteDescriptorSet::default()
+                .dst_set(self.trace_set)
+                .dst_binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .image_info(&storage_info),
+            vk::WriteDescriptorSet::default()
+                .dst_set(self.draw_set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                .image_info(&sampled_info),
+            vk::WriteDescriptorSet::default()
+                .dst_set(self.draw_set)
+                .dst_binding(1)
+                .descriptor_type(vk::DescriptorType::SAMPLER)
+                .image_info(&sampler_info),
+        ];
+        unsafe { gpu.device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    // Recreates the output image at the new size. Call with the device idle.
+    pub fn resize(&mut self, gpu: &GpuContext, extent: vk::Extent2D) {
+        self.output.destroy(gpu);
+        self.output = OutputImage::new(gpu, extent);
+        self.extent = extent;
+        self.output_initialized = false;
+        self.write_descriptor_sets(gpu);
+    }
+
+    // Traces this frame's rays into the output image. Must be called outside a
+    // render pass, before the pass that draws the output.
+    pub fn record_trace(&mut self, gpu: &GpuContext, command_buffer: vk::CommandBuffer) {
+        let raygen = Access::new(
+            vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+            vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        );
+        let sampled = Access::new(
+            vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            vk::AccessFlags2::SHADER_SAMPLED_READ,
+        );
+        // Last frame's draw may still be sampling the image about to be written
+        let (old_layout, src) = if self.output_initialized {
+            (vk::ImageLayout::GENERAL, sampled)
+        } else {
+            (vk::ImageLayout::UNDEFINED, Access::NONE)
+        };
+        self.output_initialized = true;
+        Barriers::new()
+            .image(
+                self.output.image,
+                COLOR_SUBRESOURCE_RANGE,
+                old_layout,
+                vk::ImageLayout::GENERAL,
+                src,
+                raygen,
+            )
+            .record(gpu, command_buffer);
+
+        let push_constants = RayGenPushConstants {
+            inverse_view_projection: self.view_projection.inverse().to_cols_array(),
+            eye: self.eye.extend(1.0).to_array(),
+        };
+        let device = &gpu.device;
+        let [raygen_region, miss_region, hit_region] = &self.binding_table_regions;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::RAY_TRACING_KHR,
+                self.trace_pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::RAY_TRACING_KHR,
+                self.trace_layout,
+                0,
+                &[self.trace_set],
+                &[],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.trace_layout,
+                vk::ShaderStageFlags::RAYGEN_KHR,
+                0,
+                bytemuck::bytes_of(&push_constants),
+            );
+            gpu.ray_tracing_pipelines().cmd_trace_rays(
+                command_buffer,
+                raygen_region,
+                miss_region,
+                hit_region,
+                &vk::StridedDeviceAddressRegionKHR::default(),
+                self.extent.width,
+                self.extent.height,
+                1,
+            );
+        }
+
+        Barriers::new()
+            .image(
+                self.output.image,
+                COLOR_SUBRESOURCE_RANGE,
+                vk::ImageLayout::GENERAL,
+                vk::ImageLayout::GENERAL,
+                raygen,
+                sampled,
+            )
+            .record(gpu, command_buffer);
+    }
+
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let pipeline = pipelines.get(gpu, &self.draw_pipeline);
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.draw_pipeline.layout,
+                0,
+                &[self.draw_set],
+                &[],
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, gpu: &GpuContext, pipelines: &mut PipelineManager) {
+        let device = &gpu.device;
+        pipelines.release_shaders(device, self.draw_pipeline.shaders);
+        self.output.destroy(gpu);
+        self.scene.destroy(gpu);
+        self.sphere.destroy(gpu);
+        unsafe {
+            device.destroy_pipeline_layout(self.draw_pipeline.layout, None);
+            device.destroy_descriptor_pool(self.draw_pool, None);
+            device.destroy_descriptor_set_layout(self.draw_set_layout, None);
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_buffer(self.binding_table_buffer, None);
+            device.destroy_pipeline(self.trace_pipeline, None);
+            device.destroy_pipeline_layout(self.trace_layout, None);
+            device.destroy_descriptor_pool(self.trace_pool, None);
+            device.destroy_descriptor_set_layout(self.trace_set_layout, None);
+        }
+        gpu.free_memory(self.binding_table_memory);
+    }
+}
+
+// Ray generation, miss and closest hit, in that group order
+fn create_trace_pipeline(gpu: &GpuContext, layout: vk::PipelineLayout) -> vk::Pipeline {
+    let device = &gpu.device;
+    let modules = [
+        include_bytes!("../shaders/raytrace_rgen.spv").as_slice(),
+        include_bytes!("../shaders/raytrace_rmiss.spv").as_slice(),
+        include_bytes!("../shaders/raytrace_rchit.spv").as_slice(),
+    ]
+    .map(|code| gpu.create_shader_module(code));
+    let stages = [
+        vk::ShaderStageFlags::RAYGEN_KHR,
+        vk::ShaderStageFlags::MISS_KHR,
+        vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+    ]
+    .iter()
+    .zip(&modules)
+    .map(|(&stage, &module)| {
+        vk::PipelineShaderStageCreateInfo::default()
+            .stage(stage)
+            .module(module)
+            .name(c"main")
+    })
+    .collect::<Vec<_>>();
+    let general = |shader| {
+        vk::RayTracingShaderGroupCreateInfoKHR::default()
+            .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+            .general_shader(shader)
+            .closest_hit_shader(vk::SHADER_UNUSED_KHR)
+            .any_hit_shader(vk::SHADER_UNUSED_KHR)
+            .intersection_shader(vk::SHADER_UNUSED_KHR)
+    };
+    let groups = [
+        general(0),
+        general(1),
+        vk::RayTracingShaderGroupCreateInfoKHR::default()
+            .ty(vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP)
+            .general_shader(vk::SHADER_UNUSED_KHR)
+            .closest_hit_shader(2)
+            .any_hit_shader(vk::SHADER_UNUSED_KHR)
+            .intersection_shader(vk::SHADER_UNUSED_KHR),
+    ];
+    let create_info = vk::RayTracingPipelineCreateInfoKHR::default()
+        .stages(&stages)
+        .groups(&groups)
+        // Primary rays only: the hit shader traces nothing further
+        .max_pipeline_ray_recursion_depth(1)
+        .layout(layout);
+    let pipeline = unsafe {
+        gpu.ray_tracing_pipelines()
+            .create_ray_tracing_pipelines(
+                vk::DeferredOperationKHR::null(),
+                gpu.pipeline_cache,
+                &[create_info],
+                None,
+            )
+            .map_err(|(_, error)| error)
+            .expect("Failed to create ray tracing pipeline")[0]
+    };
+    for module in modules {
+        unsafe { device.destroy_shader_module(module, None) };
+    }
+    gpu.set_name(pipeline, "ray tracing pipeline");
+    pipeline
+}
+
+// Copies the handle of each of the pipeline's three groups into its own
+// region of a HOST_VISIBLE buffer, laid out to the device's alignment rules
+fn create_binding_table(
+    gpu: &GpuContext,
+    pipeline: vk::Pipeline,
+) -> (
+    vk::Buffer,
+    vk::DeviceMemory,
+    [vk::StridedDeviceAddressRegionKHR; 3],
+) {
+    let mut pipeline_properties = vk::PhysicalDeviceRayTracingPipelinePropertiesKHR::default();
+    let mut properties =
+        vk::PhysicalDeviceProperties2::default().push_next(&mut pipeline_properties);
+    unsafe {
+        gpu.instance
+            .get_physical_device_properties2(gpu.physical_device, &mut properties);
+    }
+    let handle_size = pipeline_properties.shader_group_handle_size as usize;
+    let handle_stride = (handle_size as u32)
+        .next_multiple_of(pipeline_properties.shader_group_handle_alignment)
+        as vk::DeviceSize;
+    // Every region starts on the base alignment
+    let region_size =
+        handle_stride.next_multiple_of(pipeline_properties.shader_group_base_alignment as u64);
+
+    let handles = unsafe {
+        gpu.ray_tracing_pipelines()
+            .get_ray_tracing_shader_group_handles(pipeline, 0, 3, handle_size * 3)
+            .expect("Failed to get ray tracing shader group handles")
+    };
+    let mut table = vec![0u8; region_size as usize * 3];
+    for (group, handle) in handles.chunks_exact(handle_size).enumerate() {
+        let offset = group * region_size as usize;
+        table[offset..offset + handle_size].copy_from_slice(handle);
+    }
+    let (buffer, memory) = gpu.create_buffer(
+        table.len() as vk::DeviceSize,
+        vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR
+            | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    );
+    gpu.write_memory(memory, 0, &table);
+    gpu.set_name(buffer, "ray tracing binding table");
+
+    let address = gpu.buffer_address(buffer);
+    let regions = [0, 1, 2].map(|group| vk::StridedDeviceAddressRegionKHR {
+        device_address: address + group * region_size,
+        // The ray generation region holds one record, and its stride must match
+        stride: if group == 0 {
+            region_size
+        } else {
+            handle_stride
+        },
+        size: region_size,
+    });
+    (buffer, memory, regions)
+}
@@ -0,0 +1,464 @@
+use ash::vk;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use crate::frame_ring::FRAMES_IN_FLIGHT;
+use crate::gpu::GpuContext;
+use crate::memory::MemoryCategory;
+use crate::sync::{Access, Barriers};
+
+// Frames queued for the encoder before rendering waits for it
+const ENCODER_QUEUE_LENGTH: usize = 8;
+// NeuQuant sampling for GIF palettes, 1 (best) to 30 (fastest)
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+// One presented frame in host memory, tightly packed RGBA8 rows
+pub struct CapturedFrame {
+    // Frame number the renderer gave it; a later read with the same number is
+    // the same frame again
+    pub frame: u64,
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+// A host-visible buffer one frame is copied into, mapped for its whole lifetime
+struct ReadbackSlot {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    mapped: *const u8,
+    size: vk::DeviceSize,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    // Frame whose copy is in the buffer
+    frame: Option<u64>,
+}
+
+impl ReadbackSlot {
+    fn empty() -> Self {
+        Self {
+            buffer: vk::Buffer::null(),
+            memory: vk::DeviceMemory::null(),
+            mapped: std::ptr::null(),
+            size: 0,
+            extent: vk::Extent2D::default(),
+            format: vk::Format::UNDEFINED,
+            frame: None,
+        }
+    }
+
+    fn destroy(&mut self, gpu: &GpuContext) {
+        if self.buffer == vk::Buffer::null() {
+            return;
+        }
+        unsafe {
+            gpu.device.unmap_memory(self.memory);
+            gpu.device.destroy_buffer(self.buffer, None);
+        }
+        gpu.free_memory(self.memory);
+        *self = Self::empty();
+    }
+}
+
+// Copies of presented swapchain images, one buffer per frame in flight. A frame
+// copied by `record_copy` can be read once the GPU has finished it, while the
+// next frame is copied into the other buffer.
+pub struct FrameReadback {
+    slots: [ReadbackSlot; FRAMES_IN_FLIGHT],
+    next: usize,
+}
+
+impl Default for FrameReadback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameReadback {
+    pub fn new() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| ReadbackSlot::empty()),
+            next: 0,
+        }
+    }
+
+    // Whether frames in `format` can be turned into RGBA8
+    pub fn supports_format(format: vk::Format) -> bool {
+        matches!(
+            format,
+            vk::Format::B8G8R8A8_UNORM
+                | vk::Format::B8G8R8A8_SRGB
+                | vk::Format::R8G8B8A8_UNORM
+                | vk::Format::R8G8B8A8_SRGB
+        )
+    }
+
+    // Records a copy of `image`, a swapchain image that has just been rendered to
+    // and is in PRESENT_SRC_KHR, into the next buffer. The image is back in
+    // PRESENT_SRC_KHR afterwards. `frame` is the number the frame will signal on
+    // the frame timeline. The buffer's previous frame is overwritten, and must be
+    // finished on the GPU.
+    pub fn record_copy(
+        &mut self,
+        gpu: &GpuContext,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        frame: u64,
+    ) {
+        let slot = &mut self.slots[self.next];
+        self.next = (self.next + 1) % FRAMES_IN_FLIGHT;
+        let size = extent.width as vk::DeviceSize * extent.height as vk::DeviceSize * 4;
+        if slot.size < size {
+            slot.destroy(gpu);
+            (slot.buffer, slot.memory) = gpu.create_buffer_in(
+                MemoryCategory::Readback,
+                size,
+                vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+            gpu.set_name(slot.buffer, "frame readback buffer");
+            slot.mapped = unsafe {
+                gpu.device
+                    .map_memory(slot.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                    .expect("Failed to map readback memory") as *const u8
+            };
+            slot.size = size;
+        }
+        slot.extent = extent;
+        slot.format = format;
+        slot.frame = Some(frame);
+
+        let color_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let color_written = Access::new(
+            vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        );
+        let transfer_read = Access::new(
+            vk::PipelineStageFlags2::COPY,
+            vk::AccessFlags2::TRANSFER_READ,
+        );
+        let transfer_write = Access::new(
+            vk::PipelineStageFlags2::COPY,
+            vk::AccessFlags2::TRANSFER_WRITE,
+        );
+        let host_read = Access::new(vk::PipelineStageFlags2::HOST, vk::AccessFlags2::HOST_READ);
+        Barriers::new()
+            .image(
+                image,
+                color_range,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                color_written,
+                transfer_read,
+            )
+            .record(gpu, command_buffer);
+        unsafe {
+            gpu.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                slot.buffer,
+                &[vk::BufferImageCopy {
+                    buffer_offset: 0,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D::default(),
+                    image_extent: vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    },
+                }],
+            );
+        }
+        // Presentation waits on the frame's semaphore, so the way back needs no
+        // destination stage
+        Barriers::new()
+            .image(
+                image,
+                color_range,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                transfer_read,
+                Access::NONE,
+            )
+            .buffer(slot.buffer, transfer_write, host_read)
+            .record(gpu, command_buffer);
+    }
+
+    // The newest copied frame whose number is at most `completed`, the last frame
+    // the GPU has finished
+    pub fn latest(&self, completed: u64) -> Option<CapturedFrame> {
+        let slot = self
+            .slots
+            .iter()
+            .filter(|slot| slot.frame.is_some_and(|frame| frame <= completed))
+            .max_by_key(|slot| slot.frame)?;
+        let length = slot.extent.width as usize * slot.extent.height as usize * 4;
+        let mut rgba = unsafe { std::slice::from_raw_parts(slot.mapped, length) }.to_vec();
+        if matches!(
+            slot.format,
+            vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB
+        ) {
+            for pixel in rgba.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Some(CapturedFrame {
+            frame: slot.frame?,
+            width: slot.extent.width,
+            height: slot.extent.height,
+            rgba,
+        })
+    }
+
+    // Caller ensures the device is idle
+    pub fn destroy(&mut self, gpu: &GpuContext) {
+        for slot in &mut self.slots {
+            slot.destroy(gpu);
+        }
+    }
+}
+
+// Pipes captured frames into an ffmpeg process, which encodes them to `path`.
+// The extension picks the codec: VP9 for .webm, H.264 for anything else. Frames
+// are handed to a writer thread so encoding doesn't stall rendering until its
+// queue fills up.
+pub struct VideoRecorder {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    frames: u64,
+    sender: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+    ffmpeg: Child,
+}
+
+impl VideoRecorder {
+    // Starts ffmpeg for `width` x `height` frames arriving at `fps`. None, with
+    // the reason printed, when ffmpeg can't be run.
+    pub fn start(path: &Path, width: u32, height: u32, fps: f32) -> Option<Self> {
+        let codec: &[&str] = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("webm") => &["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32"],
+            _ => &["-c:v", "libx264", "-preset", "fast", "-crf", "20"],
+        };
+        let spawned = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pixel_format", "rgba"])
+            .args(["-video_size", &format!("{}x{}", width, height)])
+            .args(["-framerate", &format!("{}", fps)])
+            .args(["-i", "-"])
+            // Both encoders want even sizes for yuv420p
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-pix_fmt", "yuv420p"])
+            .args(codec)
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut ffmpeg = match spawned {
+            Ok(ffmpeg) => ffmpeg,
+            Err(e) => {
+                eprintln!("Failed to start ffmpeg, is it installed? {}", e);
+                return None;
+            }
+        };
+        let mut stdin = ffmpeg.stdin.take().expect("ffmpeg has no stdin");
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(ENCODER_QUEUE_LENGTH);
+        let writer = std::thread::spawn(move || {
+            for frame in receiver {
+                if let Err(e) = stdin.write_all(&frame) {
+                    eprintln!("Failed to write frame to ffmpeg: {}", e);
+                    break;
+                }
+            }
+            // Dropping stdin tells ffmpeg the video is over
+        });
+        println!(
+            "Recording {}x{} at {} FPS to {}",
+            width,
+            height,
+            fps,
+            path.display()
+        );
+        Some(Self {
+            path: path.to_owned(),
+            width,
+            height,
+            frames: 0,
+            sender: Some(sender),
+            writer: Some(writer),
+            ffmpeg,
+        })
+    }
+
+    // Queues `frame` for encoding. False when it can't be added, because its
+    // size differs from the video's or ffmpeg has gone away; the recording
+    // should then be finished.
+    pub fn push(&mut self, frame: CapturedFrame) -> bool {
+        if frame.width != self.width || frame.height != self.height {
+            println!(
+                "Frame size changed to {}x{}, stopping the recording",
+                frame.width, frame.height
+            );
+            return false;
+        }
+        let Some(sender) = self.sender.as_ref() else {
+            return false;
+        };
+        if sender.send(frame.rgba).is_err() {
+            return false;
+        }
+        self.frames += 1;
+        true
+    }
+
+    // Closes the pipe and waits for ffmpeg to write the file
+    pub fn finish(mut self) {
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            writer.join().expect("Recording writer thread panicked");
+        }
+        match self.ffmpeg.wait() {
+            Ok(status) if status.success() => println!(
+                "Recorded {} frames to {}",
+                self.frames,
+                self.path.display()
+            ),
+            Ok(status) => eprintln!("ffmpeg failed ({}), recording may be incomplete", status),
+            Err(e) => eprintln!("Failed to wait for ffmpeg: {}", e),
+        }
+    }
+}
+
+// Writes captured frames into an animated GIF at `path`, sampling them at a fixed
+// rate for a fixed length. Each frame is quantized to its own 256 color palette
+// on a writer thread, which is the slow part.
+pub struct GifRecorder {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    fps: f32,
+    // Frames in the finished GIF
+    length: u32,
+    frames: u32,
+    started: Instant,
+    sender: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<Result<(), gif::EncodingError>>>,
+}
+
+impl GifRecorder {
+    // Creates the file for `seconds` of `width` x `height` frames at `fps`. None,
+    // with the reason printed, when it can't be written or the size is too big
+    // for a GIF.
+    pub fn start(path: &Path, width: u32, height: u32, fps: f32, seconds: f32) -> Option<Self> {
+        let (Ok(gif_width), Ok(gif_height)) = (u16::try_from(width), u16::try_from(height)) else {
+            eprintln!("{}x{} is too large for a GIF", width, height);
+            return None;
+        };
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let mut encoder = match gif::Encoder::new(BufWriter::new(file), gif_width, gif_height, &[])
+        {
+            Ok(encoder) => encoder,
+            Err(e) => {
+                eprintln!("Failed to start GIF {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        // GIF delays are in hundredths of a second
+        let delay = (100.0 / fps).round() as u16;
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(ENCODER_QUEUE_LENGTH);
+        let writer = std::thread::spawn(move || {
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            for mut rgba in receiver {
+                let mut frame = gif::Frame::from_rgba_speed(
+                    gif_width,
+                    gif_height,
+                    &mut rgba,
+                    GIF_QUANTIZE_SPEED,
+                );
+                frame.delay = delay;
+                encoder.write_frame(&frame)?;
+            }
+            Ok(())
+        });
+        println!(
+            "Capturing {} s of {}x{} GIF at {} FPS to {}",
+            seconds,
+            width,
+            height,
+            fps,
+            path.display()
+        );
+        Some(Self {
+            path: path.to_owned(),
+            width,
+            height,
+            fps,
+            length: (seconds * fps).ceil().max(1.0) as u32,
+            frames: 0,
+            started: Instant::now(),
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    // Adds `frame` if the next sample is due, dropping frames that arrive between
+    // samples. False once the GIF has all its frames, or when `frame` can't be
+    // added; the GIF should then be finished.
+    pub fn push(&mut self, frame: &CapturedFrame) -> bool {
+        if frame.width != self.width || frame.height != self.height {
+            println!(
+                "Frame size changed to {}x{}, ending the GIF early",
+                frame.width, frame.height
+            );
+            return false;
+        }
+        let due = self.frames as f32 / self.fps;
+        if self.started.elapsed().as_secs_f32() < due {
+            return true;
+        }
+        let Some(sender) = self.sender.as_ref() else {
+            return false;
+        };
+        if sender.send(frame.rgba.clone()).is_err() {
+            return false;
+        }
+        self.frames += 1;
+        self.frames < self.length
+    }
+
+    // Waits for the queued frames to be quantized and closes the file
+    pub fn finish(mut self) {
+        self.sender = None;
+        let Some(writer) = self.writer.take() else {
+            return;
+        };
+        match writer.join().expect("GIF writer thread panicked") {
+            Ok(()) => println!("Wrote {} frames to {}", self.frames, self.path.display()),
+            Err(e) => eprintln!("Failed to write {}: {}", self.path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,484 @@
+use std::collections::HashSet;
+
+use ash::vk;
+use naga::{
+    AddressSpace, Binding, Expression, ImageClass, Module, ScalarKind, TypeInner, VectorSize,
+};
+
+use crate::gpu::GpuContext;
+use crate::pipeline::{Specialization, VertexLayout};
+
+// SPIR-V opcodes, capabilities, decorations and storage classes
+// `lower_for_reflection` looks for
+const OP_EXTENSION: u32 = 10;
+const OP_MEMORY_MODEL: u32 = 14;
+const OP_CAPABILITY: u32 = 17;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_FUNCTION: u32 = 54;
+const OP_VARIABLE: u32 = 59;
+const OP_LOAD: u32 = 61;
+const OP_STORE: u32 = 62;
+const OP_DECORATE: u32 = 71;
+const OP_CONVERT_U_TO_PTR: u32 = 120;
+// ShaderNonUniform up to StorageTexelBufferArrayNonUniformIndexing
+const DESCRIPTOR_INDEXING_CAPABILITIES: std::ops::RangeInclusive<u32> = 5301..=5312;
+const DECORATION_NON_UNIFORM: u32 = 5300;
+const DESCRIPTOR_INDEXING_EXTENSION: &[u8] = b"SPV_EXT_descriptor_indexing\0";
+const CAPABILITY_PHYSICAL_STORAGE_BUFFER_ADDRESSES: u32 = 5347;
+const ADDRESSING_LOGICAL: u32 = 0;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+const STORAGE_CLASS_PHYSICAL_STORAGE_BUFFER: u32 = 5349;
+const PHYSICAL_STORAGE_BUFFER_EXTENSION: &[u8] = b"SPV_KHR_physical_storage_buffer\0";
+
+// One descriptor a shader declares, merged across stages by `ShaderInterface`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    pub stage_flags: vk::ShaderStageFlags,
+}
+
+// Resources and inputs read by the shaders of one pipeline, parsed from their
+// SPIR-V with naga. Pipeline layouts are built from this instead of by hand, so
+// a binding or push constant block added to a shader can't silently go missing.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderInterface {
+    pub bindings: Vec<DescriptorBinding>,
+    pub push_constants: Option<vk::PushConstantRange>,
+    // Vertex stage inputs by location, with the 32-bit format matching the GLSL type
+    pub vertex_inputs: Vec<(u32, vk::Format)>,
+    // constant_id of every specialization constant, in any stage
+    pub spec_constants: Vec<u32>,
+}
+
+impl ShaderInterface {
+    // Merges the interfaces of every stage in `stages`
+    pub fn new(stages: &[(&[u8], vk::ShaderStageFlags)]) -> Self {
+        let mut interface = Self::default();
+        for &(spirv, stage) in stages {
+            interface.add_stage(spirv, stage);
+        }
+        interface.bindings.sort_by_key(|b| (b.set, b.binding));
+        interface
+    }
+
+    // Uniform and storage buffers are reflected as plain descriptors; this marks
+    // one as dynamic for passes that bind it with a dynamic offset
+    pub fn with_dynamic(mut self, set: u32, binding: u32) -> Self {
+        let descriptor = self
+            .bindings
+            .iter_mut()
+            .find(|b| b.set == set && b.binding == binding)
+            .expect("Failed to find dynamic binding in shader");
+        descriptor.descriptor_type = match descriptor.descriptor_type {
+            vk::DescriptorType::UNIFORM_BUFFER => vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            vk::DescriptorType::STORAGE_BUFFER => vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+            other => panic!("Descriptor type {:?} can't be dynamic", other),
+        };
+        self
+    }
+
+    // Union of two interfaces, for passes that share one pipeline layout between
+    // several shaders. Vertex inputs are not merged.
+    pub fn merge(mut self, other: &ShaderInterface) -> Self {
+        for &binding in &other.bindings {
+            self.add_binding(binding);
+        }
+        self.bindings.sort_by_key(|b| (b.set, b.binding));
+        for &id in &other.spec_constants {
+            if !self.spec_constants.contains(&id) {
+                self.spec_constants.push(id);
+            }
+        }
+        if let Some(range) = other.push_constants {
+            let merged = self.push_constants.get_or_insert(range);
+            merged.stage_flags |= range.stage_flags;
+            merged.size = merged.size.max(range.size);
+        }
+        self
+    }
+
+    // Number of descriptor sets, counting any gaps below the highest one
+    pub fn set_count(&self) -> u32 {
+        self.bindings.iter().map(|b| b.set + 1).max().unwrap_or(0)
+    }
+
+    pub fn set_bindings(&self, set: u32) -> impl Iterator<Item = &DescriptorBinding> {
+        self.bindings.iter().filter(move |b| b.set == set)
+    }
+
+    // Descriptors needed to allocate `sets` copies of every set
+    pub fn pool_sizes(&self, sets: u32) -> Vec<vk::DescriptorPoolSize> {
+        let mut pool_sizes: Vec<vk::DescriptorPoolSize> = Vec::new();
+        for binding in &self.bindings {
+            match pool_sizes
+                .iter_mut()
+                .find(|size| size.ty == binding.descriptor_type)
+            {
+                Some(size) => size.descriptor_count += binding.count * sets,
+                None => pool_sizes.push(vk::DescriptorPoolSize {
+                    ty: binding.descriptor_type,
+                    descriptor_count: binding.count * sets,
+                }),
+            }
+        }
+        pool_sizes
+    }
+
+    // Bytes of push constants the shaders read, zero without a block
+    pub fn push_constant_size(&self) -> u32 {
+        self.push_constants.map_or(0, |range| range.size)
+    }
+
+    // One layout per set in `0..set_count()`; sets the shaders skip are empty
+    pub fn create_set_layouts(&self, gpu: &GpuContext) -> Vec<vk::DescriptorSetLayout> {
+        (0..self.set_count())
+            .map(|set| {
+                let bindings: Vec<vk::DescriptorSetLayoutBinding> = self
+                    .set_bindings(set)
+                    .map(|b| vk::DescriptorSetLayoutBinding {
+                        binding: b.binding,
+                        descriptor_type: b.descriptor_type,
+                        descriptor_count: b.count,
+                        stage_flags: b.stage_flags,
+                        ..Default::default()
+                    })
+                    .collect();
+                unsafe {
+                    gpu.device
+                        .create_descriptor_set_layout(
+                            &vk::DescriptorSetLayoutCreateInfo {
+                                binding_count: bindings.len() as u32,
+                                p_bindings: bindings.as_ptr(),
+                                ..Default::default()
+                            },
+                            None,
+                        )
+                        .expect("Failed to create descriptor set layout")
+                }
+            })
+            .collect()
+    }
+
+    // `set_layouts` must come from `create_set_layouts`. Panics if the CPU side
+    // pushes `push_constant_size` bytes but the shaders expect a different block;
+    // GLSL may round the block up to its alignment, so a little tail padding is fine.
+    pub fn create_pipeline_layout(
+        &self,
+        gpu: &GpuContext,
+        set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_size: usize,
+    ) -> vk::PipelineLayout {
+        let expected = self.push_constant_size() as usize;
+        assert!(
+            push_constant_size <= expected && expected <= push_constant_size.next_multiple_of(16),
+            "Push constants are {} bytes on the CPU but {} in the shaders",
+            push_constant_size,
+            expected
+        );
+        let push_constant_ranges: Vec<vk::PushConstantRange> =
+            self.push_constants.into_iter().collect();
+        unsafe {
+            gpu.device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo {
+                        set_layout_count: set_layouts.len() as u32,
+                        p_set_layouts: set_layouts.as_ptr(),
+                        push_constant_range_count: push_constant_ranges.len() as u32,
+                        p_push_constant_ranges: push_constant_ranges.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create pipeline layout")
+        }
+    }
+
+    // Panics unless `layout` feeds exactly the locations the vertex shader reads,
+    // each with the format its GLSL type expects. Offsets and stride come from the
+    // Rust vertex structs, so only those are left to the layout.
+    pub fn check_vertex_layout(&self, layout: Option<&VertexLayout>) {
+        let mut provided: Vec<(u32, vk::Format)> = layout
+            .map(|layout| {
+                layout
+                    .attributes
+                    .iter()
+                    .map(|attribute| (attribute.location, attribute.format))
+                    .collect()
+            })
+            .unwrap_or_default();
+        provided.sort_by_key(|&(location, _)| location);
+        assert_eq!(
+            provided, self.vertex_inputs,
+            "Vertex layout doesn't match the vertex shader inputs"
+        );
+    }
+
+    // Panics if a pipeline sets a constant none of the shaders declare, which
+    // would otherwise be silently ignored
+    pub fn check_specialization(&self, specialization: &Specialization) {
+        for id in specialization.ids() {
+            assert!(
+                self.spec_constants.contains(&id),
+                "Shaders have no specialization constant {}",
+                id
+            );
+        }
+    }
+
+    fn add_stage(&mut self, spirv: &[u8], stage: vk::ShaderStageFlags) {
+        let words = lower_for_reflection(spirv);
+        let module =
+            naga::front::spv::parse_u8_slice(bytemuck::cast_slice(&words), &Default::default())
+                .expect("Failed to parse SPIR-V for reflection");
+        // Only globals some expression refers to, as unused ones may remain
+        let functions = module
+            .functions
+            .iter()
+            .map(|(_, function)| function)
+            .chain(module.entry_points.iter().map(|entry| &entry.function));
+        let mut used = HashSet::new();
+        for function in functions {
+            for (_, expression) in function.expressions.iter() {
+                if let Expression::GlobalVariable(handle) = *expression {
+                    used.insert(handle);
+                }
+            }
+        }
+
+        for (handle, global) in module.global_variables.iter() {
+            if !used.contains(&handle) {
+                continue;
+            }
+            if global.space == AddressSpace::PushConstant {
+                let size = module.types[global.ty].inner.size(module.to_ctx());
+                let range = self.push_constants.get_or_insert(vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::empty(),
+                    offset: 0,
+                    size: 0,
+                });
+                range.stage_flags |= stage;
+                range.size = range.size.max(size);
+                continue;
+            }
+            let Some(resource) = &global.binding else {
+                continue;
+            };
+            let (descriptor_type, count) = descriptor_type(&module, global.space, global.ty);
+            self.add_binding(DescriptorBinding {
+                set: resource.group,
+                binding: resource.binding,
+                descriptor_type,
+                count,
+                stage_flags: stage,
+            });
+        }
+
+        for (_, constant) in module.overrides.iter() {
+            if let Some(id) = constant.id {
+                if !self.spec_constants.contains(&(id as u32)) {
+                    self.spec_constants.push(id as u32);
+                }
+            }
+        }
+
+        if stage == vk::ShaderStageFlags::VERTEX {
+            let arguments = &module.entry_points[0].function.arguments;
+            for argument in arguments {
+                match (&argument.binding, &module.types[argument.ty].inner) {
+                    (Some(binding), inner) => self.add_vertex_input(binding, inner),
+                    // Inputs may also arrive wrapped in a struct
+                    (None, TypeInner::Struct { members, .. }) => {
+                        for member in members {
+                            if let Some(binding) = &member.binding {
+                                self.add_vertex_input(binding, &module.types[member.ty].inner);
+                            }
+                        }
+                    }
+                    (None, _) => {}
+                }
+            }
+            self.vertex_inputs.sort_by_key(|&(location, _)| location);
+        }
+    }
+
+    fn add_binding(&mut self, binding: DescriptorBinding) {
+        match self
+            .bindings
+            .iter_mut()
+            .find(|b| b.set == binding.set && b.binding == binding.binding)
+        {
+            Some(existing) => {
+                assert!(
+                    existing.descriptor_type == binding.descriptor_type
+                        && existing.count == binding.count,
+                    "Shaders disagree on set {} binding {}",
+                    binding.set,
+                    binding.binding
+                );
+                existing.stage_flags |= binding.stage_flags;
+            }
+            None => self.bindings.push(binding),
+        }
+    }
+
+    fn add_vertex_input(&mut self, binding: &Binding, inner: &TypeInner) {
+        if let Binding::Location { location, .. } = *binding {
+            self.vertex_inputs.push((location, vertex_format(inner)));
+        }
+    }
+}
+
+// naga's SPIR-V frontend rejects descriptor indexing and buffer device
+// addresses. Neither changes what a shader binds, so reflection reads a copy
+// without them:
+// - descriptor indexing only changes how the shader may index its arrays, so
+//   its capabilities, extension and NonUniform decorations are dropped
+// - pointers made from a device address become StorageBuffer pointers, each
+//   declared as a global with no binding instead, which reflection skips
+fn lower_for_reflection(spirv: &[u8]) -> Vec<u32> {
+    let words: Vec<u32> = spirv
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+    // The header is five words, then each instruction starts with its length
+    let (header, mut rest) = words.split_at(5.min(words.len()));
+    let mut instructions = Vec::new();
+    while let Some(&first) = rest.first() {
+        let length = ((first >> 16) as usize).clamp(1, rest.len());
+        let (instruction, next) = rest.split_at(length);
+        instructions.push(instruction);
+        rest = next;
+    }
+    // (type, id) of every pointer converted from an address
+    let mut address_pointers: Vec<[u32; 2]> = instructions
+        .iter()
+        .filter(|instruction| instruction[0] & 0xffff == OP_CONVERT_U_TO_PTR)
+        .filter_map(|instruction| Some([*instruction.get(1)?, *instruction.get(2)?]))
+        .collect();
+
+    let mut lowered = header.to_vec();
+    for instruction in instructions {
+        let opcode = instruction[0] & 0xffff;
+        let operands = &instruction[1..];
+        match opcode {
+            OP_CAPABILITY
+                if operands.first().is_some_and(|&capability| {
+                    DESCRIPTOR_INDEXING_CAPABILITIES.contains(&capability)
+                        || capability == CAPABILITY_PHYSICAL_STORAGE_BUFFER_ADDRESSES
+                }) => {}
+            OP_EXTENSION
+                if [
+                    DESCRIPTOR_INDEXING_EXTENSION,
+                    PHYSICAL_STORAGE_BUFFER_EXTENSION,
+                ]
+                .iter()
+                .any(|name| bytemuck::cast_slice::<u32, u8>(operands).starts_with(name)) => {}
+            OP_DECORATE if operands.get(1) == Some(&DECORATION_NON_UNIFORM) => {}
+            OP_CONVERT_U_TO_PTR => {}
+            OP_MEMORY_MODEL if operands.len() == 2 => {
+                lowered.extend([instruction[0], ADDRESSING_LOGICAL, operands[1]]);
+            }
+            OP_TYPE_POINTER
+                if operands.len() == 3 && operands[1] == STORAGE_CLASS_PHYSICAL_STORAGE_BUFFER =>
+            {
+                lowered.extend([
+                    instruction[0],
+                    operands[0],
+                    STORAGE_CLASS_STORAGE_BUFFER,
+                    operands[2],
+                ]);
+            }
+            // Accesses through those pointers carry an alignment naga can't read;
+            // the memory operands don't matter to reflection
+            OP_LOAD if operands.len() > 3 => lowered.extend(encode(OP_LOAD, &operands[..3])),
+            OP_STORE if operands.len() > 2 => {
+                lowered.extend(encode(OP_STORE, &operands[..2]));
+            }
+            OP_FUNCTION => {
+                for [ty, id] in address_pointers.drain(..) {
+                    lowered.extend(encode(OP_VARIABLE, &[ty, id, STORAGE_CLASS_STORAGE_BUFFER]));
+                }
+                lowered.extend(instruction);
+            }
+            _ => lowered.extend(instruction),
+        }
+    }
+    lowered
+}
+
+// The words of an instruction: its word count and opcode, then `operands`
+fn encode(opcode: u32, operands: &[u32]) -> impl Iterator<Item = u32> + '_ {
+    let first = ((operands.len() as u32 + 1) << 16) | opcode;
+    std::iter::once(first).chain(operands.iter().copied())
+}
+
+// Descriptor type and array length for a resource global
+fn descriptor_type(
+    module: &Module,
+    space: AddressSpace,
+    ty: naga::Handle<naga::Type>,
+) -> (vk::DescriptorType, u32) {
+    let (inner, count) = match module.types[ty].inner {
+        TypeInner::BindingArray {
+            base,
+            size: naga::ArraySize::Constant(size),
+        } => (&module.types[base].inner, size.get()),
+        ref inner => (inner, 1),
+    };
+    let descriptor_type = match (space, inner) {
+        (AddressSpace::Uniform, _) => vk::DescriptorType::UNIFORM_BUFFER,
+        (AddressSpace::Storage { .. }, _) => vk::DescriptorType::STORAGE_BUFFER,
+        (_, TypeInner::Sampler { .. }) => vk::DescriptorType::SAMPLER,
+        (
+            _,
+            TypeInner::Image {
+                class: ImageClass::Storage { .. },
+                ..
+            },
+        ) => vk::DescriptorType::STORAGE_IMAGE,
+        (_, TypeInner::Image { .. }) => vk::DescriptorType::SAMPLED_IMAGE,
+        (space, inner) => panic!("Unsupported shader resource {:?} {:?}", space, inner),
+    };
+    (descriptor_type, count)
+}
+
+// Vertex attributes are always 32-bit components
+fn vertex_format(inner: &TypeInner) -> vk::Format {
+    let (scalar, components) = match *inner {
+        TypeInner::Scalar(scalar) => (scalar, 1),
+        TypeInner::Vector { size, scalar } => (
+            scalar,
+            match size {
+                VectorSize::Bi => 2,
+                VectorSize::Tri => 3,
+                VectorSize::Quad => 4,
+            },
+        ),
+        ref inner => panic!("Unsupported vertex input type {:?}", inner),
+    };
+    let formats = match scalar.kind {
+        ScalarKind::Float => [
+            vk::Format::R32_SFLOAT,
+            vk::Format::R32G32_SFLOAT,
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        ScalarKind::Sint => [
+            vk::Format::R32_SINT,
+            vk::Format::R32G32_SINT,
+            vk::Format::R32G32B32_SINT,
+            vk::Format::R32G32B32A32_SINT,
+        ],
+        ScalarKind::Uint => [
+            vk::Format::R32_UINT,
+            vk::Format::R32G32_UINT,
+            vk::Format::R32G32B32_UINT,
+            vk::Format::R32G32B32A32_UINT,
+        ],
+        kind => panic!("Unsupported vertex input kind {:?}", kind),
+    };
+    formats[components - 1]
+}
@@ -0,0 +1,165 @@
+// Remote control over a local WebSocket (`remote` feature). Each text message
+// is one JSON request such as `{"cmd": "pause", "paused": true}` and gets one
+// JSON reply: `{"ok": true, "result": ...}` or `{"ok": false, "error": "..."}`.
+// Connections are served on their own threads; requests are queued for the
+// main thread, which answers them between frames with `drain`.
+
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tungstenite::{Message, WebSocket};
+
+// How long a connection waits for the main thread, which only answers while
+// it renders; power save still renders a frame every few hundred milliseconds
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    // Replies with `Stats`
+    Stats,
+    // Replies with every scene name, built-ins first
+    Scenes,
+    // Pauses or resumes the simulation, or toggles it without `paused`
+    Pause {
+        paused: Option<bool>,
+    },
+    TimeScale {
+        value: f32,
+    },
+    // Pixels per second for the scene's main object
+    Velocity {
+        x: f32,
+        y: f32,
+    },
+    // [r, g, b] or [r, g, b, a] in 0..1 for the scene's objects, or null to
+    // restore the scene's own colors
+    Color {
+        color: Option<Vec<f32>>,
+    },
+    Scene {
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Stats {
+    pub fps: f32,
+    // Average over the last second
+    pub frame_ms: f32,
+    pub scene: String,
+    pub paused: bool,
+    pub time_scale: f32,
+}
+
+// What the main thread answers a request with
+pub type Response = Result<Value, String>;
+
+// A request waiting for the main thread
+pub struct PendingRequest {
+    pub request: Request,
+    reply: Sender<Response>,
+}
+
+impl PendingRequest {
+    pub fn reply(self, response: Response) {
+        // The connection may have closed in the meantime
+        let _ = self.reply.send(response);
+    }
+}
+
+pub struct RemoteServer {
+    requests: Receiver<PendingRequest>,
+}
+
+impl RemoteServer {
+    // Listens on 127.0.0.1:`port`, or returns None after printing why not
+    pub fn start(port: u16) -> Option<Self> {
+        let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+            Ok(listener) => listener,
+            Err(error) => {
+                eprintln!("Remote control unavailable on port {}: {}", port, error);
+                return None;
+            }
+        };
+        let (sender, requests) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("remote".to_string())
+            .spawn(move || accept(listener, sender))
+            .expect("Failed to spawn remote control thread");
+        println!("Remote control listening on ws://127.0.0.1:{}", port);
+        Some(Self { requests })
+    }
+
+    // Every request received since the last call, oldest first
+    pub fn drain(&self) -> Vec<PendingRequest> {
+        self.requests.try_iter().collect()
+    }
+}
+
+fn accept(listener: TcpListener, sender: Sender<PendingRequest>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("Remote connection failed: {}", error);
+                continue;
+            }
+        };
+        let sender = sender.clone();
+        std::thread::spawn(move || serve(stream, sender));
+    }
+}
+
+fn serve(stream: TcpStream, sender: Sender<PendingRequest>) {
+    let peer = stream
+        .peer_addr()
+        .map(|address| address.to_string())
+        .unwrap_or_default();
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(error) => {
+            eprintln!("Remote handshake with {} failed: {}", peer, error);
+            return;
+        }
+    };
+    println!("Remote client {} connected", peer);
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) | Err(_) => break,
+            // Pings are answered by tungstenite; binary frames aren't used
+            Ok(_) => continue,
+        };
+        let response = match serde_json::from_str::<Request>(&text) {
+            Ok(request) => forward(&sender, request),
+            Err(error) => Err(format!("bad request: {}", error)),
+        };
+        if send(&mut socket, response).is_err() {
+            break;
+        }
+    }
+    println!("Remote client {} disconnected", peer);
+}
+
+// Hands `request` to the main thread and waits for its answer
+fn forward(sender: &Sender<PendingRequest>, request: Request) -> Response {
+    let (reply, response) = mpsc::channel();
+    sender
+        .send(PendingRequest { request, reply })
+        .map_err(|_| "the app is shutting down".to_string())?;
+    response
+        .recv_timeout(REPLY_TIMEOUT)
+        .map_err(|_| "the app did not answer".to_string())?
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, response: Response) -> tungstenite::Result<()> {
+    let message = match response {
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(error) => json!({ "ok": false, "error": error }),
+    };
+    socket.send(Message::text(message.to_string()))
+}
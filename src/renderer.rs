@@ -0,0 +1,2210 @@
+use ash::vk;
+use glam::{Mat4, Vec2};
+use std::path::Path;
+use std::time::Instant;
+use winit::raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+use winit::window::{Window, WindowId};
+
+use crate::background::{Background, BackgroundPass};
+use crate::breadcrumbs::Breadcrumb;
+use crate::canvas::{Canvas, CanvasOp, CanvasVertex};
+use crate::device_group::{self, GroupFrame};
+use crate::export::FrameExport;
+use crate::fractal::FractalPass;
+use crate::frame_ring::FrameRing;
+use crate::frame_times::{self, FramePhases};
+use crate::geometry::{self, Geometry, Tessellation};
+use crate::gradient::{self, GradientTable};
+use crate::gpu::GpuContext;
+use crate::hdr::{OutputEncoding, Tonemap};
+use crate::ktx;
+use crate::life::LifeSimulation;
+use crate::mesh::{self, MeshData, MeshId, MeshPass};
+use crate::mesh_circles::MeshCirclePass;
+use crate::metaballs::{self, MetaballPass};
+use crate::parallel::ParallelRecorder;
+use crate::particles::ParticleSystem;
+use crate::pipeline::{
+    BlendMode, DebugView, PipelineDesc, PipelineManager, ShaderId, StencilMode, VertexAttribute,
+    VertexLayout,
+};
+use crate::post::{PassOutput, PostEffect, PostProcessor};
+use crate::present::{PresentPacer, PresentTiming};
+use crate::profiling::GpuProfiler;
+#[cfg(feature = "raytracing")]
+use crate::raytracing::{RayTracedSpheres, TracedSphere};
+use crate::recording::{CapturedFrame, FrameReadback};
+use crate::shadertoy::ShaderToyPass;
+use crate::shading_rate::ShadingRateMode;
+use crate::sprite::{SpriteBatch, SpriteInstance, SpriteRenderer, TextureId};
+use crate::surface::required_extensions_for;
+use crate::sync::Submission;
+use crate::visualizer::{self, VisualizerPass};
+use crate::window::{PresentMode, SwapchainOptions, WindowContext};
+
+// One filled circle, optionally with an outline (outline is SDF mode only).
+// `depth` orders translucent circles: larger values are further back.
+#[derive(Clone, Copy, Debug)]
+pub struct Circle {
+    pub position: Vec2,
+    pub radius: f32,
+    pub color: [f32; 4],
+    pub outline_width: f32,
+    pub outline_color: [f32; 4],
+    pub depth: f32,
+}
+
+impl Circle {
+    pub fn new(position: Vec2, radius: f32, color: [f32; 4]) -> Self {
+        Self {
+            position,
+            radius,
+            color,
+            outline_width: 0.0,
+            outline_color: [0.0, 0.0, 0.0, 0.0],
+            depth: 0.0,
+        }
+    }
+
+    pub fn with_outline(mut self, width: f32, color: [f32; 4]) -> Self {
+        self.outline_width = width;
+        self.outline_color = color;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.color[3] >= 1.0 && (self.outline_width <= 0.0 || self.outline_color[3] >= 1.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircleMode {
+    // One quad per circle shaded by a signed distance field: smooth at any size
+    Sdf,
+    // The SDF shader specialized to light each circle like a sphere
+    ShadedSdf,
+    // The original tessellated mesh, kept as a fallback
+    Mesh,
+    // All circles evaluated as one field in a fullscreen pass, so nearby ones merge
+    Metaballs,
+}
+
+impl CircleMode {
+    pub fn next(self) -> Self {
+        match self {
+            CircleMode::Sdf => CircleMode::ShadedSdf,
+            CircleMode::ShadedSdf => CircleMode::Mesh,
+            CircleMode::Mesh => CircleMode::Metaballs,
+            CircleMode::Metaballs => CircleMode::Sdf,
+        }
+    }
+}
+
+// Circle draws from this many on are recorded on worker threads
+const PARALLEL_CIRCLE_THRESHOLD: usize = 1024;
+// Circles per secondary command buffer
+const CIRCLES_PER_CHUNK: usize = 256;
+
+// Specialization constant ids in shaders/circle_sdf_frag.glsl
+const SDF_AA_WIDTH: u32 = 0;
+const SDF_SHADING: u32 = 1;
+
+// Mirrors the push constant block in shaders/vert.glsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ObjectPushConstants {
+    mvp: [f32; 16],
+    color: [f32; 4],
+}
+
+// Mirrors the push constant block in shaders/circle_sdf_*.glsl
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SdfCirclePushConstants {
+    mvp: [f32; 16],
+    color: [f32; 4],
+    outline_color: [f32; 4],
+    params: [f32; 4],
+}
+
+// Unit circle positions, one vec2 per vertex
+const CIRCLE_VERTEX_LAYOUT: VertexLayout = VertexLayout {
+    stride: size_of::<[f32; 2]>() as u32,
+    input_rate: vk::VertexInputRate::VERTEX,
+    attributes: &[VertexAttribute {
+        location: 0,
+        format: vk::Format::R32G32_SFLOAT,
+        offset: 0,
+    }],
+};
+
+const CANVAS_VERTEX_LAYOUT: VertexLayout = VertexLayout {
+    stride: size_of::<CanvasVertex>() as u32,
+    input_rate: vk::VertexInputRate::VERTEX,
+    attributes: &[
+        VertexAttribute {
+            location: 0,
+            format: vk::Format::R32G32_SFLOAT,
+            offset: std::mem::offset_of!(CanvasVertex, position) as u32,
+        },
+        VertexAttribute {
+            location: 1,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+            offset: std::mem::offset_of!(CanvasVertex, color) as u32,
+        },
+        VertexAttribute {
+            location: 2,
+            format: vk::Format::R32_UINT,
+            offset: std::mem::offset_of!(CanvasVertex, gradient) as u32,
+        },
+    ],
+};
+
+// Draws the shared scene once per frame and presents it to every open window.
+// Per-window state lives in `WindowContext`; the primary window sets the size
+// of the scene and its coordinate space.
+pub struct Renderer {
+    pub gpu: GpuContext,
+    primary_window: WindowId,
+    // Every window's swapchain must use this format, since the render pass does
+    surface_format: vk::SurfaceFormatKHR,
+    // Asked of every window, including ones opened later
+    present_mode: PresentMode,
+    image_count: Option<u32>,
+    render_pass: vk::RenderPass,
+    command_buffer: vk::CommandBuffer,
+    // Signaled when the last frame submitted from `command_buffer` finishes, so
+    // it can be reset. Created signaled for the first frame.
+    frame_fence: vk::Fence,
+    // With timeline semaphores: signaled with the frame number when each
+    // frame's commands finish. Null otherwise.
+    frame_timeline: vk::Semaphore,
+    // Frames submitted so far
+    frame_number: u64,
+    // Paces frames to the primary window's presents and times them
+    present_pacer: PresentPacer,
+    // Where the last `render` spent its CPU time
+    frame_phases: FramePhases,
+    pipelines: PipelineManager,
+    circle_pipeline: PipelineDesc,
+    vertex_buffer: vk::Buffer,
+    vertex_buffer_memory: vk::DeviceMemory,
+    circle_index_offset: vk::DeviceSize,
+    circle_index_count: u32,
+    circle_mode: CircleMode,
+    sdf_pipeline: PipelineDesc,
+    canvas_pipeline: PipelineDesc,
+    canvas_gradients: Option<GradientTable>,
+    // Per-frame vertices, instances and uniforms
+    frame_ring: FrameRing,
+    // Records the scene pass on several threads when there are many circles
+    recorder: ParallelRecorder,
+    parallel_recording: bool,
+    // Tracy GPU zones; does nothing without the `profiling` feature
+    gpu_profiler: GpuProfiler,
+    // Where this frame's canvas vertices start in the frame ring; indices follow
+    canvas_offset: vk::DeviceSize,
+    sprites: Option<SpriteRenderer>,
+    metaballs: Option<MetaballPass>,
+    post: Option<PostProcessor>,
+    particles: Option<ParticleSystem>,
+    shadertoy: Option<ShaderToyPass>,
+    // Only while the scene's background is something other than the clear color
+    background: Option<BackgroundPass>,
+    life: Option<LifeSimulation>,
+    fractal: Option<FractalPass>,
+    visualizer: Option<VisualizerPass>,
+    meshes: Option<MeshPass>,
+    // Draws the circles in place of `circle_mode` while set
+    mesh_circles: Option<MeshCirclePass>,
+    #[cfg(feature = "raytracing")]
+    ray_traced: Option<RayTracedSpheres>,
+    // Inner size of the primary window in physical pixels
+    window_extent: vk::Extent2D,
+    // The extent the scene is rendered at: the primary window's swapchain, or
+    // the letterboxed part of it with a locked aspect ratio
+    extent: vk::Extent2D,
+    // Physical pixels per logical unit of the ortho space
+    scale_factor: f32,
+    // Width over height the scene is locked to, if any
+    aspect_ratio: Option<f32>,
+    // Fixed size of the ortho space in logical units, whatever the window size
+    virtual_size: Option<Vec2>,
+    // Windows have per-pixel alpha, so the scene clears to transparent
+    transparent: bool,
+    // Linear RGB the scene pass clears to on opaque windows
+    clear_color: [f32; 3],
+    // Copies of the primary window's frames while capturing
+    readback: Option<FrameReadback>,
+    // Copies of the primary window's frames in exported memory
+    export: Option<FrameExport>,
+}
+
+impl Renderer {
+    // A renderer drawing into a window another application owns, given as raw
+    // handles, without a winit event loop. `width` x `height` is the window's
+    // size in physical pixels. Returns the renderer and the window's context, to
+    // pass to `render` each frame and to `resize` when the host resizes it. The
+    // handles must stay valid until the context is destroyed with `close_window`.
+    pub unsafe fn from_raw_handles(
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+        width: u32,
+        height: u32,
+    ) -> (Self, WindowContext) {
+        let gpu = GpuContext::new(&required_extensions_for(display));
+        let options = SwapchainOptions::default();
+        let mut context =
+            WindowContext::from_raw_handles(&gpu, display, window, width, height, false, &options);
+        let renderer = Self::new(gpu, &mut context);
+        (renderer, context)
+    }
+
+    // Sets up the device-wide resources, with `primary` as the window the scene
+    // is sized for. Its framebuffers are created here.
+    pub fn new(gpu: GpuContext, primary: &mut WindowContext) -> Self {
+        let frame_ring = FrameRing::new(&gpu);
+        let recorder = ParallelRecorder::new(&gpu);
+        let gpu_profiler = GpuProfiler::new(&gpu);
+
+        let mut renderer = Self {
+            gpu,
+            primary_window: primary.id(),
+            surface_format: primary.surface_format(),
+            present_mode: primary.present_mode(),
+            image_count: primary.desired_image_count(),
+            render_pass: vk::RenderPass::null(),
+            command_buffer: vk::CommandBuffer::null(),
+            frame_fence: vk::Fence::null(),
+            frame_timeline: vk::Semaphore::null(),
+            frame_number: 0,
+            present_pacer: PresentPacer::new(),
+            frame_phases: FramePhases::default(),
+            pipelines: PipelineManager::new(vk::RenderPass::null()),
+            circle_pipeline: PipelineDesc::new(ShaderId::default(), vk::PipelineLayout::null()),
+            vertex_buffer: vk::Buffer::null(),
+            vertex_buffer_memory: vk::DeviceMemory::null(),
+            circle_index_offset: 0,
+            circle_index_count: 0,
+            circle_mode: CircleMode::Sdf,
+            sdf_pipeline: PipelineDesc::new(ShaderId::default(), vk::PipelineLayout::null()),
+            canvas_pipeline: PipelineDesc::new(ShaderId::default(), vk::PipelineLayout::null()),
+            canvas_gradients: None,
+            frame_ring,
+            recorder,
+            parallel_recording: false,
+            gpu_profiler,
+            canvas_offset: 0,
+            sprites: None,
+            metaballs: None,
+            post: None,
+            particles: None,
+            shadertoy: None,
+            background: None,
+            life: None,
+            fractal: None,
+            visualizer: None,
+            meshes: None,
+            mesh_circles: None,
+            #[cfg(feature = "raytracing")]
+            ray_traced: None,
+            window_extent: primary.window_extent(),
+            extent: primary.extent(),
+            scale_factor: primary.scale_factor() as f32,
+            aspect_ratio: None,
+            virtual_size: None,
+            transparent: primary.is_transparent(),
+            clear_color: [0.0, 0.0, 0.0],
+            readback: None,
+            export: None,
+        };
+
+        renderer.create_render_pass();
+        // Scene pipelines draw into the post processor's offscreen HDR target
+        let mut post = PostProcessor::new(&renderer.gpu, renderer.render_pass, renderer.extent);
+        post.set_output(OutputEncoding::of(renderer.surface_format), Tonemap::None);
+        renderer.pipelines = PipelineManager::new(post.scene_render_pass());
+        if post.shading_rate().is_some() {
+            renderer.pipelines = renderer.pipelines.with_shading_rate_attachment();
+        }
+        renderer.post = Some(post);
+        primary.create_framebuffers(&renderer.gpu, renderer.render_pass);
+        renderer.create_command_buffer();
+        renderer.create_sync_objects();
+
+        // Vertex buffer creation
+        let circle = geometry::circle(Vec2::ZERO, 1.0, Tessellation::Segments(32));
+        renderer.create_vertex_buffer(&circle);
+
+        // Graphics pipeline creation
+        renderer.create_graphics_pipeline();
+        renderer.create_sdf_pipeline();
+        renderer.create_canvas_pipeline();
+        renderer.sprites = Some(SpriteRenderer::new(&renderer.gpu, &mut renderer.pipelines));
+        renderer.metaballs = Some(MetaballPass::new(&renderer.gpu, &mut renderer.pipelines));
+        println!("Pipelines built: {}", renderer.pipelines.len());
+
+        renderer
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    // Size of the ortho projection space in logical units: the virtual
+    // resolution if there is one, otherwise the swapchain extent divided by the
+    // scale factor, so scenes look the same at any DPI
+    pub fn bounds(&self) -> Vec2 {
+        if let Some(size) = self.virtual_size {
+            return size;
+        }
+        Vec2::new(self.extent.width as f32, self.extent.height as f32) / self.scale_factor
+    }
+
+    // Framebuffer pixels per logical unit of the ortho space
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.extent.width as f32 / self.bounds().x
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    // Called on ScaleFactorChanged; the new physical size arrives as a resize
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        println!("Scale factor set to {}", scale_factor);
+        self.scale_factor = scale_factor;
+    }
+
+    // Converts a position in physical window pixels into the ortho projection space
+    // the scene is drawn in. The two differ when the swapchain extent lags the window.
+    pub fn window_to_ortho(&self, position: Vec2) -> Vec2 {
+        let viewport = self.letterbox(self.window_extent);
+        let offset = Vec2::new(viewport.offset.x as f32, viewport.offset.y as f32);
+        let size = Vec2::new(
+            viewport.extent.width.max(1) as f32,
+            viewport.extent.height.max(1) as f32,
+        );
+        (position - offset) * self.bounds() / size
+    }
+
+    pub fn aspect_ratio(&self) -> Option<f32> {
+        self.aspect_ratio
+    }
+
+    // Locks the scene to `aspect_ratio` (width over height), shown centered with
+    // black bars in every window, or lets it fill the primary window with None
+    pub fn set_aspect_ratio(&mut self, primary: &WindowContext, aspect_ratio: Option<f32>) {
+        println!("Aspect ratio set to {:?}", aspect_ratio);
+        self.aspect_ratio = aspect_ratio;
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        self.follow_primary(primary);
+    }
+
+    pub fn virtual_resolution(&self) -> Option<Vec2> {
+        self.virtual_size
+    }
+
+    // Fixes the ortho space at `size` logical units, scaled to fit the primary
+    // window and letterboxed to its aspect ratio, so scenes behave the same at
+    // any window size. None goes back to following the window.
+    pub fn set_virtual_resolution(&mut self, primary: &WindowContext, size: Option<Vec2>) {
+        println!("Virtual resolution set to {:?}", size);
+        self.virtual_size = size;
+        self.set_aspect_ratio(primary, size.map(|size| size.x / size.y));
+    }
+
+    // The part of a window or swapchain of size `extent` the scene is shown in:
+    // all of it, or the largest centered rectangle with the locked aspect ratio
+    pub fn letterbox(&self, extent: vk::Extent2D) -> vk::Rect2D {
+        let Some(aspect_ratio) = self.aspect_ratio else {
+            return vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            };
+        };
+        let (width, height) = (extent.width as f32, extent.height as f32);
+        let fitted = if width > height * aspect_ratio {
+            vk::Extent2D {
+                width: (height * aspect_ratio).round() as u32,
+                height: extent.height,
+            }
+        } else {
+            vk::Extent2D {
+                width: extent.width,
+                height: (width / aspect_ratio).round() as u32,
+            }
+        };
+        let fitted = vk::Extent2D {
+            width: fitted.width.max(1),
+            height: fitted.height.max(1),
+        };
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: (extent.width.saturating_sub(fitted.width) / 2) as i32,
+                y: (extent.height.saturating_sub(fitted.height) / 2) as i32,
+            },
+            extent: fitted,
+        }
+    }
+
+    pub fn circle_mode(&self) -> CircleMode {
+        self.circle_mode
+    }
+
+    pub fn set_circle_mode(&mut self, mode: CircleMode) {
+        println!("Circle mode set to {:?}", mode);
+        self.circle_mode = mode;
+    }
+
+    // Anti-aliased edge width of SDF circles in pixels. Each width is its own
+    // pipeline variant, built on first use.
+    pub fn set_sdf_aa_width(&mut self, width: f32) {
+        println!("SDF edge width set to {} px", width);
+        self.sdf_pipeline = self.sdf_pipeline.with_constant_f32(SDF_AA_WIDTH, width);
+    }
+
+    // Creates (or replaces) the GPU particle simulation with `count` particles
+    pub fn enable_particles(&mut self, count: u32) {
+        self.disable_particles();
+        let bounds = self.bounds();
+        self.particles = Some(ParticleSystem::new(
+            &self.gpu,
+            &mut self.pipelines,
+            count,
+            bounds,
+        ));
+    }
+
+    // Destroys the particle simulation, if any
+    pub fn disable_particles(&mut self) {
+        if let Some(particles) = self.particles.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            particles.destroy(&self.gpu, &mut self.pipelines);
+            println!("Particle system destroyed");
+        }
+    }
+
+    pub fn particles_mut(&mut self) -> Option<&mut ParticleSystem> {
+        self.particles.as_mut()
+    }
+
+    // Draws a fullscreen ShaderToy fragment shader (see `shadertoy::compile`)
+    // underneath everything else, replacing any previous one
+    pub fn enable_shadertoy(&mut self, fragment_spirv: &[u32]) {
+        self.disable_shadertoy();
+        self.shadertoy = Some(ShaderToyPass::new(
+            &self.gpu,
+            &mut self.pipelines,
+            fragment_spirv,
+        ));
+    }
+
+    pub fn disable_shadertoy(&mut self) {
+        if let Some(shadertoy) = self.shadertoy.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            shadertoy.destroy(&self.gpu.device, &mut self.pipelines);
+            println!("ShaderToy pass destroyed");
+        }
+    }
+
+    pub fn shadertoy_mut(&mut self) -> Option<&mut ShaderToyPass> {
+        self.shadertoy.as_mut()
+    }
+
+    // Runs Game of Life on the GPU at swapchain resolution, drawn as the background
+    pub fn enable_life(&mut self, seed: u32) {
+        self.disable_life();
+        self.life = Some(LifeSimulation::new(
+            &self.gpu,
+            &mut self.pipelines,
+            self.extent,
+            seed,
+        ));
+    }
+
+    pub fn disable_life(&mut self) {
+        if let Some(life) = self.life.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            life.destroy(&self.gpu, &mut self.pipelines);
+            println!("Game of Life destroyed");
+        }
+    }
+
+    pub fn life_mut(&mut self) -> Option<&mut LifeSimulation> {
+        self.life.as_mut()
+    }
+
+    // Draws the Mandelbrot set as the background; the view is set through `fractal_mut`
+    pub fn enable_fractal(&mut self) {
+        self.disable_fractal();
+        self.fractal = Some(FractalPass::new(&self.gpu, &mut self.pipelines));
+    }
+
+    pub fn disable_fractal(&mut self) {
+        if let Some(fractal) = self.fractal.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            fractal.destroy(&self.gpu.device, &mut self.pipelines);
+            println!("Fractal pass destroyed");
+        }
+    }
+
+    pub fn fractal_mut(&mut self) -> Option<&mut FractalPass> {
+        self.fractal.as_mut()
+    }
+
+    // Draws depth-tested 3D meshes over the 2D layers; meshes are uploaded with
+    // `add_mesh` and the camera and draws set through `meshes_mut`
+    pub fn enable_meshes(&mut self) {
+        self.disable_meshes();
+        let scene_view = self.post.as_ref().unwrap().scene_view();
+        let mut meshes = MeshPass::new(&self.gpu, scene_view, self.extent);
+        meshes.set_debug_view(self.pipelines.debug_view());
+        self.meshes = Some(meshes);
+    }
+
+    pub fn disable_meshes(&mut self) {
+        if let Some(mut meshes) = self.meshes.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            meshes.destroy(&self.gpu);
+            println!("Mesh pass destroyed");
+        }
+    }
+
+    // Panics unless `enable_meshes` was called
+    pub fn add_mesh(&mut self, name: &str, data: &MeshData) -> MeshId {
+        self.meshes
+            .as_mut()
+            .expect("Meshes are not enabled")
+            .add_mesh(&self.gpu, name, data)
+    }
+
+    pub fn meshes_mut(&mut self) -> Option<&mut MeshPass> {
+        self.meshes.as_mut()
+    }
+
+    // Generates every circle's geometry in a mesh shader instead of drawing it in
+    // `circle_mode`. Returns false, leaving the circles as they were, without
+    // `capabilities.mesh_shader`.
+    pub fn enable_mesh_circles(&mut self) -> bool {
+        self.disable_mesh_circles();
+        if !self.gpu.capabilities.mesh_shader {
+            println!(
+                "Mesh shaders are not supported, circles keep the {:?} mode",
+                self.circle_mode
+            );
+            return false;
+        }
+        self.mesh_circles = Some(MeshCirclePass::new(&self.gpu, &mut self.pipelines));
+        true
+    }
+
+    pub fn disable_mesh_circles(&mut self) {
+        if let Some(mesh_circles) = self.mesh_circles.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            mesh_circles.destroy(&self.gpu.device, &mut self.pipelines);
+            println!("Mesh shader circle pass destroyed");
+        }
+    }
+
+    // Ray traces `spheres` at swapchain resolution, drawn as the background; the
+    // camera is set through `ray_traced_mut`. Returns false without
+    // `capabilities.ray_tracing`.
+    #[cfg(feature = "raytracing")]
+    pub fn enable_ray_traced_spheres(&mut self, spheres: &[TracedSphere]) -> bool {
+        self.disable_ray_traced_spheres();
+        if !self.gpu.capabilities.ray_tracing {
+            println!("Ray tracing is not supported on this device");
+            return false;
+        }
+        self.ray_traced = Some(RayTracedSpheres::new(
+            &self.gpu,
+            &mut self.pipelines,
+            self.extent,
+            spheres,
+        ));
+        true
+    }
+
+    #[cfg(feature = "raytracing")]
+    pub fn disable_ray_traced_spheres(&mut self) {
+        if let Some(ray_traced) = self.ray_traced.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            ray_traced.destroy(&self.gpu, &mut self.pipelines);
+            println!("Ray traced spheres destroyed");
+        }
+    }
+
+    #[cfg(feature = "raytracing")]
+    pub fn ray_traced_mut(&mut self) -> Option<&mut RayTracedSpheres> {
+        self.ray_traced.as_mut()
+    }
+
+    // Draws spectrum bars and a waveform as the background; the data is set
+    // through `visualizer_mut`
+    pub fn enable_visualizer(&mut self) {
+        self.disable_visualizer();
+        self.visualizer = Some(VisualizerPass::new(&self.gpu, &mut self.pipelines));
+    }
+
+    pub fn disable_visualizer(&mut self) {
+        if let Some(visualizer) = self.visualizer.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            visualizer.destroy(&self.gpu.device, &mut self.pipelines);
+            println!("Visualizer pass destroyed");
+        }
+    }
+
+    pub fn visualizer_mut(&mut self) -> Option<&mut VisualizerPass> {
+        self.visualizer.as_mut()
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.pipelines.debug_view()
+    }
+
+    // Draws the scene and meshes with a debug visualization; overlays drawn
+    // through the canvas show it too, the post chain doesn't. Returns false,
+    // changing nothing, for the wireframe view without fillModeNonSolid.
+    pub fn set_debug_view(&mut self, view: DebugView) -> bool {
+        if view == DebugView::Wireframe && !self.gpu.capabilities.fill_mode_non_solid {
+            return false;
+        }
+        self.pipelines.set_debug_view(view);
+        if let Some(meshes) = self.meshes.as_mut() {
+            meshes.set_debug_view(view);
+        }
+        true
+    }
+
+    // None without `capabilities.fragment_shading_rate`
+    pub fn shading_rate_mode(&self) -> Option<ShadingRateMode> {
+        self.post.as_ref().unwrap().shading_rate().map(|map| map.mode)
+    }
+
+    pub fn set_shading_rate_mode(&mut self, mode: ShadingRateMode) {
+        if let Some(map) = self.post.as_mut().unwrap().shading_rate_mut() {
+            map.mode = mode;
+        }
+    }
+
+    // Keeps full-rate shading around the circle at `center` with `radius`, in
+    // logical units, for this frame and lets the rest of the scene shade more
+    // coarsely. Does nothing without variable rate shading.
+    pub fn set_shading_focus(&mut self, center: Vec2, radius: f32) {
+        let pixels_per_unit = self.pixels_per_unit();
+        if let Some(map) = self.post.as_mut().unwrap().shading_rate_mut() {
+            map.set_focus(center * pixels_per_unit, radius * pixels_per_unit);
+        }
+    }
+
+    // Tints what the last frame shaded coarsely, in the overlay mode only
+    pub fn draw_shading_rate_overlay(&self, canvas: &mut Canvas) {
+        let post = self.post.as_ref().unwrap();
+        if let Some(map) = post
+            .shading_rate()
+            .filter(|map| map.mode == ShadingRateMode::Overlay)
+        {
+            map.draw_overlay(canvas, self.pixels_per_unit());
+        }
+    }
+
+    // The color under every scene without a background of its own
+    pub fn set_clear_color(&mut self, color: [f32; 3]) {
+        self.clear_color = color;
+    }
+
+    pub fn clear_color(&self) -> [f32; 3] {
+        self.clear_color
+    }
+
+    // Replaces the procedural background drawn under everything else.
+    // Windows with per-pixel alpha keep clearing to transparent instead.
+    pub fn set_background(&mut self, background: Background) {
+        if let Some(pass) = self.background.as_mut() {
+            if background != Background::Clear {
+                pass.background = background;
+                return;
+            }
+        }
+        if let Some(pass) = self.background.take() {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+            pass.destroy(&self.gpu.device, &mut self.pipelines);
+            println!("Background pass destroyed");
+        }
+        if background != Background::Clear {
+            self.background = Some(BackgroundPass::new(&self.gpu, &mut self.pipelines, background));
+        }
+    }
+
+    pub fn background(&self) -> Background {
+        self.background
+            .as_ref()
+            .map_or(Background::Clear, |pass| pass.background)
+    }
+
+    // Animates the background by `dt` simulation seconds
+    pub fn advance_background(&mut self, dt: f32) {
+        if let Some(pass) = self.background.as_mut() {
+            pass.advance(dt);
+        }
+    }
+
+    // How frames are written to the swapchain, fixed by the primary window's
+    // surface format
+    pub fn output_encoding(&self) -> OutputEncoding {
+        self.post.as_ref().unwrap().encoding()
+    }
+
+    pub fn tonemap(&self) -> Tonemap {
+        self.post.as_ref().unwrap().tonemap()
+    }
+
+    // Picks how SDR output compresses scene values above 1; HDR output keeps
+    // them, so there it only takes effect if the swapchain is SDR
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        let post = self.post.as_mut().unwrap();
+        post.set_output(post.encoding(), tonemap);
+    }
+
+    pub fn post_effects(&self) -> &[PostEffect] {
+        self.post.as_ref().unwrap().effects()
+    }
+
+    // Replaces the post effect chain applied between the scene and the swapchain
+    pub fn set_post_effects(&mut self, effects: Vec<PostEffect>) {
+        self.post.as_mut().unwrap().set_effects(&self.gpu, effects);
+    }
+
+    // Uploads RGBA8 pixels as a texture usable with `SpriteBatch`
+    pub fn create_texture(&mut self, width: u32, height: u32, rgba: &[u8]) -> TextureId {
+        self.sprites
+            .as_mut()
+            .unwrap()
+            .create_texture(&self.gpu, width, height, rgba)
+    }
+
+    // Loads a KTX2 file as a texture usable with `SpriteBatch`, keeping its
+    // compressed format and mip levels
+    pub fn load_texture(&mut self, path: &Path) -> Result<TextureId, String> {
+        let ktx = ktx::load(path, &self.gpu.capabilities)?;
+        self.sprites
+            .as_mut()
+            .unwrap()
+            .create_ktx_texture(&self.gpu, &ktx)
+    }
+
+    // Frees a texture from `create_texture` or `load_texture`; its id may be reused afterwards
+    pub fn destroy_texture(&mut self, texture: TextureId) {
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        self.sprites
+            .as_mut()
+            .unwrap()
+            .destroy_texture(&self.gpu, texture);
+    }
+
+    fn create_render_pass(&mut self) {
+        // Render pass creation
+        let attachment = vk::AttachmentDescription {
+            format: self.surface_format.format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::STORE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            ..Default::default()
+        };
+        let color_attachment_ref = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        };
+        let subpass = vk::SubpassDescription {
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            color_attachment_count: 1,
+            p_color_attachments: &color_attachment_ref,
+            ..Default::default()
+        };
+        let render_pass_create_info = vk::RenderPassCreateInfo {
+            attachment_count: 1,
+            p_attachments: &attachment,
+            subpass_count: 1,
+            p_subpasses: &subpass,
+            ..Default::default()
+        };
+        self.render_pass = unsafe {
+            self.gpu
+                .device
+                .create_render_pass(&render_pass_create_info, None)
+                .expect("Failed to create render pass")
+        };
+        self.gpu.set_name(self.render_pass, "swapchain render pass");
+        println!("Render pass created: {:?}", self.render_pass);
+    }
+
+    // Makes the swapchain render pass again for `format` and points the post
+    // output pass at it, writing that format's encoding. The device, the scene
+    // pipelines and every other resource stay as they are.
+    fn set_surface_format(&mut self, format: vk::SurfaceFormatKHR) {
+        println!(
+            "Surface format changed from {:?} to {:?}, rebuilding the swapchain render pass",
+            self.surface_format.format, format.format
+        );
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+            self.gpu.device.destroy_render_pass(self.render_pass, None);
+        }
+        self.surface_format = format;
+        self.create_render_pass();
+        let post = self.post.as_mut().unwrap();
+        post.set_present_render_pass(&self.gpu, self.render_pass);
+        post.set_output(OutputEncoding::of(format), post.tonemap());
+        // Exported images are made in the swapchain format
+        if let Some(export) = self.export.as_mut() {
+            if !export.set_format(&self.gpu, format.format) {
+                println!("Frame export stopped: {:?} images can't be exported", format.format);
+                self.export.take().unwrap().destroy(&self.gpu);
+            }
+        }
+    }
+
+    fn create_command_buffer(&mut self) {
+        // Command buffer allocation
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            p_next: std::ptr::null(),
+            _marker: std::marker::PhantomData,
+            command_pool: self.gpu.command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+            command_buffer_count: 1,
+        };
+        self.command_buffer = unsafe {
+            self.gpu
+                .device
+                .allocate_command_buffers(&command_buffer_allocate_info)
+                .expect("Failed to allocate command buffers")[0]
+        };
+        self.gpu
+            .set_name(self.command_buffer, "frame command buffer");
+        println!("Command buffer allocated: {:?}", self.command_buffer);
+    }
+
+    fn create_sync_objects(&mut self) {
+        self.frame_fence = unsafe {
+            self.gpu
+                .device
+                .create_fence(
+                    &vk::FenceCreateInfo {
+                        flags: vk::FenceCreateFlags::SIGNALED,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create frame fence")
+        };
+        self.gpu.set_name(self.frame_fence, "frame fence");
+        println!("Frame fence created: {:?}", self.frame_fence);
+
+        // Frame timeline creation; the acquire and present semaphores are per window
+        if self.gpu.capabilities.timeline_semaphore {
+            let mut type_create_info = vk::SemaphoreTypeCreateInfo {
+                semaphore_type: vk::SemaphoreType::TIMELINE,
+                initial_value: 0,
+                ..Default::default()
+            };
+            self.frame_timeline = unsafe {
+                self.gpu
+                    .device
+                    .create_semaphore(
+                        &vk::SemaphoreCreateInfo::default().push_next(&mut type_create_info),
+                        None,
+                    )
+                    .expect("Failed to create frame timeline semaphore")
+            };
+            self.gpu
+                .set_name(self.frame_timeline, "frame timeline semaphore");
+            println!(
+                "Frame timeline semaphore created: {:?}",
+                self.frame_timeline
+            );
+        }
+    }
+
+    // Number of frames submitted so far; frame N signals value N on the timeline
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+
+    // How long the last `render` spent in each of its phases on the CPU
+    pub fn frame_phases(&self) -> FramePhases {
+        self.frame_phases
+    }
+
+    // Blocks until the GPU has finished frame `frame`. Without a frame timeline
+    // only the latest frame can still be running, since each frame waits for
+    // the one before it, so that one's fence is waited on.
+    pub fn wait_for_frame(&self, frame: u64) {
+        if self.gpu.capabilities.timeline_semaphore {
+            self.gpu.wait_timeline(self.frame_timeline, frame);
+        } else if frame >= self.frame_number {
+            self.wait_for_frame_fence();
+        }
+    }
+
+    // Blocks until at most one of `primary`'s presents is still waiting for the
+    // display, so the next frame reads input closer to when it will be shown.
+    // Call between `render` and the next frame's input and update. Needs
+    // `capabilities.present_wait`.
+    pub fn wait_for_present(&mut self, primary: &WindowContext) {
+        crate::zone!("wait for present");
+        self.present_pacer.wait(&self.gpu, primary);
+    }
+
+    // The primary window's average present interval and latency, once present
+    // wait has measured them
+    pub fn present_timing(&self) -> Option<PresentTiming> {
+        self.present_pacer.timing()
+    }
+
+    fn wait_for_frame_fence(&self) {
+        let result = unsafe {
+            self.gpu
+                .device
+                .wait_for_fences(&[self.frame_fence], true, u64::MAX)
+        };
+        self.gpu.expect(result, "Failed to wait for the frame fence");
+    }
+
+    // Uploads the circle geometry once, into device-local memory: positions
+    // first, then u32 indices
+    fn create_vertex_buffer(&mut self, geometry: &Geometry) {
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&geometry.positions);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&geometry.indices);
+        (self.vertex_buffer, self.vertex_buffer_memory) = self.gpu.create_device_local_buffer(
+            "circle vertex buffer",
+            &[vertex_bytes, index_bytes].concat(),
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER,
+        );
+        self.circle_index_offset = vertex_bytes.len() as vk::DeviceSize;
+        self.circle_index_count = geometry.index_count() as u32;
+        println!("Vertex buffer created: {:?}", self.vertex_buffer);
+    }
+
+    fn create_graphics_pipeline(&mut self) {
+        let shaders = self.pipelines.register_shaders(
+            &self.gpu,
+            "circle mesh",
+            include_bytes!("../shaders/vert.spv"),
+            include_bytes!("../shaders/frag.spv"),
+        );
+        let pipeline_layout =
+            self.create_reflected_layout(shaders, size_of::<ObjectPushConstants>());
+        self.circle_pipeline = PipelineDesc::new(shaders, pipeline_layout)
+            .with_vertex_layout(CIRCLE_VERTEX_LAYOUT);
+
+        // Start both variants now so the first frame has them
+        self.pipelines.prepare(&self.gpu, &self.circle_pipeline);
+        self.pipelines
+            .prepare(&self.gpu, &self.circle_pipeline.with_blend_mode(BlendMode::Alpha));
+    }
+
+    fn create_sdf_pipeline(&mut self) {
+        let shaders = self.pipelines.register_shaders(
+            &self.gpu,
+            "sdf circle",
+            include_bytes!("../shaders/circle_sdf_vert.spv"),
+            include_bytes!("../shaders/circle_sdf_frag.spv"),
+        );
+        let pipeline_layout =
+            self.create_reflected_layout(shaders, size_of::<SdfCirclePushConstants>());
+        // The quad corners come from gl_VertexIndex, so there is no vertex input.
+        // The anti-aliased edge is expressed as alpha coverage.
+        self.sdf_pipeline =
+            PipelineDesc::new(shaders, pipeline_layout).with_blend_mode(BlendMode::Alpha);
+        self.pipelines.prepare(&self.gpu, &self.sdf_pipeline);
+    }
+
+    fn create_canvas_pipeline(&mut self) {
+        let shaders = self.pipelines.register_shaders(
+            &self.gpu,
+            "canvas",
+            include_bytes!("../shaders/canvas_vert.spv"),
+            include_bytes!("../shaders/canvas_frag.spv"),
+        );
+        // The gradients are bound at a different ring offset every frame
+        let interface = self.pipelines.interface(shaders).clone().with_dynamic(0, 0);
+        let gradients = GradientTable::new(&self.gpu, &interface);
+        let pipeline_layout = interface.create_pipeline_layout(
+            &self.gpu,
+            &[gradients.set_layout()],
+            size_of::<Mat4>(),
+        );
+        self.canvas_gradients = Some(gradients);
+        // Canvas shapes carry their own alpha, so always blend
+        self.canvas_pipeline = PipelineDesc::new(shaders, pipeline_layout)
+            .with_vertex_layout(CANVAS_VERTEX_LAYOUT)
+            .with_blend_mode(BlendMode::Alpha);
+        self.canvas_pipelines();
+    }
+
+    // The canvas pipeline for each op, in `CanvasOp` order: shapes are stencil
+    // tested against the clip depth, and clip shapes mark and unmark the stencil
+    fn canvas_pipelines(&mut self) -> [vk::Pipeline; 3] {
+        [StencilMode::Test, StencilMode::Increment, StencilMode::Decrement].map(|stencil| {
+            self.pipelines
+                .get(&self.gpu, &self.canvas_pipeline.with_stencil(stencil))
+        })
+    }
+
+    // These shaders only take push constants, so there are no set layouts
+    fn create_reflected_layout(
+        &self,
+        shaders: ShaderId,
+        push_constant_size: usize,
+    ) -> vk::PipelineLayout {
+        self.pipelines
+            .interface(shaders)
+            .create_pipeline_layout(&self.gpu, &[], push_constant_size)
+    }
+
+    // Moves the frame ring on and copies everything this frame draws from host
+    // memory into it: canvas geometry, sprite instances, the metaball and
+    // visualizer data and the mesh pass's camera and lights
+    fn upload_frame(&mut self, circles: &[Circle], canvas: &Canvas, sprites: &SpriteBatch) {
+        let pixels_per_unit = self.pixels_per_unit();
+        let ring = &mut self.frame_ring;
+        let metaballs = self.circle_mode == CircleMode::Metaballs;
+        let canvas_size = size_of_val(canvas.vertices()) + size_of_val(canvas.indices());
+        let sprite_size = sprites.instance_count() * size_of::<SpriteInstance>();
+        let mut required = ring.padded_size(canvas_size) + ring.padded_size(sprite_size);
+        if !canvas.is_empty() {
+            required += ring.padded_size(gradient::UPLOAD_SIZE);
+        }
+        if metaballs {
+            required += ring.padded_size(metaballs::UPLOAD_SIZE);
+        }
+        if self.visualizer.is_some() {
+            required += ring.padded_size(visualizer::UPLOAD_SIZE);
+        }
+        if self.meshes.is_some() {
+            required += ring.padded_size(mesh::UPLOAD_SIZE);
+        }
+        let shading_rate = self.post.as_mut().unwrap().shading_rate_mut();
+        if let Some(shading_rate) = shading_rate.as_ref() {
+            required += ring.padded_size(shading_rate.upload_size());
+        }
+        ring.begin_frame(&self.gpu, required);
+
+        if !canvas.is_empty() {
+            self.canvas_offset = ring.push_parts(&[
+                bytemuck::cast_slice(canvas.vertices()),
+                bytemuck::cast_slice(canvas.indices()),
+            ]);
+            let gradients = self.canvas_gradients.as_mut().unwrap();
+            gradients.upload(&self.gpu, ring, canvas.gradients());
+        }
+        self.sprites.as_mut().unwrap().upload(ring, sprites);
+        if metaballs {
+            let metaball_pass = self.metaballs.as_mut().unwrap();
+            metaball_pass.upload(&self.gpu, ring, circles, pixels_per_unit);
+        }
+        if let Some(visualizer) = self.visualizer.as_mut() {
+            visualizer.upload(&self.gpu, ring, self.extent);
+        }
+        if let Some(meshes) = self.meshes.as_mut() {
+            meshes.upload(&self.gpu, ring);
+        }
+        if let Some(shading_rate) = shading_rate {
+            shading_rate.upload(ring);
+        }
+    }
+
+    // Draws one frame and presents it to every window in `windows` that isn't
+    // suspended. The scene and post chain run once at the primary window's size;
+    // only the final post pass is repeated, scaling the result to each swapchain.
+    pub fn render(
+        &mut self,
+        windows: &mut [&mut WindowContext],
+        circles: &[Circle],
+        canvas: &Canvas,
+        sprites: &SpriteBatch,
+    ) {
+        crate::zone!("render");
+        self.frame_phases = FramePhases::default();
+        if windows.iter().all(|window| window.is_suspended()) {
+            return;
+        }
+        let mut mark = Instant::now();
+        self.upload_frame(circles, canvas, sprites);
+
+        // Resolve every pipeline this frame needs up front; the manager builds any
+        // variant it hasn't seen yet
+        let circle_desc = match self.circle_mode {
+            CircleMode::Sdf => self.sdf_pipeline,
+            // Same SPIR-V, built as a separate pipeline variant
+            CircleMode::ShadedSdf => self.sdf_pipeline.with_constant_f32(SDF_SHADING, 1.0),
+            CircleMode::Mesh => self.circle_pipeline,
+            CircleMode::Metaballs => self.metaballs.as_ref().unwrap().pipeline(),
+        };
+        let circle_pipelines = [BlendMode::Opaque, BlendMode::Alpha].map(|blend_mode| {
+            // SDF circles and metaballs always blend their anti-aliased edge
+            let blend_mode = match self.circle_mode {
+                CircleMode::Sdf | CircleMode::ShadedSdf | CircleMode::Metaballs => BlendMode::Alpha,
+                CircleMode::Mesh => blend_mode,
+            };
+            self.pipelines
+                .get(&self.gpu, &circle_desc.with_blend_mode(blend_mode))
+        });
+        let canvas_pipelines = self.canvas_pipelines();
+        self.frame_phases.record = frame_times::lap(&mut mark);
+
+        // The command buffer is about to be reset, so the last frame recorded into
+        // it must be done. Its fence is only reset right before the next submit,
+        // in case no window has an image this frame.
+        self.wait_for_frame_fence();
+
+        // On a device group, pick the GPUs that render this frame before
+        // acquiring images for them
+        let group = self.gpu.device_group.map(|group| {
+            let presentable = windows
+                .iter()
+                .filter(|window| !window.is_suspended() && !window.is_minimized())
+                .all(|window| window.supports_group_present());
+            group.frame(self.frame_number, presentable)
+        });
+
+        // Acquire the next image of each window as (window, image index). Windows
+        // whose swapchain is out of date are rebuilt and sit this frame out.
+        let mut targets = Vec::new();
+        for (i, window) in windows.iter_mut().enumerate() {
+            if window.is_suspended() || window.is_minimized() {
+                continue;
+            }
+            window.destroy_retired_swapchains(&self.gpu, false);
+            match window.acquire_image(group.as_ref()) {
+                Some(image_index) => targets.push((i, image_index)),
+                None => self.recreate_swapchain(window),
+            }
+        }
+        self.frame_phases.acquire = frame_times::lap(&mut mark);
+        if targets.is_empty() {
+            return;
+        }
+        let presents: Vec<_> = targets
+            .iter()
+            .map(|&(i, image_index)| {
+                let extent = windows[i].extent();
+                PassOutput {
+                    viewport: self.letterbox(extent),
+                    ..PassOutput::full(windows[i].framebuffer(image_index), extent)
+                }
+            })
+            .collect();
+        let device = &self.gpu.device;
+
+        // Reset command buffer to prevent state corruption
+        unsafe {
+            device
+                .reset_command_buffer(self.command_buffer, vk::CommandBufferResetFlags::empty())
+                .expect("Failed to reset command buffer");
+        }
+        let uploads = self.gpu.take_uploads();
+
+        // Begin command buffer recording
+        unsafe {
+            crate::zone!("record");
+            device
+                .begin_command_buffer(self.command_buffer, &vk::CommandBufferBeginInfo::default())
+                .expect("Failed to begin command buffer");
+            self.gpu_profiler
+                .begin_frame(&self.gpu, self.command_buffer);
+            let frame_zone = self
+                .gpu_profiler
+                .begin_zone(&self.gpu, self.command_buffer, "frame");
+            // Breadcrumbs carry the number this frame gets when it's submitted
+            let frame = self.frame_number + 1;
+            self.gpu
+                .breadcrumb(self.command_buffer, frame, Breadcrumb::FrameStart);
+
+            // Take ownership of anything uploaded on the transfer queue first
+            uploads.record_acquire(&self.gpu, self.command_buffer);
+
+            // Compute work has to be recorded outside the render pass
+            self.gpu
+                .breadcrumb(self.command_buffer, frame, Breadcrumb::Compute);
+            let compute_zone =
+                self.gpu_profiler
+                    .begin_zone(&self.gpu, self.command_buffer, "compute");
+            let bounds = self.bounds();
+            if let Some(particles) = self.particles.as_mut() {
+                particles.record_update(&self.gpu, self.command_buffer, bounds);
+            }
+            if let Some(life) = self.life.as_mut() {
+                life.record_update(&self.gpu, self.command_buffer);
+            }
+            #[cfg(feature = "raytracing")]
+            if let Some(ray_traced) = self.ray_traced.as_mut() {
+                ray_traced.record_trace(&self.gpu, self.command_buffer);
+            }
+            if let Some(shading_rate) = self.post.as_ref().unwrap().shading_rate() {
+                shading_rate.record_copy(&self.gpu, self.command_buffer, &self.frame_ring);
+            }
+            self.gpu_profiler
+                .end_zone(&self.gpu, self.command_buffer, compute_zone);
+            // Every GPU of a group runs the compute work; only this frame's
+            // render the passes
+            if let Some(group) = group.as_ref() {
+                device.cmd_set_device_mask(self.command_buffer, group.device_mask);
+            }
+
+            // Start the offscreen scene pass with the clear color, or
+            // transparent for a window with per-pixel alpha. Overdraw counts up
+            // from black.
+            let [red, green, blue] = match self.pipelines.debug_view() {
+                DebugView::Overdraw => [0.0; 3],
+                _ => self.clear_color,
+            };
+            let clear_color = if self.transparent {
+                [0.0; 4]
+            } else {
+                [red, green, blue, 1.0]
+            };
+            // The stencil starts at zero: inside no canvas clip shape
+            let clear_values = [
+                vk::ClearValue {
+                    color: vk::ClearColorValue {
+                        float32: clear_color,
+                    },
+                },
+                vk::ClearValue {
+                    depth_stencil: vk::ClearDepthStencilValue {
+                        depth: 0.0,
+                        stencil: 0,
+                    },
+                },
+            ];
+            let post = self.post.as_ref().unwrap();
+            let render_pass_begin_info = vk::RenderPassBeginInfo {
+                render_pass: post.scene_render_pass(),
+                framebuffer: post.scene_framebuffer(),
+                render_area: vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: self.extent,
+                },
+                clear_value_count: clear_values.len() as u32,
+                p_clear_values: clear_values.as_ptr(),
+                ..Default::default()
+            };
+
+            // Enough circles to be worth splitting across threads: everything in
+            // the scene pass then has to come from secondary command buffers.
+            // Metaballs, mesh shader circles and split-screen device group
+            // frames are always recorded inline.
+            let parallel = circles.len() >= PARALLEL_CIRCLE_THRESHOLD
+                && self.circle_mode != CircleMode::Metaballs
+                && self.mesh_circles.is_none()
+                && !group.is_some_and(|group| group.is_split());
+            if parallel != self.parallel_recording {
+                self.parallel_recording = parallel;
+                println!(
+                    "Parallel recording {} ({} circles)",
+                    if parallel { "on" } else { "off" },
+                    circles.len()
+                );
+            }
+            let contents = if parallel {
+                vk::SubpassContents::SECONDARY_COMMAND_BUFFERS
+            } else {
+                vk::SubpassContents::INLINE
+            };
+            self.gpu
+                .breadcrumb(self.command_buffer, frame, Breadcrumb::ScenePass);
+            let scene_zone =
+                self.gpu_profiler
+                    .begin_zone(&self.gpu, self.command_buffer, "scene pass");
+            device_group::begin_render_pass(
+                device,
+                self.command_buffer,
+                render_pass_begin_info,
+                contents,
+                group.as_ref(),
+                true,
+            );
+
+            let ortho = Mat4::orthographic_rh(0.0, bounds.x, bounds.y, 0.0, -1.0, 1.0);
+
+            // Opaque circles keep their submission order; translucent ones are drawn
+            // afterwards, back to front, so blending composites correctly
+            let (opaque, mut translucent): (Vec<Circle>, Vec<Circle>) =
+                circles.iter().partition(|circle| circle.is_opaque());
+            translucent.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+            let [opaque_pipeline, blend_pipeline] = circle_pipelines;
+            let batch = CircleBatch {
+                mode: self.circle_mode,
+                pipeline: opaque_pipeline,
+                layout: circle_desc.layout,
+                ortho,
+                extent: self.extent,
+                vertex_buffer: self.vertex_buffer,
+                index_offset: self.circle_index_offset,
+                index_count: self.circle_index_count,
+            };
+
+            if parallel {
+                self.recorder.begin_frame(&self.gpu);
+                let inheritance = vk::CommandBufferInheritanceInfo {
+                    render_pass: render_pass_begin_info.render_pass,
+                    subpass: 0,
+                    framebuffer: render_pass_begin_info.framebuffer,
+                    ..Default::default()
+                };
+                let background = self.recorder.begin_secondary(&self.gpu, &inheritance);
+                self.record_background_layers(background, sprites, &ortho, None);
+                ParallelRecorder::end_secondary(&self.gpu.device, background);
+
+                let mut secondaries = vec![background];
+                let device = &self.gpu.device;
+                for (circles, pipeline) in
+                    [(&opaque, opaque_pipeline), (&translucent, blend_pipeline)]
+                {
+                    let batch = CircleBatch { pipeline, ..batch };
+                    secondaries.extend(self.recorder.record_chunks(
+                        &self.gpu,
+                        &inheritance,
+                        circles,
+                        CIRCLES_PER_CHUNK,
+                        |command_buffer, chunk| {
+                            set_viewport(device, command_buffer, batch.extent, None);
+                            batch.record(device, command_buffer, chunk);
+                        },
+                    ));
+                }
+
+                let overlay = self.recorder.begin_secondary(&self.gpu, &inheritance);
+                set_viewport(device, overlay, self.extent, None);
+                self.record_canvas(overlay, canvas, canvas_pipelines, &ortho);
+                ParallelRecorder::end_secondary(device, overlay);
+                secondaries.push(overlay);
+
+                device.cmd_execute_commands(self.command_buffer, &secondaries);
+            } else {
+                self.record_background_layers(
+                    self.command_buffer,
+                    sprites,
+                    &ortho,
+                    group.as_ref(),
+                );
+                let device = &self.gpu.device;
+                match (self.mesh_circles.as_ref(), self.circle_mode) {
+                    (Some(mesh_circles), _) => {
+                        for (circles, blend_mode) in
+                            [(&opaque, BlendMode::Opaque), (&translucent, BlendMode::Alpha)]
+                        {
+                            mesh_circles.record_draw(
+                                &self.gpu,
+                                &mut self.pipelines,
+                                self.command_buffer,
+                                circles,
+                                &ortho,
+                                blend_mode,
+                            );
+                        }
+                    }
+                    (None, CircleMode::Metaballs) => {
+                        // One fullscreen draw covers every circle, so order doesn't matter
+                        self.metaballs.as_ref().unwrap().record_draw(
+                            device,
+                            self.command_buffer,
+                            blend_pipeline,
+                        );
+                    }
+                    _ => {
+                        batch.record(device, self.command_buffer, &opaque);
+                        CircleBatch {
+                            pipeline: blend_pipeline,
+                            ..batch
+                        }
+                        .record(device, self.command_buffer, &translucent);
+                    }
+                }
+                self.record_canvas(self.command_buffer, canvas, canvas_pipelines, &ortho);
+            }
+
+            // End the scene pass and draw any meshes over it, then run the post
+            // chain into the swapchain image
+            let device = &self.gpu.device;
+            device.cmd_end_render_pass(self.command_buffer);
+            self.gpu
+                .breadcrumb(self.command_buffer, frame, Breadcrumb::Meshes);
+            if let Some(meshes) = self.meshes.as_mut() {
+                meshes.record(&self.gpu, self.command_buffer, group.as_ref());
+            }
+            self.gpu_profiler
+                .end_zone(&self.gpu, self.command_buffer, scene_zone);
+            self.gpu
+                .breadcrumb(self.command_buffer, frame, Breadcrumb::PostProcessing);
+            let post_zone =
+                self.gpu_profiler
+                    .begin_zone(&self.gpu, self.command_buffer, "post-processing");
+            self.post
+                .as_mut()
+                .unwrap()
+                .record(&self.gpu, self.command_buffer, &presents, group.as_ref());
+            // Readbacks copy the instance of the GPU that signals the frame
+            if let Some(group) = group.as_ref() {
+                device.cmd_set_device_mask(self.command_buffer, 1 << group.device_index);
+            }
+            // Copy the primary window's frame out while capturing
+            let primary_target = targets
+                .iter()
+                .find(|&&(i, _)| windows[i].id() == self.primary_window)
+                .filter(|&&(i, _)| windows[i].supports_readback());
+            if let (Some(readback), Some(&(i, image_index))) =
+                (self.readback.as_mut(), primary_target)
+            {
+                readback.record_copy(
+                    &self.gpu,
+                    self.command_buffer,
+                    windows[i].image(image_index),
+                    windows[i].extent(),
+                    self.surface_format.format,
+                    self.frame_number + 1,
+                );
+            }
+            if let (Some(export), Some(&(i, image_index))) = (self.export.as_mut(), primary_target)
+            {
+                export.record_copy(
+                    &self.gpu,
+                    self.command_buffer,
+                    windows[i].image(image_index),
+                    windows[i].extent(),
+                    self.frame_number + 1,
+                );
+            }
+            self.gpu_profiler
+                .end_zone(&self.gpu, self.command_buffer, post_zone);
+            self.gpu_profiler
+                .end_zone(&self.gpu, self.command_buffer, frame_zone);
+            self.gpu
+                .breadcrumb(self.command_buffer, frame, Breadcrumb::FrameEnd);
+            device
+                .end_command_buffer(self.command_buffer)
+                .expect("Failed to end command buffer");
+        }
+        self.frame_phases.record += frame_times::lap(&mut mark);
+
+        {
+            // Submit commands to the queue
+            crate::zone!("submit");
+            self.frame_number += 1;
+            let mut submission = Submission::new(self.command_buffer);
+            if let Some(group) = group.as_ref() {
+                let all_devices = self.gpu.device_group.unwrap().all_devices();
+                submission = submission.with_devices(all_devices, group.device_index);
+            }
+            for &(i, _) in &targets {
+                submission = submission
+                    .with_wait(
+                        windows[i].image_available_semaphore(),
+                        0,
+                        vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                    )
+                    .with_signal(
+                        windows[i].render_finished_semaphore(),
+                        0,
+                        vk::PipelineStageFlags2::ALL_COMMANDS,
+                    );
+            }
+            for &semaphore in uploads.semaphores() {
+                submission = submission.with_wait(semaphore, 0, uploads.wait_stage());
+            }
+            if self.gpu.capabilities.timeline_semaphore {
+                submission = submission.with_signal(
+                    self.frame_timeline,
+                    self.frame_number,
+                    vk::PipelineStageFlags2::ALL_COMMANDS,
+                );
+            }
+            if let Some(export) = self.export.as_ref() {
+                submission = submission.with_signal(
+                    export.semaphore(),
+                    self.frame_number,
+                    vk::PipelineStageFlags2::ALL_COMMANDS,
+                );
+            }
+            unsafe {
+                self.gpu
+                    .device
+                    .reset_fences(&[self.frame_fence])
+                    .expect("Failed to reset the frame fence");
+            }
+            submission.submit(&self.gpu, self.gpu.queue, self.frame_fence);
+            self.gpu.retire_uploads(uploads);
+        }
+        self.frame_phases.submit = frame_times::lap(&mut mark);
+
+        // Present the rendered image to every window at once
+        crate::zone!("present");
+        let presented: Vec<_> = targets
+            .iter()
+            .map(|&(i, image_index)| (&*windows[i], image_index))
+            .collect();
+        let out_of_date = WindowContext::present_all(
+            &self.gpu,
+            &presented,
+            group.as_ref(),
+            self.frame_number,
+        );
+        if let Some(position) = targets
+            .iter()
+            .position(|&(i, _)| windows[i].id() == self.primary_window)
+        {
+            if !out_of_date.contains(&position) {
+                self.present_pacer
+                    .presented(windows[targets[position].0], self.frame_number);
+            }
+        }
+        for position in out_of_date {
+            let (i, _) = targets[position];
+            self.recreate_swapchain(windows[i]);
+        }
+        self.frame_phases.present = frame_times::lap(&mut mark);
+    }
+
+    // Starts or stops copying the primary window's frames to host memory, for
+    // `read_frame`. Returns false when its swapchain can't be read back.
+    pub fn set_capture(&mut self, primary: &WindowContext, capture: bool) -> bool {
+        if capture && !primary.supports_readback() {
+            return false;
+        }
+        match (capture, self.readback.as_mut()) {
+            (true, None) => self.readback = Some(FrameReadback::new()),
+            (false, Some(readback)) => {
+                unsafe {
+                    self.gpu
+                        .device
+                        .device_wait_idle()
+                        .expect("Failed to wait for device idle");
+                }
+                readback.destroy(&self.gpu);
+                self.readback = None;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.readback.is_some()
+    }
+
+    // Starts or stops copying the primary window's frames into exported memory
+    // for another API or process; see `FrameExport`. Returns false when the
+    // swapchain can't be copied or the device can't export.
+    pub fn set_frame_export(&mut self, primary: &WindowContext, export: bool) -> bool {
+        if !export {
+            if let Some(export) = self.export.take() {
+                unsafe {
+                    self.gpu
+                        .device
+                        .device_wait_idle()
+                        .expect("Failed to wait for device idle");
+                }
+                export.destroy(&self.gpu);
+            }
+            return true;
+        }
+        if self.export.is_none() && primary.supports_readback() {
+            self.export = FrameExport::new(&self.gpu, self.surface_format.format);
+        }
+        self.export.is_some()
+    }
+
+    // The exported images and semaphore, once `set_frame_export` is on. The images
+    // exist from the first frame after that.
+    pub fn frame_export(&self) -> Option<&FrameExport> {
+        self.export.as_ref()
+    }
+
+    // The last frame of the primary window the GPU has finished, as RGBA8, while
+    // `set_capture` is on. This never blocks and is usually the previous
+    // `render`'s frame, since `render` waits for that one. Without a frame
+    // timeline the frame fence tells whether the latest frame is done too. None
+    // until a copied frame has completed.
+    pub fn read_frame(&self) -> Option<CapturedFrame> {
+        let readback = self.readback.as_ref()?;
+        let completed = if self.gpu.capabilities.timeline_semaphore {
+            self.gpu.timeline_value(self.frame_timeline)
+        } else {
+            let latest_done = unsafe {
+                self.gpu
+                    .device
+                    .get_fence_status(self.frame_fence)
+                    .expect("Failed to query the frame fence")
+            };
+            if latest_done {
+                self.frame_number
+            } else {
+                self.frame_number - 1
+            }
+        };
+        readback.latest(completed)
+    }
+
+    // Fullscreen passes, sprites and particles: everything under the circles
+    fn record_background_layers(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        sprites: &SpriteBatch,
+        ortho: &Mat4,
+        group: Option<&GroupFrame>,
+    ) {
+        set_viewport(&self.gpu.device, command_buffer, self.extent, group);
+        if let Some(background) = self.background.as_ref().filter(|_| !self.transparent) {
+            let resolution = Vec2::new(self.extent.width as f32, self.extent.height as f32);
+            background.record_draw(&self.gpu, &mut self.pipelines, command_buffer, resolution);
+        }
+        if let Some(shadertoy) = self.shadertoy.as_ref() {
+            shadertoy.record_draw(&self.gpu, &mut self.pipelines, command_buffer);
+        }
+        if let Some(life) = self.life.as_ref() {
+            life.record_draw(&self.gpu, &mut self.pipelines, command_buffer);
+        }
+        #[cfg(feature = "raytracing")]
+        if let Some(ray_traced) = self.ray_traced.as_ref() {
+            ray_traced.record_draw(&self.gpu, &mut self.pipelines, command_buffer);
+        }
+        if let Some(fractal) = self.fractal.as_ref() {
+            fractal.record_draw(&self.gpu, &mut self.pipelines, command_buffer);
+        }
+        if let Some(visualizer) = self.visualizer.as_ref() {
+            visualizer.record_draw(&self.gpu, &mut self.pipelines, command_buffer);
+        }
+
+        // Sprites go underneath everything but the fullscreen backgrounds
+        self.sprites.as_ref().unwrap().record(
+            &self.gpu,
+            &mut self.pipelines,
+            command_buffer,
+            sprites,
+            ortho,
+        );
+
+        if let Some(particles) = self.particles.as_ref() {
+            particles.record_draw(&self.gpu, &mut self.pipelines, command_buffer, ortho);
+        }
+    }
+
+    // The immediate-mode canvas, on top of everything else. `pipelines` are
+    // from `canvas_pipelines`.
+    fn record_canvas(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        canvas: &Canvas,
+        pipelines: [vk::Pipeline; 3],
+        ortho: &Mat4,
+    ) {
+        if canvas.is_empty() {
+            return;
+        }
+        let device = &self.gpu.device;
+        let [draw_pipeline, push_clip_pipeline, pop_clip_pipeline] = pipelines;
+        unsafe {
+            let ring_buffer = self.frame_ring.buffer();
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[ring_buffer],
+                &[self.canvas_offset],
+            );
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                ring_buffer,
+                self.canvas_offset + size_of_val(canvas.vertices()) as vk::DeviceSize,
+                vk::IndexType::UINT32,
+            );
+            self.canvas_gradients.as_ref().unwrap().bind(
+                device,
+                command_buffer,
+                self.canvas_pipeline.layout,
+            );
+            let ortho_array = ortho.to_cols_array();
+            device.cmd_push_constants(
+                command_buffer,
+                self.canvas_pipeline.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::cast_slice(&ortho_array),
+            );
+            let mut bound = vk::Pipeline::null();
+            for batch in canvas.batches() {
+                let pipeline = match batch.op {
+                    CanvasOp::Draw => draw_pipeline,
+                    CanvasOp::PushClip => push_clip_pipeline,
+                    CanvasOp::PopClip => pop_clip_pipeline,
+                };
+                if pipeline != bound {
+                    device.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline,
+                    );
+                    bound = pipeline;
+                }
+                device.cmd_set_stencil_reference(
+                    command_buffer,
+                    vk::StencilFaceFlags::FRONT_AND_BACK,
+                    batch.stencil_reference,
+                );
+                device.cmd_draw_indexed(
+                    command_buffer,
+                    batch.index_count,
+                    1,
+                    batch.first_index,
+                    0,
+                    0,
+                );
+            }
+        }
+    }
+
+    pub fn primary_window(&self) -> WindowId {
+        self.primary_window
+    }
+
+    // Creates the swapchain of another window showing the same scene. It is
+    // transparent if the primary window is. None, with the window closed, when
+    // its surface lacks the primary window's format, e.g. an SDR monitor next
+    // to an HDR one.
+    pub fn open_window(&mut self, window: Window) -> Option<WindowContext> {
+        let options = SwapchainOptions {
+            format: Some(self.surface_format),
+            present_mode: self.present_mode,
+            image_count: self.image_count,
+            ..Default::default()
+        };
+        let mut context = WindowContext::new(&self.gpu, window, self.transparent, &options);
+        // The swapchain render pass and its pipelines are shared
+        if context.surface_format() != self.surface_format {
+            eprintln!(
+                "Window not opened: its surface lacks the primary window's format {:?} in {:?}",
+                self.surface_format.format, self.surface_format.color_space
+            );
+            context.destroy(&self.gpu);
+            return None;
+        }
+        context.create_framebuffers(&self.gpu, self.render_pass);
+        Some(context)
+    }
+
+    pub fn close_window(&mut self, context: WindowContext) {
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        context.destroy(&self.gpu);
+    }
+
+    // Like `close_window`, handing the winit window back so it can be opened
+    // again on another renderer. None for an external window.
+    pub fn release_window(&mut self, context: WindowContext) -> Option<Window> {
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        context.into_window(&self.gpu)
+    }
+
+    // Frees every pass, pipeline and buffer, then the device and instance. The
+    // scene's resources must be destroyed and every window closed or released
+    // first. Textures still registered are freed along with the sprite renderer.
+    pub fn destroy(mut self) {
+        self.disable_particles();
+        self.disable_shadertoy();
+        self.disable_life();
+        self.disable_fractal();
+        self.disable_meshes();
+        self.disable_mesh_circles();
+        #[cfg(feature = "raytracing")]
+        self.disable_ray_traced_spheres();
+        self.disable_visualizer();
+        self.set_background(Background::Clear);
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        let gpu = &self.gpu;
+        if let Some(mut readback) = self.readback.take() {
+            readback.destroy(gpu);
+        }
+        if let Some(export) = self.export.take() {
+            export.destroy(gpu);
+        }
+        if let Some(mut sprites) = self.sprites.take() {
+            sprites.destroy(gpu);
+        }
+        if let Some(metaballs) = self.metaballs.take() {
+            metaballs.destroy(&gpu.device, &mut self.pipelines);
+        }
+        if let Some(mut post) = self.post.take() {
+            post.destroy(gpu);
+        }
+        if let Some(gradients) = self.canvas_gradients.take() {
+            gradients.destroy(gpu);
+        }
+        self.frame_ring.destroy(gpu);
+        self.recorder.destroy(gpu);
+        self.gpu_profiler.destroy(gpu);
+        self.pipelines.destroy(&gpu.device);
+        unsafe {
+            let device = &gpu.device;
+            for pipeline in [self.circle_pipeline, self.sdf_pipeline, self.canvas_pipeline] {
+                device.destroy_pipeline_layout(pipeline.layout, None);
+            }
+            device.destroy_buffer(self.vertex_buffer, None);
+            device.destroy_semaphore(self.frame_timeline, None);
+            device.destroy_fence(self.frame_fence, None);
+            device.destroy_render_pass(self.render_pass, None);
+        }
+        gpu.free_memory(self.vertex_buffer_memory);
+        println!("Renderer destroyed");
+        self.gpu.destroy();
+    }
+
+    pub fn resize(&mut self, window: &mut WindowContext, width: u32, height: u32) {
+        window.set_window_extent(width, height);
+        // The new size is picked up on resume, or when the window is restored
+        if !window.is_suspended() && !window.is_minimized() {
+            self.recreate_swapchain(window);
+        }
+    }
+
+    // Destroys the window's swapchain and surface, keeping the device and every
+    // other resource
+    pub fn suspend(&mut self, window: &mut WindowContext) {
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        window.suspend(&self.gpu);
+    }
+
+    // Creates a new surface and swapchain for the window, reusing the instance,
+    // device, pipelines and scene resources. Resume the primary window first:
+    // if its surface comes back with another format, the swapchain render pass
+    // and what draws into it are made again for that. Other windows ask for the
+    // primary's format; false when a surface lacks it, for the caller to close
+    // that window.
+    pub fn resume(&mut self, window: &mut WindowContext) -> bool {
+        if !window.is_suspended() {
+            return true;
+        }
+        let primary = window.id() == self.primary_window;
+        if !primary {
+            window.set_preferred_format(Some(self.surface_format));
+        }
+        window.resume(&self.gpu);
+        if window.surface_format() != self.surface_format {
+            if !primary {
+                eprintln!(
+                    "Window closed: its surface lacks the primary window's format {:?} in {:?}",
+                    self.surface_format.format, self.surface_format.color_space
+                );
+                return false;
+            }
+            self.set_surface_format(window.surface_format());
+        }
+        window.create_framebuffers(&self.gpu, self.render_pass);
+        if primary {
+            // The host keeps an external window's scale factor up to date itself
+            if !window.is_external() {
+                self.scale_factor = window.scale_factor() as f32;
+            }
+            self.follow_primary(window);
+        }
+        true
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    // Presents with `mode` from now on, in every window of `windows` and any
+    // opened later. Each window falls back on its own if its surface lacks it.
+    pub fn set_present_mode(&mut self, windows: &mut [&mut WindowContext], mode: PresentMode) {
+        self.present_mode = mode;
+        for window in windows.iter_mut() {
+            window.set_present_mode(mode);
+            // Suspended and minimized windows pick it up with their next swapchain
+            if !window.is_suspended() && !window.is_minimized() {
+                self.recreate_swapchain(window);
+            }
+        }
+    }
+
+    // Switches `window` to or from exclusive fullscreen. False when the device or
+    // the surface doesn't support it, in which case borderless fullscreen is all
+    // there is.
+    pub fn set_exclusive_fullscreen(
+        &mut self,
+        window: &mut WindowContext,
+        exclusive: bool,
+    ) -> bool {
+        if !self.gpu.capabilities.full_screen_exclusive || window.is_suspended() {
+            return false;
+        }
+        unsafe {
+            self.gpu
+                .device
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+        }
+        let set = window.set_exclusive(&self.gpu, self.render_pass, exclusive);
+        if set && window.id() == self.primary_window {
+            self.follow_primary(window);
+        }
+        set
+    }
+
+    fn recreate_swapchain(&mut self, window: &mut WindowContext) {
+        crate::zone!("recreate swapchain");
+        if self.gpu.capabilities.swapchain_maintenance1 {
+            // Every frame is one command buffer, so once the last one is done
+            // nothing uses the old framebuffers or the scene targets. The present
+            // fences say when the old swapchain can go.
+            self.wait_for_frame_fence();
+        } else {
+            unsafe {
+                self.gpu
+                    .device
+                    .device_wait_idle()
+                    .expect("Failed to wait for device idle");
+            }
+        }
+        window.recreate_swapchain(&self.gpu, self.render_pass);
+        if window.id() == self.primary_window {
+            self.follow_primary(window);
+        }
+    }
+
+    // Resizes the scene to the primary window's new swapchain (no frame running)
+    fn follow_primary(&mut self, primary: &WindowContext) {
+        self.window_extent = primary.window_extent();
+        self.extent = self.letterbox(primary.extent()).extent;
+        self.post.as_mut().unwrap().resize(&self.gpu, self.extent);
+        if let Some(life) = self.life.as_mut() {
+            life.resize(&self.gpu, self.extent);
+        }
+        #[cfg(feature = "raytracing")]
+        if let Some(ray_traced) = self.ray_traced.as_mut() {
+            ray_traced.resize(&self.gpu, self.extent);
+        }
+        if let Some(meshes) = self.meshes.as_mut() {
+            let scene_view = self.post.as_ref().unwrap().scene_view();
+            meshes.resize(&self.gpu, scene_view, self.extent);
+        }
+    }
+}
+
+// Viewport and scissor are dynamic state, which secondary command buffers don't
+// inherit, so each one sets them again. In a split-screen `group` frame each
+// GPU's scissor is narrowed to its strip.
+fn set_viewport(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    extent: vk::Extent2D,
+    group: Option<&GroupFrame>,
+) {
+    let viewport = vk::Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: extent.width as f32,
+        height: extent.height as f32,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    };
+    let scissor = vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent,
+    };
+    unsafe {
+        device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+    }
+    device_group::set_scissor(device, command_buffer, scissor, extent, group);
+}
+
+// Everything needed to record circle draws, copied out of the renderer so worker
+// threads can record chunks of circles without borrowing it
+#[derive(Clone, Copy)]
+struct CircleBatch {
+    mode: CircleMode,
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    ortho: Mat4,
+    extent: vk::Extent2D,
+    // The unit circle mesh, for CircleMode::Mesh
+    vertex_buffer: vk::Buffer,
+    index_offset: vk::DeviceSize,
+    index_count: u32,
+}
+
+impl CircleBatch {
+    fn record(&self, device: &ash::Device, command_buffer: vk::CommandBuffer, circles: &[Circle]) {
+        if circles.is_empty() {
+            return;
+        }
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+        }
+        match self.mode {
+            CircleMode::Sdf | CircleMode::ShadedSdf => {
+                self.record_sdf(device, command_buffer, circles)
+            }
+            CircleMode::Mesh => self.record_mesh(device, command_buffer, circles),
+            CircleMode::Metaballs => unreachable!("Metaballs are drawn by MetaballPass"),
+        }
+    }
+
+    // One quad per circle, padded by a couple of pixels so the anti-aliased edge
+    // and outline are never clipped
+    fn record_sdf(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        circles: &[Circle],
+    ) {
+        for circle in circles {
+            let half_size = circle.radius + 2.0;
+            let transform = Mat4::from_translation(circle.position.extend(0.0))
+                * Mat4::from_scale(glam::Vec3::new(half_size, half_size, 1.0));
+            let push_constants = SdfCirclePushConstants {
+                mvp: (self.ortho * transform).to_cols_array(),
+                color: circle.color,
+                outline_color: circle.outline_color,
+                params: [circle.radius, circle.outline_width, half_size, 0.0],
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    command_buffer,
+                    self.layout,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    bytemuck::bytes_of(&push_constants),
+                );
+                device.cmd_draw(command_buffer, 6, 1, 0, 0);
+            }
+        }
+    }
+
+    fn record_mesh(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        circles: &[Circle],
+    ) {
+        unsafe {
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.vertex_buffer], &[0]);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                self.vertex_buffer,
+                self.index_offset,
+                vk::IndexType::UINT32,
+            );
+        }
+
+        // Draw each circle (indexed triangle list, 32 segments around a center
+        // vertex) with its own transform and color
+        for circle in circles {
+            let transform = Mat4::from_translation(circle.position.extend(0.0))
+                * Mat4::from_scale(glam::Vec3::new(circle.radius, circle.radius, 1.0));
+            let push_constants = ObjectPushConstants {
+                mvp: (self.ortho * transform).to_cols_array(),
+                color: circle.color,
+            };
+            unsafe {
+                device.cmd_push_constants(
+                    command_buffer,
+                    self.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    bytemuck::bytes_of(&push_constants),
+                );
+                device.cmd_draw_indexed(command_buffer, self.index_count, 1, 0, 0, 0);
+            }
+        }
+    }
+}
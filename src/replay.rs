@@ -0,0 +1,145 @@
+// Input recordings (--record-input) and their deterministic replay (--replay).
+// A recording is JSON lines: a header, then one line per rendered frame with
+// that frame's input, its wall-clock time and the number of fixed steps it
+// simulated. Replaying feeds these back in place of live input and the clock,
+// so the same seed, scene and window size reproduce the simulation exactly.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::input::InputState;
+
+// Bumped whenever a recording from an older build can't be replayed
+pub const RECORDING_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub seed: u64,
+    // `SceneKind::name` of the starting scene
+    pub scene: String,
+    // Scene bounds in logical pixels; the replay window opens at this size
+    pub window_size: Vec2,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub frame_dt: f32,
+    pub steps: u32,
+    // Left analog stick, zero without a gamepad
+    pub stick: Vec2,
+    pub input: InputState,
+}
+
+pub struct InputRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    frames: u64,
+    failed: bool,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path, header: &RecordingHeader) -> Result<Self, String> {
+        let file = File::create(path).map_err(|error| error.to_string())?;
+        let mut recorder = Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            frames: 0,
+            failed: false,
+        };
+        recorder.write_line(header)?;
+        println!("Recording input to {}", path.display());
+        Ok(recorder)
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> Result<(), String> {
+        serde_json::to_writer(&mut self.writer, value).map_err(|error| error.to_string())?;
+        self.writer.write_all(b"\n").map_err(|error| error.to_string())
+    }
+
+    // Appends one frame; after a write error the rest of the run is dropped
+    pub fn push(&mut self, frame: &RecordedFrame) {
+        if self.failed {
+            return;
+        }
+        match self.write_line(frame) {
+            Ok(()) => self.frames += 1,
+            Err(error) => {
+                eprintln!("Input recording to {} failed: {}", self.path.display(), error);
+                self.failed = true;
+            }
+        }
+    }
+
+    pub fn finish(mut self) {
+        if let Err(error) = self.writer.flush() {
+            eprintln!("Input recording to {} failed: {}", self.path.display(), error);
+            return;
+        }
+        println!("Recorded {} frames of input to {}", self.frames, self.path.display());
+    }
+}
+
+pub struct Replay {
+    header: RecordingHeader,
+    frames: Vec<RecordedFrame>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|error| error.to_string())?;
+        let mut lines = BufReader::new(file).lines();
+        let header_line = lines
+            .next()
+            .ok_or("the recording is empty")?
+            .map_err(|error| error.to_string())?;
+        let header: RecordingHeader =
+            serde_json::from_str(&header_line).map_err(|error| format!("header: {}", error))?;
+        if header.version != RECORDING_VERSION {
+            return Err(format!(
+                "recording version {} is not {}",
+                header.version, RECORDING_VERSION
+            ));
+        }
+        let mut frames = Vec::new();
+        for (index, line) in lines.enumerate() {
+            let line = line.map_err(|error| error.to_string())?;
+            let frame = serde_json::from_str(&line)
+                .map_err(|error| format!("frame {}: {}", index, error))?;
+            frames.push(frame);
+        }
+        Ok(Self {
+            header,
+            frames,
+            next: 0,
+        })
+    }
+
+    pub fn header(&self) -> &RecordingHeader {
+        &self.header
+    }
+
+    // The next recorded frame, None once all have been played
+    pub fn next_frame(&mut self) -> Option<&RecordedFrame> {
+        let frame = self.frames.get(self.next)?;
+        self.next += 1;
+        Some(frame)
+    }
+
+    pub fn frames_played(&self) -> usize {
+        self.next
+    }
+}
+
+// FNV-1a over a scene's saved state, printed at the end of a replay so two runs
+// can be compared at a glance
+pub fn state_checksum(state: &serde_json::Value) -> u64 {
+    state.to_string().bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,121 @@
+// The startup report (--report): one JSON document describing the GPU, queue
+// families, swapchain and extensions the app ended up with, so bug reports all
+// carry the same environment details. Unlike `--info` it describes what was
+// chosen rather than everything available.
+
+use serde::Serialize;
+
+use crate::gpu::GpuContext;
+use crate::info::version_string;
+use crate::window::WindowContext;
+
+#[derive(Serialize)]
+pub struct StartupReport {
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub gpu: GpuReport,
+    pub queue_families: Vec<QueueFamilyReport>,
+    pub swapchain: SwapchainReport,
+    pub instance_extensions: Vec<String>,
+    pub device_extensions: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct GpuReport {
+    // Numbered as --info lists them
+    pub index: usize,
+    pub count: usize,
+    pub name: String,
+    pub device_type: String,
+    // What the app uses, the lower of the device's and the loader's
+    pub api_version: String,
+    // Vendor-specific encoding, so reported raw
+    pub driver_version: u32,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    // GPUs rendering together, None outside a device group
+    pub device_group: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct QueueFamilyReport {
+    pub index: u32,
+    pub flags: String,
+    pub queue_count: u32,
+    // What the app submits to it: "graphics" (with compute and present) or
+    // "transfer", empty when unused
+    pub used_for: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct SwapchainReport {
+    pub format: String,
+    pub color_space: String,
+    // As asked for and as the surface allowed
+    pub requested_present_mode: &'static str,
+    pub present_mode: &'static str,
+    pub requested_image_count: Option<u32>,
+    pub image_count: usize,
+    pub extent: [u32; 2],
+}
+
+impl StartupReport {
+    // Describes `gpu` and the swapchain of `primary`, the window it was created for
+    pub fn collect(gpu: &GpuContext, primary: &WindowContext) -> Self {
+        let properties = unsafe {
+            gpu.instance
+                .get_physical_device_properties(gpu.physical_device)
+        };
+        let queue_families = unsafe {
+            gpu.instance
+                .get_physical_device_queue_family_properties(gpu.physical_device)
+        }
+        .iter()
+        .zip(0..)
+        .map(|(family, index)| {
+            let mut used_for = Vec::new();
+            if index == gpu.queue_family_index {
+                used_for.push("graphics");
+            }
+            if Some(index) == gpu.transfer_family_index {
+                used_for.push("transfer");
+            }
+            QueueFamilyReport {
+                index,
+                flags: format!("{:?}", family.queue_flags),
+                queue_count: family.queue_count,
+                used_for,
+            }
+        })
+        .collect();
+        let surface_format = primary.surface_format();
+        let extent = primary.extent();
+        Self {
+            app_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            gpu: GpuReport {
+                index: gpu.physical_device_index,
+                count: gpu.physical_device_count,
+                name: gpu.device_name.clone(),
+                device_type: format!("{:?}", properties.device_type),
+                api_version: version_string(gpu.capabilities.api_version),
+                driver_version: properties.driver_version,
+                vendor_id: properties.vendor_id,
+                device_id: properties.device_id,
+                device_group: gpu.device_group.map(|group| group.device_count),
+            },
+            queue_families,
+            swapchain: SwapchainReport {
+                format: format!("{:?}", surface_format.format),
+                color_space: format!("{:?}", surface_format.color_space),
+                requested_present_mode: primary.present_mode().name(),
+                present_mode: primary.active_present_mode().name(),
+                requested_image_count: primary.desired_image_count(),
+                image_count: primary.image_count(),
+                extent: [extent.width, extent.height],
+            },
+            instance_extensions: gpu.instance_extensions.clone(),
+            device_extensions: gpu.device_extensions.clone(),
+        }
+    }
+}
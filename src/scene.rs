@@ -0,0 +1,104 @@
+use glam::Vec2;
+
+use crate::canvas::Canvas;
+use crate::input::Input;
+use crate::renderer::{Circle, Renderer};
+use crate::rng::Rng;
+use crate::sound::SoundQueue;
+use crate::sprite::SpriteBatch;
+
+// Everything a scene submits for one frame, in the shapes `Renderer::render` takes
+#[derive(Default)]
+pub struct DrawList {
+    pub circles: Vec<Circle>,
+    pub canvas: Canvas,
+    pub sprites: SpriteBatch,
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.circles.clear();
+        self.canvas.clear();
+        self.sprites.clear();
+    }
+}
+
+// Per-frame state the app hands to the active scene
+pub struct SceneContext<'a> {
+    pub renderer: &'a mut Renderer,
+    pub input: &'a Input,
+    pub rng: &'a mut Rng,
+    // Sound effects to play this frame
+    pub sounds: &'a mut SoundQueue,
+    // Left analog stick, zero without a gamepad
+    pub stick: Vec2,
+    // Wall-clock seconds since the last frame, for things that ignore pause
+    pub frame_dt: f32,
+    // Fixed simulation steps to run this frame, each `fixed_dt` seconds long
+    pub steps: u32,
+    pub fixed_dt: f32,
+}
+
+impl SceneContext<'_> {
+    // Size of the ortho drawing space in logical units
+    pub fn bounds(&self) -> Vec2 {
+        self.renderer.bounds()
+    }
+
+    // Cursor position in ortho space, None while outside the window
+    pub fn cursor(&self) -> Option<Vec2> {
+        self.input
+            .cursor_position()
+            .map(|position| self.renderer.window_to_ortho(position))
+    }
+
+    // Simulation time covered by this frame's steps
+    pub fn sim_dt(&self) -> f32 {
+        self.steps as f32 * self.fixed_dt
+    }
+}
+
+// A setting changed from outside the scene, such as by the remote control
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SceneParam {
+    // Velocity of the scene's main object in pixels per second
+    Velocity(Vec2),
+    // Replaces the scene's own colors, or brings them back when None
+    Color(Option<[f32; 4]>),
+}
+
+// A self-contained demo. The app calls `create_resources` once when the scene
+// becomes active, then `update` and `record_draws` every frame, and
+// `destroy_resources` before switching away.
+pub trait Scene {
+    // Creates textures, particles, post effects and the background; the post
+    // chain starts empty and the background is the plain clear color
+    fn create_resources(&mut self, renderer: &mut Renderer);
+
+    // Frees everything `create_resources` made
+    fn destroy_resources(&mut self, renderer: &mut Renderer);
+
+    fn update(&mut self, context: &mut SceneContext);
+
+    // Adds this frame's draws to `draws`, which the app cleared beforehand
+    fn record_draws(&self, draws: &mut DrawList);
+
+    // Applies `param`, or returns false if the scene has nothing it applies to
+    fn set_param(&mut self, _param: SceneParam) -> bool {
+        false
+    }
+
+    // The simulation state for a snapshot, or None if the scene can't be saved
+    fn save_state(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    // Replaces the simulation state with one `save_state` returned
+    fn load_state(&mut self, _state: serde_json::Value) -> Result<(), String> {
+        Err("this scene doesn't support snapshots".to_string())
+    }
+}
@@ -0,0 +1,132 @@
+use glam::Vec2;
+use winit::event::MouseButton;
+
+use crate::background::Background;
+use crate::boids::Flock;
+use crate::color::hsv_to_rgba;
+use crate::input::Action;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+use crate::sprite::{Sprite, TextureId};
+
+const INITIAL_BOIDS: usize = 3000;
+// Boids added per N press
+const SPAWN_BATCH: usize = 500;
+const BOID_SIZE: Vec2 = Vec2::new(12.0, 8.0);
+const BOID_TEXTURE_SIZE: u32 = 32;
+// Dusk sky behind the flock
+const SKY_TOP: [f32; 3] = [0.02, 0.04, 0.12];
+const SKY_BOTTOM: [f32; 3] = [0.12, 0.05, 0.06];
+
+// White arrowhead pointing along +x with an anti-aliased edge
+fn create_boid_pixels() -> Vec<u8> {
+    let size = BOID_TEXTURE_SIZE;
+    // Corners in -1..1 texture space, counter-clockwise
+    let corners = [
+        Vec2::new(1.0, 0.0),
+        Vec2::new(-1.0, 0.9),
+        Vec2::new(-1.0, -0.9),
+    ];
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let local = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            // Signed distance to the nearest edge in pixels, positive inside
+            let inside = (0..3)
+                .map(|i| {
+                    let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                    let normal = (b - a).perp().normalize();
+                    (local - a).dot(normal) * size as f32 * 0.5
+                })
+                .fold(f32::INFINITY, f32::min);
+            let coverage = (inside + 0.5).clamp(0.0, 1.0);
+            pixels.extend_from_slice(&[255, 255, 255, (coverage * 255.0) as u8]);
+        }
+    }
+    pixels
+}
+
+// A few thousand boids flocking with separation, alignment and cohesion. Each
+// boid is one rotated sprite, so the whole flock is a single instanced draw.
+// Holding the left button scatters the flock away from the cursor.
+pub struct BoidsScene {
+    flock: Flock,
+    texture: Option<TextureId>,
+    bounds: Vec2,
+}
+
+impl BoidsScene {
+    pub fn new() -> Self {
+        Self {
+            flock: Flock::new(),
+            texture: None,
+            bounds: Vec2::ZERO,
+        }
+    }
+}
+
+impl Default for BoidsScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for BoidsScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        let pixels = create_boid_pixels();
+        self.texture = Some(renderer.create_texture(BOID_TEXTURE_SIZE, BOID_TEXTURE_SIZE, &pixels));
+        renderer.set_background(Background::VerticalGradient {
+            top: SKY_TOP,
+            bottom: SKY_BOTTOM,
+        });
+        self.bounds = renderer.bounds();
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        if let Some(texture) = self.texture.take() {
+            renderer.destroy_texture(texture);
+        }
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        self.bounds = context.bounds();
+        if self.flock.is_empty() || context.input.was_pressed(Action::SpawnBall) {
+            let count = if self.flock.is_empty() {
+                INITIAL_BOIDS
+            } else {
+                SPAWN_BATCH
+            };
+            self.flock.spawn(context.rng, self.bounds, count);
+            println!("Boids: {}", self.flock.len());
+        }
+        if context.input.was_pressed(Action::ClearBalls) {
+            // The next update refills the flock from scratch
+            self.flock.clear();
+        }
+        self.flock.scatter = context
+            .cursor()
+            .filter(|_| context.input.is_mouse_held(MouseButton::Left));
+        for _ in 0..context.steps {
+            self.flock.update(context.fixed_dt, self.bounds);
+        }
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        let Some(texture) = self.texture else {
+            return;
+        };
+        for boid in self.flock.boids() {
+            let heading = boid.velocity.to_angle();
+            // Hue follows the heading so groups flying together share a color
+            let hue = heading / std::f32::consts::TAU;
+            draws.sprites.draw(
+                texture,
+                &Sprite {
+                    rotation: heading,
+                    tint: hsv_to_rgba(hue, 0.6, 1.0, 1.0),
+                    ..Sprite::new(boid.position, BOID_SIZE)
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::VecDeque;
+
+use glam::Vec2;
+use hecs::{Entity, World};
+use serde::{Deserialize, Serialize};
+use winit::event::MouseButton;
+
+use crate::ball::{Ball, Physics};
+use crate::ecs::{self, Color, Frozen, HueCycle, Outline, Shape, Transform, Velocity};
+use crate::geometry::{LineCap, LineJoin, StrokeStyle};
+use crate::input::Action;
+use crate::path::Path;
+use crate::post::PostEffect;
+use crate::renderer::{Circle, Renderer};
+use crate::scene::{DrawList, Scene, SceneContext, SceneParam};
+use crate::sound::SoundQueue;
+use crate::sprite::TextureAtlas;
+
+// Number of past positions drawn as translucent ghosts behind the circle
+const TRAIL_LENGTH: usize = 12;
+// GPU particles orbiting the circle
+const PARTICLE_COUNT: u32 = 1_000_000;
+// Arrow-key acceleration in pixels per second squared
+const STEER_ACCELERATION: f32 = 600.0;
+// Velocity multiplier per +/- press
+const SPEED_STEP: f32 = 1.25;
+const BALL_RADIUS: f32 = 50.0;
+const SPAWNED_BALL_RADIUS: f32 = 30.0;
+
+const BALL_OUTLINE: Outline = Outline {
+    width: 3.0,
+    color: [1.0, 1.0, 1.0, 1.0],
+};
+
+// What a snapshot keeps of one ball
+#[derive(Serialize, Deserialize)]
+struct SavedBall {
+    position: Vec2,
+    velocity: Vec2,
+    radius: f32,
+    hue_offset: f32,
+}
+
+// The scene's part of a snapshot
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    // The steered ball comes first
+    balls: Vec<SavedBall>,
+    trail: VecDeque<Vec2>,
+    physics: Physics,
+    elapsed: f32,
+    color: Option<[f32; 4]>,
+}
+
+// A ball being dragged with the mouse. Its velocity follows the cursor so
+// releasing mid-motion flings it.
+struct Drag {
+    ball: Entity,
+    offset: Vec2,
+    last_cursor: Vec2,
+    velocity: Vec2,
+}
+
+// Builds a small 4x1 atlas of soft colored discs for the sprite demo
+fn create_demo_atlas_pixels() -> (u32, u32, Vec<u8>) {
+    let cell = 32;
+    let (width, height) = (cell * 4, cell);
+    let colors = [[255, 80, 80], [80, 255, 80], [80, 160, 255], [255, 220, 80]];
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let color = colors[(x / cell) as usize];
+            let local = Vec2::new((x % cell) as f32 + 0.5, y as f32 + 0.5) - cell as f32 / 2.0;
+            let coverage = (cell as f32 / 2.0 - local.length()).clamp(0.0, 1.0);
+            let i = ((y * width + x) * 4) as usize;
+            pixels[i..i + 4].copy_from_slice(&[
+                color[0],
+                color[1],
+                color[2],
+                (coverage * 255.0) as u8,
+            ]);
+        }
+    }
+    (width, height, pixels)
+}
+
+// The original demo: a steerable glowing ball with a trail, extra balls spawned
+// and flung with the mouse, corner sprites and a particle swarm chasing it
+pub struct BouncingScene {
+    // Every ball, built from the components in ecs.rs
+    world: World,
+    // The ball steered with the keyboard
+    player: Entity,
+    drag: Option<Drag>,
+    trail: VecDeque<Vec2>,
+    physics: Physics,
+    // What the scene was started with; `G` switches between this and a preset
+    configured_physics: Physics,
+    sprite_atlas: Option<TextureAtlas>,
+    bounds: Vec2,
+    elapsed: f32,
+    // Set remotely; replaces the cycling hues
+    color: Option<[f32; 4]>,
+}
+
+impl BouncingScene {
+    pub fn new(physics: Physics) -> Self {
+        Self {
+            world: World::new(),
+            player: Entity::DANGLING,
+            drag: None,
+            trail: VecDeque::with_capacity(TRAIL_LENGTH + 1),
+            physics,
+            configured_physics: physics,
+            sprite_atlas: None,
+            bounds: Vec2::ZERO,
+            elapsed: 0.0,
+            color: None,
+        }
+    }
+
+    fn player_position(&self) -> Vec2 {
+        self.world.get::<&Transform>(self.player).unwrap().position
+    }
+
+    // One fixed simulation step; balls bounce off the walls and each other
+    fn update_balls(&mut self, dt: f32, steer: Vec2, sounds: &mut SoundQueue) {
+        self.world.get::<&mut Velocity>(self.player).unwrap().0 += steer * STEER_ACCELERATION * dt;
+        self.trail.push_front(self.player_position());
+        self.trail.truncate(TRAIL_LENGTH);
+
+        ecs::integrate(&mut self.world, dt, self.bounds, &self.physics, |speed| {
+            sounds.bounce(speed)
+        });
+        ecs::collide(&mut self.world, self.physics.restitution, |speed| {
+            sounds.bounce(speed)
+        });
+    }
+
+    // Left click on a ball grabs it, anywhere else spawns a new one. Releasing a
+    // grabbed ball lets it keep the cursor's velocity.
+    fn update_mouse(&mut self, context: &mut SceneContext) {
+        let Some(cursor) = context.cursor() else {
+            self.release();
+            return;
+        };
+
+        if context.input.was_mouse_pressed(MouseButton::Left) {
+            match ecs::pick(&self.world, cursor) {
+                Some(ball) => {
+                    let position = self.world.get::<&Transform>(ball).unwrap().position;
+                    self.drag = Some(Drag {
+                        ball,
+                        offset: position - cursor,
+                        last_cursor: cursor,
+                        velocity: Vec2::ZERO,
+                    });
+                    self.world.insert_one(ball, Frozen).unwrap();
+                }
+                None => self.spawn_ball(context, cursor),
+            }
+        }
+
+        if let Some(drag) = self.drag.as_mut() {
+            if context.frame_dt > 0.0 {
+                // Smooth the cursor velocity a little so a jittery release still flings
+                let cursor_velocity = (cursor - drag.last_cursor) / context.frame_dt;
+                drag.velocity = drag.velocity.lerp(cursor_velocity, 0.5);
+            }
+            drag.last_cursor = cursor;
+            let (transform, velocity) = self
+                .world
+                .query_one_mut::<(&mut Transform, &mut Velocity)>(drag.ball)
+                .unwrap();
+            transform.position = cursor + drag.offset;
+            velocity.0 = drag.velocity;
+        }
+
+        if !context.input.is_mouse_held(MouseButton::Left) {
+            self.release();
+        }
+    }
+
+    fn saved_state(&self) -> SavedState {
+        let mut query = self
+            .world
+            .query::<(&Transform, &Velocity, &Shape, &HueCycle)>();
+        let mut balls: Vec<(Entity, SavedBall)> = query
+            .iter()
+            .map(|(entity, (transform, velocity, shape, hue))| {
+                let ball = SavedBall {
+                    position: transform.position,
+                    velocity: velocity.0,
+                    radius: shape.radius(),
+                    hue_offset: hue.offset,
+                };
+                (entity, ball)
+            })
+            .collect();
+        balls.sort_by_key(|(entity, _)| *entity != self.player);
+        SavedState {
+            balls: balls.into_iter().map(|(_, ball)| ball).collect(),
+            trail: self.trail.clone(),
+            physics: self.physics,
+            elapsed: self.elapsed,
+            color: self.color,
+        }
+    }
+
+    // Lets go of the dragged ball, which keeps moving on its own
+    fn release(&mut self) {
+        if let Some(drag) = self.drag.take() {
+            let _ = self.world.remove_one::<Frozen>(drag.ball);
+        }
+    }
+
+    fn spawn_ball(&mut self, context: &mut SceneContext, position: Vec2) {
+        let angle = context.rng.range(0.0, std::f32::consts::TAU);
+        let speed = context.rng.range(150.0, 400.0);
+        let mut ball = Ball::new(
+            position,
+            Vec2::from_angle(angle) * speed,
+            SPAWNED_BALL_RADIUS,
+        );
+        ball.keep_inside(self.bounds, &Physics::default());
+        let hue = HueCycle {
+            offset: context.rng.next_f32(),
+        };
+        println!("Spawned ball {} at {:?}", self.world.len(), position);
+        self.spawn(ball.position, ball.velocity, ball.radius, hue);
+    }
+
+    fn spawn(&mut self, position: Vec2, velocity: Vec2, radius: f32, hue: HueCycle) -> Entity {
+        let ball = ecs::spawn_ball(&mut self.world, position, velocity, radius);
+        let color = self.color.unwrap_or([1.0; 4]);
+        self.world.insert(ball, (hue, Color(color), BALL_OUTLINE)).unwrap();
+        ball
+    }
+
+    // One-shot actions triggered since the last frame
+    fn handle_actions(&mut self, context: &mut SceneContext) {
+        if context.input.was_pressed(Action::ToggleGravity) {
+            // The preset is the demo's bouncy one, or lossless reflection
+            // when that's what was configured
+            let preset = if self.configured_physics == Physics::default() {
+                Physics::bouncy()
+            } else {
+                Physics::default()
+            };
+            self.physics = if self.physics == self.configured_physics {
+                preset
+            } else {
+                self.configured_physics
+            };
+            println!("Physics: {:?}", self.physics);
+        }
+        if context.input.was_pressed(Action::SpeedUp) {
+            self.world.get::<&mut Velocity>(self.player).unwrap().0 *= SPEED_STEP;
+        }
+        if context.input.was_pressed(Action::SpeedDown) {
+            self.world.get::<&mut Velocity>(self.player).unwrap().0 /= SPEED_STEP;
+        }
+        if context.input.was_pressed(Action::SpawnBall) {
+            let position = self.player_position();
+            self.spawn_ball(context, position);
+        }
+        if context.input.was_pressed(Action::ClearBalls) {
+            self.release();
+            let player = self.player;
+            let spawned: Vec<Entity> = self
+                .world
+                .iter()
+                .map(|entity| entity.entity())
+                .filter(|&entity| entity != player)
+                .collect();
+            println!("Cleared {} balls", spawned.len());
+            for entity in spawned {
+                self.world.despawn(entity).unwrap();
+            }
+        }
+    }
+
+    // Held arrow keys (or the left stick) accelerate the circle
+    fn steer_direction(context: &SceneContext) -> Vec2 {
+        let mut direction = Vec2::ZERO;
+        if context.input.is_held(Action::SteerLeft) {
+            direction.x -= 1.0;
+        }
+        if context.input.is_held(Action::SteerRight) {
+            direction.x += 1.0;
+        }
+        if context.input.is_held(Action::SteerUp) {
+            direction.y -= 1.0;
+        }
+        if context.input.is_held(Action::SteerDown) {
+            direction.y += 1.0;
+        }
+        (direction.normalize_or_zero() + context.stick).clamp_length_max(1.0)
+    }
+
+    // Fading ghosts of recent positions; older ones are fainter and sit further back
+    fn trail_circles(&self) -> impl Iterator<Item = Circle> + '_ {
+        let (shape, base) = self
+            .world
+            .query_one::<(&Shape, &Color)>(self.player)
+            .unwrap()
+            .get()
+            .map(|(shape, color)| (*shape, color.0))
+            .unwrap();
+        self.trail.iter().enumerate().map(move |(age, &position)| {
+            let fade = 1.0 - (age + 1) as f32 / (TRAIL_LENGTH + 1) as f32;
+            let color = [base[0], base[1], base[2], 0.35 * fade];
+            Circle::new(position, shape.radius() * (0.6 + 0.4 * fade), color)
+                .with_depth((age + 1) as f32)
+        })
+    }
+
+    // Immediate-mode overlay: the path behind the circle and a velocity
+    // indicator on it
+    fn draw_overlay(&self, draws: &mut DrawList) {
+        let path: Vec<Vec2> = self.trail.iter().copied().collect();
+        let path_style = StrokeStyle::new(2.0)
+            .with_join(LineJoin::Round)
+            .with_cap(LineCap::Round);
+        draws
+            .canvas
+            .draw_polyline(&path, false, path_style, [1.0, 1.0, 1.0, 0.25]);
+        let position = self.player_position();
+        let velocity = self.world.get::<&Velocity>(self.player).unwrap().0;
+        let tip = position + velocity * 0.25;
+        let yellow = [1.0, 1.0, 0.0, 1.0];
+        draws.canvas.stroke_line(position, tip, 3.0, yellow);
+        let direction = velocity.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            draws.canvas.fill_circle(tip, 6.0, yellow);
+            return;
+        }
+        // An arrowhead with a curved back, outlined
+        let side = direction.perp() * 8.0;
+        let back = tip - direction * 8.0;
+        let mut arrow = Path::new();
+        arrow
+            .move_to(tip + direction * 8.0)
+            .line_to(back + side)
+            .quad_to(tip, back - side)
+            .close();
+        draws.canvas.fill_path(&arrow, yellow);
+        draws
+            .canvas
+            .stroke_path(&arrow, StrokeStyle::new(1.5), [0.4, 0.3, 0.0, 1.0]);
+    }
+
+    // One sprite from each atlas cell, parked in the window corners
+    fn draw_sprites(&self, draws: &mut DrawList) {
+        let Some(atlas) = self.sprite_atlas.as_ref() else {
+            return;
+        };
+        let (width, height) = (self.bounds.x, self.bounds.y);
+        let corners = [
+            Vec2::new(24.0, 24.0),
+            Vec2::new(width - 24.0, 24.0),
+            Vec2::new(width - 24.0, height - 24.0),
+            Vec2::new(24.0, height - 24.0),
+        ];
+        for (region, corner) in corners.into_iter().enumerate() {
+            draws.sprites.draw_region(
+                atlas,
+                region,
+                corner,
+                Vec2::splat(32.0),
+                [1.0, 1.0, 1.0, 1.0],
+            );
+        }
+    }
+}
+
+impl Default for BouncingScene {
+    fn default() -> Self {
+        Self::new(Physics::default())
+    }
+}
+
+impl Scene for BouncingScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.set_post_effects(vec![PostEffect::Bloom {
+            threshold: 0.6,
+            intensity: 1.5,
+            sigma: 4.0,
+        }]);
+        renderer.enable_particles(PARTICLE_COUNT);
+        let (atlas_width, atlas_height, atlas_pixels) = create_demo_atlas_pixels();
+        let atlas_texture = renderer.create_texture(atlas_width, atlas_height, &atlas_pixels);
+        self.sprite_atlas = Some(TextureAtlas::grid(
+            atlas_texture,
+            atlas_width,
+            atlas_height,
+            4,
+            1,
+        ));
+
+        self.bounds = renderer.bounds();
+        self.world.clear();
+        self.player = self.spawn(
+            self.bounds / 2.0,
+            Vec2::new(200.0, 150.0), // pixels per second
+            BALL_RADIUS,
+            HueCycle { offset: 0.0 },
+        );
+        self.trail.clear();
+        self.drag = None;
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_particles();
+        if let Some(atlas) = self.sprite_atlas.take() {
+            renderer.destroy_texture(atlas.texture);
+        }
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        self.bounds = context.bounds();
+        self.elapsed += context.frame_dt;
+        self.handle_actions(context);
+        self.update_mouse(context);
+        let steer = Self::steer_direction(context);
+        for _ in 0..context.steps {
+            self.update_balls(context.fixed_dt, steer, context.sounds);
+        }
+        if self.color.is_none() {
+            ecs::cycle_hues(&mut self.world, self.elapsed);
+        }
+
+        // The particle swarm chases the circle
+        let attractor = self.player_position();
+        let sim_dt = context.sim_dt();
+        if let Some(particles) = context.renderer.particles_mut() {
+            particles.attractor = attractor;
+            particles.simulate(sim_dt);
+        }
+        // Shade at full rate only around the circle
+        let radius = self.world.get::<&Shape>(self.player).unwrap().radius();
+        context.renderer.set_shading_focus(attractor, radius);
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        self.draw_overlay(draws);
+        self.draw_sprites(draws);
+        draws.circles.extend(self.trail_circles());
+        ecs::record_circles(&self.world, &mut draws.circles);
+    }
+
+    // Velocity steers the keyboard ball; color paints every ball
+    fn set_param(&mut self, param: SceneParam) -> bool {
+        match param {
+            SceneParam::Velocity(velocity) => match self.world.get::<&mut Velocity>(self.player) {
+                Ok(mut player) => player.0 = velocity,
+                Err(_) => return false,
+            },
+            SceneParam::Color(color) => {
+                self.color = color;
+                if let Some(color) = color {
+                    for (_, ball) in self.world.query_mut::<&mut Color>() {
+                        ball.0 = color;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn save_state(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self.saved_state()).ok()
+    }
+
+    fn load_state(&mut self, state: serde_json::Value) -> Result<(), String> {
+        let state: SavedState = serde_json::from_value(state).map_err(|error| error.to_string())?;
+        if state.balls.is_empty() {
+            return Err("the snapshot has no balls".to_string());
+        }
+        self.drag = None;
+        self.world.clear();
+        self.trail = state.trail;
+        self.physics = state.physics;
+        self.elapsed = state.elapsed;
+        self.color = state.color;
+        let balls: Vec<Entity> = state
+            .balls
+            .iter()
+            .map(|ball| {
+                let hue = HueCycle {
+                    offset: ball.hue_offset,
+                };
+                self.spawn(ball.position, ball.velocity, ball.radius, hue)
+            })
+            .collect();
+        self.player = balls[0];
+        if self.color.is_none() {
+            ecs::cycle_hues(&mut self.world, self.elapsed);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use glam::{DVec2, Vec2};
+use winit::event::MouseButton;
+
+use crate::fractal::FractalInputs;
+use crate::input::Action;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+
+const START_CENTER: DVec2 = DVec2::new(-0.5, 0.0);
+// Complex units visible top to bottom before zooming in
+const START_VIEW_HEIGHT: f64 = 3.0;
+// Scale factor per scroll notch
+const ZOOM_STEP: f64 = 1.25;
+// Double-single floats run out of precision around here
+const MIN_SCALE: f64 = 1e-13;
+// Deeper zooms need more iterations to resolve the boundary
+const ITERATIONS_PER_ZOOM_LEVEL: f64 = 32.0;
+const MIN_BASE_ITERATIONS: u32 = 32;
+const MAX_BASE_ITERATIONS: u32 = 4096;
+
+// Mandelbrot explorer: drag with the left button to pan, scroll to zoom around
+// the cursor. The view is kept in f64 on the CPU and handed to the shader as
+// pairs of floats.
+pub struct FractalScene {
+    center: DVec2,
+    // Complex units per pixel, zero until the window size is known
+    scale: f64,
+    base_iterations: u32,
+    last_cursor: Option<Vec2>,
+    high_precision: bool,
+}
+
+impl FractalScene {
+    pub fn new() -> Self {
+        Self {
+            center: START_CENTER,
+            scale: 0.0,
+            base_iterations: 128,
+            last_cursor: None,
+            high_precision: false,
+        }
+    }
+
+    // Complex plane position of a point given in window pixels
+    fn to_complex(&self, point: Vec2, bounds: Vec2) -> DVec2 {
+        let offset = (point - bounds * 0.5).as_dvec2();
+        self.center + DVec2::new(offset.x, -offset.y) * self.scale
+    }
+
+    // Zooms by `factor` keeping the point under `anchor` fixed on screen
+    fn zoom_at(&mut self, anchor: Vec2, bounds: Vec2, factor: f64) {
+        let fixed = self.to_complex(anchor, bounds);
+        self.scale = (self.scale * factor).max(MIN_SCALE);
+        self.center += fixed - self.to_complex(anchor, bounds);
+    }
+
+    fn max_iterations(&self, bounds: Vec2) -> u32 {
+        let zoom_levels = (START_VIEW_HEIGHT / (self.scale * bounds.y as f64)).log2();
+        self.base_iterations + (zoom_levels.max(0.0) * ITERATIONS_PER_ZOOM_LEVEL) as u32
+    }
+
+    fn handle_mouse(&mut self, context: &SceneContext, bounds: Vec2) {
+        let cursor = context.cursor();
+        if let (Some(cursor), Some(last)) = (cursor, self.last_cursor) {
+            if context.input.is_mouse_held(MouseButton::Left) {
+                let delta = (cursor - last).as_dvec2();
+                self.center -= DVec2::new(delta.x, -delta.y) * self.scale;
+            }
+        }
+        self.last_cursor = cursor;
+
+        let scroll = context.input.scroll_delta();
+        if scroll != 0.0 {
+            let anchor = cursor.unwrap_or(bounds * 0.5);
+            self.zoom_at(anchor, bounds, ZOOM_STEP.powf(-scroll as f64));
+        }
+    }
+}
+
+impl Default for FractalScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for FractalScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.enable_fractal();
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_fractal();
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        let bounds = context.bounds();
+        if self.scale == 0.0 || context.input.was_pressed(Action::ClearBalls) {
+            self.center = START_CENTER;
+            self.scale = START_VIEW_HEIGHT / bounds.y.max(1.0) as f64;
+        }
+        if context.input.was_pressed(Action::SpeedUp) {
+            self.base_iterations = (self.base_iterations * 2).min(MAX_BASE_ITERATIONS);
+            println!("Fractal base iterations: {}", self.base_iterations);
+        }
+        if context.input.was_pressed(Action::SpeedDown) {
+            self.base_iterations = (self.base_iterations / 2).max(MIN_BASE_ITERATIONS);
+            println!("Fractal base iterations: {}", self.base_iterations);
+        }
+        self.handle_mouse(context, bounds);
+
+        let inputs =
+            FractalInputs::new(self.center, self.scale, bounds, self.max_iterations(bounds));
+        if inputs.is_high_precision() != self.high_precision {
+            self.high_precision = inputs.is_high_precision();
+            println!(
+                "Fractal switched to {} precision at scale {:e}",
+                if self.high_precision {
+                    "double-single"
+                } else {
+                    "single"
+                },
+                self.scale
+            );
+        }
+        if let Some(pass) = context.renderer.fractal_mut() {
+            pass.inputs = inputs;
+        }
+    }
+
+    fn record_draws(&self, _draws: &mut DrawList) {
+        // The renderer draws the fullscreen pass itself
+    }
+}
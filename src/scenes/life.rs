@@ -0,0 +1,53 @@
+use crate::input::Action;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+
+// Conway's Game of Life at one cell per pixel, simulated with a compute shader
+// (see `life::LifeSimulation`). One generation runs per fixed simulation step.
+pub struct LifeScene {
+    // The grid is seeded from the scene rng on the first update
+    seeded: bool,
+}
+
+impl LifeScene {
+    pub fn new() -> Self {
+        Self { seeded: false }
+    }
+}
+
+impl Default for LifeScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for LifeScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.enable_life(0);
+        self.seeded = false;
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_life();
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        let reseed = !self.seeded || context.input.was_pressed(Action::SpawnBall);
+        let seed = context.rng.next_u32();
+        let Some(life) = context.renderer.life_mut() else {
+            return;
+        };
+        if reseed {
+            life.randomize(seed);
+            self.seeded = true;
+        }
+        if context.input.was_pressed(Action::ClearBalls) {
+            life.clear();
+        }
+        life.simulate(context.steps);
+    }
+
+    fn record_draws(&self, _draws: &mut DrawList) {
+        // The renderer draws the cell grid itself
+    }
+}
@@ -0,0 +1,89 @@
+use glam::Vec2;
+
+use crate::color::hsv_to_rgba;
+use crate::input::Action;
+use crate::renderer::{Circle, Renderer};
+use crate::scene::{DrawList, Scene, SceneContext};
+
+// Golden angle in radians, which spreads the seeds of a sunflower evenly
+const GOLDEN_ANGLE: f32 = 2.399_963;
+const START_COUNT: usize = 800;
+const MIN_COUNT: usize = 50;
+const MAX_COUNT: usize = 12_800;
+// Radians per second the whole pattern turns
+const SPIN_SPEED: f32 = 0.15;
+// Hue cycles per second
+const HUE_SPEED: f32 = 0.05;
+
+// A sunflower of circles whose geometry comes from a mesh shader, with no
+// vertex or index buffer: each circle is one workgroup fed by push constants.
+// Without mesh shader support the same circles are drawn in the current circle
+// mode instead. +/- double or halve the number of circles.
+pub struct MeshShaderScene {
+    count: usize,
+    spin: f32,
+    hue: f32,
+    bounds: Vec2,
+}
+
+impl MeshShaderScene {
+    pub fn new() -> Self {
+        Self {
+            count: START_COUNT,
+            spin: 0.0,
+            hue: 0.0,
+            bounds: Vec2::ZERO,
+        }
+    }
+}
+
+impl Default for MeshShaderScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for MeshShaderScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.enable_mesh_circles();
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_mesh_circles();
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        self.bounds = context.bounds();
+        if context.input.was_pressed(Action::ClearBalls) {
+            self.count = START_COUNT;
+            self.spin = 0.0;
+        }
+        if context.input.was_pressed(Action::SpeedUp) {
+            self.count = (self.count * 2).min(MAX_COUNT);
+            println!("Mesh shader circles: {}", self.count);
+        }
+        if context.input.was_pressed(Action::SpeedDown) {
+            self.count = (self.count / 2).max(MIN_COUNT);
+            println!("Mesh shader circles: {}", self.count);
+        }
+        let dt = context.sim_dt();
+        self.spin += SPIN_SPEED * dt;
+        self.hue += HUE_SPEED * dt;
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        let center = self.bounds * 0.5;
+        // Seed n sits at sqrt(n) times this from the middle, so the outermost
+        // seeds just reach the nearer window edge
+        let spacing = self.bounds.min_element() * 0.48 / (self.count as f32).sqrt();
+        let radius = spacing * 0.45;
+        for index in 0..self.count {
+            let angle = index as f32 * GOLDEN_ANGLE + self.spin;
+            let distance = spacing * (index as f32).sqrt();
+            let position = center + Vec2::from_angle(angle) * distance;
+            let along = index as f32 / self.count as f32;
+            let color = hsv_to_rgba(self.hue + along * 0.5, 0.8, 1.0, 1.0);
+            draws.circles.push(Circle::new(position, radius, color));
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// Built-in demos selectable with --scene and cycled at runtime
+pub mod boids;
+pub mod bouncing;
+pub mod fractal;
+pub mod life;
+pub mod meshshader;
+pub mod particles;
+pub mod plugin;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "raytracing")]
+pub mod raytrace;
+#[cfg(feature = "rhai")]
+pub mod script;
+pub mod shadertoy;
+pub mod solids;
+pub mod sparks;
+pub mod starfield;
+pub mod tilemap;
+pub mod visualizer;
+
+use std::path::PathBuf;
+
+use crate::ball::Physics;
+use crate::plugin::registry;
+use crate::scene::Scene;
+
+use boids::BoidsScene;
+use bouncing::BouncingScene;
+use fractal::FractalScene;
+use life::LifeScene;
+use meshshader::MeshShaderScene;
+use particles::ParticlesScene;
+use plugin::PluginScene;
+#[cfg(feature = "python")]
+use python::PythonScene;
+#[cfg(feature = "raytracing")]
+use raytrace::RayTraceScene;
+#[cfg(feature = "rhai")]
+use script::ScriptScene;
+use shadertoy::ShaderToyScene;
+use solids::SolidsScene;
+use sparks::SparksScene;
+use starfield::StarfieldScene;
+use tilemap::TilemapScene;
+use visualizer::VisualizerScene;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneKind {
+    Bouncing,
+    Particles,
+    Sparks,
+    Starfield,
+    ShaderToy,
+    Life,
+    Boids,
+    Fractal,
+    Visualizer,
+    Solids,
+    Tilemap,
+    MeshShader,
+    #[cfg(feature = "raytracing")]
+    RayTrace,
+    #[cfg(feature = "python")]
+    Python,
+    #[cfg(feature = "rhai")]
+    Script,
+    // Index into registry()
+    Plugin(usize),
+}
+
+// Scene inputs that come from the command line
+#[derive(Clone, Debug, Default)]
+pub struct SceneConfig {
+    // GLSL file with a ShaderToy `mainImage` for the shadertoy scene
+    pub shader_path: Option<PathBuf>,
+    // Python or Rhai file with `setup`/`update`/`on_click` for the python or
+    // script scene
+    pub script_path: Option<PathBuf>,
+    // OBJ file the solids scene shows instead of its cube
+    pub model_path: Option<PathBuf>,
+    // Tiled map the tilemap scene shows instead of its built-in one
+    pub map_path: Option<PathBuf>,
+    // Ball physics the bouncing scene starts with
+    pub physics: Physics,
+}
+
+impl SceneKind {
+    pub const ALL: &'static [SceneKind] = &[
+        SceneKind::Bouncing,
+        SceneKind::Particles,
+        SceneKind::Sparks,
+        SceneKind::Starfield,
+        SceneKind::ShaderToy,
+        SceneKind::Life,
+        SceneKind::Boids,
+        SceneKind::Fractal,
+        SceneKind::Visualizer,
+        SceneKind::Solids,
+        SceneKind::Tilemap,
+        SceneKind::MeshShader,
+        #[cfg(feature = "raytracing")]
+        SceneKind::RayTrace,
+        #[cfg(feature = "python")]
+        SceneKind::Python,
+        #[cfg(feature = "rhai")]
+        SceneKind::Script,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SceneKind::Bouncing => "bouncing",
+            SceneKind::Particles => "particles",
+            SceneKind::Sparks => "sparks",
+            SceneKind::Starfield => "starfield",
+            SceneKind::ShaderToy => "shadertoy",
+            SceneKind::Life => "life",
+            SceneKind::Boids => "boids",
+            SceneKind::Fractal => "fractal",
+            SceneKind::Visualizer => "visualizer",
+            SceneKind::Solids => "solids",
+            SceneKind::Tilemap => "tilemap",
+            SceneKind::MeshShader => "meshshader",
+            #[cfg(feature = "raytracing")]
+            SceneKind::RayTrace => "raytrace",
+            #[cfg(feature = "python")]
+            SceneKind::Python => "python",
+            #[cfg(feature = "rhai")]
+            SceneKind::Script => "script",
+            SceneKind::Plugin(index) => registry()[index].name(),
+        }
+    }
+
+    // The built-in scenes followed by any plugins found in plugins/
+    pub fn all() -> Vec<Self> {
+        let plugins = (0..registry().len()).map(SceneKind::Plugin);
+        Self::ALL.iter().copied().chain(plugins).collect()
+    }
+
+    // Built-in scenes win over a plugin with the same name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|kind| kind.name() == name)
+    }
+
+    // The following scene in `all()` order, wrapping around
+    pub fn next(self) -> Self {
+        let all = Self::all();
+        let index = all.iter().position(|&kind| kind == self).unwrap();
+        all[(index + 1) % all.len()]
+    }
+
+    pub fn create(self, config: &SceneConfig) -> Box<dyn Scene> {
+        match self {
+            SceneKind::Bouncing => Box::new(BouncingScene::new(config.physics)),
+            SceneKind::Particles => Box::new(ParticlesScene::new()),
+            SceneKind::Sparks => Box::new(SparksScene::new()),
+            SceneKind::Starfield => Box::new(StarfieldScene::new()),
+            SceneKind::ShaderToy => Box::new(ShaderToyScene::new(config.shader_path.clone())),
+            SceneKind::Life => Box::new(LifeScene::new()),
+            SceneKind::Boids => Box::new(BoidsScene::new()),
+            SceneKind::Fractal => Box::new(FractalScene::new()),
+            SceneKind::Visualizer => Box::new(VisualizerScene::new()),
+            SceneKind::Solids => Box::new(SolidsScene::new(config.model_path.clone())),
+            SceneKind::Tilemap => Box::new(TilemapScene::new(config.map_path.clone())),
+            SceneKind::MeshShader => Box::new(MeshShaderScene::new()),
+            #[cfg(feature = "raytracing")]
+            SceneKind::RayTrace => Box::new(RayTraceScene::new()),
+            #[cfg(feature = "python")]
+            SceneKind::Python => Box::new(PythonScene::new(config.script_path.clone())),
+            #[cfg(feature = "rhai")]
+            SceneKind::Script => Box::new(ScriptScene::new(config.script_path.clone())),
+            SceneKind::Plugin(index) => Box::new(PluginScene::new(&registry()[index])),
+        }
+    }
+}
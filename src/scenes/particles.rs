@@ -0,0 +1,76 @@
+use glam::Vec2;
+use winit::event::MouseButton;
+
+use crate::input::Action;
+use crate::post::PostEffect;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+
+const PARTICLE_COUNT: u32 = 1_000_000;
+// Radius and angular speed of the attractor's orbit when the cursor is away
+const ORBIT_RADIUS: f32 = 0.3;
+const ORBIT_SPEED: f32 = 0.5;
+// Attraction multiplier per +/- press
+const STRENGTH_STEP: f32 = 1.5;
+
+// Just the GPU particle swarm: it follows the cursor while the left button is
+// held and otherwise chases a point orbiting the window center
+pub struct ParticlesScene {
+    angle: f32,
+}
+
+impl ParticlesScene {
+    pub fn new() -> Self {
+        Self { angle: 0.0 }
+    }
+}
+
+impl Default for ParticlesScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for ParticlesScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.set_post_effects(vec![PostEffect::Bloom {
+            threshold: 0.4,
+            intensity: 1.0,
+            sigma: 3.0,
+        }]);
+        renderer.enable_particles(PARTICLE_COUNT);
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_particles();
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        let sim_dt = context.sim_dt();
+        self.angle += ORBIT_SPEED * sim_dt;
+        let bounds = context.bounds();
+        let orbit =
+            bounds * 0.5 + Vec2::from_angle(self.angle) * bounds.min_element() * ORBIT_RADIUS;
+        let attractor = match context.cursor() {
+            Some(cursor) if context.input.is_mouse_held(MouseButton::Left) => cursor,
+            _ => orbit,
+        };
+        let speed_up = context.input.was_pressed(Action::SpeedUp);
+        let speed_down = context.input.was_pressed(Action::SpeedDown);
+        let Some(particles) = context.renderer.particles_mut() else {
+            return;
+        };
+        if speed_up {
+            particles.strength *= STRENGTH_STEP;
+        }
+        if speed_down {
+            particles.strength /= STRENGTH_STEP;
+        }
+        particles.attractor = attractor;
+        particles.simulate(sim_dt);
+    }
+
+    fn record_draws(&self, _draws: &mut DrawList) {
+        // The renderer draws the particle system itself
+    }
+}
@@ -0,0 +1,80 @@
+use std::ffi::c_void;
+
+use glam::Vec2;
+use winit::event::MouseButton;
+
+use crate::plugin::{Plugin, PluginCircle, PluginFrame};
+use crate::renderer::{Circle, Renderer};
+use crate::scene::{DrawList, Scene, SceneContext};
+
+// A scene whose state lives in a plugin library; see plugin.rs for the ABI.
+// Nothing is drawn if the plugin's `create` returns null.
+pub struct PluginScene {
+    plugin: &'static Plugin,
+    state: *mut c_void,
+}
+
+impl PluginScene {
+    pub fn new(plugin: &'static Plugin) -> Self {
+        Self {
+            plugin,
+            state: std::ptr::null_mut(),
+        }
+    }
+}
+
+// `record_draws` hands every circle to this, with the draw list as the sink
+unsafe extern "C" fn emit_circle(sink: *mut c_void, circle: *const PluginCircle) {
+    let (Some(draws), Some(circle)) = ((sink as *mut DrawList).as_mut(), circle.as_ref()) else {
+        return;
+    };
+    let position = Vec2::new(circle.x, circle.y);
+    draws.circles.push(Circle::new(position, circle.radius, circle.color));
+}
+
+impl Scene for PluginScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        let bounds = renderer.bounds();
+        self.state = unsafe { (self.plugin.api().create)(bounds.x, bounds.y) };
+        if self.state.is_null() {
+            eprintln!("Plugin {} failed to start", self.plugin.name());
+        }
+    }
+
+    fn destroy_resources(&mut self, _renderer: &mut Renderer) {
+        if !self.state.is_null() {
+            unsafe { (self.plugin.api().destroy)(self.state) };
+            self.state = std::ptr::null_mut();
+        }
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        if self.state.is_null() {
+            return;
+        }
+        let bounds = context.bounds();
+        let cursor = context.cursor();
+        let frame = PluginFrame {
+            width: bounds.x,
+            height: bounds.y,
+            steps: context.steps,
+            fixed_dt: context.fixed_dt,
+            frame_dt: context.frame_dt,
+            has_cursor: cursor.is_some(),
+            cursor_x: cursor.map_or(0.0, |cursor| cursor.x),
+            cursor_y: cursor.map_or(0.0, |cursor| cursor.y),
+            mouse_held: context.input.is_mouse_held(MouseButton::Left),
+            mouse_pressed: context.input.was_mouse_pressed(MouseButton::Left),
+            random: context.rng.next_u32(),
+        };
+        unsafe { (self.plugin.api().update)(self.state, &frame) };
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        if self.state.is_null() {
+            return;
+        }
+        let sink = draws as *mut DrawList as *mut c_void;
+        unsafe { (self.plugin.api().record_draws)(self.state, sink, emit_circle) };
+    }
+}
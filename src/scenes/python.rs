@@ -0,0 +1,146 @@
+use std::ffi::CString;
+use std::path::PathBuf;
+
+use pyo3::prelude::*;
+use pyo3::types::PyModule;
+use winit::event::MouseButton;
+
+use crate::python::World;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+
+// Used without --script, or when the script fails to load
+const DEFAULT_SCRIPT: &str = include_str!("../../assets/python/fountain.py");
+
+// Hands a `World` to a Python script's `setup`, `update` and `on_click`
+// functions. The script is read from `path` (falling back to the built-in
+// fountain). A callback that raises is printed with its traceback and not
+// called again.
+pub struct PythonScene {
+    path: Option<PathBuf>,
+    script: Option<Py<PyModule>>,
+    world: Option<Py<World>>,
+    update_failed: bool,
+    click_failed: bool,
+}
+
+impl PythonScene {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            script: None,
+            world: None,
+            update_failed: false,
+            click_failed: false,
+        }
+    }
+
+    fn load_script<'py>(&self, py: Python<'py>) -> Bound<'py, PyModule> {
+        if let Some(path) = self.path.as_ref() {
+            let result = std::fs::read_to_string(path)
+                .map_err(|error| error.to_string())
+                .and_then(|source| {
+                    let source = CString::new(source).map_err(|error| error.to_string())?;
+                    let file_name = CString::new(path.to_string_lossy().as_bytes())
+                        .map_err(|error| error.to_string())?;
+                    PyModule::from_code(py, &source, &file_name, c"script").map_err(|error| {
+                        error.display(py);
+                        "the script raised an exception".to_string()
+                    })
+                });
+            match result {
+                Ok(module) => {
+                    println!("Loaded Python script from {}", path.display());
+                    return module;
+                }
+                Err(error) => {
+                    eprintln!("Failed to load {}: {}", path.display(), error);
+                    eprintln!("Falling back to the built-in script");
+                }
+            }
+        }
+        let source = CString::new(DEFAULT_SCRIPT).unwrap();
+        PyModule::from_code(py, &source, c"fountain.py", c"script")
+            .expect("Failed to load built-in script")
+    }
+
+    // Calls the script's function `name` through `call`, if the script defines
+    // one. Returns false if it raised.
+    fn call<'py>(
+        py: Python<'py>,
+        script: &Bound<'py, PyModule>,
+        name: &str,
+        call: impl FnOnce(Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>>,
+    ) -> bool {
+        let result = script.getattr_opt(name).and_then(|callback| match callback {
+            Some(callback) => call(callback).map(drop),
+            None => Ok(()),
+        });
+        match result {
+            Ok(()) => true,
+            Err(error) => {
+                eprintln!("Python {} failed:", name);
+                error.display(py);
+                false
+            }
+        }
+    }
+}
+
+impl Scene for PythonScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        Python::attach(|py| {
+            let script = self.load_script(py);
+            let world = Py::new(py, World::new(renderer.bounds())).expect("Failed to create world");
+            Self::call(py, &script, "setup", |setup| setup.call1((&world,)));
+            self.script = Some(script.unbind());
+            self.world = Some(world);
+        });
+        self.update_failed = false;
+        self.click_failed = false;
+    }
+
+    fn destroy_resources(&mut self, _renderer: &mut Renderer) {
+        Python::attach(|_| {
+            self.script = None;
+            self.world = None;
+        });
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        let (Some(script), Some(world)) = (self.script.as_ref(), self.world.as_ref()) else {
+            return;
+        };
+        Python::attach(|py| {
+            let script = script.bind(py);
+            {
+                let mut world = world.borrow_mut(py);
+                world.set_bounds(context.bounds());
+                for _ in 0..context.steps {
+                    world.step(context.fixed_dt);
+                }
+            }
+            if context.steps > 0 && !self.update_failed {
+                let dt = context.sim_dt();
+                let ok = Self::call(py, script, "update", |update| {
+                    update.call1((world, dt))
+                });
+                self.update_failed = !ok;
+            }
+            if let Some(cursor) = context.cursor() {
+                if context.input.was_mouse_pressed(MouseButton::Left) && !self.click_failed {
+                    let ok = Self::call(py, script, "on_click", |on_click| {
+                        on_click.call1((world, cursor.x, cursor.y))
+                    });
+                    self.click_failed = !ok;
+                }
+            }
+        });
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        if let Some(world) = self.world.as_ref() {
+            Python::attach(|py| world.borrow(py).record_draws(draws));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use glam::{Vec2, Vec3};
+use winit::event::MouseButton;
+
+use crate::camera::OrbitCamera;
+use crate::input::Action;
+use crate::raytracing::TracedSphere;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+
+const CAMERA_TARGET: Vec3 = Vec3::new(0.0, 0.8, 0.0);
+const CAMERA_DISTANCE: f32 = 8.0;
+// Logical units of drag per second at full stick deflection
+const STICK_ROTATE_SPEED: f32 = 150.0;
+// A huge sphere under the others stands in for the floor
+const GROUND_RADIUS: f32 = 1000.0;
+const SPHERES: [TracedSphere; 6] = [
+    TracedSphere {
+        center: Vec3::new(0.0, -GROUND_RADIUS, 0.0),
+        radius: GROUND_RADIUS,
+        color: [0.55, 0.55, 0.55, 1.0],
+    },
+    TracedSphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+        color: [0.9, 0.25, 0.2, 1.0],
+    },
+    TracedSphere {
+        center: Vec3::new(2.2, 0.7, 0.6),
+        radius: 0.7,
+        color: [0.25, 0.8, 0.3, 1.0],
+    },
+    TracedSphere {
+        center: Vec3::new(-2.0, 0.8, -0.4),
+        radius: 0.8,
+        color: [0.25, 0.45, 0.95, 1.0],
+    },
+    TracedSphere {
+        center: Vec3::new(0.6, 0.4, 2.0),
+        radius: 0.4,
+        color: [0.95, 0.8, 0.2, 1.0],
+    },
+    TracedSphere {
+        center: Vec3::new(-0.9, 0.35, 1.9),
+        radius: 0.35,
+        color: [0.8, 0.3, 0.9, 1.0],
+    },
+];
+
+// A few spheres on a floor, ray traced with the renderer's
+// `RayTracedSpheres` pass. Drag with the left button to orbit the camera and
+// scroll to dolly; C resets the view. Without ray tracing support only the
+// clear color is shown.
+pub struct RayTraceScene {
+    camera: OrbitCamera,
+    last_cursor: Option<Vec2>,
+}
+
+impl RayTraceScene {
+    pub fn new() -> Self {
+        Self {
+            camera: OrbitCamera::new(CAMERA_TARGET, CAMERA_DISTANCE),
+            last_cursor: None,
+        }
+    }
+}
+
+impl Default for RayTraceScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for RayTraceScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.enable_ray_traced_spheres(&SPHERES);
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_ray_traced_spheres();
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        if context.input.was_pressed(Action::ClearBalls) {
+            self.camera = OrbitCamera::new(CAMERA_TARGET, CAMERA_DISTANCE);
+        }
+        let cursor = context.cursor();
+        if let (Some(cursor), Some(last)) = (cursor, self.last_cursor) {
+            if context.input.is_mouse_held(MouseButton::Left) {
+                self.camera.rotate(cursor - last);
+            }
+        }
+        self.last_cursor = cursor;
+        self.camera.dolly(context.input.scroll_delta());
+        self.camera
+            .rotate(context.stick * STICK_ROTATE_SPEED * context.frame_dt);
+
+        let bounds = context.bounds();
+        let aspect_ratio = bounds.x / bounds.y.max(1.0);
+        let Some(pass) = context.renderer.ray_traced_mut() else {
+            return;
+        };
+        pass.view_projection = self.camera.view_projection(aspect_ratio);
+        pass.eye = self.camera.eye();
+    }
+
+    fn record_draws(&self, _draws: &mut DrawList) {
+        // The renderer draws the traced image itself
+    }
+}
@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Scope, AST, FLOAT};
+use winit::event::MouseButton;
+
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+use crate::script::{self, World};
+
+// Used without --script, or when the script fails to compile on the first load
+const DEFAULT_SCRIPT: &str = include_str!("../../assets/rhai/orbits.rhai");
+// Seconds of wall-clock time between checks for a changed script file
+const RELOAD_INTERVAL: f32 = 0.5;
+
+// Hands a `World` to a Rhai script's `setup`, `update` and `on_click`
+// functions. The script is read from `path` (falling back to the built-in
+// orbits) and reloaded when the file changes: the world is emptied and `setup`
+// runs again. A failed reload keeps the running script. A callback that fails
+// is printed and not called again until the next reload.
+pub struct ScriptScene {
+    path: Option<PathBuf>,
+    engine: Engine,
+    ast: AST,
+    // Top-level variables of the script, kept between calls
+    scope: Scope<'static>,
+    world: World,
+    modified: Option<SystemTime>,
+    reload_timer: f32,
+    update_failed: bool,
+    click_failed: bool,
+}
+
+impl ScriptScene {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            engine: script::engine(),
+            ast: AST::empty(),
+            scope: Scope::new(),
+            world: World::new(glam::Vec2::ZERO),
+            modified: None,
+            reload_timer: 0.0,
+            update_failed: false,
+            click_failed: false,
+        }
+    }
+
+    fn modified_time(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    // Reads and compiles the script file, or None (after printing why) if that fails
+    fn compile_file(&self) -> Option<AST> {
+        let path = self.path.as_ref()?;
+        let result = std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| self.engine.compile(source).map_err(|error| error.to_string()));
+        match result {
+            Ok(ast) => Some(ast),
+            Err(error) => {
+                eprintln!("Failed to load {}: {}", path.display(), error);
+                None
+            }
+        }
+    }
+
+    // Runs the script's top level and `setup` against an empty world
+    fn start(&mut self, ast: AST) {
+        self.ast = ast;
+        self.scope = Scope::new();
+        self.world.shapes().clear();
+        self.update_failed = false;
+        self.click_failed = false;
+        if let Err(error) = self.engine.run_ast_with_scope(&mut self.scope, &self.ast) {
+            eprintln!("Script failed: {}", error);
+        }
+        self.call("setup", (self.world.clone(),));
+    }
+
+    fn reload_if_changed(&mut self) {
+        let modified = self.modified_time();
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        if let Some(ast) = self.compile_file() {
+            println!("Reloaded {}", self.path.as_ref().unwrap().display());
+            self.start(ast);
+        }
+    }
+
+    // Calls the script's function `name` with `args`, if the script defines one
+    // taking that many. Returns false if it failed.
+    fn call(&mut self, name: &str, args: impl FuncArgs) -> bool {
+        let mut values = Vec::new();
+        args.parse(&mut values);
+        let defined = self
+            .ast
+            .iter_functions()
+            .any(|function| function.name == name && function.params.len() == values.len());
+        if !defined {
+            return true;
+        }
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            values,
+        );
+        match result {
+            Ok(_) => true,
+            Err(error) => {
+                eprintln!("Script {} failed: {}", name, error);
+                false
+            }
+        }
+    }
+}
+
+impl Scene for ScriptScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        self.world.shapes().set_bounds(renderer.bounds());
+        self.modified = self.modified_time();
+        let ast = match self.compile_file() {
+            Some(ast) => {
+                println!("Loaded script from {}", self.path.as_ref().unwrap().display());
+                ast
+            }
+            None => {
+                if self.path.is_some() {
+                    eprintln!("Falling back to the built-in script");
+                }
+                self.engine
+                    .compile(DEFAULT_SCRIPT)
+                    .expect("Failed to compile built-in script")
+            }
+        };
+        self.start(ast);
+    }
+
+    fn destroy_resources(&mut self, _renderer: &mut Renderer) {
+        self.world.shapes().clear();
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        self.reload_timer += context.frame_dt;
+        if self.reload_timer >= RELOAD_INTERVAL {
+            self.reload_timer = 0.0;
+            self.reload_if_changed();
+        }
+
+        {
+            let mut shapes = self.world.shapes();
+            shapes.set_bounds(context.bounds());
+            for _ in 0..context.steps {
+                shapes.step(context.fixed_dt);
+            }
+        }
+        if context.steps > 0 && !self.update_failed {
+            let dt = context.sim_dt() as FLOAT;
+            self.update_failed = !self.call("update", (self.world.clone(), dt));
+        }
+        if let Some(cursor) = context.cursor() {
+            if context.input.was_mouse_pressed(MouseButton::Left) && !self.click_failed {
+                let args = (self.world.clone(), cursor.x as FLOAT, cursor.y as FLOAT);
+                self.click_failed = !self.call("on_click", args);
+            }
+        }
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        self.world.shapes().record_draws(draws);
+    }
+}
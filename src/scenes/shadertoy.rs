@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use glam::Vec2;
+use winit::event::MouseButton;
+
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+use crate::shadertoy;
+
+// Runs a ShaderToy-style `mainImage` fragment shader over the whole window.
+// The GLSL is read from `path` (falling back to a built-in shader if there is
+// none or it fails to compile). iTime follows the simulation clock, so pause
+// and time scale apply.
+pub struct ShaderToyScene {
+    path: Option<PathBuf>,
+    time: f32,
+    frame: i32,
+    mouse: [f32; 4],
+}
+
+impl ShaderToyScene {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            time: 0.0,
+            frame: 0,
+            mouse: [0.0; 4],
+        }
+    }
+
+    fn load_spirv(&self) -> Vec<u32> {
+        if let Some(path) = self.path.as_ref() {
+            let result = std::fs::read_to_string(path)
+                .map_err(|error| error.to_string())
+                .and_then(|source| shadertoy::compile(&source));
+            match result {
+                Ok(spirv) => {
+                    println!("Loaded ShaderToy shader from {}", path.display());
+                    return spirv;
+                }
+                Err(error) => {
+                    eprintln!("Failed to load {}:\n{}", path.display(), error);
+                    eprintln!("Falling back to the built-in shader");
+                }
+            }
+        }
+        shadertoy::compile(shadertoy::DEFAULT_SHADER).expect("Failed to compile built-in shader")
+    }
+
+    // ShaderToy's iMouse convention with a bottom-left origin
+    fn update_mouse(&mut self, context: &SceneContext, height: f32) {
+        let held = context.input.is_mouse_held(MouseButton::Left);
+        if let Some(cursor) = context.cursor() {
+            let cursor = Vec2::new(cursor.x, height - cursor.y);
+            if context.input.was_mouse_pressed(MouseButton::Left) {
+                self.mouse[2] = cursor.x;
+                self.mouse[3] = cursor.y;
+            }
+            if held {
+                self.mouse[0] = cursor.x;
+                self.mouse[1] = cursor.y;
+            }
+        }
+        if !held {
+            self.mouse[2] = -self.mouse[2].abs();
+        }
+    }
+}
+
+impl Scene for ShaderToyScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        let spirv = self.load_spirv();
+        renderer.enable_shadertoy(&spirv);
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_shadertoy();
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        let bounds = context.bounds();
+        self.update_mouse(context, bounds.y);
+        let sim_dt = context.sim_dt();
+        self.time += sim_dt;
+        if context.steps > 0 {
+            self.frame += 1;
+        }
+        if let Some(pass) = context.renderer.shadertoy_mut() {
+            pass.inputs = shadertoy::ShaderToyInputs {
+                resolution: [bounds.x, bounds.y, 1.0],
+                time: self.time,
+                mouse: self.mouse,
+                time_delta: sim_dt,
+                frame: self.frame,
+            };
+        }
+    }
+
+    fn record_draws(&self, _draws: &mut DrawList) {
+        // The renderer draws the fullscreen pass itself
+    }
+}
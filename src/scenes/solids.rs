@@ -0,0 +1,206 @@
+use std::f32::consts::TAU;
+use std::path::PathBuf;
+
+use glam::{Mat4, Quat, Vec2, Vec3};
+use winit::event::MouseButton;
+
+use crate::camera::OrbitCamera;
+use crate::color::hsv_to_rgba;
+use crate::input::Action;
+use crate::lighting::{Lighting, PointLight};
+use crate::mesh::{MeshData, MeshDraw, MeshId, MeshShading};
+use crate::obj;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+
+const CAMERA_DISTANCE: f32 = 7.0;
+const CUBE_HALF_EXTENT: f32 = 1.0;
+// Radius a loaded model is scaled to, about the cube's
+const MODEL_RADIUS: f32 = 1.5;
+const SPHERE_COUNT: usize = 3;
+const SPHERE_RADIUS: f32 = 0.45;
+const ORBIT_RADIUS: f32 = 2.6;
+// Radians per second at spin speed 1
+const BASE_SPIN: f32 = 0.8;
+const SPEED_STEP: f32 = 1.5;
+const MAX_SPEED: f32 = 8.0;
+// Two colored lights circling above and below the solids, in opposite directions
+const POINT_LIGHT_COLORS: [Vec3; 2] = [Vec3::new(1.0, 0.55, 0.2), Vec3::new(0.3, 0.5, 1.0)];
+const POINT_LIGHT_RADIUS: f32 = 3.5;
+const POINT_LIGHT_HEIGHT: f32 = 1.5;
+const POINT_LIGHT_RANGE: f32 = 7.0;
+// Logical units of drag per second at full stick deflection
+const STICK_ROTATE_SPEED: f32 = 150.0;
+
+// A spinning cube (or an OBJ model) with spheres orbiting it, drawn in 3D
+// through the renderer's mesh pass and lit by a directional light and two
+// orbiting point lights. Drag with the left button to orbit the camera and
+// scroll to dolly; M switches between Blinn-Phong and diffuse-only shading.
+pub struct SolidsScene {
+    camera: OrbitCamera,
+    model_path: Option<PathBuf>,
+    // What spins in the middle: the cube, or each part of the model with its
+    // material color
+    centerpiece: Vec<(MeshId, [f32; 4])>,
+    // Centers and scales the model to MODEL_RADIUS
+    centerpiece_transform: Mat4,
+    sphere: Option<MeshId>,
+    // Simulation seconds of spinning, scaled by `speed`
+    spin: f32,
+    speed: f32,
+    shading: MeshShading,
+    last_cursor: Option<Vec2>,
+}
+
+impl SolidsScene {
+    pub fn new(model_path: Option<PathBuf>) -> Self {
+        Self {
+            camera: OrbitCamera::new(Vec3::ZERO, CAMERA_DISTANCE),
+            model_path,
+            centerpiece: Vec::new(),
+            centerpiece_transform: Mat4::IDENTITY,
+            sphere: None,
+            spin: 0.0,
+            speed: 1.0,
+            shading: MeshShading::default(),
+            last_cursor: None,
+        }
+    }
+
+    // Uploads the --model file, falling back to the cube if there is none or
+    // it can't be loaded
+    fn load_centerpiece(&mut self, renderer: &mut Renderer) {
+        self.centerpiece_transform = Mat4::IDENTITY;
+        if let Some(path) = self.model_path.as_ref() {
+            match obj::load(path) {
+                Ok(meshes) => {
+                    let (center, radius) = obj::bounding_sphere(&meshes);
+                    self.centerpiece = meshes
+                        .iter()
+                        .map(|mesh| (renderer.add_mesh(&mesh.name, &mesh.data), mesh.color))
+                        .collect();
+                    self.centerpiece_transform =
+                        Mat4::from_scale(Vec3::splat(MODEL_RADIUS / radius.max(f32::EPSILON)))
+                            * Mat4::from_translation(-center);
+                    return;
+                }
+                Err(error) => eprintln!("Failed to load {}: {}", path.display(), error),
+            }
+        }
+        let cube = renderer.add_mesh("cube", &MeshData::cube(CUBE_HALF_EXTENT));
+        self.centerpiece = vec![(cube, [0.9, 0.9, 0.95, 1.0])];
+    }
+
+    fn handle_camera_input(&mut self, context: &SceneContext) {
+        let cursor = context.cursor();
+        if let (Some(cursor), Some(last)) = (cursor, self.last_cursor) {
+            if context.input.is_mouse_held(MouseButton::Left) {
+                self.camera.rotate(cursor - last);
+            }
+        }
+        self.last_cursor = cursor;
+        self.camera.dolly(context.input.scroll_delta());
+        self.camera
+            .rotate(context.stick * STICK_ROTATE_SPEED * context.frame_dt);
+    }
+}
+
+impl Default for SolidsScene {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Scene for SolidsScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.enable_meshes();
+        self.load_centerpiece(renderer);
+        self.sphere = Some(renderer.add_mesh("sphere", &MeshData::uv_sphere(SPHERE_RADIUS, 32, 16)));
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_meshes();
+        self.centerpiece.clear();
+        self.sphere = None;
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        if context.input.was_pressed(Action::ClearBalls) {
+            self.camera = OrbitCamera::new(Vec3::ZERO, CAMERA_DISTANCE);
+        }
+        if context.input.was_pressed(Action::SpeedUp) {
+            self.speed = (self.speed * SPEED_STEP).min(MAX_SPEED);
+        }
+        if context.input.was_pressed(Action::SpeedDown) {
+            self.speed = (self.speed / SPEED_STEP).max(1.0 / MAX_SPEED);
+        }
+        if context.input.was_pressed(Action::CycleCircleMode) {
+            self.shading = match self.shading {
+                MeshShading::BlinnPhong => MeshShading::Diffuse,
+                MeshShading::Diffuse => MeshShading::BlinnPhong,
+            };
+            println!("Solids shading: {:?}", self.shading);
+        }
+        self.handle_camera_input(context);
+        self.spin += context.sim_dt() * self.speed;
+
+        let bounds = context.bounds();
+        let aspect_ratio = bounds.x / bounds.y.max(1.0);
+        let Some(sphere) = self.sphere else {
+            return;
+        };
+        let angle = self.spin * BASE_SPIN;
+        let Some(pass) = context.renderer.meshes_mut() else {
+            return;
+        };
+        pass.view_projection = self.camera.view_projection(aspect_ratio);
+        pass.eye = self.camera.eye();
+        pass.shading = self.shading;
+        pass.lighting = Lighting {
+            point_lights: POINT_LIGHT_COLORS
+                .iter()
+                .enumerate()
+                .map(|(i, &color)| {
+                    let side = if i == 0 { 1.0 } else { -1.0 };
+                    let orbit = angle * 0.7 * side + i as f32 * TAU / 2.0;
+                    PointLight {
+                        position: Vec3::new(
+                            orbit.cos() * POINT_LIGHT_RADIUS,
+                            POINT_LIGHT_HEIGHT * side,
+                            orbit.sin() * POINT_LIGHT_RADIUS,
+                        ),
+                        color,
+                        intensity: 1.2,
+                        range: POINT_LIGHT_RANGE,
+                    }
+                })
+                .collect(),
+            ..Lighting::default()
+        };
+        pass.draws.clear();
+        let tilt = Vec3::new(1.0, 1.0, 0.3).normalize();
+        let rotation = Mat4::from_quat(Quat::from_axis_angle(tilt, angle));
+        for &(mesh, color) in &self.centerpiece {
+            pass.draws.push(MeshDraw {
+                mesh,
+                transform: rotation * self.centerpiece_transform,
+                color,
+            });
+        }
+        for i in 0..SPHERE_COUNT {
+            let phase = i as f32 / SPHERE_COUNT as f32;
+            let orbit = angle * 1.3 + phase * TAU;
+            // Spread evenly around the cube, bobbing above and below it twice per orbit
+            let position = Vec3::new(orbit.cos(), (orbit * 2.0).sin() * 0.3, orbit.sin()) * ORBIT_RADIUS;
+            pass.draws.push(MeshDraw {
+                mesh: sphere,
+                transform: Mat4::from_translation(position),
+                color: hsv_to_rgba(phase, 0.7, 1.0, 1.0),
+            });
+        }
+    }
+
+    fn record_draws(&self, _draws: &mut DrawList) {
+        // The renderer's mesh pass draws the solids itself
+    }
+}
@@ -0,0 +1,189 @@
+use glam::Vec2;
+use winit::event::MouseButton;
+
+use crate::background::Background;
+use crate::ball::{self, Ball, Physics};
+use crate::color::hsv_to_rgba;
+use crate::cpu_particles::{CpuParticleSystem, Emitter};
+use crate::input::Action;
+use crate::post::PostEffect;
+use crate::renderer::{Circle, Renderer};
+use crate::scene::{DrawList, Scene, SceneContext};
+use crate::sprite::TextureId;
+
+const MAX_SPARKS: usize = 20_000;
+const BALL_COUNT: usize = 5;
+const BALL_RADIUS: f32 = 24.0;
+// Wall impacts slower than this (pixels per second) make no sparks
+const MIN_IMPACT_SPEED: f32 = 80.0;
+// Sparks per pixel-per-second of impact speed
+const SPARKS_PER_SPEED: f32 = 0.1;
+const FOUNTAIN_RATE: f32 = 600.0;
+const SPARK_TEXTURE_SIZE: u32 = 16;
+// Kept well under the bloom threshold so only the sparks glow
+const PLASMA_LOW: [f32; 3] = [0.01, 0.0, 0.02];
+const PLASMA_HIGH: [f32; 3] = [0.06, 0.02, 0.1];
+const PLASMA_SPEED: f32 = 0.3;
+
+// White disc with a soft edge, tinted per spark
+fn create_spark_pixels() -> Vec<u8> {
+    let size = SPARK_TEXTURE_SIZE;
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let local = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+            let falloff = (1.0 - local.length()).clamp(0.0, 1.0);
+            pixels.extend_from_slice(&[255, 255, 255, (falloff * falloff * 255.0) as u8]);
+        }
+    }
+    pixels
+}
+
+// Balls bouncing under gravity throw sparks whenever they hit a wall; holding
+// the left button turns the cursor into a spark fountain. Showcases the CPU
+// particle system drawn as instanced sprites.
+pub struct SparksScene {
+    balls: Vec<Ball>,
+    physics: Physics,
+    sparks: CpuParticleSystem,
+    fountain: Emitter,
+    texture: Option<TextureId>,
+    bounds: Vec2,
+}
+
+impl SparksScene {
+    pub fn new() -> Self {
+        let mut sparks = CpuParticleSystem::new(MAX_SPARKS);
+        sparks.gravity = Vec2::new(0.0, 600.0);
+        sparks.drag = 0.5;
+        let fountain = Emitter {
+            spread: 0.3,
+            speed: (300.0, 500.0),
+            lifetime: (0.8, 1.6),
+            size: (3.0, 6.0),
+            color: [0.4, 0.8, 1.0, 1.0],
+            rate: FOUNTAIN_RATE,
+            ..Emitter::new(Vec2::ZERO)
+        };
+        Self {
+            balls: Vec::new(),
+            physics: Physics::bouncy(),
+            sparks,
+            fountain,
+            texture: None,
+            bounds: Vec2::ZERO,
+        }
+    }
+
+    fn spawn_ball(&mut self, context: &mut SceneContext) {
+        let position = Vec2::new(
+            context.rng.range(BALL_RADIUS, self.bounds.x - BALL_RADIUS),
+            context.rng.range(BALL_RADIUS, self.bounds.y * 0.5),
+        );
+        let velocity = Vec2::new(context.rng.range(-300.0, 300.0), 0.0);
+        let mut ball = Ball::new(position, velocity, BALL_RADIUS);
+        ball.hue_offset = context.rng.next_f32();
+        self.balls.push(ball);
+    }
+
+    // One fixed simulation step; wall hits throw sparks back off the wall
+    fn step(&mut self, context: &mut SceneContext, dt: f32) {
+        ball::collide_all(&mut self.balls, self.physics.restitution, |speed| {
+            context.sounds.bounce(speed)
+        });
+        for ball in &mut self.balls {
+            let velocity = ball.velocity;
+            let Some(normal) = ball.step(dt, self.bounds, &self.physics) else {
+                continue;
+            };
+            let impact = -velocity.dot(normal);
+            context.sounds.bounce(impact);
+            if impact < MIN_IMPACT_SPEED {
+                continue;
+            }
+            let emitter = Emitter {
+                direction: normal,
+                spread: 1.2,
+                speed: (impact * 0.3, impact * 0.9),
+                lifetime: (0.3, 0.8),
+                size: (2.0, 5.0),
+                color: hsv_to_rgba(0.08 + 0.1 * ball.hue_offset, 0.8, 1.0, 1.0),
+                ..Emitter::new(ball.position - normal * ball.radius)
+            };
+            let count = (impact * SPARKS_PER_SPEED) as usize;
+            emitter.burst(context.rng, &mut self.sparks, count);
+        }
+
+        if let Some(cursor) = context.cursor() {
+            if context.input.is_mouse_held(MouseButton::Left) {
+                self.fountain.position = cursor;
+                self.fountain.update(dt, context.rng, &mut self.sparks);
+            }
+        }
+        self.sparks.update(dt);
+    }
+}
+
+impl Default for SparksScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for SparksScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.set_post_effects(vec![PostEffect::Bloom {
+            threshold: 0.5,
+            intensity: 1.2,
+            sigma: 3.0,
+        }]);
+        renderer.set_background(Background::Plasma {
+            low: PLASMA_LOW,
+            high: PLASMA_HIGH,
+            speed: PLASMA_SPEED,
+        });
+        let pixels = create_spark_pixels();
+        self.texture =
+            Some(renderer.create_texture(SPARK_TEXTURE_SIZE, SPARK_TEXTURE_SIZE, &pixels));
+        self.bounds = renderer.bounds();
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        if let Some(texture) = self.texture.take() {
+            renderer.destroy_texture(texture);
+        }
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        self.bounds = context.bounds();
+        if self.balls.is_empty() {
+            for _ in 0..BALL_COUNT {
+                self.spawn_ball(context);
+            }
+        }
+        if context.input.was_pressed(Action::SpawnBall) {
+            self.spawn_ball(context);
+        }
+        if context.input.was_pressed(Action::ClearBalls) {
+            self.balls.clear();
+            self.sparks.clear();
+        }
+        let dt = context.fixed_dt;
+        for _ in 0..context.steps {
+            self.step(context, dt);
+        }
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        if let Some(texture) = self.texture {
+            self.sparks.draw(&mut draws.sprites, texture);
+        }
+        draws.circles.extend(self.balls.iter().map(|ball| {
+            Circle::new(
+                ball.position,
+                ball.radius,
+                hsv_to_rgba(ball.hue_offset, 0.6, 1.0, 1.0),
+            )
+        }));
+    }
+}
@@ -0,0 +1,164 @@
+use std::f32::consts::TAU;
+
+use glam::{Vec2, Vec3};
+
+use crate::geometry::{self, Tessellation};
+use crate::gradient::Gradient;
+use crate::input::Action;
+use crate::renderer::Renderer;
+use crate::rng::Rng;
+use crate::scene::{DrawList, Scene, SceneContext};
+
+const STAR_COUNT: usize = 600;
+// Depth units per second at warp 1
+const BASE_SPEED: f32 = 0.25;
+const WARP_STEP: f32 = 1.5;
+const MAX_WARP: f32 = 16.0;
+// Stars closer than this are recycled to the far plane
+const NEAR_PLANE: f32 = 0.02;
+// Porthole radius as a fraction of the smaller window side, and its rim width
+const PORTHOLE_RADIUS: f32 = 0.4;
+const RIM_WIDTH: f32 = 12.0;
+
+// Classic fly-through starfield drawn with the canvas. Stars live in a unit
+// box in front of the camera and are projected with a simple perspective divide.
+pub struct StarfieldScene {
+    // x and y in -1..1, z (depth) in NEAR_PLANE..1
+    stars: Vec<Vec3>,
+    warp: f32,
+    // Depth travelled in the last frame, used to stretch stars into streaks
+    frame_travel: f32,
+    bounds: Vec2,
+    // Only show the stars through a round window, clipped with the canvas stencil
+    porthole: bool,
+}
+
+impl StarfieldScene {
+    pub fn new() -> Self {
+        Self {
+            stars: Vec::new(),
+            warp: 1.0,
+            frame_travel: 0.0,
+            bounds: Vec2::ZERO,
+            porthole: false,
+        }
+    }
+
+    fn random_star(rng: &mut Rng, depth: f32) -> Vec3 {
+        Vec3::new(rng.range(-1.0, 1.0), rng.range(-1.0, 1.0), depth)
+    }
+
+    // Screen position of a star, or None if it projects outside the window
+    fn project(&self, star: Vec3) -> Option<Vec2> {
+        let center = self.bounds * 0.5;
+        let screen = center + star.truncate() / star.z * center.max_element() * 0.5;
+        let inside = screen.cmpge(Vec2::ZERO).all() && screen.cmple(self.bounds).all();
+        inside.then_some(screen)
+    }
+}
+
+impl Default for StarfieldScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for StarfieldScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        self.bounds = renderer.bounds();
+    }
+
+    fn destroy_resources(&mut self, _renderer: &mut Renderer) {
+        // Everything is drawn through the shared canvas; nothing to free
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        self.bounds = context.bounds();
+        if self.stars.is_empty() {
+            self.stars = (0..STAR_COUNT)
+                .map(|_| {
+                    let depth = context.rng.range(NEAR_PLANE, 1.0);
+                    Self::random_star(context.rng, depth)
+                })
+                .collect();
+        }
+        if context.input.was_pressed(Action::SpeedUp) {
+            self.warp = (self.warp * WARP_STEP).min(MAX_WARP);
+        }
+        if context.input.was_pressed(Action::SpeedDown) {
+            self.warp = (self.warp / WARP_STEP).max(1.0 / MAX_WARP);
+        }
+        if context.input.was_pressed(Action::CycleCircleMode) {
+            self.porthole = !self.porthole;
+            println!("Starfield porthole: {}", self.porthole);
+        }
+
+        let travel = BASE_SPEED * self.warp * context.fixed_dt;
+        for _ in 0..context.steps {
+            for index in 0..self.stars.len() {
+                let mut star = self.stars[index];
+                star.z -= travel;
+                if star.z < NEAR_PLANE || self.project(star).is_none() {
+                    star = Self::random_star(context.rng, 1.0);
+                }
+                self.stars[index] = star;
+            }
+        }
+        self.frame_travel = travel * context.steps as f32;
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        let center = self.bounds * 0.5;
+        let porthole_radius = self.bounds.min_element() * PORTHOLE_RADIUS;
+        // A faint blue glow towards the vanishing point
+        let sky = geometry::fill_polygon(&[
+            Vec2::ZERO,
+            Vec2::new(self.bounds.x, 0.0),
+            self.bounds,
+            Vec2::new(0.0, self.bounds.y),
+        ]);
+        let glow = Gradient::radial(center, self.bounds.max_element() * 0.6)
+            .with_stop(0.0, [0.05, 0.08, 0.2, 1.0])
+            .with_stop(1.0, [0.0, 0.0, 0.0, 1.0]);
+        draws.canvas.fill_gradient(&sky, &glow);
+        if self.porthole {
+            draws.canvas.push_clip_shape(&geometry::circle(
+                center,
+                porthole_radius,
+                Tessellation::default(),
+            ));
+        }
+        for &star in &self.stars {
+            let Some(head) = self.project(star) else {
+                continue;
+            };
+            // Nearer stars are bigger and brighter
+            let closeness = 1.0 - star.z;
+            let brightness = closeness * closeness;
+            let color = [brightness, brightness, brightness.sqrt(), 1.0];
+            let radius = 0.5 + 2.5 * closeness;
+            let tail_star = star + Vec3::Z * self.frame_travel * 2.0;
+            if let Some(tail) = self.project(tail_star).filter(|_| self.frame_travel > 0.0) {
+                draws.canvas.stroke_line(tail, head, radius, color);
+            }
+            draws.canvas.fill_circle(head, radius, color);
+        }
+        if self.porthole {
+            draws.canvas.pop_clip();
+            let rim = geometry::arc(
+                center,
+                porthole_radius,
+                porthole_radius + RIM_WIDTH,
+                0.0,
+                TAU,
+                Tessellation::default(),
+            );
+            // Brushed metal, lit from the top left
+            let metal = Gradient::linear(center - porthole_radius, center + porthole_radius)
+                .with_stop(0.0, [0.7, 0.72, 0.75, 1.0])
+                .with_stop(0.5, [0.3, 0.32, 0.36, 1.0])
+                .with_stop(1.0, [0.5, 0.52, 0.56, 1.0]);
+            draws.canvas.fill_gradient(&rim, &metal);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+
+use glam::Vec2;
+use winit::event::MouseButton;
+
+use crate::camera::Camera2d;
+use crate::input::Action;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+use crate::tilemap::{self, TileAtlas, TileMap};
+
+// Shown without --map; relative to the crate so it loads from any directory
+const DEFAULT_MAP: &str = "assets/tilemaps/island.tmx";
+// Screen pixels per world pixel when the map opens
+const START_ZOOM: f32 = 2.0;
+// Scale factor per scroll notch
+const ZOOM_STEP: f32 = 1.25;
+// Screen units per second the arrow keys or a full stick move the view
+const PAN_SPEED: f32 = 500.0;
+
+// A Tiled map from --map (or the built-in island) drawn through the sprite
+// batcher. Drag with the left button or use the arrow keys to scroll, and the
+// wheel to zoom around the cursor; C recenters the view.
+pub struct TilemapScene {
+    map_path: Option<PathBuf>,
+    map: Option<TileMap>,
+    atlas: Option<TileAtlas>,
+    camera: Camera2d,
+    bounds: Vec2,
+    last_cursor: Option<Vec2>,
+}
+
+impl TilemapScene {
+    pub fn new(map_path: Option<PathBuf>) -> Self {
+        Self {
+            map_path,
+            map: None,
+            atlas: None,
+            camera: Camera2d::new(Vec2::ZERO, START_ZOOM),
+            bounds: Vec2::ZERO,
+            last_cursor: None,
+        }
+    }
+
+    // The --map file, falling back to the built-in map if there is none or it
+    // can't be loaded
+    fn load_map(&self, renderer: &mut Renderer) -> Option<(TileMap, TileAtlas)> {
+        let default = Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_MAP);
+        let paths = self.map_path.iter().chain(std::iter::once(&default));
+        for path in paths {
+            let loaded = tilemap::load(path).and_then(|map| {
+                let atlas = TileAtlas::new(renderer, &map)?;
+                Ok((map, atlas))
+            });
+            match loaded {
+                Ok(loaded) => return Some(loaded),
+                Err(error) => eprintln!("Failed to load tile map {}: {}", path.display(), error),
+            }
+        }
+        None
+    }
+
+    fn reset_camera(&mut self) {
+        let center = self
+            .map
+            .as_ref()
+            .map_or(Vec2::ZERO, |map| map.pixel_size() * 0.5);
+        self.camera = Camera2d::new(center, START_ZOOM);
+    }
+}
+
+impl Scene for TilemapScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        if let Some((map, atlas)) = self.load_map(renderer) {
+            self.map = Some(map);
+            self.atlas = Some(atlas);
+        }
+        self.bounds = renderer.bounds();
+        self.reset_camera();
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        if let Some(atlas) = self.atlas.take() {
+            atlas.destroy(renderer);
+        }
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        self.bounds = context.bounds();
+        if context.input.was_pressed(Action::ClearBalls) {
+            self.reset_camera();
+        }
+
+        // Scrolling is part of looking around, so it ignores pause
+        let mut direction = context.stick;
+        if context.input.is_held(Action::SteerLeft) {
+            direction.x -= 1.0;
+        }
+        if context.input.is_held(Action::SteerRight) {
+            direction.x += 1.0;
+        }
+        if context.input.is_held(Action::SteerUp) {
+            direction.y -= 1.0;
+        }
+        if context.input.is_held(Action::SteerDown) {
+            direction.y += 1.0;
+        }
+        let step = direction.clamp_length_max(1.0) * PAN_SPEED * context.frame_dt;
+        self.camera.pan(-step);
+
+        let cursor = context.cursor();
+        if let (Some(cursor), Some(last)) = (cursor, self.last_cursor) {
+            if context.input.is_mouse_held(MouseButton::Left) {
+                self.camera.pan(cursor - last);
+            }
+        }
+        self.last_cursor = cursor;
+        let scroll = context.input.scroll_delta();
+        if scroll != 0.0 {
+            let anchor = cursor.unwrap_or(self.bounds * 0.5);
+            self.camera
+                .zoom_at(anchor, self.bounds, ZOOM_STEP.powf(scroll));
+        }
+
+        // Keep the middle of the view over the map
+        if let Some(map) = self.map.as_ref() {
+            self.camera.position = self.camera.position.clamp(Vec2::ZERO, map.pixel_size());
+        }
+    }
+
+    fn record_draws(&self, draws: &mut DrawList) {
+        if let (Some(map), Some(atlas)) = (self.map.as_ref(), self.atlas.as_ref()) {
+            map.draw(&mut draws.sprites, atlas, &self.camera, self.bounds);
+        }
+    }
+}
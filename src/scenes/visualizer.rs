@@ -0,0 +1,106 @@
+#[cfg(feature = "audio")]
+use crate::audio::AudioCapture;
+use crate::renderer::Renderer;
+use crate::scene::{DrawList, Scene, SceneContext};
+use crate::spectrum::{self, SpectrumAnalyzer, FFT_SIZE};
+
+// Sample rate of the built-in demo signal
+const DEMO_SAMPLE_RATE: f32 = 48_000.0;
+
+// Music visualizer: spectrum bars and a waveform driven by the default audio
+// input (with the `audio` feature) or by a built-in demo signal otherwise
+pub struct VisualizerScene {
+    analyzer: SpectrumAnalyzer,
+    samples: Vec<f32>,
+    // Seconds of demo signal played so far
+    time: f64,
+    #[cfg(feature = "audio")]
+    capture: Option<AudioCapture>,
+}
+
+impl VisualizerScene {
+    pub fn new() -> Self {
+        Self {
+            analyzer: SpectrumAnalyzer::new(DEMO_SAMPLE_RATE),
+            samples: vec![0.0; FFT_SIZE],
+            time: 0.0,
+            #[cfg(feature = "audio")]
+            capture: None,
+        }
+    }
+
+    // Fills `samples` with the newest FFT_SIZE samples; false when there is no
+    // live capture
+    #[cfg(feature = "audio")]
+    fn capture_samples(&mut self) -> bool {
+        let Some(capture) = self.capture.as_ref() else {
+            return false;
+        };
+        capture.latest(&mut self.samples);
+        true
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn capture_samples(&mut self) -> bool {
+        false
+    }
+
+    // The demo signal over the window ending at `time`
+    fn demo_samples(&mut self) {
+        let rate = DEMO_SAMPLE_RATE as f64;
+        for (i, sample) in self.samples.iter_mut().enumerate() {
+            let age = (FFT_SIZE - 1 - i) as f64 / rate;
+            *sample = spectrum::demo_signal(self.time - age);
+        }
+    }
+}
+
+impl Default for VisualizerScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene for VisualizerScene {
+    fn create_resources(&mut self, renderer: &mut Renderer) {
+        renderer.enable_visualizer();
+        #[cfg(feature = "audio")]
+        {
+            self.capture = AudioCapture::new(FFT_SIZE);
+            if let Some(capture) = self.capture.as_ref() {
+                self.analyzer = SpectrumAnalyzer::new(capture.sample_rate());
+            }
+        }
+    }
+
+    fn destroy_resources(&mut self, renderer: &mut Renderer) {
+        renderer.disable_visualizer();
+        #[cfg(feature = "audio")]
+        {
+            self.capture = None;
+        }
+    }
+
+    fn update(&mut self, context: &mut SceneContext) {
+        // Pausing freezes the bars
+        if context.steps == 0 {
+            return;
+        }
+        let dt = context.sim_dt();
+        self.time += dt as f64;
+        if !self.capture_samples() {
+            self.demo_samples();
+        }
+        self.analyzer.analyze(&self.samples, dt);
+
+        if let Some(pass) = context.renderer.visualizer_mut() {
+            pass.bands = *self.analyzer.bands();
+            pass.waveform = *self.analyzer.waveform();
+            pass.time = self.time as f32;
+        }
+    }
+
+    fn record_draws(&self, _draws: &mut DrawList) {
+        // The renderer draws the fullscreen pass itself
+    }
+}
@@ -0,0 +1,124 @@
+// Rhai bindings for the script scene (`rhai` feature). A script gets a `World`,
+// the `ShapeWorld` of circles it can spawn, move and recolor. Numbers passed to
+// the API are floats (`100.0`, not `100`) and shape ids are integers.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glam::Vec2;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Position, FLOAT, INT};
+
+use crate::color::hsv_to_rgba;
+use crate::shapes::{self, Shape, ShapeWorld};
+
+// Shared between the scene and every copy the script holds
+#[derive(Clone)]
+pub struct World(Rc<RefCell<ShapeWorld>>);
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+impl World {
+    pub fn new(bounds: Vec2) -> Self {
+        Self(Rc::new(RefCell::new(ShapeWorld::new(bounds))))
+    }
+
+    pub fn shapes(&self) -> std::cell::RefMut<'_, ShapeWorld> {
+        self.0.borrow_mut()
+    }
+
+    fn with_shape<T>(&mut self, id: INT, f: impl FnOnce(&mut Shape) -> T) -> ScriptResult<T> {
+        let mut shapes = self.0.borrow_mut();
+        let shape = u64::try_from(id).ok().and_then(|id| shapes.get_mut(id));
+        match shape {
+            Some(shape) => Ok(f(shape)),
+            None => Err(runtime_error(format!("no shape with id {}", id))),
+        }
+    }
+}
+
+fn runtime_error(message: String) -> Box<EvalAltResult> {
+    Box::new(EvalAltResult::ErrorRuntime(message.into(), Position::NONE))
+}
+
+// [r, g, b] or [r, g, b, a], each a float in 0..1
+fn parse_color(color: Array) -> ScriptResult<[f32; 4]> {
+    let components: Option<Vec<f32>> = color
+        .iter()
+        .map(|value| value.as_float().ok().map(|value| value as f32))
+        .collect();
+    components
+        .as_deref()
+        .and_then(shapes::parse_color)
+        .ok_or_else(|| runtime_error(shapes::COLOR_ERROR.to_string()))
+}
+
+fn color_array(color: [f32; 4]) -> Array {
+    color.iter().map(|&value| Dynamic::from_float(value as FLOAT)).collect()
+}
+
+fn vector_array(vector: Vec2) -> Array {
+    vec![Dynamic::from_float(vector.x as FLOAT), Dynamic::from_float(vector.y as FLOAT)]
+}
+
+fn spawn_circle(world: &mut World, x: FLOAT, y: FLOAT, radius: FLOAT, color: Array) -> ScriptResult<INT> {
+    let color = parse_color(color)?;
+    let position = Vec2::new(x as f32, y as f32);
+    Ok(world.shapes().spawn_circle(position, radius as f32, color) as INT)
+}
+
+fn set_color(world: &mut World, id: INT, color: Array) -> ScriptResult<()> {
+    let color = parse_color(color)?;
+    world.with_shape(id, |shape| shape.color = color)
+}
+
+// An engine with the `World` API and an `hsv(h, s, v)` color helper registered
+pub fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .register_type_with_name::<World>("World")
+        .register_get("width", |world: &mut World| world.shapes().bounds().x as FLOAT)
+        .register_get("height", |world: &mut World| world.shapes().bounds().y as FLOAT)
+        .register_get("time", |world: &mut World| world.shapes().time() as FLOAT)
+        .register_get("len", |world: &mut World| world.shapes().len() as INT)
+        .register_fn("spawn_circle", spawn_circle)
+        .register_fn(
+            "spawn_circle",
+            |world: &mut World, x: FLOAT, y: FLOAT, radius: FLOAT| {
+                spawn_circle(world, x, y, radius, color_array([1.0; 4]))
+            },
+        )
+        .register_fn("remove", |world: &mut World, id: INT| {
+            u64::try_from(id).is_ok_and(|id| world.shapes().remove(id))
+        })
+        .register_fn("clear", |world: &mut World| world.shapes().clear())
+        .register_fn("ids", |world: &mut World| -> Array {
+            let ids = world.shapes().ids();
+            ids.into_iter().map(|id| Dynamic::from_int(id as INT)).collect()
+        })
+        .register_fn("position", |world: &mut World, id: INT| {
+            world.with_shape(id, |shape| vector_array(shape.ball.position))
+        })
+        .register_fn("set_position", |world: &mut World, id: INT, x: FLOAT, y: FLOAT| {
+            world.with_shape(id, |shape| shape.ball.position = Vec2::new(x as f32, y as f32))
+        })
+        .register_fn("velocity", |world: &mut World, id: INT| {
+            world.with_shape(id, |shape| vector_array(shape.ball.velocity))
+        })
+        .register_fn("set_velocity", |world: &mut World, id: INT, vx: FLOAT, vy: FLOAT| {
+            world.with_shape(id, |shape| shape.ball.velocity = Vec2::new(vx as f32, vy as f32))
+        })
+        .register_fn("set_radius", |world: &mut World, id: INT, radius: FLOAT| {
+            world.with_shape(id, |shape| shape.ball.radius = (radius as f32).max(0.0))
+        })
+        .register_fn("set_color", set_color)
+        .register_fn("set_gravity", |world: &mut World, x: FLOAT, y: FLOAT| {
+            world.shapes().physics.gravity = Vec2::new(x as f32, y as f32);
+        })
+        .register_fn("set_restitution", |world: &mut World, restitution: FLOAT| {
+            world.shapes().physics.restitution = (restitution as f32).clamp(0.0, 1.0);
+        })
+        .register_fn("hsv", |hue: FLOAT, saturation: FLOAT, value: FLOAT| {
+            color_array(hsv_to_rgba(hue as f32, saturation as f32, value as f32, 1.0))
+        });
+    engine
+}
@@ -0,0 +1,162 @@
+use ash::vk;
+
+use crate::gpu::GpuContext;
+use crate::pipeline::{PipelineDesc, PipelineManager};
+
+// Declares the ShaderToy inputs ahead of the user's code. Push constants stand
+// in for ShaderToy's uniforms; the macros keep the familiar names.
+const HEADER: &str = "#version 450
+layout(push_constant) uniform ShaderToyInputs {
+    vec3 resolution;
+    float time;
+    vec4 mouse;
+    float timeDelta;
+    int frame;
+} shaderToy;
+#define iResolution shaderToy.resolution
+#define iTime shaderToy.time
+#define iMouse shaderToy.mouse
+#define iTimeDelta shaderToy.timeDelta
+#define iFrame shaderToy.frame
+";
+
+// Calls the user's mainImage with ShaderToy's bottom-left pixel origin
+const FOOTER: &str = "
+layout(location = 0) out vec4 shaderToyColor;
+void main() {
+    vec4 color = vec4(0.0, 0.0, 0.0, 1.0);
+    mainImage(color, vec2(gl_FragCoord.x, iResolution.y - gl_FragCoord.y));
+    shaderToyColor = vec4(color.rgb, 1.0);
+}
+";
+
+// Shown when no shader path is given or the user's shader fails to compile
+pub const DEFAULT_SHADER: &str = "
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = fragCoord / iResolution.xy;
+    vec3 color = 0.5 + 0.5 * cos(iTime + uv.xyx + vec3(0.0, 2.0, 4.0));
+    if (iMouse.z > 0.0) {
+        color += 0.5 * smoothstep(40.0, 0.0, length(fragCoord - iMouse.xy));
+    }
+    fragColor = vec4(color, 1.0);
+}
+";
+
+// Mirrors the push constant block in HEADER (std430)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShaderToyInputs {
+    // Viewport size in pixels; z is the pixel aspect ratio (always 1)
+    pub resolution: [f32; 3],
+    pub time: f32,
+    // xy: cursor while the button is held, zw: click position. z is negative
+    // once the button is released, as on ShaderToy. Bottom-left origin.
+    pub mouse: [f32; 4],
+    pub time_delta: f32,
+    pub frame: i32,
+}
+
+// Wraps a ShaderToy `mainImage` in a fragment shader and compiles it to SPIR-V.
+// Errors carry naga's annotated message for the wrapped source.
+pub fn compile(source: &str) -> Result<Vec<u32>, String> {
+    let full_source = format!("{}{}{}", HEADER, source, FOOTER);
+    let mut frontend = naga::front::glsl::Frontend::default();
+    let module = frontend
+        .parse(
+            &naga::front::glsl::Options::from(naga::ShaderStage::Fragment),
+            &full_source,
+        )
+        .map_err(|error| error.emit_to_string(&full_source))?;
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|error| error.emit_to_string(&full_source))?;
+
+    let mut options = naga::back::spv::Options {
+        lang_version: (1, 0),
+        ..Default::default()
+    };
+    options
+        .flags
+        .remove(naga::back::spv::WriterFlags::ADJUST_COORDINATE_SPACE);
+    naga::back::spv::write_vec(
+        &module,
+        &info,
+        &options,
+        Some(&naga::back::spv::PipelineOptions {
+            shader_stage: naga::ShaderStage::Fragment,
+            entry_point: "main".into(),
+        }),
+    )
+    .map_err(|error| error.to_string())
+}
+
+// A fullscreen triangle running a compiled ShaderToy fragment shader. Drawn as
+// the scene background; `inputs` is pushed every frame.
+pub struct ShaderToyPass {
+    pub inputs: ShaderToyInputs,
+    pipeline: PipelineDesc,
+}
+
+impl ShaderToyPass {
+    pub fn new(gpu: &GpuContext, pipelines: &mut PipelineManager, fragment_spirv: &[u32]) -> Self {
+        let pipeline_layout = unsafe {
+            gpu.device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo {
+                        push_constant_range_count: 1,
+                        p_push_constant_ranges: &vk::PushConstantRange {
+                            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                            offset: 0,
+                            size: size_of::<ShaderToyInputs>() as u32,
+                        },
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create ShaderToy pipeline layout")
+        };
+        let shaders = pipelines.register_shaders(
+            gpu,
+            "shadertoy",
+            include_bytes!("../shaders/fullscreen_vert.spv"),
+            bytemuck::cast_slice(fragment_spirv),
+        );
+        let pipeline = PipelineDesc::new(shaders, pipeline_layout);
+        pipelines.prepare(gpu, &pipeline);
+        println!("ShaderToy pass created");
+
+        Self {
+            inputs: ShaderToyInputs::default(),
+            pipeline,
+        }
+    }
+
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let pipeline = pipelines.get(gpu, &self.pipeline);
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.inputs),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, pipelines: &mut PipelineManager) {
+        pipelines.release_shaders(device, self.pipeline.shaders);
+        unsafe { device.destroy_pipeline_layout(self.pipeline.layout, None) };
+    }
+}
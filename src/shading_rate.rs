@@ -0,0 +1,440 @@
+use ash::vk;
+use glam::Vec2;
+
+use crate::canvas::Canvas;
+use crate::frame_ring::FrameRing;
+use crate::gpu::GpuContext;
+use crate::memory::MemoryCategory;
+use crate::sync::{Access, Barriers};
+
+const RATE_FORMAT: vk::Format = vk::Format::R8_UINT;
+const COLOR_SUBRESOURCE_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+// Scene pixels each rate texel covers, if the device allows it
+const PREFERRED_TEXEL_SIZE: u32 = 16;
+// Rates as the attachment encodes them: log2 of the fragment width in bits 2-3
+// and log2 of its height in bits 0-1
+const RATE_1X1: u8 = 0b0000;
+const RATE_2X2: u8 = 0b0101;
+const RATE_4X4: u8 = 0b1010;
+// Scene pixels past the focus circle's edge shaded at full rate, and then at 2x2
+const FULL_RATE_MARGIN: f32 = 96.0;
+const HALF_RATE_MARGIN: f32 = 320.0;
+// Overlay tints for texels shaded at 2x2 and at 4x4
+const HALF_RATE_TINT: [f32; 4] = [1.0, 0.8, 0.1, 0.25];
+const QUARTER_RATE_TINT: [f32; 4] = [1.0, 0.2, 0.1, 0.3];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShadingRateMode {
+    // Every texel at full rate
+    Off,
+    // Full rate around the focus, coarser further out
+    On,
+    // Like `On`, with the coarse texels tinted on screen
+    Overlay,
+}
+
+impl ShadingRateMode {
+    pub fn next(self) -> Self {
+        match self {
+            ShadingRateMode::On => ShadingRateMode::Overlay,
+            ShadingRateMode::Overlay => ShadingRateMode::Off,
+            ShadingRateMode::Off => ShadingRateMode::On,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadingRateMode::Off => "off",
+            ShadingRateMode::On => "on",
+            ShadingRateMode::Overlay => "on with overlay",
+        }
+    }
+}
+
+// The scene pass's fragment shading rate attachment: one R8_UINT texel per
+// `texel_size` block of the scene, rewritten from the CPU every frame. Texels
+// near the focus circle stay at 1x1, a ring around them drops to 2x2 and the
+// rest to 4x4 (or 2x2 where the device can't go coarser). Without a focus the
+// whole scene is shaded at full rate. Needs `capabilities.fragment_shading_rate`.
+pub struct ShadingRateMap {
+    pub mode: ShadingRateMode,
+    // Center and radius in scene pixels, set for one frame at a time
+    focus: Option<(Vec2, f32)>,
+    texel_size: vk::Extent2D,
+    coarsest_rate: u8,
+    // Size of the image in texels
+    size: vk::Extent2D,
+    rates: Vec<u8>,
+    // Where this frame's rates are in the frame ring
+    offset: vk::DeviceSize,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+impl ShadingRateMap {
+    pub fn new(gpu: &GpuContext, extent: vk::Extent2D) -> Self {
+        let mut rate_properties = vk::PhysicalDeviceFragmentShadingRatePropertiesKHR::default();
+        let mut properties =
+            vk::PhysicalDeviceProperties2::default().push_next(&mut rate_properties);
+        unsafe {
+            gpu.instance
+                .get_physical_device_properties2(gpu.physical_device, &mut properties);
+        }
+        // Both limits are powers of two, so the clamped size is one too
+        let min_texel = rate_properties.min_fragment_shading_rate_attachment_texel_size;
+        let max_texel = rate_properties.max_fragment_shading_rate_attachment_texel_size;
+        let texel_size = vk::Extent2D {
+            width: PREFERRED_TEXEL_SIZE.clamp(min_texel.width, max_texel.width),
+            height: PREFERRED_TEXEL_SIZE.clamp(min_texel.height, max_texel.height),
+        };
+        let max_fragment = rate_properties.max_fragment_size;
+        let coarsest_rate = if max_fragment.width >= 4 && max_fragment.height >= 4 {
+            RATE_4X4
+        } else {
+            RATE_2X2
+        };
+
+        let mut map = Self {
+            mode: ShadingRateMode::On,
+            focus: None,
+            texel_size,
+            coarsest_rate,
+            size: vk::Extent2D::default(),
+            rates: Vec::new(),
+            offset: 0,
+            image: vk::Image::null(),
+            memory: vk::DeviceMemory::null(),
+            view: vk::ImageView::null(),
+        };
+        map.create_image(gpu, extent);
+        println!(
+            "Shading rate map created: {}x{} texels of {}x{} pixels",
+            map.size.width, map.size.height, texel_size.width, texel_size.height
+        );
+        map
+    }
+
+    fn create_image(&mut self, gpu: &GpuContext, extent: vk::Extent2D) {
+        let device = &gpu.device;
+        self.size = vk::Extent2D {
+            width: extent.width.div_ceil(self.texel_size.width),
+            height: extent.height.div_ceil(self.texel_size.height),
+        };
+        self.rates = vec![RATE_1X1; (self.size.width * self.size.height) as usize];
+        self.image = unsafe {
+            device
+                .create_image(
+                    &vk::ImageCreateInfo {
+                        image_type: vk::ImageType::TYPE_2D,
+                        format: RATE_FORMAT,
+                        extent: vk::Extent3D {
+                            width: self.size.width,
+                            height: self.size.height,
+                            depth: 1,
+                        },
+                        mip_levels: 1,
+                        array_layers: 1,
+                        samples: vk::SampleCountFlags::TYPE_1,
+                        tiling: vk::ImageTiling::OPTIMAL,
+                        usage: vk::ImageUsageFlags::FRAGMENT_SHADING_RATE_ATTACHMENT_KHR
+                            | vk::ImageUsageFlags::TRANSFER_DST,
+                        sharing_mode: vk::SharingMode::EXCLUSIVE,
+                        initial_layout: vk::ImageLayout::UNDEFINED,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create shading rate image")
+        };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(self.image) };
+        self.memory = gpu.allocate_memory(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Images,
+        );
+        unsafe {
+            device
+                .bind_image_memory(self.image, self.memory, 0)
+                .expect("Failed to bind shading rate image memory");
+        }
+        self.view = unsafe {
+            device
+                .create_image_view(
+                    &vk::ImageViewCreateInfo {
+                        image: self.image,
+                        view_type: vk::ImageViewType::TYPE_2D,
+                        format: RATE_FORMAT,
+                        components: vk::ComponentMapping::default(),
+                        subresource_range: COLOR_SUBRESOURCE_RANGE,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create shading rate image view")
+        };
+        gpu.set_name(self.image, "shading rate image");
+        gpu.set_name(self.view, "shading rate image view");
+    }
+
+    fn destroy_image(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device.destroy_image_view(self.view, None);
+            gpu.device.destroy_image(self.image, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+
+    // Caller ensures the device is idle
+    pub fn destroy(&self, gpu: &GpuContext) {
+        self.destroy_image(gpu);
+    }
+
+    // Scene pixels per rate texel, which the render pass has to declare
+    pub fn texel_size(&self) -> vk::Extent2D {
+        self.texel_size
+    }
+
+    // Goes in the scene framebuffer after the color and stencil attachments
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    // Keeps full-rate shading around this circle, in scene pixels, for the
+    // next upload only
+    pub fn set_focus(&mut self, center: Vec2, radius: f32) {
+        self.focus = Some((center, radius));
+    }
+
+    // Recreates the image for a scene of the new size. Call with the device idle.
+    pub fn resize(&mut self, gpu: &GpuContext, extent: vk::Extent2D) {
+        self.destroy_image(gpu);
+        self.create_image(gpu, extent);
+    }
+
+    // Bytes `upload` pushes to the frame ring
+    pub fn upload_size(&self) -> usize {
+        self.rates.len()
+    }
+
+    // Works out this frame's rates from the focus, then clears it, and copies
+    // them into the frame ring
+    pub fn upload(&mut self, ring: &mut FrameRing) {
+        let focus = self
+            .focus
+            .take()
+            .filter(|_| self.mode != ShadingRateMode::Off);
+        let texel = Vec2::new(self.texel_size.width as f32, self.texel_size.height as f32);
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let rate = match focus {
+                    Some((center, radius)) => {
+                        // Distance from the texel's nearest point to the circle's edge
+                        let min = Vec2::new(x as f32, y as f32) * texel;
+                        let nearest = center.clamp(min, min + texel);
+                        let distance = nearest.distance(center) - radius;
+                        if distance < FULL_RATE_MARGIN {
+                            RATE_1X1
+                        } else if distance < HALF_RATE_MARGIN {
+                            RATE_2X2
+                        } else {
+                            self.coarsest_rate
+                        }
+                    }
+                    None => RATE_1X1,
+                };
+                self.rates[(y * self.size.width + x) as usize] = rate;
+            }
+        }
+        self.offset = ring.push(&self.rates);
+    }
+
+    // Copies the uploaded rates into the image. Must be recorded outside a
+    // render pass, before the scene pass reads them.
+    pub fn record_copy(
+        &self,
+        gpu: &GpuContext,
+        command_buffer: vk::CommandBuffer,
+        ring: &FrameRing,
+    ) {
+        let attachment_read = Access::new(
+            vk::PipelineStageFlags2::FRAGMENT_SHADING_RATE_ATTACHMENT_KHR,
+            vk::AccessFlags2::FRAGMENT_SHADING_RATE_ATTACHMENT_READ_KHR,
+        );
+        let transfer_write = Access::new(
+            vk::PipelineStageFlags2::TRANSFER,
+            vk::AccessFlags2::TRANSFER_WRITE,
+        );
+        // Every texel is overwritten, so the old contents can go, but only once
+        // the previous scene pass has finished reading them
+        Barriers::new()
+            .image(
+                self.image,
+                COLOR_SUBRESOURCE_RANGE,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                Access::new(
+                    vk::PipelineStageFlags2::FRAGMENT_SHADING_RATE_ATTACHMENT_KHR,
+                    vk::AccessFlags2::NONE,
+                ),
+                transfer_write,
+            )
+            .record(gpu, command_buffer);
+        unsafe {
+            gpu.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                ring.buffer(),
+                self.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::BufferImageCopy {
+                    buffer_offset: self.offset,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D::default(),
+                    image_extent: vk::Extent3D {
+                        width: self.size.width,
+                        height: self.size.height,
+                        depth: 1,
+                    },
+                }],
+            );
+        }
+        Barriers::new()
+            .image(
+                self.image,
+                COLOR_SUBRESOURCE_RANGE,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::FRAGMENT_SHADING_RATE_ATTACHMENT_OPTIMAL_KHR,
+                transfer_write,
+                attachment_read,
+            )
+            .record(gpu, command_buffer);
+    }
+
+    // Tints every coarse texel of the last upload, one rectangle per run of
+    // equal texels in a row. `pixels_per_unit` converts to canvas units.
+    pub fn draw_overlay(&self, canvas: &mut Canvas, pixels_per_unit: f32) {
+        let texel = Vec2::new(self.texel_size.width as f32, self.texel_size.height as f32)
+            / pixels_per_unit;
+        for (y, row) in self.rates.chunks(self.size.width as usize).enumerate() {
+            let mut start = 0;
+            while start < row.len() {
+                let rate = row[start];
+                let end = row[start..]
+                    .iter()
+                    .position(|&other| other != rate)
+                    .map_or(row.len(), |run| start + run);
+                let tint = match rate {
+                    RATE_1X1 => None,
+                    RATE_2X2 => Some(HALF_RATE_TINT),
+                    _ => Some(QUARTER_RATE_TINT),
+                };
+                if let Some(tint) = tint {
+                    canvas.fill_rect(
+                        Vec2::new(start as f32, y as f32) * texel,
+                        Vec2::new((end - start) as f32, 1.0) * texel,
+                        tint,
+                    );
+                }
+                start = end;
+            }
+        }
+    }
+}
+
+// Creates the scene render pass through render pass 2, with the shading rate
+// image as one more attachment after `attachments`. The other arguments are
+// the single subpass's attachments and the dependencies a version 1 render
+// pass would have used.
+pub fn create_render_pass(
+    gpu: &GpuContext,
+    attachments: &[vk::AttachmentDescription],
+    color_attachment: vk::AttachmentReference,
+    depth_stencil_attachment: Option<vk::AttachmentReference>,
+    dependencies: &[vk::SubpassDependency],
+    texel_size: vk::Extent2D,
+) -> vk::RenderPass {
+    let mut attachments2: Vec<vk::AttachmentDescription2> = attachments
+        .iter()
+        .map(|attachment| {
+            vk::AttachmentDescription2::default()
+                .format(attachment.format)
+                .samples(attachment.samples)
+                .load_op(attachment.load_op)
+                .store_op(attachment.store_op)
+                .stencil_load_op(attachment.stencil_load_op)
+                .stencil_store_op(attachment.stencil_store_op)
+                .initial_layout(attachment.initial_layout)
+                .final_layout(attachment.final_layout)
+        })
+        .collect();
+    // Written before the pass and only read during it
+    attachments2.push(
+        vk::AttachmentDescription2::default()
+            .format(RATE_FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::FRAGMENT_SHADING_RATE_ATTACHMENT_OPTIMAL_KHR)
+            .final_layout(vk::ImageLayout::FRAGMENT_SHADING_RATE_ATTACHMENT_OPTIMAL_KHR),
+    );
+    let reference = |reference: vk::AttachmentReference| {
+        vk::AttachmentReference2::default()
+            .attachment(reference.attachment)
+            .layout(reference.layout)
+    };
+    let color_attachments = [reference(color_attachment)];
+    let depth_stencil_attachment = depth_stencil_attachment.map(reference);
+    let rate_attachment = vk::AttachmentReference2::default()
+        .attachment(attachments.len() as u32)
+        .layout(vk::ImageLayout::FRAGMENT_SHADING_RATE_ATTACHMENT_OPTIMAL_KHR);
+    let mut rate_info = vk::FragmentShadingRateAttachmentInfoKHR::default()
+        .fragment_shading_rate_attachment(&rate_attachment)
+        .shading_rate_attachment_texel_size(texel_size);
+    let mut subpass = vk::SubpassDescription2::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color_attachments)
+        .push_next(&mut rate_info);
+    if let Some(depth_stencil_attachment) = depth_stencil_attachment.as_ref() {
+        subpass = subpass.depth_stencil_attachment(depth_stencil_attachment);
+    }
+    let dependencies2: Vec<vk::SubpassDependency2> = dependencies
+        .iter()
+        .map(|dependency| {
+            vk::SubpassDependency2::default()
+                .src_subpass(dependency.src_subpass)
+                .dst_subpass(dependency.dst_subpass)
+                .src_stage_mask(dependency.src_stage_mask)
+                .dst_stage_mask(dependency.dst_stage_mask)
+                .src_access_mask(dependency.src_access_mask)
+                .dst_access_mask(dependency.dst_access_mask)
+                .dependency_flags(dependency.dependency_flags)
+        })
+        .collect();
+    let subpasses = [subpass];
+    unsafe {
+        gpu.device
+            .create_render_pass2(
+                &vk::RenderPassCreateInfo2::default()
+                    .attachments(&attachments2)
+                    .subpasses(&subpasses)
+                    .dependencies(&dependencies2),
+                None,
+            )
+            .expect("Failed to create scene render pass with a shading rate attachment")
+    }
+}
@@ -0,0 +1,111 @@
+// The circles a scripted scene spawns, moves and recolors. Script bindings
+// (`python.rs`, `script.rs`) wrap a `ShapeWorld` and address shapes by the id
+// `spawn_circle` returns; the scene steps their physics and draws them.
+
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+
+use crate::ball::{Ball, Physics};
+use crate::renderer::Circle;
+use crate::scene::DrawList;
+
+pub struct Shape {
+    pub ball: Ball,
+    pub color: [f32; 4],
+}
+
+pub struct ShapeWorld {
+    // Ordered by id, so later spawns draw on top
+    shapes: BTreeMap<u64, Shape>,
+    next_id: u64,
+    bounds: Vec2,
+    time: f32,
+    pub physics: Physics,
+}
+
+impl ShapeWorld {
+    pub fn new(bounds: Vec2) -> Self {
+        Self {
+            shapes: BTreeMap::new(),
+            next_id: 0,
+            bounds,
+            time: 0.0,
+            physics: Physics::default(),
+        }
+    }
+
+    // Window size in logical pixels; the origin is the top-left corner, +y down
+    pub fn bounds(&self) -> Vec2 {
+        self.bounds
+    }
+
+    pub fn set_bounds(&mut self, bounds: Vec2) {
+        self.bounds = bounds;
+    }
+
+    // Simulated seconds since the world was created
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    // Moves every shape one fixed step, bouncing off the window edges
+    pub fn step(&mut self, dt: f32) {
+        for shape in self.shapes.values_mut() {
+            shape.ball.step(dt, self.bounds, &self.physics);
+        }
+        self.time += dt;
+    }
+
+    pub fn record_draws(&self, draws: &mut DrawList) {
+        draws.circles.extend(
+            self.shapes
+                .values()
+                .map(|shape| Circle::new(shape.ball.position, shape.ball.radius, shape.color)),
+        );
+    }
+
+    pub fn spawn_circle(&mut self, position: Vec2, radius: f32, color: [f32; 4]) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let ball = Ball::new(position, Vec2::ZERO, radius.max(0.0));
+        self.shapes.insert(id, Shape { ball, color });
+        id
+    }
+
+    // True if the shape existed
+    pub fn remove(&mut self, id: u64) -> bool {
+        self.shapes.remove(&id).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.shapes.clear();
+    }
+
+    pub fn ids(&self) -> Vec<u64> {
+        self.shapes.keys().copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut Shape> {
+        self.shapes.get_mut(&id)
+    }
+}
+
+// (r, g, b) or (r, g, b, a), each 0..1
+pub fn parse_color(color: &[f32]) -> Option<[f32; 4]> {
+    match *color {
+        [r, g, b] => Some([r, g, b, 1.0]),
+        [r, g, b, a] => Some([r, g, b, a]),
+        _ => None,
+    }
+}
+
+pub const COLOR_ERROR: &str = "a color has 3 (r, g, b) or 4 (r, g, b, a) components";
@@ -0,0 +1,48 @@
+// Snapshots of the running simulation, saved as JSON so a bug report can carry
+// the exact state that shows the problem. The app fills in what it owns (scene,
+// RNG, clock) and the scene adds its own state through `Scene::save_state`.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rng::Rng;
+
+// Bumped whenever a snapshot from an older build can't be read back
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    // `SceneKind::name` of the scene that was running
+    pub scene: String,
+    // Seed the scene started from, reused by later scene switches
+    pub seed: u64,
+    pub rng: Rng,
+    pub paused: bool,
+    pub time_scale: f32,
+    // `SimulationClock::accumulator`; zero in snapshots from before it was saved
+    #[serde(default)]
+    pub accumulator: f32,
+    // Whatever the scene's `save_state` returned
+    pub state: serde_json::Value,
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, json).map_err(|error| error.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let snapshot: Self = serde_json::from_str(&json).map_err(|error| error.to_string())?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "snapshot version {} is not {}",
+                snapshot.version, SNAPSHOT_VERSION
+            ));
+        }
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,133 @@
+// Impacts slower than this (pixels per second) are silent
+const MIN_IMPACT_SPEED: f32 = 60.0;
+// Caps how many sounds start per frame so a pile of balls doesn't roar
+const MAX_SOUNDS_PER_FRAME: usize = 8;
+
+// Sound effects a scene can ask for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sound {
+    // A ball hit a wall or another ball at `speed` pixels per second
+    Bounce { speed: f32 },
+}
+
+// Sounds requested during the current frame. Scenes push into it; the app
+// drains it into the player (or just clears it when muted or built without
+// the `audio` feature).
+#[derive(Default)]
+pub struct SoundQueue {
+    sounds: Vec<Sound>,
+}
+
+impl SoundQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, sound: Sound) {
+        match sound {
+            Sound::Bounce { speed } if speed < MIN_IMPACT_SPEED => {}
+            _ => self.sounds.push(sound),
+        }
+    }
+
+    // Shorthand for pushing a `Sound::Bounce`
+    pub fn bounce(&mut self, speed: f32) {
+        self.push(Sound::Bounce { speed });
+    }
+
+    // The loudest sounds first, at most MAX_SOUNDS_PER_FRAME of them
+    pub fn drain(&mut self) -> impl Iterator<Item = Sound> + '_ {
+        self.sounds
+            .sort_by(|a, b| b.loudness().total_cmp(&a.loudness()));
+        self.sounds.truncate(MAX_SOUNDS_PER_FRAME);
+        self.sounds.drain(..)
+    }
+
+    pub fn clear(&mut self) {
+        self.sounds.clear();
+    }
+}
+
+impl Sound {
+    fn loudness(&self) -> f32 {
+        match *self {
+            Sound::Bounce { speed } => speed,
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+pub use player::SoundPlayer;
+
+#[cfg(feature = "audio")]
+mod player {
+    use std::f32::consts::TAU;
+
+    use rodio::buffer::SamplesBuffer;
+    use rodio::source::Buffered;
+    use rodio::{OutputStream, OutputStreamHandle, Source};
+
+    use super::Sound;
+
+    const SAMPLE_RATE: u32 = 44_100;
+    // Length and base pitch of the bounce "plink"
+    const BOUNCE_SECONDS: f32 = 0.15;
+    const BOUNCE_FREQUENCY: f32 = 660.0;
+
+    // Plays sound effects on the default output device through rodio. Clips are
+    // generated once at startup and replayed with per-impact pitch and volume.
+    pub struct SoundPlayer {
+        // Kept alive for as long as sounds should play
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        bounce: Buffered<SamplesBuffer<f32>>,
+    }
+
+    impl SoundPlayer {
+        // None if there is no output device
+        pub fn new() -> Option<Self> {
+            let (stream, handle) = match OutputStream::try_default() {
+                Ok(output) => output,
+                Err(e) => {
+                    println!("Sound effects unavailable: {:?}", e);
+                    return None;
+                }
+            };
+            println!("Sound effects enabled");
+            Some(Self {
+                _stream: stream,
+                handle,
+                bounce: bounce_clip().buffered(),
+            })
+        }
+
+        pub fn play(&self, sound: Sound) {
+            let result = match sound {
+                // Harder hits are higher and louder
+                Sound::Bounce { speed } => self.handle.play_raw(
+                    self.bounce
+                        .clone()
+                        .speed((0.7 + speed / 1000.0).min(2.0))
+                        .amplify((speed / 800.0).clamp(0.1, 1.0)),
+                ),
+            };
+            if let Err(e) = result {
+                println!("Failed to play sound: {:?}", e);
+            }
+        }
+    }
+
+    // A decaying sine with a little second harmonic
+    fn bounce_clip() -> SamplesBuffer<f32> {
+        let length = (SAMPLE_RATE as f32 * BOUNCE_SECONDS) as usize;
+        let samples: Vec<f32> = (0..length)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let phase = TAU * BOUNCE_FREQUENCY * t;
+                let tone = phase.sin() + 0.3 * (phase * 2.0).sin();
+                0.4 * tone * (-t * 30.0).exp()
+            })
+            .collect();
+        SamplesBuffer::new(1, SAMPLE_RATE, samples)
+    }
+}
@@ -0,0 +1,139 @@
+use std::f32::consts::TAU;
+
+// Samples per analysis window; must be a power of two
+pub const FFT_SIZE: usize = 2048;
+// Log-spaced frequency bands shown as bars
+pub const BAND_COUNT: usize = 64;
+// Samples of the raw signal kept for the waveform display
+pub const WAVEFORM_SIZE: usize = 128;
+const MIN_FREQUENCY: f32 = 30.0;
+const MAX_FREQUENCY: f32 = 16_000.0;
+// Bands map -60 dB..0 dB onto 0..1
+const DYNAMIC_RANGE_DB: f32 = 60.0;
+// Band level lost per second once the sound stops
+const FALL_RATE: f32 = 1.5;
+
+// In-place iterative radix-2 FFT. `real` and `imaginary` must have the same
+// power-of-two length.
+pub fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+    let n = real.len();
+    assert!(n.is_power_of_two() && imaginary.len() == n);
+
+    // Bit-reversal permutation
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            real.swap(i, j);
+            imaginary.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let angle = -TAU / length as f32;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + length / 2);
+                let twiddled_real = real[b] * cos - imaginary[b] * sin;
+                let twiddled_imaginary = real[b] * sin + imaginary[b] * cos;
+                real[b] = real[a] - twiddled_real;
+                imaginary[b] = imaginary[a] - twiddled_imaginary;
+                real[a] += twiddled_real;
+                imaginary[a] += twiddled_imaginary;
+            }
+        }
+        length *= 2;
+    }
+}
+
+// Turns the latest FFT_SIZE samples into smoothed, log-spaced band levels in
+// 0..1 plus a downsampled waveform. Levels jump up immediately and fall back
+// at FALL_RATE so bars don't flicker.
+pub struct SpectrumAnalyzer {
+    sample_rate: f32,
+    window: Vec<f32>,
+    real: Vec<f32>,
+    imaginary: Vec<f32>,
+    bands: [f32; BAND_COUNT],
+    waveform: [f32; WAVEFORM_SIZE],
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(sample_rate: f32) -> Self {
+        // Hann window to keep strong tones from leaking into every band
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (TAU * i as f32 / (FFT_SIZE - 1) as f32).cos())
+            .collect();
+        Self {
+            sample_rate,
+            window,
+            real: vec![0.0; FFT_SIZE],
+            imaginary: vec![0.0; FFT_SIZE],
+            bands: [0.0; BAND_COUNT],
+            waveform: [0.0; WAVEFORM_SIZE],
+        }
+    }
+
+    pub fn bands(&self) -> &[f32; BAND_COUNT] {
+        &self.bands
+    }
+
+    pub fn waveform(&self) -> &[f32; WAVEFORM_SIZE] {
+        &self.waveform
+    }
+
+    // Frequency of the lower edge of band `band` (BAND_COUNT gives the top edge)
+    fn band_edge(band: usize) -> f32 {
+        MIN_FREQUENCY * (MAX_FREQUENCY / MIN_FREQUENCY).powf(band as f32 / BAND_COUNT as f32)
+    }
+
+    // Analyzes `samples`, the most recent FFT_SIZE mono samples, oldest first.
+    // `dt` is the time since the last call, for the fall-off.
+    pub fn analyze(&mut self, samples: &[f32], dt: f32) {
+        assert_eq!(samples.len(), FFT_SIZE);
+        for (i, slot) in self.waveform.iter_mut().enumerate() {
+            *slot = samples[FFT_SIZE - WAVEFORM_SIZE * 4 + i * 4];
+        }
+
+        for ((real, &sample), &weight) in self.real.iter_mut().zip(samples).zip(&self.window) {
+            *real = sample * weight;
+        }
+        self.imaginary.fill(0.0);
+        fft(&mut self.real, &mut self.imaginary);
+
+        // A full-scale sine peaks at FFT_SIZE / 4 through the Hann window
+        let normalization = 4.0 / FFT_SIZE as f32;
+        let bin_width = self.sample_rate / FFT_SIZE as f32;
+        let nyquist_bin = FFT_SIZE / 2;
+        for band in 0..BAND_COUNT {
+            let low = (Self::band_edge(band) / bin_width) as usize;
+            let high = ((Self::band_edge(band + 1) / bin_width) as usize).max(low + 1);
+            // Low bands can be narrower than a bin; they take the bin they fall in
+            let peak = (low.min(nyquist_bin)..high.min(nyquist_bin + 1))
+                .map(|bin| self.real[bin].hypot(self.imaginary[bin]) * normalization)
+                .fold(0.0, f32::max);
+            let decibels = 20.0 * peak.max(1e-6).log10();
+            let level = (1.0 + decibels / DYNAMIC_RANGE_DB).clamp(0.0, 1.0);
+            let current = &mut self.bands[band];
+            *current = level.max(*current - FALL_RATE * dt);
+        }
+    }
+}
+
+// A stand-in signal for when no audio device is captured: a kick drum on every
+// beat, a lead stepping through a pentatonic scale and a bit of hiss. Time is
+// f64 so the phases stay accurate after the demo has run for a while.
+pub fn demo_signal(time: f64) -> f32 {
+    use std::f64::consts::TAU;
+
+    const SCALE: [f64; 8] = [0.0, 3.0, 5.0, 7.0, 10.0, 12.0, 15.0, 17.0];
+    let beat = time * 2.0;
+    let kick = (TAU * 55.0 * time).sin() * (-beat.fract() * 8.0).exp();
+    let note = SCALE[(beat as usize * 5) % SCALE.len()];
+    let frequency = 220.0 * 2f64.powf(note / 12.0);
+    let lead = 0.3 * (TAU * frequency * time).sin() * (-beat.fract() * 2.0).exp();
+    let hiss = 0.05 * ((time * 12_345.678).sin() * 43_758.547).fract();
+    (kick * 0.6 + lead + hiss).clamp(-1.0, 1.0) as f32
+}
@@ -0,0 +1,766 @@
+use ash::vk;
+use glam::{Mat4, Vec2};
+use std::collections::BTreeMap;
+
+use crate::frame_ring::FrameRing;
+use crate::gpu::GpuContext;
+use crate::pipeline::{BlendMode, PipelineDesc, PipelineManager, VertexAttribute, VertexLayout};
+use crate::ktx::KtxTexture;
+use crate::reflect::ShaderInterface;
+use crate::texture::Texture;
+
+// Also the size of the texture array in sprite_bindless_frag.glsl
+const MAX_SPRITE_TEXTURES: u32 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextureId(pub u32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UvRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl UvRect {
+    pub const FULL: UvRect = UvRect {
+        min: [0.0, 0.0],
+        max: [1.0, 1.0],
+    };
+}
+
+// Named sub-rectangles of one texture. Regions are stored in UV space so the
+// batcher never needs to know the texture size.
+pub struct TextureAtlas {
+    pub texture: TextureId,
+    pub width: u32,
+    pub height: u32,
+    regions: Vec<UvRect>,
+}
+
+impl TextureAtlas {
+    pub fn new(texture: TextureId, width: u32, height: u32) -> Self {
+        Self {
+            texture,
+            width,
+            height,
+            regions: Vec::new(),
+        }
+    }
+
+    // Splits the whole texture into equally sized cells, row by row
+    pub fn grid(texture: TextureId, width: u32, height: u32, columns: u32, rows: u32) -> Self {
+        let mut atlas = Self::new(texture, width, height);
+        let cell_width = width / columns;
+        let cell_height = height / rows;
+        for row in 0..rows {
+            for column in 0..columns {
+                atlas.add_region(column * cell_width, row * cell_height, cell_width, cell_height);
+            }
+        }
+        atlas
+    }
+
+    // Adds a region in pixel coordinates and returns its index
+    pub fn add_region(&mut self, x: u32, y: u32, width: u32, height: u32) -> usize {
+        let texture_size = Vec2::new(self.width as f32, self.height as f32);
+        let min = Vec2::new(x as f32, y as f32) / texture_size;
+        let max = Vec2::new((x + width) as f32, (y + height) as f32) / texture_size;
+        self.regions.push(UvRect {
+            min: min.to_array(),
+            max: max.to_array(),
+        });
+        self.regions.len() - 1
+    }
+
+    pub fn region(&self, index: usize) -> UvRect {
+        self.regions[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+}
+
+// A texture region that stretches to any size without distorting its border:
+// the corners are drawn at their own size, the edges stretch along their length
+// and the middle fills what is left. Suits panels and buttons.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NinePatch {
+    pub texture: TextureId,
+    pub uv: UvRect,
+    // Size of the region in pixels
+    pub size: Vec2,
+    // Border widths in pixels: left, top, right, bottom
+    pub insets: [f32; 4],
+}
+
+impl NinePatch {
+    // The whole of a `width` x `height` texture
+    pub fn new(texture: TextureId, width: u32, height: u32, insets: [f32; 4]) -> Self {
+        Self {
+            texture,
+            uv: UvRect::FULL,
+            size: Vec2::new(width as f32, height as f32),
+            insets,
+        }
+    }
+
+    pub fn from_region(atlas: &TextureAtlas, region: usize, insets: [f32; 4]) -> Self {
+        let uv = atlas.region(region);
+        let texture_size = Vec2::new(atlas.width as f32, atlas.height as f32);
+        Self {
+            texture: atlas.texture,
+            uv,
+            size: (Vec2::from(uv.max) - Vec2::from(uv.min)) * texture_size,
+            insets,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sprite {
+    pub position: Vec2,
+    pub size: Vec2,
+    pub rotation: f32,
+    pub uv: UvRect,
+    pub tint: [f32; 4],
+}
+
+impl Sprite {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            position,
+            size,
+            rotation: 0.0,
+            uv: UvRect::FULL,
+            tint: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpriteInstance {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    pub tint: [f32; 4],
+    pub rotation: f32,
+    // The `TextureId`, which only the bindless shaders read
+    pub texture: u32,
+}
+
+// Every instance attribute; the per-texture shaders read all but the last
+const SPRITE_INSTANCE_ATTRIBUTES: [VertexAttribute; 7] = [
+    VertexAttribute {
+        location: 0,
+        format: vk::Format::R32G32_SFLOAT,
+        offset: std::mem::offset_of!(SpriteInstance, position) as u32,
+    },
+    VertexAttribute {
+        location: 1,
+        format: vk::Format::R32G32_SFLOAT,
+        offset: std::mem::offset_of!(SpriteInstance, size) as u32,
+    },
+    VertexAttribute {
+        location: 2,
+        format: vk::Format::R32G32_SFLOAT,
+        offset: std::mem::offset_of!(SpriteInstance, uv_min) as u32,
+    },
+    VertexAttribute {
+        location: 3,
+        format: vk::Format::R32G32_SFLOAT,
+        offset: std::mem::offset_of!(SpriteInstance, uv_max) as u32,
+    },
+    VertexAttribute {
+        location: 4,
+        format: vk::Format::R32G32B32A32_SFLOAT,
+        offset: std::mem::offset_of!(SpriteInstance, tint) as u32,
+    },
+    VertexAttribute {
+        location: 5,
+        format: vk::Format::R32_SFLOAT,
+        offset: std::mem::offset_of!(SpriteInstance, rotation) as u32,
+    },
+    VertexAttribute {
+        location: 6,
+        format: vk::Format::R32_UINT,
+        offset: std::mem::offset_of!(SpriteInstance, texture) as u32,
+    },
+];
+
+// One instance per sprite; the quad corners come from gl_VertexIndex
+const SPRITE_INSTANCE_LAYOUT: VertexLayout = VertexLayout {
+    stride: size_of::<SpriteInstance>() as u32,
+    input_rate: vk::VertexInputRate::INSTANCE,
+    attributes: SPRITE_INSTANCE_ATTRIBUTES.split_at(6).0,
+};
+
+const BINDLESS_SPRITE_INSTANCE_LAYOUT: VertexLayout = VertexLayout {
+    attributes: &SPRITE_INSTANCE_ATTRIBUTES,
+    ..SPRITE_INSTANCE_LAYOUT
+};
+
+// Accumulates sprite quads for one frame, grouped by texture so each texture
+// costs exactly one instanced draw.
+#[derive(Default)]
+pub struct SpriteBatch {
+    batches: BTreeMap<TextureId, Vec<SpriteInstance>>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Empties the batch but keeps the per-texture allocations for the next frame
+    pub fn clear(&mut self) {
+        for instances in self.batches.values_mut() {
+            instances.clear();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.values().all(|instances| instances.is_empty())
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.batches.values().map(|instances| instances.len()).sum()
+    }
+
+    pub fn draw(&mut self, texture: TextureId, sprite: &Sprite) {
+        self.batches.entry(texture).or_default().push(SpriteInstance {
+            position: sprite.position.to_array(),
+            size: sprite.size.to_array(),
+            uv_min: sprite.uv.min,
+            uv_max: sprite.uv.max,
+            tint: sprite.tint,
+            rotation: sprite.rotation,
+            texture: texture.0,
+        });
+    }
+
+    pub fn draw_region(
+        &mut self,
+        atlas: &TextureAtlas,
+        region: usize,
+        position: Vec2,
+        size: Vec2,
+        tint: [f32; 4],
+    ) {
+        let sprite = Sprite {
+            uv: atlas.region(region),
+            tint,
+            ..Sprite::new(position, size)
+        };
+        self.draw(atlas.texture, &sprite);
+    }
+
+    // Draws `patch` stretched over the rectangle at `min`, as up to nine
+    // sprites. Borders keep their pixel size unless the rectangle is too small
+    // for both sides, then they shrink to meet in the middle.
+    pub fn draw_nine_patch(&mut self, patch: &NinePatch, min: Vec2, size: Vec2, tint: [f32; 4]) {
+        let [left, top, right, bottom] = patch.insets;
+        let border_scale = Vec2::new(
+            (size.x / (left + right)).min(1.0),
+            (size.y / (top + bottom)).min(1.0),
+        );
+        let max = min + size;
+        let xs = [
+            min.x,
+            min.x + left * border_scale.x,
+            max.x - right * border_scale.x,
+            max.x,
+        ];
+        let ys = [
+            min.y,
+            min.y + top * border_scale.y,
+            max.y - bottom * border_scale.y,
+            max.y,
+        ];
+        // Insets in UV space, measured from the region's edges
+        let uv_min = Vec2::from(patch.uv.min);
+        let uv_max = Vec2::from(patch.uv.max);
+        let uv_per_pixel = (uv_max - uv_min) / patch.size;
+        let us = [
+            uv_min.x,
+            uv_min.x + left * uv_per_pixel.x,
+            uv_max.x - right * uv_per_pixel.x,
+            uv_max.x,
+        ];
+        let vs = [
+            uv_min.y,
+            uv_min.y + top * uv_per_pixel.y,
+            uv_max.y - bottom * uv_per_pixel.y,
+            uv_max.y,
+        ];
+        for row in 0..3 {
+            for column in 0..3 {
+                let cell_min = Vec2::new(xs[column], ys[row]);
+                let cell_size = Vec2::new(xs[column + 1], ys[row + 1]) - cell_min;
+                if cell_size.x <= 0.0 || cell_size.y <= 0.0 {
+                    continue;
+                }
+                let sprite = Sprite {
+                    uv: UvRect {
+                        min: [us[column], vs[row]],
+                        max: [us[column + 1], vs[row + 1]],
+                    },
+                    tint,
+                    ..Sprite::new(cell_min + cell_size * 0.5, cell_size)
+                };
+                self.draw(patch.texture, &sprite);
+            }
+        }
+    }
+
+    // Non-empty batches in draw order
+    pub fn batches(&self) -> impl Iterator<Item = (TextureId, &[SpriteInstance])> {
+        self.batches
+            .iter()
+            .filter(|(_, instances)| !instances.is_empty())
+            .map(|(&texture, instances)| (texture, instances.as_slice()))
+    }
+}
+
+// A registered texture. Without bindless it has its own descriptor set.
+struct SpriteTexture {
+    texture: Texture,
+    descriptor_set: Option<vk::DescriptorSet>,
+}
+
+// GPU side of the sprite batcher: owns the sprite pipeline, the registered
+// textures with their descriptors, and where this frame's instances sit in
+// the renderer's frame ring.
+//
+// With descriptor indexing every texture is a slot in one array, and each
+// instance carries the index it samples, so a whole batch is one draw.
+// Otherwise each texture has its own set and gets its own draw. With buffer
+// device address the vertex shader reads the instances from the frame ring
+// through an address in its push constants instead of vertex attributes.
+pub struct SpriteRenderer {
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    sampler: vk::Sampler,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: PipelineDesc,
+    // The one set holding the texture array, when bindless
+    bindless_set: Option<vk::DescriptorSet>,
+    // Destroyed textures leave a None slot for the next create_texture
+    textures: Vec<Option<SpriteTexture>>,
+    instance_buffer: vk::Buffer,
+    instance_offset: vk::DeviceSize,
+    // Set when the instances are read through their device address
+    pull_instances: bool,
+    instance_address: Option<vk::DeviceAddress>,
+}
+
+impl SpriteRenderer {
+    pub fn new(gpu: &GpuContext, pipelines: &mut PipelineManager) -> Self {
+        let device = &gpu.device;
+        let bindless = gpu.capabilities.descriptor_indexing;
+        let pull_instances = gpu.capabilities.buffer_device_address;
+
+        // The address vertex shader passes on the texture index too, so it goes
+        // with either fragment shader
+        let (name, vertex, fragment): (&str, &[u8], &[u8]) = match (bindless, pull_instances) {
+            (true, true) => (
+                "bindless sprite (device address)",
+                include_bytes!("../shaders/sprite_address_vert.spv"),
+                include_bytes!("../shaders/sprite_bindless_frag.spv"),
+            ),
+            (true, false) => (
+                "bindless sprite",
+                include_bytes!("../shaders/sprite_bindless_vert.spv"),
+                include_bytes!("../shaders/sprite_bindless_frag.spv"),
+            ),
+            (false, true) => (
+                "sprite (device address)",
+                include_bytes!("../shaders/sprite_address_vert.spv"),
+                include_bytes!("../shaders/sprite_frag.spv"),
+            ),
+            (false, false) => (
+                "sprite",
+                include_bytes!("../shaders/sprite_vert.spv"),
+                include_bytes!("../shaders/sprite_frag.spv"),
+            ),
+        };
+        let shaders = pipelines.register_shaders(gpu, name, vertex, fragment);
+        let interface = pipelines.interface(shaders);
+        let (descriptor_set_layout, descriptor_pool) = if bindless {
+            Self::create_bindless_descriptors(gpu, interface)
+        } else {
+            let descriptor_set_layout = interface.create_set_layouts(gpu)[0];
+            let pool_sizes = interface.pool_sizes(MAX_SPRITE_TEXTURES);
+            let descriptor_pool = unsafe {
+                device
+                    .create_descriptor_pool(
+                        &vk::DescriptorPoolCreateInfo {
+                            flags: vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+                            max_sets: MAX_SPRITE_TEXTURES,
+                            pool_size_count: pool_sizes.len() as u32,
+                            p_pool_sizes: pool_sizes.as_ptr(),
+                            ..Default::default()
+                        },
+                        None,
+                    )
+                    .expect("Failed to create sprite descriptor pool")
+            };
+            (descriptor_set_layout, descriptor_pool)
+        };
+
+        let sampler = unsafe {
+            device
+                .create_sampler(
+                    &vk::SamplerCreateInfo {
+                        mag_filter: vk::Filter::LINEAR,
+                        min_filter: vk::Filter::LINEAR,
+                        mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+                        address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        address_mode_w: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                        max_lod: vk::LOD_CLAMP_NONE,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create sprite sampler")
+        };
+
+        // The instance address follows the projection
+        let push_constant_size = if pull_instances {
+            size_of::<Mat4>() + size_of::<vk::DeviceAddress>()
+        } else {
+            size_of::<Mat4>()
+        };
+        let pipeline_layout =
+            interface.create_pipeline_layout(gpu, &[descriptor_set_layout], push_constant_size);
+
+        // Sprites are usually cut out of an atlas with transparent borders
+        let mut pipeline =
+            PipelineDesc::new(shaders, pipeline_layout).with_blend_mode(BlendMode::Alpha);
+        if !pull_instances {
+            let vertex_layout = if bindless {
+                BINDLESS_SPRITE_INSTANCE_LAYOUT
+            } else {
+                SPRITE_INSTANCE_LAYOUT
+            };
+            pipeline = pipeline.with_vertex_layout(vertex_layout);
+        }
+        pipelines.prepare(gpu, &pipeline);
+
+        let mut sprites = Self {
+            descriptor_set_layout,
+            descriptor_pool,
+            sampler,
+            pipeline_layout,
+            pipeline,
+            bindless_set: None,
+            textures: Vec::new(),
+            instance_buffer: vk::Buffer::null(),
+            instance_offset: 0,
+            pull_instances,
+            instance_address: None,
+        };
+        if bindless {
+            let descriptor_set = sprites.allocate_descriptor_set(gpu);
+            sprites.write_sampler(gpu, descriptor_set);
+            sprites.bindless_set = Some(descriptor_set);
+            println!("Sprite textures are bindless: one draw per sprite batch");
+        } else {
+            println!("Sprite textures are bound one at a time: one draw per texture");
+        }
+        if pull_instances {
+            println!("Sprite instances are read through their device address");
+        }
+        sprites
+    }
+
+    // Layout and pool for the single bindless set. Its texture slots can be
+    // written while frames using other slots are in flight, and slots without
+    // a texture are fine as long as no sprite samples them.
+    fn create_bindless_descriptors(
+        gpu: &GpuContext,
+        interface: &ShaderInterface,
+    ) -> (vk::DescriptorSetLayout, vk::DescriptorPool) {
+        let device = &gpu.device;
+        let bindings: Vec<vk::DescriptorSetLayoutBinding> = interface
+            .set_bindings(0)
+            .map(|binding| vk::DescriptorSetLayoutBinding {
+                binding: binding.binding,
+                descriptor_type: binding.descriptor_type,
+                descriptor_count: binding.count,
+                stage_flags: binding.stage_flags,
+                ..Default::default()
+            })
+            .collect();
+        let binding_flags: Vec<vk::DescriptorBindingFlags> = bindings
+            .iter()
+            .map(|binding| {
+                if binding.descriptor_type == vk::DescriptorType::SAMPLED_IMAGE {
+                    vk::DescriptorBindingFlags::PARTIALLY_BOUND
+                        | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+                        | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING
+                } else {
+                    vk::DescriptorBindingFlags::empty()
+                }
+            })
+            .collect();
+        let mut binding_flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo::default()
+            .binding_flags(&binding_flags);
+        let descriptor_set_layout = unsafe {
+            device
+                .create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::default()
+                        .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+                        .bindings(&bindings)
+                        .push_next(&mut binding_flags_info),
+                    None,
+                )
+                .expect("Failed to create bindless sprite descriptor set layout")
+        };
+        let pool_sizes = interface.pool_sizes(1);
+        let descriptor_pool = unsafe {
+            device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        flags: vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND,
+                        max_sets: 1,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create bindless sprite descriptor pool")
+        };
+        (descriptor_set_layout, descriptor_pool)
+    }
+
+    // Uploads RGBA8 pixels and registers them for sprite drawing
+    pub fn create_texture(&mut self, gpu: &GpuContext, width: u32, height: u32, rgba: &[u8]) -> TextureId {
+        let slot = self.next_slot();
+        let texture = Texture::from_rgba(
+            gpu,
+            &format!("sprite texture {}", slot),
+            width,
+            height,
+            rgba,
+        );
+        self.add_texture(gpu, slot, texture)
+    }
+
+    // Uploads a KTX2 texture with its mip levels and registers it for sprite
+    // drawing
+    pub fn create_ktx_texture(&mut self, gpu: &GpuContext, ktx: &KtxTexture) -> Result<TextureId, String> {
+        let slot = self.next_slot();
+        let texture = Texture::from_ktx(gpu, &format!("sprite texture {}", slot), ktx)?;
+        Ok(self.add_texture(gpu, slot, texture))
+    }
+
+    // The slot the next texture goes in: the first freed one, or a new one
+    fn next_slot(&self) -> usize {
+        let free_slot = self.textures.iter().position(Option::is_none);
+        assert!(
+            free_slot.is_some() || (self.textures.len() as u32) < MAX_SPRITE_TEXTURES,
+            "Sprite texture limit of {} reached",
+            MAX_SPRITE_TEXTURES
+        );
+        free_slot.unwrap_or(self.textures.len())
+    }
+
+    fn allocate_descriptor_set(&self, gpu: &GpuContext) -> vk::DescriptorSet {
+        unsafe {
+            gpu.device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool: self.descriptor_pool,
+                    descriptor_set_count: 1,
+                    p_set_layouts: &self.descriptor_set_layout,
+                    ..Default::default()
+                })
+                .expect("Failed to allocate sprite descriptor set")[0]
+        }
+    }
+
+    fn write_sampler(&self, gpu: &GpuContext, descriptor_set: vk::DescriptorSet) {
+        let sampler_info = vk::DescriptorImageInfo {
+            sampler: self.sampler,
+            ..Default::default()
+        };
+        let write = vk::WriteDescriptorSet {
+            dst_set: descriptor_set,
+            dst_binding: 1,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::SAMPLER,
+            p_image_info: &sampler_info,
+            ..Default::default()
+        };
+        unsafe { gpu.device.update_descriptor_sets(&[write], &[]) };
+    }
+
+    // Writes the texture into its own new set, or into its slot of the
+    // bindless array
+    fn add_texture(&mut self, gpu: &GpuContext, slot: usize, texture: Texture) -> TextureId {
+        let descriptor_set = match self.bindless_set {
+            Some(_) => None,
+            None => {
+                let descriptor_set = self.allocate_descriptor_set(gpu);
+                self.write_sampler(gpu, descriptor_set);
+                Some(descriptor_set)
+            }
+        };
+        let image_info = vk::DescriptorImageInfo {
+            image_view: texture.view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..Default::default()
+        };
+        let write = vk::WriteDescriptorSet {
+            dst_set: descriptor_set.or(self.bindless_set).unwrap(),
+            dst_binding: 0,
+            dst_array_element: if descriptor_set.is_some() { 0 } else { slot as u32 },
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::SAMPLED_IMAGE,
+            p_image_info: &image_info,
+            ..Default::default()
+        };
+        unsafe { gpu.device.update_descriptor_sets(&[write], &[]) };
+
+        let entry = Some(SpriteTexture {
+            texture,
+            descriptor_set,
+        });
+        if slot == self.textures.len() {
+            self.textures.push(entry);
+        } else {
+            self.textures[slot] = entry;
+        }
+        TextureId(slot as u32)
+    }
+
+    // Frees the texture and its descriptor set. The GPU must be done with it.
+    // A bindless slot keeps pointing at the destroyed view until it is reused,
+    // which is allowed since nothing samples it.
+    pub fn destroy_texture(&mut self, gpu: &GpuContext, texture: TextureId) {
+        let sprite_texture = self.textures[texture.0 as usize]
+            .take()
+            .expect("Sprite texture was already destroyed");
+        sprite_texture.texture.destroy(gpu);
+        if let Some(descriptor_set) = sprite_texture.descriptor_set {
+            unsafe {
+                gpu.device
+                    .free_descriptor_sets(self.descriptor_pool, &[descriptor_set])
+                    .expect("Failed to free sprite descriptor set");
+            }
+        }
+    }
+
+    // Frees every texture along with the descriptors and sampler. The pipeline
+    // goes with the renderer's pipeline manager. Caller ensures the device is
+    // idle.
+    pub fn destroy(&mut self, gpu: &GpuContext) {
+        for sprite_texture in self.textures.drain(..).flatten() {
+            sprite_texture.texture.destroy(gpu);
+        }
+        unsafe {
+            let device = &gpu.device;
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+
+    // Copies every batch into the frame ring in draw order
+    pub fn upload(&mut self, ring: &mut FrameRing, batch: &SpriteBatch) {
+        if batch.is_empty() {
+            return;
+        }
+        let parts: Vec<&[u8]> = batch
+            .batches()
+            .map(|(_, instances)| bytemuck::cast_slice(instances))
+            .collect();
+        self.instance_buffer = ring.buffer();
+        self.instance_offset = ring.push_parts(&parts);
+        if self.pull_instances {
+            self.instance_address = ring.address().map(|address| address + self.instance_offset);
+        }
+    }
+
+    // Records one instanced draw for the whole batch when bindless, otherwise
+    // one per texture. Must match the order used by `upload`.
+    pub fn record(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+        batch: &SpriteBatch,
+        projection: &Mat4,
+    ) {
+        if batch.is_empty() {
+            return;
+        }
+        let pipeline = pipelines.get(gpu, &self.pipeline);
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            let projection_array = projection.to_cols_array();
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::cast_slice(&projection_array),
+            );
+            match self.instance_address {
+                Some(address) => device.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    size_of::<Mat4>() as u32,
+                    bytemuck::bytes_of(&address),
+                ),
+                None => device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[self.instance_buffer],
+                    &[self.instance_offset],
+                ),
+            }
+
+            if let Some(descriptor_set) = self.bindless_set {
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    0,
+                    &[descriptor_set],
+                    &[],
+                );
+                device.cmd_draw(command_buffer, 6, batch.instance_count() as u32, 0, 0);
+                return;
+            }
+            let mut first_instance = 0;
+            for (texture, instances) in batch.batches() {
+                let descriptor_set = self.textures[texture.0 as usize]
+                    .as_ref()
+                    .and_then(|texture| texture.descriptor_set)
+                    .expect("Sprite batch uses a destroyed texture");
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.pipeline_layout,
+                    0,
+                    &[descriptor_set],
+                    &[],
+                );
+                device.cmd_draw(command_buffer, 6, instances.len() as u32, 0, first_instance);
+                first_instance += instances.len() as u32;
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+// Per-frame statistics for offline analysis (--stats-out): CPU frame time and
+// where it went, the FPS and GPU memory use, one record per rendered frame. A
+// `.csv` path gets a header row and comma-separated values; anything else
+// gets JSON lines.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::frame_times::{millis, FramePhases};
+use crate::memory::MemoryReport;
+
+// Records reach the file at least this often, so a crash loses little
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const CSV_HEADER: &str = "frame,time,cpu_ms,update_ms,acquire_ms,record_ms,submit_ms,\
+present_ms,fps,memory_tracked,memory_usage";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    Csv,
+    JsonLines,
+}
+
+impl StatsFormat {
+    pub fn from_path(path: &Path) -> Self {
+        let csv = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+        if csv {
+            StatsFormat::Csv
+        } else {
+            StatsFormat::JsonLines
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsRecord {
+    pub frame: u64,
+    // Seconds since the stats file was opened
+    pub time: f64,
+    // CPU time as `FrameTimes` counts it, then its parts. `update_ms` is
+    // everything outside the renderer.
+    pub cpu_ms: f64,
+    pub update_ms: f64,
+    pub acquire_ms: f64,
+    pub record_ms: f64,
+    pub submit_ms: f64,
+    pub present_ms: f64,
+    // Over the last full second, as the title shows it
+    pub fps: f32,
+    // Bytes allocated by the app, and the driver-reported usage of every heap
+    // with VK_EXT_memory_budget
+    pub memory_tracked: u64,
+    pub memory_usage: Option<u64>,
+}
+
+impl StatsRecord {
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{:.6},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.2},{},{}",
+            self.frame,
+            self.time,
+            self.cpu_ms,
+            self.update_ms,
+            self.acquire_ms,
+            self.record_ms,
+            self.submit_ms,
+            self.present_ms,
+            self.fps,
+            self.memory_tracked,
+            self.memory_usage
+                .map_or(String::new(), |usage| usage.to_string())
+        )
+    }
+}
+
+pub struct StatsWriter {
+    path: PathBuf,
+    format: StatsFormat,
+    writer: BufWriter<File>,
+    start: Instant,
+    last_flush: Instant,
+    records: u64,
+    failed: bool,
+}
+
+impl StatsWriter {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|error| error.to_string())?;
+        let format = StatsFormat::from_path(path);
+        let mut writer = BufWriter::new(file);
+        if format == StatsFormat::Csv {
+            writeln!(writer, "{}", CSV_HEADER).map_err(|error| error.to_string())?;
+        }
+        println!("Writing frame stats to {}", path.display());
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            writer,
+            start: Instant::now(),
+            last_flush: Instant::now(),
+            records: 0,
+            failed: false,
+        })
+    }
+
+    // Appends frame `frame`, which took `cpu_time` of which the renderer spent
+    // `phases`. After a write error the rest of the run is dropped.
+    pub fn push(
+        &mut self,
+        frame: u64,
+        cpu_time: Duration,
+        phases: FramePhases,
+        fps: f32,
+        memory: &MemoryReport,
+    ) {
+        if self.failed {
+            return;
+        }
+        let usages: Vec<_> = memory.heaps.iter().filter_map(|heap| heap.usage).collect();
+        let record = StatsRecord {
+            frame,
+            time: self.start.elapsed().as_secs_f64(),
+            cpu_ms: millis(cpu_time),
+            update_ms: millis(cpu_time.saturating_sub(phases.total())),
+            acquire_ms: millis(phases.acquire),
+            record_ms: millis(phases.record),
+            submit_ms: millis(phases.submit),
+            present_ms: millis(phases.present),
+            fps,
+            memory_tracked: memory.tracked_total(),
+            memory_usage: (!usages.is_empty()).then(|| usages.iter().sum()),
+        };
+        match self.write_record(&record) {
+            Ok(()) => self.records += 1,
+            Err(error) => {
+                eprintln!("Writing stats to {} failed: {}", self.path.display(), error);
+                self.failed = true;
+            }
+        }
+    }
+
+    fn write_record(&mut self, record: &StatsRecord) -> Result<(), String> {
+        match self.format {
+            StatsFormat::Csv => {
+                writeln!(self.writer, "{}", record.to_csv()).map_err(|error| error.to_string())?
+            }
+            StatsFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, record)
+                    .map_err(|error| error.to_string())?;
+                self.writer
+                    .write_all(b"\n")
+                    .map_err(|error| error.to_string())?;
+            }
+        }
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.last_flush = Instant::now();
+            self.writer.flush().map_err(|error| error.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) {
+        if let Err(error) = self.writer.flush() {
+            eprintln!("Writing stats to {} failed: {}", self.path.display(), error);
+            return;
+        }
+        println!(
+            "Wrote stats for {} frames to {}",
+            self.records,
+            self.path.display()
+        );
+    }
+}
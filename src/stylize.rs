@@ -0,0 +1,173 @@
+use glam::Vec2;
+
+use crate::canvas::Canvas;
+use crate::post::PostEffect;
+
+// Slider rows, in pixels
+const SLIDER_HEIGHT: f32 = 12.0;
+const SLIDER_SPACING: f32 = 6.0;
+// What a full slider means for each parameter
+const MAX_ABERRATION_PIXELS: f32 = 8.0;
+const MAX_CURVATURE: f32 = 0.3;
+
+// The stylized post effects, each with a 0..1 amount where 0 leaves it out of
+// the chain. Kept apart from the effects scenes choose, and always applied
+// after them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stylize {
+    pub chromatic_aberration: f32,
+    pub vignette: f32,
+    pub curvature: f32,
+    pub scanlines: f32,
+}
+
+impl Stylize {
+    // Slider colors, in row order; the panel has no text
+    const COLORS: [[f32; 4]; 4] = [
+        [0.9, 0.3, 0.6, 0.9],
+        [0.5, 0.5, 0.9, 0.9],
+        [0.3, 0.8, 0.5, 0.9],
+        [0.9, 0.7, 0.2, 0.9],
+    ];
+
+    fn amounts(&self) -> [f32; 4] {
+        [
+            self.chromatic_aberration,
+            self.vignette,
+            self.curvature,
+            self.scanlines,
+        ]
+    }
+
+    fn amounts_mut(&mut self) -> [&mut f32; 4] {
+        [
+            &mut self.chromatic_aberration,
+            &mut self.vignette,
+            &mut self.curvature,
+            &mut self.scanlines,
+        ]
+    }
+
+    // Lens effects come before the CRT so they bend with the tube, and the
+    // vignette last so the tube's edge stays black
+    pub fn effects(&self) -> Vec<PostEffect> {
+        let mut effects = Vec::new();
+        if self.chromatic_aberration > 0.0 {
+            effects.push(PostEffect::ChromaticAberration {
+                offset: self.chromatic_aberration * MAX_ABERRATION_PIXELS,
+            });
+        }
+        if self.curvature > 0.0 || self.scanlines > 0.0 {
+            effects.push(PostEffect::Crt {
+                curvature: self.curvature * MAX_CURVATURE,
+                scanlines: self.scanlines,
+            });
+        }
+        if self.vignette > 0.0 {
+            effects.push(PostEffect::Vignette {
+                strength: self.vignette,
+            });
+        }
+        effects
+    }
+
+    // `effects` with its stylized effects replaced by these
+    pub fn apply(&self, effects: &[PostEffect]) -> Vec<PostEffect> {
+        effects
+            .iter()
+            .copied()
+            .filter(|effect| !is_stylized(effect))
+            .chain(self.effects())
+            .collect()
+    }
+}
+
+pub fn is_stylized(effect: &PostEffect) -> bool {
+    matches!(
+        effect,
+        PostEffect::ChromaticAberration { .. }
+            | PostEffect::Vignette { .. }
+            | PostEffect::Crt { .. }
+    )
+}
+
+// One slider per `Stylize` amount, stacked from `origin`: chromatic
+// aberration, vignette, CRT curvature and scanlines. Click or drag along a
+// slider to set it; all the way left turns the effect off.
+pub struct StylizePanel {
+    pub stylize: Stylize,
+    origin: Vec2,
+    width: f32,
+    // The slider being dragged
+    dragging: Option<usize>,
+}
+
+impl StylizePanel {
+    pub fn new(stylize: Stylize, origin: Vec2, width: f32) -> Self {
+        Self {
+            stylize,
+            origin,
+            width,
+            dragging: None,
+        }
+    }
+
+    pub fn size(&self) -> Vec2 {
+        let rows = Stylize::COLORS.len() as f32;
+        Vec2::new(
+            self.width,
+            rows * SLIDER_HEIGHT + (rows - 1.0) * SLIDER_SPACING,
+        )
+    }
+
+    pub fn origin(&self) -> Vec2 {
+        self.origin
+    }
+
+    pub fn set_origin(&mut self, origin: Vec2) {
+        self.origin = origin;
+    }
+
+    fn row_top(&self, row: usize) -> Vec2 {
+        self.origin + Vec2::new(0.0, row as f32 * (SLIDER_HEIGHT + SLIDER_SPACING))
+    }
+
+    // Follows a left button press or drag at `cursor` (in ortho space).
+    // Returns whether the panel took the mouse, which the caller should then
+    // hide from the scene.
+    pub fn handle_mouse(&mut self, cursor: Option<Vec2>, pressed: bool, released: bool) -> bool {
+        if pressed {
+            self.dragging = cursor.and_then(|cursor| {
+                (0..Stylize::COLORS.len()).find(|&row| {
+                    let local = cursor - self.row_top(row);
+                    (0.0..=self.width).contains(&local.x)
+                        && (0.0..=SLIDER_HEIGHT).contains(&local.y)
+                })
+            });
+        }
+        let Some(row) = self.dragging else {
+            return false;
+        };
+        if released {
+            self.dragging = None;
+        }
+        if let Some(cursor) = cursor {
+            let amount = ((cursor.x - self.origin.x) / self.width).clamp(0.0, 1.0);
+            *self.stylize.amounts_mut()[row] = amount;
+        }
+        true
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let amounts = self.stylize.amounts();
+        for (row, (amount, color)) in amounts.into_iter().zip(Stylize::COLORS).enumerate() {
+            let top = self.row_top(row);
+            canvas.fill_rect(
+                top,
+                Vec2::new(self.width, SLIDER_HEIGHT),
+                [0.1, 0.1, 0.1, 0.7],
+            );
+            canvas.fill_rect(top, Vec2::new(self.width * amount, SLIDER_HEIGHT), color);
+        }
+    }
+}
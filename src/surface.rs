@@ -0,0 +1,251 @@
+use std::ffi::CStr;
+
+use ash::vk;
+use winit::raw_window_handle::{
+    HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle,
+};
+use winit::window::Window;
+#[cfg(target_os = "macos")]
+use objc::{
+    rc::autoreleasepool,
+    runtime::{Object, YES, NO},
+    class,
+    msg_send,
+    sel,
+    sel_impl,
+};
+#[cfg(target_os = "ios")]
+use objc::{class, msg_send, runtime::{Object, BOOL, YES}, sel, sel_impl};
+
+// CoreGraphics geometry, as returned by UIView; CGFloat is f64 on every 64-bit iOS device
+#[cfg(target_os = "ios")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CGPoint {
+    x: f64,
+    y: f64,
+}
+
+#[cfg(target_os = "ios")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CGSize {
+    width: f64,
+    height: f64,
+}
+
+#[cfg(target_os = "ios")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CGRect {
+    origin: CGPoint,
+    size: CGSize,
+}
+
+// Instance extensions needed to create a surface for `window`: VK_KHR_surface
+// plus the one for the windowing system it is actually running on, so an X11
+// session doesn't need the Wayland extension and vice versa
+pub fn required_extensions(window: &Window) -> Vec<&'static CStr> {
+    let display_handle = window
+        .display_handle()
+        .expect("Failed to get display handle");
+    required_extensions_for(display_handle.as_raw())
+}
+
+// Like `required_extensions`, for a window system connection given as a raw handle
+pub fn required_extensions_for(display_handle: RawDisplayHandle) -> Vec<&'static CStr> {
+    let platform_extension = match display_handle {
+        RawDisplayHandle::Windows(_) => ash::khr::win32_surface::NAME,
+        RawDisplayHandle::AppKit(_) | RawDisplayHandle::UiKit(_) => ash::ext::metal_surface::NAME,
+        RawDisplayHandle::Xlib(_) => ash::khr::xlib_surface::NAME,
+        RawDisplayHandle::Wayland(_) => ash::khr::wayland_surface::NAME,
+        _ => panic!("Unsupported platform."),
+    };
+    vec![ash::khr::surface::NAME, platform_extension]
+}
+
+// Doesn't log, so `--info --json` output stays clean
+pub fn create_surface(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    window: &Window,
+) -> vk::SurfaceKHR {
+    let display_handle = window.display_handle().expect("Failed to get display handle");
+    let window_handle = window.window_handle().expect("Failed to get window handle");
+    // winit keeps both handles valid for as long as `window` lives
+    unsafe {
+        create_surface_from_raw(entry, instance, display_handle.as_raw(), window_handle.as_raw())
+    }
+}
+
+// Creates a surface for a window given as raw handles, e.g. one owned by another
+// application. The handles must be valid, and stay valid until the surface has
+// been destroyed.
+// Only X11 and Wayland surfaces need the display
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub unsafe fn create_surface_from_raw(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    display_handle: RawDisplayHandle,
+    window_handle: RawWindowHandle,
+) -> vk::SurfaceKHR {
+    match window_handle {
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Win32(handle) => {
+            let surface_create_info = vk::Win32SurfaceCreateInfoKHR {
+                hinstance: handle.hinstance.map(|nz| nz.get()).unwrap_or(0),
+                hwnd: handle.hwnd.get(),
+                ..Default::default()
+            };
+            let win32_surface_instance = ash::khr::win32_surface::Instance::new(entry, instance);
+            unsafe {
+                win32_surface_instance
+                    .create_win32_surface(&surface_create_info, None)
+                    .expect("Failed to create Vulkan surface")
+            }
+        }
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::AppKit(handle) => {
+            use ash::ext::metal_surface;
+
+            #[allow(unexpected_cfgs)]
+            autoreleasepool(|| {
+                let ns_view = handle.ns_view.as_ptr() as *mut Object;
+
+                // Create a CAMetalLayer
+                let metal_layer: *mut Object = unsafe { msg_send![class!(CAMetalLayer), layer] };
+
+                // Set the layer on the NSView
+                unsafe {
+                    let () = msg_send![ns_view, setLayer: metal_layer];
+                    let () = msg_send![ns_view, setWantsLayer: YES];
+                    let () = msg_send![metal_layer, setDisplaySyncEnabled: NO];
+                }
+
+                // Create Vulkan surface with the CAMetalLayer
+                let surface_create_info = vk::MetalSurfaceCreateInfoEXT {
+                    s_type: vk::StructureType::METAL_SURFACE_CREATE_INFO_EXT,
+                    p_next: std::ptr::null(),
+                    flags: vk::MetalSurfaceCreateFlagsEXT::empty(),
+                    p_layer: metal_layer as *const _,
+                    _marker: std::marker::PhantomData,
+                };
+                let metal_surface_instance = metal_surface::Instance::new(entry, instance);
+                unsafe {
+                    metal_surface_instance
+                        .create_metal_surface(&surface_create_info, None)
+                        .expect("Failed to create Vulkan surface")
+                }
+            })
+        }
+        #[cfg(target_os = "ios")]
+        RawWindowHandle::UiKit(handle) => {
+            let ui_view = handle.ui_view.as_ptr() as *mut Object;
+            // UIView's own layer can't be swapped, so a CAMetalLayer is added on
+            // top of it and sized to the view in pixels. A resumed window reuses
+            // the one added the first time.
+            let metal_layer = find_metal_layer(ui_view).unwrap_or_else(|| unsafe {
+                let metal_layer: *mut Object = msg_send![class!(CAMetalLayer), layer];
+                let view_layer: *mut Object = msg_send![ui_view, layer];
+                let () = msg_send![view_layer, addSublayer: metal_layer];
+                metal_layer
+            });
+            let scale_factor: f64 = unsafe { msg_send![ui_view, contentScaleFactor] };
+            size_metal_layer(ui_view, metal_layer, scale_factor);
+
+            let surface_create_info = vk::MetalSurfaceCreateInfoEXT {
+                p_layer: metal_layer as *const _,
+                ..Default::default()
+            };
+            let metal_surface_instance = ash::ext::metal_surface::Instance::new(entry, instance);
+            unsafe {
+                metal_surface_instance
+                    .create_metal_surface(&surface_create_info, None)
+                    .expect("Failed to create Vulkan surface")
+            }
+        }
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xlib(handle) => {
+            let xlib_display_handle = match display_handle {
+                RawDisplayHandle::Xlib(xlib) => xlib,
+                _ => panic!("Expected Xlib display handle for X11 window"),
+            };
+            let display = xlib_display_handle.display.unwrap().as_ptr();
+            let surface_create_info = vk::XlibSurfaceCreateInfoKHR {
+                dpy: display,
+                window: handle.window,
+                ..Default::default()
+            };
+            let xlib_surface_instance = ash::khr::xlib_surface::Instance::new(entry, instance);
+            unsafe { xlib_surface_instance.create_xlib_surface(&surface_create_info, None).expect("Failed to create Xlib surface") }
+        }
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Wayland(handle) => {
+            let wayland_display_handle = match display_handle {
+                RawDisplayHandle::Wayland(wayland) => wayland,
+                _ => panic!("Expected Wayland display handle for Wayland window"),
+            };
+            let display = wayland_display_handle.display.as_ptr();
+            let surface = handle.surface.as_ptr(); // Get surface from RawWindowHandle::Wayland
+            let surface_create_info = vk::WaylandSurfaceCreateInfoKHR {
+                display,
+                surface,
+                ..Default::default()
+            };
+            let wayland_surface_instance = ash::khr::wayland_surface::Instance::new(entry, instance);
+            unsafe { wayland_surface_instance.create_wayland_surface(&surface_create_info, None).expect("Failed to create Wayland surface") }
+        }
+        _ => panic!("Unsupported platform."),
+    }
+}
+
+// Matches the CAMetalLayer added by `create_surface` to the view's bounds after
+// a rotation or split-view resize. MoltenVK reports the layer's drawable size as
+// the surface extent, so call this before recreating the swapchain.
+#[cfg(target_os = "ios")]
+pub fn resize_metal_layer(window: &Window) {
+    let RawWindowHandle::UiKit(handle) = window
+        .window_handle()
+        .expect("Failed to get window handle")
+        .as_raw()
+    else {
+        return;
+    };
+    let ui_view = handle.ui_view.as_ptr() as *mut Object;
+    if let Some(metal_layer) = find_metal_layer(ui_view) {
+        size_metal_layer(ui_view, metal_layer, window.scale_factor());
+    }
+}
+
+// The CAMetalLayer `create_surface` added to the view, if it has run for it
+#[cfg(target_os = "ios")]
+fn find_metal_layer(ui_view: *mut Object) -> Option<*mut Object> {
+    unsafe {
+        let view_layer: *mut Object = msg_send![ui_view, layer];
+        let sublayers: *mut Object = msg_send![view_layer, sublayers];
+        if sublayers.is_null() {
+            return None;
+        }
+        let count: usize = msg_send![sublayers, count];
+        (0..count).find_map(|index| {
+            let layer: *mut Object = msg_send![sublayers, objectAtIndex: index];
+            let is_metal: BOOL = msg_send![layer, isKindOfClass: class!(CAMetalLayer)];
+            (is_metal == YES).then_some(layer)
+        })
+    }
+}
+
+// Covers the whole view, with one drawable pixel per physical pixel
+#[cfg(target_os = "ios")]
+fn size_metal_layer(ui_view: *mut Object, metal_layer: *mut Object, scale_factor: f64) {
+    unsafe {
+        let bounds: CGRect = msg_send![ui_view, bounds];
+        let () = msg_send![metal_layer, setFrame: bounds];
+        let () = msg_send![metal_layer, setContentsScale: scale_factor];
+        let drawable_size = CGSize {
+            width: bounds.size.width * scale_factor,
+            height: bounds.size.height * scale_factor,
+        };
+        let () = msg_send![metal_layer, setDrawableSize: drawable_size];
+    }
+}
@@ -0,0 +1,361 @@
+use ash::vk;
+
+use crate::gpu::GpuContext;
+
+// One side of a dependency: the pipeline stages involved and the memory
+// accesses they make, in synchronization2 terms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub stage: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+}
+
+impl Access {
+    // Nothing before: the first use of a resource, or an ownership acquire
+    pub const NONE: Access = Access {
+        stage: vk::PipelineStageFlags2::NONE,
+        access: vk::AccessFlags2::NONE,
+    };
+
+    pub fn new(stage: vk::PipelineStageFlags2, access: vk::AccessFlags2) -> Self {
+        Self { stage, access }
+    }
+}
+
+// Buffer and image barriers, each with its own stages and accesses. Recorded
+// with vkCmdPipelineBarrier2 when the device has synchronization2; otherwise the
+// stages are merged into one vkCmdPipelineBarrier and mapped to the nearest
+// legacy flags.
+#[derive(Default)]
+pub struct Barriers {
+    buffers: Vec<vk::BufferMemoryBarrier2<'static>>,
+    images: Vec<vk::ImageMemoryBarrier2<'static>>,
+}
+
+impl Barriers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty() && self.images.is_empty()
+    }
+
+    // Source stages of every barrier, combined
+    fn src_stages(&self) -> vk::PipelineStageFlags2 {
+        self.buffers
+            .iter()
+            .map(|barrier| barrier.src_stage_mask)
+            .chain(self.images.iter().map(|barrier| barrier.src_stage_mask))
+            .fold(vk::PipelineStageFlags2::NONE, |stages, stage| {
+                stages | stage
+            })
+    }
+
+    // Destination stages of every barrier, combined
+    pub fn dst_stages(&self) -> vk::PipelineStageFlags2 {
+        self.buffers
+            .iter()
+            .map(|barrier| barrier.dst_stage_mask)
+            .chain(self.images.iter().map(|barrier| barrier.dst_stage_mask))
+            .fold(vk::PipelineStageFlags2::NONE, |stages, stage| {
+                stages | stage
+            })
+    }
+
+    // The whole of `buffer`
+    pub fn buffer(&mut self, buffer: vk::Buffer, src: Access, dst: Access) -> &mut Self {
+        self.buffers.push(vk::BufferMemoryBarrier2 {
+            src_stage_mask: src.stage,
+            src_access_mask: src.access,
+            dst_stage_mask: dst.stage,
+            dst_access_mask: dst.access,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn image(
+        &mut self,
+        image: vk::Image,
+        subresource_range: vk::ImageSubresourceRange,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+        src: Access,
+        dst: Access,
+    ) -> &mut Self {
+        self.images.push(vk::ImageMemoryBarrier2 {
+            src_stage_mask: src.stage,
+            src_access_mask: src.access,
+            dst_stage_mask: dst.stage,
+            dst_access_mask: dst.access,
+            old_layout,
+            new_layout,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range,
+            ..Default::default()
+        });
+        self
+    }
+
+    // Moves the barriers of `other` into this set
+    pub fn append(&mut self, mut other: Barriers) {
+        self.buffers.append(&mut other.buffers);
+        self.images.append(&mut other.images);
+    }
+
+    // Turns every barrier added so far into a queue family ownership transfer.
+    // The same barriers are recorded on both queues: the release on the source
+    // and the acquire on the destination.
+    pub fn transfer_ownership(&mut self, src_family: u32, dst_family: u32) -> &mut Self {
+        for barrier in &mut self.buffers {
+            barrier.src_queue_family_index = src_family;
+            barrier.dst_queue_family_index = dst_family;
+        }
+        for barrier in &mut self.images {
+            barrier.src_queue_family_index = src_family;
+            barrier.dst_queue_family_index = dst_family;
+        }
+        self
+    }
+
+    pub fn record(&self, gpu: &GpuContext, command_buffer: vk::CommandBuffer) {
+        if self.is_empty() {
+            return;
+        }
+        if gpu.capabilities.synchronization2 {
+            let dependency_info = vk::DependencyInfo::default()
+                .buffer_memory_barriers(&self.buffers)
+                .image_memory_barriers(&self.images);
+            gpu.cmd_pipeline_barrier2(command_buffer, &dependency_info);
+            return;
+        }
+        let buffers: Vec<vk::BufferMemoryBarrier> = self
+            .buffers
+            .iter()
+            .map(|barrier| vk::BufferMemoryBarrier {
+                src_access_mask: legacy_access(barrier.src_access_mask),
+                dst_access_mask: legacy_access(barrier.dst_access_mask),
+                src_queue_family_index: barrier.src_queue_family_index,
+                dst_queue_family_index: barrier.dst_queue_family_index,
+                buffer: barrier.buffer,
+                offset: barrier.offset,
+                size: barrier.size,
+                ..Default::default()
+            })
+            .collect();
+        let images: Vec<vk::ImageMemoryBarrier> = self
+            .images
+            .iter()
+            .map(|barrier| vk::ImageMemoryBarrier {
+                src_access_mask: legacy_access(barrier.src_access_mask),
+                dst_access_mask: legacy_access(barrier.dst_access_mask),
+                old_layout: barrier.old_layout,
+                new_layout: barrier.new_layout,
+                src_queue_family_index: barrier.src_queue_family_index,
+                dst_queue_family_index: barrier.dst_queue_family_index,
+                image: barrier.image,
+                subresource_range: barrier.subresource_range,
+                ..Default::default()
+            })
+            .collect();
+        unsafe {
+            gpu.device.cmd_pipeline_barrier(
+                command_buffer,
+                legacy_stages(self.src_stages(), vk::PipelineStageFlags::TOP_OF_PIPE),
+                legacy_stages(self.dst_stages(), vk::PipelineStageFlags::BOTTOM_OF_PIPE),
+                vk::DependencyFlags::empty(),
+                &[],
+                &buffers,
+                &images,
+            );
+        }
+    }
+}
+
+// One vkQueueSubmit of command buffers with semaphore waits and signals. Values
+// only matter for timeline semaphores; binary ones ignore them. Submitted with
+// vkQueueSubmit2 when the device has synchronization2.
+#[derive(Default)]
+pub struct Submission {
+    command_buffers: Vec<vk::CommandBuffer>,
+    waits: Vec<vk::SemaphoreSubmitInfo<'static>>,
+    signals: Vec<vk::SemaphoreSubmitInfo<'static>>,
+    // On a device group: the GPUs the command buffers run on, 0 for all of them,
+    // and the one that waits and signals
+    device_mask: u32,
+    device_index: u32,
+}
+
+impl Submission {
+    pub fn new(command_buffer: vk::CommandBuffer) -> Self {
+        Self {
+            command_buffers: vec![command_buffer],
+            ..Default::default()
+        }
+    }
+
+    // Work in `stage` and later doesn't start until `semaphore` is signaled
+    pub fn with_wait(
+        mut self,
+        semaphore: vk::Semaphore,
+        value: u64,
+        stage: vk::PipelineStageFlags2,
+    ) -> Self {
+        self.waits.push(vk::SemaphoreSubmitInfo {
+            semaphore,
+            value,
+            stage_mask: stage,
+            ..Default::default()
+        });
+        self
+    }
+
+    // `semaphore` is signaled once the work in `stage` is done. Legacy submits
+    // always wait for all of it.
+    pub fn with_signal(
+        mut self,
+        semaphore: vk::Semaphore,
+        value: u64,
+        stage: vk::PipelineStageFlags2,
+    ) -> Self {
+        self.signals.push(vk::SemaphoreSubmitInfo {
+            semaphore,
+            value,
+            stage_mask: stage,
+            ..Default::default()
+        });
+        self
+    }
+
+    // Runs the command buffers on the GPUs in `device_mask` of a device group,
+    // with the semaphores waited on and signaled by GPU `device_index`
+    pub fn with_devices(mut self, device_mask: u32, device_index: u32) -> Self {
+        self.device_mask = device_mask;
+        self.device_index = device_index;
+        self
+    }
+
+    pub fn submit(&self, gpu: &GpuContext, queue: vk::Queue, fence: vk::Fence) {
+        if gpu.capabilities.synchronization2 {
+            let command_buffer_infos: Vec<vk::CommandBufferSubmitInfo> = self
+                .command_buffers
+                .iter()
+                .map(|&command_buffer| vk::CommandBufferSubmitInfo {
+                    command_buffer,
+                    device_mask: self.device_mask,
+                    ..Default::default()
+                })
+                .collect();
+            let on_device = |infos: &[vk::SemaphoreSubmitInfo<'static>]| {
+                infos
+                    .iter()
+                    .map(|info| info.device_index(self.device_index))
+                    .collect::<Vec<_>>()
+            };
+            let waits = on_device(&self.waits);
+            let signals = on_device(&self.signals);
+            let submit_info = vk::SubmitInfo2::default()
+                .wait_semaphore_infos(&waits)
+                .command_buffer_infos(&command_buffer_infos)
+                .signal_semaphore_infos(&signals);
+            gpu.queue_submit2(queue, &[submit_info], fence);
+            return;
+        }
+        let wait_semaphores: Vec<vk::Semaphore> =
+            self.waits.iter().map(|wait| wait.semaphore).collect();
+        let wait_stages: Vec<vk::PipelineStageFlags> = self
+            .waits
+            .iter()
+            .map(|wait| legacy_stages(wait.stage_mask, vk::PipelineStageFlags::TOP_OF_PIPE))
+            .collect();
+        let wait_values: Vec<u64> = self.waits.iter().map(|wait| wait.value).collect();
+        let signal_semaphores: Vec<vk::Semaphore> =
+            self.signals.iter().map(|signal| signal.semaphore).collect();
+        let signal_values: Vec<u64> = self.signals.iter().map(|signal| signal.value).collect();
+        let mut timeline_submit_info = vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let mut submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&self.command_buffers)
+            .signal_semaphores(&signal_semaphores);
+        if gpu.capabilities.timeline_semaphore {
+            submit_info = submit_info.push_next(&mut timeline_submit_info);
+        }
+        // The legacy struct has no "all GPUs" mask
+        let device_mask = match gpu.device_group {
+            Some(group) if self.device_mask == 0 => group.all_devices(),
+            _ => self.device_mask,
+        };
+        let wait_device_indices = vec![self.device_index; self.waits.len()];
+        let device_masks = vec![device_mask; self.command_buffers.len()];
+        let signal_device_indices = vec![self.device_index; self.signals.len()];
+        let mut group_submit_info = vk::DeviceGroupSubmitInfo::default()
+            .wait_semaphore_device_indices(&wait_device_indices)
+            .command_buffer_device_masks(&device_masks)
+            .signal_semaphore_device_indices(&signal_device_indices);
+        if gpu.device_group.is_some() {
+            submit_info = submit_info.push_next(&mut group_submit_info);
+        }
+        let result = unsafe { gpu.device.queue_submit(queue, &[submit_info], fence) };
+        gpu.expect(result, "Failed to submit command buffer");
+    }
+}
+
+// Synchronization2 stages for vkCmdPipelineBarrier. The low 32 bits are the same
+// in both; the finer stages added with synchronization2 fall back to the legacy
+// stage containing them. `empty` stands in for NONE.
+fn legacy_stages(
+    stages: vk::PipelineStageFlags2,
+    empty: vk::PipelineStageFlags,
+) -> vk::PipelineStageFlags {
+    let mut legacy = vk::PipelineStageFlags::from_raw(stages.as_raw() as u32);
+    if stages.intersects(
+        vk::PipelineStageFlags2::COPY
+            | vk::PipelineStageFlags2::RESOLVE
+            | vk::PipelineStageFlags2::BLIT
+            | vk::PipelineStageFlags2::CLEAR,
+    ) {
+        legacy |= vk::PipelineStageFlags::TRANSFER;
+    }
+    if stages.intersects(
+        vk::PipelineStageFlags2::INDEX_INPUT | vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT,
+    ) {
+        legacy |= vk::PipelineStageFlags::VERTEX_INPUT;
+    }
+    if stages.contains(vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS) {
+        legacy |= vk::PipelineStageFlags::VERTEX_SHADER
+            | vk::PipelineStageFlags::TESSELLATION_CONTROL_SHADER
+            | vk::PipelineStageFlags::TESSELLATION_EVALUATION_SHADER
+            | vk::PipelineStageFlags::GEOMETRY_SHADER;
+    }
+    if legacy.is_empty() {
+        empty
+    } else {
+        legacy
+    }
+}
+
+// Same for access flags: sampled and storage reads become SHADER_READ, storage
+// writes SHADER_WRITE
+fn legacy_access(access: vk::AccessFlags2) -> vk::AccessFlags {
+    let mut legacy = vk::AccessFlags::from_raw(access.as_raw() as u32);
+    if access
+        .intersects(vk::AccessFlags2::SHADER_SAMPLED_READ | vk::AccessFlags2::SHADER_STORAGE_READ)
+    {
+        legacy |= vk::AccessFlags::SHADER_READ;
+    }
+    if access.contains(vk::AccessFlags2::SHADER_STORAGE_WRITE) {
+        legacy |= vk::AccessFlags::SHADER_WRITE;
+    }
+    legacy
+}
@@ -0,0 +1,265 @@
+use ash::vk;
+
+use crate::gpu::GpuContext;
+use crate::ktx::{self, KtxTexture};
+use crate::memory::MemoryCategory;
+use crate::mipmap::{self, MipChain, MipmapMethod};
+use crate::sync::{Access, Barriers};
+use crate::transfer::UploadTarget;
+
+// A sampled 2D texture living in device-local memory: RGBA8 pixels, or a
+// KTX2 file in whatever format it was encoded in
+pub struct Texture {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Texture {
+    // `name` labels the image and its view for debugging tools
+    pub fn from_rgba(gpu: &GpuContext, name: &str, width: u32, height: u32, rgba: &[u8]) -> Self {
+        assert_eq!(
+            rgba.len(),
+            (width * height * 4) as usize,
+            "Texture data does not match {}x{} RGBA8",
+            width,
+            height
+        );
+        Self::from_levels(
+            gpu,
+            name,
+            vk::Format::R8G8B8A8_UNORM,
+            width,
+            height,
+            &[rgba],
+        )
+    }
+
+    // Uploads a loaded KTX2 texture with all its mip levels. Fails if the device
+    // can't sample its format.
+    pub fn from_ktx(gpu: &GpuContext, name: &str, ktx: &KtxTexture) -> Result<Self, String> {
+        let format_properties = unsafe {
+            gpu.instance
+                .get_physical_device_format_properties(gpu.physical_device, ktx.format)
+        };
+        let sampleable = format_properties
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE);
+        if !ktx::compression_enabled(ktx.format, &gpu.capabilities) || !sampleable {
+            return Err(format!("{:?} is not supported by this device", ktx.format));
+        }
+        let levels: Vec<&[u8]> = ktx.levels.iter().map(Vec::as_slice).collect();
+        Ok(Self::from_levels(
+            gpu, name, ktx.format, ktx.width, ktx.height, &levels,
+        ))
+    }
+
+    // `levels` holds the pixels of each mip level in `format`, largest first.
+    // A single level gets the rest of the chain generated on the GPU when the
+    // format allows it, so the texture doesn't shimmer when drawn small.
+    fn from_levels(
+        gpu: &GpuContext,
+        name: &str,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+    ) -> Self {
+        let device = &gpu.device;
+
+        // Stage every level in host-visible memory. Copies from a buffer must
+        // start on a multiple of the texel block size, at most 16 bytes.
+        let mut offsets = Vec::with_capacity(levels.len());
+        let mut staging_size = 0;
+        for level in levels {
+            offsets.push(staging_size);
+            staging_size = (staging_size + level.len() as vk::DeviceSize).next_multiple_of(16);
+        }
+        let (staging_buffer, staging_memory) = gpu.create_buffer(
+            staging_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        for (&offset, level) in offsets.iter().zip(levels) {
+            gpu.write_memory(staging_memory, offset, level);
+        }
+
+        let mipmap_method = if levels.len() == 1 {
+            MipmapMethod::for_format(gpu, format)
+        } else {
+            None
+        };
+        let mip_levels = match mipmap_method {
+            Some(_) => mipmap::level_count(width, height),
+            None => levels.len() as u32,
+        };
+        let image_create_info = vk::ImageCreateInfo {
+            image_type: vk::ImageType::TYPE_2D,
+            format,
+            extent: vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_levels,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED
+                | mipmap_method.map_or(vk::ImageUsageFlags::empty(), MipmapMethod::image_usage),
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            ..Default::default()
+        };
+        let image = unsafe {
+            device
+                .create_image(&image_create_info, None)
+                .expect("Failed to create texture image")
+        };
+        gpu.set_name(image, name);
+        gpu.set_name(staging_buffer, &format!("{} staging", name));
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory = gpu.allocate_memory(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryCategory::Images,
+        );
+        unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .expect("Failed to bind texture memory");
+        }
+
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: mip_levels,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let record_copy = |command_buffer| unsafe {
+            Barriers::new()
+                .image(
+                    image,
+                    subresource_range,
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    Access::NONE,
+                    Access::new(
+                        vk::PipelineStageFlags2::COPY,
+                        vk::AccessFlags2::TRANSFER_WRITE,
+                    ),
+                )
+                .record(gpu, command_buffer);
+
+            let regions: Vec<vk::BufferImageCopy> = offsets
+                .iter()
+                .enumerate()
+                .map(|(mip_level, &buffer_offset)| vk::BufferImageCopy {
+                    buffer_offset,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: mip_level as u32,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                    image_extent: vk::Extent3D {
+                        width: (width >> mip_level).max(1),
+                        height: (height >> mip_level).max(1),
+                        depth: 1,
+                    },
+                })
+                .collect();
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+        };
+        match mipmap_method {
+            // The final barrier to SHADER_READ_ONLY_OPTIMAL is part of the upload
+            None => gpu.upload(
+                staging_buffer,
+                staging_memory,
+                UploadTarget::Image {
+                    image,
+                    subresource_range,
+                },
+                record_copy,
+            ),
+            // Blits and dispatches need the graphics queue, so the copy can't go
+            // to the transfer queue. Generation leaves every level ready to sample.
+            Some(method) => {
+                let chain = MipChain {
+                    image,
+                    format,
+                    width,
+                    height,
+                    mip_levels,
+                };
+                let mut scratch = None;
+                gpu.submit_immediate(|command_buffer| {
+                    record_copy(command_buffer);
+                    scratch = mipmap::generate_mipmaps(gpu, command_buffer, method, chain);
+                });
+                if let Some(scratch) = scratch {
+                    scratch.destroy(gpu);
+                }
+                unsafe { device.destroy_buffer(staging_buffer, None) };
+                gpu.free_memory(staging_memory);
+            }
+        }
+
+        let view_create_info = vk::ImageViewCreateInfo {
+            image,
+            view_type: vk::ImageViewType::TYPE_2D,
+            format,
+            components: vk::ComponentMapping::default(),
+            subresource_range,
+            ..Default::default()
+        };
+        let view = unsafe {
+            device
+                .create_image_view(&view_create_info, None)
+                .expect("Failed to create texture image view")
+        };
+        gpu.set_name(view, &format!("{} view", name));
+        println!(
+            "Texture created: {}x{} {:?}, {} mip levels{} {:?}",
+            width,
+            height,
+            format,
+            mip_levels,
+            match mipmap_method {
+                Some(MipmapMethod::Blit) => " (blitted)",
+                Some(MipmapMethod::Compute) => " (computed)",
+                None => "",
+            },
+            image
+        );
+
+        Self {
+            image,
+            memory,
+            view,
+            width,
+            height,
+        }
+    }
+
+    pub fn destroy(&self, gpu: &GpuContext) {
+        unsafe {
+            gpu.device.destroy_image_view(self.view, None);
+            gpu.device.destroy_image(self.image, None);
+        }
+        gpu.free_memory(self.memory);
+    }
+}
@@ -0,0 +1,878 @@
+// Orthogonal tile maps made with Tiled (https://www.mapeditor.org). Maps are
+// read from TMX (XML) or TMJ (JSON) files, with tilesets embedded or in TSX or
+// TSJ files next to them. Tile layer data can be CSV, or base64 either raw or
+// compressed with zlib, gzip or Zstandard. Only tile layers are drawn; object
+// and image layers are skipped, and groups are flattened into their layers.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use glam::Vec2;
+use quick_xml::events::{BytesStart, Event};
+use serde::Deserialize;
+
+use crate::camera::Camera2d;
+use crate::renderer::Renderer;
+use crate::sprite::{Sprite, SpriteBatch, TextureAtlas, UvRect};
+
+// Tiled keeps the flips of each placed tile in the top bits of its global id
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const GID_MASK: u32 = 0x0fff_ffff;
+// Transparent border around each tile in the atlas, filled with copies of the
+// tile's edge pixels so linear filtering never blends in its neighbours
+const TILE_PADDING: u32 = 1;
+// The largest 2D image every Vulkan device can create
+const MAX_ATLAS_SIZE: u32 = 4096;
+
+// Tiles cut from one image in a regular grid
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tileset {
+    // Global id of the first tile; ids count on row by row
+    pub first_gid: u32,
+    pub name: String,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    // Pixels between neighbouring tiles, and around the edge of the image
+    pub spacing: u32,
+    pub margin: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    // Resolved against the file the tileset was defined in
+    pub image: PathBuf,
+    // Pixels of this color are transparent (Tiled's `trans`)
+    pub transparent_color: Option<[u8; 3]>,
+}
+
+impl Tileset {
+    pub fn contains(&self, gid: u32) -> bool {
+        (self.first_gid..self.first_gid + self.tile_count).contains(&gid)
+    }
+
+    // Top-left corner of a tile in the image, in pixels
+    fn tile_origin(&self, gid: u32) -> (u32, u32) {
+        let index = gid - self.first_gid;
+        (
+            self.margin + (index % self.columns) * (self.tile_width + self.spacing),
+            self.margin + (index / self.columns) * (self.tile_height + self.spacing),
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    // In tiles
+    pub width: u32,
+    pub height: u32,
+    pub visible: bool,
+    pub opacity: f32,
+    // Shift of the whole layer in pixels
+    pub offset: Vec2,
+    // Global tile ids row by row, 0 for empty cells, with the flip bits
+    pub tiles: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileMap {
+    // In tiles
+    pub width: u32,
+    pub height: u32,
+    // Grid cell size in pixels. Tiles larger than a cell stick out to the
+    // right and upwards, as Tiled draws them.
+    pub tile_width: u32,
+    pub tile_height: u32,
+    // Sorted by first global id
+    pub tilesets: Vec<Tileset>,
+    // Bottom first
+    pub layers: Vec<TileLayer>,
+}
+
+impl TileMap {
+    // Size of the whole map in pixels
+    pub fn pixel_size(&self) -> Vec2 {
+        Vec2::new(
+            (self.width * self.tile_width) as f32,
+            (self.height * self.tile_height) as f32,
+        )
+    }
+
+    // The tileset a global id (without flip bits) comes from
+    pub fn tileset_for(&self, gid: u32) -> Option<&Tileset> {
+        self.tilesets.iter().find(|tileset| tileset.contains(gid))
+    }
+
+    // Adds every tile of the visible layers that overlaps the camera's view to
+    // `batch`, bottom layer first, and returns how many there were. All tiles
+    // share the atlas texture, so the whole map is one instanced draw.
+    pub fn draw(
+        &self,
+        batch: &mut SpriteBatch,
+        atlas: &TileAtlas,
+        camera: &Camera2d,
+        viewport: Vec2,
+    ) -> usize {
+        let cell = Vec2::new(self.tile_width as f32, self.tile_height as f32);
+        // Oversized tiles can reach into view from cells left of or below it
+        let largest = self.tilesets.iter().fold(cell, |largest, tileset| {
+            largest.max(Vec2::new(
+                tileset.tile_width as f32,
+                tileset.tile_height as f32,
+            ))
+        });
+        let overhang = ((largest - cell) / cell).ceil();
+        let (view_min, view_max) = camera.visible_rect(viewport);
+        let mut drawn = 0;
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            let min = ((view_min - layer.offset) / cell).floor() - Vec2::new(overhang.x, 0.0);
+            let max = ((view_max - layer.offset) / cell).ceil() + Vec2::new(0.0, overhang.y);
+            let columns = min.x.max(0.0) as u32..(max.x.max(0.0) as u32).min(layer.width);
+            let rows = min.y.max(0.0) as u32..(max.y.max(0.0) as u32).min(layer.height);
+            let tint = [1.0, 1.0, 1.0, layer.opacity];
+            for row in rows {
+                for column in columns.clone() {
+                    let tile = layer.tiles[(row * layer.width + column) as usize];
+                    let gid = tile & GID_MASK;
+                    let (Some(mut uv), Some(tileset)) = (atlas.region(gid), self.tileset_for(gid))
+                    else {
+                        continue;
+                    };
+                    if tile & FLIPPED_HORIZONTALLY != 0 {
+                        std::mem::swap(&mut uv.min[0], &mut uv.max[0]);
+                    }
+                    if tile & FLIPPED_VERTICALLY != 0 {
+                        std::mem::swap(&mut uv.min[1], &mut uv.max[1]);
+                    }
+                    // Anchored at the bottom-left corner of its cell
+                    let size = Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32);
+                    let bottom_left =
+                        layer.offset + Vec2::new(column as f32, (row + 1) as f32) * cell;
+                    let center = bottom_left + Vec2::new(size.x, -size.y) * 0.5;
+                    batch.draw(
+                        atlas.atlas.texture,
+                        &Sprite {
+                            uv,
+                            tint,
+                            ..Sprite::new(
+                                camera.world_to_screen(center, viewport),
+                                size * camera.zoom,
+                            )
+                        },
+                    );
+                    drawn += 1;
+                }
+            }
+        }
+        drawn
+    }
+}
+
+// Reads a map from a .tmx or .tmj file, with the tilesets it references
+pub fn load(path: &Path) -> Result<TileMap, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut map = match extension(path).as_str() {
+        "tmx" | "xml" => parse_tmx(&text, directory)?,
+        "tmj" | "json" => parse_tmj(&text, directory)?,
+        other => return Err(format!("unknown map format .{}", other)),
+    };
+    map.tilesets.sort_by_key(|tileset| tileset.first_gid);
+    for layer in &map.layers {
+        if layer.tiles.len() != (layer.width * layer.height) as usize {
+            return Err(format!(
+                "layer {} has {} tiles for {}x{} cells",
+                layer.name,
+                layer.tiles.len(),
+                layer.width,
+                layer.height
+            ));
+        }
+    }
+    println!(
+        "Loaded {}: {}x{} tiles of {}x{}, {} layers, {} tilesets",
+        path.display(),
+        map.width,
+        map.height,
+        map.tile_width,
+        map.tile_height,
+        map.layers.len(),
+        map.tilesets.len()
+    );
+    Ok(map)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+// An external tileset, from a .tsx or .tsj file
+fn load_tileset(path: &Path, first_gid: u32) -> Result<Tileset, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| format!("tileset {}: {}", path.display(), error))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    match extension(path).as_str() {
+        "tsx" | "xml" => parse_tsx(&text, directory, first_gid),
+        "tsj" | "json" => {
+            let tileset: JsonTileset = serde_json::from_str(&text)
+                .map_err(|error| format!("tileset {}: {}", path.display(), error))?;
+            tileset.resolve(directory, first_gid)
+        }
+        other => Err(format!("unknown tileset format .{}", other)),
+    }
+}
+
+// Tiled writes colors as "#rrggbb" in JSON and "rrggbb" in XML
+fn parse_color(text: &str) -> Result<[u8; 3], String> {
+    let hex = text.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("bad color {}", text))?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+// Turns a layer's `data` into global ids, however it was encoded
+fn decode_tiles(
+    data: &str,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+) -> Result<Vec<u32>, String> {
+    match encoding {
+        Some("csv") => data
+            .split(',')
+            .map(|value| value.trim().parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|error| format!("bad CSV tile data: {}", error)),
+        Some("base64") => {
+            let bytes = decode_base64(data)?;
+            let bytes = match compression {
+                None | Some("") => bytes,
+                Some(scheme) => decompress(&bytes, scheme)?,
+            };
+            if bytes.len() % 4 != 0 {
+                return Err("tile data is not a whole number of ids".to_string());
+            }
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+                .collect())
+        }
+        Some(other) => Err(format!("unsupported tile data encoding {}", other)),
+        None => Err("tile data needs an encoding".to_string()),
+    }
+}
+
+fn decompress(bytes: &[u8], scheme: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let result = match scheme {
+        "zlib" => flate2::read::ZlibDecoder::new(bytes).read_to_end(&mut data),
+        "gzip" => flate2::read::GzDecoder::new(bytes).read_to_end(&mut data),
+        "zstd" => ruzstd::decoding::StreamingDecoder::new(bytes)
+            .map_err(|error| error.to_string())?
+            .read_to_end(&mut data),
+        other => return Err(format!("unsupported tile data compression {}", other)),
+    };
+    result.map_err(|error| format!("{} tile data: {}", scheme, error))?;
+    Ok(data)
+}
+
+// Standard base64, skipping whitespace; the padding is optional
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    for character in text.bytes() {
+        let value = match character {
+            b'A'..=b'Z' => character - b'A',
+            b'a'..=b'z' => character - b'a' + 26,
+            b'0'..=b'9' => character - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ if character.is_ascii_whitespace() => continue,
+            _ => return Err(format!("bad base64 character {:?}", character as char)),
+        };
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+// Attributes of one XML element by name
+struct Attributes(HashMap<String, String>);
+
+impl Attributes {
+    fn read(element: &BytesStart) -> Result<Self, String> {
+        let mut attributes = HashMap::new();
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(|error| error.to_string())?;
+            let value = attribute
+                .unescape_value()
+                .map_err(|error| error.to_string())?;
+            attributes.insert(
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                value.into_owned(),
+            );
+        }
+        Ok(Self(attributes))
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("bad value {:?} for {}", value, name))
+            })
+            .transpose()
+    }
+
+    fn require<T: FromStr>(&self, name: &str) -> Result<T, String> {
+        self.parse(name)?
+            .ok_or_else(|| format!("missing attribute {}", name))
+    }
+}
+
+fn xml_error(error: quick_xml::Error) -> String {
+    format!("XML error: {}", error)
+}
+
+// A layer read so far: its attributes, then its data once the element ends
+struct PendingLayer {
+    attributes: Attributes,
+    encoding: Option<String>,
+    compression: Option<String>,
+    data: String,
+}
+
+fn parse_tmx(text: &str, directory: &Path) -> Result<TileMap, String> {
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut map: Option<TileMap> = None;
+    // An embedded tileset while its children are read
+    let mut tileset: Option<(Attributes, Option<Attributes>)> = None;
+    let mut layer: Option<PendingLayer> = None;
+    let mut in_data = false;
+    loop {
+        let event = reader.read_event().map_err(xml_error)?;
+        let (element, empty) = match &event {
+            Event::Start(element) => (Some(element), false),
+            Event::Empty(element) => (Some(element), true),
+            _ => (None, false),
+        };
+        if let Some(element) = element {
+            let attributes = Attributes::read(element)?;
+            match element.name().as_ref() {
+                b"map" => {
+                    let orientation = attributes.get("orientation").unwrap_or("orthogonal");
+                    if orientation != "orthogonal" {
+                        return Err(format!("{} maps are not supported", orientation));
+                    }
+                    if attributes.get("infinite") == Some("1") {
+                        return Err("infinite maps are not supported".to_string());
+                    }
+                    map = Some(TileMap {
+                        width: attributes.require("width")?,
+                        height: attributes.require("height")?,
+                        tile_width: attributes.require("tilewidth")?,
+                        tile_height: attributes.require("tileheight")?,
+                        tilesets: Vec::new(),
+                        layers: Vec::new(),
+                    });
+                }
+                b"tileset" => {
+                    let map = map.as_mut().ok_or("tileset outside a map")?;
+                    let first_gid = attributes.require("firstgid")?;
+                    if let Some(source) = attributes.get("source") {
+                        map.tilesets
+                            .push(load_tileset(&directory.join(source), first_gid)?);
+                    } else if empty {
+                        return Err("tileset without an image".to_string());
+                    } else {
+                        tileset = Some((attributes, None));
+                    }
+                }
+                // Also found in <tile> elements of image collections, which
+                // aren't supported
+                b"image" => {
+                    if let Some((_, image)) = tileset.as_mut() {
+                        *image = Some(attributes);
+                    }
+                }
+                b"layer" => {
+                    layer = Some(PendingLayer {
+                        attributes,
+                        encoding: None,
+                        compression: None,
+                        data: String::new(),
+                    });
+                }
+                b"data" => {
+                    let layer = layer.as_mut().ok_or("data outside a layer")?;
+                    layer.encoding = attributes.get("encoding").map(str::to_string);
+                    layer.compression = attributes.get("compression").map(str::to_string);
+                    in_data = !empty;
+                }
+                b"chunk" => return Err("infinite maps are not supported".to_string()),
+                _ => {}
+            }
+        }
+        match event {
+            Event::Text(data) if in_data => {
+                let data = data.unescape().map_err(xml_error)?;
+                layer.as_mut().unwrap().data.push_str(&data);
+            }
+            Event::End(element) => match element.name().as_ref() {
+                b"data" => in_data = false,
+                b"tileset" => {
+                    if let (Some((attributes, image)), Some(map)) = (tileset.take(), map.as_mut()) {
+                        let first_gid = attributes.require("firstgid")?;
+                        let image = image.ok_or("tileset without an image")?;
+                        map.tilesets
+                            .push(xml_tileset(&attributes, &image, directory, first_gid)?);
+                    }
+                }
+                b"layer" => {
+                    let map = map.as_mut().ok_or("layer outside a map")?;
+                    let PendingLayer {
+                        attributes,
+                        encoding,
+                        compression,
+                        data,
+                    } = layer.take().unwrap();
+                    let name = attributes.get("name").unwrap_or_default().to_string();
+                    map.layers.push(TileLayer {
+                        width: attributes.require("width")?,
+                        height: attributes.require("height")?,
+                        visible: attributes.get("visible") != Some("0"),
+                        opacity: attributes.parse("opacity")?.unwrap_or(1.0),
+                        offset: Vec2::new(
+                            attributes.parse("offsetx")?.unwrap_or(0.0),
+                            attributes.parse("offsety")?.unwrap_or(0.0),
+                        ),
+                        tiles: decode_tiles(&data, encoding.as_deref(), compression.as_deref())
+                            .map_err(|error| format!("layer {}: {}", name, error))?,
+                        name,
+                    });
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    map.ok_or_else(|| "no map element".to_string())
+}
+
+// A tileset from the attributes of its <tileset> and <image> elements
+fn xml_tileset(
+    attributes: &Attributes,
+    image: &Attributes,
+    directory: &Path,
+    first_gid: u32,
+) -> Result<Tileset, String> {
+    let columns = attributes.require("columns")?;
+    if columns == 0 {
+        return Err("image collection tilesets are not supported".to_string());
+    }
+    Ok(Tileset {
+        first_gid,
+        name: attributes.get("name").unwrap_or_default().to_string(),
+        tile_width: attributes.require("tilewidth")?,
+        tile_height: attributes.require("tileheight")?,
+        spacing: attributes.parse("spacing")?.unwrap_or(0),
+        margin: attributes.parse("margin")?.unwrap_or(0),
+        columns,
+        tile_count: attributes.require("tilecount")?,
+        image: directory.join(image.get("source").ok_or("image without a source")?),
+        transparent_color: image.get("trans").map(parse_color).transpose()?,
+    })
+}
+
+fn parse_tsx(text: &str, directory: &Path, first_gid: u32) -> Result<Tileset, String> {
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut tileset = None;
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(element) | Event::Empty(element) => match element.name().as_ref() {
+                b"tileset" => tileset = Some(Attributes::read(&element)?),
+                b"image" => {
+                    let attributes = tileset.as_ref().ok_or("image outside a tileset")?;
+                    return xml_tileset(
+                        attributes,
+                        &Attributes::read(&element)?,
+                        directory,
+                        first_gid,
+                    );
+                }
+                _ => {}
+            },
+            Event::Eof => return Err("tileset without an image".to_string()),
+            _ => {}
+        }
+    }
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    orientation: Option<String>,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<JsonLayer>,
+    tilesets: Vec<JsonTileset>,
+}
+
+#[derive(Deserialize)]
+struct JsonLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    // An array of ids, or a base64 string
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    encoding: Option<String>,
+    #[serde(default)]
+    compression: Option<String>,
+    #[serde(default = "default_visible")]
+    visible: bool,
+    #[serde(default = "default_opacity")]
+    opacity: f32,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    // Children of a group
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+}
+
+// Either a reference to a .tsj/.tsx file (`firstgid` and `source`) or a whole
+// tileset, in a map or in its own file
+#[derive(Deserialize)]
+struct JsonTileset {
+    #[serde(default)]
+    firstgid: u32,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default)]
+    image: Option<String>,
+    #[serde(default)]
+    transparentcolor: Option<String>,
+}
+
+impl JsonTileset {
+    fn resolve(self, directory: &Path, first_gid: u32) -> Result<Tileset, String> {
+        if let Some(source) = self.source {
+            return load_tileset(&directory.join(source), first_gid);
+        }
+        let image = self.image.ok_or("tileset without an image")?;
+        if self.columns == 0 || self.tilewidth == 0 || self.tileheight == 0 {
+            return Err(format!("tileset {} has no tile grid", self.name));
+        }
+        Ok(Tileset {
+            first_gid,
+            name: self.name,
+            tile_width: self.tilewidth,
+            tile_height: self.tileheight,
+            spacing: self.spacing,
+            margin: self.margin,
+            columns: self.columns,
+            tile_count: self.tilecount,
+            image: directory.join(image),
+            transparent_color: self
+                .transparentcolor
+                .as_deref()
+                .map(parse_color)
+                .transpose()?,
+        })
+    }
+}
+
+fn parse_tmj(text: &str, directory: &Path) -> Result<TileMap, String> {
+    let json: JsonMap = serde_json::from_str(text).map_err(|error| error.to_string())?;
+    let orientation = json.orientation.as_deref().unwrap_or("orthogonal");
+    if orientation != "orthogonal" {
+        return Err(format!("{} maps are not supported", orientation));
+    }
+    if json.infinite {
+        return Err("infinite maps are not supported".to_string());
+    }
+    let mut map = TileMap {
+        width: json.width,
+        height: json.height,
+        tile_width: json.tilewidth,
+        tile_height: json.tileheight,
+        tilesets: Vec::new(),
+        layers: Vec::new(),
+    };
+    for tileset in json.tilesets {
+        let first_gid = tileset.firstgid;
+        map.tilesets.push(tileset.resolve(directory, first_gid)?);
+    }
+    push_json_layers(&mut map.layers, json.layers)?;
+    Ok(map)
+}
+
+fn push_json_layers(layers: &mut Vec<TileLayer>, json: Vec<JsonLayer>) -> Result<(), String> {
+    for layer in json {
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let tiles = match layer.data {
+                    Some(serde_json::Value::String(data)) => decode_tiles(
+                        &data,
+                        layer.encoding.as_deref(),
+                        layer.compression.as_deref(),
+                    ),
+                    Some(data) => serde_json::from_value(data).map_err(|error| error.to_string()),
+                    None => Err("infinite maps are not supported".to_string()),
+                }
+                .map_err(|error| format!("layer {}: {}", layer.name, error))?;
+                layers.push(TileLayer {
+                    name: layer.name,
+                    width: layer.width,
+                    height: layer.height,
+                    visible: layer.visible,
+                    opacity: layer.opacity,
+                    offset: Vec2::new(layer.offsetx, layer.offsety),
+                    tiles,
+                });
+            }
+            "group" => push_json_layers(layers, layer.layers)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// The tiles a map's layers use, copied out of their tileset images into one
+// RGBA8 image
+pub struct PackedTiles {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    // Pixel rectangle (x, y, width, height) of each global id, None for ids
+    // no layer uses
+    pub rects: Vec<Option<[u32; 4]>>,
+}
+
+impl PackedTiles {
+    // Tiles go into a square grid of cells as large as the largest tile, each
+    // with TILE_PADDING around it
+    pub fn new(map: &TileMap) -> Result<Self, String> {
+        let mut used = Vec::new();
+        for &tile in map.layers.iter().flat_map(|layer| &layer.tiles) {
+            let gid = (tile & GID_MASK) as usize;
+            if gid > 0 && map.tileset_for(gid as u32).is_some() {
+                if used.len() <= gid {
+                    used.resize(gid + 1, false);
+                }
+                used[gid] = true;
+            }
+        }
+        let count = used.iter().filter(|&&used| used).count() as u32;
+        let cell_width = map.tilesets.iter().map(|tileset| tileset.tile_width).max();
+        let cell_height = map.tilesets.iter().map(|tileset| tileset.tile_height).max();
+        let cell_width = cell_width.unwrap_or(1) + 2 * TILE_PADDING;
+        let cell_height = cell_height.unwrap_or(1) + 2 * TILE_PADDING;
+        let columns = (count as f32).sqrt().ceil().max(1.0) as u32;
+        let rows = count.div_ceil(columns).max(1);
+        let (width, height) = (columns * cell_width, rows * cell_height);
+        if width > MAX_ATLAS_SIZE || height > MAX_ATLAS_SIZE {
+            return Err(format!(
+                "{} tiles need a {}x{} atlas, more than {}x{}",
+                count, width, height, MAX_ATLAS_SIZE, MAX_ATLAS_SIZE
+            ));
+        }
+
+        let mut packed = Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+            rects: vec![None; used.len()],
+        };
+        let mut images = HashMap::new();
+        let gids = (0..used.len() as u32).filter(|&gid| used[gid as usize]);
+        for (slot, gid) in (0..).zip(gids) {
+            let tileset = map.tileset_for(gid).unwrap();
+            if !images.contains_key(&tileset.image) {
+                images.insert(tileset.image.clone(), load_image(tileset)?);
+            }
+            let image = &images[&tileset.image];
+            let x = (slot % columns) * cell_width + TILE_PADDING;
+            let y = (slot / columns) * cell_height + TILE_PADDING;
+            packed.copy_tile(image, tileset, gid, x, y)?;
+            packed.rects[gid as usize] = Some([x, y, tileset.tile_width, tileset.tile_height]);
+        }
+        Ok(packed)
+    }
+
+    // Copies a tile to (x, y), clamping reads to the tile so the padding
+    // repeats its edge pixels
+    fn copy_tile(
+        &mut self,
+        image: &RgbaImage,
+        tileset: &Tileset,
+        gid: u32,
+        x: u32,
+        y: u32,
+    ) -> Result<(), String> {
+        let (origin_x, origin_y) = tileset.tile_origin(gid);
+        let (tile_width, tile_height) = (tileset.tile_width, tileset.tile_height);
+        if origin_x + tile_width > image.width || origin_y + tile_height > image.height {
+            return Err(format!(
+                "tile {} lies outside {}",
+                gid,
+                tileset.image.display()
+            ));
+        }
+        let padding = TILE_PADDING as i32;
+        for row in -padding..tile_height as i32 + padding {
+            for column in -padding..tile_width as i32 + padding {
+                let source_x = origin_x + column.clamp(0, tile_width as i32 - 1) as u32;
+                let source_y = origin_y + row.clamp(0, tile_height as i32 - 1) as u32;
+                let source = ((source_y * image.width + source_x) * 4) as usize;
+                let target_x = (x as i32 + column) as u32;
+                let target_y = (y as i32 + row) as u32;
+                let target = ((target_y * self.width + target_x) * 4) as usize;
+                self.pixels[target..target + 4].copy_from_slice(&image.pixels[source..source + 4]);
+            }
+        }
+        Ok(())
+    }
+}
+
+struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+// Decodes a tileset's PNG to RGBA8, applying its transparent color
+fn load_image(tileset: &Tileset) -> Result<RgbaImage, String> {
+    let path = &tileset.image;
+    let error = |error: png::DecodingError| format!("{}: {}", path.display(), error);
+    let file =
+        std::fs::File::open(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(error)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(error)?;
+    let samples = &buffer[..info.buffer_size()];
+    let pixels: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => samples.to_vec(),
+        png::ColorType::Rgb => samples
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => samples
+            .chunks_exact(2)
+            .flat_map(|gray| [gray[0], gray[0], gray[0], gray[1]])
+            .collect(),
+        png::ColorType::Grayscale => samples
+            .iter()
+            .flat_map(|&gray| [gray, gray, gray, 255])
+            .collect(),
+        png::ColorType::Indexed => {
+            return Err(format!("{}: palette was not expanded", path.display()))
+        }
+    };
+    let mut image = RgbaImage {
+        width: info.width,
+        height: info.height,
+        pixels,
+    };
+    if let Some(key) = tileset.transparent_color {
+        for pixel in image.pixels.chunks_exact_mut(4) {
+            if pixel[..3] == key {
+                pixel[3] = 0;
+            }
+        }
+    }
+    Ok(image)
+}
+
+// A map's packed tiles uploaded as one sprite texture, with a region per tile
+pub struct TileAtlas {
+    pub atlas: TextureAtlas,
+    // Region index in `atlas` of each global id the map uses
+    regions: Vec<Option<usize>>,
+}
+
+impl TileAtlas {
+    pub fn new(renderer: &mut Renderer, map: &TileMap) -> Result<Self, String> {
+        let packed = PackedTiles::new(map)?;
+        let texture = renderer.create_texture(packed.width, packed.height, &packed.pixels);
+        let mut atlas = TextureAtlas::new(texture, packed.width, packed.height);
+        let regions = packed
+            .rects
+            .iter()
+            .map(|rect| rect.map(|[x, y, width, height]| atlas.add_region(x, y, width, height)))
+            .collect();
+        println!(
+            "Tile atlas: {} tiles in {}x{}",
+            atlas.len(),
+            packed.width,
+            packed.height
+        );
+        Ok(Self { atlas, regions })
+    }
+
+    // UVs of a global id without flip bits, None if the map doesn't use it
+    pub fn region(&self, gid: u32) -> Option<UvRect> {
+        self.regions
+            .get(gid as usize)
+            .copied()
+            .flatten()
+            .map(|region| self.atlas.region(region))
+    }
+
+    pub fn destroy(self, renderer: &mut Renderer) {
+        renderer.destroy_texture(self.atlas.texture);
+    }
+}
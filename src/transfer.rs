@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+
+use ash::vk;
+
+use crate::frame_ring::FRAMES_IN_FLIGHT;
+use crate::gpu::GpuContext;
+use crate::sync::{Access, Barriers, Submission};
+
+// A device-local resource filled by an upload, and how the graphics queue will
+// first use it
+#[derive(Clone, Copy)]
+pub enum UploadTarget {
+    Buffer(vk::Buffer),
+    // Moved from TRANSFER_DST_OPTIMAL to SHADER_READ_ONLY_OPTIMAL for sampling
+    Image {
+        image: vk::Image,
+        subresource_range: vk::ImageSubresourceRange,
+    },
+}
+
+impl UploadTarget {
+    // Buffers can be vertex, index or storage data, so any stage may read them
+    fn first_use(self) -> Access {
+        match self {
+            UploadTarget::Buffer(_) => Access::new(
+                vk::PipelineStageFlags2::ALL_COMMANDS,
+                vk::AccessFlags2::MEMORY_READ,
+            ),
+            UploadTarget::Image { .. } => Access::new(
+                vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::SHADER_SAMPLED_READ,
+            ),
+        }
+    }
+
+    fn add_barrier(self, barriers: &mut Barriers, src: Access, dst: Access) {
+        match self {
+            UploadTarget::Buffer(buffer) => barriers.buffer(buffer, src, dst),
+            UploadTarget::Image {
+                image,
+                subresource_range,
+            } => barriers.image(
+                image,
+                subresource_range,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                src,
+                dst,
+            ),
+        };
+    }
+}
+
+const COPY_WRITE: Access = Access {
+    stage: vk::PipelineStageFlags2::COPY,
+    access: vk::AccessFlags2::TRANSFER_WRITE,
+};
+
+// The last barrier of an upload recorded on the graphics queue: the copy is made
+// visible to the target's first use
+pub fn record_upload_barrier(
+    gpu: &GpuContext,
+    command_buffer: vk::CommandBuffer,
+    target: UploadTarget,
+) {
+    let mut barriers = Barriers::new();
+    target.add_barrier(&mut barriers, COPY_WRITE, target.first_use());
+    barriers.record(gpu, command_buffer);
+}
+
+// Uploads the next graphics submission has to wait for. The frame records the
+// acquire barriers before anything else, waits on the semaphores, then hands
+// this back with `GpuContext::retire_uploads`.
+#[derive(Default)]
+pub struct UploadHandoff {
+    semaphores: Vec<vk::Semaphore>,
+    acquires: Barriers,
+}
+
+impl UploadHandoff {
+    pub fn semaphores(&self) -> &[vk::Semaphore] {
+        &self.semaphores
+    }
+
+    // Stages each semaphore wait blocks. The acquire barriers start from the same
+    // stages, so they run after the wait.
+    pub fn wait_stage(&self) -> vk::PipelineStageFlags2 {
+        self.acquires.dst_stages()
+    }
+
+    pub fn record_acquire(&self, gpu: &GpuContext, command_buffer: vk::CommandBuffer) {
+        self.acquires.record(gpu, command_buffer);
+    }
+}
+
+// A copy submitted to the transfer queue; its staging buffer is freed once the
+// fence signals
+struct Upload {
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    staging_buffer: vk::Buffer,
+    staging_memory: vk::DeviceMemory,
+}
+
+// A queue from a family that only does transfers, used for staging copies so
+// they run alongside rendering instead of idling the graphics queue. Ownership
+// of every uploaded resource is released here and acquired by the next frame.
+pub struct TransferQueue {
+    queue: vk::Queue,
+    family_index: u32,
+    graphics_family_index: u32,
+    command_pool: vk::CommandPool,
+    in_flight: Vec<Upload>,
+    pending: UploadHandoff,
+    // Semaphores waited on by recent frames, destroyed once those frames are done
+    retired: VecDeque<Vec<vk::Semaphore>>,
+}
+
+impl TransferQueue {
+    pub fn new(
+        device: &ash::Device,
+        queue: vk::Queue,
+        family_index: u32,
+        graphics_family_index: u32,
+    ) -> Self {
+        let command_pool = unsafe {
+            device
+                .create_command_pool(
+                    &vk::CommandPoolCreateInfo {
+                        flags: vk::CommandPoolCreateFlags::TRANSIENT,
+                        queue_family_index: family_index,
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create transfer command pool")
+        };
+        Self {
+            queue,
+            family_index,
+            graphics_family_index,
+            command_pool,
+            in_flight: Vec::new(),
+            pending: UploadHandoff::default(),
+            retired: VecDeque::new(),
+        }
+    }
+
+    // Records `record` (the copies out of `staging_buffer`) and a release barrier
+    // for `target`, and submits without waiting
+    pub fn submit(
+        &mut self,
+        gpu: &GpuContext,
+        staging_buffer: vk::Buffer,
+        staging_memory: vk::DeviceMemory,
+        target: UploadTarget,
+        record: impl FnOnce(vk::CommandBuffer),
+    ) {
+        self.collect(gpu);
+        let device = &gpu.device;
+        let mut release = Barriers::new();
+        target.add_barrier(&mut release, COPY_WRITE, Access::NONE);
+        release.transfer_ownership(self.family_index, self.graphics_family_index);
+        unsafe {
+            let command_buffer = device
+                .allocate_command_buffers(&vk::CommandBufferAllocateInfo {
+                    command_pool: self.command_pool,
+                    level: vk::CommandBufferLevel::PRIMARY,
+                    command_buffer_count: 1,
+                    ..Default::default()
+                })
+                .expect("Failed to allocate transfer command buffer")[0];
+            gpu.set_name(command_buffer, "transfer upload");
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo {
+                        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+                        ..Default::default()
+                    },
+                )
+                .expect("Failed to begin transfer command buffer");
+            record(command_buffer);
+            release.record(gpu, command_buffer);
+            device
+                .end_command_buffer(command_buffer)
+                .expect("Failed to end transfer command buffer");
+
+            let semaphore = device
+                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                .expect("Failed to create upload semaphore");
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .expect("Failed to create upload fence");
+            gpu.set_name(semaphore, "upload semaphore");
+            gpu.set_name(fence, "upload fence");
+            Submission::new(command_buffer)
+                .with_signal(semaphore, 0, vk::PipelineStageFlags2::ALL_COMMANDS)
+                .submit(gpu, self.queue, fence);
+
+            self.in_flight.push(Upload {
+                command_buffer,
+                fence,
+                staging_buffer,
+                staging_memory,
+            });
+            self.pending.semaphores.push(semaphore);
+        }
+        // The acquire starts from the stages the frame's semaphore wait blocks
+        let first_use = target.first_use();
+        let mut acquire = Barriers::new();
+        target.add_barrier(
+            &mut acquire,
+            Access::new(first_use.stage, vk::AccessFlags2::NONE),
+            first_use,
+        );
+        acquire.transfer_ownership(self.family_index, self.graphics_family_index);
+        self.pending.acquires.append(acquire);
+    }
+
+    // Everything submitted since the last call
+    pub fn take_pending(&mut self, gpu: &GpuContext) -> UploadHandoff {
+        self.collect(gpu);
+        std::mem::take(&mut self.pending)
+    }
+
+    // Called once per frame after the submission that waited on `handoff`
+    pub fn retire(&mut self, gpu: &GpuContext, handoff: UploadHandoff) {
+        self.retired.push_back(handoff.semaphores);
+        while self.retired.len() > FRAMES_IN_FLIGHT {
+            for semaphore in self.retired.pop_front().unwrap() {
+                unsafe { gpu.device.destroy_semaphore(semaphore, None) };
+            }
+        }
+    }
+
+    // Frees the pool, the staging memory and every semaphore, handed off or
+    // not. Caller ensures the device is idle, so every copy has finished.
+    pub fn destroy(mut self, gpu: &GpuContext) {
+        self.collect(gpu);
+        let pending = self.pending.semaphores.drain(..);
+        for semaphore in self.retired.drain(..).flatten().chain(pending) {
+            unsafe { gpu.device.destroy_semaphore(semaphore, None) };
+        }
+        unsafe { gpu.device.destroy_command_pool(self.command_pool, None) };
+    }
+
+    // Frees the staging memory of every finished copy
+    fn collect(&mut self, gpu: &GpuContext) {
+        let device = &gpu.device;
+        self.in_flight.retain(|upload| {
+            let done = unsafe {
+                device
+                    .get_fence_status(upload.fence)
+                    .expect("Failed to query upload fence")
+            };
+            if done {
+                unsafe {
+                    device.destroy_fence(upload.fence, None);
+                    device.free_command_buffers(self.command_pool, &[upload.command_buffer]);
+                    device.destroy_buffer(upload.staging_buffer, None);
+                }
+                gpu.free_memory(upload.staging_memory);
+            }
+            !done
+        });
+    }
+}
@@ -0,0 +1,179 @@
+use ash::vk;
+
+use crate::frame_ring::FrameRing;
+use crate::gpu::GpuContext;
+use crate::pipeline::{PipelineDesc, PipelineManager};
+use crate::spectrum::{BAND_COUNT, WAVEFORM_SIZE};
+
+// Bytes pushed to the frame ring each frame while the visualizer is on
+pub const UPLOAD_SIZE: usize = size_of::<SpectrumUniforms>();
+
+// Mirrors the `Spectrum` uniform block in shaders/visualizer_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpectrumUniforms {
+    bands: [f32; BAND_COUNT],
+    waveform: [f32; WAVEFORM_SIZE],
+}
+
+// Mirrors the push constant block in shaders/visualizer_frag.glsl
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VisualizerPushConstants {
+    resolution: [f32; 2],
+    time: f32,
+}
+
+// Fullscreen spectrum bars and waveform. Whatever the scene leaves in `bands`
+// and `waveform` is copied into the frame ring each frame and read through a
+// dynamic uniform buffer descriptor.
+pub struct VisualizerPass {
+    // Band levels in 0..1, lowest frequency first
+    pub bands: [f32; BAND_COUNT],
+    // Recent samples in -1..1, oldest first
+    pub waveform: [f32; WAVEFORM_SIZE],
+    // Seconds, animates the waveform color
+    pub time: f32,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    // Frame ring generation the descriptor set points at, None before the first upload
+    bound_generation: Option<u64>,
+    offset: u32,
+    push_constants: VisualizerPushConstants,
+    pipeline: PipelineDesc,
+}
+
+impl VisualizerPass {
+    pub fn new(gpu: &GpuContext, pipelines: &mut PipelineManager) -> Self {
+        let shaders = pipelines.register_shaders(
+            gpu,
+            "visualizer",
+            include_bytes!("../shaders/fullscreen_vert.spv"),
+            include_bytes!("../shaders/visualizer_frag.spv"),
+        );
+        // The spectrum uniforms are bound at a different ring offset every frame
+        let interface = pipelines.interface(shaders).clone().with_dynamic(0, 0);
+        let set_layouts = interface.create_set_layouts(gpu);
+        let pool_sizes = interface.pool_sizes(1);
+        let descriptor_pool = unsafe {
+            gpu.device
+                .create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo {
+                        max_sets: 1,
+                        pool_size_count: pool_sizes.len() as u32,
+                        p_pool_sizes: pool_sizes.as_ptr(),
+                        ..Default::default()
+                    },
+                    None,
+                )
+                .expect("Failed to create visualizer descriptor pool")
+        };
+        let descriptor_set = unsafe {
+            gpu.device
+                .allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo {
+                    descriptor_pool,
+                    descriptor_set_count: 1,
+                    p_set_layouts: set_layouts.as_ptr(),
+                    ..Default::default()
+                })
+                .expect("Failed to allocate visualizer descriptor set")[0]
+        };
+        let pipeline_layout = interface.create_pipeline_layout(
+            gpu,
+            &set_layouts,
+            size_of::<VisualizerPushConstants>(),
+        );
+        let pipeline = PipelineDesc::new(shaders, pipeline_layout);
+        pipelines.prepare(gpu, &pipeline);
+        println!("Visualizer pass created");
+
+        Self {
+            bands: [0.0; BAND_COUNT],
+            waveform: [0.0; WAVEFORM_SIZE],
+            time: 0.0,
+            descriptor_set_layout: set_layouts[0],
+            descriptor_pool,
+            descriptor_set,
+            bound_generation: None,
+            offset: 0,
+            push_constants: VisualizerPushConstants {
+                resolution: [1.0, 1.0],
+                time: 0.0,
+            },
+            pipeline,
+        }
+    }
+
+    // Copies this frame's spectrum into the frame ring; call before recording
+    pub fn upload(&mut self, gpu: &GpuContext, ring: &mut FrameRing, extent: vk::Extent2D) {
+        let uniforms = SpectrumUniforms {
+            bands: self.bands,
+            waveform: self.waveform,
+        };
+        self.offset = ring.push(bytemuck::bytes_of(&uniforms)) as u32;
+        if self.bound_generation != Some(ring.generation()) {
+            self.bound_generation = Some(ring.generation());
+            let buffer_info = vk::DescriptorBufferInfo {
+                buffer: ring.buffer(),
+                offset: 0,
+                range: UPLOAD_SIZE as vk::DeviceSize,
+            };
+            unsafe {
+                gpu.device.update_descriptor_sets(
+                    &[vk::WriteDescriptorSet {
+                        dst_set: self.descriptor_set,
+                        dst_binding: 0,
+                        descriptor_count: 1,
+                        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                        p_buffer_info: &buffer_info,
+                        ..Default::default()
+                    }],
+                    &[],
+                );
+            }
+        }
+        self.push_constants = VisualizerPushConstants {
+            resolution: [extent.width as f32, extent.height as f32],
+            time: self.time,
+        };
+    }
+
+    pub fn record_draw(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &mut PipelineManager,
+        command_buffer: vk::CommandBuffer,
+    ) {
+        let pipeline = pipelines.get(gpu, &self.pipeline);
+        let device = &gpu.device;
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.layout,
+                0,
+                &[self.descriptor_set],
+                &[self.offset],
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&self.push_constants),
+            );
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, pipelines: &mut PipelineManager) {
+        pipelines.release_shaders(device, self.pipeline.shaders);
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline.layout, None);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+    }
+}
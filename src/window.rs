@@ -0,0 +1,1196 @@
+use ash::vk;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+use winit::raw_window_handle::{RawDisplayHandle, RawWindowHandle};
+use winit::window::{Window, WindowId};
+
+use crate::device_group::GroupFrame;
+use crate::gpu::GpuContext;
+use crate::hdr::{self, OutputEncoding};
+use crate::recording::FrameReadback;
+use crate::surface::{create_surface, create_surface_from_raw};
+
+// How the swapchain hands frames to the display, chosen with `--present-mode`.
+// Surfaces don't support every mode; each falls back to the next one in its
+// `preferences` that the surface lists, and FIFO is always there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PresentMode {
+    // The newest frame replaces any still waiting, shown at the next refresh
+    #[default]
+    Mailbox,
+    // Shown right away, tearing if mid-refresh
+    Immediate,
+    // FIFO: one frame per refresh, rendering held to the refresh rate
+    Vsync,
+    // FIFO_RELAXED: like vsync, but a frame that misses its refresh is shown
+    // as soon as it's ready, tearing instead of waiting a whole refresh
+    AdaptiveVsync,
+}
+
+impl PresentMode {
+    const ALL: [PresentMode; 4] = [
+        PresentMode::Mailbox,
+        PresentMode::Immediate,
+        PresentMode::Vsync,
+        PresentMode::AdaptiveVsync,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "mailbox" => Some(PresentMode::Mailbox),
+            "immediate" => Some(PresentMode::Immediate),
+            "vsync" | "fifo" => Some(PresentMode::Vsync),
+            "adaptive" | "fifo-relaxed" => Some(PresentMode::AdaptiveVsync),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PresentMode::Mailbox => "mailbox",
+            PresentMode::Immediate => "immediate",
+            PresentMode::Vsync => "vsync",
+            PresentMode::AdaptiveVsync => "adaptive vsync",
+        }
+    }
+
+    pub fn vk_mode(self) -> vk::PresentModeKHR {
+        match self {
+            PresentMode::Mailbox => vk::PresentModeKHR::MAILBOX,
+            PresentMode::Immediate => vk::PresentModeKHR::IMMEDIATE,
+            PresentMode::Vsync => vk::PresentModeKHR::FIFO,
+            PresentMode::AdaptiveVsync => vk::PresentModeKHR::FIFO_RELAXED,
+        }
+    }
+
+    // This mode and what to use instead, in order, when the surface lacks it
+    fn preferences(self) -> &'static [PresentMode] {
+        match self {
+            PresentMode::Mailbox => &[
+                PresentMode::Mailbox,
+                PresentMode::Immediate,
+                PresentMode::Vsync,
+            ],
+            PresentMode::Immediate => &[
+                PresentMode::Immediate,
+                PresentMode::Mailbox,
+                PresentMode::Vsync,
+            ],
+            PresentMode::Vsync => &[PresentMode::Vsync],
+            PresentMode::AdaptiveVsync => &[PresentMode::AdaptiveVsync, PresentMode::Vsync],
+        }
+    }
+
+    // Whether the display's refresh already paces rendering
+    pub fn is_vsync(self) -> bool {
+        matches!(self, PresentMode::Vsync | PresentMode::AdaptiveVsync)
+    }
+}
+
+// What a new window's swapchain asks for. `WindowContext::new` takes it so the
+// swapchain is created once, already as asked.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SwapchainOptions {
+    // Used when the surface lists it, ahead of an HDR one
+    pub format: Option<vk::SurfaceFormatKHR>,
+    // An HDR format when the surface has one, trying `hdr_encoding`'s first
+    pub hdr: bool,
+    pub hdr_encoding: Option<OutputEncoding>,
+    pub present_mode: PresentMode,
+    // Minimum image count, within the surface's limits; None for one more than
+    // the surface's minimum
+    pub image_count: Option<u32>,
+}
+
+// What the swapchain presents to: a window the app created with winit, or one
+// another application owns and passed in as raw handles
+enum Host {
+    Winit(Window),
+    External {
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+        // Made up from the window handle, so the renderer can tell windows apart
+        id: WindowId,
+    },
+}
+
+// Everything tied to one OS window: its surface, swapchain, framebuffers and the
+// semaphores ordering acquire and present. The device, pipelines and scene live
+// in the renderer, so every window shows the same simulation.
+pub struct WindowContext {
+    host: Host,
+    surface_ext: ash::khr::surface::Instance,
+    swapchain_ext: ash::khr::swapchain::Device,
+    surface: vk::SurfaceKHR,
+    swapchain: vk::SwapchainKHR,
+    surface_format: vk::SurfaceFormatKHR,
+    // Used instead of the surface's first format while the surface lists it
+    preferred_format: Option<vk::SurfaceFormatKHR>,
+    images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+    framebuffers: Vec<vk::Framebuffer>,
+    image_available_semaphore: vk::Semaphore,
+    render_finished_semaphore: vk::Semaphore,
+    // With `capabilities.swapchain_maintenance1`: one fence per swapchain image,
+    // given to each present of that image and signaled when the presentation
+    // engine is done with it. Created signaled.
+    present_fences: Vec<vk::Fence>,
+    // Swapchains replaced by `recreate_swapchain` and the present fences that
+    // have to signal before they can be destroyed
+    retired_swapchains: Vec<(vk::SwapchainKHR, Vec<vk::Fence>)>,
+    // Inner size of the window in physical pixels
+    window_extent: vk::Extent2D,
+    extent: vk::Extent2D,
+    // Asked for per-pixel alpha; the swapchain gets it where the surface allows
+    transparent: bool,
+    // Swapchain images can be copied from, for recording
+    readback: bool,
+    // Set with `capabilities.full_screen_exclusive` or
+    // `capabilities.swapchain_maintenance1`
+    surface_capabilities2_ext: Option<ash::khr::get_surface_capabilities2::Instance>,
+    full_screen_exclusive_ext: Option<ash::ext::full_screen_exclusive::Device>,
+    // Set with `capabilities.present_wait`
+    present_wait_ext: Option<ash::khr::present_wait::Device>,
+    // The swapchain is created for application-controlled exclusive fullscreen,
+    // and whether it holds it right now. It is given up while the window is in
+    // the background.
+    exclusive: bool,
+    exclusive_held: bool,
+    // The mode asked for, the one the swapchain was created with, and every
+    // mode the surface supports, in `PresentMode` order
+    present_mode: PresentMode,
+    active_present_mode: PresentMode,
+    supported_present_modes: Vec<PresentMode>,
+    // Minimum swapchain image count to ask for, within the surface's limits;
+    // None for one more than the surface's minimum
+    desired_image_count: Option<u32>,
+    // The swapchain can present in the device group's mode. Without it every
+    // frame stays on the first GPU while this window is shown.
+    group_present: bool,
+}
+
+impl WindowContext {
+    // Creates the surface, swapchain and semaphores. Framebuffers come later from
+    // `create_framebuffers`, once there is a render pass for `surface_format`.
+    // `transparent` windows must have been created with `with_transparent(true)`.
+    pub fn new(
+        gpu: &GpuContext,
+        window: Window,
+        transparent: bool,
+        options: &SwapchainOptions,
+    ) -> Self {
+        let surface = create_surface(&gpu.entry, &gpu.instance, &window);
+        let window_size = window.inner_size();
+        let window_extent = vk::Extent2D {
+            width: window_size.width,
+            height: window_size.height,
+        };
+        Self::with_host(
+            gpu,
+            Host::Winit(window),
+            surface,
+            window_extent,
+            transparent,
+            options,
+        )
+    }
+
+    // Like `new`, for a window another application owns, e.g. a widget in a host
+    // application's UI. `width` x `height` is its size in physical pixels; report
+    // changes through `Renderer::resize`, since there are no winit events for it.
+    // The handles must stay valid until the context is destroyed.
+    pub unsafe fn from_raw_handles(
+        gpu: &GpuContext,
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+        width: u32,
+        height: u32,
+        transparent: bool,
+        options: &SwapchainOptions,
+    ) -> Self {
+        let surface = create_surface_from_raw(&gpu.entry, &gpu.instance, display, window);
+        let mut hasher = DefaultHasher::new();
+        window.hash(&mut hasher);
+        let host = Host::External {
+            display,
+            window,
+            id: WindowId::from(hasher.finish()),
+        };
+        let extent = vk::Extent2D { width, height };
+        Self::with_host(gpu, host, surface, extent, transparent, options)
+    }
+
+    fn with_host(
+        gpu: &GpuContext,
+        host: Host,
+        surface: vk::SurfaceKHR,
+        window_extent: vk::Extent2D,
+        transparent: bool,
+        options: &SwapchainOptions,
+    ) -> Self {
+        println!("Vulkan surface created: {:?}", surface);
+        let mut context = Self {
+            host,
+            surface_ext: ash::khr::surface::Instance::new(&gpu.entry, &gpu.instance),
+            swapchain_ext: ash::khr::swapchain::Device::new(&gpu.instance, &gpu.device),
+            surface,
+            swapchain: vk::SwapchainKHR::null(),
+            surface_format: vk::SurfaceFormatKHR::default(),
+            preferred_format: None,
+            images: Vec::new(),
+            image_views: Vec::new(),
+            framebuffers: Vec::new(),
+            image_available_semaphore: vk::Semaphore::null(),
+            render_finished_semaphore: vk::Semaphore::null(),
+            present_fences: Vec::new(),
+            retired_swapchains: Vec::new(),
+            window_extent,
+            extent: vk::Extent2D {
+                width: 0,
+                height: 0,
+            },
+            transparent,
+            readback: false,
+            surface_capabilities2_ext: (gpu.capabilities.full_screen_exclusive
+                || gpu.capabilities.swapchain_maintenance1)
+                .then(|| {
+                    ash::khr::get_surface_capabilities2::Instance::new(&gpu.entry, &gpu.instance)
+                }),
+            full_screen_exclusive_ext: gpu
+                .capabilities
+                .full_screen_exclusive
+                .then(|| ash::ext::full_screen_exclusive::Device::new(&gpu.instance, &gpu.device)),
+            present_wait_ext: gpu
+                .capabilities
+                .present_wait
+                .then(|| ash::khr::present_wait::Device::new(&gpu.instance, &gpu.device)),
+            exclusive: false,
+            exclusive_held: false,
+            present_mode: options.present_mode,
+            active_present_mode: options.present_mode,
+            supported_present_modes: Vec::new(),
+            desired_image_count: options.image_count,
+            group_present: false,
+        };
+        context.preferred_format = options.format.or_else(|| {
+            options
+                .hdr
+                .then(|| context.find_hdr_format(gpu, options.hdr_encoding))
+                .flatten()
+        });
+        context.create_swapchain(gpu, vk::SwapchainKHR::null());
+        context.create_sync_objects(gpu);
+        context
+    }
+
+    pub fn id(&self) -> WindowId {
+        match &self.host {
+            Host::Winit(window) => window.id(),
+            Host::External { id, .. } => *id,
+        }
+    }
+
+    // The winit window. Panics for a window from `from_raw_handles`.
+    pub fn window(&self) -> &Window {
+        self.host_window()
+            .expect("External window has no winit window")
+    }
+
+    pub fn is_external(&self) -> bool {
+        matches!(self.host, Host::External { .. })
+    }
+
+    // Physical pixels per logical pixel. External windows are taken as 1; the
+    // host sets its own with `Renderer::set_scale_factor`.
+    pub fn scale_factor(&self) -> f64 {
+        match &self.host {
+            Host::Winit(window) => window.scale_factor(),
+            Host::External { .. } => 1.0,
+        }
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn window_extent(&self) -> vk::Extent2D {
+        self.window_extent
+    }
+
+    pub fn surface_format(&self) -> vk::SurfaceFormatKHR {
+        self.surface_format
+    }
+
+    pub fn swapchain(&self) -> vk::SwapchainKHR {
+        self.swapchain
+    }
+
+    pub fn image(&self, image_index: u32) -> vk::Image {
+        self.images[image_index as usize]
+    }
+
+    fn host_window(&self) -> Option<&Window> {
+        match &self.host {
+            Host::Winit(window) => Some(window),
+            Host::External { .. } => None,
+        }
+    }
+
+    // Whether `image` can be copied from: the surface allows TRANSFER_SRC and the
+    // format can be converted to RGBA8
+    pub fn supports_readback(&self) -> bool {
+        self.readback
+    }
+
+    pub fn framebuffer(&self, image_index: u32) -> vk::Framebuffer {
+        self.framebuffers[image_index as usize]
+    }
+
+    pub fn image_available_semaphore(&self) -> vk::Semaphore {
+        self.image_available_semaphore
+    }
+
+    pub fn render_finished_semaphore(&self) -> vk::Semaphore {
+        self.render_finished_semaphore
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive_held
+    }
+
+    // True between `suspend` and `resume`, when there is nothing to render to
+    pub fn is_suspended(&self) -> bool {
+        self.surface == vk::SurfaceKHR::null()
+    }
+
+    // A zero-sized window can't have a swapchain, so it isn't rendered to
+    pub fn supports_group_present(&self) -> bool {
+        self.group_present
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.window_extent.width == 0 || self.window_extent.height == 0
+    }
+
+    // Records the new inner size; the swapchain follows in `recreate_swapchain`
+    pub fn set_window_extent(&mut self, width: u32, height: u32) {
+        self.window_extent = vk::Extent2D { width, height };
+    }
+
+    // `old_swapchain` is the one being replaced, or null. Passing it lets the
+    // presentation engine hand its images over to the new one.
+    // Frames are blended with premultiplied color over a transparent clear, so that
+    // is the preferred mode for transparent windows. INHERIT leaves it to the window
+    // system, which is all some platforms offer.
+    fn composite_alpha(&self, supported: vk::CompositeAlphaFlagsKHR) -> vk::CompositeAlphaFlagsKHR {
+        use vk::CompositeAlphaFlagsKHR as Alpha;
+        let preferred = if self.transparent {
+            [
+                Alpha::PRE_MULTIPLIED,
+                Alpha::POST_MULTIPLIED,
+                Alpha::INHERIT,
+                Alpha::OPAQUE,
+            ]
+        } else {
+            [
+                Alpha::OPAQUE,
+                Alpha::INHERIT,
+                Alpha::PRE_MULTIPLIED,
+                Alpha::POST_MULTIPLIED,
+            ]
+        };
+        // Every surface supports at least one of them
+        let mode = preferred
+            .into_iter()
+            .find(|&mode| supported.contains(mode))
+            .unwrap_or(Alpha::OPAQUE);
+        if self.transparent {
+            if mode == Alpha::OPAQUE {
+                println!("Surface has no transparent composite alpha, window stays opaque");
+            } else {
+                println!("Composite alpha: {:?}", mode);
+            }
+        }
+        mode
+    }
+
+    fn create_swapchain(&mut self, gpu: &GpuContext, old_swapchain: vk::SwapchainKHR) {
+        // Swapchain creation
+        let surface_capabilities = unsafe {
+            self.surface_ext
+                .get_physical_device_surface_capabilities(gpu.physical_device, self.surface)
+                .expect("Failed to get surface capabilities")
+        };
+        let surface_formats = unsafe {
+            self.surface_ext
+                .get_physical_device_surface_formats(gpu.physical_device, self.surface)
+                .expect("Failed to get surface formats")
+        };
+        let present_modes = unsafe {
+            self.surface_ext
+                .get_physical_device_surface_present_modes(gpu.physical_device, self.surface)
+                .expect("Failed to get present modes")
+        };
+        println!("Surface formats: {:?}", surface_formats);
+        println!("Present modes: {:?}", present_modes);
+
+        self.surface_format = self
+            .preferred_format
+            .filter(|format| surface_formats.contains(format))
+            .unwrap_or(surface_formats[0]);
+        self.supported_present_modes = PresentMode::ALL
+            .into_iter()
+            .filter(|mode| present_modes.contains(&mode.vk_mode()))
+            .collect();
+        // Every surface has FIFO
+        let previous_present_mode = self.active_present_mode;
+        self.active_present_mode = self
+            .present_mode
+            .preferences()
+            .iter()
+            .copied()
+            .find(|mode| self.supported_present_modes.contains(mode))
+            .unwrap_or(PresentMode::Vsync);
+        // Said once, not on every resize
+        let changed = old_swapchain == vk::SwapchainKHR::null()
+            || self.active_present_mode != previous_present_mode;
+        if self.active_present_mode != self.present_mode && changed {
+            println!(
+                "Present mode {} not supported, using {}",
+                self.present_mode.name(),
+                self.active_present_mode.name()
+            );
+        }
+        let present_mode = self.active_present_mode.vk_mode();
+        self.extent = if surface_capabilities.current_extent.width == u32::MAX {
+            self.window_extent
+        } else {
+            surface_capabilities.current_extent
+        };
+        let composite_alpha = self.composite_alpha(surface_capabilities.supported_composite_alpha);
+        self.readback = surface_capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+            && FrameReadback::supports_format(self.surface_format.format);
+        let image_usage = if self.readback {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        };
+        // A max of zero means no limit
+        let max_image_count = match surface_capabilities.max_image_count {
+            0 => u32::MAX,
+            max => max,
+        };
+        let image_count = self
+            .desired_image_count
+            .unwrap_or(surface_capabilities.min_image_count + 1)
+            .clamp(surface_capabilities.min_image_count, max_image_count);
+        if let Some(desired) = self.desired_image_count.filter(|&count| count != image_count) {
+            let limits = match surface_capabilities.max_image_count {
+                0 => format!("at least {}", surface_capabilities.min_image_count),
+                max => format!("{} to {}", surface_capabilities.min_image_count, max),
+            };
+            println!(
+                "Surface allows {} swapchain images, asking for {} instead of {}",
+                limits, image_count, desired
+            );
+        }
+
+        let mut swapchain_create_info = vk::SwapchainCreateInfoKHR {
+            surface: self.surface,
+            min_image_count: image_count,
+            image_format: self.surface_format.format,
+            image_color_space: self.surface_format.color_space,
+            image_extent: self.extent,
+            image_array_layers: 1,
+            image_usage,
+            pre_transform: surface_capabilities.current_transform,
+            composite_alpha,
+            present_mode,
+            clipped: vk::TRUE,
+            old_swapchain,
+            ..Default::default()
+        };
+        let mut exclusive_info = vk::SurfaceFullScreenExclusiveInfoEXT::default()
+            .full_screen_exclusive(vk::FullScreenExclusiveEXT::APPLICATION_CONTROLLED);
+        let mut exclusive_monitor =
+            vk::SurfaceFullScreenExclusiveWin32InfoEXT::default().hmonitor(self.hmonitor());
+        if self.exclusive {
+            swapchain_create_info = swapchain_create_info
+                .push_next(&mut exclusive_info)
+                .push_next(&mut exclusive_monitor);
+        }
+        // Local presentation always works; the group's mode has to be asked for
+        // and only some surfaces allow it
+        let group_modes = match gpu.device_group {
+            Some(group) => {
+                let surface_modes = unsafe {
+                    self.swapchain_ext
+                        .get_device_group_surface_present_modes(self.surface)
+                        .expect("Failed to get device group present modes")
+                };
+                self.group_present = surface_modes.contains(group.mode.present_mode());
+                if !self.group_present {
+                    println!(
+                        "Surface can't present {} ({:?}), frames stay on the first GPU",
+                        group.mode.name(),
+                        surface_modes
+                    );
+                }
+                vk::DeviceGroupPresentModeFlagsKHR::LOCAL
+                    | (surface_modes & group.mode.present_mode())
+            }
+            None => vk::DeviceGroupPresentModeFlagsKHR::LOCAL,
+        };
+        let mut group_swapchain_info = vk::DeviceGroupSwapchainCreateInfoKHR::default()
+            .modes(group_modes);
+        if gpu.device_group.is_some() {
+            swapchain_create_info = swapchain_create_info.push_next(&mut group_swapchain_info);
+        }
+        let mut scaling_info = self.present_scaling(gpu, present_mode);
+        if let Some(scaling_info) = scaling_info.as_mut() {
+            swapchain_create_info = swapchain_create_info.push_next(scaling_info);
+        }
+        self.swapchain = unsafe {
+            self.swapchain_ext
+                .create_swapchain(&swapchain_create_info, None)
+                .expect("Failed to create swapchain")
+        };
+        println!("Swapchain created: {:?}", self.swapchain);
+        self.images = unsafe {
+            self.swapchain_ext
+                .get_swapchain_images(self.swapchain)
+                .expect("Failed to get swapchain images")
+        };
+        for (i, &image) in self.images.iter().enumerate() {
+            gpu.set_name(image, &format!("swapchain image {}", i));
+        }
+        println!("Swapchain images obtained: {:?}", self.images);
+        // The driver may create more than asked for
+        println!(
+            "Swapchain image count: asked for {}, got {}",
+            image_count,
+            self.images.len()
+        );
+        if gpu.capabilities.swapchain_maintenance1 {
+            let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+            self.present_fences = (0..self.images.len())
+                .map(|i| {
+                    let fence = unsafe {
+                        gpu.device
+                            .create_fence(&fence_info, None)
+                            .expect("Failed to create present fence")
+                    };
+                    gpu.set_name(fence, &format!("present fence {}", i));
+                    fence
+                })
+                .collect();
+        }
+
+        // Image views creation
+        self.image_views = self
+            .images
+            .iter()
+            .map(|&image| {
+                let create_info = vk::ImageViewCreateInfo {
+                    image,
+                    view_type: vk::ImageViewType::TYPE_2D,
+                    format: self.surface_format.format,
+                    components: vk::ComponentMapping::default(),
+                    subresource_range: vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    ..Default::default()
+                };
+                unsafe {
+                    gpu.device
+                        .create_image_view(&create_info, None)
+                        .expect("Failed to create image view")
+                }
+            })
+            .collect();
+        for (i, &view) in self.image_views.iter().enumerate() {
+            gpu.set_name(view, &format!("swapchain image view {}", i));
+        }
+        println!("Image views created: {:?}", self.image_views);
+    }
+
+    // How the presentation engine fits an image that no longer matches the
+    // window, such as the last frame while a resize is under way: stretched
+    // with its aspect ratio kept, or else at its own size, centered where the
+    // surface allows. None leaves it to the platform, which usually stretches
+    // it to fill the window.
+    fn present_scaling(
+        &self,
+        gpu: &GpuContext,
+        present_mode: vk::PresentModeKHR,
+    ) -> Option<vk::SwapchainPresentScalingCreateInfoEXT<'static>> {
+        if !gpu.capabilities.swapchain_maintenance1 {
+            return None;
+        }
+        let surface_capabilities2_ext = self.surface_capabilities2_ext.as_ref()?;
+        let mut surface_present_mode =
+            vk::SurfacePresentModeEXT::default().present_mode(present_mode);
+        let surface_info = vk::PhysicalDeviceSurfaceInfo2KHR::default()
+            .surface(self.surface)
+            .push_next(&mut surface_present_mode);
+        let mut scaling_capabilities = vk::SurfacePresentScalingCapabilitiesEXT::default();
+        let mut capabilities =
+            vk::SurfaceCapabilities2KHR::default().push_next(&mut scaling_capabilities);
+        unsafe {
+            surface_capabilities2_ext
+                .get_physical_device_surface_capabilities2(
+                    gpu.physical_device,
+                    &surface_info,
+                    &mut capabilities,
+                )
+                .ok()?;
+        }
+        use vk::PresentGravityFlagsEXT as Gravity;
+        use vk::PresentScalingFlagsEXT as Scaling;
+        let scaling = [Scaling::ASPECT_RATIO_STRETCH, Scaling::ONE_TO_ONE]
+            .into_iter()
+            .find(|&scaling| scaling_capabilities.supported_present_scaling.contains(scaling))?;
+        let gravity = |supported: Gravity| {
+            [Gravity::CENTERED, Gravity::MIN]
+                .into_iter()
+                .find(|&gravity| supported.contains(gravity))
+        };
+        // Gravity is given for both axes or neither
+        let (gravity_x, gravity_y) = match (
+            gravity(scaling_capabilities.supported_present_gravity_x),
+            gravity(scaling_capabilities.supported_present_gravity_y),
+        ) {
+            (Some(x), Some(y)) => (x, y),
+            _ => (Gravity::empty(), Gravity::empty()),
+        };
+        println!(
+            "Present scaling: {:?}, gravity {:?} x {:?}",
+            scaling, gravity_x, gravity_y
+        );
+        Some(
+            vk::SwapchainPresentScalingCreateInfoEXT::default()
+                .scaling_behavior(scaling)
+                .present_gravity_x(gravity_x)
+                .present_gravity_y(gravity_y),
+        )
+    }
+
+    // The mode `set_present_mode` asked for
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    // The mode the swapchain presents with, which differs from `present_mode`
+    // when the surface doesn't support that
+    pub fn active_present_mode(&self) -> PresentMode {
+        self.active_present_mode
+    }
+
+    pub fn supported_present_modes(&self) -> &[PresentMode] {
+        &self.supported_present_modes
+    }
+
+    // Asks for `mode`, taking effect when the swapchain is next created. Call
+    // `Renderer::set_present_mode` to switch a running window.
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        self.present_mode = mode;
+    }
+
+    // Images in the current swapchain
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    // The minimum image count the window's `SwapchainOptions` asked for
+    pub fn desired_image_count(&self) -> Option<u32> {
+        self.desired_image_count
+    }
+
+    // Whether the swapchain has an HDR format
+    pub fn is_hdr(&self) -> bool {
+        OutputEncoding::of(self.surface_format).is_hdr()
+    }
+
+    // The surface's HDR format, trying `preferred`'s formats first. None when
+    // it has none.
+    fn find_hdr_format(
+        &self,
+        gpu: &GpuContext,
+        preferred: Option<OutputEncoding>,
+    ) -> Option<vk::SurfaceFormatKHR> {
+        let surface_formats = unsafe {
+            self.surface_ext
+                .get_physical_device_surface_formats(gpu.physical_device, self.surface)
+                .expect("Failed to get surface formats")
+        };
+        let format = hdr::find_hdr_format(&surface_formats, preferred)?;
+        println!(
+            "{} swapchain: {:?}",
+            OutputEncoding::of(format).name(),
+            format
+        );
+        Some(format)
+    }
+
+    // Whether the surface can go exclusive fullscreen on the window's monitor
+    fn supports_exclusive(&self, gpu: &GpuContext) -> bool {
+        let Some(surface_capabilities2_ext) = self.surface_capabilities2_ext.as_ref() else {
+            return false;
+        };
+        if !gpu.capabilities.full_screen_exclusive {
+            return false;
+        }
+        let mut exclusive_info = vk::SurfaceFullScreenExclusiveInfoEXT::default()
+            .full_screen_exclusive(vk::FullScreenExclusiveEXT::APPLICATION_CONTROLLED);
+        let mut exclusive_monitor =
+            vk::SurfaceFullScreenExclusiveWin32InfoEXT::default().hmonitor(self.hmonitor());
+        let surface_info = vk::PhysicalDeviceSurfaceInfo2KHR::default()
+            .surface(self.surface)
+            .push_next(&mut exclusive_info)
+            .push_next(&mut exclusive_monitor);
+        let mut exclusive_capabilities = vk::SurfaceCapabilitiesFullScreenExclusiveEXT::default();
+        let mut capabilities =
+            vk::SurfaceCapabilities2KHR::default().push_next(&mut exclusive_capabilities);
+        let result = unsafe {
+            surface_capabilities2_ext.get_physical_device_surface_capabilities2(
+                gpu.physical_device,
+                &surface_info,
+                &mut capabilities,
+            )
+        };
+        result.is_ok() && exclusive_capabilities.full_screen_exclusive_supported == vk::TRUE
+    }
+
+    // The monitor exclusive fullscreen takes over, the one the window is on
+    fn hmonitor(&self) -> vk::HMONITOR {
+        #[cfg(target_os = "windows")]
+        {
+            use winit::platform::windows::MonitorHandleExtWindows;
+            if let Some(monitor) = self
+                .host_window()
+                .and_then(|window| window.current_monitor())
+            {
+                return monitor.hmonitor();
+            }
+        }
+        0
+    }
+
+    // Rebuilds the swapchain for or without exclusive fullscreen, taking it right
+    // away when switching on. The window should already cover its monitor.
+    // Returns false, changing nothing, when the surface doesn't support it.
+    // Caller ensures the device is idle.
+    pub fn set_exclusive(
+        &mut self,
+        gpu: &GpuContext,
+        render_pass: vk::RenderPass,
+        exclusive: bool,
+    ) -> bool {
+        if exclusive == self.exclusive {
+            return true;
+        }
+        if exclusive && !self.supports_exclusive(gpu) {
+            return false;
+        }
+        self.release_exclusive();
+        self.exclusive = exclusive;
+        self.recreate_swapchain(gpu, render_pass);
+        self.acquire_exclusive();
+        true
+    }
+
+    // Takes exclusive fullscreen for a swapchain created for it. Fails while
+    // another window has focus, and presenting then goes on as borderless.
+    pub fn acquire_exclusive(&mut self) {
+        let Some(full_screen_exclusive_ext) = self.full_screen_exclusive_ext.as_ref() else {
+            return;
+        };
+        if !self.exclusive || self.exclusive_held || self.is_suspended() {
+            return;
+        }
+        let result =
+            unsafe { full_screen_exclusive_ext.acquire_full_screen_exclusive_mode(self.swapchain) };
+        match result {
+            Ok(()) => {
+                self.exclusive_held = true;
+                println!("Exclusive fullscreen acquired");
+            }
+            Err(e) => println!("Exclusive fullscreen not acquired: {:?}", e),
+        }
+    }
+
+    // Hands exclusive fullscreen back, e.g. when alt-tabbing away
+    pub fn release_exclusive(&mut self) {
+        let Some(full_screen_exclusive_ext) = self.full_screen_exclusive_ext.as_ref() else {
+            return;
+        };
+        if !self.exclusive_held {
+            return;
+        }
+        self.exclusive_held = false;
+        // Fails if it was already lost, which is just as good
+        if unsafe { full_screen_exclusive_ext.release_full_screen_exclusive_mode(self.swapchain) }
+            .is_ok()
+        {
+            println!("Exclusive fullscreen released");
+        }
+    }
+
+    pub fn create_framebuffers(&mut self, gpu: &GpuContext, render_pass: vk::RenderPass) {
+        // Framebuffers creation
+        self.framebuffers = self
+            .image_views
+            .iter()
+            .map(|&image_view| {
+                let framebuffer_create_info = vk::FramebufferCreateInfo {
+                    render_pass,
+                    attachment_count: 1,
+                    p_attachments: &image_view,
+                    width: self.extent.width,
+                    height: self.extent.height,
+                    layers: 1,
+                    ..Default::default()
+                };
+                unsafe {
+                    gpu.device
+                        .create_framebuffer(&framebuffer_create_info, None)
+                        .expect("Failed to create framebuffer")
+                }
+            })
+            .collect();
+        for (i, &framebuffer) in self.framebuffers.iter().enumerate() {
+            gpu.set_name(framebuffer, &format!("swapchain framebuffer {}", i));
+        }
+        println!("Framebuffers created: {:?}", self.framebuffers);
+    }
+
+    fn create_sync_objects(&mut self, gpu: &GpuContext) {
+        // Semaphore creation
+        self.image_available_semaphore = unsafe {
+            gpu.device
+                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                .expect("Failed to create image available semaphore")
+        };
+        gpu.set_name(self.image_available_semaphore, "image available semaphore");
+        println!(
+            "Image available semaphore created: {:?}",
+            self.image_available_semaphore
+        );
+        self.render_finished_semaphore = unsafe {
+            gpu.device
+                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                .expect("Failed to create render finished semaphore")
+        };
+        gpu.set_name(self.render_finished_semaphore, "render finished semaphore");
+        println!(
+            "Render finished semaphore created: {:?}",
+            self.render_finished_semaphore
+        );
+    }
+
+    // Acquires the next swapchain image, signaling the image available semaphore.
+    // On a device group the image is acquired for the GPUs rendering `group`.
+    // None when the swapchain is out of date and has to be recreated.
+    pub fn acquire_image(&mut self, group: Option<&GroupFrame>) -> Option<u32> {
+        let result = unsafe {
+            match group {
+                Some(group) => self.swapchain_ext.acquire_next_image2(
+                    &vk::AcquireNextImageInfoKHR::default()
+                        .swapchain(self.swapchain)
+                        .timeout(u64::MAX)
+                        .semaphore(self.image_available_semaphore)
+                        .device_mask(group.device_mask),
+                ),
+                None => self.swapchain_ext.acquire_next_image(
+                    self.swapchain,
+                    u64::MAX,
+                    self.image_available_semaphore,
+                    vk::Fence::null(),
+                ),
+            }
+        };
+        match result {
+            Ok((index, _)) => Some(index),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => None,
+            // Another window took the display; `acquire_exclusive` takes it back
+            // once this one has focus again
+            Err(vk::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT) => {
+                println!("Exclusive fullscreen lost");
+                self.exclusive_held = false;
+                None
+            }
+            Err(e) => panic!("Failed to acquire next image: {:?}", e),
+        }
+    }
+
+    // Presents one image from each of `windows` in a single call, waiting on their
+    // render finished semaphores. On a device group the instances of the GPUs
+    // that rendered `group` are presented in its mode. Returns the positions in
+    // `windows` of the swapchains that turned out to be out of date or lost
+    // exclusive fullscreen. With `capabilities.present_wait` every image is
+    // tagged with `present_id`, which must be higher than the last one given to
+    // the same swapchain. With `capabilities.swapchain_maintenance1` each image's
+    // present fence is waited on, reset and signaled again by this present.
+    pub fn present_all(
+        gpu: &GpuContext,
+        windows: &[(&WindowContext, u32)],
+        group: Option<&GroupFrame>,
+        present_id: u64,
+    ) -> Vec<usize> {
+        let Some(&(first, _)) = windows.first() else {
+            return Vec::new();
+        };
+        let wait_semaphores: Vec<_> = windows
+            .iter()
+            .map(|(window, _)| window.render_finished_semaphore)
+            .collect();
+        let swapchains: Vec<_> = windows.iter().map(|(window, _)| window.swapchain).collect();
+        let image_indices: Vec<_> = windows.iter().map(|&(_, index)| index).collect();
+        let mut results = vec![vk::Result::SUCCESS; windows.len()];
+        let mut present_info = vk::PresentInfoKHR {
+            wait_semaphore_count: wait_semaphores.len() as u32,
+            p_wait_semaphores: wait_semaphores.as_ptr(),
+            swapchain_count: swapchains.len() as u32,
+            p_swapchains: swapchains.as_ptr(),
+            p_image_indices: image_indices.as_ptr(),
+            p_results: results.as_mut_ptr(),
+            ..Default::default()
+        };
+        let device_masks = vec![group.map_or(0, |group| group.device_mask); windows.len()];
+        let mut group_present_info = vk::DeviceGroupPresentInfoKHR::default()
+            .device_masks(&device_masks)
+            .mode(group.map_or(vk::DeviceGroupPresentModeFlagsKHR::LOCAL, |group| {
+                group.present_mode
+            }));
+        if group.is_some() {
+            present_info = present_info.push_next(&mut group_present_info);
+        }
+        let present_fences: Vec<_> = if gpu.capabilities.swapchain_maintenance1 {
+            windows
+                .iter()
+                .map(|&(window, index)| window.present_fences[index as usize])
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut present_fence_info =
+            vk::SwapchainPresentFenceInfoEXT::default().fences(&present_fences);
+        if gpu.capabilities.swapchain_maintenance1 {
+            // The image was acquired again, so its last present is done or
+            // about to be
+            let result = unsafe { gpu.device.wait_for_fences(&present_fences, true, u64::MAX) };
+            gpu.expect(result, "Failed to wait for present fences");
+            unsafe {
+                gpu.device
+                    .reset_fences(&present_fences)
+                    .expect("Failed to reset present fences");
+            }
+            present_info = present_info.push_next(&mut present_fence_info);
+        }
+        let present_ids = vec![present_id; windows.len()];
+        let mut present_id_info = vk::PresentIdKHR::default().present_ids(&present_ids);
+        if gpu.capabilities.present_wait {
+            present_info = present_info.push_next(&mut present_id_info);
+        }
+        let present_result = unsafe { first.swapchain_ext.queue_present(gpu.queue, &present_info) };
+        let needs_recreate = |result: vk::Result| {
+            result == vk::Result::ERROR_OUT_OF_DATE_KHR
+                || result == vk::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT
+        };
+        match present_result {
+            Ok(_) => (),
+            Err(e) if needs_recreate(e) => (),
+            Err(vk::Result::ERROR_DEVICE_LOST) => gpu.device_lost("Failed to present queue"),
+            Err(e) => panic!("Failed to present queue: {:?}", e),
+        }
+        results
+            .iter()
+            .enumerate()
+            .filter(|(_, &result)| needs_recreate(result))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Blocks until the image presented with `present_id` has reached the
+    // display, or `timeout` runs out. True once it has; false on timeout, for a
+    // swapchain that never got that ID, or without `capabilities.present_wait`.
+    pub fn wait_for_present(&self, gpu: &GpuContext, present_id: u64, timeout: Duration) -> bool {
+        let Some(present_wait_ext) = self.present_wait_ext.as_ref() else {
+            return false;
+        };
+        if self.is_suspended() {
+            return false;
+        }
+        let result = unsafe {
+            present_wait_ext.wait_for_present(
+                self.swapchain,
+                present_id,
+                u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX),
+            )
+        };
+        match result {
+            Ok(()) => true,
+            // Nothing more will be shown on an out of date swapchain; it is
+            // rebuilt on the next acquire or present
+            Err(vk::Result::TIMEOUT)
+            | Err(vk::Result::ERROR_OUT_OF_DATE_KHR)
+            | Err(vk::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT) => false,
+            Err(vk::Result::ERROR_DEVICE_LOST) => gpu.device_lost("Failed to wait for present"),
+            Err(e) => panic!("Failed to wait for present: {:?}", e),
+        }
+    }
+
+    // Rebuilds the swapchain at the current window size, created from the old
+    // one so a resize doesn't drop back to a blank surface. Exclusive fullscreen
+    // moves over to the new swapchain. Caller ensures the device is idle or,
+    // with `capabilities.swapchain_maintenance1`, that no submitted commands
+    // still use the framebuffers; the old swapchain is then kept until its
+    // present fences signal.
+    pub fn recreate_swapchain(&mut self, gpu: &GpuContext, render_pass: vk::RenderPass) {
+        let exclusive_held = self.exclusive_held;
+        self.release_exclusive();
+        self.replace_swapchain(gpu);
+        self.create_framebuffers(gpu, render_pass);
+        if exclusive_held {
+            self.acquire_exclusive();
+        }
+    }
+
+    // Destroys the swapchain and the surface, keeping the semaphores. On Android
+    // the native window goes away while suspended, so the surface can't outlive
+    // it. Caller ensures the device is idle.
+    pub fn suspend(&mut self, gpu: &GpuContext) {
+        if self.is_suspended() {
+            return;
+        }
+        unsafe {
+            self.destroy_swapchain(gpu);
+            self.surface_ext.destroy_surface(self.surface, None);
+        }
+        self.surface = vk::SurfaceKHR::null();
+        println!("Surface destroyed");
+    }
+
+    // Used instead of the surface's first format, when the surface lists it,
+    // from the next swapchain on
+    pub fn set_preferred_format(&mut self, format: Option<vk::SurfaceFormatKHR>) {
+        self.preferred_format = format;
+    }
+
+    // Creates a new surface for the window and a swapchain on it. Framebuffers
+    // come after from `create_framebuffers`, since the surface may have come
+    // back with another format than before.
+    pub fn resume(&mut self, gpu: &GpuContext) {
+        if !self.is_suspended() {
+            return;
+        }
+        // An external window keeps the size it was last given
+        self.surface = match &self.host {
+            Host::Winit(window) => {
+                let window_size = window.inner_size();
+                self.window_extent = vk::Extent2D {
+                    width: window_size.width,
+                    height: window_size.height,
+                };
+                create_surface(&gpu.entry, &gpu.instance, window)
+            }
+            &Host::External {
+                display, window, ..
+            } => unsafe { create_surface_from_raw(&gpu.entry, &gpu.instance, display, window) },
+        };
+        println!("Vulkan surface created: {:?}", self.surface);
+        self.create_swapchain(gpu, vk::SwapchainKHR::null());
+    }
+
+    // Frees everything the window owns. Caller ensures the device is idle.
+    pub fn destroy(self, gpu: &GpuContext) {
+        self.into_window(gpu);
+    }
+
+    // Like `destroy`, but hands the winit window back so a context can be
+    // created for it again, e.g. on another device. None for an external window.
+    pub fn into_window(mut self, gpu: &GpuContext) -> Option<Window> {
+        self.suspend(gpu);
+        unsafe {
+            gpu.device
+                .destroy_semaphore(self.image_available_semaphore, None);
+            gpu.device
+                .destroy_semaphore(self.render_finished_semaphore, None);
+        }
+        println!("Window context destroyed");
+        match self.host {
+            Host::Winit(window) => Some(window),
+            Host::External { .. } => None,
+        }
+    }
+
+    // Creates a new swapchain from the current one, which is retired along with
+    // its image views and framebuffers
+    fn replace_swapchain(&mut self, gpu: &GpuContext) {
+        let old_swapchain = self.swapchain;
+        unsafe {
+            self.destroy_image_views(gpu);
+        }
+        self.retired_swapchains
+            .push((old_swapchain, std::mem::take(&mut self.present_fences)));
+        self.create_swapchain(gpu, old_swapchain);
+        self.destroy_retired_swapchains(gpu, false);
+    }
+
+    // Destroys the swapchains `recreate_swapchain` replaced once their present
+    // fences have signaled, waiting for them with `wait`. Without
+    // `capabilities.swapchain_maintenance1` there are no fences to wait for.
+    pub fn destroy_retired_swapchains(&mut self, gpu: &GpuContext, wait: bool) {
+        self.retired_swapchains.retain(|(swapchain, fences)| {
+            let done = if wait && !fences.is_empty() {
+                let result = unsafe { gpu.device.wait_for_fences(fences, true, u64::MAX) };
+                gpu.expect(result, "Failed to wait for present fences");
+                true
+            } else {
+                fences
+                    .iter()
+                    .all(|&fence| unsafe { gpu.device.get_fence_status(fence) } == Ok(true))
+            };
+            if done {
+                unsafe {
+                    for &fence in fences {
+                        gpu.device.destroy_fence(fence, None);
+                    }
+                    self.swapchain_ext.destroy_swapchain(*swapchain, None);
+                }
+            }
+            !done
+        });
+    }
+
+    unsafe fn destroy_swapchain(&mut self, gpu: &GpuContext) {
+        self.release_exclusive();
+        self.destroy_image_views(gpu);
+        self.retired_swapchains
+            .push((self.swapchain, std::mem::take(&mut self.present_fences)));
+        self.destroy_retired_swapchains(gpu, true);
+        self.swapchain = vk::SwapchainKHR::null();
+    }
+
+    // Framebuffers and views of the swapchain images, but not the swapchain
+    unsafe fn destroy_image_views(&mut self, gpu: &GpuContext) {
+        for &framebuffer in &self.framebuffers {
+            gpu.device.destroy_framebuffer(framebuffer, None);
+        }
+        for &image_view in &self.image_views {
+            gpu.device.destroy_image_view(image_view, None);
+        }
+        self.framebuffers.clear();
+        self.image_views.clear();
+    }
+}
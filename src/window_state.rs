@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::window::{Fullscreen, WindowAttributes};
+
+// Kept in the platform config directory, e.g. ~/.config on Linux,
+// %APPDATA% on Windows and ~/Library/Application Support on macOS
+const CONFIG_DIR: &str = "vulkan_vibe";
+const FILE_NAME: &str = "window.cfg";
+
+// The primary window's geometry, saved on exit and restored at startup. Position
+// and size are of the window outside fullscreen, so leaving fullscreen after a
+// restore goes back to them. Unknown values are left to the window system.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindowState {
+    pub position: Option<PhysicalPosition<i32>>,
+    pub size: Option<PhysicalSize<u32>>,
+    pub fullscreen: bool,
+}
+
+impl WindowState {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(FILE_NAME))
+    }
+
+    // The saved state, or None when there is none or it can't be read
+    pub fn load() -> Option<Self> {
+        let path = Self::path()?;
+        let text = std::fs::read_to_string(&path).ok()?;
+        let state = Self::parse(&text);
+        if state.is_none() {
+            eprintln!("Ignoring unreadable window state in {}", path.display());
+        }
+        state
+    }
+
+    // One `key = value` per line; unknown keys are skipped
+    fn parse(text: &str) -> Option<Self> {
+        let mut state = Self::default();
+        let (mut x, mut y, mut width, mut height) = (None, None, None, None);
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "x" => x = Some(value.parse().ok()?),
+                "y" => y = Some(value.parse().ok()?),
+                "width" => width = Some(value.parse().ok()?),
+                "height" => height = Some(value.parse().ok()?),
+                "fullscreen" => state.fullscreen = value.parse().ok()?,
+                _ => {}
+            }
+        }
+        if let (Some(x), Some(y)) = (x, y) {
+            state.position = Some(PhysicalPosition::new(x, y));
+        }
+        if let (Some(width), Some(height)) = (width, height) {
+            if width > 0 && height > 0 {
+                state.size = Some(PhysicalSize::new(width, height));
+            }
+        }
+        Some(state)
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            eprintln!("No config directory, window state not saved");
+            return;
+        };
+        let mut text = String::new();
+        if let Some(position) = self.position {
+            text += &format!("x = {}\ny = {}\n", position.x, position.y);
+        }
+        if let Some(size) = self.size {
+            text += &format!("width = {}\nheight = {}\n", size.width, size.height);
+        }
+        text += &format!("fullscreen = {}\n", self.fullscreen);
+        let result = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, text));
+        match result {
+            Ok(()) => println!("Window state saved to {}", path.display()),
+            Err(e) => eprintln!("Failed to save window state: {}", e),
+        }
+    }
+
+    // Deletes the saved state, so the next start uses the defaults
+    pub fn reset() {
+        if let Some(path) = Self::path() {
+            if std::fs::remove_file(&path).is_ok() {
+                println!("Removed saved window state {}", path.display());
+            }
+        }
+    }
+
+    pub fn apply(&self, mut attributes: WindowAttributes) -> WindowAttributes {
+        if let Some(position) = self.position {
+            attributes = attributes.with_position(position);
+        }
+        if let Some(size) = self.size {
+            attributes = attributes.with_inner_size(size);
+        }
+        if self.fullscreen {
+            attributes = attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        attributes
+    }
+}